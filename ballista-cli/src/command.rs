@@ -42,6 +42,8 @@ pub enum Command {
     SearchFunctions(String),
     QuietMode(Option<bool>),
     OutputFormat(Option<String>),
+    ClusterConfig,
+    DecommissionExecutor(String, u64),
 }
 
 pub enum OutputFormat {
@@ -104,6 +106,48 @@ impl Command {
                     Err(BallistaError::NotImplemented(msg))
                 }
             }
+            Self::ClusterConfig => {
+                let config = ctx.cluster_config().await?;
+                let schema = Arc::new(Schema::new(vec![
+                    Field::new("Setting", DataType::Utf8, false),
+                    Field::new("Value", DataType::Utf8, false),
+                ]));
+                let settings = StringArray::from(vec![
+                    "default shuffle partitions",
+                    "default batch size",
+                    "file formats",
+                    "table factories",
+                    "REST API",
+                ]);
+                let values = StringArray::from(vec![
+                    config.default_shuffle_partitions.to_string(),
+                    config.default_batch_size.to_string(),
+                    config.file_formats.join(", "),
+                    config.table_factories.join(", "),
+                    if config.rest_api {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                    .to_owned(),
+                ]);
+                let batch = RecordBatch::try_new(
+                    schema,
+                    vec![Arc::new(settings) as ArrayRef, Arc::new(values)],
+                )?;
+                print_options
+                    .print_batches(&[batch], now)
+                    .map_err(BallistaError::DataFusionError)
+            }
+            Self::DecommissionExecutor(executor_id, timeout_seconds) => {
+                let active_jobs = ctx
+                    .decommission_executor(executor_id, *timeout_seconds)
+                    .await?;
+                println!(
+                    "Decommissioning executor {executor_id}, used by {active_jobs} active jobs"
+                );
+                Ok(())
+            }
             Self::OutputFormat(_) => Err(BallistaError::Internal(
                 "Unexpected change output format, this should be handled outside"
                     .to_string(),
@@ -123,11 +167,16 @@ impl Command {
             Self::OutputFormat(_) => {
                 ("\\pset [NAME [VALUE]]", "set table output option\n(format)")
             }
+            Self::ClusterConfig => ("\\cluster", "show cluster configuration"),
+            Self::DecommissionExecutor(_, _) => (
+                "\\decommission executor_id [timeout_seconds]",
+                "stop and remove an executor once the jobs using it completed",
+            ),
         }
     }
 }
 
-const ALL_COMMANDS: [Command; 8] = [
+const ALL_COMMANDS: [Command; 10] = [
     Command::ListTables,
     Command::DescribeTable(String::new()),
     Command::Quit,
@@ -136,6 +185,8 @@ const ALL_COMMANDS: [Command; 8] = [
     Command::SearchFunctions(String::new()),
     Command::QuietMode(None),
     Command::OutputFormat(None),
+    Command::ClusterConfig,
+    Command::DecommissionExecutor(String::new(), 0),
 ];
 
 fn all_commands_info() -> RecordBatch {
@@ -184,6 +235,14 @@ impl FromStr for Command {
                 Self::OutputFormat(Some(subcommand.to_string()))
             }
             ("pset", None) => Self::OutputFormat(None),
+            ("cluster", None) => Self::ClusterConfig,
+            ("decommission", Some(args)) => match args.split_once(' ') {
+                Some((executor_id, timeout_seconds)) => Self::DecommissionExecutor(
+                    executor_id.to_owned(),
+                    timeout_seconds.trim().parse().map_err(|_| ())?,
+                ),
+                None => Self::DecommissionExecutor(args.to_owned(), 0),
+            },
             _ => return Err(()),
         })
     }
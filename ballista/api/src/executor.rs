@@ -0,0 +1,213 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Builder of an executor embedded in another binary
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ballista_core::config::TaskSchedulingPolicy;
+use ballista_core::serde::BallistaCodec;
+use ballista_executor::execution_engine::ExecutionEngine;
+use ballista_executor::executor::TaskContextExtension;
+use ballista_executor::executor_process::{
+    start_executor_process, ExecutorProcessConfig,
+};
+use ballista_executor::plugin::ExecutorPlugin;
+use datafusion::datasource::provider::TableProviderFactory;
+
+/// Builder of an executor registering with a scheduler and serving the shuffle data of its
+/// tasks, like the executor binary. Its settings are the ones of the [ExecutorProcessConfig]
+/// of the binary, its hooks are set with the `with_*` methods.
+pub struct ExecutorBuilder {
+    config: ExecutorProcessConfig,
+}
+
+impl Default for ExecutorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExecutorBuilder {
+    /// An executor with the defaults of the executor binary, registering with the scheduler
+    /// at `localhost:50050`
+    pub fn new() -> Self {
+        Self {
+            config: ExecutorProcessConfig::default(),
+        }
+    }
+
+    /// Replace the settings of the executor, including the codec, plugins and execution
+    /// engine set before
+    pub fn with_config(mut self, config: ExecutorProcessConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Register with the scheduler at `host:port`
+    pub fn with_scheduler(mut self, host: impl Into<String>, port: u16) -> Self {
+        self.config.scheduler_host = host.into();
+        self.config.scheduler_port = port;
+        self
+    }
+
+    /// Listen on `host`, serving the shuffle data of the tasks on `port` and the gRPC
+    /// service receiving tasks from the scheduler on `grpc_port`
+    pub fn with_bind_address(
+        mut self,
+        host: impl Into<String>,
+        port: u16,
+        grpc_port: u16,
+    ) -> Self {
+        self.config.bind_host = host.into();
+        self.config.port = port;
+        self.config.grpc_port = grpc_port;
+        self
+    }
+
+    /// Advertise `host` to the scheduler and the other executors instead of the address
+    /// the scheduler sees the executor connect from
+    pub fn with_external_host(mut self, host: impl Into<String>) -> Self {
+        self.config.external_host = Some(host.into());
+        self
+    }
+
+    /// Run up to `concurrent_tasks` tasks at once, 0 for one per CPU core
+    pub fn with_concurrent_tasks(mut self, concurrent_tasks: usize) -> Self {
+        self.config.concurrent_tasks = concurrent_tasks;
+        self
+    }
+
+    /// Write the shuffle files to `work_dir`, a comma separated list of directories such as
+    /// one per local disk
+    pub fn with_work_dir(mut self, work_dir: impl Into<String>) -> Self {
+        self.config.work_dir = Some(work_dir.into());
+        self
+    }
+
+    /// Join the pool of executors `pool`, whose jobs select it
+    pub fn with_pool(mut self, pool: impl Into<String>) -> Self {
+        self.config.pool = pool.into();
+        self
+    }
+
+    pub fn with_scheduling_policy(mut self, policy: TaskSchedulingPolicy) -> Self {
+        self.config.task_scheduling_policy = policy;
+        self
+    }
+
+    /// Decode the plans of the tasks with `codec`, whose extension codecs decode the custom
+    /// plan nodes and tables the scheduler encoded
+    pub fn with_codec(mut self, codec: BallistaCodec) -> Self {
+        self.config.codec = codec;
+        self
+    }
+
+    /// Run the plans of the tasks with `engine` instead of DataFusion
+    pub fn with_execution_engine(mut self, engine: Arc<dyn ExecutionEngine>) -> Self {
+        self.config.execution_engine = Some(engine);
+        self
+    }
+
+    /// Register a plugin contributing object stores, functions, table factories and task
+    /// context extensions
+    pub fn with_plugin(mut self, plugin: Arc<dyn ExecutorPlugin>) -> Self {
+        self.config.plugins.push(plugin);
+        self
+    }
+
+    /// Create the tables of file type `file_type` in the plans of the tasks with `factory`
+    pub fn with_table_factory(
+        self,
+        file_type: impl Into<String>,
+        factory: Arc<dyn TableProviderFactory>,
+    ) -> Self {
+        self.with_plugin(Arc::new(TableFactory {
+            file_type: file_type.into(),
+            factory,
+        }))
+    }
+
+    pub fn with_task_context_extension(
+        mut self,
+        extension: Arc<dyn TaskContextExtension>,
+    ) -> Self {
+        self.config.task_context_extensions.push(extension);
+        self
+    }
+
+    pub fn config(&self) -> &ExecutorProcessConfig {
+        &self.config
+    }
+
+    /// Run the executor until it is asked to shut down or its scheduler stops it
+    pub async fn serve(self) -> anyhow::Result<()> {
+        start_executor_process(Arc::new(self.config)).await
+    }
+}
+
+/// Plugin registering a table factory with the executor
+struct TableFactory {
+    file_type: String,
+    factory: Arc<dyn TableProviderFactory>,
+}
+
+impl ExecutorPlugin for TableFactory {
+    fn name(&self) -> &str {
+        &self.file_type
+    }
+
+    fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+        HashMap::from([(self.file_type.clone(), self.factory.clone())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::datasource::listing_table_factory::ListingTableFactory;
+
+    #[test]
+    fn test_executor_builder() {
+        let builder = ExecutorBuilder::new()
+            .with_scheduler("scheduler", 50060)
+            .with_bind_address("127.0.0.1", 50061, 50062)
+            .with_concurrent_tasks(4)
+            .with_pool("gpu")
+            .with_table_factory("deltatable", Arc::new(ListingTableFactory::new()));
+        let config = builder.config();
+        assert_eq!(config.scheduler_host, "scheduler");
+        assert_eq!(config.scheduler_port, 50060);
+        assert_eq!((config.port, config.grpc_port), (50061, 50062));
+        assert_eq!(config.concurrent_tasks, 4);
+        assert_eq!(config.pool, "gpu");
+        // The other settings keep the defaults of the executor binary
+        assert!(matches!(
+            config.task_scheduling_policy,
+            TaskSchedulingPolicy::PullStaged
+        ));
+        assert_eq!(config.job_data_ttl_seconds, 604800);
+
+        let file_types: Vec<String> = config
+            .plugins
+            .iter()
+            .flat_map(|plugin| plugin.table_factories().into_keys())
+            .collect();
+        assert_eq!(file_types, vec!["deltatable"]);
+    }
+}
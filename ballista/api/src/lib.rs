@@ -0,0 +1,34 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+#![doc = include_str!("../README.md")]
+
+pub mod executor;
+pub mod scheduler;
+
+pub use executor::ExecutorBuilder;
+pub use scheduler::SchedulerBuilder;
+
+// The types of the hooks, so that embedding binaries need not depend on the other crates
+pub use ballista_core::serde::BallistaCodec;
+pub use ballista_executor::execution_engine::ExecutionEngine;
+pub use ballista_executor::executor::TaskContextExtension;
+pub use ballista_executor::executor_process::ExecutorProcessConfig;
+pub use ballista_executor::plugin::ExecutorPlugin;
+pub use ballista_scheduler::cluster::BallistaCluster;
+pub use ballista_scheduler::config::SchedulerConfig;
+pub use ballista_scheduler::plugin::SchedulerPlugin;
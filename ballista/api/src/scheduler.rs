@@ -0,0 +1,239 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Builder of a scheduler embedded in another binary
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use ballista_core::error::Result;
+use ballista_core::serde::BallistaCodec;
+use ballista_scheduler::cluster::BallistaCluster;
+use ballista_scheduler::config::SchedulerConfig;
+use ballista_scheduler::plugin::SchedulerPlugin;
+use ballista_scheduler::scheduler_process::start_server;
+use ballista_scheduler::scheduler_server::SessionBuilder;
+use datafusion::datasource::provider::TableProviderFactory;
+use datafusion::optimizer::OptimizerRule;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use tonic::metadata::MetadataMap;
+
+/// Builder of a scheduler serving the gRPC, Flight SQL and REST services of the scheduler
+/// binary on a single address. Its settings are the ones of the [SchedulerConfig] of the
+/// binary, its hooks are set with the `with_*` methods.
+pub struct SchedulerBuilder {
+    addr: SocketAddr,
+    config: SchedulerConfig,
+    cluster: Option<BallistaCluster>,
+}
+
+impl Default for SchedulerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SchedulerBuilder {
+    /// A scheduler listening on `0.0.0.0:50050` with the defaults of the scheduler binary
+    pub fn new() -> Self {
+        Self {
+            addr: SocketAddr::from(([0, 0, 0, 0], 50050)),
+            config: SchedulerConfig::default(),
+            cluster: None,
+        }
+    }
+
+    /// Replace the settings of the scheduler, including the codec, plugins and optimizer
+    /// rules set before
+    pub fn with_config(mut self, config: SchedulerConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Listen on `addr`, whose port the scheduler advertises to executors and clients
+    pub fn with_bind_address(mut self, addr: SocketAddr) -> Self {
+        self.addr = addr;
+        self.config.bind_port = addr.port();
+        self
+    }
+
+    /// Keep the state of the cluster and its jobs in `cluster`, e.g. a
+    /// [BallistaCluster::new_kv] over a custom key value store, instead of the state backend
+    /// of the settings
+    pub fn with_cluster(mut self, cluster: BallistaCluster) -> Self {
+        self.cluster = Some(cluster);
+        self
+    }
+
+    /// Encode the plans of jobs with `codec`, whose extension codecs encode the custom plan
+    /// nodes and tables of the plans. Executors need a codec decoding them.
+    pub fn with_codec(mut self, codec: BallistaCodec) -> Self {
+        self.config = self.config.with_codec(codec);
+        self
+    }
+
+    /// Register a plugin contributing catalogs, table factories, authentication,
+    /// authorization and optimizer rules
+    pub fn with_plugin(mut self, plugin: Arc<dyn SchedulerPlugin>) -> Self {
+        self.config = self.config.with_plugin(plugin);
+        self
+    }
+
+    /// Authenticate the clients with `authenticate`, which returns the identity of a client
+    /// from the metadata of its requests, None to leave it to the authenticators registered
+    /// after it, or an error to reject its queries
+    pub fn with_authenticator<F>(self, name: impl Into<String>, authenticate: F) -> Self
+    where
+        F: Fn(&MetadataMap) -> Result<Option<String>> + Send + Sync + 'static,
+    {
+        self.with_plugin(Arc::new(Authenticator {
+            name: name.into(),
+            authenticate,
+        }))
+    }
+
+    /// Build the DataFusion state of every session with `session_builder`, e.g. to register
+    /// functions with the sessions
+    pub fn with_session_builder(mut self, session_builder: SessionBuilder) -> Self {
+        self.config = self.config.with_session_builder(session_builder);
+        self
+    }
+
+    /// Create the tables `STORED AS` the file type `file_type` in every session with
+    /// `factory`. Executors need a codec or a table factory of their own to scan them.
+    pub fn with_table_factory(
+        self,
+        file_type: impl Into<String>,
+        factory: Arc<dyn TableProviderFactory>,
+    ) -> Self {
+        self.with_plugin(Arc::new(TableFactory {
+            file_type: file_type.into(),
+            factory,
+        }))
+    }
+
+    /// Apply a logical optimizer rule to every job after the rules of its session
+    pub fn with_optimizer_rule(
+        mut self,
+        rule: Arc<dyn OptimizerRule + Send + Sync>,
+    ) -> Self {
+        self.config = self.config.with_optimizer_rule(rule);
+        self
+    }
+
+    /// Apply a physical optimizer rule to every job after the rules of its session
+    pub fn with_physical_optimizer_rule(
+        mut self,
+        rule: Arc<dyn PhysicalOptimizerRule + Send + Sync>,
+    ) -> Self {
+        self.config = self.config.with_physical_optimizer_rule(rule);
+        self
+    }
+
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// Serve the scheduler until it is drained. The state backend of the settings is
+    /// connected to unless a cluster was given.
+    pub async fn serve(self) -> anyhow::Result<()> {
+        let cluster = match self.cluster {
+            Some(cluster) => cluster,
+            None => BallistaCluster::new_from_config(&self.config).await?,
+        };
+        start_server(cluster, self.addr, self.config).await
+    }
+}
+
+/// Plugin authenticating the clients of the scheduler with a function
+struct Authenticator<F> {
+    name: String,
+    authenticate: F,
+}
+
+impl<F> SchedulerPlugin for Authenticator<F>
+where
+    F: Fn(&MetadataMap) -> Result<Option<String>> + Send + Sync,
+{
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn authenticate(&self, metadata: &MetadataMap) -> Result<Option<String>> {
+        (self.authenticate)(metadata)
+    }
+}
+
+/// Plugin registering a table factory with the sessions of the scheduler
+struct TableFactory {
+    file_type: String,
+    factory: Arc<dyn TableProviderFactory>,
+}
+
+impl SchedulerPlugin for TableFactory {
+    fn name(&self) -> &str {
+        &self.file_type
+    }
+
+    fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+        HashMap::from([(self.file_type.clone(), self.factory.clone())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::utils::default_session_builder;
+    use datafusion::datasource::listing_table_factory::ListingTableFactory;
+    use datafusion::execution::context::SessionState;
+    use datafusion::prelude::SessionConfig;
+
+    fn information_schema_session(config: SessionConfig) -> SessionState {
+        default_session_builder(config.with_information_schema(true))
+    }
+
+    #[test]
+    fn test_scheduler_builder() -> Result<()> {
+        let builder = SchedulerBuilder::new()
+            .with_config(SchedulerConfig::default().with_namespace("embedded"))
+            .with_bind_address("127.0.0.1:50060".parse().unwrap())
+            .with_authenticator("token", |metadata| {
+                Ok(metadata.get("authorization").map(|_| "alice".to_owned()))
+            })
+            .with_session_builder(information_schema_session)
+            .with_table_factory("deltatable", Arc::new(ListingTableFactory::new()));
+        let config = builder.config();
+        assert_eq!(config.namespace, "embedded");
+        assert_eq!(config.scheduler_name(), "localhost:50060");
+
+        assert_eq!(config.plugins.authenticate(&MetadataMap::new())?, None);
+        let mut metadata = MetadataMap::new();
+        metadata.insert("authorization", "Bearer token".parse().unwrap());
+        assert_eq!(
+            config.plugins.authenticate(&metadata)?,
+            Some("alice".to_owned())
+        );
+
+        let session = (config.session_builder)(SessionConfig::new());
+        assert!(session.config().information_schema());
+        let file_types: Vec<String> =
+            config.plugins.table_factories().into_keys().collect();
+        assert_eq!(file_types, vec!["deltatable"]);
+        Ok(())
+    }
+}
@@ -19,19 +19,33 @@
 
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::execution::context::DataFilePaths;
-use log::info;
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use log::{info, warn};
 use parking_lot::Mutex;
 use sqlparser::ast::Statement;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::Token;
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use tonic::Code;
 
 use ballista_core::config::BallistaConfig;
+use ballista_core::error::BallistaError;
 use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
-use ballista_core::serde::protobuf::{ExecuteQueryParams, KeyValuePair};
+use ballista_core::serde::protobuf::{
+    CancelJobGroupParams, CloseSessionParams, ClusterConfig, DecommissionExecutorParams,
+    DropTableParams, ExecuteQueryParams, GetClusterConfigParams, GetJobGroupStatusParams,
+    GetJobGroupStatusResult, GetTablesParams, GetTaskLogsParams, JobSummary,
+    KeyValuePair, ListJobsParams, RegisterTableParams, TaskLogLines, UndropTableParams,
+};
 use ballista_core::utils::{
     create_df_ctx_with_ballista_query_planner, create_grpc_client_connection,
 };
+use datafusion_proto::logical_plan::{AsLogicalPlan, DefaultLogicalExtensionCodec};
 use datafusion_proto::protobuf::LogicalPlanNode;
 
 use datafusion::catalog::TableReference;
@@ -39,13 +53,17 @@ use datafusion::dataframe::DataFrame;
 use datafusion::datasource::{source_as_provider, TableProvider};
 use datafusion::error::{DataFusionError, Result};
 use datafusion::logical_expr::{
-    CreateExternalTable, DdlStatement, LogicalPlan, TableScan,
+    CreateExternalTable, DdlStatement, DropTable, LogicalPlan, LogicalPlanBuilder,
+    TableScan,
 };
 use datafusion::prelude::{
     AvroReadOptions, CsvReadOptions, NdJsonReadOptions, ParquetReadOptions,
     SessionConfig, SessionContext,
 };
 use datafusion::sql::parser::{DFParser, Statement as DFStatement};
+use datafusion::sql::planner::object_name_to_table_reference;
+
+use crate::discovery::resolve_schedulers;
 
 struct BallistaContextState {
     /// Ballista configuration
@@ -56,6 +74,14 @@ struct BallistaContextState {
     scheduler_port: u16,
     /// Tables that have been registered with this context
     tables: HashMap<String, Arc<dyn TableProvider>>,
+    /// Names of the registered tables which are shared with the other clients
+    shared_tables: HashSet<String>,
+    /// Whether the tables shared by the other clients were fetched from the scheduler
+    shared_tables_loaded: bool,
+    /// Defaults and capabilities of the cluster, once fetched from the scheduler
+    cluster_config: Option<ClusterConfig>,
+    /// DNS service whose SRV records list the schedulers, if the scheduler was discovered
+    scheduler_service: Option<String>,
 }
 
 impl BallistaContextState {
@@ -69,6 +95,10 @@ impl BallistaContextState {
             scheduler_host,
             scheduler_port,
             tables: HashMap::new(),
+            shared_tables: HashSet::new(),
+            shared_tables_loaded: false,
+            cluster_config: None,
+            scheduler_service: None,
         }
     }
 
@@ -79,7 +109,7 @@ impl BallistaContextState {
 
 pub struct BallistaContext {
     state: Arc<Mutex<BallistaContextState>>,
-    context: Arc<SessionContext>,
+    context: Mutex<Arc<SessionContext>>,
 }
 
 impl BallistaContext {
@@ -90,9 +120,106 @@ impl BallistaContext {
         config: &BallistaConfig,
     ) -> ballista_core::error::Result<Self> {
         let state = BallistaContextState::new(host.to_owned(), port, config);
+        let ctx = Self::connect(host, port, config).await?;
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            context: Mutex::new(Arc::new(ctx)),
+        })
+    }
+
+    /// Create a context for executing queries against one of the schedulers listed by the DNS
+    /// SRV records of `service`, e.g. `_ballista._tcp.cluster.local`. The schedulers are tried
+    /// by priority and weight until one of them accepts the session. Once the scheduler can no
+    /// longer be reached, the records are resolved again and the context moves to another
+    /// scheduler with a new session.
+    pub async fn remote_from_dns(
+        service: &str,
+        config: &BallistaConfig,
+    ) -> ballista_core::error::Result<Self> {
+        let (host, port, ctx) = Self::connect_from_dns(service, config).await?;
+        let mut state = BallistaContextState::new(host, port, config);
+        state.scheduler_service = Some(service.to_owned());
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+            context: Mutex::new(Arc::new(ctx)),
+        })
+    }
+
+    /// Connect to the first scheduler listed by the SRV records of `service` which accepts a
+    /// session
+    async fn connect_from_dns(
+        service: &str,
+        config: &BallistaConfig,
+    ) -> ballista_core::error::Result<(String, u16, SessionContext)> {
+        let mut last_error = None;
+        for scheduler in resolve_schedulers(service).await? {
+            match Self::connect(&scheduler.host, scheduler.port, config).await {
+                Ok(ctx) => return Ok((scheduler.host, scheduler.port, ctx)),
+                Err(e) => {
+                    warn!(
+                        "Scheduler {}:{} of {} is not available: {:?}",
+                        scheduler.host, scheduler.port, service, e
+                    );
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            BallistaError::General(format!(
+                "No scheduler found in the SRV records of {service}"
+            ))
+        }))
+    }
+
+    /// Move to another scheduler of the DNS service the scheduler was discovered with, and
+    /// close the session of the context on the schedulers, which share their sessions.
+    /// Returns false if the scheduler was not discovered with DNS.
+    async fn reconnect(&self) -> ballista_core::error::Result<bool> {
+        let (service, config) = {
+            let state = self.state.lock();
+            match &state.scheduler_service {
+                Some(service) => (service.clone(), state.config().clone()),
+                None => return Ok(false),
+            }
+        };
+        let (host, port, ctx) = Self::connect_from_dns(&service, &config).await?;
+        info!("Moved to Ballista scheduler {host}:{port} of {service}");
+        {
+            let mut state = self.state.lock();
+            state.scheduler_host = host;
+            state.scheduler_port = port;
+            state.cluster_config = None;
+        }
+        let old_ctx = std::mem::replace(&mut *self.context.lock(), Arc::new(ctx));
+        if let Err(e) =
+            Self::close_remote_session(self.scheduler_url(), old_ctx.session_id()).await
+        {
+            warn!("Failed to close session {}: {e:?}", old_ctx.session_id());
+        }
+        Ok(true)
+    }
+
+    /// Whether the scheduler of the context can no longer be reached
+    async fn scheduler_unreachable(&self) -> bool {
+        let connection = match create_grpc_client_connection(self.scheduler_url()).await {
+            Ok(connection) => connection,
+            Err(_) => return true,
+        };
+        SchedulerGrpcClient::new(connection)
+            .get_cluster_config(GetClusterConfigParams {})
+            .await
+            .is_err()
+    }
 
-        let scheduler_url =
-            format!("http://{}:{}", &state.scheduler_host, state.scheduler_port);
+    /// Create a session on a scheduler, returns the client context planning queries with it
+    async fn connect(
+        host: &str,
+        port: u16,
+        config: &BallistaConfig,
+    ) -> ballista_core::error::Result<SessionContext> {
+        let scheduler_url = format!("http://{host}:{port}");
         info!(
             "Connecting to Ballista scheduler at {}",
             scheduler_url.clone()
@@ -114,6 +241,11 @@ impl BallistaContext {
                     })
                     .collect::<Vec<_>>(),
                 optional_session_id: None,
+                optional_job_id: None,
+                job_settings: vec![],
+                idempotent: false,
+                run_as: String::new(),
+                reports_consumed_partitions: false,
             })
             .await
             .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
@@ -125,18 +257,13 @@ impl BallistaContext {
             remote_session_id
         );
 
-        let ctx = {
+        Ok(
             create_df_ctx_with_ballista_query_planner::<LogicalPlanNode>(
                 scheduler_url,
                 remote_session_id,
-                state.config(),
-            )
-        };
-
-        Ok(Self {
-            state: Arc::new(Mutex::new(state)),
-            context: Arc::new(ctx),
-        })
+                config,
+            ),
+        )
     }
 
     #[cfg(feature = "standalone")]
@@ -173,6 +300,11 @@ impl BallistaContext {
                     })
                     .collect::<Vec<_>>(),
                 optional_session_id: None,
+                optional_job_id: None,
+                job_settings: vec![],
+                idempotent: false,
+                run_as: String::new(),
+                reports_consumed_partitions: false,
             })
             .await
             .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
@@ -207,10 +339,335 @@ impl BallistaContext {
 
         Ok(Self {
             state: Arc::new(Mutex::new(state)),
-            context: Arc::new(ctx),
+            context: Mutex::new(Arc::new(ctx)),
         })
     }
 
+    /// Remove the session of this context from the scheduler, returns whether it existed.
+    /// Queries run with the context afterwards create the session again.
+    pub async fn close_session(&self) -> ballista_core::error::Result<bool> {
+        Self::close_remote_session(self.scheduler_url(), self.context().session_id())
+            .await
+    }
+
+    /// Remove a session from the schedulers, through the scheduler at `scheduler_url`
+    async fn close_remote_session(
+        scheduler_url: String,
+        session_id: String,
+    ) -> ballista_core::error::Result<bool> {
+        let connection = create_grpc_client_connection(scheduler_url)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+
+        let closed = scheduler
+            .close_session(CloseSessionParams { session_id })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner()
+            .closed;
+        Ok(closed)
+    }
+
+    /// List the jobs of the cluster carrying all the given labels
+    pub async fn list_jobs(
+        &self,
+        labels: HashMap<String, String>,
+    ) -> Result<Vec<JobSummary>> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let jobs = scheduler
+            .list_jobs(ListJobsParams { labels })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner()
+            .jobs;
+        Ok(jobs)
+    }
+
+    /// Get the aggregated status of the jobs of a group
+    pub async fn job_group_status(
+        &self,
+        group_id: &str,
+    ) -> Result<GetJobGroupStatusResult> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let status = scheduler
+            .get_job_group_status(GetJobGroupStatusParams {
+                group_id: group_id.to_owned(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+        Ok(status)
+    }
+
+    /// Cancel the queued and running jobs of a group, returns the IDs of the cancelled jobs
+    pub async fn cancel_job_group(&self, group_id: &str) -> Result<Vec<String>> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let job_ids = scheduler
+            .cancel_job_group(CancelJobGroupParams {
+                group_id: group_id.to_owned(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner()
+            .job_ids;
+        Ok(job_ids)
+    }
+
+    /// Decommission an executor of the cluster. The scheduler offers it no more tasks and
+    /// stops it once the jobs using it completed, or after `timeout_seconds` unless it is 0.
+    /// Returns the number of active jobs using the executor.
+    pub async fn decommission_executor(
+        &self,
+        executor_id: &str,
+        timeout_seconds: u64,
+    ) -> Result<u64> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let active_jobs = scheduler
+            .decommission_executor(DecommissionExecutorParams {
+                executor_id: executor_id.to_owned(),
+                timeout_seconds,
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner()
+            .active_jobs;
+        Ok(active_jobs)
+    }
+
+    /// Stream the log lines of a task attempt of a job from line `from_line`, fetched by the
+    /// scheduler from the executor the task ran on. When following the log, the stream ends
+    /// once the task finished.
+    pub async fn task_logs(
+        &self,
+        job_id: &str,
+        task_id: u32,
+        from_line: u64,
+        follow: bool,
+    ) -> Result<BoxStream<'static, Result<TaskLogLines>>> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let lines = scheduler
+            .get_task_logs(GetTaskLogsParams {
+                job_id: job_id.to_owned(),
+                task_id,
+                from_line,
+                follow,
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+        Ok(lines
+            .map(|lines| lines.map_err(|e| DataFusionError::Execution(format!("{e:?}"))))
+            .boxed())
+    }
+
+    /// Get the defaults and capabilities of the cluster, fetched from the scheduler once
+    pub async fn cluster_config(&self) -> Result<ClusterConfig> {
+        if let Some(config) = self.state.lock().cluster_config.clone() {
+            return Ok(config);
+        }
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let config = scheduler
+            .get_cluster_config(GetClusterConfigParams {})
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner()
+            .config
+            .ok_or_else(|| {
+                DataFusionError::Internal(
+                    "Scheduler returned no cluster config".to_owned(),
+                )
+            })?;
+        self.state.lock().cluster_config = Some(config.clone());
+        Ok(config)
+    }
+
+    /// URL of the REST API of the scheduler, none if the scheduler only serves gRPC
+    pub async fn rest_api_url(&self) -> Result<Option<String>> {
+        let config = self.cluster_config().await?;
+        Ok(config
+            .rest_api
+            .then(|| format!("{}/api", self.scheduler_url())))
+    }
+
+    /// Check that the cluster can read a table of the given file type. Whether it can read
+    /// the location is left to the executors, which resolve its object store.
+    async fn check_table_supported(&self, file_type: &str) -> Result<()> {
+        let config = self.cluster_config().await?;
+        let file_type = file_type.to_lowercase();
+        if !config.file_formats.contains(&file_type) {
+            return Err(DataFusionError::NotImplemented(format!(
+                "Unsupported file type {file_type:?}, the cluster supports {:?}.",
+                config.file_formats
+            )));
+        }
+        Ok(())
+    }
+
+    /// The client context planning queries with the scheduler
+    fn context(&self) -> Arc<SessionContext> {
+        self.context.lock().clone()
+    }
+
+    fn scheduler_url(&self) -> String {
+        let state = self.state.lock();
+        format!("http://{}:{}", state.scheduler_host, state.scheduler_port)
+    }
+
+    /// Share a table created with a CREATE EXTERNAL TABLE plan with the other clients of the
+    /// scheduler
+    async fn share_table(&self, plan: &LogicalPlan) -> Result<()> {
+        let node = LogicalPlanNode::try_from_logical_plan(
+            plan,
+            &DefaultLogicalExtensionCodec {},
+        )?;
+        let mut buf = vec![];
+        node.try_encode(&mut buf)?;
+        let settings = self
+            .state
+            .lock()
+            .config
+            .settings()
+            .iter()
+            .map(|(k, v)| KeyValuePair {
+                key: k.to_owned(),
+                value: v.to_owned(),
+            })
+            .collect();
+
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        scheduler
+            .register_table(RegisterTableParams {
+                session_id: self.context().session_id(),
+                plan: buf,
+                settings,
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        Ok(())
+    }
+
+    /// Drop a table shared with the other clients of the scheduler, which can be restored
+    /// with UNDROP TABLE within the retention window of the scheduler
+    async fn drop_shared_table(&self, name: &str, if_exists: bool) -> Result<()> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        scheduler
+            .drop_table(DropTableParams {
+                name: name.to_owned(),
+                if_exists,
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        Ok(())
+    }
+
+    /// Restore a shared table dropped within the retention window of the scheduler
+    async fn undrop_shared_table(&self, name: &str) -> Result<()> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        scheduler
+            .undrop_table(UndropTableParams {
+                name: name.to_owned(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        Ok(())
+    }
+
+    /// Remove a table from this context
+    fn deregister_table(&self, name: &str) -> Result<()> {
+        {
+            let mut state = self.state.lock();
+            state.tables.remove(name);
+            state.shared_tables.remove(name);
+        }
+        self.context().deregister_table(name)?;
+        Ok(())
+    }
+
+    /// Register the tables shared by the other clients of the scheduler the first time a
+    /// statement is planned. The tables they share or drop later are only seen by this
+    /// context once it calls [Self::refresh_shared_tables].
+    async fn load_shared_tables(&self) -> Result<()> {
+        if self.state.lock().shared_tables_loaded {
+            return Ok(());
+        }
+        self.refresh_shared_tables().await
+    }
+
+    /// Register the tables shared by the other clients of the scheduler which are not
+    /// registered with this context yet, and remove the shared tables they dropped.
+    /// A scheduler which does not support shared tables shares none.
+    pub async fn refresh_shared_tables(&self) -> Result<()> {
+        let connection = create_grpc_client_connection(self.scheduler_url())
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let mut scheduler = SchedulerGrpcClient::new(connection);
+        let tables = match scheduler.get_tables(GetTablesParams {}).await {
+            Ok(response) => response.into_inner().tables,
+            Err(status) if status.code() == Code::Unimplemented => vec![],
+            Err(e) => return Err(DataFusionError::Execution(format!("{e:?}"))),
+        };
+
+        let dropped: Vec<String> = self
+            .state
+            .lock()
+            .shared_tables
+            .iter()
+            .filter(|name| !tables.iter().any(|table| &table.name == *name))
+            .cloned()
+            .collect();
+        for name in dropped {
+            self.deregister_table(&name)?;
+        }
+
+        // The tables are resolved in a context of their own, the context of the client
+        // planning queries with the scheduler
+        let ctx = SessionContext::new();
+        for table in tables {
+            if self.state.lock().tables.contains_key(&table.name) {
+                continue;
+            }
+            let plan = LogicalPlanNode::try_decode(&table.plan)?
+                .try_into_logical_plan(&ctx, &DefaultLogicalExtensionCodec {})?;
+            if let Err(e) = ctx.execute_logical_plan(plan).await {
+                warn!("Failed to create shared table {}: {e}", table.name);
+                continue;
+            }
+            let provider = ctx.table_provider(table.name.as_str()).await?;
+            self.register_table(&table.name, provider)?;
+            self.state.lock().shared_tables.insert(table.name);
+        }
+        self.state.lock().shared_tables_loaded = true;
+        Ok(())
+    }
+
     /// Create a DataFrame representing an Json table scan
     /// TODO fetch schema from scheduler instead of resolving locally
     pub async fn read_json<P: DataFilePaths>(
@@ -218,7 +675,7 @@ impl BallistaContext {
         paths: P,
         options: NdJsonReadOptions<'_>,
     ) -> Result<DataFrame> {
-        let df = self.context.read_json(paths, options).await?;
+        let df = self.context().read_json(paths, options).await?;
         Ok(df)
     }
 
@@ -229,7 +686,7 @@ impl BallistaContext {
         paths: P,
         options: AvroReadOptions<'_>,
     ) -> Result<DataFrame> {
-        let df = self.context.read_avro(paths, options).await?;
+        let df = self.context().read_avro(paths, options).await?;
         Ok(df)
     }
 
@@ -240,7 +697,7 @@ impl BallistaContext {
         paths: P,
         options: ParquetReadOptions<'_>,
     ) -> Result<DataFrame> {
-        let df = self.context.read_parquet(paths, options).await?;
+        let df = self.context().read_parquet(paths, options).await?;
         Ok(df)
     }
 
@@ -251,7 +708,7 @@ impl BallistaContext {
         paths: P,
         options: CsvReadOptions<'_>,
     ) -> Result<DataFrame> {
-        let df = self.context.read_csv(paths, options).await?;
+        let df = self.context().read_csv(paths, options).await?;
         Ok(df)
     }
 
@@ -354,9 +811,50 @@ impl BallistaContext {
     /// Create a DataFrame from a SQL statement.
     ///
     /// This method is `async` because queries of type `CREATE EXTERNAL TABLE`
-    /// might require the schema to be inferred.
+    /// might require the schema to be inferred. Tables created with `CREATE EXTERNAL TABLE`
+    /// are shared with the other clients of the scheduler, whose tables are registered with
+    /// this context before its first statement is planned and refreshed with
+    /// [Self::refresh_shared_tables]. DROP TABLE of a shared table drops it
+    /// for all clients, and `UNDROP TABLE <name>` restores it within the retention window
+    /// of the scheduler.
+    ///
+    /// A context which discovered its scheduler with DNS moves to another scheduler of the
+    /// service when the statement fails because its scheduler can no longer be reached, and
+    /// plans the statement again there.
     pub async fn sql(&self, sql: &str) -> Result<DataFrame> {
-        let mut ctx = self.context.clone();
+        match self.plan_sql(sql).await {
+            Err(e) if self.scheduler_unreachable().await => {
+                if !self
+                    .reconnect()
+                    .await
+                    .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+                {
+                    return Err(e);
+                }
+                self.plan_sql(sql).await
+            }
+            result => result,
+        }
+    }
+
+    async fn plan_sql(&self, sql: &str) -> Result<DataFrame> {
+        self.load_shared_tables().await?;
+        let normalize_idents = self
+            .context()
+            .copied_config()
+            .options()
+            .sql_parser
+            .enable_ident_normalization;
+        if let Some(name) = undrop_table_name(sql, normalize_idents)? {
+            self.undrop_shared_table(&name).await?;
+            self.refresh_shared_tables().await?;
+            let ctx = self.context();
+            return Ok(DataFrame::new(
+                ctx.state(),
+                LogicalPlanBuilder::empty(false).build()?,
+            ));
+        }
+        let mut ctx = self.context();
 
         let is_show = self.is_show_statement(sql).await?;
         // the show tables、 show columns sql can not run at scheduler because the tables is store at client
@@ -417,53 +915,103 @@ impl BallistaContext {
                     .collect::<Result<Vec<_>>>()?;
 
                 match (if_not_exists, table_exists) {
-                    (_, false) => match file_type.to_lowercase().as_str() {
-                        "csv" => {
-                            let mut options = CsvReadOptions::new()
-                                .has_header(*has_header)
-                                .delimiter(*delimiter as u8)
-                                .table_partition_cols(table_partition_cols.to_vec());
-                            if !schema.fields().is_empty() {
-                                options = options.schema(&schema);
+                    (_, false) => {
+                        self.check_table_supported(file_type).await?;
+                        match file_type.to_lowercase().as_str() {
+                            "csv" => {
+                                let mut options = CsvReadOptions::new()
+                                    .has_header(*has_header)
+                                    .delimiter(*delimiter as u8)
+                                    .table_partition_cols(table_partition_cols.to_vec());
+                                if !schema.fields().is_empty() {
+                                    options = options.schema(&schema);
+                                }
+                                self.register_csv(name.table(), location, options)
+                                    .await?;
+                            }
+                            "parquet" => {
+                                self.register_parquet(
+                                    name.table(),
+                                    location,
+                                    ParquetReadOptions::default()
+                                        .table_partition_cols(table_partition_cols),
+                                )
+                                .await?;
+                            }
+                            "avro" => {
+                                self.register_avro(
+                                    name.table(),
+                                    location,
+                                    AvroReadOptions::default()
+                                        .table_partition_cols(table_partition_cols),
+                                )
+                                .await?;
+                            }
+                            _ => {
+                                return Err(DataFusionError::NotImplemented(format!(
+                                    "Unsupported file type {file_type:?}."
+                                )))
                             }
-                            self.register_csv(name.table(), location, options).await?;
-                            Ok(DataFrame::new(ctx.state(), plan))
-                        }
-                        "parquet" => {
-                            self.register_parquet(
-                                name.table(),
-                                location,
-                                ParquetReadOptions::default()
-                                    .table_partition_cols(table_partition_cols),
-                            )
-                            .await?;
-                            Ok(DataFrame::new(ctx.state(), plan))
-                        }
-                        "avro" => {
-                            self.register_avro(
-                                name.table(),
-                                location,
-                                AvroReadOptions::default()
-                                    .table_partition_cols(table_partition_cols),
-                            )
-                            .await?;
-                            Ok(DataFrame::new(ctx.state(), plan))
                         }
-                        _ => Err(DataFusionError::NotImplemented(format!(
-                            "Unsupported file type {file_type:?}."
-                        ))),
-                    },
+                        self.share_table(&plan).await?;
+                        self.state.lock().shared_tables.insert(name.to_string());
+                        Ok(DataFrame::new(ctx.state(), plan))
+                    }
                     (true, true) => Ok(DataFrame::new(ctx.state(), plan)),
                     (false, true) => Err(DataFusionError::Execution(format!(
                         "Table '{name:?}' already exists"
                     ))),
                 }
             }
+            LogicalPlan::Ddl(DdlStatement::DropTable(DropTable {
+                ref name,
+                if_exists,
+                ..
+            })) => {
+                let name = name.to_string();
+                if self.state.lock().shared_tables.contains(&name) {
+                    self.drop_shared_table(&name, if_exists).await?;
+                }
+                // Tables registered with this context are registered again with the
+                // DataFusion context before each statement
+                {
+                    let mut state = self.state.lock();
+                    state.tables.remove(&name);
+                    state.shared_tables.remove(&name);
+                }
+                ctx.sql(sql).await
+            }
             _ => ctx.sql(sql).await,
         }
     }
 }
 
+/// The name of the table restored by an `UNDROP TABLE <name>` statement, which DataFusion
+/// does not parse, or `None` if the SQL is another statement. The name is normalised the
+/// same way DataFusion normalises the names of the tables created and dropped.
+fn undrop_table_name(sql: &str, normalize_idents: bool) -> Result<Option<String>> {
+    let dialect = GenericDialect {};
+    // tokenizer errors are left to DataFusion to report when it parses the statement
+    let mut parser = match Parser::new(&dialect).try_with_sql(sql) {
+        Ok(parser) => parser,
+        Err(_) => return Ok(None),
+    };
+    match parser.peek_token().token {
+        Token::Word(word)
+            if word.quote_style.is_none()
+                && word.value.eq_ignore_ascii_case("UNDROP") => {}
+        _ => return Ok(None),
+    }
+    parser.next_token();
+    parser.expect_keyword(Keyword::TABLE)?;
+    let name = parser.parse_object_name()?;
+    while parser.consume_token(&Token::SemiColon) {}
+    parser.expect_token(&Token::EOF)?;
+    Ok(Some(
+        object_name_to_table_reference(name, normalize_idents)?.to_string(),
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "standalone")]
@@ -480,6 +1028,30 @@ mod tests {
         df.collect().await.unwrap();
     }
 
+    #[tokio::test]
+    #[cfg(feature = "standalone")]
+    async fn test_cluster_config() {
+        use super::*;
+        let config = BallistaConfig::new().unwrap();
+        let context = BallistaContext::standalone(&config, 1).await.unwrap();
+        let cluster_config = context.cluster_config().await.unwrap();
+        assert_eq!(
+            cluster_config.default_shuffle_partitions,
+            config.default_shuffle_partitions() as u64
+        );
+        assert!(cluster_config.file_formats.contains(&"csv".to_owned()));
+        // The standalone scheduler only serves gRPC
+        assert_eq!(context.rest_api_url().await.unwrap(), None);
+
+        let err = context
+            .sql("CREATE EXTERNAL TABLE t STORED AS CSV LOCATION 'gs://bucket/t.csv'")
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("No object store for gs://bucket/t.csv"));
+    }
+
     #[tokio::test]
     #[cfg(feature = "standalone")]
     async fn test_ballista_show_tables() {
@@ -569,6 +1141,72 @@ mod tests {
         assert!(df.is_ok());
     }
 
+    #[tokio::test]
+    #[cfg(feature = "standalone")]
+    async fn test_drop_and_undrop_shared_table() {
+        use super::*;
+        use std::fs::File;
+        use std::io::Write;
+        use tempfile::TempDir;
+        let context = BallistaContext::standalone(&BallistaConfig::new().unwrap(), 1)
+            .await
+            .unwrap();
+
+        let tmp_dir = TempDir::new().unwrap();
+        let file_path = tmp_dir.path().join("names.csv");
+        File::create(&file_path)
+            .expect("creating temp file")
+            .write_all(b"Jorge\nAndrew\n")
+            .expect("writing data");
+        let sql = format!(
+            "CREATE EXTERNAL TABLE names (name VARCHAR) STORED AS CSV LOCATION '{}'",
+            file_path.to_str().expect("path is utf8")
+        );
+        context.sql(sql.as_str()).await.unwrap();
+
+        context.sql("DROP TABLE names").await.unwrap();
+        assert!(context.sql("SELECT * FROM names").await.is_err());
+        assert!(context.sql("DROP TABLE names").await.is_err());
+        context.sql("DROP TABLE IF EXISTS names").await.unwrap();
+
+        context.sql("undrop table names;").await.unwrap();
+        let batches = context
+            .sql("SELECT * FROM names")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        assert!(context.sql("UNDROP TABLE names").await.is_err());
+    }
+
+    #[test]
+    fn test_undrop_table_name() {
+        use super::*;
+
+        let name = |sql| undrop_table_name(sql, true).unwrap();
+        assert_eq!(name(" UNDROP TABLE \"t\";"), Some("t".to_owned()));
+        assert_eq!(
+            name("undrop table Names -- restore"),
+            Some("names".to_owned())
+        );
+        assert_eq!(
+            name("/* restore */ UNDROP TABLE \"My Schema\".\"My Table\""),
+            Some("My Schema.My Table".to_owned())
+        );
+        assert_eq!(name("UNDROP TABLE S.T;\n"), Some("s.t".to_owned()));
+        assert_eq!(
+            undrop_table_name("UNDROP TABLE Names", false).unwrap(),
+            Some("Names".to_owned())
+        );
+        assert_eq!(name("DROP TABLE t"), None);
+        assert_eq!(name("SELECT 'undrop"), None);
+        assert!(undrop_table_name("UNDROP TABLE", true).is_err());
+        assert!(undrop_table_name("UNDROP TABLE t u", true).is_err());
+        assert!(undrop_table_name("UNDROP VIEW t", true).is_err());
+    }
+
     #[tokio::test]
     #[cfg(feature = "standalone")]
     #[ignore]
@@ -975,4 +1613,46 @@ mod tests {
             );
         }
     }
+
+    #[tokio::test]
+    #[cfg(feature = "standalone")]
+    async fn test_nested_and_dictionary_types() {
+        use super::*;
+        use ballista_core::config::BallistaConfigBuilder;
+        use datafusion::arrow::util::pretty::pretty_format_batches;
+
+        let config = BallistaConfigBuilder::default().build().unwrap();
+        let context = BallistaContext::standalone(&config, 2).await.unwrap();
+        let local = SessionContext::new();
+
+        let values =
+            "(VALUES ('a', 1, 'x'), ('b', 2, 'y'), ('c', 3, 'x'), ('a', 4, 'y'))";
+        let queries = [
+            format!(
+                "SELECT column1, CAST(column2 AS DECIMAL(10, 2)) AS d, \
+                struct(column2, column3) AS s, make_array(column2, column2) AS l, \
+                arrow_cast(column1, 'Dictionary(Int32, Utf8)') AS d1, \
+                arrow_cast(column3, 'Dictionary(Int32, Utf8)') AS d2 \
+                FROM {values} ORDER BY column2"
+            ),
+            // the dictionary encoded group keys are hash shuffled
+            format!(
+                "SELECT d1, d2, MIN(d) AS m FROM (\
+                SELECT arrow_cast(column1, 'Dictionary(Int32, Utf8)') AS d1, \
+                arrow_cast(column3, 'Dictionary(Int32, Utf8)') AS d2, \
+                CAST(column2 AS DECIMAL(10, 2)) AS d FROM {values}) \
+                GROUP BY d1, d2 ORDER BY d1, d2"
+            ),
+        ];
+
+        for sql in queries {
+            let expected = local.sql(&sql).await.unwrap().collect().await.unwrap();
+            let actual = context.sql(&sql).await.unwrap().collect().await.unwrap();
+            assert_eq!(
+                pretty_format_batches(&expected).unwrap().to_string(),
+                pretty_format_batches(&actual).unwrap().to_string(),
+                "{sql}"
+            );
+        }
+    }
 }
@@ -0,0 +1,143 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Discovery of the schedulers of a Ballista cluster from DNS SRV records, such as the
+//! records of a headless Kubernetes service
+
+use ballista_core::error::{BallistaError, Result};
+use rand::Rng;
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// A scheduler listed by an SRV record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchedulerRecord {
+    pub host: String,
+    pub port: u16,
+    pub priority: u16,
+    pub weight: u16,
+}
+
+/// Resolve the SRV records of a service, e.g. `_ballista._tcp.cluster.local`, into the
+/// schedulers it lists in the order they should be tried
+pub async fn resolve_schedulers(service: &str) -> Result<Vec<SchedulerRecord>> {
+    let resolver = TokioAsyncResolver::tokio_from_system_conf().map_err(|e| {
+        BallistaError::General(format!("Failed to create a DNS resolver: {e}"))
+    })?;
+    let lookup = resolver.srv_lookup(service).await.map_err(|e| {
+        BallistaError::General(format!(
+            "Failed to resolve the SRV records of {service}: {e}"
+        ))
+    })?;
+
+    let records = lookup
+        .iter()
+        .map(|srv| SchedulerRecord {
+            host: srv.target().to_utf8().trim_end_matches('.').to_owned(),
+            port: srv.port(),
+            priority: srv.priority(),
+            weight: srv.weight(),
+        })
+        .collect();
+    Ok(order_schedulers(records, &mut rand::thread_rng()))
+}
+
+/// Order the schedulers by increasing priority. The schedulers of the same priority are
+/// ordered at random, each picked next with a probability proportional to its weight as
+/// described by RFC 2782, so that the clients spread over them.
+fn order_schedulers(
+    mut records: Vec<SchedulerRecord>,
+    rng: &mut impl Rng,
+) -> Vec<SchedulerRecord> {
+    // Records of weight 0 come first so that they have a small chance to be picked
+    records.sort_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then_with(|| a.weight.cmp(&b.weight))
+    });
+    let mut ordered = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].priority;
+        let same_priority = records
+            .iter()
+            .take_while(|record| record.priority == priority)
+            .count();
+        let mut candidates: Vec<SchedulerRecord> =
+            records.drain(..same_priority).collect();
+        while !candidates.is_empty() {
+            let total_weight: u32 =
+                candidates.iter().map(|record| record.weight as u32).sum();
+            let pick = rng.gen_range(0..=total_weight);
+            let mut running_weight = 0;
+            let index = candidates
+                .iter()
+                .position(|record| {
+                    running_weight += record.weight as u32;
+                    running_weight >= pick
+                })
+                .unwrap_or_default();
+            ordered.push(candidates.remove(index));
+        }
+    }
+    ordered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+
+    fn record(host: &str, priority: u16, weight: u16) -> SchedulerRecord {
+        SchedulerRecord {
+            host: host.to_owned(),
+            port: 50050,
+            priority,
+            weight,
+        }
+    }
+
+    #[test]
+    fn test_order_schedulers() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut first = HashMap::new();
+        for _ in 0..1000 {
+            let ordered = order_schedulers(
+                vec![
+                    record("scheduler-3", 10, 100),
+                    record("scheduler-2", 0, 0),
+                    record("scheduler-1", 0, 10),
+                    record("scheduler-0", 0, 30),
+                ],
+                &mut rng,
+            );
+            let hosts: Vec<&str> = ordered.iter().map(|r| r.host.as_str()).collect();
+            // The schedulers of a lower priority come last whatever their weight
+            assert_eq!(hosts.len(), 4);
+            assert_eq!(hosts[3], "scheduler-3");
+            *first.entry(hosts[0].to_owned()).or_insert(0) += 1;
+        }
+        // The schedulers of the same priority are tried first in proportion to their weight
+        let picked = |host: &str| first.get(host).copied().unwrap_or(0);
+        assert!(picked("scheduler-0") > 2 * picked("scheduler-1"));
+        assert!(picked("scheduler-1") > picked("scheduler-2"));
+        assert_eq!(
+            picked("scheduler-0") + picked("scheduler-1") + picked("scheduler-2"),
+            1000
+        );
+    }
+}
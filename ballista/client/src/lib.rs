@@ -19,4 +19,7 @@
 
 pub mod columnar_batch;
 pub mod context;
+pub mod discovery;
 pub mod prelude;
+#[cfg(feature = "standalone")]
+pub mod standalone;
@@ -30,3 +30,5 @@ pub use ballista_core::{
 pub use futures::StreamExt;
 
 pub use crate::context::BallistaContext;
+#[cfg(feature = "standalone")]
+pub use crate::standalone::StandaloneCluster;
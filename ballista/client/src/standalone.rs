@@ -0,0 +1,154 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! In-process Ballista cluster shared by many contexts.
+
+use std::sync::mpsc;
+use std::sync::Arc;
+
+use ballista_core::config::BallistaConfig;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::BallistaCodec;
+use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+use log::info;
+use tokio::sync::oneshot;
+
+use crate::context::BallistaContext;
+
+/// A scheduler and an executor running in this process, against which any number of
+/// [BallistaContext]s can be created, each of them with its own session.
+///
+/// Starting the cluster once, e.g. in a `static` shared by the tests of a crate, avoids
+/// paying the startup of a scheduler and an executor for each context as
+/// [BallistaContext::standalone] does. The cluster runs on its own Tokio runtime so that it
+/// outlives the runtimes of the tests using it, and stops once all the clones of its handle
+/// are dropped.
+#[derive(Clone)]
+pub struct StandaloneCluster {
+    scheduler_port: u16,
+    // Stops the runtime of the cluster once the last handle is dropped
+    _shutdown: Arc<mpsc::Sender<()>>,
+}
+
+impl StandaloneCluster {
+    /// Start a cluster whose executor runs as many tasks at a time as there are CPUs
+    pub async fn start() -> Result<Self> {
+        let concurrent_tasks = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Self::start_with_concurrent_tasks(concurrent_tasks).await
+    }
+
+    /// Start a cluster whose executor runs up to `concurrent_tasks` tasks at a time
+    pub async fn start_with_concurrent_tasks(concurrent_tasks: usize) -> Result<Self> {
+        let (started_tx, started_rx) = oneshot::channel();
+        let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>();
+
+        std::thread::Builder::new()
+            .name("ballista-standalone".to_owned())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let _ = started_tx.send(Err(BallistaError::IoError(e)));
+                        return;
+                    }
+                };
+                let started =
+                    runtime.block_on(start_standalone_cluster(concurrent_tasks));
+                let is_started = started.is_ok();
+                let _ = started_tx.send(started);
+                if is_started {
+                    // Returns once all the handles of the cluster are dropped
+                    let _ = shutdown_rx.recv();
+                }
+            })?;
+
+        let scheduler_port = started_rx.await.map_err(|_| {
+            BallistaError::Internal(
+                "Standalone cluster stopped before it started".to_owned(),
+            )
+        })??;
+        info!("Standalone cluster started with scheduler on port {scheduler_port}");
+
+        Ok(Self {
+            scheduler_port,
+            _shutdown: Arc::new(shutdown_tx),
+        })
+    }
+
+    /// Port of the scheduler of the cluster, listening on localhost
+    pub fn scheduler_port(&self) -> u16 {
+        self.scheduler_port
+    }
+
+    /// Create a context with a new session on the cluster
+    pub async fn context(&self, config: &BallistaConfig) -> Result<BallistaContext> {
+        BallistaContext::remote("localhost", self.scheduler_port, config).await
+    }
+}
+
+/// Start a scheduler and an executor on the current runtime, returns the port of the scheduler
+pub(crate) async fn start_standalone_cluster(concurrent_tasks: usize) -> Result<u16> {
+    let addr = ballista_scheduler::standalone::new_standalone_scheduler().await?;
+    let scheduler_url = format!("http://localhost:{}", addr.port());
+    let scheduler = loop {
+        match SchedulerGrpcClient::connect(scheduler_url.clone()).await {
+            Err(_) => {
+                tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+                info!("Attempting to connect to in-proc scheduler...");
+            }
+            Ok(scheduler) => break scheduler,
+        }
+    };
+
+    let default_codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
+        BallistaCodec::default();
+    ballista_executor::new_standalone_executor(
+        scheduler,
+        concurrent_tasks,
+        default_codec,
+    )
+    .await?;
+
+    Ok(addr.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_standalone_cluster() -> Result<()> {
+        let cluster = StandaloneCluster::start_with_concurrent_tasks(2).await?;
+        let config = BallistaConfig::new()?;
+
+        let first = cluster.context(&config).await?;
+        let second = cluster.clone().context(&config).await?;
+
+        for context in [first, second] {
+            let batches = context.sql("SELECT 1;").await?.collect().await?;
+            assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 1);
+        }
+
+        Ok(())
+    }
+}
@@ -39,8 +39,10 @@ fn main() -> Result<(), String> {
     if Path::new("proto/datafusion.proto").exists() {
         println!("cargo:rerun-if-changed=proto/datafusion.proto");
         println!("cargo:rerun-if-changed=proto/ballista.proto");
+        let descriptor_path = out.join("ballista_descriptor.bin");
         tonic_build::configure()
             .extern_path(".datafusion", "::datafusion_proto::protobuf")
+            .file_descriptor_set_path(&descriptor_path)
             .compile(&["proto/ballista.proto"], &["proto"])
             .map_err(|e| format!("protobuf compilation failed: {e}"))?;
         let generated_source_path = out.join("ballista.protobuf.rs");
@@ -52,6 +54,14 @@ fn main() -> Result<(), String> {
             .open(path)
             .unwrap();
         file.write_all(code.as_str().as_ref()).unwrap();
+
+        // The descriptors are served by the gRPC reflection services
+        #[cfg(not(feature = "docsrs"))]
+        std::fs::copy(
+            descriptor_path,
+            "src/serde/generated/ballista_descriptor.bin",
+        )
+        .unwrap();
     }
 
     Ok(())
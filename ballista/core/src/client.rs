@@ -32,6 +32,9 @@ use arrow_flight::utils::flight_data_to_arrow_batch;
 use arrow_flight::Ticket;
 use arrow_flight::{flight_service_client::FlightServiceClient, FlightData};
 use datafusion::arrow::array::ArrayRef;
+use datafusion::arrow::buffer::Buffer;
+use datafusion::arrow::ipc::reader::read_dictionary;
+use datafusion::arrow::ipc::root_as_message;
 use datafusion::arrow::{
     datatypes::{Schema, SchemaRef},
     error::ArrowError,
@@ -42,9 +45,11 @@ use datafusion::error::DataFusionError;
 use crate::serde::protobuf;
 use crate::utils::create_grpc_client_connection;
 use datafusion::physical_plan::{RecordBatchStream, SendableRecordBatchStream};
-use futures::{Stream, StreamExt};
+use futures::stream::BoxStream;
+use futures::{ready, Stream, StreamExt};
 use log::{debug, warn};
 use prost::Message;
+use tonic::metadata::{Ascii, MetadataValue};
 use tonic::{Code, Streaming};
 
 /// Client for interacting with Ballista executors.
@@ -77,7 +82,8 @@ impl BallistaClient {
         Ok(Self { flight_client })
     }
 
-    /// Fetch a partition from an executor
+    /// Fetch a partition from an executor, which verifies the content of the partition file
+    /// against `checksum` if any
     pub async fn fetch_partition(
         &mut self,
         executor_id: &str,
@@ -85,6 +91,7 @@ impl BallistaClient {
         path: &str,
         host: &str,
         port: u16,
+        checksum: Option<u32>,
     ) -> Result<SendableRecordBatchStream> {
         let action = Action::FetchPartition {
             job_id: partition_id.job_id.clone(),
@@ -93,6 +100,7 @@ impl BallistaClient {
             path: path.to_owned(),
             host: host.to_owned(),
             port,
+            checksum,
         };
         self.execute_action(&action)
             .await
@@ -185,6 +193,54 @@ impl BallistaClient {
         }
         unreachable!("Did not receive schema batch from flight server");
     }
+
+    /// Fetch the log lines of a task attempt from line `from_line`. When following the log,
+    /// the stream ends once the task finished, with the lines it logs in the meantime.
+    /// The `authorization` header of the user is forwarded to the executor, which serves
+    /// the logs of the tasks of owned jobs to their owner only.
+    pub async fn fetch_task_logs(
+        &mut self,
+        job_id: &str,
+        task_id: usize,
+        from_line: u64,
+        follow: bool,
+        authorization: Option<MetadataValue<Ascii>>,
+    ) -> Result<BoxStream<'static, Result<protobuf::TaskLogLines>>> {
+        let action: protobuf::Action = Action::FetchTaskLogs {
+            job_id: job_id.to_owned(),
+            task_id,
+            from_line,
+            follow,
+        }
+        .try_into()?;
+        let mut request = tonic::Request::new(arrow_flight::Action {
+            r#type: "fetch_task_logs".to_owned(),
+            body: action.encode_to_vec().into(),
+        });
+        if let Some(authorization) = authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization);
+        }
+        let stream = self
+            .flight_client
+            .do_action(request)
+            .await
+            .map_err(|e| BallistaError::GrpcActionError(e.message().to_owned()))?
+            .into_inner();
+        Ok(stream
+            .map(|result| {
+                let result = result.map_err(|e| {
+                    BallistaError::GrpcActionError(e.message().to_owned())
+                })?;
+                protobuf::TaskLogLines::decode(result.body).map_err(|e| {
+                    BallistaError::GrpcActionError(format!(
+                        "Could not decode the task log lines: {e}"
+                    ))
+                })
+            })
+            .boxed())
+    }
 }
 
 struct FlightDataStream {
@@ -201,6 +257,35 @@ impl FlightDataStream {
             dictionaries_by_id: HashMap::new(),
         }
     }
+
+    /// Decode a message of the stream. Dictionaries are kept for the following batches
+    /// referencing them, until replaced, and None is returned
+    fn decode(
+        &mut self,
+        flight_data: &FlightData,
+    ) -> std::result::Result<Option<RecordBatch>, ArrowError> {
+        let message = root_as_message(&flight_data.data_header[..]).map_err(|e| {
+            ArrowError::ParseError(format!("Unable to get root as message: {e:?}"))
+        })?;
+        match message.header_as_dictionary_batch() {
+            Some(dictionary_batch) => {
+                read_dictionary(
+                    &Buffer::from(flight_data.data_body.as_ref()),
+                    dictionary_batch,
+                    &self.schema,
+                    &mut self.dictionaries_by_id,
+                    &message.version(),
+                )?;
+                Ok(None)
+            }
+            None => flight_data_to_arrow_batch(
+                flight_data,
+                self.schema.clone(),
+                &self.dictionaries_by_id,
+            )
+            .map(Some),
+        }
+    }
 }
 
 impl Stream for FlightDataStream {
@@ -210,22 +295,24 @@ impl Stream for FlightDataStream {
         mut self: std::pin::Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        self.stream.poll_next_unpin(cx).map(|x| match x {
-            Some(flight_data_chunk_result) => {
-                let converted_chunk = flight_data_chunk_result
-                    .map_err(|e| ArrowError::from_external_error(Box::new(e)).into())
-                    .and_then(|flight_data_chunk| {
-                        flight_data_to_arrow_batch(
-                            &flight_data_chunk,
-                            self.schema.clone(),
-                            &self.dictionaries_by_id,
-                        )
-                        .map_err(DataFusionError::ArrowError)
-                    });
-                Some(converted_chunk)
+        loop {
+            let flight_data_chunk = match ready!(self.stream.poll_next_unpin(cx)) {
+                Some(Ok(flight_data_chunk)) => flight_data_chunk,
+                Some(Err(e)) => {
+                    return Poll::Ready(Some(Err(ArrowError::from_external_error(
+                        Box::new(e),
+                    )
+                    .into())))
+                }
+                None => return Poll::Ready(None),
+            };
+            match self.decode(&flight_data_chunk) {
+                Ok(Some(batch)) => return Poll::Ready(Some(Ok(batch))),
+                // Dictionaries are followed by the batches using them
+                Ok(None) => continue,
+                Err(e) => return Poll::Ready(Some(Err(DataFusionError::ArrowError(e)))),
             }
-            None => None,
-        })
+        }
     }
 }
 
@@ -22,12 +22,58 @@ use clap::ArgEnum;
 use core::fmt;
 use std::collections::HashMap;
 use std::result;
+use std::time::Duration;
+use tonic::codec::CompressionEncoding;
 
 use crate::error::{BallistaError, Result};
 
 use datafusion::arrow::datatypes::DataType;
 
 pub const BALLISTA_JOB_NAME: &str = "ballista.job.name";
+/// Comma separated `key=value` labels attached to the jobs of a session, to filter jobs by
+pub const BALLISTA_JOB_LABELS: &str = "ballista.job.labels";
+/// ID of the group of the jobs of a session, which can be followed and cancelled together
+pub const BALLISTA_JOB_GROUP: &str = "ballista.job.group";
+/// Pool of executors the jobs of a session run on, empty for the default pool
+pub const BALLISTA_JOB_POOL: &str = "ballista.job.pool";
+/// User the jobs of a session run on behalf of, empty to run them as the authenticated client
+pub const BALLISTA_JOB_RUN_AS: &str = "ballista.job.run_as";
+/// User owning a job, the authenticated client or the user it runs the job on behalf of, set
+/// by the scheduler. Only the owner may read the results of the job.
+pub const BALLISTA_JOB_OWNER: &str = "ballista.job.owner";
+/// ID the jobs of a session are submitted with, empty to let the scheduler generate it
+pub const BALLISTA_JOB_ID: &str = "ballista.job.id";
+/// Whether submitting a job with the ID of an existing job returns the existing job instead
+/// of failing
+pub const BALLISTA_JOB_IDEMPOTENT: &str = "ballista.job.idempotent";
+/// Directory shared by the executors, e.g. a network file system mount, the outputs of the
+/// stages of the jobs of a session are copied to, empty to not checkpoint them. The copies
+/// are deleted with the data of the jobs.
+pub const BALLISTA_JOB_CHECKPOINT_DIR: &str = "ballista.job.checkpoint_dir";
+/// Number of result partitions of a job waiting to be fetched by its client after which
+/// the scheduler stops launching the tasks of the final stage, 0 for no limit
+pub const BALLISTA_JOB_RESULT_BUFFER: &str = "ballista.job.result_buffer";
+/// Maximum number of stages of a job running at once, 0 for no limit
+pub const BALLISTA_MAX_CONCURRENT_STAGES_PER_JOB: &str =
+    "ballista.job.max_concurrent_stages";
+/// Estimated number of bytes scanned by a job up to which it runs as a single task on one
+/// executor instead of being split into stages, 0 to always split jobs into stages
+pub const BALLISTA_JOB_SINGLE_TASK_MAX_BYTES: &str = "ballista.job.single_task_max_bytes";
+/// Whether the scan stages of a job are shared with the identical scan stages of the other
+/// in-flight jobs enabling it, instead of scanning the same files again
+pub const BALLISTA_JOB_SHARED_SCANS: &str = "ballista.job.shared_scans";
+/// Seconds the results of a successful job are kept on the executors before they expire, 0
+/// for the data clean up interval of the scheduler
+pub const BALLISTA_JOB_RESULT_TTL_SECONDS: &str = "ballista.job.result_ttl_seconds";
+/// Object store prefix, e.g. `s3://bucket/exports`, the results of the jobs of a session are
+/// exported to instead of being fetched by the client, empty to fetch them
+pub const BALLISTA_JOB_EXPORT_LOCATION: &str = "ballista.job.export_location";
+/// Comma separated `key=value` options of the object store of the export location, except
+/// credentials which come from the export profile
+pub const BALLISTA_JOB_EXPORT_OPTIONS: &str = "ballista.job.export_options";
+/// Name of the export profile of the executors and schedulers holding the credentials of the
+/// object store of the export location, empty for none
+pub const BALLISTA_JOB_EXPORT_PROFILE: &str = "ballista.job.export_profile";
 pub const BALLISTA_DEFAULT_SHUFFLE_PARTITIONS: &str = "ballista.shuffle.partitions";
 pub const BALLISTA_DEFAULT_BATCH_SIZE: &str = "ballista.batch.size";
 pub const BALLISTA_REPARTITION_JOINS: &str = "ballista.repartition.joins";
@@ -37,6 +83,35 @@ pub const BALLISTA_PARQUET_PRUNING: &str = "ballista.parquet.pruning";
 pub const BALLISTA_WITH_INFORMATION_SCHEMA: &str = "ballista.with_information_schema";
 /// give a plugin files dir, and then the dynamic library files in this dir will be load when scheduler state init.
 pub const BALLISTA_PLUGIN_DIR: &str = "ballista.plugin_dir";
+/// Maximum number of task slots the jobs of a session may use at once, 0 means unlimited
+pub const BALLISTA_SESSION_MAX_TASK_SLOTS: &str = "ballista.session.max_task_slots";
+/// Priority of the jobs of a session, jobs with a higher priority are scheduled first
+pub const BALLISTA_JOB_PRIORITY: &str = "ballista.job.priority";
+/// Prefix of the settings of the object stores of the tasks of a session
+pub const BALLISTA_OBJECT_STORE_PREFIX: &str = "ballista.object_store.";
+/// Maximum number of times a failed object store request is retried
+pub const BALLISTA_OBJECT_STORE_MAX_RETRIES: &str = "ballista.object_store.max_retries";
+/// Backoff in milliseconds before the first retry of a failed object store request
+pub const BALLISTA_OBJECT_STORE_RETRY_INIT_BACKOFF_MS: &str =
+    "ballista.object_store.retry_init_backoff_ms";
+/// Maximum backoff in milliseconds between retries of a failed object store request
+pub const BALLISTA_OBJECT_STORE_RETRY_MAX_BACKOFF_MS: &str =
+    "ballista.object_store.retry_max_backoff_ms";
+/// Time in seconds after which a failed object store request is no longer retried
+pub const BALLISTA_OBJECT_STORE_RETRY_TIMEOUT_SECS: &str =
+    "ballista.object_store.retry_timeout_secs";
+/// Whether the results of the queries of a session are served from the scheduler's result
+/// cache when an identical query over unchanged files completed recently
+pub const BALLISTA_CACHE_RESULTS: &str = "ballista.cache.results";
+/// Number of task failures in a stage after which the stage and its job fail, 0 means that
+/// only the retries of each task are limited
+pub const BALLISTA_STAGE_MAX_FAILURES: &str = "ballista.stage.max_failures";
+/// Maximum number of tasks of each stage of a job running at once, 0 for no limit
+pub const BALLISTA_STAGE_MAX_CONCURRENT_TASKS: &str =
+    "ballista.stage.max_concurrent_tasks";
+/// Whether the scheduler checks that the stages of a job read as many rows from the stages
+/// they depend on as these wrote, to catch exchange bugs
+pub const BALLISTA_SHUFFLE_VERIFY_ROWS: &str = "ballista.shuffle.verify_rows";
 
 pub type ParseResult<T> = result::Result<T, String>;
 
@@ -127,6 +202,18 @@ impl BallistaConfig {
                 )));
             }
         }
+        if let Some(labels) = settings.get(BALLISTA_JOB_LABELS) {
+            parse_key_values(labels).map_err(|e| BallistaError::General(format!("Failed to parse user-supplied value '{BALLISTA_JOB_LABELS}' for configuration setting '{labels}': {e}")))?;
+        }
+        // The value is not part of the error, as the options may hold credentials
+        if let Some(options) = settings.get(BALLISTA_JOB_EXPORT_OPTIONS) {
+            let options = parse_key_values(options).map_err(|_| BallistaError::General(format!("Failed to parse user-supplied value of configuration setting '{BALLISTA_JOB_EXPORT_OPTIONS}', expected comma separated key=value options")))?;
+            // Credentials would be stored with the settings of the session and sent with
+            // the plans, so they are only taken from export profiles
+            if let Some(key) = options.keys().find(|key| is_credential(key)) {
+                return Err(BallistaError::General(format!("Configuration setting '{BALLISTA_JOB_EXPORT_OPTIONS}' cannot hold the credential '{key}', use an export profile with '{BALLISTA_JOB_EXPORT_PROFILE}' instead")));
+            }
+        }
 
         Ok(Self { settings })
     }
@@ -138,6 +225,11 @@ impl BallistaConfig {
                     .parse::<usize>()
                     .map_err(|e| format!("{e:?}"))?;
             }
+            DataType::UInt64 => {
+                val.to_string()
+                    .parse::<u64>()
+                    .map_err(|e| format!("{e:?}"))?;
+            }
             DataType::Boolean => {
                 val.to_string()
                     .parse::<bool>()
@@ -160,6 +252,54 @@ impl BallistaConfig {
             ConfigEntry::new(BALLISTA_JOB_NAME.to_string(),
                              "Sets the job name that will appear in the web user interface for any submitted jobs".to_string(),
                              DataType::Utf8, None),
+            ConfigEntry::new(BALLISTA_JOB_LABELS.to_string(),
+                             "Sets the comma separated key=value labels attached to any submitted jobs, e.g. team=analytics,pipeline=daily, by which jobs can be listed".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_GROUP.to_string(),
+                             "Sets the ID of the group of any submitted jobs, e.g. the ID of a dashboard refresh, by which the jobs can be followed and cancelled together".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_POOL.to_string(),
+                             "Sets the pool of executors, e.g. etl or interactive, the tasks of any submitted jobs run on. Jobs without a pool run on the executors of the default pool".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_RUN_AS.to_string(),
+                             "Sets the user any submitted jobs run on behalf of, for service accounts allowed to impersonate their users. Empty to run jobs as the authenticated client".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_OWNER.to_string(),
+                             "Set by the scheduler to the user owning a submitted job, who is the only one allowed to read its results. Values set by clients are replaced".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_ID.to_string(),
+                             "Sets the ID the next job is submitted with, so that external systems can track the job before it is submitted. It has to be unique in the cluster, and should be set for a single query. Empty to let the scheduler generate it".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_IDEMPOTENT.to_string(),
+                             "Sets whether submitting a job with the ID of an existing job returns the existing job instead of failing, so that retried submissions do not run the query again".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_CHECKPOINT_DIR.to_string(),
+                             "Sets the directory shared by all executors, e.g. a network file system mount, where stage outputs are copied so that jobs resume from them when executors are lost".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_RESULT_BUFFER.to_string(),
+                             "Sets the number of result partitions waiting to be fetched by the client after which the tasks of the final stage of a job are held back until the client catches up. 0 means that results are not buffered".to_string(),
+                             DataType::UInt16, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_MAX_CONCURRENT_STAGES_PER_JOB.to_string(),
+                             "Sets the maximum number of stages of a job whose tasks are scheduled at once, so that jobs with many independent stages do not starve the other jobs. 0 means no limit".to_string(),
+                             DataType::UInt16, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_SINGLE_TASK_MAX_BYTES.to_string(),
+                             "Sets the estimated number of bytes scanned by a job up to which the whole job runs as a single task on one executor, without any shuffle, which cuts the latency of small queries. 0 means that jobs are always split into stages".to_string(),
+                             DataType::UInt16, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_SHARED_SCANS.to_string(),
+                             "Sets whether the stages of a job scanning files are shared with the identical stages of the other running jobs enabling it, which then read the output of a single set of scan tasks. Queries scanning files that change while they run should not enable it".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_RESULT_TTL_SECONDS.to_string(),
+                             "Sets the number of seconds the result partitions of a successful job are kept on the executors, after which they are deleted and the results of the job are reported as expired. 0 means the finished job data clean up interval of the scheduler applies".to_string(),
+                             DataType::UInt16, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_EXPORT_LOCATION.to_string(),
+                             "Sets the object store prefix, e.g. s3://bucket/exports, the executors write the result files of any submitted jobs to, in which case the query returns the manifest of the exported files instead of the results. Empty means that the client fetches the results".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_EXPORT_OPTIONS.to_string(),
+                             "Sets the comma separated key=value options of the object store of the export location, e.g. aws_region=us-east-1. Credentials are rejected, they come from the export profile".to_string(),
+                             DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_EXPORT_PROFILE.to_string(),
+                             "Sets the name of the export profile configured on the executors and schedulers which holds the credentials of the object store of the export location. Empty means no credentials are added to the export options".to_string(),
+                             DataType::Utf8, Some("".to_string())),
             ConfigEntry::new(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS.to_string(),
                              "Sets the default number of partitions to create when repartitioning query stages".to_string(),
                              DataType::UInt16, Some("16".to_string())),
@@ -184,6 +324,36 @@ impl BallistaConfig {
             ConfigEntry::new(BALLISTA_PLUGIN_DIR.to_string(),
                              "Sets the plugin dir".to_string(),
                              DataType::Utf8, Some("".to_string())),
+            ConfigEntry::new(BALLISTA_SESSION_MAX_TASK_SLOTS.to_string(),
+                             "Sets the maximum number of task slots the jobs of a session may use at once, 0 means unlimited".to_string(),
+                             DataType::UInt64, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_JOB_PRIORITY.to_string(),
+                             "Sets the priority of the jobs of a session, jobs with a higher priority are scheduled first and may preempt the tasks of jobs with a lower priority".to_string(),
+                             DataType::UInt16, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_OBJECT_STORE_MAX_RETRIES.to_string(),
+                             "Sets the maximum number of times a failed request to an object store, e.g. a throttled S3 request, is retried".to_string(),
+                             DataType::UInt16, Some("10".to_string())),
+            ConfigEntry::new(BALLISTA_OBJECT_STORE_RETRY_INIT_BACKOFF_MS.to_string(),
+                             "Sets the backoff in milliseconds before the first retry of a failed object store request, doubled on each further retry".to_string(),
+                             DataType::UInt64, Some("100".to_string())),
+            ConfigEntry::new(BALLISTA_OBJECT_STORE_RETRY_MAX_BACKOFF_MS.to_string(),
+                             "Sets the maximum backoff in milliseconds between retries of a failed object store request".to_string(),
+                             DataType::UInt64, Some("15000".to_string())),
+            ConfigEntry::new(BALLISTA_OBJECT_STORE_RETRY_TIMEOUT_SECS.to_string(),
+                             "Sets the time in seconds after which a failed object store request is no longer retried".to_string(),
+                             DataType::UInt64, Some("180".to_string())),
+            ConfigEntry::new(BALLISTA_CACHE_RESULTS.to_string(),
+                             "Sets whether the results of queries are served from the result cache of the scheduler if an identical query over the same files completed recently. Queries using volatile functions, e.g. random(), should not enable it".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
+            ConfigEntry::new(BALLISTA_STAGE_MAX_FAILURES.to_string(),
+                             "Sets the number of task failures in an attempt of a stage after which the stage and its job fail, even if the failed tasks could still be retried. 0 means that only the retries of each task are limited".to_string(),
+                             DataType::UInt16, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_STAGE_MAX_CONCURRENT_TASKS.to_string(),
+                             "Sets the maximum number of tasks of each stage of a job running at once whatever the number of available task slots, e.g. to bound the connections to an external system. Stages annotated with a ConcurrencyLimitExec keep their limit if it is lower. 0 means no limit".to_string(),
+                             DataType::UInt16, Some("0".to_string())),
+            ConfigEntry::new(BALLISTA_SHUFFLE_VERIFY_ROWS.to_string(),
+                             "Sets whether the scheduler fails the jobs whose stages read another number of rows from the stages they depend on than these wrote. Meant for verifying changes to the exchange of data between stages".to_string(),
+                             DataType::Boolean, Some("false".to_string())),
         ];
         entries
             .iter()
@@ -227,6 +397,152 @@ impl BallistaConfig {
         self.get_bool_setting(BALLISTA_WITH_INFORMATION_SCHEMA)
     }
 
+    pub fn session_max_task_slots(&self) -> usize {
+        self.get_usize_setting(BALLISTA_SESSION_MAX_TASK_SLOTS)
+    }
+
+    pub fn job_priority(&self) -> usize {
+        self.get_usize_setting(BALLISTA_JOB_PRIORITY)
+    }
+
+    pub fn cache_results(&self) -> bool {
+        self.get_bool_setting(BALLISTA_CACHE_RESULTS)
+    }
+
+    pub fn stage_max_failures(&self) -> usize {
+        self.get_usize_setting(BALLISTA_STAGE_MAX_FAILURES)
+    }
+
+    /// Maximum number of tasks of each stage of the jobs of the session running at once, 0
+    /// for no limit
+    pub fn stage_max_concurrent_tasks(&self) -> usize {
+        self.get_usize_setting(BALLISTA_STAGE_MAX_CONCURRENT_TASKS)
+    }
+
+    pub fn shuffle_verify_rows(&self) -> bool {
+        self.get_bool_setting(BALLISTA_SHUFFLE_VERIFY_ROWS)
+    }
+
+    /// Labels attached to the jobs of the session
+    pub fn job_labels(&self) -> HashMap<String, String> {
+        // infallible because we validate all configs in the constructor
+        parse_key_values(&self.get_string_setting(BALLISTA_JOB_LABELS)).unwrap()
+    }
+
+    /// ID of the group of the jobs of the session, empty if they belong to no group
+    pub fn job_group(&self) -> String {
+        self.get_string_setting(BALLISTA_JOB_GROUP)
+    }
+
+    /// Pool of executors the jobs of the session run on, empty for the default pool
+    pub fn job_pool(&self) -> String {
+        self.get_string_setting(BALLISTA_JOB_POOL)
+    }
+
+    /// User the jobs of the session run on behalf of, empty to run them as the
+    /// authenticated client
+    pub fn job_run_as(&self) -> String {
+        self.get_string_setting(BALLISTA_JOB_RUN_AS)
+    }
+
+    /// ID the jobs of the session are submitted with, `None` to let the scheduler
+    /// generate it
+    pub fn job_id(&self) -> Option<String> {
+        Some(self.get_string_setting(BALLISTA_JOB_ID)).filter(|job_id| !job_id.is_empty())
+    }
+
+    /// Whether submitting a job with the ID of an existing job returns the existing job
+    pub fn job_idempotent(&self) -> bool {
+        self.get_bool_setting(BALLISTA_JOB_IDEMPOTENT)
+    }
+
+    /// Directory shared by the executors the outputs of the stages of the jobs of the
+    /// session are copied to, empty if they are not checkpointed
+    pub fn job_checkpoint_dir(&self) -> String {
+        self.get_string_setting(BALLISTA_JOB_CHECKPOINT_DIR)
+    }
+
+    /// Number of result partitions of the jobs of the session waiting to be fetched after
+    /// which their final stages are paused, 0 if results are not buffered
+    pub fn job_result_buffer(&self) -> usize {
+        self.get_usize_setting(BALLISTA_JOB_RESULT_BUFFER)
+    }
+
+    /// Maximum number of stages of the jobs of the session running at once, 0 for no limit
+    pub fn max_concurrent_stages_per_job(&self) -> usize {
+        self.get_usize_setting(BALLISTA_MAX_CONCURRENT_STAGES_PER_JOB)
+    }
+
+    /// Estimated number of bytes scanned by the jobs of the session up to which they run as
+    /// a single task, 0 if jobs are always split into stages
+    pub fn job_single_task_max_bytes(&self) -> usize {
+        self.get_usize_setting(BALLISTA_JOB_SINGLE_TASK_MAX_BYTES)
+    }
+
+    pub fn job_shared_scans(&self) -> bool {
+        self.get_bool_setting(BALLISTA_JOB_SHARED_SCANS)
+    }
+
+    pub fn job_result_ttl_seconds(&self) -> usize {
+        self.get_usize_setting(BALLISTA_JOB_RESULT_TTL_SECONDS)
+    }
+
+    /// Object store prefix the results of the jobs of the session are exported to, empty
+    /// if their clients fetch them
+    pub fn job_export_location(&self) -> String {
+        self.get_string_setting(BALLISTA_JOB_EXPORT_LOCATION)
+    }
+
+    /// Options of the object store of the export location, without credentials
+    pub fn job_export_options(&self) -> HashMap<String, String> {
+        // infallible because we validate all configs in the constructor
+        parse_key_values(&self.get_string_setting(BALLISTA_JOB_EXPORT_OPTIONS)).unwrap()
+    }
+
+    /// Name of the export profile holding the credentials of the export location
+    pub fn job_export_profile(&self) -> String {
+        self.get_string_setting(BALLISTA_JOB_EXPORT_PROFILE)
+    }
+
+    /// Retry policy for requests to object stores
+    pub fn object_store_retry(&self) -> ObjectStoreRetryConfig {
+        self.object_store_retry_or(&ObjectStoreRetryConfig::default())
+    }
+
+    /// Retry policy for requests to object stores, with the values of `defaults` for the
+    /// settings which are not set, e.g. the retry policy of an executor
+    pub fn object_store_retry_or(
+        &self,
+        defaults: &ObjectStoreRetryConfig,
+    ) -> ObjectStoreRetryConfig {
+        // infallible because we validate all configs in the constructor
+        let setting = |key: &str| -> Option<u64> {
+            self.settings.get(key).map(|v| v.parse().unwrap())
+        };
+        ObjectStoreRetryConfig {
+            max_retries: setting(BALLISTA_OBJECT_STORE_MAX_RETRIES)
+                .map(|retries| retries as usize)
+                .unwrap_or(defaults.max_retries),
+            init_backoff: setting(BALLISTA_OBJECT_STORE_RETRY_INIT_BACKOFF_MS)
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.init_backoff),
+            max_backoff: setting(BALLISTA_OBJECT_STORE_RETRY_MAX_BACKOFF_MS)
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.max_backoff),
+            retry_timeout: setting(BALLISTA_OBJECT_STORE_RETRY_TIMEOUT_SECS)
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.retry_timeout),
+        }
+    }
+
+    /// Settings applied by the executors to the tasks of the jobs of this session, which are
+    /// sent with the tasks
+    pub fn task_settings(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.settings
+            .iter()
+            .filter(|(key, _)| key.starts_with(BALLISTA_OBJECT_STORE_PREFIX))
+    }
+
     fn get_usize_setting(&self, key: &str) -> usize {
         if let Some(v) = self.settings.get(key) {
             // infallible because we validate all configs in the constructor
@@ -263,6 +579,39 @@ impl BallistaConfig {
     }
 }
 
+/// Parse comma separated `key=value` pairs, such as job labels, ignoring whitespace around
+/// keys and values
+pub fn parse_key_values(pairs: &str) -> ParseResult<HashMap<String, String>> {
+    pairs
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_owned(), value.trim().to_owned()))
+            }
+            _ => Err(format!("{pair:?} is not of the form key=value")),
+        })
+        .collect()
+}
+
+/// Whether the option of an object store holds a credential, such as
+/// `aws_secret_access_key` or `google_service_account`
+fn is_credential(key: &str) -> bool {
+    let key = key.to_lowercase();
+    [
+        "secret",
+        "token",
+        "password",
+        "credential",
+        "access_key",
+        "account_key",
+        "sas",
+        "service_account",
+    ]
+    .iter()
+    .any(|credential| key.contains(credential))
+}
+
 // an enum used to configure the scheduler policy
 // needs to be visible to code generated by configure_me
 #[derive(Clone, ArgEnum, Copy, Debug, serde::Deserialize)]
@@ -285,6 +634,98 @@ impl parse_arg::ParseArgFromStr for TaskSchedulingPolicy {
     }
 }
 
+// an enum used to configure the compression of the gRPC messages between schedulers and
+// executors, needs to be visible to code generated by configure_me
+#[derive(Clone, ArgEnum, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum GrpcCompression {
+    None,
+    Gzip,
+}
+
+impl GrpcCompression {
+    /// Encoding to compress sent messages with, None if they are sent uncompressed
+    pub fn encoding(&self) -> Option<CompressionEncoding> {
+        match self {
+            GrpcCompression::None => None,
+            GrpcCompression::Gzip => Some(CompressionEncoding::Gzip),
+        }
+    }
+}
+
+impl std::str::FromStr for GrpcCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ArgEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for GrpcCompression {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(writer, "The compression of gRPC messages")
+    }
+}
+
+// an enum used to configure how an executor with several work dirs chooses the one each task
+// writes to, needs to be visible to code generated by configure_me
+#[derive(Clone, ArgEnum, Copy, Debug, PartialEq, Eq, serde::Deserialize)]
+pub enum WorkDirSelection {
+    /// Each task writes to the next work dir in turn
+    RoundRobin,
+    /// Each task writes to the work dir with the most free space
+    FreeSpace,
+}
+
+impl std::str::FromStr for WorkDirSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ArgEnum::from_str(s, true)
+    }
+}
+
+impl parse_arg::ParseArgFromStr for WorkDirSelection {
+    fn describe_type<W: fmt::Write>(mut writer: W) -> fmt::Result {
+        write!(writer, "The selection of the work dir of each task")
+    }
+}
+
+/// Retry policy for failed requests to object stores, such as requests throttled by S3.
+/// Backoff between retries doubles from `init_backoff` up to `max_backoff`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ObjectStoreRetryConfig {
+    pub max_retries: usize,
+    pub init_backoff: Duration,
+    pub max_backoff: Duration,
+    pub retry_timeout: Duration,
+}
+
+impl Default for ObjectStoreRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 10,
+            init_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(15),
+            retry_timeout: Duration::from_secs(180),
+        }
+    }
+}
+
+#[cfg(any(feature = "s3", feature = "azure"))]
+impl From<&ObjectStoreRetryConfig> for object_store::RetryConfig {
+    fn from(config: &ObjectStoreRetryConfig) -> Self {
+        object_store::RetryConfig {
+            backoff: object_store::BackoffConfig {
+                init_backoff: config.init_backoff,
+                max_backoff: config.max_backoff,
+                base: 2.0,
+            },
+            max_retries: config.max_retries,
+            retry_timeout: config.retry_timeout,
+        }
+    }
+}
+
 // an enum used to configure the log rolling policy
 // needs to be visible to code generated by configure_me
 #[derive(Clone, ArgEnum, Copy, Debug, serde::Deserialize)]
@@ -319,6 +760,17 @@ mod tests {
         assert_eq!(16, config.default_shuffle_partitions());
         assert!(!config.default_with_information_schema());
         assert_eq!("", config.default_plugin_dir().as_str());
+        assert_eq!(0, config.session_max_task_slots());
+        assert_eq!(0, config.job_priority());
+        assert!(!config.cache_results());
+        assert!(config.job_labels().is_empty());
+        assert_eq!("", config.job_group());
+        assert_eq!(None, config.job_id());
+        assert!(!config.job_idempotent());
+        assert_eq!(
+            ObjectStoreRetryConfig::default(),
+            config.object_store_retry()
+        );
         Ok(())
     }
 
@@ -327,9 +779,56 @@ mod tests {
         let config = BallistaConfig::builder()
             .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "123")
             .set(BALLISTA_WITH_INFORMATION_SCHEMA, "true")
+            .set(BALLISTA_SESSION_MAX_TASK_SLOTS, "100000")
+            .set(BALLISTA_JOB_PRIORITY, "2")
+            .set(BALLISTA_OBJECT_STORE_MAX_RETRIES, "3")
+            .set(BALLISTA_OBJECT_STORE_RETRY_MAX_BACKOFF_MS, "2000")
+            .set(BALLISTA_CACHE_RESULTS, "true")
+            .set(BALLISTA_JOB_LABELS, "team=analytics, pipeline = daily,")
+            .set(BALLISTA_JOB_GROUP, "refresh-42")
+            .set(BALLISTA_JOB_POOL, "interactive")
+            .set(BALLISTA_JOB_ID, "nightly-2023-06-01")
+            .set(BALLISTA_JOB_IDEMPOTENT, "true")
             .build()?;
         assert_eq!(123, config.default_shuffle_partitions());
         assert!(config.default_with_information_schema());
+        assert_eq!(100_000, config.session_max_task_slots());
+        assert_eq!(2, config.job_priority());
+        let retry = config.object_store_retry();
+        assert_eq!(3, retry.max_retries);
+        assert_eq!(Duration::from_millis(100), retry.init_backoff);
+        assert_eq!(Duration::from_secs(2), retry.max_backoff);
+        let executor_retry = ObjectStoreRetryConfig {
+            max_retries: 5,
+            init_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(60),
+            retry_timeout: Duration::from_secs(600),
+        };
+        let retry = config.object_store_retry_or(&executor_retry);
+        assert_eq!(3, retry.max_retries);
+        assert_eq!(Duration::from_millis(50), retry.init_backoff);
+        assert_eq!(Duration::from_secs(2), retry.max_backoff);
+        assert_eq!(Duration::from_secs(600), retry.retry_timeout);
+        let mut task_settings: Vec<_> = config
+            .task_settings()
+            .map(|(key, _)| key.as_str())
+            .collect();
+        task_settings.sort_unstable();
+        assert_eq!(
+            vec![
+                BALLISTA_OBJECT_STORE_MAX_RETRIES,
+                BALLISTA_OBJECT_STORE_RETRY_MAX_BACKOFF_MS
+            ],
+            task_settings
+        );
+        assert!(config.cache_results());
+        let labels = config.job_labels();
+        assert_eq!(2, labels.len());
+        assert_eq!("analytics", labels["team"]);
+        assert_eq!("daily", labels["pipeline"]);
+        assert_eq!("interactive", config.job_pool());
+        assert_eq!(Some("nightly-2023-06-01".to_owned()), config.job_id());
+        assert!(config.job_idempotent());
         Ok(())
     }
 
@@ -347,6 +846,31 @@ mod tests {
             .build();
         assert!(config.is_err());
         assert_eq!("General(\"Failed to parse user-supplied value 'ballista.with_information_schema' for configuration setting '123': ParseBoolError\")", format!("{:?}", config.unwrap_err()));
+
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_JOB_LABELS, "team=analytics,daily")
+            .build();
+        assert!(config.is_err());
+
+        // The invalid options are not echoed, as they may hold credentials
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_JOB_EXPORT_OPTIONS, "aws_secret_access_key")
+            .build();
+        assert!(!format!("{:?}", config.unwrap_err()).contains("aws_secret_access_key"));
+
+        // Credentials are only taken from export profiles
+        let config = BallistaConfig::builder()
+            .set(
+                BALLISTA_JOB_EXPORT_OPTIONS,
+                "aws_region=eu-west-1,aws_secret_access_key=hidden",
+            )
+            .build();
+        assert!(!format!("{:?}", config.unwrap_err()).contains("hidden"));
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_JOB_EXPORT_OPTIONS, "aws_region=eu-west-1")
+            .set(BALLISTA_JOB_EXPORT_PROFILE, "analysts")
+            .build()?;
+        assert_eq!(config.job_export_profile(), "analysts");
         Ok(())
     }
 }
@@ -24,7 +24,9 @@ use std::{
 };
 
 use crate::serde::protobuf::failed_task::FailedReason;
-use crate::serde::protobuf::{ExecutionError, FailedTask, FetchPartitionError, IoError};
+use crate::serde::protobuf::{
+    ExecutionError, FailedTask, FetchPartitionError, IoError, TaskKilled,
+};
 use datafusion::arrow::error::ArrowError;
 use datafusion::error::DataFusionError;
 use futures::future::Aborted;
@@ -266,6 +268,14 @@ impl From<BallistaError> for FailedTask {
                     failed_reason: Some(FailedReason::IoError(IoError {})),
                 }
             }
+            BallistaError::Cancelled => FailedTask {
+                error: "Task was killed".to_string(),
+                // killed tasks are either cancelled with their job or preempted and
+                // re-queued by the scheduler, so they are never retried here
+                retryable: false,
+                count_to_failures: false,
+                failed_reason: Some(FailedReason::TaskKilled(TaskKilled {})),
+            },
             other => FailedTask {
                 error: format!("Task failed due to runtime execution error: {other:?}"),
                 retryable: false,
@@ -133,6 +133,11 @@ impl<E> EventSender<E> {
         Self { tx_event }
     }
 
+    /// Number of events posted which are not yet received by the event loop
+    pub fn queued_events(&self) -> usize {
+        self.tx_event.max_capacity() - self.tx_event.capacity()
+    }
+
     pub async fn post_event(&self, event: E) -> Result<()> {
         self.tx_event
             .send(event)
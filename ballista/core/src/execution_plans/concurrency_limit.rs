@@ -0,0 +1,129 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Annotation of the stages whose tasks run with a bounded parallelism.
+
+use std::any::Any;
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_plan::expressions::PhysicalSortExpr;
+use datafusion::physical_plan::{
+    DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
+};
+
+/// ConcurrencyLimitExec limits the number of tasks of the query stage containing it which run
+/// at once, whatever the number of available task slots, e.g. for stages reading from an
+/// external system which cannot serve many connections.
+///
+/// The scheduler records the limit of each stage and removes this node from the stage before
+/// its tasks are scheduled. Executed locally, it passes the batches of its input through.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimitExec {
+    input: Arc<dyn ExecutionPlan>,
+    max_concurrent_tasks: usize,
+}
+
+impl ConcurrencyLimitExec {
+    /// Create a new ConcurrencyLimitExec running at most `max_concurrent_tasks` tasks of the
+    /// stage of `input` at once
+    pub fn new(input: Arc<dyn ExecutionPlan>, max_concurrent_tasks: usize) -> Self {
+        Self {
+            input,
+            max_concurrent_tasks,
+        }
+    }
+
+    pub fn input(&self) -> &Arc<dyn ExecutionPlan> {
+        &self.input
+    }
+
+    pub fn max_concurrent_tasks(&self) -> usize {
+        self.max_concurrent_tasks
+    }
+}
+
+impl ExecutionPlan for ConcurrencyLimitExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        mut children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if children.len() != 1 {
+            return Err(DataFusionError::Plan(
+                "Ballista ConcurrencyLimitExec expects a single child".to_owned(),
+            ));
+        }
+        Ok(Arc::new(Self::new(
+            children.remove(0),
+            self.max_concurrent_tasks,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(
+                    f,
+                    "ConcurrencyLimitExec: max_concurrent_tasks={}",
+                    self.max_concurrent_tasks
+                )
+            }
+        }
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+}
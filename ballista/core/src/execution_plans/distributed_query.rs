@@ -17,13 +17,15 @@
 
 use crate::client::BallistaClient;
 use crate::config::BallistaConfig;
-use crate::serde::protobuf::execute_query_params::OptionalSessionId;
+use crate::execution_plans::{export_manifest_schema, ResultExport};
+use crate::serde::protobuf::execute_query_params::{OptionalJobId, OptionalSessionId};
 use crate::serde::protobuf::{
     execute_query_params::Query, job_status, scheduler_grpc_client::SchedulerGrpcClient,
     ExecuteQueryParams, GetJobStatusParams, GetJobStatusResult, KeyValuePair,
     PartitionLocation,
 };
 use crate::utils::create_grpc_client_connection;
+use datafusion::arrow::array::StringArray;
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::record_batch::RecordBatch;
@@ -38,13 +40,18 @@ use datafusion::physical_plan::{
 use datafusion_proto::logical_plan::{
     AsLogicalPlan, DefaultLogicalExtensionCodec, LogicalExtensionCodec,
 };
+use futures::stream::BoxStream;
 use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
 use log::{error, info};
 use std::any::Any;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Channel;
 
 /// This operator sends a logical plan to a Ballista scheduler for execution and
 /// polls the scheduler until the query is complete and then fetches the resulting
@@ -125,7 +132,12 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
     }
 
     fn schema(&self) -> SchemaRef {
-        self.plan.schema().as_ref().clone().into()
+        // A query exporting its results returns the manifest of the exported files
+        if ResultExport::from_config(&self.config).is_some() {
+            export_manifest_schema()
+        } else {
+            self.plan.schema().as_ref().clone().into()
+        }
     }
 
     fn output_partitioning(&self) -> Partitioning {
@@ -187,15 +199,26 @@ impl<T: 'static + AsLogicalPlan> ExecutionPlan for DistributedQueryExec<T> {
             optional_session_id: Some(OptionalSessionId::SessionId(
                 self.session_id.clone(),
             )),
+            optional_job_id: self.config.job_id().map(OptionalJobId::JobId),
+            job_settings: vec![],
+            idempotent: self.config.job_idempotent(),
+            run_as: self.config.job_run_as(),
+            reports_consumed_partitions: true,
         };
 
+        let schema = self.schema();
         let stream = futures::stream::once(
-            execute_query(self.scheduler_url.clone(), self.session_id.clone(), query)
-                .map_err(|e| ArrowError::ExternalError(Box::new(e))),
+            execute_query(
+                self.scheduler_url.clone(),
+                self.session_id.clone(),
+                query,
+                schema.clone(),
+                ResultExport::from_config(&self.config),
+            )
+            .map_err(|e| ArrowError::ExternalError(Box::new(e))),
         )
         .try_flatten();
 
-        let schema = self.schema();
         Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
     }
 
@@ -227,7 +250,9 @@ async fn execute_query(
     scheduler_url: String,
     session_id: String,
     query: ExecuteQueryParams,
-) -> Result<impl Stream<Item = Result<RecordBatch>> + Send> {
+    schema: SchemaRef,
+    result_export: Option<ResultExport>,
+) -> Result<BoxStream<'static, Result<RecordBatch>>> {
     info!("Connecting to Ballista scheduler at {}", scheduler_url);
     // TODO reuse the scheduler to avoid connecting to the Ballista scheduler again and again
     let connection = create_grpc_client_connection(scheduler_url)
@@ -254,6 +279,7 @@ async fn execute_query(
         let GetJobStatusResult { status } = scheduler
             .get_job_status(GetJobStatusParams {
                 job_id: job_id.clone(),
+                consumed_partitions: vec![],
             })
             .await
             .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
@@ -276,6 +302,19 @@ async fn execute_query(
                 wait_future.await;
                 prev_status = status;
             }
+            // A job buffering its results publishes them while running, they are fetched
+            // as they are produced so that the job is not held back
+            Some(job_status::Status::Running(running))
+                if !running.partition_location.is_empty() =>
+            {
+                info!("Job {} is running, fetching its results...", job_id);
+                break Ok(buffered_results(
+                    scheduler,
+                    job_id,
+                    running.partition_location,
+                )
+                .boxed());
+            }
             Some(job_status::Status::Running(_)) => {
                 if has_status_change {
                     info!("Job {} is running...", job_id);
@@ -288,7 +327,44 @@ async fn execute_query(
                 error!("{}", msg);
                 break Err(DataFusionError::Execution(msg));
             }
+            Some(job_status::Status::Cancelled(_)) => {
+                let msg = format!("Job {job_id} was cancelled");
+                error!("{}", msg);
+                break Err(DataFusionError::Execution(msg));
+            }
+            Some(job_status::Status::Successful(successful))
+                if successful.results_expired =>
+            {
+                let msg = format!("Results of job {job_id} expired");
+                error!("{}", msg);
+                break Err(DataFusionError::Execution(msg));
+            }
             Some(job_status::Status::Successful(successful)) => {
+                // EXPLAIN ANALYZE returns the plan annotated with the metrics of its stages
+                // instead of the results of the query
+                if !successful.analyzed_plan.is_empty() {
+                    let batch = RecordBatch::try_new(
+                        schema,
+                        vec![
+                            Arc::new(StringArray::from(vec!["Plan with Metrics"])),
+                            Arc::new(StringArray::from(vec![successful.analyzed_plan])),
+                        ],
+                    )?;
+                    break Ok(futures::stream::iter(vec![Ok(batch)]).boxed());
+                }
+
+                // The results were written to the export location by the executors
+                if let Some(result_export) = &result_export {
+                    info!(
+                        "Job {} exported its results to {}",
+                        job_id,
+                        result_export.location()
+                    );
+                    let manifest = result_export
+                        .manifest(&job_id, &successful.partition_location)?;
+                    break Ok(futures::stream::iter(vec![Ok(manifest)]).boxed());
+                }
+
                 let streams = successful.partition_location.into_iter().map(|p| {
                     let f = fetch_partition(p)
                         .map_err(|e| ArrowError::ExternalError(Box::new(e)));
@@ -296,8 +372,107 @@ async fn execute_query(
                     futures::stream::once(f).try_flatten()
                 });
 
-                break Ok(futures::stream::iter(streams).flatten());
+                break Ok(futures::stream::iter(streams).flatten().boxed());
+            }
+        };
+    }
+}
+
+/// Stream the results of a job buffering them, fetching them as the final stage produces
+/// them. The results are fetched as fast as they are consumed, and the partitions fetched
+/// are reported to the scheduler which holds back the final stage until they are.
+fn buffered_results(
+    mut scheduler: SchedulerGrpcClient<Channel>,
+    job_id: String,
+    partition_location: Vec<PartitionLocation>,
+) -> impl Stream<Item = Result<RecordBatch>> + Send {
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        if let Err(e) =
+            forward_buffered_results(&mut scheduler, &job_id, partition_location, &tx)
+                .await
+        {
+            let _ = tx.send(Err(e)).await;
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+async fn forward_buffered_results(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    job_id: &str,
+    mut partition_location: Vec<PartitionLocation>,
+    tx: &mpsc::Sender<Result<RecordBatch>>,
+) -> Result<()> {
+    // Output partitions already fetched by map partition of the final stage
+    let mut fetched: HashSet<(u32, u32)> = HashSet::new();
+    let mut consumed_partitions: HashSet<u32> = HashSet::new();
+    let mut job_successful = false;
+
+    loop {
+        let mut new_partitions = false;
+        for location in partition_location {
+            let map_partition_id = location.map_partition_id;
+            let partition_id = location
+                .partition_id
+                .as_ref()
+                .map(|id| id.partition_id)
+                .unwrap_or_default();
+            if !fetched.insert((map_partition_id, partition_id)) {
+                continue;
+            }
+            new_partitions = true;
+
+            let mut stream = fetch_partition(location).await?;
+            while let Some(batch) = stream.next().await {
+                // Blocks while the consumer is behind
+                if tx.send(Ok(batch?)).await.is_err() {
+                    info!("Results of job {} no longer consumed", job_id);
+                    return Ok(());
+                }
+            }
+            consumed_partitions.insert(map_partition_id);
+        }
+
+        if job_successful {
+            return Ok(());
+        }
+        if !new_partitions {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let GetJobStatusResult { status } = scheduler
+            .get_job_status(GetJobStatusParams {
+                job_id: job_id.to_owned(),
+                consumed_partitions: consumed_partitions.iter().copied().collect(),
+            })
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?
+            .into_inner();
+        partition_location = match status.and_then(|s| s.status) {
+            Some(job_status::Status::Running(running)) => running.partition_location,
+            Some(job_status::Status::Successful(successful))
+                if successful.results_expired =>
+            {
+                let msg = format!("Results of job {job_id} expired");
+                error!("{}", msg);
+                return Err(DataFusionError::Execution(msg));
+            }
+            Some(job_status::Status::Successful(successful)) => {
+                job_successful = true;
+                successful.partition_location
+            }
+            Some(job_status::Status::Failed(err)) => {
+                let msg = format!("Job {} failed: {}", job_id, err.error);
+                error!("{}", msg);
+                return Err(DataFusionError::Execution(msg));
+            }
+            Some(job_status::Status::Cancelled(_)) => {
+                let msg = format!("Job {job_id} was cancelled");
+                error!("{}", msg);
+                return Err(DataFusionError::Execution(msg));
             }
+            Some(job_status::Status::Queued(_)) | None => vec![],
         };
     }
 }
@@ -323,6 +498,7 @@ async fn fetch_partition(
             &location.path,
             host,
             port,
+            location.checksum.as_ref().map(|checksum| checksum.crc32),
         )
         .await
         .map_err(|e| DataFusionError::External(Box::new(e)))
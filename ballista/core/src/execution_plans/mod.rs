@@ -18,12 +18,27 @@
 //! This module contains execution plans that are needed to distribute DataFusion's execution plans into
 //! several Ballista executors.
 
+mod concurrency_limit;
 mod distributed_query;
+mod gpu_requirement;
+mod result_export;
+mod shuffle_codec;
+mod shuffle_partitioner;
 mod shuffle_reader;
 mod shuffle_writer;
 mod unresolved_shuffle;
 
+pub use concurrency_limit::ConcurrencyLimitExec;
 pub use distributed_query::DistributedQueryExec;
-pub use shuffle_reader::ShuffleReaderExec;
+pub use gpu_requirement::GpuRequirementExec;
+pub use result_export::{export_manifest_schema, ExportConfig, ResultExport};
+pub use shuffle_codec::{
+    with_unique_dict_ids, IpcStreamFileWriter, RowFileReader, RowFileWriter,
+    ShuffleCodec, ShuffleFileReader, ShuffleFileWriter,
+};
+pub use shuffle_partitioner::HashRepartitioner;
+pub use shuffle_reader::{
+    ShuffleReaderExec, SHUFFLE_PARTITIONS_READ_METRIC, SHUFFLE_ROWS_READ_METRIC,
+};
 pub use shuffle_writer::ShuffleWriterExec;
 pub use unresolved_shuffle::UnresolvedShuffleExec;
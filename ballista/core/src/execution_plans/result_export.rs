@@ -0,0 +1,513 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Export of the results of a job to an object store prefix given by its client, such as an
+//! S3 or GCS prefix, so that large results flow through neither the scheduler nor the client
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, PathBuf};
+use std::sync::Arc;
+
+use datafusion::arrow::array::{StringArray, UInt32Array, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::error::{DataFusionError, Result};
+use futures::TryStreamExt;
+use log::debug;
+#[cfg(feature = "s3")]
+use object_store::aws::AmazonS3Builder;
+#[cfg(feature = "azure")]
+use object_store::azure::MicrosoftAzureBuilder;
+#[cfg(feature = "gcs")]
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::ObjectStore;
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+use crate::config::BallistaConfig;
+use crate::execution_plans::ShuffleCodec;
+use crate::serde::protobuf::PartitionLocation;
+
+/// Configuration of the executors and schedulers for the exports of results: the named
+/// profiles holding the credentials of the object stores, so that credentials are neither
+/// stored with the session settings nor sent with the plans, and the local directory the
+/// results may be exported to with `file://` URLs, which are rejected without one.
+#[derive(Clone, Default)]
+pub struct ExportConfig {
+    profiles: HashMap<String, HashMap<String, String>>,
+    local_root: Option<PathBuf>,
+}
+
+impl fmt::Debug for ExportConfig {
+    // Only the names of the profiles are shown, as their options hold credentials
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut profiles: Vec<_> = self.profiles.keys().collect();
+        profiles.sort();
+        f.debug_struct("ExportConfig")
+            .field("profiles", &profiles)
+            .field("local_root", &self.local_root)
+            .finish()
+    }
+}
+
+impl ExportConfig {
+    /// Add a profile with the options of the object stores, e.g. `aws_secret_access_key`
+    pub fn with_profile(
+        mut self,
+        name: impl Into<String>,
+        options: HashMap<String, String>,
+    ) -> Self {
+        self.profiles.insert(name.into(), options);
+        self
+    }
+
+    /// Allow exports with `file://` URLs below `local_root`
+    pub fn with_local_root(mut self, local_root: impl Into<PathBuf>) -> Self {
+        self.local_root = Some(local_root.into());
+        self
+    }
+
+    /// Add the profiles of a file with one `profile.option=value` line per option, where
+    /// lines starting with `#` are comments
+    pub fn with_profiles_file(
+        mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let entry = line.split_once('=').and_then(|(key, value)| {
+                let (profile, option) = key.trim().split_once('.')?;
+                Some((
+                    profile.to_owned(),
+                    option.to_owned(),
+                    value.trim().to_owned(),
+                ))
+            });
+            let (profile, option, value) = entry.ok_or_else(|| {
+                DataFusionError::Configuration(format!(
+                    "Line {} of export profiles file {} is not profile.option=value",
+                    number + 1,
+                    path.display()
+                ))
+            })?;
+            self.profiles
+                .entry(profile)
+                .or_default()
+                .insert(option, value);
+        }
+        Ok(self)
+    }
+
+    pub fn local_root(&self) -> Option<&std::path::Path> {
+        self.local_root.as_deref()
+    }
+
+    /// The options of the profile, the empty profile having none
+    fn profile(&self, name: &str) -> Result<HashMap<String, String>> {
+        if name.is_empty() {
+            return Ok(HashMap::new());
+        }
+        self.profiles.get(name).cloned().ok_or_else(|| {
+            DataFusionError::Execution(format!("Unknown export profile {name}"))
+        })
+    }
+
+    /// Whether `url` is a `file://` URL below the local root
+    fn allows_local(&self, url: &Url) -> bool {
+        let root = match &self.local_root {
+            Some(root) => root,
+            None => return false,
+        };
+        match url.to_file_path() {
+            Ok(path) => {
+                !path.components().any(|c| matches!(c, Component::ParentDir))
+                    && path.starts_with(root)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Object store prefix the result files of a job are exported to, with the options of the
+/// object store and the name of the [ExportConfig] profile holding its credentials. Only
+/// the credentials of the profile are used, never the ones of the executors.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ResultExport {
+    /// URL of the prefix, e.g. `s3://bucket/exports`
+    location: String,
+    options: HashMap<String, String>,
+    /// Name of the profile, empty for none
+    profile: String,
+}
+
+impl fmt::Debug for ResultExport {
+    // The values of the options are left out, as they may hold credentials
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut options: Vec<_> = self.options.keys().collect();
+        options.sort();
+        f.debug_struct("ResultExport")
+            .field("location", &self.location)
+            .field("options", &options)
+            .field("profile", &self.profile)
+            .finish()
+    }
+}
+
+impl ResultExport {
+    pub fn new(location: impl Into<String>, options: HashMap<String, String>) -> Self {
+        Self {
+            location: location.into(),
+            options,
+            profile: String::new(),
+        }
+    }
+
+    /// Take the credentials from the profile of the [ExportConfig]
+    pub fn with_profile(mut self, profile: impl Into<String>) -> Self {
+        self.profile = profile.into();
+        self
+    }
+
+    /// The export of the results of the jobs with the configuration, none if their clients
+    /// fetch them
+    pub fn from_config(config: &BallistaConfig) -> Option<Self> {
+        let location = config.job_export_location();
+        (!location.is_empty()).then(|| {
+            Self::new(location, config.job_export_options())
+                .with_profile(config.job_export_profile())
+        })
+    }
+
+    pub fn location(&self) -> &str {
+        &self.location
+    }
+
+    pub fn options(&self) -> &HashMap<String, String> {
+        &self.options
+    }
+
+    pub fn profile(&self) -> &str {
+        &self.profile
+    }
+
+    /// URL of the file the result partition of a job, written to `local_path` by the
+    /// executor, is exported to
+    pub fn partition_url(
+        &self,
+        job_id: &str,
+        partition_id: usize,
+        local_path: &str,
+    ) -> String {
+        let codec = ShuffleCodec::from_path(std::path::Path::new(local_path));
+        format!(
+            "{}/{job_id}/part-{partition_id}.{}",
+            self.location.trim_end_matches('/'),
+            codec.file_extension()
+        )
+    }
+
+    /// Upload a result file to `url`, streaming it instead of reading it in memory. The file
+    /// only becomes visible at `url` once it is completely uploaded.
+    pub async fn export_file(
+        &self,
+        local_path: &str,
+        url: &str,
+        config: &ExportConfig,
+    ) -> Result<()> {
+        let url = Url::parse(url).map_err(|e| {
+            DataFusionError::Execution(format!("Invalid export URL {url}: {e}"))
+        })?;
+        let store = self.object_store(&url, config)?;
+        let path = Path::from_url_path(url.path()).map_err(|e| {
+            DataFusionError::Execution(format!("Invalid export URL {url}: {e}"))
+        })?;
+        let (multipart_id, mut writer) = store.put_multipart(&path).await?;
+        let upload = async {
+            let mut file = tokio::fs::File::open(local_path).await?;
+            tokio::io::copy(&mut file, &mut writer).await?;
+            writer.shutdown().await
+        };
+        if let Err(e) = upload.await {
+            // Do not leave the parts uploaded so far behind
+            let _ = store.abort_multipart(&path, &multipart_id).await;
+            return Err(DataFusionError::Execution(format!(
+                "Failed to export {local_path} to {url}: {e}"
+            )));
+        }
+        debug!("Exported result file {local_path} to {url}");
+        Ok(())
+    }
+
+    /// Delete the files the results of a job were exported to, once the results expired
+    pub async fn delete_results(
+        &self,
+        job_id: &str,
+        config: &ExportConfig,
+    ) -> Result<()> {
+        let prefix = format!("{}/{job_id}", self.location.trim_end_matches('/'));
+        let url = Url::parse(&prefix).map_err(|e| {
+            DataFusionError::Execution(format!("Invalid export URL {prefix}: {e}"))
+        })?;
+        let store = self.object_store(&url, config)?;
+        let prefix = Path::from_url_path(url.path()).map_err(|e| {
+            DataFusionError::Execution(format!("Invalid export URL {url}: {e}"))
+        })?;
+        let files: Vec<Path> = store
+            .list(Some(&prefix))
+            .await?
+            .map_ok(|file| file.location)
+            .try_collect()
+            .await?;
+        for file in &files {
+            store.delete(file).await?;
+        }
+        debug!(
+            "Deleted {} exported result files of job {job_id}",
+            files.len()
+        );
+        Ok(())
+    }
+
+    /// Manifest of the files the result partitions of a successful job were exported to,
+    /// with the [export_manifest_schema]
+    pub fn manifest(
+        &self,
+        job_id: &str,
+        partition_location: &[PartitionLocation],
+    ) -> Result<RecordBatch> {
+        let mut partitions = vec![];
+        let mut urls = vec![];
+        let mut num_rows = vec![];
+        let mut num_bytes = vec![];
+        for location in partition_location {
+            let partition_id = location
+                .partition_id
+                .as_ref()
+                .map(|id| id.partition_id)
+                .unwrap_or_default();
+            let stats = location.partition_stats.clone().unwrap_or_default();
+            partitions.push(partition_id);
+            urls.push(self.partition_url(job_id, partition_id as usize, &location.path));
+            num_rows.push(stats.num_rows.max(0) as u64);
+            num_bytes.push(stats.num_bytes.max(0) as u64);
+        }
+        Ok(RecordBatch::try_new(
+            export_manifest_schema(),
+            vec![
+                Arc::new(UInt32Array::from(partitions)),
+                Arc::new(StringArray::from(urls)),
+                Arc::new(UInt64Array::from(num_rows)),
+                Arc::new(UInt64Array::from(num_bytes)),
+            ],
+        )?)
+    }
+
+    /// The object store of the export location, configured with the options of the export
+    /// and the ones of its profile, which take precedence. Local files may only be written below the local root of
+    /// the configuration.
+    fn object_store(
+        &self,
+        url: &Url,
+        config: &ExportConfig,
+    ) -> Result<Arc<dyn ObjectStore>> {
+        if url.scheme() == "file" {
+            if !config.allows_local(url) {
+                return Err(DataFusionError::Execution(format!(
+                    "Results may only be exported to file URLs below the local export root, not {url}"
+                )));
+            }
+            return Ok(Arc::new(LocalFileSystem::new()));
+        }
+        #[allow(unused_variables)]
+        let options: HashMap<String, String> = self
+            .options
+            .clone()
+            .into_iter()
+            .chain(config.profile(&self.profile)?)
+            .collect();
+        match url.scheme() {
+            #[cfg(feature = "s3")]
+            "s3" => Ok(Arc::new(
+                AmazonS3Builder::new()
+                    .with_bucket_name(url.host_str().unwrap_or_default())
+                    .try_with_options(&options)?
+                    .build()?,
+            )),
+            #[cfg(feature = "gcs")]
+            "gs" => Ok(Arc::new(
+                GoogleCloudStorageBuilder::new()
+                    .with_bucket_name(url.host_str().unwrap_or_default())
+                    .try_with_options(&options)?
+                    .build()?,
+            )),
+            #[cfg(feature = "azure")]
+            "azure" => Ok(Arc::new(
+                MicrosoftAzureBuilder::new()
+                    .with_container_name(url.host_str().unwrap_or_default())
+                    .try_with_options(&options)?
+                    .build()?,
+            )),
+            scheme => Err(DataFusionError::Execution(format!(
+                "No object store available to export results to {scheme} URLs"
+            ))),
+        }
+    }
+}
+
+/// Schema of the manifest returned by the queries exporting their results: the URL of the
+/// file of each result partition with its number of rows and bytes
+pub fn export_manifest_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("partition", DataType::UInt32, false),
+        Field::new("url", DataType::Utf8, false),
+        Field::new("num_rows", DataType::UInt64, false),
+        Field::new("num_bytes", DataType::UInt64, false),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::protobuf::{PartitionId, PartitionStats};
+    use datafusion::arrow::array::Array;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_export_file() -> Result<()> {
+        let work_dir = TempDir::new()?;
+        let export_dir = TempDir::new()?;
+        let local_path = work_dir.path().join("data.arrow");
+        std::fs::write(&local_path, "batches")?;
+        let local_path = local_path.to_str().unwrap();
+
+        let export = ResultExport::new(
+            format!("file://{}/", export_dir.path().display()),
+            HashMap::new(),
+        );
+        let config = ExportConfig::default().with_local_root(export_dir.path());
+        let url = export.partition_url("job", 2, local_path);
+        assert_eq!(
+            url,
+            format!("file://{}/job/part-2.arrow", export_dir.path().display())
+        );
+        export.export_file(local_path, &url, &config).await?;
+        assert_eq!(
+            std::fs::read_to_string(export_dir.path().join("job/part-2.arrow"))?,
+            "batches"
+        );
+        export.delete_results("job", &config).await?;
+        assert!(!export_dir.path().join("job/part-2.arrow").exists());
+
+        // File URLs are rejected without a local root or outside of it
+        assert!(export
+            .export_file(local_path, &url, &ExportConfig::default())
+            .await
+            .is_err());
+        let outside = format!("file://{}/job/part-2.arrow", work_dir.path().display());
+        assert!(export
+            .export_file(local_path, &outside, &config)
+            .await
+            .is_err());
+        let escaping =
+            format!("file://{}/../job/part-2.arrow", export_dir.path().display());
+        assert!(export
+            .export_file(local_path, &escaping, &config)
+            .await
+            .is_err());
+
+        assert!(export
+            .export_file(local_path, "unknown://bucket/job/part-2.arrow", &config)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_profiles() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("profiles");
+        std::fs::write(
+            &path,
+            "# Exports of the analysts\nanalysts.aws_access_key_id = key\nanalysts.aws_secret_access_key=secret\n",
+        )?;
+        let config = ExportConfig::default().with_profiles_file(&path)?;
+        assert_eq!(
+            config.profile("analysts")?,
+            HashMap::from([
+                ("aws_access_key_id".to_owned(), "key".to_owned()),
+                ("aws_secret_access_key".to_owned(), "secret".to_owned()),
+            ])
+        );
+        assert!(config.profile("")?.is_empty());
+        assert!(config.profile("unknown").is_err());
+        assert!(!format!("{config:?}").contains("secret"));
+
+        std::fs::write(&path, "secret")?;
+        assert!(ExportConfig::default().with_profiles_file(&path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_manifest() -> Result<()> {
+        let export = ResultExport::new(
+            "s3://bucket/exports",
+            HashMap::from([("aws_secret_access_key".to_owned(), "secret".to_owned())]),
+        );
+        assert!(!format!("{export:?}").contains("secret\""));
+
+        let location = |partition_id: u32, path: &str| PartitionLocation {
+            partition_id: Some(PartitionId {
+                job_id: "job".to_owned(),
+                stage_id: 2,
+                partition_id,
+            }),
+            partition_stats: Some(PartitionStats {
+                num_rows: 10,
+                num_batches: 1,
+                num_bytes: 100,
+                column_stats: vec![],
+            }),
+            path: path.to_owned(),
+            ..Default::default()
+        };
+        let manifest = export.manifest(
+            "job",
+            &[
+                location(0, "/work/job/2/0/data.arrow"),
+                location(1, "/work/job/2/1/data.arrows"),
+            ],
+        )?;
+        assert_eq!(manifest.schema(), export_manifest_schema());
+        let urls = manifest
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(urls.len(), 2);
+        assert_eq!(urls.value(0), "s3://bucket/exports/job/part-0.arrow");
+        assert_eq!(urls.value(1), "s3://bucket/exports/job/part-1.arrows");
+        Ok(())
+    }
+}
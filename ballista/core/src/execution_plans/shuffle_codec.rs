@@ -0,0 +1,850 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Codecs used to encode the shuffle files written by [ShuffleWriterExec](super::ShuffleWriterExec).
+//!
+//! Arrow IPC is used by default. For narrow schemas, such as the key/value exchanges of high
+//! cardinality grouped aggregations, the row codec encodes each batch using the Arrow row format
+//! which avoids the per-column buffer overhead of an IPC message.
+//!
+//! Schemas with dictionary encoded columns can be shuffled using the Arrow IPC streaming format
+//! instead of the file format. A file only holds a single dictionary per column, so batches
+//! with different dictionaries could not be written to the same file. A stream writes a
+//! dictionary again only when it is replaced, so the batches sharing a dictionary, such as the
+//! slices of a batch repartitioned by hash or the batches read from a shuffle file, keep their
+//! dictionary encoded columns as they are, and the dictionary is only written once per file.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use datafusion::arrow::error::ArrowError;
+use datafusion::arrow::ipc::reader::{FileReader, StreamReader};
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use datafusion::arrow::row::{RowConverter, SortField};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::physical_plan::common::{batch_byte_size, IPCWriter};
+use prost::Message;
+
+use crate::serde::protobuf;
+
+/// Magic bytes at the start of every row encoded shuffle file
+const ROW_FILE_MAGIC: &[u8; 8] = b"BLSTROW1";
+
+/// Encoding used for shuffle files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShuffleCodec {
+    /// Arrow IPC file format
+    #[default]
+    ArrowIpc,
+    /// Length prefixed rows in the Arrow row format
+    Row,
+    /// Arrow IPC streaming format, which lets the dictionaries of dictionary encoded columns
+    /// change between batches
+    ArrowIpcStream,
+}
+
+impl ShuffleCodec {
+    /// Choose the codec for a shuffle of the given schema. The row codec is selected when the
+    /// schema has at most `row_codec_max_columns` columns which can all be row encoded.
+    /// A `row_codec_max_columns` of 0 disables the row codec. Otherwise the IPC streaming
+    /// format is selected for schemas with dictionary encoded columns if `share_dictionaries`
+    /// is true.
+    pub fn for_schema(
+        schema: &Schema,
+        row_codec_max_columns: usize,
+        share_dictionaries: bool,
+    ) -> Self {
+        let fields = schema.fields();
+        if !fields.is_empty()
+            && fields.len() <= row_codec_max_columns
+            && fields.iter().all(|f| is_row_encodable(f.data_type()))
+        {
+            ShuffleCodec::Row
+        } else if share_dictionaries
+            && fields.iter().any(|f| has_dictionary(f.data_type()))
+        {
+            ShuffleCodec::ArrowIpcStream
+        } else {
+            ShuffleCodec::ArrowIpc
+        }
+    }
+
+    /// File extension used for shuffle files written with this codec
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ShuffleCodec::ArrowIpc => "arrow",
+            ShuffleCodec::Row => "rows",
+            ShuffleCodec::ArrowIpcStream => "arrows",
+        }
+    }
+
+    /// Infer the codec of a shuffle file from its path
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rows") => ShuffleCodec::Row,
+            Some("arrows") => ShuffleCodec::ArrowIpcStream,
+            _ => ShuffleCodec::ArrowIpc,
+        }
+    }
+}
+
+impl From<protobuf::ShuffleCodec> for ShuffleCodec {
+    fn from(codec: protobuf::ShuffleCodec) -> Self {
+        match codec {
+            protobuf::ShuffleCodec::ArrowIpc => ShuffleCodec::ArrowIpc,
+            protobuf::ShuffleCodec::Row => ShuffleCodec::Row,
+            protobuf::ShuffleCodec::ArrowIpcStream => ShuffleCodec::ArrowIpcStream,
+        }
+    }
+}
+
+impl From<ShuffleCodec> for protobuf::ShuffleCodec {
+    fn from(codec: ShuffleCodec) -> Self {
+        match codec {
+            ShuffleCodec::ArrowIpc => protobuf::ShuffleCodec::ArrowIpc,
+            ShuffleCodec::Row => protobuf::ShuffleCodec::Row,
+            ShuffleCodec::ArrowIpcStream => protobuf::ShuffleCodec::ArrowIpcStream,
+        }
+    }
+}
+
+// Decimal256 is left out as the schema at the start of row files can not be encoded in
+// protobuf with this type
+fn is_row_encodable(data_type: &DataType) -> bool {
+    matches!(
+        data_type,
+        DataType::Boolean
+            | DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::UInt8
+            | DataType::UInt16
+            | DataType::UInt32
+            | DataType::UInt64
+            | DataType::Float16
+            | DataType::Float32
+            | DataType::Float64
+            | DataType::Date32
+            | DataType::Date64
+            | DataType::Time32(_)
+            | DataType::Time64(_)
+            | DataType::Timestamp(_, _)
+            | DataType::Duration(_)
+            | DataType::Interval(_)
+            | DataType::Decimal128(_, _)
+            | DataType::Utf8
+            | DataType::LargeUtf8
+            | DataType::Binary
+            | DataType::LargeBinary
+    )
+}
+
+fn has_dictionary(data_type: &DataType) -> bool {
+    match data_type {
+        DataType::Dictionary(_, _) => true,
+        DataType::List(field)
+        | DataType::LargeList(field)
+        | DataType::FixedSizeList(field, _)
+        | DataType::Map(field, _) => has_dictionary(field.data_type()),
+        DataType::Struct(fields) => fields.iter().any(|f| has_dictionary(f.data_type())),
+        _ => false,
+    }
+}
+
+/// The schema with each of its dictionary encoded fields, nested ones included, given its own
+/// dictionary id.
+///
+/// Arrow IPC identifies the dictionary of a field by its dictionary id, which is 0 for the
+/// fields created without one, so the dictionaries of the different dictionary encoded
+/// fields of a schema would otherwise replace one another when written.
+pub fn with_unique_dict_ids(schema: &Schema) -> Schema {
+    let mut next_dict_id = 0;
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|field| with_dict_ids(field, &mut next_dict_id))
+        .collect::<Vec<_>>();
+    Schema::new_with_metadata(fields, schema.metadata().clone())
+}
+
+fn with_dict_ids(field: &Field, next_dict_id: &mut i64) -> Field {
+    let data_type = match field.data_type() {
+        DataType::List(field) => {
+            DataType::List(Arc::new(with_dict_ids(field, next_dict_id)))
+        }
+        DataType::LargeList(field) => {
+            DataType::LargeList(Arc::new(with_dict_ids(field, next_dict_id)))
+        }
+        DataType::FixedSizeList(field, size) => {
+            DataType::FixedSizeList(Arc::new(with_dict_ids(field, next_dict_id)), *size)
+        }
+        DataType::Map(field, sorted) => {
+            DataType::Map(Arc::new(with_dict_ids(field, next_dict_id)), *sorted)
+        }
+        DataType::Struct(fields) => DataType::Struct(
+            fields
+                .iter()
+                .map(|field| with_dict_ids(field, next_dict_id))
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+        data_type => data_type.clone(),
+    };
+
+    let new_field = if matches!(data_type, DataType::Dictionary(_, _)) {
+        let dict_id = *next_dict_id;
+        *next_dict_id += 1;
+        Field::new_dict(
+            field.name(),
+            data_type,
+            field.is_nullable(),
+            dict_id,
+            field.dict_is_ordered().unwrap_or_default(),
+        )
+    } else {
+        Field::new(field.name(), data_type, field.is_nullable())
+    };
+    new_field.with_metadata(field.metadata().clone())
+}
+
+/// The batch with the schema given by [with_unique_dict_ids] if it has dictionary encoded
+/// columns
+fn with_unique_dict_ids_batch(batch: &RecordBatch) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    if !schema
+        .fields()
+        .iter()
+        .any(|f| has_dictionary(f.data_type()))
+    {
+        return Ok(batch.clone());
+    }
+    let options = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+    Ok(RecordBatch::try_new_with_options(
+        Arc::new(with_unique_dict_ids(&schema)),
+        batch.columns().to_vec(),
+        &options,
+    )?)
+}
+
+fn sort_fields(schema: &Schema) -> Vec<SortField> {
+    schema
+        .fields()
+        .iter()
+        .map(|f| SortField::new(f.data_type().clone()))
+        .collect()
+}
+
+/// Writer for a single shuffle output file
+pub enum ShuffleFileWriter {
+    Ipc(IPCWriter),
+    Row(RowFileWriter),
+    IpcStream(IpcStreamFileWriter),
+}
+
+impl ShuffleFileWriter {
+    /// Create a new writer for `path` using the given codec
+    pub fn try_new(codec: ShuffleCodec, path: &Path, schema: &Schema) -> Result<Self> {
+        match codec {
+            ShuffleCodec::ArrowIpc => Ok(Self::Ipc(IPCWriter::new(
+                path,
+                &with_unique_dict_ids(schema),
+            )?)),
+            ShuffleCodec::Row => Ok(Self::Row(RowFileWriter::try_new(path, schema)?)),
+            ShuffleCodec::ArrowIpcStream => Ok(Self::IpcStream(
+                IpcStreamFileWriter::try_new(path, &with_unique_dict_ids(schema))?,
+            )),
+        }
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            Self::Ipc(w) => w.write(&with_unique_dict_ids_batch(batch)?),
+            Self::Row(w) => w.write(batch),
+            Self::IpcStream(w) => w.write(&with_unique_dict_ids_batch(batch)?),
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        match self {
+            Self::Ipc(w) => w.finish(),
+            Self::Row(w) => w.finish(),
+            Self::IpcStream(w) => w.finish(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Ipc(w) => w.path(),
+            Self::Row(w) => w.path(),
+            Self::IpcStream(w) => w.path(),
+        }
+    }
+
+    pub fn num_batches(&self) -> u64 {
+        match self {
+            Self::Ipc(w) => w.num_batches,
+            Self::Row(w) => w.num_batches,
+            Self::IpcStream(w) => w.num_batches,
+        }
+    }
+
+    pub fn num_rows(&self) -> u64 {
+        match self {
+            Self::Ipc(w) => w.num_rows,
+            Self::Row(w) => w.num_rows,
+            Self::IpcStream(w) => w.num_rows,
+        }
+    }
+
+    pub fn num_bytes(&self) -> u64 {
+        match self {
+            Self::Ipc(w) => w.num_bytes,
+            Self::Row(w) => w.num_bytes,
+            Self::IpcStream(w) => w.num_bytes,
+        }
+    }
+}
+
+/// Reader for a single shuffle output file
+pub enum ShuffleFileReader {
+    Ipc(FileReader<File>),
+    Row(RowFileReader),
+    IpcStream(StreamReader<BufReader<File>>),
+}
+
+impl ShuffleFileReader {
+    /// Create a reader for a shuffle file written with the given codec
+    pub fn try_new(
+        file: File,
+        codec: ShuffleCodec,
+    ) -> std::result::Result<Self, ArrowError> {
+        match codec {
+            ShuffleCodec::ArrowIpc => Ok(Self::Ipc(FileReader::try_new(file, None)?)),
+            ShuffleCodec::Row => Ok(Self::Row(RowFileReader::try_new(file)?)),
+            ShuffleCodec::ArrowIpcStream => Ok(Self::IpcStream(StreamReader::try_new(
+                BufReader::new(file),
+                None,
+            )?)),
+        }
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        match self {
+            Self::Ipc(r) => r.schema(),
+            Self::Row(r) => r.schema(),
+            Self::IpcStream(r) => r.schema(),
+        }
+    }
+}
+
+impl Iterator for ShuffleFileReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Ipc(r) => r.next(),
+            Self::Row(r) => r.next(),
+            Self::IpcStream(r) => r.next(),
+        }
+    }
+}
+
+/// Writes record batches to a file using the Arrow IPC streaming format
+pub struct IpcStreamFileWriter {
+    path: PathBuf,
+    writer: StreamWriter<BufWriter<File>>,
+    pub num_batches: u64,
+    pub num_rows: u64,
+    pub num_bytes: u64,
+}
+
+impl IpcStreamFileWriter {
+    pub fn try_new(path: &Path, schema: &Schema) -> Result<Self> {
+        let writer = StreamWriter::try_new(BufWriter::new(File::create(path)?), schema)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            writer,
+            num_batches: 0,
+            num_rows: 0,
+            num_bytes: 0,
+        })
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.writer.write(batch)?;
+        self.num_batches += 1;
+        self.num_rows += batch.num_rows() as u64;
+        self.num_bytes += batch_byte_size(batch) as u64;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        self.writer.finish()?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Writes record batches to a file using the Arrow row format.
+///
+/// The file starts with [ROW_FILE_MAGIC] followed by the length prefixed protobuf encoded
+/// schema. Each batch is written as its row count followed by the length prefixed rows, and
+/// a row count of 0 marks the end of the file.
+pub struct RowFileWriter {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    converter: RowConverter,
+    pub num_batches: u64,
+    pub num_rows: u64,
+    pub num_bytes: u64,
+}
+
+impl RowFileWriter {
+    pub fn try_new(path: &Path, schema: &Schema) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let converter = RowConverter::new(sort_fields(schema))?;
+
+        let schema = datafusion_proto::protobuf::Schema::try_from(schema)
+            .map_err(|e| DataFusionError::Internal(e.to_string()))?
+            .encode_to_vec();
+        writer.write_all(ROW_FILE_MAGIC)?;
+        writer.write_all(&(schema.len() as u32).to_le_bytes())?;
+        writer.write_all(&schema)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            writer,
+            converter,
+            num_batches: 0,
+            num_rows: 0,
+            num_bytes: (ROW_FILE_MAGIC.len() + 4 + schema.len()) as u64,
+        })
+    }
+
+    pub fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        let rows = self.converter.convert_columns(batch.columns())?;
+        self.writer
+            .write_all(&(rows.num_rows() as u32).to_le_bytes())?;
+        self.num_bytes += 4;
+        for row in rows.iter() {
+            let bytes = row.as_ref();
+            self.writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            self.writer.write_all(bytes)?;
+            self.num_bytes += 4 + bytes.len() as u64;
+        }
+
+        self.num_batches += 1;
+        self.num_rows += batch.num_rows() as u64;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        self.writer.write_all(&0u32.to_le_bytes())?;
+        self.num_bytes += 4;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Reads record batches written by a [RowFileWriter]
+pub struct RowFileReader {
+    reader: BufReader<File>,
+    schema: SchemaRef,
+    converter: RowConverter,
+    finished: bool,
+}
+
+impl RowFileReader {
+    pub fn try_new(file: File) -> std::result::Result<Self, ArrowError> {
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != ROW_FILE_MAGIC {
+            return Err(ArrowError::ParseError(
+                "Shuffle file is not a row encoded file".to_owned(),
+            ));
+        }
+
+        let mut schema = vec![0u8; read_u32(&mut reader)? as usize];
+        reader.read_exact(&mut schema)?;
+        let schema = datafusion_proto::protobuf::Schema::decode(schema.as_slice())
+            .map_err(|e| {
+                ArrowError::ParseError(format!("Could not decode row file schema: {e}"))
+            })?;
+        let schema = Schema::try_from(&schema).map_err(|e| {
+            ArrowError::ParseError(format!("Invalid row file schema: {e}"))
+        })?;
+        let converter = RowConverter::new(sort_fields(&schema))?;
+
+        Ok(Self {
+            reader,
+            schema: Arc::new(schema),
+            converter,
+            finished: false,
+        })
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn read_batch(&mut self) -> std::result::Result<Option<RecordBatch>, ArrowError> {
+        let num_rows = read_u32(&mut self.reader)? as usize;
+        if num_rows == 0 {
+            return Ok(None);
+        }
+
+        let mut buffer: Vec<u8> = vec![];
+        let mut offsets = Vec::with_capacity(num_rows + 1);
+        offsets.push(0);
+        for _ in 0..num_rows {
+            let len = read_u32(&mut self.reader)? as usize;
+            let start = buffer.len();
+            buffer.resize(start + len, 0);
+            self.reader.read_exact(&mut buffer[start..])?;
+            offsets.push(buffer.len());
+        }
+
+        let parser = self.converter.parser();
+        let rows = offsets
+            .windows(2)
+            .map(|w| parser.parse(&buffer[w[0]..w[1]]));
+        let columns = self.converter.convert_rows(rows)?;
+
+        RecordBatch::try_new(self.schema.clone(), columns).map(Some)
+    }
+}
+
+impl Iterator for RowFileReader {
+    type Item = std::result::Result<RecordBatch, ArrowError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        match self.read_batch() {
+            Ok(Some(batch)) => Some(Ok(batch)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(e) => {
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::result::Result<u32, ArrowError> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{
+        ArrayRef, Decimal128Array, Decimal256Array, DictionaryArray, Float64Array,
+        Int32Array, Int32Builder, Int64Array, ListArray, ListBuilder, MapBuilder,
+        StringArray, StringBuilder, StringDictionaryBuilder, StructArray,
+    };
+    use datafusion::arrow::datatypes::TimeUnit;
+    use datafusion::arrow::datatypes::{i256, Int32Type};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_codec_for_schema() {
+        let narrow = Schema::new(vec![
+            Field::new("k", DataType::Utf8, false),
+            Field::new("v", DataType::Int64, true),
+        ]);
+        assert_eq!(
+            ShuffleCodec::for_schema(&narrow, 0, true),
+            ShuffleCodec::ArrowIpc
+        );
+        assert_eq!(
+            ShuffleCodec::for_schema(&narrow, 1, true),
+            ShuffleCodec::ArrowIpc
+        );
+        assert_eq!(
+            ShuffleCodec::for_schema(&narrow, 2, true),
+            ShuffleCodec::Row
+        );
+
+        let dictionary = Schema::new(vec![Field::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            true,
+        )]);
+        assert_eq!(
+            ShuffleCodec::for_schema(&dictionary, 4, true),
+            ShuffleCodec::ArrowIpcStream
+        );
+        assert_eq!(
+            ShuffleCodec::for_schema(&dictionary, 4, false),
+            ShuffleCodec::ArrowIpc
+        );
+
+        let nested = Schema::new(vec![Field::new(
+            "l",
+            DataType::List(Arc::new(Field::new(
+                "item",
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+                true,
+            ))),
+            true,
+        )]);
+        assert_eq!(
+            ShuffleCodec::for_schema(&nested, 0, true),
+            ShuffleCodec::ArrowIpcStream
+        );
+
+        let ts = Schema::new(vec![Field::new(
+            "ts",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        )]);
+        assert_eq!(ShuffleCodec::for_schema(&ts, 4, true), ShuffleCodec::Row);
+    }
+
+    #[test]
+    fn test_row_file_round_trip() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("k", DataType::Utf8, false),
+            Field::new("v", DataType::Int64, true),
+            Field::new("f", DataType::Float64, true),
+        ]));
+        let batch1 = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                Arc::new(Int64Array::from(vec![Some(1), None, Some(3)])),
+                Arc::new(Float64Array::from(vec![Some(-0.5), Some(1.5), None])),
+            ],
+        )?;
+        let batch2 = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(StringArray::from(vec![""])),
+                Arc::new(Int64Array::from(vec![i64::MIN])),
+                Arc::new(Float64Array::from(vec![f64::MAX])),
+            ],
+        )?;
+
+        let dir = TempDir::new()?;
+        let path = dir.path().join("data-0.rows");
+        let mut writer =
+            ShuffleFileWriter::try_new(ShuffleCodec::Row, &path, schema.as_ref())?;
+        writer.write(&batch1)?;
+        writer.write(&RecordBatch::new_empty(schema.clone()))?;
+        writer.write(&batch2)?;
+        writer.finish()?;
+
+        assert_eq!(writer.num_batches(), 2);
+        assert_eq!(writer.num_rows(), 4);
+        assert_eq!(
+            writer.num_bytes(),
+            std::fs::metadata(&path)?.len(),
+            "num_bytes should match the size of the file"
+        );
+
+        let codec = ShuffleCodec::from_path(&path);
+        let reader = ShuffleFileReader::try_new(File::open(&path)?, codec)?;
+        assert!(matches!(reader, ShuffleFileReader::Row(_)));
+        assert_eq!(reader.schema(), schema);
+
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(batches, vec![batch1, batch2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipc_stream_file_round_trip() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            true,
+        )]));
+        let first: DictionaryArray<Int32Type> =
+            vec![Some("a"), Some("b"), None, Some("a")]
+                .into_iter()
+                .collect();
+        let second: DictionaryArray<Int32Type> =
+            vec!["c", "d", "c"].into_iter().collect();
+        let batch1 = RecordBatch::try_new(schema.clone(), vec![Arc::new(first)])?;
+        // Slices of a batch share its dictionary
+        let batch2 = batch1.slice(1, 2);
+        // Batches with another dictionary replace it
+        let batch3 = RecordBatch::try_new(schema.clone(), vec![Arc::new(second)])?;
+
+        let dir = TempDir::new()?;
+        let path = dir.path().join("data-0.arrows");
+        let mut writer = ShuffleFileWriter::try_new(
+            ShuffleCodec::ArrowIpcStream,
+            &path,
+            schema.as_ref(),
+        )?;
+        writer.write(&batch1)?;
+        writer.write(&batch2)?;
+        writer.write(&batch3)?;
+        writer.finish()?;
+
+        assert_eq!(writer.num_batches(), 3);
+        assert_eq!(writer.num_rows(), 9);
+
+        let codec = ShuffleCodec::from_path(&path);
+        let reader = ShuffleFileReader::try_new(File::open(&path)?, codec)?;
+        assert!(matches!(reader, ShuffleFileReader::IpcStream(_)));
+        assert_eq!(reader.schema(), schema);
+
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(batches, vec![batch1, batch2, batch3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipc_files_round_trip_types() -> Result<()> {
+        let decimal128 = Decimal128Array::from(vec![Some(12345), None, Some(-1)])
+            .with_precision_and_scale(10, 2)?;
+        let decimal256 = Decimal256Array::from(vec![
+            Some(i256::from_parts(u128::MAX, 1)),
+            Some(i256::from_i128(-7)),
+            None,
+        ])
+        .with_precision_and_scale(50, 4)?;
+        let structs = StructArray::from(vec![
+            (
+                Field::new("a", DataType::Int32, true),
+                Arc::new(Int32Array::from(vec![Some(1), None, Some(3)])) as ArrayRef,
+            ),
+            (
+                Field::new("b", DataType::Utf8, true),
+                Arc::new(StringArray::from(vec![Some("x"), Some("y"), None])),
+            ),
+        ]);
+        let lists = ListArray::from_iter_primitive::<Int32Type, _, _>(vec![
+            Some(vec![Some(1), None]),
+            None,
+            Some(vec![]),
+        ]);
+        let mut maps = MapBuilder::new(None, StringBuilder::new(), Int32Builder::new());
+        maps.keys().append_value("k1");
+        maps.values().append_value(1);
+        maps.keys().append_value("k2");
+        maps.values().append_null();
+        maps.append(true)?;
+        maps.append(false)?;
+        maps.append(true)?;
+        let first: DictionaryArray<Int32Type> =
+            vec![Some("a"), None, Some("a")].into_iter().collect();
+        let second: DictionaryArray<Int32Type> =
+            vec!["b", "c", "d"].into_iter().collect();
+        let mut nested = ListBuilder::new(StringDictionaryBuilder::<Int32Type>::new());
+        nested.values().append_value("e");
+        nested.values().append_value("f");
+        nested.append(true);
+        nested.append(false);
+        nested.values().append_value("e");
+        nested.append(true);
+
+        let batch = RecordBatch::try_from_iter(vec![
+            ("decimal128", Arc::new(decimal128) as ArrayRef),
+            ("decimal256", Arc::new(decimal256)),
+            ("struct", Arc::new(structs)),
+            ("list", Arc::new(lists)),
+            ("map", Arc::new(maps.finish())),
+            ("dict1", Arc::new(first)),
+            ("dict2", Arc::new(second)),
+            ("dict_list", Arc::new(nested.finish())),
+        ])?;
+        let schema = batch.schema();
+
+        // Every dictionary encoded field gets its own dictionary id
+        let dict_ids_schema = with_unique_dict_ids(&schema);
+        assert_eq!(dict_ids_schema, *schema);
+        assert_eq!(dict_ids_schema.field(5).dict_id(), Some(0));
+        assert_eq!(dict_ids_schema.field(6).dict_id(), Some(1));
+        match dict_ids_schema.field(7).data_type() {
+            DataType::List(item) => assert_eq!(item.dict_id(), Some(2)),
+            other => panic!("Unexpected data type {other}"),
+        }
+
+        assert_eq!(
+            ShuffleCodec::for_schema(&schema, usize::MAX, true),
+            ShuffleCodec::ArrowIpcStream
+        );
+        let dir = TempDir::new()?;
+        for codec in [ShuffleCodec::ArrowIpc, ShuffleCodec::ArrowIpcStream] {
+            let path = dir
+                .path()
+                .join(format!("data-0.{}", codec.file_extension()));
+            let mut writer = ShuffleFileWriter::try_new(codec, &path, &schema)?;
+            writer.write(&batch)?;
+            writer.write(&batch.slice(1, 2))?;
+            writer.finish()?;
+
+            let reader = ShuffleFileReader::try_new(File::open(&path)?, codec)?;
+            assert_eq!(reader.schema(), schema);
+            let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+            assert_eq!(batches, vec![batch.clone(), batch.slice(1, 2)]);
+        }
+
+        // Decimals are row encodable, except for the ones which do not fit in 128 bits
+        let decimals = batch.project(&[0, 1])?.schema();
+        assert_eq!(
+            ShuffleCodec::for_schema(&decimals, 2, true),
+            ShuffleCodec::ArrowIpc
+        );
+        let decimal128 = batch.project(&[0])?;
+        assert_eq!(
+            ShuffleCodec::for_schema(&decimal128.schema(), 2, true),
+            ShuffleCodec::Row
+        );
+        let path = dir.path().join("data-0.rows");
+        let mut writer =
+            ShuffleFileWriter::try_new(ShuffleCodec::Row, &path, &decimal128.schema())?;
+        writer.write(&decimal128)?;
+        writer.finish()?;
+        let reader = ShuffleFileReader::try_new(File::open(&path)?, ShuffleCodec::Row)?;
+        let batches = reader.collect::<std::result::Result<Vec<_>, _>>()?;
+        assert_eq!(batches, vec![decimal128]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,434 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Vectorized hash repartitioning of record batches for hash partitioned shuffle writes.
+
+use std::sync::Arc;
+
+use datafusion::arrow::array::{Array, ArrayRef};
+use datafusion::arrow::compute::{concat_batches, interleave};
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use datafusion::error::Result;
+use datafusion::physical_expr::hash_utils::create_hashes;
+use datafusion::physical_plan::metrics;
+use datafusion::physical_plan::PhysicalExpr;
+
+/// Maximum number of input batches referenced by buffered rows. Once reached, the rows of
+/// all output partitions are flushed or gathered so that the input batches can be released.
+const MAX_BUFFERED_BATCHES: usize = 16;
+
+/// Splits record batches into hash partitions.
+///
+/// Hashes and partition ids are computed for a whole input batch at once. Rows are then
+/// buffered per output partition as (batch, row) indices and gathered with a single
+/// `interleave` per column once an output partition holds `batch_size` rows, instead of
+/// copying small slices of every input batch into each output partition.
+///
+/// With a write buffer size set, output partitions are instead flushed once their buffered
+/// rows take roughly that many bytes. Rows buffered for longer than `MAX_BUFFERED_BATCHES`
+/// input batches are then gathered into per partition chunks which are concatenated on flush.
+///
+/// Rows are assigned to the same partitions as DataFusion's `RepartitionExec` would.
+pub struct HashRepartitioner {
+    exprs: Vec<Arc<dyn PhysicalExpr>>,
+    num_partitions: usize,
+    schema: SchemaRef,
+    batch_size: usize,
+    /// Flush an output partition once its buffered rows take this many bytes, 0 to flush
+    /// every `batch_size` rows instead
+    write_buffer_size: usize,
+    random_state: ahash::RandomState,
+    hashes_buf: Vec<u64>,
+    /// Input batches referenced by the buffered rows
+    batches: Vec<RecordBatch>,
+    /// (batch index, row index) of the rows buffered for each output partition
+    indices: Vec<Vec<(usize, usize)>>,
+    /// Gathered rows buffered for each output partition, only used with a write buffer size
+    chunks: Vec<Vec<RecordBatch>>,
+    /// Estimated size in bytes of the rows buffered for each output partition
+    buffered_bytes: Vec<usize>,
+    repart_time: metrics::Time,
+}
+
+impl HashRepartitioner {
+    pub fn new(
+        exprs: Vec<Arc<dyn PhysicalExpr>>,
+        num_partitions: usize,
+        schema: SchemaRef,
+        batch_size: usize,
+        repart_time: metrics::Time,
+    ) -> Self {
+        Self {
+            exprs,
+            num_partitions,
+            schema,
+            batch_size: batch_size.max(1),
+            write_buffer_size: 0,
+            // Use fixed random state, same as DataFusion's BatchPartitioner
+            random_state: ahash::RandomState::with_seeds(0, 0, 0, 0),
+            hashes_buf: vec![],
+            batches: vec![],
+            indices: vec![vec![]; num_partitions],
+            chunks: vec![vec![]; num_partitions],
+            buffered_bytes: vec![0; num_partitions],
+            repart_time,
+        }
+    }
+
+    /// Flush output partitions once their buffered rows take `write_buffer_size` bytes
+    /// instead of every `batch_size` rows. A size of 0 keeps flushing by row count.
+    pub fn with_write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = write_buffer_size;
+        self
+    }
+
+    /// Compute the output partition of every row of `batch`
+    pub fn partition_ids(&mut self, batch: &RecordBatch) -> Result<Vec<u32>> {
+        let arrays = self
+            .exprs
+            .iter()
+            .map(|expr| Ok(expr.evaluate(batch)?.into_array(batch.num_rows())))
+            .collect::<Result<Vec<ArrayRef>>>()?;
+
+        self.hashes_buf.clear();
+        self.hashes_buf.resize(batch.num_rows(), 0);
+        create_hashes(&arrays, &self.random_state, &mut self.hashes_buf)?;
+
+        let num_partitions = self.num_partitions as u64;
+        Ok(self
+            .hashes_buf
+            .iter()
+            .map(|hash| (hash % num_partitions) as u32)
+            .collect())
+    }
+
+    /// Buffer the rows of `batch` for their output partitions. `output` is called with every
+    /// output batch which got full, never with an empty batch.
+    pub fn partition<F>(&mut self, batch: RecordBatch, mut output: F) -> Result<()>
+    where
+        F: FnMut(usize, RecordBatch) -> Result<()>,
+    {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+
+        let timer = self.repart_time.timer();
+
+        let partition_ids = self.partition_ids(&batch)?;
+        let row_bytes = batch.get_array_memory_size() / batch.num_rows();
+        let batch_idx = self.batches.len();
+        self.batches.push(batch);
+        for (row, partition) in partition_ids.into_iter().enumerate() {
+            let partition = partition as usize;
+            self.indices[partition].push((batch_idx, row));
+            self.buffered_bytes[partition] += row_bytes;
+        }
+
+        let release_batches = self.batches.len() >= MAX_BUFFERED_BATCHES;
+        let mut output_batches = vec![];
+        for partition in 0..self.num_partitions {
+            if self.is_full(partition) {
+                output_batches.push((partition, self.take_partition(partition)?));
+            } else if release_batches && !self.indices[partition].is_empty() {
+                if self.write_buffer_size > 0 {
+                    let chunk = self.take_indices(partition)?;
+                    self.chunks[partition].push(chunk);
+                } else {
+                    output_batches.push((partition, self.take_partition(partition)?));
+                }
+            }
+        }
+        if release_batches {
+            self.batches.clear();
+        }
+
+        timer.done();
+
+        for (partition, output_batch) in output_batches {
+            output(partition, output_batch)?;
+        }
+        Ok(())
+    }
+
+    /// Output all buffered rows
+    pub fn finish<F>(&mut self, mut output: F) -> Result<()>
+    where
+        F: FnMut(usize, RecordBatch) -> Result<()>,
+    {
+        for partition in 0..self.num_partitions {
+            if !self.indices[partition].is_empty() || !self.chunks[partition].is_empty() {
+                let timer = self.repart_time.timer();
+                let output_batch = self.take_partition(partition)?;
+                timer.done();
+
+                output(partition, output_batch)?;
+            }
+        }
+        self.batches.clear();
+        Ok(())
+    }
+
+    fn is_full(&self, partition: usize) -> bool {
+        if self.write_buffer_size > 0 {
+            self.buffered_bytes[partition] >= self.write_buffer_size
+        } else {
+            self.indices[partition].len() >= self.batch_size
+        }
+    }
+
+    /// Take all rows buffered for `partition` as a single batch
+    fn take_partition(&mut self, partition: usize) -> Result<RecordBatch> {
+        self.buffered_bytes[partition] = 0;
+        let mut chunks = std::mem::take(&mut self.chunks[partition]);
+        if chunks.is_empty() {
+            return self.take_indices(partition);
+        }
+        if !self.indices[partition].is_empty() {
+            chunks.push(self.take_indices(partition)?);
+        }
+        Ok(concat_batches(&self.schema, &chunks)?)
+    }
+
+    /// Gather the rows of the input batches referenced by `partition`
+    fn take_indices(&mut self, partition: usize) -> Result<RecordBatch> {
+        let indices = std::mem::take(&mut self.indices[partition]);
+
+        let columns = (0..self.schema.fields().len())
+            .map(|column| {
+                let values: Vec<&dyn Array> = self
+                    .batches
+                    .iter()
+                    .map(|batch| batch.column(column).as_ref())
+                    .collect();
+                interleave(&values, &indices)
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let options = RecordBatchOptions::new().with_row_count(Some(indices.len()));
+        Ok(RecordBatch::try_new_with_options(
+            self.schema.clone(),
+            columns,
+            &options,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{StringArray, UInt32Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::expressions::Column;
+    use datafusion::physical_plan::repartition::BatchPartitioner;
+    use datafusion::physical_plan::Partitioning;
+
+    fn create_batch(offset: u32, num_rows: u32) -> Result<RecordBatch> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("a", DataType::UInt32, true),
+            Field::new("b", DataType::Utf8, true),
+        ]));
+        let values = (offset..offset + num_rows).collect::<Vec<_>>();
+        Ok(RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(UInt32Array::from(values.clone())),
+                Arc::new(StringArray::from(
+                    values.iter().map(|v| format!("v{v}")).collect::<Vec<_>>(),
+                )),
+            ],
+        )?)
+    }
+
+    fn column_values(batches: &[RecordBatch]) -> Vec<u32> {
+        let mut values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .unwrap()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn test_same_partitions_as_datafusion() -> Result<()> {
+        let num_partitions = 4;
+        let exprs: Vec<Arc<dyn PhysicalExpr>> = vec![Arc::new(Column::new("a", 0))];
+        let batches = (0..3)
+            .map(|i| create_batch(i * 100, 100))
+            .collect::<Result<Vec<_>>>()?;
+        let schema = batches[0].schema();
+
+        let mut expected = vec![vec![]; num_partitions];
+        let mut partitioner = BatchPartitioner::try_new(
+            Partitioning::Hash(exprs.clone(), num_partitions),
+            metrics::Time::new(),
+        )?;
+        for batch in batches.iter() {
+            partitioner.partition(batch.clone(), |partition, output_batch| {
+                expected[partition].push(output_batch);
+                Ok(())
+            })?;
+        }
+
+        let mut actual = vec![vec![]; num_partitions];
+        let mut repartitioner = HashRepartitioner::new(
+            exprs,
+            num_partitions,
+            schema,
+            8192,
+            metrics::Time::new(),
+        );
+        for batch in batches {
+            repartitioner.partition(batch, |partition, output_batch| {
+                actual[partition].push(output_batch);
+                Ok(())
+            })?;
+        }
+        // nothing is output before the batch size is reached
+        assert!(actual.iter().all(|batches| batches.is_empty()));
+        repartitioner.finish(|partition, output_batch| {
+            actual[partition].push(output_batch);
+            Ok(())
+        })?;
+
+        for partition in 0..num_partitions {
+            assert!(actual[partition].len() <= 1);
+            assert_eq!(
+                column_values(&expected[partition]),
+                column_values(&actual[partition])
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_batch_size() -> Result<()> {
+        let exprs: Vec<Arc<dyn PhysicalExpr>> = vec![Arc::new(Column::new("a", 0))];
+        let batch = create_batch(0, 30)?;
+        let mut repartitioner =
+            HashRepartitioner::new(exprs, 1, batch.schema(), 64, metrics::Time::new());
+
+        let mut output = vec![];
+        for _ in 0..4 {
+            repartitioner.partition(batch.clone(), |_, output_batch| {
+                output.push(output_batch);
+                Ok(())
+            })?;
+        }
+        repartitioner.finish(|_, output_batch| {
+            output.push(output_batch);
+            Ok(())
+        })?;
+
+        // small input batches are coalesced until the batch size is reached
+        assert_eq!(
+            output
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>(),
+            vec![90, 30]
+        );
+        let names = output[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(names.value(0), "v0");
+        assert_eq!(names.value(89), "v29");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_buffer_size() -> Result<()> {
+        let exprs: Vec<Arc<dyn PhysicalExpr>> = vec![Arc::new(Column::new("a", 0))];
+        let batch = create_batch(0, 30)?;
+        let row_bytes = batch.get_array_memory_size() / batch.num_rows();
+        let mut repartitioner =
+            HashRepartitioner::new(exprs, 1, batch.schema(), 8, metrics::Time::new())
+                .with_write_buffer_size(60 * row_bytes);
+
+        let mut output = vec![];
+        for _ in 0..4 {
+            repartitioner.partition(batch.clone(), |_, output_batch| {
+                output.push(output_batch);
+                Ok(())
+            })?;
+        }
+        repartitioner.finish(|_, output_batch| {
+            output.push(output_batch);
+            Ok(())
+        })?;
+
+        // the batch size is ignored, partitions are flushed once the buffer size is reached
+        assert_eq!(
+            output
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>(),
+            vec![60, 60]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_buffer_releases_batches() -> Result<()> {
+        let exprs: Vec<Arc<dyn PhysicalExpr>> = vec![Arc::new(Column::new("a", 0))];
+        let batches = (0..20)
+            .map(|i| create_batch(i * 30, 30))
+            .collect::<Result<Vec<_>>>()?;
+        let mut repartitioner = HashRepartitioner::new(
+            exprs,
+            1,
+            batches[0].schema(),
+            8,
+            metrics::Time::new(),
+        )
+        .with_write_buffer_size(usize::MAX);
+
+        let mut output = vec![];
+        for batch in batches {
+            repartitioner.partition(batch, |_, output_batch| {
+                output.push(output_batch);
+                Ok(())
+            })?;
+        }
+        // rows are gathered into chunks instead of output when input batches are released
+        assert!(output.is_empty());
+        assert!(repartitioner.batches.len() < MAX_BUFFERED_BATCHES);
+
+        repartitioner.finish(|_, output_batch| {
+            output.push(output_batch);
+            Ok(())
+        })?;
+
+        assert_eq!(output.len(), 1);
+        assert_eq!(column_values(&output), (0..600).collect::<Vec<_>>());
+
+        Ok(())
+    }
+}
@@ -20,22 +20,26 @@ use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs::File;
+use std::path::Path;
 use std::pin::Pin;
 use std::result;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use crate::client::BallistaClient;
+use crate::execution_plans::{ShuffleCodec, ShuffleFileReader};
 use crate::serde::scheduler::{PartitionLocation, PartitionStats};
+use crate::utils::verify_file_checksum;
 
 use datafusion::arrow::datatypes::SchemaRef;
 use datafusion::arrow::error::ArrowError;
-use datafusion::arrow::ipc::reader::FileReader;
 use datafusion::arrow::record_batch::RecordBatch;
 
 use datafusion::error::{DataFusionError, Result};
 use datafusion::physical_plan::expressions::PhysicalSortExpr;
-use datafusion::physical_plan::metrics::{ExecutionPlanMetricsSet, MetricsSet};
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
 use datafusion::physical_plan::{
     DisplayFormatType, ExecutionPlan, Partitioning, RecordBatchStream,
     SendableRecordBatchStream, Statistics,
@@ -54,6 +58,12 @@ use tokio::sync::{mpsc, Semaphore};
 use tokio::task::JoinHandle;
 use tokio_stream::wrappers::ReceiverStream;
 
+/// Name of the metric counting the rows read by a [ShuffleReaderExec]
+pub const SHUFFLE_ROWS_READ_METRIC: &str = "shuffle_rows_read";
+/// Name of the metric counting the partitions of a [ShuffleReaderExec] read until their end,
+/// e.g. not cut short by a limit
+pub const SHUFFLE_PARTITIONS_READ_METRIC: &str = "shuffle_partitions_read";
+
 /// ShuffleReaderExec reads partitions that have already been materialized by a ShuffleWriterExec
 /// being executed by an executor
 #[derive(Debug, Clone)]
@@ -145,7 +155,14 @@ impl ExecutionPlan for ShuffleReaderExec {
             Arc::new(self.schema.as_ref().clone()),
             response_receiver.try_flatten(),
         );
-        Ok(Box::pin(result))
+        Ok(Box::pin(ShuffleReadCountingStream {
+            inner: Box::pin(result),
+            rows_read: MetricBuilder::new(&self.metrics)
+                .counter(SHUFFLE_ROWS_READ_METRIC, partition),
+            partitions_read: MetricBuilder::new(&self.metrics)
+                .counter(SHUFFLE_PARTITIONS_READ_METRIC, partition),
+            finished: false,
+        }))
     }
 
     fn fmt_as(
@@ -198,11 +215,11 @@ fn stats_for_partitions(
 }
 
 struct LocalShuffleStream {
-    reader: FileReader<File>,
+    reader: ShuffleFileReader,
 }
 
 impl LocalShuffleStream {
-    pub fn new(reader: FileReader<File>) -> Self {
+    pub fn new(reader: ShuffleFileReader) -> Self {
         LocalShuffleStream { reader }
     }
 }
@@ -227,6 +244,41 @@ impl RecordBatchStream for LocalShuffleStream {
     }
 }
 
+/// Counts the rows read from a partition of a shuffle, and the partition once it is read until
+/// its end, so that schedulers can reconcile them with the rows written by the map stages
+struct ShuffleReadCountingStream {
+    inner: SendableRecordBatchStream,
+    rows_read: Count,
+    partitions_read: Count,
+    finished: bool,
+}
+
+impl Stream for ShuffleReadCountingStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(batch))) => self.rows_read.add(batch.num_rows()),
+            Poll::Ready(None) if !self.finished => {
+                self.finished = true;
+                self.partitions_read.add(1);
+            }
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl RecordBatchStream for ShuffleReadCountingStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
 /// Adapter for a tokio ReceiverStream that implements the SendableRecordBatchStream interface
 struct AbortableReceiverStream {
     inner: ReceiverStream<result::Result<SendableRecordBatchStream, BallistaError>>,
@@ -376,7 +428,14 @@ async fn fetch_partition_remote(
             })?;
 
     ballista_client
-        .fetch_partition(&metadata.id, partition_id, &location.path, host, port)
+        .fetch_partition(
+            &metadata.id,
+            partition_id,
+            &location.path,
+            host,
+            port,
+            location.checksum,
+        )
         .await
 }
 
@@ -387,7 +446,7 @@ async fn fetch_partition_local(
     let metadata = &location.executor_meta;
     let partition_id = &location.partition_id;
 
-    let reader = fetch_partition_local_inner(path).map_err(|e| {
+    let reader = fetch_partition_local_inner(path, location.checksum).map_err(|e| {
         // return BallistaError::FetchFailed may let scheduler retry this task.
         BallistaError::FetchFailed(
             metadata.id.clone(),
@@ -401,12 +460,19 @@ async fn fetch_partition_local(
 
 fn fetch_partition_local_inner(
     path: &str,
-) -> result::Result<FileReader<File>, BallistaError> {
+    checksum: Option<u32>,
+) -> result::Result<ShuffleFileReader, BallistaError> {
+    if let Some(checksum) = checksum {
+        verify_file_checksum(path, checksum)?;
+    }
     let file = File::open(path).map_err(|e| {
         BallistaError::General(format!("Failed to open partition file at {path}: {e:?}"))
     })?;
-    FileReader::try_new(file, None).map_err(|e| {
-        BallistaError::General(format!("Failed to new arrow FileReader at {path}: {e:?}"))
+    let codec = ShuffleCodec::from_path(Path::new(path));
+    ShuffleFileReader::try_new(file, codec).map_err(|e| {
+        BallistaError::General(format!(
+            "Failed to new shuffle file reader at {path}: {e:?}"
+        ))
     })
 }
 
@@ -527,10 +593,16 @@ mod tests {
                     host: "executor_1".to_string(),
                     port: 7070,
                     grpc_port: 8080,
-                    specification: ExecutorSpecification { task_slots: 1 },
+                    specification: ExecutorSpecification {
+                        task_slots: 1,
+                        ..Default::default()
+                    },
+                    pool: String::new(),
                 },
                 partition_stats: Default::default(),
                 path: "test_path".to_string(),
+                checksum: None,
+                checkpoint_path: None,
             })
         }
 
@@ -589,7 +661,7 @@ mod tests {
 
         // from to input partitions test the first one with two batches
         let file_path = path.value(0);
-        let reader = fetch_partition_local_inner(file_path).unwrap();
+        let reader = fetch_partition_local_inner(file_path, None).unwrap();
 
         let mut stream: Pin<Box<dyn RecordBatchStream + Send>> =
             async { Box::pin(LocalShuffleStream::new(reader)) }.await;
@@ -605,6 +677,105 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_read_corrupted_local_shuffle() -> Result<()> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+        let work_dir = TempDir::new().unwrap();
+        let input = ShuffleWriterExec::try_new(
+            "local_file".to_owned(),
+            1,
+            create_test_data_plan()?,
+            work_dir.path().to_str().unwrap().to_owned(),
+            Some(Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 1)),
+        )?
+        .with_checksum(true);
+
+        let partitions = input.execute_shuffle_write(0, task_ctx).await?;
+        assert_eq!(partitions.len(), 1);
+        let checksum = partitions[0].checksum.as_ref().unwrap().crc32;
+        let path = partitions[0].path.clone();
+        assert_eq!(utils::file_checksum(&path).unwrap(), checksum);
+
+        let location = PartitionLocation {
+            map_partition_id: 0,
+            partition_id: PartitionId {
+                job_id: "local_file".to_string(),
+                stage_id: 1,
+                partition_id: 0,
+            },
+            executor_meta: ExecutorMetadata {
+                id: "executor_1".to_string(),
+                host: "executor_1".to_string(),
+                port: 7070,
+                grpc_port: 8080,
+                specification: ExecutorSpecification {
+                    task_slots: 1,
+                    ..Default::default()
+                },
+                pool: String::new(),
+            },
+            partition_stats: Default::default(),
+            path: path.clone(),
+            checksum: Some(checksum),
+            checkpoint_path: None,
+        };
+        let mut stream = fetch_partition_local(&location).await.unwrap();
+        assert_eq!(utils::collect_stream(&mut stream).await.unwrap().len(), 2);
+
+        // Flip a byte in the middle of the file
+        let mut content = std::fs::read(&path)?;
+        let mid = content.len() / 2;
+        content[mid] ^= 0xff;
+        std::fs::write(&path, content)?;
+
+        let result = fetch_partition_local(&location).await;
+        assert!(matches!(
+            result,
+            Err(BallistaError::FetchFailed(_, 1, 0, msg)) if msg.contains("Checksum mismatch")
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_read_metrics() -> Result<()> {
+        let schema = get_test_partition_schema();
+        let batch = RecordBatch::try_new(
+            Arc::new(schema.clone()),
+            vec![Arc::new(Int32Array::from(vec![1, 2]))],
+        )?;
+        let tmp_dir = tempdir()?;
+        let file_path = tmp_dir.path().join("shuffle_data");
+        let mut writer = FileWriter::try_new(File::create(&file_path)?, &schema)?;
+        writer.write(&batch)?;
+        writer.finish()?;
+
+        let partition_locations =
+            get_test_partition_locations(3, file_path.to_str().unwrap().to_string());
+        let shuffle_reader_exec =
+            ShuffleReaderExec::try_new(vec![partition_locations], Arc::new(schema))?;
+        let task_ctx = SessionContext::new().task_ctx();
+        let result = common::collect(shuffle_reader_exec.execute(0, task_ctx)?).await?;
+        assert_eq!(result.len(), 3);
+
+        let metrics = shuffle_reader_exec.metrics().unwrap();
+        assert_eq!(
+            metrics
+                .sum_by_name(SHUFFLE_ROWS_READ_METRIC)
+                .map(|metric| metric.as_usize()),
+            Some(6)
+        );
+        assert_eq!(
+            metrics
+                .sum_by_name(SHUFFLE_PARTITIONS_READ_METRIC)
+                .map(|metric| metric.as_usize()),
+            Some(1)
+        );
+
+        Ok(())
+    }
+
     async fn test_send_fetch_partitions(max_request_num: usize, partition_num: usize) {
         let schema = get_test_partition_schema();
         let data_array = Int32Array::from(vec![1]);
@@ -649,10 +820,16 @@ mod tests {
                     host: "localhost".to_string(),
                     port: 50051,
                     grpc_port: 50052,
-                    specification: ExecutorSpecification { task_slots: 12 },
+                    specification: ExecutorSpecification {
+                        task_slots: 12,
+                        ..Default::default()
+                    },
+                    pool: String::new(),
                 },
                 partition_stats: Default::default(),
                 path: path.clone(),
+                checksum: None,
+                checkpoint_path: None,
             })
             .collect()
     }
@@ -25,13 +25,16 @@ use datafusion::physical_plan::expressions::PhysicalSortExpr;
 use std::any::Any;
 use std::future::Future;
 use std::iter::Iterator;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Instant;
 
+use crate::execution_plans::{
+    ExportConfig, HashRepartitioner, ResultExport, ShuffleCodec, ShuffleFileWriter,
+};
 use crate::utils;
 
-use crate::serde::protobuf::ShuffleWritePartition;
+use crate::serde::protobuf::{ShuffleChecksum, ShuffleWritePartition};
 use crate::serde::scheduler::PartitionStats;
 use datafusion::arrow::array::{
     ArrayBuilder, ArrayRef, StringBuilder, StructBuilder, UInt32Builder, UInt64Builder,
@@ -40,7 +43,6 @@ use datafusion::arrow::datatypes::{DataType, Field, Schema, SchemaRef};
 
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::error::{DataFusionError, Result};
-use datafusion::physical_plan::common::IPCWriter;
 use datafusion::physical_plan::memory::MemoryStream;
 use datafusion::physical_plan::metrics::{
     self, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
@@ -53,9 +55,9 @@ use futures::{StreamExt, TryFutureExt, TryStreamExt};
 
 use datafusion::arrow::error::ArrowError;
 use datafusion::execution::context::TaskContext;
-use datafusion::physical_plan::repartition::BatchPartitioner;
 use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
 use log::{debug, info};
+use parking_lot::Mutex;
 
 /// ShuffleWriterExec represents a section of a query plan that has consistent partitioning and
 /// can be executed as one unit with each partition being executed in parallel. The output of each
@@ -73,6 +75,21 @@ pub struct ShuffleWriterExec {
     work_dir: String,
     /// Optional shuffle output partitioning
     shuffle_output_partitioning: Option<Partitioning>,
+    /// Encoding used for hash partitioned shuffle files
+    shuffle_codec: ShuffleCodec,
+    /// Size in bytes of the rows buffered per output partition before they are written to
+    /// hash partitioned shuffle files, 0 to write every `batch_size` rows
+    write_buffer_size: usize,
+    /// Whether to record the CRC32 checksum of the shuffle files, so that their readers can
+    /// detect corrupted files
+    checksum: bool,
+    /// Directory shared by the executors the shuffle files are copied to, so that they
+    /// outlive the executor writing them
+    checkpoint_dir: Option<String>,
+    /// Object store prefix the result files of the final stage of a job are exported to
+    result_export: Option<ResultExport>,
+    /// Schema of the batches written by the last execution of the shuffle write
+    written_schema: Arc<Mutex<Option<SchemaRef>>>,
     /// Execution metrics
     metrics: ExecutionPlanMetricsSet,
 }
@@ -84,6 +101,8 @@ struct ShuffleWriteMetrics {
     repart_time: metrics::Time,
     input_rows: metrics::Count,
     output_rows: metrics::Count,
+    /// Bytes written to shuffle files so far
+    output_bytes: metrics::Count,
 }
 
 impl ShuffleWriteMetrics {
@@ -96,11 +115,14 @@ impl ShuffleWriteMetrics {
 
         let output_rows = MetricBuilder::new(metrics).output_rows(partition);
 
+        let output_bytes = MetricBuilder::new(metrics).counter("output_bytes", partition);
+
         Self {
             write_time,
             repart_time,
             input_rows,
             output_rows,
+            output_bytes,
         }
     }
 }
@@ -120,10 +142,48 @@ impl ShuffleWriterExec {
             plan,
             work_dir,
             shuffle_output_partitioning,
+            shuffle_codec: ShuffleCodec::default(),
+            write_buffer_size: 0,
+            checksum: false,
+            checkpoint_dir: None,
+            result_export: None,
+            written_schema: Arc::new(Mutex::new(None)),
             metrics: ExecutionPlanMetricsSet::new(),
         })
     }
 
+    /// Set the encoding used for hash partitioned shuffle files
+    pub fn with_shuffle_codec(mut self, shuffle_codec: ShuffleCodec) -> Self {
+        self.shuffle_codec = shuffle_codec;
+        self
+    }
+
+    /// Buffer `write_buffer_size` bytes of rows per output partition before writing them
+    /// to hash partitioned shuffle files
+    pub fn with_write_buffer_size(mut self, write_buffer_size: usize) -> Self {
+        self.write_buffer_size = write_buffer_size;
+        self
+    }
+
+    /// Record the CRC32 checksum of the shuffle files in the partition locations
+    pub fn with_checksum(mut self, checksum: bool) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Copy the shuffle files to `checkpoint_dir`, a directory shared by the executors
+    pub fn with_checkpoint_dir(mut self, checkpoint_dir: Option<String>) -> Self {
+        self.checkpoint_dir = checkpoint_dir;
+        self
+    }
+
+    /// Export the result files of a final stage, which has no output partitioning, to an
+    /// object store prefix of the client
+    pub fn with_result_export(mut self, result_export: Option<ResultExport>) -> Self {
+        self.result_export = result_export;
+        self
+    }
+
     /// Get the Job ID for this query stage
     pub fn job_id(&self) -> &str {
         &self.job_id
@@ -139,6 +199,37 @@ impl ShuffleWriterExec {
         self.shuffle_output_partitioning.as_ref()
     }
 
+    /// Get the encoding used for hash partitioned shuffle files
+    pub fn shuffle_codec(&self) -> ShuffleCodec {
+        self.shuffle_codec
+    }
+
+    /// Get the size in bytes of the rows buffered per output partition
+    pub fn write_buffer_size(&self) -> usize {
+        self.write_buffer_size
+    }
+
+    /// Whether the CRC32 checksum of the shuffle files is recorded
+    pub fn checksum(&self) -> bool {
+        self.checksum
+    }
+
+    /// Get the directory the shuffle files are copied to, if any
+    pub fn checkpoint_dir(&self) -> Option<&str> {
+        self.checkpoint_dir.as_deref()
+    }
+
+    /// Get the object store prefix the result files are exported to, if any
+    pub fn result_export(&self) -> Option<&ResultExport> {
+        self.result_export.as_ref()
+    }
+
+    /// Get the schema of the batches written by the last execution of the shuffle write,
+    /// which may differ from the schema of the plan if an operator misbehaves
+    pub fn written_schema(&self) -> Option<SchemaRef> {
+        self.written_schema.lock().clone()
+    }
+
     pub fn execute_shuffle_write(
         &self,
         input_partition: usize,
@@ -147,21 +238,41 @@ impl ShuffleWriterExec {
         let mut path = PathBuf::from(&self.work_dir);
         path.push(&self.job_id);
         path.push(&format!("{}", self.stage_id));
+        let stage_dir = path.clone();
 
         let write_metrics = ShuffleWriteMetrics::new(input_partition, &self.metrics);
         let output_partitioning = self.shuffle_output_partitioning.clone();
+        let shuffle_codec = self.shuffle_codec;
+        let write_buffer_size = self.write_buffer_size;
+        let checksum = self.checksum;
+        let checkpoint_dir = self.checkpoint_dir.as_ref().map(|checkpoint_dir| {
+            let mut checkpoint_dir = PathBuf::from(checkpoint_dir);
+            checkpoint_dir.push(&self.job_id);
+            checkpoint_dir.push(&format!("{}", self.stage_id));
+            checkpoint_dir
+        });
+        let result_export = self.result_export.clone();
+        let job_id = self.job_id.clone();
         let plan = self.plan.clone();
+        let written_schema = self.written_schema.clone();
 
         async move {
             let now = Instant::now();
+            let batch_size = context.session_config().batch_size();
+            // Profiles and local root of the exports, none outside of the executors
+            let export_config = context
+                .session_config()
+                .get_extension::<ExportConfig>()
+                .unwrap_or_default();
             let mut stream = plan.execute(input_partition, context)?;
+            *written_schema.lock() = Some(stream.schema());
 
             match output_partitioning {
                 None => {
                     let timer = write_metrics.write_time.timer();
                     path.push(&format!("{input_partition}"));
-                    std::fs::create_dir_all(&path)?;
-                    path.push("data.arrow");
+                    utils::create_private_dir_all(&path)?;
+                    path.push(format!("data.{}", shuffle_codec.file_extension()));
                     let path = path.to_str().unwrap();
                     debug!("Writing results to {}", path);
 
@@ -169,10 +280,24 @@ impl ShuffleWriterExec {
                     let stats = utils::write_stream_to_disk(
                         &mut stream,
                         path,
+                        shuffle_codec,
                         &write_metrics.write_time,
                     )
                     .await
                     .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+                    let crc32 = shuffle_file_checksum(checksum, path)?;
+                    let checkpoint_path = checkpoint_shuffle_file(
+                        checkpoint_dir.as_deref(),
+                        &stage_dir,
+                        path,
+                    )?;
+                    if let Some(result_export) = &result_export {
+                        let url =
+                            result_export.partition_url(&job_id, input_partition, path);
+                        result_export
+                            .export_file(path, &url, &export_config)
+                            .await?;
+                    }
 
                     write_metrics
                         .input_rows
@@ -180,6 +305,9 @@ impl ShuffleWriterExec {
                     write_metrics
                         .output_rows
                         .add(stats.num_rows.unwrap_or(0) as usize);
+                    write_metrics
+                        .output_bytes
+                        .add(stats.num_bytes.unwrap_or(0) as usize);
                     timer.done();
 
                     info!(
@@ -195,61 +323,76 @@ impl ShuffleWriterExec {
                         num_batches: stats.num_batches.unwrap_or(0),
                         num_rows: stats.num_rows.unwrap_or(0),
                         num_bytes: stats.num_bytes.unwrap_or(0),
+                        checksum: crc32,
+                        checkpoint_path,
                     }])
                 }
 
                 Some(Partitioning::Hash(exprs, num_output_partitions)) => {
                     // we won't necessary produce output for every possible partition, so we
                     // create writers on demand
-                    let mut writers: Vec<Option<IPCWriter>> = vec![];
+                    let mut writers: Vec<Option<ShuffleFileWriter>> = vec![];
                     for _ in 0..num_output_partitions {
                         writers.push(None);
                     }
 
-                    let mut partitioner = BatchPartitioner::try_new(
-                        Partitioning::Hash(exprs, num_output_partitions),
+                    let schema = stream.schema();
+                    let mut partitioner = HashRepartitioner::new(
+                        exprs,
+                        num_output_partitions,
+                        schema.clone(),
+                        batch_size,
                         write_metrics.repart_time.clone(),
-                    )?;
+                    )
+                    .with_write_buffer_size(write_buffer_size);
+
+                    let mut write_output_batch = |output_partition: usize,
+                                                  output_batch: RecordBatch|
+                     -> Result<()> {
+                        let timer = write_metrics.write_time.timer();
+                        let num_bytes = match &mut writers[output_partition] {
+                            Some(w) => {
+                                let num_bytes = w.num_bytes();
+                                w.write(&output_batch)?;
+                                w.num_bytes() - num_bytes
+                            }
+                            None => {
+                                let mut path = path.clone();
+                                path.push(&format!("{output_partition}"));
+                                utils::create_private_dir_all(&path)?;
+
+                                path.push(format!(
+                                    "data-{input_partition}.{}",
+                                    shuffle_codec.file_extension()
+                                ));
+                                debug!("Writing results to {:?}", path);
+
+                                let mut writer = ShuffleFileWriter::try_new(
+                                    shuffle_codec,
+                                    &path,
+                                    schema.as_ref(),
+                                )?;
+
+                                writer.write(&output_batch)?;
+                                let num_bytes = writer.num_bytes();
+                                writers[output_partition] = Some(writer);
+                                num_bytes
+                            }
+                        };
+                        write_metrics.output_rows.add(output_batch.num_rows());
+                        write_metrics.output_bytes.add(num_bytes as usize);
+                        timer.done();
+                        Ok(())
+                    };
 
                     while let Some(result) = stream.next().await {
                         let input_batch = result?;
 
                         write_metrics.input_rows.add(input_batch.num_rows());
 
-                        partitioner.partition(
-                            input_batch,
-                            |output_partition, output_batch| {
-                                // partition func in datafusion make sure not write empty output_batch.
-                                let timer = write_metrics.write_time.timer();
-                                match &mut writers[output_partition] {
-                                    Some(w) => {
-                                        w.write(&output_batch)?;
-                                    }
-                                    None => {
-                                        let mut path = path.clone();
-                                        path.push(&format!("{output_partition}"));
-                                        std::fs::create_dir_all(&path)?;
-
-                                        path.push(format!(
-                                            "data-{input_partition}.arrow"
-                                        ));
-                                        debug!("Writing results to {:?}", path);
-
-                                        let mut writer = IPCWriter::new(
-                                            &path,
-                                            stream.schema().as_ref(),
-                                        )?;
-
-                                        writer.write(&output_batch)?;
-                                        writers[output_partition] = Some(writer);
-                                    }
-                                }
-                                write_metrics.output_rows.add(output_batch.num_rows());
-                                timer.done();
-                                Ok(())
-                            },
-                        )?;
+                        partitioner.partition(input_batch, &mut write_output_batch)?;
                     }
+                    partitioner.finish(&mut write_output_batch)?;
 
                     let mut part_locs = vec![];
 
@@ -261,17 +404,26 @@ impl ShuffleWriterExec {
                                     "Finished writing shuffle partition {} at {:?}. Batches: {}. Rows: {}. Bytes: {}.",
                                     i,
                                     w.path(),
-                                    w.num_batches,
-                                    w.num_rows,
-                                    w.num_bytes
+                                    w.num_batches(),
+                                    w.num_rows(),
+                                    w.num_bytes()
                                 );
 
+                                let path = w.path().to_string_lossy().to_string();
+                                let crc32 = shuffle_file_checksum(checksum, &path)?;
+                                let checkpoint_path = checkpoint_shuffle_file(
+                                    checkpoint_dir.as_deref(),
+                                    &stage_dir,
+                                    &path,
+                                )?;
                                 part_locs.push(ShuffleWritePartition {
                                     partition_id: i as u64,
-                                    path: w.path().to_string_lossy().to_string(),
-                                    num_batches: w.num_batches,
-                                    num_rows: w.num_rows,
-                                    num_bytes: w.num_bytes,
+                                    path,
+                                    num_batches: w.num_batches(),
+                                    num_rows: w.num_rows(),
+                                    num_bytes: w.num_bytes(),
+                                    checksum: crc32,
+                                    checkpoint_path,
                                 });
                             }
                             None => {}
@@ -288,6 +440,47 @@ impl ShuffleWriterExec {
     }
 }
 
+/// Compute the checksum of a finished shuffle file if checksums are enabled
+fn shuffle_file_checksum(checksum: bool, path: &str) -> Result<Option<ShuffleChecksum>> {
+    if !checksum {
+        return Ok(None);
+    }
+    let crc32 = utils::file_checksum(path)
+        .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+    Ok(Some(ShuffleChecksum { crc32 }))
+}
+
+/// Copy a finished shuffle file from the directory of its stage in the work dir to the same
+/// relative path in the checkpoint directory of the stage, returns the path of the copy or
+/// an empty string without checkpoint directory
+fn checkpoint_shuffle_file(
+    checkpoint_dir: Option<&Path>,
+    stage_dir: &Path,
+    path: &str,
+) -> Result<String> {
+    let checkpoint_dir = match checkpoint_dir {
+        Some(checkpoint_dir) => checkpoint_dir,
+        None => return Ok(String::new()),
+    };
+    let relative_path = Path::new(path).strip_prefix(stage_dir).map_err(|e| {
+        DataFusionError::Execution(format!(
+            "Shuffle file {path} is not in the stage directory {stage_dir:?}: {e}"
+        ))
+    })?;
+    let checkpoint_path = checkpoint_dir.join(relative_path);
+    if let Some(parent) = checkpoint_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Readers of the checkpoint only ever see complete files, also when several attempts of
+    // a task copy the same file
+    let in_progress_path =
+        checkpoint_path.with_extension(format!("{}.inprogress", uuid::Uuid::new_v4()));
+    std::fs::copy(path, &in_progress_path)?;
+    std::fs::rename(&in_progress_path, &checkpoint_path)?;
+    debug!("Checkpointed shuffle file {path} to {checkpoint_path:?}");
+    Ok(checkpoint_path.to_string_lossy().to_string())
+}
+
 impl ExecutionPlan for ShuffleWriterExec {
     fn as_any(&self) -> &dyn Any {
         self
@@ -317,13 +510,20 @@ impl ExecutionPlan for ShuffleWriterExec {
         self: Arc<Self>,
         children: Vec<Arc<dyn ExecutionPlan>>,
     ) -> Result<Arc<dyn ExecutionPlan>> {
-        Ok(Arc::new(ShuffleWriterExec::try_new(
-            self.job_id.clone(),
-            self.stage_id,
-            children[0].clone(),
-            self.work_dir.clone(),
-            self.shuffle_output_partitioning.clone(),
-        )?))
+        Ok(Arc::new(
+            ShuffleWriterExec::try_new(
+                self.job_id.clone(),
+                self.stage_id,
+                children[0].clone(),
+                self.work_dir.clone(),
+                self.shuffle_output_partitioning.clone(),
+            )?
+            .with_shuffle_codec(self.shuffle_codec)
+            .with_write_buffer_size(self.write_buffer_size)
+            .with_checksum(self.checksum)
+            .with_checkpoint_dir(self.checkpoint_dir.clone())
+            .with_result_export(self.result_export.clone()),
+        ))
     }
 
     fn execute(
@@ -426,6 +626,7 @@ fn result_schema() -> SchemaRef {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::execution_plans::ShuffleFileReader;
     use datafusion::arrow::array::{StringArray, StructArray, UInt32Array, UInt64Array};
     use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
     use datafusion::physical_plan::expressions::Column;
@@ -531,6 +732,129 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_checkpoint_dir() -> Result<()> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let input_plan = create_input_plan()?;
+        let work_dir = TempDir::new()?;
+        let checkpoint_dir = TempDir::new()?;
+        let query_stage = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            input_plan,
+            work_dir.path().to_str().unwrap().to_owned(),
+            Some(Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 2)),
+        )?
+        .with_checkpoint_dir(Some(checkpoint_dir.path().to_str().unwrap().to_owned()));
+        let partitions = query_stage.execute_shuffle_write(0, task_ctx).await?;
+        assert_eq!(2, partitions.len());
+
+        for partition in partitions {
+            let expected_path = checkpoint_dir
+                .path()
+                .join("jobOne")
+                .join("1")
+                .join(partition.partition_id.to_string())
+                .join("data-0.arrow");
+            assert_eq!(partition.checkpoint_path, expected_path.to_str().unwrap());
+            assert_eq!(
+                std::fs::read(&partition.path)?,
+                std::fs::read(&partition.checkpoint_path)?
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_row_codec() -> Result<()> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let input_plan = Arc::new(CoalescePartitionsExec::new(create_input_plan()?));
+        let work_dir = TempDir::new()?;
+        let query_stage = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            input_plan,
+            work_dir.into_path().to_str().unwrap().to_owned(),
+            Some(Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 2)),
+        )?
+        .with_shuffle_codec(ShuffleCodec::Row);
+        let mut stream = query_stage.execute(0, task_ctx)?;
+        let batches = utils::collect_stream(&mut stream)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        assert_eq!(query_stage.written_schema(), Some(query_stage.schema()));
+        let batch = &batches[0];
+        let path = batch.columns()[1]
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+
+        let mut num_rows = 0;
+        for path in path.iter().flatten() {
+            assert!(path.ends_with("data-0.rows"));
+            let reader = ShuffleFileReader::try_new(
+                std::fs::File::open(path)?,
+                ShuffleCodec::from_path(std::path::Path::new(path)),
+            )?;
+            assert_eq!(reader.schema(), query_stage.schema());
+            for batch in reader {
+                num_rows += batch?.num_rows();
+            }
+        }
+        assert_eq!(8, num_rows);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_buffer_size() -> Result<()> {
+        let session_ctx = SessionContext::new();
+        let task_ctx = session_ctx.task_ctx();
+
+        let input_plan = Arc::new(CoalescePartitionsExec::new(create_input_plan()?));
+        let work_dir = TempDir::new()?;
+        let query_stage = ShuffleWriterExec::try_new(
+            "jobOne".to_owned(),
+            1,
+            input_plan,
+            work_dir.into_path().to_str().unwrap().to_owned(),
+            Some(Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 2)),
+        )?
+        .with_write_buffer_size(1024 * 1024);
+        let mut stream = query_stage.execute(0, task_ctx)?;
+        let batches = utils::collect_stream(&mut stream)
+            .await
+            .map_err(|e| DataFusionError::Execution(format!("{e:?}")))?;
+        let stats = batches[0].columns()[2]
+            .as_any()
+            .downcast_ref::<StructArray>()
+            .unwrap();
+        let num_batches = stats
+            .column_by_name("num_batches")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        let num_rows = stats
+            .column_by_name("num_rows")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+
+        // all rows of an output partition are buffered into a single batch
+        assert_eq!(2, num_batches.len());
+        assert!(num_batches.iter().all(|n| n == Some(1)));
+        assert_eq!(8, num_rows.iter().flatten().sum::<u64>());
+
+        Ok(())
+    }
+
     fn create_input_plan() -> Result<Arc<dyn ExecutionPlan>> {
         let schema = Arc::new(Schema::new(vec![
             Field::new("a", DataType::UInt32, true),
@@ -15,9 +15,9 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::error::Result;
+use crate::error::{BallistaError, Result};
 use crate::plugin::udf::UDFPluginManager;
-use libloading::Library;
+use libloading::{Library, Symbol};
 use std::any::Any;
 use std::env;
 use std::sync::Arc;
@@ -44,6 +44,10 @@ pub trait Plugin {
 pub enum PluginEnum {
     /// UDF/UDAF plugin
     UDF,
+    /// Executor plugin, created by the executor from the libraries holding one
+    Executor,
+    /// Scheduler plugin, created by the scheduler from the libraries holding one
+    Scheduler,
 }
 
 impl PluginEnum {
@@ -51,10 +55,69 @@ impl PluginEnum {
     pub fn init_plugin_manager(&self) -> Box<dyn PluginRegistrar> {
         match self {
             PluginEnum::UDF => Box::<UDFPluginManager>::default(),
+            PluginEnum::Executor | PluginEnum::Scheduler => {
+                Box::<PluginLibraries>::default()
+            }
         }
     }
 }
 
+/// Registrar keeping the libraries of the plugins which are created by the crate using
+/// them, such as executor and scheduler plugins, with [PluginLibraries::create_plugins]
+#[derive(Default, Clone)]
+pub struct PluginLibraries {
+    /// All libraries of the plugin type loaded from the plugin dir
+    pub libraries: Vec<Arc<Library>>,
+}
+
+impl PluginLibraries {
+    /// Create the plugins of the libraries by calling the `fn() -> T` each library exports
+    /// as `registrar`, a nul terminated symbol name
+    ///
+    /// # Safety
+    /// `registrar` must be a function of that signature in every library
+    pub unsafe fn create_plugins<T>(
+        &self,
+        registrar: &[u8],
+    ) -> Result<Vec<LoadedPlugin<T>>> {
+        self.libraries
+            .iter()
+            .map(|library| {
+                let register: Symbol<fn() -> T> =
+                    library.get(registrar).map_err(|e| {
+                        BallistaError::General(format!(
+                            "Plugin registrar {} not found in the library: {e}",
+                            String::from_utf8_lossy(registrar).trim_end_matches('\0')
+                        ))
+                    })?;
+                Ok(LoadedPlugin {
+                    plugin: register(),
+                    _library: library.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl PluginRegistrar for PluginLibraries {
+    unsafe fn load(&mut self, library: Arc<Library>) -> Result<()> {
+        self.libraries.push(library);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A plugin created by a library loaded from the plugin dir, along with the library holding
+/// its code, which is dropped after the plugin
+pub struct LoadedPlugin<T> {
+    /// The plugin
+    pub plugin: T,
+    _library: Arc<Library>,
+}
+
 /// Every plugin need a PluginDeclaration
 #[derive(Copy, Clone)]
 pub struct PluginDeclaration {
@@ -23,7 +23,8 @@ use std::sync::{Arc, Mutex};
 use walkdir::{DirEntry, WalkDir};
 
 use crate::plugin::{
-    PluginDeclaration, PluginEnum, PluginRegistrar, CORE_VERSION, RUSTC_VERSION,
+    PluginDeclaration, PluginEnum, PluginLibraries, PluginRegistrar, CORE_VERSION,
+    RUSTC_VERSION,
 };
 use once_cell::sync::OnceCell;
 
@@ -43,6 +44,21 @@ pub fn global_plugin_manager(
     })
 }
 
+/// Load the libraries in `plugin_path` which were not loaded yet with the global plugin
+/// manager, e.g. the plugin dir of an executor or scheduler, returning the manager
+pub fn load_global_plugins(
+    plugin_path: &str,
+) -> Result<&'static Arc<Mutex<GlobalPluginManager>>> {
+    let gpm =
+        INSTANCE.get_or_init(|| Arc::new(Mutex::new(GlobalPluginManager::default())));
+    // Safety: the libraries are checked to be compiled by the same rustc against the same
+    // ballista-core before any of their code is called
+    unsafe {
+        gpm.lock().unwrap().load(plugin_path)?;
+    }
+    Ok(gpm)
+}
+
 #[derive(Default)]
 /// manager all plugin_type's plugin_manager
 pub struct GlobalPluginManager {
@@ -90,7 +106,14 @@ impl GlobalPluginManager {
             if dec.rustc_version != RUSTC_VERSION || dec.core_version != CORE_VERSION {
                 return Err(BallistaError::IoError(io::Error::new(
                     io::ErrorKind::Other,
-                    "Version mismatch",
+                    format!(
+                        "Version mismatch: {} was compiled with rustc {} against \
+                         ballista-core {}, expected rustc {RUSTC_VERSION} and \
+                         ballista-core {CORE_VERSION}",
+                        plugin_file.path().display(),
+                        dec.rustc_version,
+                        dec.core_version
+                    ),
                 )));
             }
 
@@ -111,22 +134,32 @@ impl GlobalPluginManager {
         Ok(())
     }
 
+    /// The libraries loaded for plugins of `plugin_type`, e.g. to create executor plugins
+    pub fn plugin_libraries(&self, plugin_type: PluginEnum) -> PluginLibraries {
+        self.plugin_managers
+            .get(&plugin_type)
+            .and_then(|manager| manager.as_any().downcast_ref::<PluginLibraries>())
+            .cloned()
+            .unwrap_or_default()
+    }
+
     /// get all plugin file in the dir
     fn get_all_plugin_files(&self, plugin_path: &str) -> io::Result<Vec<DirEntry>> {
         let mut plugin_files = Vec::new();
-        for entry in WalkDir::new(plugin_path).into_iter().filter_map(|e| {
-            let item = e.unwrap();
+        for entry in WalkDir::new(plugin_path).sort_by_file_name() {
+            // a missing or unreadable plugin dir is an error rather than a panic
+            let item = entry.map_err(io::Error::from)?;
             // every file only load once
             if self
                 .plugin_files
                 .contains(&item.path().to_str().unwrap().to_string())
             {
-                return None;
+                continue;
             }
 
             let file_type = item.file_type();
             if !file_type.is_file() {
-                return None;
+                continue;
             }
 
             if let Some(path) = item.path().extension() {
@@ -136,14 +169,10 @@ impl GlobalPluginManager {
                             "load plugin from library file:{}",
                             item.path().to_str().unwrap()
                         );
-                        return Some(item);
+                        plugin_files.push(item);
                     }
                 }
             }
-
-            None
-        }) {
-            plugin_files.push(entry);
         }
         Ok(plugin_files)
     }
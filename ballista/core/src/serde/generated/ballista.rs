@@ -37,6 +37,29 @@ pub struct ShuffleWriterExecNode {
     pub output_partitioning: ::core::option::Option<
         ::datafusion_proto::protobuf::PhysicalHashRepartition,
     >,
+    #[prost(enumeration = "ShuffleCodec", tag = "5")]
+    pub shuffle_codec: i32,
+    /// Bytes of rows buffered per output partition before writing, 0 to write every batch_size rows
+    #[prost(uint64, tag = "6")]
+    pub write_buffer_size: u64,
+    /// Whether the checksums of the shuffle files are computed for their readers to verify them
+    #[prost(bool, tag = "7")]
+    pub checksum: bool,
+    /// Directory shared by the executors the shuffle files are copied to, none if empty
+    #[prost(string, tag = "8")]
+    pub checkpoint_dir: ::prost::alloc::string::String,
+    /// Object store prefix the result files of a final stage are exported to, none if empty
+    #[prost(string, tag = "9")]
+    pub export_location: ::prost::alloc::string::String,
+    /// Options of the object store of the export location, without credentials
+    #[prost(map = "string, string", tag = "10")]
+    pub export_options: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    /// Export profile of the executor holding the credentials of the export location, none if empty
+    #[prost(string, tag = "11")]
+    pub export_profile: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -97,6 +120,38 @@ pub struct ExecutionGraph {
     pub end_time: u64,
     #[prost(uint64, tag = "13")]
     pub queued_at: u64,
+    /// Resources required by each task of a stage, keyed by stage ID
+    #[prost(map = "uint32, message", tag = "14")]
+    pub task_resources: ::std::collections::HashMap<u32, TaskResources>,
+    /// Whether the job runs the input of an EXPLAIN ANALYZE
+    #[prost(bool, tag = "15")]
+    pub explain_analyze: bool,
+    /// Labels attached to the job by its session
+    #[prost(map = "string, string", tag = "16")]
+    pub labels: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    /// Settings of the job overriding the ones of its session
+    #[prost(map = "string, string", tag = "17")]
+    pub settings: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+    /// ID of the group of the job, empty if it belongs to no group
+    #[prost(string, tag = "18")]
+    pub job_group: ::prost::alloc::string::String,
+    /// Attempts of the tasks of the job which finished
+    #[prost(message, repeated, tag = "19")]
+    pub task_attempts: ::prost::alloc::vec::Vec<TaskAttempt>,
+    /// Maximum number of tasks running at once of the stages annotated with a limit, keyed by
+    /// stage ID
+    #[prost(map = "uint32, uint32", tag = "20")]
+    pub stage_max_concurrent_tasks: ::std::collections::HashMap<u32, u32>,
+    /// Seconds the results of the job are kept once it succeeds, 0 for the default of the
+    /// scheduler
+    #[prost(uint64, tag = "21")]
+    pub result_ttl_seconds: u64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -106,6 +161,64 @@ pub struct StageAttempts {
     #[prost(uint32, repeated, tag = "2")]
     pub stage_attempt_num: ::prost::alloc::vec::Vec<u32>,
 }
+/// A queued job held back by the maximum number of concurrent jobs of its scheduler, saved so
+/// that the scheduler holds it again after a restart
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HeldJob {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub job_name: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub session_id: ::prost::alloc::string::String,
+    /// Logical plan of the job, encoded with the logical codec of the scheduler
+    #[prost(bytes = "vec", tag = "4")]
+    pub logical_plan: ::prost::alloc::vec::Vec<u8>,
+    #[prost(uint64, tag = "5")]
+    pub queued_at: u64,
+    /// Settings of the job overriding the ones of its session
+    #[prost(message, repeated, tag = "6")]
+    pub job_settings: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// Scheduler holding the job
+    #[prost(string, tag = "7")]
+    pub scheduler: ::prost::alloc::string::String,
+}
+/// An attempt to run the task of a partition of a stage
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskAttempt {
+    #[prost(uint32, tag = "1")]
+    pub task_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "3")]
+    pub stage_attempt_num: u32,
+    #[prost(uint32, tag = "4")]
+    pub partition_id: u32,
+    #[prost(string, tag = "5")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(enumeration = "TaskAttemptState", tag = "6")]
+    pub state: i32,
+    #[prost(uint64, tag = "7")]
+    pub launch_time: u64,
+    #[prost(uint64, tag = "8")]
+    pub start_exec_time: u64,
+    #[prost(uint64, tag = "9")]
+    pub end_exec_time: u64,
+    /// Time at which the scheduler learned that the attempt finished
+    #[prost(uint64, tag = "10")]
+    pub finish_time: u64,
+    /// Error of a failed attempt
+    #[prost(string, tag = "11")]
+    pub error: ::prost::alloc::string::String,
+    #[prost(bool, tag = "12")]
+    pub retryable: bool,
+    #[prost(message, repeated, tag = "13")]
+    pub metrics: ::prost::alloc::vec::Vec<OperatorMetricsSet>,
+    #[prost(message, optional, tag = "14")]
+    pub resource_usage: ::core::option::Option<TaskResourceUsage>,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutionGraphStage {
@@ -243,6 +356,8 @@ pub struct TaskInfo {
     /// Scheduler side finish time
     #[prost(uint64, tag = "7")]
     pub finish_time: u64,
+    #[prost(message, optional, tag = "11")]
+    pub resource_usage: ::core::option::Option<TaskResourceUsage>,
     #[prost(oneof = "task_info::Status", tags = "8, 9, 10")]
     pub status: ::core::option::Option<task_info::Status>,
 }
@@ -291,7 +406,7 @@ pub struct Action {
     /// configuration settings
     #[prost(message, repeated, tag = "100")]
     pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
-    #[prost(oneof = "action::ActionType", tags = "3")]
+    #[prost(oneof = "action::ActionType", tags = "3, 4")]
     pub action_type: ::core::option::Option<action::ActionType>,
 }
 /// Nested message and enum types in `Action`.
@@ -302,6 +417,9 @@ pub mod action {
         /// Fetch a partition from an executor
         #[prost(message, tag = "3")]
         FetchPartition(super::FetchPartition),
+        /// Fetch the log lines of a task attempt from the executor it ran on
+        #[prost(message, tag = "4")]
+        FetchTaskLogs(super::FetchTaskLogs),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -339,6 +457,80 @@ pub struct FetchPartition {
     pub host: ::prost::alloc::string::String,
     #[prost(uint32, tag = "6")]
     pub port: u32,
+    /// Checksum the partition file is verified against before it is streamed
+    #[prost(message, optional, tag = "7")]
+    pub checksum: ::core::option::Option<ShuffleChecksum>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FetchTaskLogs {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub task_id: u32,
+    #[prost(uint64, tag = "3")]
+    pub from_line: u64,
+    #[prost(bool, tag = "4")]
+    pub follow: bool,
+}
+/// Checksum of the content of a shuffle file
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ShuffleChecksum {
+    #[prost(uint32, tag = "1")]
+    pub crc32: u32,
+}
+/// Event of an interactive Flight SQL session, sent by the scheduler in the app_metadata of
+/// the DoExchange messages without data
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct InteractiveEvent {
+    /// Sequence number of the statement in the session, starting at 1
+    #[prost(uint64, tag = "1")]
+    pub statement_id: u64,
+    #[prost(oneof = "interactive_event::Event", tags = "2, 3, 4, 5")]
+    pub event: ::core::option::Option<interactive_event::Event>,
+}
+/// Nested message and enum types in `InteractiveEvent`.
+pub mod interactive_event {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "2")]
+        Started(super::StatementStarted),
+        #[prost(message, tag = "3")]
+        Progress(super::StatementProgress),
+        #[prost(message, tag = "4")]
+        Finished(super::StatementFinished),
+        #[prost(message, tag = "5")]
+        Failed(super::StatementFailed),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatementStarted {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatementProgress {
+    #[prost(uint32, tag = "1")]
+    pub num_stages: u32,
+    #[prost(uint32, tag = "2")]
+    pub completed_stages: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatementFinished {
+    #[prost(uint64, tag = "1")]
+    pub num_rows: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatementFailed {
+    #[prost(string, tag = "1")]
+    pub error: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -355,6 +547,12 @@ pub struct PartitionLocation {
     pub partition_stats: ::core::option::Option<PartitionStats>,
     #[prost(string, tag = "5")]
     pub path: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "6")]
+    pub checksum: ::core::option::Option<ShuffleChecksum>,
+    /// Path of the copy of the partition file in the checkpoint directory of the job, which
+    /// outlives the executor. Empty if the partition is not checkpointed.
+    #[prost(string, tag = "7")]
+    pub checkpoint_path: ::prost::alloc::string::String,
 }
 /// Unique identifier for a materialized partition of data
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -478,6 +676,9 @@ pub struct ExecutorMetadata {
     pub grpc_port: u32,
     #[prost(message, optional, tag = "5")]
     pub specification: ::core::option::Option<ExecutorSpecification>,
+    /// Pool the executor serves, empty for the default pool
+    #[prost(string, tag = "6")]
+    pub pool: ::prost::alloc::string::String,
 }
 /// Used by grpc
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -491,6 +692,9 @@ pub struct ExecutorRegistration {
     pub grpc_port: u32,
     #[prost(message, optional, tag = "5")]
     pub specification: ::core::option::Option<ExecutorSpecification>,
+    /// Pool the executor serves, empty for the default pool
+    #[prost(string, tag = "6")]
+    pub pool: ::prost::alloc::string::String,
     /// "optional" keyword is stable in protoc 3.15 but prost is still on 3.14 (see <https://github.com/tokio-rs/prost/issues/430> and <https://github.com/tokio-rs/prost/pull/455>)
     /// this syntax is ugly but is binary compatible with the "optional" keyword (see <https://stackoverflow.com/questions/42622015/how-to-define-an-optional-field-in-protobuf-3>)
     #[prost(oneof = "executor_registration::OptionalHost", tags = "2")]
@@ -519,12 +723,26 @@ pub struct ExecutorHeartbeat {
     pub metrics: ::prost::alloc::vec::Vec<ExecutorMetric>,
     #[prost(message, optional, tag = "4")]
     pub status: ::core::option::Option<ExecutorStatus>,
+    /// Progress of the tasks running on the executor
+    #[prost(message, repeated, tag = "5")]
+    pub task_progress: ::prost::alloc::vec::Vec<TaskProgress>,
+}
+/// Liveness of a scheduler sharing the cluster with other schedulers
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SchedulerHeartbeat {
+    /// Address of the scheduler, host:port
+    #[prost(string, tag = "1")]
+    pub scheduler_id: ::prost::alloc::string::String,
+    /// Unix epoch-based timestamp in seconds
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorMetric {
     /// TODO add more metrics
-    #[prost(oneof = "executor_metric::Metric", tags = "1")]
+    #[prost(oneof = "executor_metric::Metric", tags = "1, 2, 3")]
     pub metric: ::core::option::Option<executor_metric::Metric>,
 }
 /// Nested message and enum types in `ExecutorMetric`.
@@ -535,8 +753,52 @@ pub mod executor_metric {
     pub enum Metric {
         #[prost(uint64, tag = "1")]
         AvailableMemory(u64),
+        #[prost(message, tag = "2")]
+        DiskUsage(super::DiskUsage),
+        #[prost(message, tag = "3")]
+        CgroupStats(super::CgroupStats),
     }
 }
+/// Disk space of the work dirs of an executor
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DiskUsage {
+    /// Bytes free for the next tasks, in the work dir with the least free space unless the
+    /// executor chooses the work dir with the most free space for each task
+    #[prost(uint64, tag = "1")]
+    pub available_bytes: u64,
+    /// Total bytes of the file systems of the work dirs
+    #[prost(uint64, tag = "2")]
+    pub total_bytes: u64,
+    /// Whether the free space is below the watermark under which the executor refuses new tasks
+    #[prost(bool, tag = "3")]
+    pub below_watermark: bool,
+}
+/// CPU and memory usage of the cgroup v2 an executor runs in
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CgroupStats {
+    #[prost(uint64, tag = "1")]
+    pub cpu_usage_us: u64,
+    /// Enforcement periods of the CPU limit elapsed, and the ones in which the executor was
+    /// throttled for exceeding it
+    #[prost(uint64, tag = "2")]
+    pub cpu_periods: u64,
+    #[prost(uint64, tag = "3")]
+    pub cpu_throttled_periods: u64,
+    #[prost(uint64, tag = "4")]
+    pub cpu_throttled_us: u64,
+    #[prost(uint64, tag = "5")]
+    pub memory_bytes: u64,
+    /// Memory limit, 0 if the memory is not limited
+    #[prost(uint64, tag = "6")]
+    pub memory_max_bytes: u64,
+    /// Times the memory of the executor was reclaimed for exceeding its high limit
+    #[prost(uint64, tag = "7")]
+    pub memory_high_events: u64,
+    #[prost(uint64, tag = "8")]
+    pub memory_oom_kills: u64,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorStatus {
@@ -568,7 +830,7 @@ pub struct ExecutorSpecification {
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorResource {
     /// TODO add more resources
-    #[prost(oneof = "executor_resource::Resource", tags = "1")]
+    #[prost(oneof = "executor_resource::Resource", tags = "1, 2, 3, 4, 5")]
     pub resource: ::core::option::Option<executor_resource::Resource>,
 }
 /// Nested message and enum types in `ExecutorResource`.
@@ -579,6 +841,19 @@ pub mod executor_resource {
     pub enum Resource {
         #[prost(uint32, tag = "1")]
         TaskSlots(u32),
+        /// Memory available for running tasks, in bytes
+        #[prost(uint64, tag = "2")]
+        MemoryBytes(u64),
+        /// Virtual cores available for running tasks
+        #[prost(uint32, tag = "3")]
+        Vcores(u32),
+        /// Logical task slots handed out per hundred task slots, e.g. 150 to run one and a half
+        /// tasks per task slot. 0 for as many logical task slots as task slots
+        #[prost(uint32, tag = "4")]
+        TaskSlotOversubscriptionPercent(u32),
+        /// GPUs available for running the tasks of the stages requiring GPUs
+        #[prost(uint32, tag = "5")]
+        Gpus(u32),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -588,12 +863,59 @@ pub struct AvailableTaskSlots {
     pub executor_id: ::prost::alloc::string::String,
     #[prost(uint32, tag = "2")]
     pub slots: u32,
+    /// Available memory in bytes. u64::MAX if the executor does not limit task memory
+    #[prost(uint64, tag = "3")]
+    pub memory_bytes: u64,
+    /// Available virtual cores. u32::MAX if the executor does not limit task cores
+    #[prost(uint32, tag = "4")]
+    pub vcores: u32,
+    /// Task slots removed from the executor while they were reserved, withheld from the
+    /// reservations returning them
+    #[prost(uint32, tag = "5")]
+    pub withheld_slots: u32,
+    /// Available GPUs, 0 if the executor has none
+    #[prost(uint32, tag = "6")]
+    pub gpus: u32,
+}
+/// Resources required by a single task in addition to its task slot
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskResources {
+    #[prost(uint64, tag = "1")]
+    pub memory_bytes: u64,
+    #[prost(uint32, tag = "2")]
+    pub vcores: u32,
+    #[prost(uint32, tag = "3")]
+    pub gpus: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorTaskSlots {
     #[prost(message, repeated, tag = "1")]
     pub task_slots: ::prost::alloc::vec::Vec<AvailableTaskSlots>,
+    /// Leases of the reserved task slots which are neither filled with tasks nor cancelled yet
+    #[prost(message, repeated, tag = "2")]
+    pub leases: ::prost::alloc::vec::Vec<ReservationLease>,
+}
+/// Lease of a reserved task slot. The slot and its resources return to the available task slots
+/// when the lease expires, so that a scheduler crashing before launching tasks on its
+/// reservations or cancelling them does not lose the slots
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReservationLease {
+    #[prost(string, tag = "1")]
+    pub lease_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub memory_bytes: u64,
+    #[prost(uint32, tag = "4")]
+    pub vcores: u32,
+    /// Milliseconds since the epoch
+    #[prost(uint64, tag = "5")]
+    pub expires_at: u64,
+    #[prost(uint32, tag = "6")]
+    pub gpus: u32,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -611,6 +933,133 @@ pub struct ExecutorResourcePair {
     #[prost(message, optional, tag = "2")]
     pub available: ::core::option::Option<ExecutorResource>,
 }
+/// Input or decision of a scheduler about task slots, recorded to replay a scheduling sequence
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SchedulingRecord {
+    #[prost(oneof = "scheduling_record::Record", tags = "1, 2, 3, 4, 5, 6, 7")]
+    pub record: ::core::option::Option<scheduling_record::Record>,
+}
+/// Nested message and enum types in `SchedulingRecord`.
+pub mod scheduling_record {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Record {
+        #[prost(message, tag = "1")]
+        RegisterExecutor(super::RegisterExecutorRecord),
+        #[prost(message, tag = "2")]
+        RemoveExecutor(super::RemoveExecutorRecord),
+        #[prost(message, tag = "3")]
+        ReserveSlots(super::ReserveSlotsRecord),
+        #[prost(message, tag = "4")]
+        CancelReservations(super::CancelReservationsRecord),
+        #[prost(message, tag = "5")]
+        FillReservations(super::FillReservationsRecord),
+        #[prost(message, tag = "6")]
+        LaunchTasks(super::LaunchTasksRecord),
+        #[prost(message, tag = "7")]
+        ResizeExecutor(super::ResizeExecutorRecord),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RegisterExecutorRecord {
+    #[prost(message, optional, tag = "1")]
+    pub metadata: ::core::option::Option<ExecutorMetadata>,
+    #[prost(uint32, tag = "2")]
+    pub total_task_slots: u32,
+    #[prost(uint32, tag = "3")]
+    pub available_task_slots: u32,
+    #[prost(bool, tag = "4")]
+    pub reserve: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveExecutorRecord {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ResizeExecutorRecord {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    /// Task slots added to the executor, negative if task slots were removed
+    #[prost(int32, tag = "2")]
+    pub task_slots_delta: i32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReserveSlotsRecord {
+    #[prost(uint32, tag = "1")]
+    pub num_slots: u32,
+    #[prost(message, optional, tag = "2")]
+    pub resources: ::core::option::Option<TaskResources>,
+    /// Executors the slots could be reserved on
+    #[prost(string, repeated, tag = "3")]
+    pub executors: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Executors of the reserved slots, in the order they were reserved
+    #[prost(string, repeated, tag = "4")]
+    pub reserved: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Whether either all the slots or none were reserved
+    #[prost(bool, tag = "5")]
+    pub exact: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordedReservation {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub resources: ::core::option::Option<TaskResources>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelReservationsRecord {
+    #[prost(message, repeated, tag = "1")]
+    pub reservations: ::prost::alloc::vec::Vec<RecordedReservation>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordedTask {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "3")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "4")]
+    pub partition_id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FillReservationsRecord {
+    #[prost(message, repeated, tag = "1")]
+    pub offered: ::prost::alloc::vec::Vec<RecordedReservation>,
+    #[prost(message, repeated, tag = "2")]
+    pub assignments: ::prost::alloc::vec::Vec<RecordedTask>,
+    /// Number of offered reservations which were not filled
+    #[prost(uint32, tag = "3")]
+    pub unassigned: u32,
+    #[prost(uint64, tag = "4")]
+    pub pending_tasks: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LaunchTasksRecord {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub tasks: ::prost::alloc::vec::Vec<RecordedTask>,
+    /// Error launching the tasks, empty if they were launched
+    #[prost(string, tag = "3")]
+    pub error: ::prost::alloc::string::String,
+    /// Tasks which the executor did not accept
+    #[prost(message, repeated, tag = "4")]
+    pub rejected: ::prost::alloc::vec::Vec<RecordedTask>,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct RunningTask {
@@ -659,6 +1108,10 @@ pub struct SuccessfulTask {
     /// so we might want to think about some refactoring of the task definitions
     #[prost(message, repeated, tag = "2")]
     pub partitions: ::prost::alloc::vec::Vec<ShuffleWritePartition>,
+    /// Schema of the batches written by the task, unset if the task served shuffle files
+    /// written before
+    #[prost(message, optional, tag = "3")]
+    pub output_schema: ::core::option::Option<::datafusion_proto::protobuf::Schema>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -698,6 +1151,12 @@ pub struct ShuffleWritePartition {
     pub num_rows: u64,
     #[prost(uint64, tag = "5")]
     pub num_bytes: u64,
+    /// Checksum of the file, if the shuffle writer computes checksums
+    #[prost(message, optional, tag = "6")]
+    pub checksum: ::core::option::Option<ShuffleChecksum>,
+    /// Path of the copy of the file in the checkpoint directory, empty if not checkpointed
+    #[prost(string, tag = "7")]
+    pub checkpoint_path: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -720,6 +1179,8 @@ pub struct TaskStatus {
     pub end_exec_time: u64,
     #[prost(message, repeated, tag = "12")]
     pub metrics: ::prost::alloc::vec::Vec<OperatorMetricsSet>,
+    #[prost(message, optional, tag = "13")]
+    pub resource_usage: ::core::option::Option<TaskResourceUsage>,
     #[prost(oneof = "task_status::Status", tags = "9, 10, 11")]
     pub status: ::core::option::Option<task_status::Status>,
 }
@@ -736,6 +1197,31 @@ pub mod task_status {
         Successful(super::SuccessfulTask),
     }
 }
+/// Resources used by a task while it was executed
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskResourceUsage {
+    /// CPU time spent in user mode, in microseconds
+    #[prost(uint64, tag = "1")]
+    pub user_cpu_time_us: u64,
+    /// CPU time spent in kernel mode, in microseconds
+    #[prost(uint64, tag = "2")]
+    pub sys_cpu_time_us: u64,
+    /// Highest amount of memory reserved at once by the task
+    #[prost(uint64, tag = "3")]
+    pub peak_memory_bytes: u64,
+    #[prost(uint64, tag = "4")]
+    pub spilled_bytes: u64,
+    /// Batch size the task was executed with
+    #[prost(uint64, tag = "5")]
+    pub batch_size: u64,
+    /// Time elapsed since the task started, in microseconds
+    #[prost(uint64, tag = "6")]
+    pub wall_clock_time_us: u64,
+    /// Rows written to the output partitions of the task
+    #[prost(uint64, tag = "7")]
+    pub output_rows: u64,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct PollWorkParams {
@@ -746,6 +1232,9 @@ pub struct PollWorkParams {
     /// All tasks must be reported until they reach the failed or completed state
     #[prost(message, repeated, tag = "3")]
     pub task_status: ::prost::alloc::vec::Vec<TaskStatus>,
+    /// Tasks running on the executor, for a restarted scheduler to reconcile its jobs with
+    #[prost(message, repeated, tag = "4")]
+    pub running_tasks: ::prost::alloc::vec::Vec<RunningTaskInfo>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -770,6 +1259,10 @@ pub struct TaskDefinition {
     pub launch_time: u64,
     #[prost(message, repeated, tag = "11")]
     pub props: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// User owning the job of the task, the only one the executor serves the logs of the task
+    /// to. Empty for jobs without an owner.
+    #[prost(string, tag = "12")]
+    pub owner: ::prost::alloc::string::String,
 }
 /// A set of tasks in the same stage
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -791,37 +1284,88 @@ pub struct MultiTaskDefinition {
     pub launch_time: u64,
     #[prost(message, repeated, tag = "9")]
     pub props: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// User owning the job of the tasks, empty for none
+    #[prost(string, tag = "10")]
+    pub owner: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SessionSettings {
     #[prost(message, repeated, tag = "1")]
     pub configs: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// Seconds since the epoch at which the session was last used
+    #[prost(uint64, tag = "2")]
+    pub last_accessed: u64,
 }
+/// Table registered with CREATE EXTERNAL TABLE, shared by the sessions of all clients
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct JobSessionConfig {
+pub struct TableDefinition {
     #[prost(string, tag = "1")]
-    pub session_id: ::prost::alloc::string::String,
-    #[prost(message, repeated, tag = "2")]
-    pub configs: ::prost::alloc::vec::Vec<KeyValuePair>,
+    pub name: ::prost::alloc::string::String,
+    /// Serialized CREATE EXTERNAL TABLE plan with the location, format and options of the table
+    /// and the schema resolved when it was registered
+    #[prost(bytes = "vec", tag = "2")]
+    pub plan: ::prost::alloc::vec::Vec<u8>,
+    /// Seconds since the epoch at which the table was dropped, 0 if it was not. Dropped tables
+    /// are kept for the retention window of the scheduler and can be restored with UNDROP TABLE.
+    #[prost(uint64, tag = "3")]
+    pub dropped_at: u64,
 }
+/// Tables and prepared statements of a session, which any scheduler serving the session
+/// restores, such as the session of a Flight SQL bearer token
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct PollWorkResult {
+pub struct SessionObjects {
+    /// Tables created with CREATE EXTERNAL TABLE in the session only
     #[prost(message, repeated, tag = "1")]
-    pub tasks: ::prost::alloc::vec::Vec<TaskDefinition>,
+    pub tables: ::prost::alloc::vec::Vec<TableDefinition>,
+    #[prost(message, repeated, tag = "2")]
+    pub prepared_statements: ::prost::alloc::vec::Vec<PreparedStatementDefinition>,
+    /// Random secret of the bearer tokens of the Flight SQL clients of the session, none if empty
+    #[prost(string, tag = "3")]
+    pub token_secret: ::prost::alloc::string::String,
+    /// User who created the session, the only one allowed to close it, none if empty
+    #[prost(string, tag = "4")]
+    pub owner: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct RegisterExecutorParams {
-    #[prost(message, optional, tag = "1")]
-    pub metadata: ::core::option::Option<ExecutorRegistration>,
+pub struct PreparedStatementDefinition {
+    #[prost(bytes = "vec", tag = "1")]
+    pub handle: ::prost::alloc::vec::Vec<u8>,
+    /// SQL of the statement, planned again by the schedulers which did not prepare it
+    #[prost(string, tag = "2")]
+    pub query: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct RegisterExecutorResult {
-    #[prost(bool, tag = "1")]
+pub struct JobSessionConfig {
+    #[prost(string, tag = "1")]
+    pub session_id: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub configs: ::prost::alloc::vec::Vec<KeyValuePair>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PollWorkResult {
+    #[prost(message, repeated, tag = "1")]
+    pub tasks: ::prost::alloc::vec::Vec<TaskDefinition>,
+    /// Logical task slots the executor hands out to tasks, changed at runtime with the
+    /// oversubscription of its task slots. 0 if unknown
+    #[prost(uint32, tag = "2")]
+    pub task_slots: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RegisterExecutorParams {
+    #[prost(message, optional, tag = "1")]
+    pub metadata: ::core::option::Option<ExecutorRegistration>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RegisterExecutorResult {
+    #[prost(bool, tag = "1")]
     pub success: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -835,6 +1379,43 @@ pub struct HeartBeatParams {
     pub status: ::core::option::Option<ExecutorStatus>,
     #[prost(message, optional, tag = "4")]
     pub metadata: ::core::option::Option<ExecutorRegistration>,
+    /// Statuses of finished tasks curated by the scheduler, reported along with the heartbeat
+    #[prost(message, repeated, tag = "5")]
+    pub task_status: ::prost::alloc::vec::Vec<TaskStatus>,
+    #[prost(message, repeated, tag = "6")]
+    pub task_progress: ::prost::alloc::vec::Vec<TaskProgress>,
+    /// Task slots the executor currently advertises, which change when they are resized while it
+    /// runs. 0 for the task slots it registered with
+    #[prost(uint32, tag = "7")]
+    pub task_slots: u32,
+}
+/// Progress of a running task, reported in the heartbeats of its executor
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskProgress {
+    #[prost(uint32, tag = "1")]
+    pub task_id: u32,
+    #[prost(string, tag = "2")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "3")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "4")]
+    pub partition_id: u32,
+    /// Operator furthest from the scans of the plan of the task which produced rows so far
+    #[prost(string, tag = "5")]
+    pub operator: ::prost::alloc::string::String,
+    /// Rows produced so far by the operator
+    #[prost(uint64, tag = "6")]
+    pub output_rows: u64,
+    /// Bytes written to the shuffle files of the task so far
+    #[prost(uint64, tag = "7")]
+    pub output_bytes: u64,
+    /// Time since the task started, in milliseconds
+    #[prost(uint64, tag = "8")]
+    pub elapsed_ms: u64,
+    /// Time since the progress of the task last changed, in milliseconds
+    #[prost(uint64, tag = "9")]
+    pub idle_ms: u64,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -870,6 +1451,28 @@ pub struct ExecutorStoppedParams {
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct ExecutorStoppedResult {}
+/// Sent by an executor on startup with the shuffle files of the jobs left in its work dirs
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecoverExecutorDataParams {
+    #[prost(message, optional, tag = "1")]
+    pub metadata: ::core::option::Option<ExecutorRegistration>,
+    /// ID of the executor before it restarted, empty if it is unknown
+    #[prost(string, tag = "2")]
+    pub previous_executor_id: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "3")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Paths of the intact shuffle files
+    #[prost(string, repeated, tag = "4")]
+    pub shuffle_files: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecoverExecutorDataResult {
+    /// Jobs which are no longer active, whose data is removed by the executor
+    #[prost(string, repeated, tag = "1")]
+    pub orphaned_job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct UpdateTaskStatusParams {
@@ -890,12 +1493,31 @@ pub struct UpdateTaskStatusResult {
 pub struct ExecuteQueryParams {
     #[prost(message, repeated, tag = "4")]
     pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// Settings overriding the ones of the session for this query only
+    #[prost(message, repeated, tag = "6")]
+    pub job_settings: ::prost::alloc::vec::Vec<KeyValuePair>,
+    /// Return the existing job instead of failing when a job with the given job ID already
+    /// exists, so that retried submissions do not run the query again
+    #[prost(bool, tag = "7")]
+    pub idempotent: bool,
+    /// User the query runs on behalf of, for service accounts such as query gateways submitting
+    /// the queries of their users. Empty to run the query as the authenticated client.
+    #[prost(string, tag = "8")]
+    pub run_as: ::prost::alloc::string::String,
+    /// Whether the client reports the result partitions it fetched with GetJobStatus. The
+    /// result buffer of the job is disabled otherwise, as its final stage would wait forever.
+    #[prost(bool, tag = "9")]
+    pub reports_consumed_partitions: bool,
     #[prost(oneof = "execute_query_params::Query", tags = "1, 2")]
     pub query: ::core::option::Option<execute_query_params::Query>,
     #[prost(oneof = "execute_query_params::OptionalSessionId", tags = "3")]
     pub optional_session_id: ::core::option::Option<
         execute_query_params::OptionalSessionId,
     >,
+    /// ID of the job to run the query as, generated by the scheduler if not given. It has to
+    /// be unique in the cluster.
+    #[prost(oneof = "execute_query_params::OptionalJobId", tags = "5")]
+    pub optional_job_id: ::core::option::Option<execute_query_params::OptionalJobId>,
 }
 /// Nested message and enum types in `ExecuteQueryParams`.
 pub mod execute_query_params {
@@ -913,6 +1535,14 @@ pub mod execute_query_params {
         #[prost(string, tag = "3")]
         SessionId(::prost::alloc::string::String),
     }
+    /// ID of the job to run the query as, generated by the scheduler if not given. It has to
+    /// be unique in the cluster.
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum OptionalJobId {
+        #[prost(string, tag = "5")]
+        JobId(::prost::alloc::string::String),
+    }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -930,9 +1560,86 @@ pub struct ExecuteQueryResult {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EstimateQueryParams {
+    #[prost(string, tag = "1")]
+    pub sql: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
+    #[prost(oneof = "estimate_query_params::OptionalSessionId", tags = "2")]
+    pub optional_session_id: ::core::option::Option<
+        estimate_query_params::OptionalSessionId,
+    >,
+}
+/// Nested message and enum types in `EstimateQueryParams`.
+pub mod estimate_query_params {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum OptionalSessionId {
+        #[prost(string, tag = "2")]
+        SessionId(::prost::alloc::string::String),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct EstimateQueryResult {
+    #[prost(uint32, tag = "2")]
+    pub stage_count: u32,
+    #[prost(uint64, tag = "3")]
+    pub task_count: u64,
+    /// Expected time in seconds the tasks of the query hold task slots for
+    #[prost(double, tag = "4")]
+    pub slot_seconds: f64,
+    /// Number of recently completed jobs with a similar plan the slot seconds are based on
+    #[prost(uint32, tag = "5")]
+    pub similar_jobs: u32,
+    #[prost(string, tag = "6")]
+    pub session_id: ::prost::alloc::string::String,
+    /// Bytes scanned from the sources of the query, unset if their statistics are unknown
+    #[prost(oneof = "estimate_query_result::OptionalScannedBytes", tags = "1")]
+    pub optional_scanned_bytes: ::core::option::Option<
+        estimate_query_result::OptionalScannedBytes,
+    >,
+}
+/// Nested message and enum types in `EstimateQueryResult`.
+pub mod estimate_query_result {
+    /// Bytes scanned from the sources of the query, unset if their statistics are unknown
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum OptionalScannedBytes {
+        #[prost(uint64, tag = "1")]
+        ScannedBytes(u64),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetResultsPageParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Index of the first row of the page in the job's results
+    #[prost(uint64, tag = "2")]
+    pub offset: u64,
+    /// Maximum number of rows in the page
+    #[prost(uint64, tag = "3")]
+    pub limit: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetResultsPageResult {
+    /// Rows of the page encoded as an Arrow IPC stream, empty if the offset is past the last row
+    #[prost(bytes = "vec", tag = "1")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// Number of rows in all the job's results
+    #[prost(uint64, tag = "2")]
+    pub total_rows: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetJobStatusParams {
     #[prost(string, tag = "1")]
     pub job_id: ::prost::alloc::string::String,
+    /// Map partitions of the final stage whose output the client has fetched
+    #[prost(uint32, repeated, tag = "2")]
+    pub consumed_partitions: ::prost::alloc::vec::Vec<u32>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -945,6 +1652,16 @@ pub struct SuccessfulJob {
     pub started_at: u64,
     #[prost(uint64, tag = "4")]
     pub ended_at: u64,
+    /// Plan of the stages annotated with their metrics, for jobs running an EXPLAIN ANALYZE
+    #[prost(string, tag = "5")]
+    pub analyzed_plan: ::prost::alloc::string::String,
+    /// Whether the result partitions were deleted once their time to live elapsed
+    #[prost(bool, tag = "6")]
+    pub results_expired: bool,
+    /// ID of the job whose results the job shares, when they were served from the result cache
+    /// or computed once for identical concurrent queries
+    #[prost(string, tag = "7")]
+    pub shared_job_id: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -962,6 +1679,9 @@ pub struct RunningJob {
     pub started_at: u64,
     #[prost(string, tag = "3")]
     pub scheduler: ::prost::alloc::string::String,
+    /// Output of the final stage produced so far, only set when the job buffers its results
+    #[prost(message, repeated, tag = "4")]
+    pub partition_location: ::prost::alloc::vec::Vec<PartitionLocation>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -977,12 +1697,23 @@ pub struct FailedJob {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelledJob {
+    #[prost(uint64, tag = "1")]
+    pub queued_at: u64,
+    /// 0 if the job was cancelled before it started running
+    #[prost(uint64, tag = "2")]
+    pub started_at: u64,
+    #[prost(uint64, tag = "3")]
+    pub ended_at: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct JobStatus {
     #[prost(string, tag = "5")]
     pub job_id: ::prost::alloc::string::String,
     #[prost(string, tag = "6")]
     pub job_name: ::prost::alloc::string::String,
-    #[prost(oneof = "job_status::Status", tags = "1, 2, 3, 4")]
+    #[prost(oneof = "job_status::Status", tags = "1, 2, 3, 4, 7")]
     pub status: ::core::option::Option<job_status::Status>,
 }
 /// Nested message and enum types in `JobStatus`.
@@ -998,6 +1729,8 @@ pub mod job_status {
         Failed(super::FailedJob),
         #[prost(message, tag = "4")]
         Successful(super::SuccessfulJob),
+        #[prost(message, tag = "7")]
+        Cancelled(super::CancelledJob),
     }
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
@@ -1008,6 +1741,22 @@ pub struct GetJobStatusResult {
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskAttemptsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Only return the attempts of the tasks of this stage, all stages if 0
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTaskAttemptsResult {
+    /// The finished and running attempts, ordered by stage, partition and task ID
+    #[prost(message, repeated, tag = "1")]
+    pub attempts: ::prost::alloc::vec::Vec<TaskAttempt>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetFileMetadataParams {
     #[prost(string, tag = "1")]
     pub path: ::prost::alloc::string::String,
@@ -1049,84 +1798,480 @@ pub struct CleanJobDataParams {
 pub struct CleanJobDataResult {}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct LaunchTaskParams {
-    /// Allow to launch a task set to an executor at once
-    #[prost(message, repeated, tag = "1")]
-    pub tasks: ::prost::alloc::vec::Vec<TaskDefinition>,
-    #[prost(string, tag = "2")]
-    pub scheduler_id: ::prost::alloc::string::String,
+pub struct SubscribeJobEventsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct LaunchMultiTaskParams {
-    /// Allow to launch a task set to an executor at once
-    #[prost(message, repeated, tag = "1")]
-    pub multi_tasks: ::prost::alloc::vec::Vec<MultiTaskDefinition>,
-    #[prost(string, tag = "2")]
-    pub scheduler_id: ::prost::alloc::string::String,
+pub struct GetTaskLogsParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// ID of the task attempt, unique within its job
+    #[prost(uint32, tag = "2")]
+    pub task_id: u32,
+    /// Number of the first line to return, to skip the lines fetched already
+    #[prost(uint64, tag = "3")]
+    pub from_line: u64,
+    /// Keep streaming the lines the task logs until it finishes
+    #[prost(bool, tag = "4")]
+    pub follow: bool,
 }
+/// Lines logged by a task attempt while it ran, numbered from 0
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct LaunchTaskResult {
-    /// TODO when part of the task set are scheduled successfully
-    #[prost(bool, tag = "1")]
-    pub success: bool,
+pub struct TaskLogLines {
+    /// Number of the first line, the first lines of long logs being dropped by the executor
+    #[prost(uint64, tag = "1")]
+    pub first_line: u64,
+    #[prost(string, repeated, tag = "2")]
+    pub lines: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// The task finished, so that no more lines follow
+    #[prost(bool, tag = "3")]
+    pub finished: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct LaunchMultiTaskResult {
-    /// TODO when part of the task set are scheduled successfully
+pub struct JobEvent {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Milliseconds since the epoch at which the event was produced
+    #[prost(uint64, tag = "2")]
+    pub timestamp: u64,
+    #[prost(oneof = "job_event::Event", tags = "3, 4, 5, 6")]
+    pub event: ::core::option::Option<job_event::Event>,
+}
+/// Nested message and enum types in `JobEvent`.
+pub mod job_event {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Event {
+        #[prost(message, tag = "3")]
+        StageStarted(super::StageStartedEvent),
+        #[prost(message, tag = "4")]
+        StageCompleted(super::StageCompletedEvent),
+        #[prost(message, tag = "5")]
+        TaskFailed(super::TaskFailedEvent),
+        /// Final status of the job, the last event of a subscription
+        #[prost(message, tag = "6")]
+        JobCompleted(super::JobStatus),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StageStartedEvent {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub partitions: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StageCompletedEvent {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TaskFailedEvent {
+    #[prost(uint32, tag = "1")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "2")]
+    pub partition_id: u32,
+    #[prost(uint32, tag = "3")]
+    pub task_id: u32,
+    #[prost(string, tag = "4")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub error: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CloseSessionParams {
+    #[prost(string, tag = "1")]
+    pub session_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CloseSessionResult {
+    /// Whether the session existed
     #[prost(bool, tag = "1")]
-    pub success: bool,
+    pub closed: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct CancelTasksParams {
-    #[prost(message, repeated, tag = "1")]
-    pub task_infos: ::prost::alloc::vec::Vec<RunningTaskInfo>,
+pub struct RegisterTableParams {
+    /// Session in which the table is resolved
+    #[prost(string, tag = "1")]
+    pub session_id: ::prost::alloc::string::String,
+    /// Serialized CREATE EXTERNAL TABLE plan
+    #[prost(bytes = "vec", tag = "2")]
+    pub plan: ::prost::alloc::vec::Vec<u8>,
+    /// Settings of the session, which is created again if it was removed
+    #[prost(message, repeated, tag = "3")]
+    pub settings: ::prost::alloc::vec::Vec<KeyValuePair>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct CancelTasksResult {
+pub struct RegisterTableResult {
+    /// Whether the table was registered, false if a table of the same name exists and the plan
+    /// is IF NOT EXISTS
     #[prost(bool, tag = "1")]
-    pub cancelled: bool,
+    pub registered: bool,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct RemoveJobDataParams {
+pub struct ListJobsParams {
+    /// Only list the jobs with all of these labels
+    #[prost(map = "string, string", tag = "1")]
+    pub labels: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct JobSummary {
     #[prost(string, tag = "1")]
     pub job_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub job_name: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub status: ::core::option::Option<JobStatus>,
+    #[prost(map = "string, string", tag = "4")]
+    pub labels: ::std::collections::HashMap<
+        ::prost::alloc::string::String,
+        ::prost::alloc::string::String,
+    >,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct RemoveJobDataResult {}
+pub struct ListJobsResult {
+    #[prost(message, repeated, tag = "1")]
+    pub jobs: ::prost::alloc::vec::Vec<JobSummary>,
+}
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
-pub struct RunningTaskInfo {
-    #[prost(uint32, tag = "1")]
-    pub task_id: u32,
-    #[prost(string, tag = "2")]
-    pub job_id: ::prost::alloc::string::String,
+pub struct GetJobGroupStatusParams {
+    #[prost(string, tag = "1")]
+    pub group_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetJobGroupStatusResult {
+    /// Jobs of the group, including the queued jobs which are not planned yet
+    #[prost(message, repeated, tag = "1")]
+    pub jobs: ::prost::alloc::vec::Vec<JobSummary>,
+    #[prost(uint32, tag = "2")]
+    pub queued_jobs: u32,
     #[prost(uint32, tag = "3")]
-    pub stage_id: u32,
+    pub running_jobs: u32,
     #[prost(uint32, tag = "4")]
-    pub partition_id: u32,
+    pub successful_jobs: u32,
+    #[prost(uint32, tag = "5")]
+    pub failed_jobs: u32,
+    /// Stages of the planned jobs of the group, and how many of them completed
+    #[prost(uint32, tag = "6")]
+    pub num_stages: u32,
+    #[prost(uint32, tag = "7")]
+    pub completed_stages: u32,
+    #[prost(uint32, tag = "8")]
+    pub cancelled_jobs: u32,
 }
-/// Generated client implementations.
-pub mod scheduler_grpc_client {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
-    #[derive(Debug, Clone)]
-    pub struct SchedulerGrpcClient<T> {
-        inner: tonic::client::Grpc<T>,
-    }
-    impl SchedulerGrpcClient<tonic::transport::Channel> {
-        /// Attempt to create a new client by connecting to a given endpoint.
-        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
-        where
-            D: TryInto<tonic::transport::Endpoint>,
-            D::Error: Into<StdError>,
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelJobGroupParams {
+    #[prost(string, tag = "1")]
+    pub group_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelJobGroupResult {
+    /// IDs of the unfinished jobs of the group which were cancelled
+    #[prost(string, repeated, tag = "1")]
+    pub job_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DropTableParams {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// Whether to succeed if there is no shared table of that name
+    #[prost(bool, tag = "2")]
+    pub if_exists: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DropTableResult {
+    /// Whether the table was dropped, false if there is no shared table of that name and the
+    /// statement is IF EXISTS
+    #[prost(bool, tag = "1")]
+    pub dropped: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UndropTableParams {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UndropTableResult {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTablesParams {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetTablesResult {
+    #[prost(message, repeated, tag = "1")]
+    pub tables: ::prost::alloc::vec::Vec<TableDefinition>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetClusterConfigParams {}
+/// Defaults and capabilities of the cluster, for clients to validate queries before submitting
+/// them
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ClusterConfig {
+    /// Default number of partitions of the shuffles of a session
+    #[prost(uint64, tag = "1")]
+    pub default_shuffle_partitions: u64,
+    /// Default batch size of a session
+    #[prost(uint64, tag = "2")]
+    pub default_batch_size: u64,
+    /// Formats of the files which tables can be created for with CREATE EXTERNAL TABLE
+    #[prost(string, repeated, tag = "3")]
+    pub file_formats: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// File types of the table provider factories registered with the sessions
+    #[prost(string, repeated, tag = "4")]
+    pub table_factories: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// Whether the scheduler serves the REST API of the web UI under /api, which
+    /// security-sensitive deployments disable to only serve gRPC
+    #[prost(bool, tag = "6")]
+    pub rest_api: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetClusterConfigResult {
+    #[prost(message, optional, tag = "1")]
+    pub config: ::core::option::Option<ClusterConfig>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DrainParams {
+    /// Seconds to wait for unfinished jobs before the scheduler exits, 0 to use the configured
+    /// drain timeout of the scheduler
+    #[prost(uint64, tag = "1")]
+    pub timeout_seconds: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DrainResult {
+    /// Number of jobs which were unfinished when draining started
+    #[prost(uint64, tag = "1")]
+    pub unfinished_jobs: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DecommissionExecutorParams {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    /// Seconds to wait for the jobs using the executor before it is removed, 0 to wait until
+    /// they complete
+    #[prost(uint64, tag = "2")]
+    pub timeout_seconds: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DecommissionExecutorResult {
+    /// Number of active jobs running tasks on the executor or reading its shuffle data
+    #[prost(uint64, tag = "1")]
+    pub active_jobs: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LaunchTaskParams {
+    /// Allow to launch a task set to an executor at once
+    #[prost(message, repeated, tag = "1")]
+    pub tasks: ::prost::alloc::vec::Vec<TaskDefinition>,
+    #[prost(string, tag = "2")]
+    pub scheduler_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LaunchMultiTaskParams {
+    /// Allow to launch a task set to an executor at once
+    #[prost(message, repeated, tag = "1")]
+    pub multi_tasks: ::prost::alloc::vec::Vec<MultiTaskDefinition>,
+    #[prost(string, tag = "2")]
+    pub scheduler_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LaunchTaskResult {
+    /// TODO when part of the task set are scheduled successfully
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct LaunchMultiTaskResult {
+    /// Whether all the tasks were accepted
+    #[prost(bool, tag = "1")]
+    pub success: bool,
+    /// Tasks which the executor did not accept, the other tasks were accepted
+    #[prost(message, repeated, tag = "2")]
+    pub rejected_tasks: ::prost::alloc::vec::Vec<RejectedTask>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RejectedTask {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub stage_id: u32,
+    #[prost(message, optional, tag = "3")]
+    pub task_id: ::core::option::Option<TaskId>,
+    #[prost(string, tag = "4")]
+    pub reason: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelTasksParams {
+    #[prost(message, repeated, tag = "1")]
+    pub task_infos: ::prost::alloc::vec::Vec<RunningTaskInfo>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelTasksResult {
+    #[prost(bool, tag = "1")]
+    pub cancelled: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveJobDataParams {
+    #[prost(string, tag = "1")]
+    pub job_id: ::prost::alloc::string::String,
+    /// Directory shared by the executors the stage outputs of the job were checkpointed to,
+    /// empty if they were not checkpointed
+    #[prost(string, tag = "2")]
+    pub checkpoint_dir: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveJobDataResult {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetTaskSlotsParams {
+    #[prost(string, tag = "1")]
+    pub executor_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "2")]
+    pub task_slots: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetTaskSlotsResult {
+    /// Task slots the executor advertised before
+    #[prost(uint32, tag = "1")]
+    pub previous_task_slots: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetRunningTasksParams {}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetRunningTasksResult {
+    #[prost(message, repeated, tag = "1")]
+    pub task_infos: ::prost::alloc::vec::Vec<RunningTaskInfo>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RunningTaskInfo {
+    #[prost(uint32, tag = "1")]
+    pub task_id: u32,
+    #[prost(string, tag = "2")]
+    pub job_id: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "3")]
+    pub stage_id: u32,
+    #[prost(uint32, tag = "4")]
+    pub partition_id: u32,
+}
+/// Encoding used for the shuffle files written by a ShuffleWriterExec
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum ShuffleCodec {
+    ArrowIpc = 0,
+    Row = 1,
+    /// Arrow IPC streaming format, writing each dictionary once for the batches sharing it
+    ArrowIpcStream = 2,
+}
+impl ShuffleCodec {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            ShuffleCodec::ArrowIpc => "ARROW_IPC",
+            ShuffleCodec::Row => "ROW",
+            ShuffleCodec::ArrowIpcStream => "ARROW_IPC_STREAM",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ARROW_IPC" => Some(Self::ArrowIpc),
+            "ROW" => Some(Self::Row),
+            "ARROW_IPC_STREAM" => Some(Self::ArrowIpcStream),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum TaskAttemptState {
+    AttemptRunning = 0,
+    AttemptSuccessful = 1,
+    AttemptFailed = 2,
+}
+impl TaskAttemptState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            TaskAttemptState::AttemptRunning => "ATTEMPT_RUNNING",
+            TaskAttemptState::AttemptSuccessful => "ATTEMPT_SUCCESSFUL",
+            TaskAttemptState::AttemptFailed => "ATTEMPT_FAILED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ATTEMPT_RUNNING" => Some(Self::AttemptRunning),
+            "ATTEMPT_SUCCESSFUL" => Some(Self::AttemptSuccessful),
+            "ATTEMPT_FAILED" => Some(Self::AttemptFailed),
+            _ => None,
+        }
+    }
+}
+/// Generated client implementations.
+pub mod scheduler_grpc_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    #[derive(Debug, Clone)]
+    pub struct SchedulerGrpcClient<T> {
+        inner: tonic::client::Grpc<T>,
+    }
+    impl SchedulerGrpcClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
         {
             let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
             Ok(Self::new(conn))
@@ -1393,12 +2538,12 @@ pub mod scheduler_grpc_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-        /// Used by Executor to tell Scheduler it is stopped.
-        pub async fn executor_stopped(
+        /// All the attempts of the tasks of a job, to debug the tasks which failed and were retried
+        pub async fn get_task_attempts(
             &mut self,
-            request: impl tonic::IntoRequest<super::ExecutorStoppedParams>,
+            request: impl tonic::IntoRequest<super::GetTaskAttemptsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Response<super::GetTaskAttemptsResult>,
             tonic::Status,
         > {
             self.inner
@@ -1412,20 +2557,44 @@ pub mod scheduler_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped",
+                "/ballista.protobuf.SchedulerGrpc/GetTaskAttempts",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "ExecutorStopped"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetTaskAttempts"),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn cancel_job(
+        /// List the active jobs, optionally only the ones with the given labels
+        pub async fn list_jobs(
             &mut self,
-            request: impl tonic::IntoRequest<super::CancelJobParams>,
+            request: impl tonic::IntoRequest<super::ListJobsParams>,
+        ) -> std::result::Result<tonic::Response<super::ListJobsResult>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ListJobs",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "ListJobs"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Estimate the cost of a query without executing it
+        pub async fn estimate_query(
+            &mut self,
+            request: impl tonic::IntoRequest<super::EstimateQueryParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CancelJobResult>,
+            tonic::Response<super::EstimateQueryResult>,
             tonic::Status,
         > {
             self.inner
@@ -1439,18 +2608,21 @@ pub mod scheduler_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.SchedulerGrpc/CancelJob",
+                "/ballista.protobuf.SchedulerGrpc/EstimateQuery",
             );
             let mut req = request.into_request();
             req.extensions_mut()
-                .insert(GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "CancelJob"));
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "EstimateQuery"),
+                );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn clean_job_data(
+        /// Read a page of the results of a successful job without executing it again
+        pub async fn get_results_page(
             &mut self,
-            request: impl tonic::IntoRequest<super::CleanJobDataParams>,
+            request: impl tonic::IntoRequest<super::GetResultsPageParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CleanJobDataResult>,
+            tonic::Response<super::GetResultsPageResult>,
             tonic::Status,
         > {
             self.inner
@@ -1464,107 +2636,80 @@ pub mod scheduler_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.SchedulerGrpc/CleanJobData",
+                "/ballista.protobuf.SchedulerGrpc/GetResultsPage",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "CleanJobData"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetResultsPage"),
                 );
             self.inner.unary(req, path, codec).await
         }
-    }
-}
-/// Generated client implementations.
-pub mod executor_grpc_client {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    use tonic::codegen::http::Uri;
-    #[derive(Debug, Clone)]
-    pub struct ExecutorGrpcClient<T> {
-        inner: tonic::client::Grpc<T>,
-    }
-    impl ExecutorGrpcClient<tonic::transport::Channel> {
-        /// Attempt to create a new client by connecting to a given endpoint.
-        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
-        where
-            D: TryInto<tonic::transport::Endpoint>,
-            D::Error: Into<StdError>,
-        {
-            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
-            Ok(Self::new(conn))
-        }
-    }
-    impl<T> ExecutorGrpcClient<T>
-    where
-        T: tonic::client::GrpcService<tonic::body::BoxBody>,
-        T::Error: Into<StdError>,
-        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
-        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
-    {
-        pub fn new(inner: T) -> Self {
-            let inner = tonic::client::Grpc::new(inner);
-            Self { inner }
-        }
-        pub fn with_origin(inner: T, origin: Uri) -> Self {
-            let inner = tonic::client::Grpc::with_origin(inner, origin);
-            Self { inner }
-        }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> ExecutorGrpcClient<InterceptedService<T, F>>
-        where
-            F: tonic::service::Interceptor,
-            T::ResponseBody: Default,
-            T: tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-                Response = http::Response<
-                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
-                >,
-            >,
-            <T as tonic::codegen::Service<
-                http::Request<tonic::body::BoxBody>,
-            >>::Error: Into<StdError> + Send + Sync,
-        {
-            ExecutorGrpcClient::new(InterceptedService::new(inner, interceptor))
-        }
-        /// Compress requests with the given encoding.
-        ///
-        /// This requires the server to support it otherwise it might respond with an
-        /// error.
-        #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.send_compressed(encoding);
-            self
-        }
-        /// Enable decompressing responses.
-        #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.inner = self.inner.accept_compressed(encoding);
-            self
-        }
-        /// Limits the maximum size of a decoded message.
-        ///
-        /// Default: `4MB`
-        #[must_use]
-        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_decoding_message_size(limit);
-            self
+        /// Used by Executor to tell Scheduler it is stopped.
+        pub async fn executor_stopped(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExecutorStoppedParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "ExecutorStopped"),
+                );
+            self.inner.unary(req, path, codec).await
         }
-        /// Limits the maximum size of an encoded message.
-        ///
-        /// Default: `usize::MAX`
-        #[must_use]
-        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.inner = self.inner.max_encoding_message_size(limit);
-            self
+        /// Used by a restarted executor to reuse the shuffle files left in its work dirs and to
+        /// learn which of them belong to jobs no longer active
+        pub async fn recover_executor_data(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecoverExecutorDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RecoverExecutorDataResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/RecoverExecutorData",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "RecoverExecutorData",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
         }
-        pub async fn launch_task(
+        pub async fn cancel_job(
             &mut self,
-            request: impl tonic::IntoRequest<super::LaunchTaskParams>,
+            request: impl tonic::IntoRequest<super::CancelJobParams>,
         ) -> std::result::Result<
-            tonic::Response<super::LaunchTaskResult>,
+            tonic::Response<super::CancelJobResult>,
             tonic::Status,
         > {
             self.inner
@@ -1578,18 +2723,18 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/LaunchTask",
+                "/ballista.protobuf.SchedulerGrpc/CancelJob",
             );
             let mut req = request.into_request();
             req.extensions_mut()
-                .insert(GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchTask"));
+                .insert(GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "CancelJob"));
             self.inner.unary(req, path, codec).await
         }
-        pub async fn launch_multi_task(
+        pub async fn get_job_group_status(
             &mut self,
-            request: impl tonic::IntoRequest<super::LaunchMultiTaskParams>,
+            request: impl tonic::IntoRequest<super::GetJobGroupStatusParams>,
         ) -> std::result::Result<
-            tonic::Response<super::LaunchMultiTaskResult>,
+            tonic::Response<super::GetJobGroupStatusResult>,
             tonic::Status,
         > {
             self.inner
@@ -1603,20 +2748,23 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/LaunchMultiTask",
+                "/ballista.protobuf.SchedulerGrpc/GetJobGroupStatus",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchMultiTask"),
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "GetJobGroupStatus",
+                    ),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn stop_executor(
+        pub async fn cancel_job_group(
             &mut self,
-            request: impl tonic::IntoRequest<super::StopExecutorParams>,
+            request: impl tonic::IntoRequest<super::CancelJobGroupParams>,
         ) -> std::result::Result<
-            tonic::Response<super::StopExecutorResult>,
+            tonic::Response<super::CancelJobGroupResult>,
             tonic::Status,
         > {
             self.inner
@@ -1630,20 +2778,20 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/StopExecutor",
+                "/ballista.protobuf.SchedulerGrpc/CancelJobGroup",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "StopExecutor"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "CancelJobGroup"),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn cancel_tasks(
+        pub async fn clean_job_data(
             &mut self,
-            request: impl tonic::IntoRequest<super::CancelTasksParams>,
+            request: impl tonic::IntoRequest<super::CleanJobDataParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CancelTasksResult>,
+            tonic::Response<super::CleanJobDataResult>,
             tonic::Status,
         > {
             self.inner
@@ -1657,20 +2805,21 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/CancelTasks",
+                "/ballista.protobuf.SchedulerGrpc/CleanJobData",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "CancelTasks"),
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "CleanJobData"),
                 );
             self.inner.unary(req, path, codec).await
         }
-        pub async fn remove_job_data(
+        /// Stream the progress of a job until it completes instead of polling GetJobStatus
+        pub async fn subscribe_job_events(
             &mut self,
-            request: impl tonic::IntoRequest<super::RemoveJobDataParams>,
+            request: impl tonic::IntoRequest<super::SubscribeJobEventsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::RemoveJobDataResult>,
+            tonic::Response<tonic::codec::Streaming<super::JobEvent>>,
             tonic::Status,
         > {
             self.inner
@@ -1684,132 +2833,337 @@ pub mod executor_grpc_client {
                 })?;
             let codec = tonic::codec::ProstCodec::default();
             let path = http::uri::PathAndQuery::from_static(
-                "/ballista.protobuf.ExecutorGrpc/RemoveJobData",
+                "/ballista.protobuf.SchedulerGrpc/SubscribeJobEvents",
             );
             let mut req = request.into_request();
             req.extensions_mut()
                 .insert(
-                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "RemoveJobData"),
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "SubscribeJobEvents",
+                    ),
                 );
-            self.inner.unary(req, path, codec).await
+            self.inner.server_streaming(req, path, codec).await
         }
-    }
-}
-/// Generated server implementations.
-pub mod scheduler_grpc_server {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with SchedulerGrpcServer.
-    #[async_trait]
-    pub trait SchedulerGrpc: Send + Sync + 'static {
-        /// Executors must poll the scheduler for heartbeat and to receive tasks
-        async fn poll_work(
-            &self,
-            request: tonic::Request<super::PollWorkParams>,
-        ) -> std::result::Result<tonic::Response<super::PollWorkResult>, tonic::Status>;
-        async fn register_executor(
-            &self,
-            request: tonic::Request<super::RegisterExecutorParams>,
+        /// Fetch the log lines of a task attempt from the executor it ran on, then with follow the
+        /// lines it logs until it finishes
+        pub async fn get_task_logs(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetTaskLogsParams>,
         ) -> std::result::Result<
-            tonic::Response<super::RegisterExecutorResult>,
+            tonic::Response<tonic::codec::Streaming<super::TaskLogLines>>,
             tonic::Status,
-        >;
-        /// Push-based task scheduler will only leverage this interface
-        /// rather than the PollWork interface to report executor states
-        async fn heart_beat_from_executor(
-            &self,
-            request: tonic::Request<super::HeartBeatParams>,
-        ) -> std::result::Result<tonic::Response<super::HeartBeatResult>, tonic::Status>;
-        async fn update_task_status(
-            &self,
-            request: tonic::Request<super::UpdateTaskStatusParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/GetTaskLogs",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetTaskLogs"),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        /// Stop accepting queries, let unfinished jobs complete and then shut down the scheduler
+        pub async fn drain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DrainParams>,
+        ) -> std::result::Result<tonic::Response<super::DrainResult>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/Drain",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "Drain"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Stop offering the task slots of an executor, wait for the jobs using it to complete and
+        /// then stop and remove it
+        pub async fn decommission_executor(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DecommissionExecutorParams>,
         ) -> std::result::Result<
-            tonic::Response<super::UpdateTaskStatusResult>,
+            tonic::Response<super::DecommissionExecutorResult>,
             tonic::Status,
-        >;
-        async fn get_file_metadata(
-            &self,
-            request: tonic::Request<super::GetFileMetadataParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/DecommissionExecutor",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "DecommissionExecutor",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Remove a session which the client does not use anymore
+        pub async fn close_session(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CloseSessionParams>,
         ) -> std::result::Result<
-            tonic::Response<super::GetFileMetadataResult>,
+            tonic::Response<super::CloseSessionResult>,
             tonic::Status,
-        >;
-        async fn execute_query(
-            &self,
-            request: tonic::Request<super::ExecuteQueryParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/CloseSession",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "CloseSession"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Share a table created with CREATE EXTERNAL TABLE with the sessions of all clients
+        pub async fn register_table(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RegisterTableParams>,
         ) -> std::result::Result<
-            tonic::Response<super::ExecuteQueryResult>,
+            tonic::Response<super::RegisterTableResult>,
             tonic::Status,
-        >;
-        async fn get_job_status(
-            &self,
-            request: tonic::Request<super::GetJobStatusParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/RegisterTable",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "RegisterTable"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_tables(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetTablesParams>,
         ) -> std::result::Result<
-            tonic::Response<super::GetJobStatusResult>,
+            tonic::Response<super::GetTablesResult>,
             tonic::Status,
-        >;
-        /// Used by Executor to tell Scheduler it is stopped.
-        async fn executor_stopped(
-            &self,
-            request: tonic::Request<super::ExecutorStoppedParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/GetTables",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "GetTables"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Drop a shared table, which is kept for the retention window of the scheduler
+        pub async fn drop_table(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DropTableParams>,
         ) -> std::result::Result<
-            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Response<super::DropTableResult>,
             tonic::Status,
-        >;
-        async fn cancel_job(
-            &self,
-            request: tonic::Request<super::CancelJobParams>,
-        ) -> std::result::Result<tonic::Response<super::CancelJobResult>, tonic::Status>;
-        async fn clean_job_data(
-            &self,
-            request: tonic::Request<super::CleanJobDataParams>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/DropTable",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "DropTable"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Restore a shared table dropped within the retention window
+        pub async fn undrop_table(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UndropTableParams>,
         ) -> std::result::Result<
-            tonic::Response<super::CleanJobDataResult>,
+            tonic::Response<super::UndropTableResult>,
             tonic::Status,
-        >;
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/UndropTable",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.SchedulerGrpc", "UndropTable"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Defaults and capabilities of the cluster
+        pub async fn get_cluster_config(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetClusterConfigParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetClusterConfigResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.SchedulerGrpc/GetClusterConfig",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "ballista.protobuf.SchedulerGrpc",
+                        "GetClusterConfig",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
     }
-    #[derive(Debug)]
-    pub struct SchedulerGrpcServer<T: SchedulerGrpc> {
-        inner: _Inner<T>,
-        accept_compression_encodings: EnabledCompressionEncodings,
-        send_compression_encodings: EnabledCompressionEncodings,
-        max_decoding_message_size: Option<usize>,
-        max_encoding_message_size: Option<usize>,
+}
+/// Generated client implementations.
+pub mod executor_grpc_client {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    use tonic::codegen::http::Uri;
+    #[derive(Debug, Clone)]
+    pub struct ExecutorGrpcClient<T> {
+        inner: tonic::client::Grpc<T>,
     }
-    struct _Inner<T>(Arc<T>);
-    impl<T: SchedulerGrpc> SchedulerGrpcServer<T> {
+    impl ExecutorGrpcClient<tonic::transport::Channel> {
+        /// Attempt to create a new client by connecting to a given endpoint.
+        pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+        where
+            D: TryInto<tonic::transport::Endpoint>,
+            D::Error: Into<StdError>,
+        {
+            let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+            Ok(Self::new(conn))
+        }
+    }
+    impl<T> ExecutorGrpcClient<T>
+    where
+        T: tonic::client::GrpcService<tonic::body::BoxBody>,
+        T::Error: Into<StdError>,
+        T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+        <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+    {
         pub fn new(inner: T) -> Self {
-            Self::from_arc(Arc::new(inner))
+            let inner = tonic::client::Grpc::new(inner);
+            Self { inner }
         }
-        pub fn from_arc(inner: Arc<T>) -> Self {
-            let inner = _Inner(inner);
-            Self {
-                inner,
-                accept_compression_encodings: Default::default(),
-                send_compression_encodings: Default::default(),
-                max_decoding_message_size: None,
-                max_encoding_message_size: None,
-            }
+        pub fn with_origin(inner: T, origin: Uri) -> Self {
+            let inner = tonic::client::Grpc::with_origin(inner, origin);
+            Self { inner }
         }
         pub fn with_interceptor<F>(
             inner: T,
             interceptor: F,
-        ) -> InterceptedService<Self, F>
+        ) -> ExecutorGrpcClient<InterceptedService<T, F>>
         where
             F: tonic::service::Interceptor,
+            T::ResponseBody: Default,
+            T: tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+                Response = http::Response<
+                    <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                >,
+            >,
+            <T as tonic::codegen::Service<
+                http::Request<tonic::body::BoxBody>,
+            >>::Error: Into<StdError> + Send + Sync,
         {
-            InterceptedService::new(Self::new(inner), interceptor)
+            ExecutorGrpcClient::new(InterceptedService::new(inner, interceptor))
         }
-        /// Enable decompressing requests with the given encoding.
+        /// Compress requests with the given encoding.
+        ///
+        /// This requires the server to support it otherwise it might respond with an
+        /// error.
         #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.accept_compression_encodings.enable(encoding);
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.send_compressed(encoding);
             self
         }
-        /// Compress responses with the given encoding, if the client supports it.
+        /// Enable decompressing responses.
         #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.send_compression_encodings.enable(encoding);
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.inner = self.inner.accept_compressed(encoding);
             self
         }
         /// Limits the maximum size of a decoded message.
@@ -1817,7 +3171,7 @@ pub mod scheduler_grpc_server {
         /// Default: `4MB`
         #[must_use]
         pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
-            self.max_decoding_message_size = Some(limit);
+            self.inner = self.inner.max_decoding_message_size(limit);
             self
         }
         /// Limits the maximum size of an encoded message.
@@ -1825,46 +3179,1192 @@ pub mod scheduler_grpc_server {
         /// Default: `usize::MAX`
         #[must_use]
         pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
-            self.max_encoding_message_size = Some(limit);
+            self.inner = self.inner.max_encoding_message_size(limit);
             self
         }
-    }
-    impl<T, B> tonic::codegen::Service<http::Request<B>> for SchedulerGrpcServer<T>
-    where
-        T: SchedulerGrpc,
-        B: Body + Send + 'static,
-        B::Error: Into<StdError> + Send + 'static,
-    {
-        type Response = http::Response<tonic::body::BoxBody>;
-        type Error = std::convert::Infallible;
-        type Future = BoxFuture<Self::Response, Self::Error>;
-        fn poll_ready(
+        pub async fn launch_task(
             &mut self,
-            _cx: &mut Context<'_>,
-        ) -> Poll<std::result::Result<(), Self::Error>> {
-            Poll::Ready(Ok(()))
+            request: impl tonic::IntoRequest<super::LaunchTaskParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::LaunchTaskResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/LaunchTask",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchTask"));
+            self.inner.unary(req, path, codec).await
         }
-        fn call(&mut self, req: http::Request<B>) -> Self::Future {
-            let inner = self.inner.clone();
-            match req.uri().path() {
-                "/ballista.protobuf.SchedulerGrpc/PollWork" => {
+        pub async fn launch_multi_task(
+            &mut self,
+            request: impl tonic::IntoRequest<super::LaunchMultiTaskParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::LaunchMultiTaskResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/LaunchMultiTask",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "LaunchMultiTask"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn stop_executor(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StopExecutorParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::StopExecutorResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/StopExecutor",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "StopExecutor"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn cancel_tasks(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CancelTasksParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelTasksResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/CancelTasks",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "CancelTasks"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn remove_job_data(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RemoveJobDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveJobDataResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/RemoveJobData",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "RemoveJobData"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// Change the task slots the executor advertises to the scheduler without restarting it
+        pub async fn set_task_slots(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetTaskSlotsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetTaskSlotsResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/SetTaskSlots",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "SetTaskSlots"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        /// The tasks running on the executor, for a restarted scheduler to reconcile its jobs with
+        pub async fn get_running_tasks(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetRunningTasksParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetRunningTasksResult>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/ballista.protobuf.ExecutorGrpc/GetRunningTasks",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("ballista.protobuf.ExecutorGrpc", "GetRunningTasks"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod scheduler_grpc_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with SchedulerGrpcServer.
+    #[async_trait]
+    pub trait SchedulerGrpc: Send + Sync + 'static {
+        /// Executors must poll the scheduler for heartbeat and to receive tasks
+        async fn poll_work(
+            &self,
+            request: tonic::Request<super::PollWorkParams>,
+        ) -> std::result::Result<tonic::Response<super::PollWorkResult>, tonic::Status>;
+        async fn register_executor(
+            &self,
+            request: tonic::Request<super::RegisterExecutorParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RegisterExecutorResult>,
+            tonic::Status,
+        >;
+        /// Push-based task scheduler will only leverage this interface
+        /// rather than the PollWork interface to report executor states
+        async fn heart_beat_from_executor(
+            &self,
+            request: tonic::Request<super::HeartBeatParams>,
+        ) -> std::result::Result<tonic::Response<super::HeartBeatResult>, tonic::Status>;
+        async fn update_task_status(
+            &self,
+            request: tonic::Request<super::UpdateTaskStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UpdateTaskStatusResult>,
+            tonic::Status,
+        >;
+        async fn get_file_metadata(
+            &self,
+            request: tonic::Request<super::GetFileMetadataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetFileMetadataResult>,
+            tonic::Status,
+        >;
+        async fn execute_query(
+            &self,
+            request: tonic::Request<super::ExecuteQueryParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecuteQueryResult>,
+            tonic::Status,
+        >;
+        async fn get_job_status(
+            &self,
+            request: tonic::Request<super::GetJobStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetJobStatusResult>,
+            tonic::Status,
+        >;
+        /// All the attempts of the tasks of a job, to debug the tasks which failed and were retried
+        async fn get_task_attempts(
+            &self,
+            request: tonic::Request<super::GetTaskAttemptsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetTaskAttemptsResult>,
+            tonic::Status,
+        >;
+        /// List the active jobs, optionally only the ones with the given labels
+        async fn list_jobs(
+            &self,
+            request: tonic::Request<super::ListJobsParams>,
+        ) -> std::result::Result<tonic::Response<super::ListJobsResult>, tonic::Status>;
+        /// Estimate the cost of a query without executing it
+        async fn estimate_query(
+            &self,
+            request: tonic::Request<super::EstimateQueryParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::EstimateQueryResult>,
+            tonic::Status,
+        >;
+        /// Read a page of the results of a successful job without executing it again
+        async fn get_results_page(
+            &self,
+            request: tonic::Request<super::GetResultsPageParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetResultsPageResult>,
+            tonic::Status,
+        >;
+        /// Used by Executor to tell Scheduler it is stopped.
+        async fn executor_stopped(
+            &self,
+            request: tonic::Request<super::ExecutorStoppedParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExecutorStoppedResult>,
+            tonic::Status,
+        >;
+        /// Used by a restarted executor to reuse the shuffle files left in its work dirs and to
+        /// learn which of them belong to jobs no longer active
+        async fn recover_executor_data(
+            &self,
+            request: tonic::Request<super::RecoverExecutorDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RecoverExecutorDataResult>,
+            tonic::Status,
+        >;
+        async fn cancel_job(
+            &self,
+            request: tonic::Request<super::CancelJobParams>,
+        ) -> std::result::Result<tonic::Response<super::CancelJobResult>, tonic::Status>;
+        async fn get_job_group_status(
+            &self,
+            request: tonic::Request<super::GetJobGroupStatusParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetJobGroupStatusResult>,
+            tonic::Status,
+        >;
+        async fn cancel_job_group(
+            &self,
+            request: tonic::Request<super::CancelJobGroupParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CancelJobGroupResult>,
+            tonic::Status,
+        >;
+        async fn clean_job_data(
+            &self,
+            request: tonic::Request<super::CleanJobDataParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CleanJobDataResult>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the SubscribeJobEvents method.
+        type SubscribeJobEventsStream: futures_core::Stream<
+                Item = std::result::Result<super::JobEvent, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Stream the progress of a job until it completes instead of polling GetJobStatus
+        async fn subscribe_job_events(
+            &self,
+            request: tonic::Request<super::SubscribeJobEventsParams>,
+        ) -> std::result::Result<
+            tonic::Response<Self::SubscribeJobEventsStream>,
+            tonic::Status,
+        >;
+        /// Server streaming response type for the GetTaskLogs method.
+        type GetTaskLogsStream: futures_core::Stream<
+                Item = std::result::Result<super::TaskLogLines, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        /// Fetch the log lines of a task attempt from the executor it ran on, then with follow the
+        /// lines it logs until it finishes
+        async fn get_task_logs(
+            &self,
+            request: tonic::Request<super::GetTaskLogsParams>,
+        ) -> std::result::Result<
+            tonic::Response<Self::GetTaskLogsStream>,
+            tonic::Status,
+        >;
+        /// Stop accepting queries, let unfinished jobs complete and then shut down the scheduler
+        async fn drain(
+            &self,
+            request: tonic::Request<super::DrainParams>,
+        ) -> std::result::Result<tonic::Response<super::DrainResult>, tonic::Status>;
+        /// Stop offering the task slots of an executor, wait for the jobs using it to complete and
+        /// then stop and remove it
+        async fn decommission_executor(
+            &self,
+            request: tonic::Request<super::DecommissionExecutorParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::DecommissionExecutorResult>,
+            tonic::Status,
+        >;
+        /// Remove a session which the client does not use anymore
+        async fn close_session(
+            &self,
+            request: tonic::Request<super::CloseSessionParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::CloseSessionResult>,
+            tonic::Status,
+        >;
+        /// Share a table created with CREATE EXTERNAL TABLE with the sessions of all clients
+        async fn register_table(
+            &self,
+            request: tonic::Request<super::RegisterTableParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::RegisterTableResult>,
+            tonic::Status,
+        >;
+        async fn get_tables(
+            &self,
+            request: tonic::Request<super::GetTablesParams>,
+        ) -> std::result::Result<tonic::Response<super::GetTablesResult>, tonic::Status>;
+        /// Drop a shared table, which is kept for the retention window of the scheduler
+        async fn drop_table(
+            &self,
+            request: tonic::Request<super::DropTableParams>,
+        ) -> std::result::Result<tonic::Response<super::DropTableResult>, tonic::Status>;
+        /// Restore a shared table dropped within the retention window
+        async fn undrop_table(
+            &self,
+            request: tonic::Request<super::UndropTableParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::UndropTableResult>,
+            tonic::Status,
+        >;
+        /// Defaults and capabilities of the cluster
+        async fn get_cluster_config(
+            &self,
+            request: tonic::Request<super::GetClusterConfigParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetClusterConfigResult>,
+            tonic::Status,
+        >;
+    }
+    #[derive(Debug)]
+    pub struct SchedulerGrpcServer<T: SchedulerGrpc> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: SchedulerGrpc> SchedulerGrpcServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
+        }
+        /// Limits the maximum size of a decoded message.
+        ///
+        /// Default: `4MB`
+        #[must_use]
+        pub fn max_decoding_message_size(mut self, limit: usize) -> Self {
+            self.max_decoding_message_size = Some(limit);
+            self
+        }
+        /// Limits the maximum size of an encoded message.
+        ///
+        /// Default: `usize::MAX`
+        #[must_use]
+        pub fn max_encoding_message_size(mut self, limit: usize) -> Self {
+            self.max_encoding_message_size = Some(limit);
+            self
+        }
+    }
+    impl<T, B> tonic::codegen::Service<http::Request<B>> for SchedulerGrpcServer<T>
+    where
+        T: SchedulerGrpc,
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = std::convert::Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+        fn poll_ready(
+            &mut self,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+        fn call(&mut self, req: http::Request<B>) -> Self::Future {
+            let inner = self.inner.clone();
+            match req.uri().path() {
+                "/ballista.protobuf.SchedulerGrpc/PollWork" => {
+                    #[allow(non_camel_case_types)]
+                    struct PollWorkSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::PollWorkParams>
+                    for PollWorkSvc<T> {
+                        type Response = super::PollWorkResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PollWorkParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).poll_work(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = PollWorkSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/RegisterExecutor" => {
+                    #[allow(non_camel_case_types)]
+                    struct RegisterExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::RegisterExecutorParams>
+                    for RegisterExecutorSvc<T> {
+                        type Response = super::RegisterExecutorResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RegisterExecutorParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).register_executor(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RegisterExecutorSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/HeartBeatFromExecutor" => {
+                    #[allow(non_camel_case_types)]
+                    struct HeartBeatFromExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::HeartBeatParams>
+                    for HeartBeatFromExecutorSvc<T> {
+                        type Response = super::HeartBeatResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::HeartBeatParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).heart_beat_from_executor(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = HeartBeatFromExecutorSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/UpdateTaskStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateTaskStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::UpdateTaskStatusParams>
+                    for UpdateTaskStatusSvc<T> {
+                        type Response = super::UpdateTaskStatusResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateTaskStatusParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).update_task_status(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateTaskStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetFileMetadata" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetFileMetadataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetFileMetadataParams>
+                    for GetFileMetadataSvc<T> {
+                        type Response = super::GetFileMetadataResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetFileMetadataParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_file_metadata(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetFileMetadataSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ExecuteQuery" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExecuteQuerySvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ExecuteQueryParams>
+                    for ExecuteQuerySvc<T> {
+                        type Response = super::ExecuteQueryResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExecuteQueryParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).execute_query(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExecuteQuerySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetJobStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetJobStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetJobStatusParams>
+                    for GetJobStatusSvc<T> {
+                        type Response = super::GetJobStatusResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetJobStatusParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_job_status(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetJobStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetTaskAttempts" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetTaskAttemptsSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetTaskAttemptsParams>
+                    for GetTaskAttemptsSvc<T> {
+                        type Response = super::GetTaskAttemptsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetTaskAttemptsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_task_attempts(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetTaskAttemptsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ListJobs" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListJobsSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ListJobsParams>
+                    for ListJobsSvc<T> {
+                        type Response = super::ListJobsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListJobsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).list_jobs(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListJobsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/EstimateQuery" => {
+                    #[allow(non_camel_case_types)]
+                    struct EstimateQuerySvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::EstimateQueryParams>
+                    for EstimateQuerySvc<T> {
+                        type Response = super::EstimateQueryResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::EstimateQueryParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).estimate_query(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = EstimateQuerySvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetResultsPage" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetResultsPageSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetResultsPageParams>
+                    for GetResultsPageSvc<T> {
+                        type Response = super::GetResultsPageResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetResultsPageParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_results_page(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetResultsPageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExecutorStoppedSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::ExecutorStoppedParams>
+                    for ExecutorStoppedSvc<T> {
+                        type Response = super::ExecutorStoppedResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExecutorStoppedParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).executor_stopped(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExecutorStoppedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/RecoverExecutorData" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecoverExecutorDataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::RecoverExecutorDataParams>
+                    for RecoverExecutorDataSvc<T> {
+                        type Response = super::RecoverExecutorDataResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecoverExecutorDataParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).recover_executor_data(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RecoverExecutorDataSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/CancelJob" => {
+                    #[allow(non_camel_case_types)]
+                    struct CancelJobSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::CancelJobParams>
+                    for CancelJobSvc<T> {
+                        type Response = super::CancelJobResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CancelJobParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).cancel_job(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CancelJobSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetJobGroupStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetJobGroupStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetJobGroupStatusParams>
+                    for GetJobGroupStatusSvc<T> {
+                        type Response = super::GetJobGroupStatusResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetJobGroupStatusParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_job_group_status(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetJobGroupStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/CancelJobGroup" => {
                     #[allow(non_camel_case_types)]
-                    struct PollWorkSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct CancelJobGroupSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::PollWorkParams>
-                    for PollWorkSvc<T> {
-                        type Response = super::PollWorkResult;
+                    > tonic::server::UnaryService<super::CancelJobGroupParams>
+                    for CancelJobGroupSvc<T> {
+                        type Response = super::CancelJobGroupResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::PollWorkParams>,
+                            request: tonic::Request<super::CancelJobGroupParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move { (*inner).poll_work(request).await };
+                            let fut = async move {
+                                (*inner).cancel_job_group(request).await
+                            };
                             Box::pin(fut)
                         }
                     }
@@ -1875,7 +4375,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = PollWorkSvc(inner);
+                        let method = CancelJobGroupSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1891,25 +4391,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/RegisterExecutor" => {
+                "/ballista.protobuf.SchedulerGrpc/CleanJobData" => {
                     #[allow(non_camel_case_types)]
-                    struct RegisterExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct CleanJobDataSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::RegisterExecutorParams>
-                    for RegisterExecutorSvc<T> {
-                        type Response = super::RegisterExecutorResult;
+                    > tonic::server::UnaryService<super::CleanJobDataParams>
+                    for CleanJobDataSvc<T> {
+                        type Response = super::CleanJobDataResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RegisterExecutorParams>,
+                            request: tonic::Request<super::CleanJobDataParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).register_executor(request).await
+                                (*inner).clean_job_data(request).await
                             };
                             Box::pin(fut)
                         }
@@ -1921,7 +4421,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = RegisterExecutorSvc(inner);
+                        let method = CleanJobDataSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1937,25 +4437,27 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/HeartBeatFromExecutor" => {
+                "/ballista.protobuf.SchedulerGrpc/SubscribeJobEvents" => {
                     #[allow(non_camel_case_types)]
-                    struct HeartBeatFromExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct SubscribeJobEventsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::HeartBeatParams>
-                    for HeartBeatFromExecutorSvc<T> {
-                        type Response = super::HeartBeatResult;
+                    > tonic::server::ServerStreamingService<
+                        super::SubscribeJobEventsParams,
+                    > for SubscribeJobEventsSvc<T> {
+                        type Response = super::JobEvent;
+                        type ResponseStream = T::SubscribeJobEventsStream;
                         type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
+                            tonic::Response<Self::ResponseStream>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::HeartBeatParams>,
+                            request: tonic::Request<super::SubscribeJobEventsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).heart_beat_from_executor(request).await
+                                (*inner).subscribe_job_events(request).await
                             };
                             Box::pin(fut)
                         }
@@ -1967,7 +4469,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = HeartBeatFromExecutorSvc(inner);
+                        let method = SubscribeJobEventsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -1978,30 +4480,31 @@ pub mod scheduler_grpc_server {
                                 max_decoding_message_size,
                                 max_encoding_message_size,
                             );
-                        let res = grpc.unary(method, req).await;
+                        let res = grpc.server_streaming(method, req).await;
                         Ok(res)
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/UpdateTaskStatus" => {
+                "/ballista.protobuf.SchedulerGrpc/GetTaskLogs" => {
                     #[allow(non_camel_case_types)]
-                    struct UpdateTaskStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetTaskLogsSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::UpdateTaskStatusParams>
-                    for UpdateTaskStatusSvc<T> {
-                        type Response = super::UpdateTaskStatusResult;
+                    > tonic::server::ServerStreamingService<super::GetTaskLogsParams>
+                    for GetTaskLogsSvc<T> {
+                        type Response = super::TaskLogLines;
+                        type ResponseStream = T::GetTaskLogsStream;
                         type Future = BoxFuture<
-                            tonic::Response<Self::Response>,
+                            tonic::Response<Self::ResponseStream>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::UpdateTaskStatusParams>,
+                            request: tonic::Request<super::GetTaskLogsParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).update_task_status(request).await
+                                (*inner).get_task_logs(request).await
                             };
                             Box::pin(fut)
                         }
@@ -2013,7 +4516,50 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = UpdateTaskStatusSvc(inner);
+                        let method = GetTaskLogsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/Drain" => {
+                    #[allow(non_camel_case_types)]
+                    struct DrainSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::DrainParams> for DrainSvc<T> {
+                        type Response = super::DrainResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DrainParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { (*inner).drain(request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DrainSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2029,25 +4575,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/GetFileMetadata" => {
+                "/ballista.protobuf.SchedulerGrpc/DecommissionExecutor" => {
                     #[allow(non_camel_case_types)]
-                    struct GetFileMetadataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct DecommissionExecutorSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::GetFileMetadataParams>
-                    for GetFileMetadataSvc<T> {
-                        type Response = super::GetFileMetadataResult;
+                    > tonic::server::UnaryService<super::DecommissionExecutorParams>
+                    for DecommissionExecutorSvc<T> {
+                        type Response = super::DecommissionExecutorResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetFileMetadataParams>,
+                            request: tonic::Request<super::DecommissionExecutorParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).get_file_metadata(request).await
+                                (*inner).decommission_executor(request).await
                             };
                             Box::pin(fut)
                         }
@@ -2059,7 +4605,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = GetFileMetadataSvc(inner);
+                        let method = DecommissionExecutorSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2075,25 +4621,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/ExecuteQuery" => {
+                "/ballista.protobuf.SchedulerGrpc/CloseSession" => {
                     #[allow(non_camel_case_types)]
-                    struct ExecuteQuerySvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct CloseSessionSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::ExecuteQueryParams>
-                    for ExecuteQuerySvc<T> {
-                        type Response = super::ExecuteQueryResult;
+                    > tonic::server::UnaryService<super::CloseSessionParams>
+                    for CloseSessionSvc<T> {
+                        type Response = super::CloseSessionResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ExecuteQueryParams>,
+                            request: tonic::Request<super::CloseSessionParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).execute_query(request).await
+                                (*inner).close_session(request).await
                             };
                             Box::pin(fut)
                         }
@@ -2105,7 +4651,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = ExecuteQuerySvc(inner);
+                        let method = CloseSessionSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2121,25 +4667,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/GetJobStatus" => {
+                "/ballista.protobuf.SchedulerGrpc/RegisterTable" => {
                     #[allow(non_camel_case_types)]
-                    struct GetJobStatusSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct RegisterTableSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::GetJobStatusParams>
-                    for GetJobStatusSvc<T> {
-                        type Response = super::GetJobStatusResult;
+                    > tonic::server::UnaryService<super::RegisterTableParams>
+                    for RegisterTableSvc<T> {
+                        type Response = super::RegisterTableResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::GetJobStatusParams>,
+                            request: tonic::Request<super::RegisterTableParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).get_job_status(request).await
+                                (*inner).register_table(request).await
                             };
                             Box::pin(fut)
                         }
@@ -2151,7 +4697,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = GetJobStatusSvc(inner);
+                        let method = RegisterTableSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2167,26 +4713,24 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/ExecutorStopped" => {
+                "/ballista.protobuf.SchedulerGrpc/GetTables" => {
                     #[allow(non_camel_case_types)]
-                    struct ExecutorStoppedSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct GetTablesSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::ExecutorStoppedParams>
-                    for ExecutorStoppedSvc<T> {
-                        type Response = super::ExecutorStoppedResult;
+                    > tonic::server::UnaryService<super::GetTablesParams>
+                    for GetTablesSvc<T> {
+                        type Response = super::GetTablesResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ExecutorStoppedParams>,
+                            request: tonic::Request<super::GetTablesParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move {
-                                (*inner).executor_stopped(request).await
-                            };
+                            let fut = async move { (*inner).get_tables(request).await };
                             Box::pin(fut)
                         }
                     }
@@ -2197,7 +4741,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = ExecutorStoppedSvc(inner);
+                        let method = GetTablesSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2213,24 +4757,24 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CancelJob" => {
+                "/ballista.protobuf.SchedulerGrpc/DropTable" => {
                     #[allow(non_camel_case_types)]
-                    struct CancelJobSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct DropTableSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CancelJobParams>
-                    for CancelJobSvc<T> {
-                        type Response = super::CancelJobResult;
+                    > tonic::server::UnaryService<super::DropTableParams>
+                    for DropTableSvc<T> {
+                        type Response = super::DropTableResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CancelJobParams>,
+                            request: tonic::Request<super::DropTableParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
-                            let fut = async move { (*inner).cancel_job(request).await };
+                            let fut = async move { (*inner).drop_table(request).await };
                             Box::pin(fut)
                         }
                     }
@@ -2241,7 +4785,7 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = CancelJobSvc(inner);
+                        let method = DropTableSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2257,25 +4801,25 @@ pub mod scheduler_grpc_server {
                     };
                     Box::pin(fut)
                 }
-                "/ballista.protobuf.SchedulerGrpc/CleanJobData" => {
+                "/ballista.protobuf.SchedulerGrpc/UndropTable" => {
                     #[allow(non_camel_case_types)]
-                    struct CleanJobDataSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    struct UndropTableSvc<T: SchedulerGrpc>(pub Arc<T>);
                     impl<
                         T: SchedulerGrpc,
-                    > tonic::server::UnaryService<super::CleanJobDataParams>
-                    for CleanJobDataSvc<T> {
-                        type Response = super::CleanJobDataResult;
+                    > tonic::server::UnaryService<super::UndropTableParams>
+                    for UndropTableSvc<T> {
+                        type Response = super::UndropTableResult;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::CleanJobDataParams>,
+                            request: tonic::Request<super::UndropTableParams>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                (*inner).clean_job_data(request).await
+                                (*inner).undrop_table(request).await
                             };
                             Box::pin(fut)
                         }
@@ -2287,7 +4831,53 @@ pub mod scheduler_grpc_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = CleanJobDataSvc(inner);
+                        let method = UndropTableSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.SchedulerGrpc/GetClusterConfig" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetClusterConfigSvc<T: SchedulerGrpc>(pub Arc<T>);
+                    impl<
+                        T: SchedulerGrpc,
+                    > tonic::server::UnaryService<super::GetClusterConfigParams>
+                    for GetClusterConfigSvc<T> {
+                        type Response = super::GetClusterConfigResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetClusterConfigParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_cluster_config(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetClusterConfigSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -2386,6 +4976,22 @@ pub mod executor_grpc_server {
             tonic::Response<super::RemoveJobDataResult>,
             tonic::Status,
         >;
+        /// Change the task slots the executor advertises to the scheduler without restarting it
+        async fn set_task_slots(
+            &self,
+            request: tonic::Request<super::SetTaskSlotsParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetTaskSlotsResult>,
+            tonic::Status,
+        >;
+        /// The tasks running on the executor, for a restarted scheduler to reconcile its jobs with
+        async fn get_running_tasks(
+            &self,
+            request: tonic::Request<super::GetRunningTasksParams>,
+        ) -> std::result::Result<
+            tonic::Response<super::GetRunningTasksResult>,
+            tonic::Status,
+        >;
     }
     #[derive(Debug)]
     pub struct ExecutorGrpcServer<T: ExecutorGrpc> {
@@ -2694,6 +5300,98 @@ pub mod executor_grpc_server {
                     };
                     Box::pin(fut)
                 }
+                "/ballista.protobuf.ExecutorGrpc/SetTaskSlots" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetTaskSlotsSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::SetTaskSlotsParams>
+                    for SetTaskSlotsSvc<T> {
+                        type Response = super::SetTaskSlotsResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetTaskSlotsParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).set_task_slots(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SetTaskSlotsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/ballista.protobuf.ExecutorGrpc/GetRunningTasks" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetRunningTasksSvc<T: ExecutorGrpc>(pub Arc<T>);
+                    impl<
+                        T: ExecutorGrpc,
+                    > tonic::server::UnaryService<super::GetRunningTasksParams>
+                    for GetRunningTasksSvc<T> {
+                        type Response = super::GetRunningTasksResult;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetRunningTasksParams>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                (*inner).get_running_tasks(request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetRunningTasksSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => {
                     Box::pin(async move {
                         Ok(
@@ -26,3 +26,11 @@ pub mod ballista;
 pub mod ballista {
     include!(concat!(env!("OUT_DIR"), "/ballista.rs"));
 }
+
+/// Encoded descriptors of the Ballista protocol and of the DataFusion types it uses
+#[cfg(not(docsrs))]
+pub const FILE_DESCRIPTOR_SET: &[u8] = include_bytes!("ballista_descriptor.bin");
+
+#[cfg(docsrs)]
+pub const FILE_DESCRIPTOR_SET: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/ballista_descriptor.bin"));
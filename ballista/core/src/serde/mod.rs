@@ -40,7 +40,7 @@ use std::sync::Arc;
 use std::{convert::TryInto, io::Cursor};
 
 use crate::execution_plans::{
-    ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec,
+    ResultExport, ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec,
 };
 use crate::serde::protobuf::ballista_physical_plan_node::PhysicalPlanType;
 use crate::serde::scheduler::PartitionLocation;
@@ -148,13 +148,32 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                     input.schema().as_ref(),
                 )?;
 
-                Ok(Arc::new(ShuffleWriterExec::try_new(
-                    shuffle_writer.job_id.clone(),
-                    shuffle_writer.stage_id as usize,
-                    input,
-                    "".to_string(), // this is intentional but hacky - the executor will fill this in
-                    shuffle_output_partitioning,
-                )?))
+                Ok(Arc::new(
+                    ShuffleWriterExec::try_new(
+                        shuffle_writer.job_id.clone(),
+                        shuffle_writer.stage_id as usize,
+                        input,
+                        "".to_string(), // this is intentional but hacky - the executor will fill this in
+                        shuffle_output_partitioning,
+                    )?
+                    .with_shuffle_codec(shuffle_writer.shuffle_codec().into())
+                    .with_write_buffer_size(shuffle_writer.write_buffer_size as usize)
+                    .with_checksum(shuffle_writer.checksum)
+                    .with_checkpoint_dir(if shuffle_writer.checkpoint_dir.is_empty() {
+                        None
+                    } else {
+                        Some(shuffle_writer.checkpoint_dir.clone())
+                    })
+                    .with_result_export(
+                        (!shuffle_writer.export_location.is_empty()).then(|| {
+                            ResultExport::new(
+                                shuffle_writer.export_location.clone(),
+                                shuffle_writer.export_options.clone(),
+                            )
+                            .with_profile(shuffle_writer.export_profile.clone())
+                        }),
+                    ),
+                ))
             }
             PhysicalPlanType::ShuffleReader(shuffle_reader) => {
                 let schema = Arc::new(convert_required!(shuffle_reader.schema)?);
@@ -225,6 +244,26 @@ impl PhysicalExtensionCodec for BallistaPhysicalExtensionCodec {
                         stage_id: exec.stage_id() as u32,
                         input: None,
                         output_partitioning,
+                        shuffle_codec: protobuf::ShuffleCodec::from(exec.shuffle_codec())
+                            as i32,
+                        write_buffer_size: exec.write_buffer_size() as u64,
+                        checksum: exec.checksum(),
+                        checkpoint_dir: exec
+                            .checkpoint_dir()
+                            .unwrap_or_default()
+                            .to_owned(),
+                        export_location: exec
+                            .result_export()
+                            .map(|export| export.location().to_owned())
+                            .unwrap_or_default(),
+                        export_options: exec
+                            .result_export()
+                            .map(|export| export.options().clone())
+                            .unwrap_or_default(),
+                        export_profile: exec
+                            .result_export()
+                            .map(|export| export.profile().to_owned())
+                            .unwrap_or_default(),
                     },
                 )),
             };
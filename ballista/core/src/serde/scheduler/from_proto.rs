@@ -28,7 +28,7 @@ use std::time::Duration;
 use crate::error::BallistaError;
 use crate::serde::scheduler::{
     Action, ExecutorData, ExecutorMetadata, ExecutorSpecification, PartitionId,
-    PartitionLocation, PartitionStats, TaskDefinition,
+    PartitionLocation, PartitionStats, TaskDefinition, TaskResources,
 };
 
 use crate::serde::protobuf;
@@ -47,6 +47,15 @@ impl TryInto<Action> for protobuf::Action {
                     path: fetch.path,
                     host: fetch.host,
                     port: fetch.port as u16,
+                    checksum: fetch.checksum.map(|checksum| checksum.crc32),
+                })
+            }
+            Some(protobuf::action::ActionType::FetchTaskLogs(fetch)) => {
+                Ok(Action::FetchTaskLogs {
+                    job_id: fetch.job_id,
+                    task_id: fetch.task_id as usize,
+                    from_line: fetch.from_line,
+                    follow: fetch.follow,
                 })
             }
             _ => Err(BallistaError::General(
@@ -117,6 +126,12 @@ impl TryInto<PartitionLocation> for protobuf::PartitionLocation {
                 })?
                 .into(),
             path: self.path,
+            checksum: self.checksum.map(|checksum| checksum.crc32),
+            checkpoint_path: if self.checkpoint_path.is_empty() {
+                None
+            } else {
+                Some(self.checkpoint_path)
+            },
         })
     }
 }
@@ -220,6 +235,7 @@ impl Into<ExecutorMetadata> for protobuf::ExecutorMetadata {
             port: self.port as u16,
             grpc_port: self.grpc_port as u16,
             specification: self.specification.unwrap().into(),
+            pool: self.pool,
         }
     }
 }
@@ -227,12 +243,25 @@ impl Into<ExecutorMetadata> for protobuf::ExecutorMetadata {
 #[allow(clippy::from_over_into)]
 impl Into<ExecutorSpecification> for protobuf::ExecutorSpecification {
     fn into(self) -> ExecutorSpecification {
-        let mut ret = ExecutorSpecification { task_slots: 0 };
+        let mut ret = ExecutorSpecification::default();
         for resource in self.resources {
-            if let Some(protobuf::executor_resource::Resource::TaskSlots(task_slots)) =
-                resource.resource
-            {
-                ret.task_slots = task_slots
+            match resource.resource {
+                Some(protobuf::executor_resource::Resource::TaskSlots(task_slots)) => {
+                    ret.task_slots = task_slots
+                }
+                Some(protobuf::executor_resource::Resource::MemoryBytes(
+                    memory_bytes,
+                )) => ret.memory_bytes = memory_bytes,
+                Some(protobuf::executor_resource::Resource::Vcores(vcores)) => {
+                    ret.vcores = vcores
+                }
+                Some(
+                    protobuf::executor_resource::Resource::TaskSlotOversubscriptionPercent(
+                        percent,
+                    ),
+                ) => ret.task_slot_oversubscription_percent = percent,
+                Some(protobuf::executor_resource::Resource::Gpus(gpus)) => ret.gpus = gpus,
+                None => {}
             }
         }
         ret
@@ -290,6 +319,7 @@ impl TryInto<(TaskDefinition, Vec<u8>)> for protobuf::TaskDefinition {
                 session_id: self.session_id,
                 launch_time: self.launch_time,
                 props,
+                owner: self.owner,
             },
             self.plan,
         ))
@@ -311,6 +341,7 @@ impl TryInto<(Vec<TaskDefinition>, Vec<u8>)> for protobuf::MultiTaskDefinition {
         let stage_id = self.stage_id as usize;
         let stage_attempt_num = self.stage_attempt_num as usize;
         let launch_time = self.launch_time;
+        let owner = self.owner;
         let task_ids = self.task_ids;
 
         Ok((
@@ -327,9 +358,21 @@ impl TryInto<(Vec<TaskDefinition>, Vec<u8>)> for protobuf::MultiTaskDefinition {
                     session_id: session_id.clone(),
                     launch_time,
                     props: props.clone(),
+                    owner: owner.clone(),
                 })
                 .collect(),
             plan,
         ))
     }
 }
+
+#[allow(clippy::from_over_into)]
+impl Into<TaskResources> for protobuf::TaskResources {
+    fn into(self) -> TaskResources {
+        TaskResources {
+            memory_bytes: self.memory_bytes,
+            vcores: self.vcores,
+            gpus: self.gpus,
+        }
+    }
+}
@@ -21,6 +21,7 @@ use datafusion::arrow::array::{
     ArrayBuilder, StructArray, StructBuilder, UInt64Array, UInt64Builder,
 };
 use datafusion::arrow::datatypes::{DataType, Field};
+use datafusion::execution::context::TaskContext;
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::physical_plan::Partitioning;
 use serde::Serialize;
@@ -41,6 +42,16 @@ pub enum Action {
         path: String,
         host: String,
         port: u16,
+        /// CRC32 checksum of the partition file, verified before it is read
+        checksum: Option<u32>,
+    },
+    /// Collect the log lines of a task attempt from line `from_line`, then with `follow` the
+    /// lines it logs until it finishes
+    FetchTaskLogs {
+        job_id: String,
+        task_id: usize,
+        from_line: u64,
+        follow: bool,
     },
 }
 
@@ -69,6 +80,11 @@ pub struct PartitionLocation {
     pub executor_meta: ExecutorMetadata,
     pub partition_stats: PartitionStats,
     pub path: String,
+    /// CRC32 checksum of the partition file, if recorded by the shuffle writer
+    pub checksum: Option<u32>,
+    /// Path of the copy of the partition file in the checkpoint directory of the job, read
+    /// instead of the file of the executor once the executor is lost
+    pub checkpoint_path: Option<String>,
 }
 
 /// Meta-data for an executor, used when fetching shuffle partitions from other executors
@@ -79,12 +95,106 @@ pub struct ExecutorMetadata {
     pub port: u16,
     pub grpc_port: u16,
     pub specification: ExecutorSpecification,
+    /// Pool of executors this executor belongs to, empty for the default pool. Jobs only run
+    /// on the executors of the pool they select
+    pub pool: String,
 }
 
 /// Specification of an executor, indicting executor resources, like total task slots
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
 pub struct ExecutorSpecification {
     pub task_slots: u32,
+    /// Memory available for running tasks in bytes, 0 if the executor does not limit task memory
+    pub memory_bytes: u64,
+    /// Virtual cores available for running tasks, 0 if the executor does not limit task cores
+    pub vcores: u32,
+    /// Logical task slots handed out per hundred task slots, e.g. 150 to run one and a half
+    /// tasks per task slot when tasks are mostly waiting on I/O. 0 for no oversubscription
+    pub task_slot_oversubscription_percent: u32,
+    /// GPUs available for running the tasks of the stages requiring GPUs, 0 if the executor
+    /// has none
+    pub gpus: u32,
+}
+
+impl ExecutorSpecification {
+    /// Task slots handed out to tasks on this executor, its task slots scaled by its
+    /// oversubscription and rounded up
+    pub fn logical_task_slots(&self) -> u32 {
+        if self.task_slot_oversubscription_percent == 0 {
+            return self.task_slots;
+        }
+        let slots = (self.task_slots as u64
+            * self.task_slot_oversubscription_percent as u64
+            + 99)
+            / 100;
+        slots.min(u32::MAX as u64) as u32
+    }
+
+    /// Resources initially available for tasks on this executor. Resources which are not
+    /// limited by the executor are unbounded, except for GPUs which an executor only has if
+    /// it declares them.
+    pub fn available_resources(&self) -> TaskResources {
+        TaskResources {
+            memory_bytes: if self.memory_bytes == 0 {
+                u64::MAX
+            } else {
+                self.memory_bytes
+            },
+            vcores: if self.vcores == 0 {
+                u32::MAX
+            } else {
+                self.vcores
+            },
+            gpus: self.gpus,
+        }
+    }
+}
+
+/// Resources required by a single task in addition to its task slot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize)]
+pub struct TaskResources {
+    pub memory_bytes: u64,
+    pub vcores: u32,
+    /// GPUs of the executor used by the task, 0 for the tasks of stages not requiring GPUs
+    pub gpus: u32,
+}
+
+impl TaskResources {
+    pub fn new(memory_bytes: u64, vcores: u32) -> Self {
+        Self {
+            memory_bytes,
+            vcores,
+            gpus: 0,
+        }
+    }
+
+    pub fn with_gpus(mut self, gpus: u32) -> Self {
+        self.gpus = gpus;
+        self
+    }
+
+    /// Resources which fit into any reservation, used where task slots are not
+    /// accounted by the scheduler
+    pub fn unbounded() -> Self {
+        Self {
+            memory_bytes: u64::MAX,
+            vcores: u32::MAX,
+            gpus: u32::MAX,
+        }
+    }
+
+    /// Whether the memory and cores are unbounded, as for the reservations of executors
+    /// whose task slots are not accounted by the scheduler
+    pub fn is_unbounded(&self) -> bool {
+        self.memory_bytes == u64::MAX && self.vcores == u32::MAX
+    }
+
+    /// Whether these resources fit into the `available` resources
+    pub fn fits(&self, available: &TaskResources) -> bool {
+        self.memory_bytes <= available.memory_bytes
+            && self.vcores <= available.vcores
+            && self.gpus <= available.gpus
+    }
 }
 
 /// From Spark, available resources for an executor, like available task slots
@@ -131,6 +241,21 @@ impl PartitionStats {
         }
     }
 
+    /// Number of rows of the partition, if known
+    pub fn num_rows(&self) -> Option<u64> {
+        self.num_rows
+    }
+
+    /// Number of batches of the partition, if known
+    pub fn num_batches(&self) -> Option<u64> {
+        self.num_batches
+    }
+
+    /// Size of the partition in bytes, if known
+    pub fn num_bytes(&self) -> Option<u64> {
+        self.num_bytes
+    }
+
     pub fn arrow_struct_repr(self) -> Field {
         Field::new(
             "partition_stats",
@@ -283,4 +408,85 @@ pub struct TaskDefinition {
     pub session_id: String,
     pub launch_time: u64,
     pub props: HashMap<String, String>,
+    /// User owning the job of the task, empty for none
+    pub owner: String,
+}
+
+impl TaskDefinition {
+    /// Metadata of the task, to be made available to the plan it executes
+    pub fn metadata(&self) -> TaskMetadata {
+        TaskMetadata {
+            job_id: self.job_id.clone(),
+            stage_id: self.stage_id,
+            stage_attempt_num: self.stage_attempt_num,
+            partition_id: self.partition_id,
+            task_id: self.task_id,
+            task_attempt_num: self.task_attempt_num,
+        }
+    }
+}
+
+/// Identity of the task executing a plan on an executor. It is added as an extension to the
+/// session config of the task's `TaskContext`, so that the operators of the plan, such as the
+/// scans of custom table providers, know which job, stage and partition they are serving.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskMetadata {
+    pub job_id: String,
+    pub stage_id: usize,
+    pub stage_attempt_num: usize,
+    /// Partition of the stage computed by the task
+    pub partition_id: usize,
+    pub task_id: usize,
+    pub task_attempt_num: usize,
+}
+
+impl TaskMetadata {
+    /// Metadata of the task running in the given context, None if the plan is not executed
+    /// as a task of a Ballista job
+    pub fn from_task_context(ctx: &TaskContext) -> Option<Arc<Self>> {
+        ctx.session_config().get_extension::<Self>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::prelude::{SessionConfig, SessionContext};
+
+    #[test]
+    fn test_task_metadata() {
+        let metadata = TaskMetadata {
+            job_id: "job".to_owned(),
+            stage_id: 2,
+            stage_attempt_num: 0,
+            partition_id: 3,
+            task_id: 7,
+            task_attempt_num: 1,
+        };
+        let config = SessionConfig::new().with_extension(Arc::new(metadata.clone()));
+        let ctx = SessionContext::with_config(config).task_ctx();
+        assert_eq!(
+            TaskMetadata::from_task_context(&ctx).as_deref(),
+            Some(&metadata)
+        );
+
+        let ctx = SessionContext::new().task_ctx();
+        assert!(TaskMetadata::from_task_context(&ctx).is_none());
+    }
+
+    #[test]
+    fn test_logical_task_slots() {
+        let mut specification = ExecutorSpecification {
+            task_slots: 8,
+            ..Default::default()
+        };
+        assert_eq!(specification.logical_task_slots(), 8);
+
+        specification.task_slot_oversubscription_percent = 150;
+        assert_eq!(specification.logical_task_slots(), 12);
+
+        // Partial logical task slots are rounded up
+        specification.task_slots = 3;
+        assert_eq!(specification.logical_task_slots(), 5);
+    }
 }
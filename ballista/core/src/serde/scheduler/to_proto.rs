@@ -26,7 +26,7 @@ use datafusion_proto::protobuf as datafusion_protobuf;
 
 use crate::serde::scheduler::{
     Action, ExecutorData, ExecutorMetadata, ExecutorSpecification, PartitionId,
-    PartitionLocation, PartitionStats, TaskDefinition,
+    PartitionLocation, PartitionStats, TaskDefinition, TaskResources,
 };
 use datafusion::physical_plan::Partitioning;
 use protobuf::{
@@ -45,6 +45,7 @@ impl TryInto<protobuf::Action> for Action {
                 path,
                 host,
                 port,
+                checksum,
             } => Ok(protobuf::Action {
                 action_type: Some(ActionType::FetchPartition(protobuf::FetchPartition {
                     job_id,
@@ -53,6 +54,21 @@ impl TryInto<protobuf::Action> for Action {
                     path,
                     host,
                     port: port as u32,
+                    checksum: checksum.map(|crc32| protobuf::ShuffleChecksum { crc32 }),
+                })),
+                settings: vec![],
+            }),
+            Action::FetchTaskLogs {
+                job_id,
+                task_id,
+                from_line,
+                follow,
+            } => Ok(protobuf::Action {
+                action_type: Some(ActionType::FetchTaskLogs(protobuf::FetchTaskLogs {
+                    job_id,
+                    task_id: task_id as u32,
+                    from_line,
+                    follow,
                 })),
                 settings: vec![],
             }),
@@ -81,6 +97,10 @@ impl TryInto<protobuf::PartitionLocation> for PartitionLocation {
             executor_meta: Some(self.executor_meta.into()),
             partition_stats: Some(self.partition_stats.into()),
             path: self.path,
+            checksum: self
+                .checksum
+                .map(|crc32| protobuf::ShuffleChecksum { crc32 }),
+            checkpoint_path: self.checkpoint_path.unwrap_or_default(),
         })
     }
 }
@@ -193,6 +213,7 @@ impl Into<protobuf::ExecutorMetadata> for ExecutorMetadata {
             port: self.port as u32,
             grpc_port: self.grpc_port as u32,
             specification: Some(self.specification.into()),
+            pool: self.pool,
         }
     }
 }
@@ -201,9 +222,15 @@ impl Into<protobuf::ExecutorMetadata> for ExecutorMetadata {
 impl Into<protobuf::ExecutorSpecification> for ExecutorSpecification {
     fn into(self) -> protobuf::ExecutorSpecification {
         protobuf::ExecutorSpecification {
-            resources: vec![protobuf::executor_resource::Resource::TaskSlots(
-                self.task_slots,
-            )]
+            resources: vec![
+                protobuf::executor_resource::Resource::TaskSlots(self.task_slots),
+                protobuf::executor_resource::Resource::MemoryBytes(self.memory_bytes),
+                protobuf::executor_resource::Resource::Vcores(self.vcores),
+                protobuf::executor_resource::Resource::TaskSlotOversubscriptionPercent(
+                    self.task_slot_oversubscription_percent,
+                ),
+                protobuf::executor_resource::Resource::Gpus(self.gpus),
+            ]
             .into_iter()
             .map(|r| protobuf::ExecutorResource { resource: Some(r) })
             .collect(),
@@ -266,6 +293,18 @@ impl Into<protobuf::TaskDefinition> for TaskDefinition {
             session_id: self.session_id,
             launch_time: self.launch_time,
             props,
+            owner: self.owner,
+        }
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<protobuf::TaskResources> for TaskResources {
+    fn into(self) -> protobuf::TaskResources {
+        protobuf::TaskResources {
+            memory_bytes: self.memory_bytes,
+            vcores: self.vcores,
+            gpus: self.gpus,
         }
     }
 }
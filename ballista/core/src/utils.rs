@@ -15,15 +15,16 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::config::BallistaConfig;
+use crate::config::{BallistaConfig, ObjectStoreRetryConfig};
 use crate::error::{BallistaError, Result};
 use crate::execution_plans::{
-    DistributedQueryExec, ShuffleWriterExec, UnresolvedShuffleExec,
+    DistributedQueryExec, ShuffleCodec, ShuffleFileWriter, ShuffleWriterExec,
+    UnresolvedShuffleExec,
 };
 use crate::serde::scheduler::PartitionStats;
 use async_trait::async_trait;
 use datafusion::arrow::datatypes::Schema;
-use datafusion::arrow::{ipc::writer::FileWriter, record_batch::RecordBatch};
+use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::datasource::object_store::{
     DefaultObjectStoreRegistry, ObjectStoreRegistry,
 };
@@ -36,7 +37,6 @@ use datafusion::logical_expr::{DdlStatement, LogicalPlan};
 use datafusion::physical_plan::aggregates::AggregateExec;
 use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
 use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
-use datafusion::physical_plan::common::batch_byte_size;
 use datafusion::physical_plan::empty::EmptyExec;
 use datafusion::physical_plan::file_format::{CsvExec, ParquetExec};
 use datafusion::physical_plan::filter::FilterExec;
@@ -57,22 +57,30 @@ use object_store::aws::AmazonS3Builder;
 #[cfg(feature = "azure")]
 use object_store::azure::MicrosoftAzureBuilder;
 use object_store::ObjectStore;
-use std::io::{BufWriter, Write};
+use std::collections::HashMap;
+use std::io::{BufWriter, Read, Write};
 use std::marker::PhantomData;
+use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use std::{fs::File, pin::Pin};
 use tonic::codegen::StdError;
 use tonic::transport::{Channel, Error, Server};
+use tonic_reflection::server::{ServerReflection, ServerReflectionServer};
 use url::Url;
 
-/// Default session builder using the provided configuration
+/// Default session builder using the provided configuration. Object stores retry failed
+/// requests as configured by the [BallistaConfig] extension of the configuration, if any.
 pub fn default_session_builder(config: SessionConfig) -> SessionState {
+    let retry = config
+        .get_extension::<BallistaConfig>()
+        .map(|ballista_config| ballista_config.object_store_retry())
+        .unwrap_or_default();
     SessionState::with_config_rt(
         config,
         Arc::new(
-            RuntimeEnv::new(with_object_store_provider(RuntimeConfig::default()))
+            RuntimeEnv::new(with_object_store_retry(RuntimeConfig::default(), retry))
                 .unwrap(),
         ),
     )
@@ -80,14 +88,28 @@ pub fn default_session_builder(config: SessionConfig) -> SessionState {
 
 /// Get a RuntimeConfig with specific ObjectStoreDetector in the ObjectStoreRegistry
 pub fn with_object_store_provider(config: RuntimeConfig) -> RuntimeConfig {
-    let object_store_registry = BallistaObjectStoreRegistry::new();
+    with_object_store_retry(config, ObjectStoreRetryConfig::default())
+}
+
+/// Get a RuntimeConfig with specific ObjectStoreDetector in the ObjectStoreRegistry whose
+/// object stores retry failed requests with the given policy
+pub fn with_object_store_retry(
+    config: RuntimeConfig,
+    retry: ObjectStoreRetryConfig,
+) -> RuntimeConfig {
+    let object_store_registry = BallistaObjectStoreRegistry::new().with_retry(retry);
     config.with_object_store_registry(Arc::new(object_store_registry))
 }
 
 /// An object store detector based on which features are enable for different kinds of object stores
 #[derive(Debug, Default)]
 pub struct BallistaObjectStoreRegistry {
-    inner: DefaultObjectStoreRegistry,
+    /// Object stores registered explicitly, e.g. by plugins, shared with the registries
+    /// created with [Self::with_shared_stores]
+    inner: Arc<DefaultObjectStoreRegistry>,
+    /// Object stores detected from the URLs of the tables
+    detected: DefaultObjectStoreRegistry,
+    retry: ObjectStoreRetryConfig,
 }
 
 impl BallistaObjectStoreRegistry {
@@ -95,10 +117,35 @@ impl BallistaObjectStoreRegistry {
         Default::default()
     }
 
-    /// Find a suitable object store based on its url and enabled features if possible
+    /// Retry failed requests of the detected object stores with the given policy
+    pub fn with_retry(mut self, retry: ObjectStoreRetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Retry policy of the detected object stores
+    pub fn retry(&self) -> &ObjectStoreRetryConfig {
+        &self.retry
+    }
+
+    /// A registry with the object stores registered with this registry, before and after
+    /// it is created, whose detected object stores retry failed requests with the given
+    /// policy, e.g. the retry policy of a session
+    pub fn with_shared_stores(&self, retry: ObjectStoreRetryConfig) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            detected: DefaultObjectStoreRegistry::default(),
+            retry,
+        }
+    }
+
+    /// Find a suitable object store based on its url and enabled features if possible,
+    /// configured from the environment and with the given options, e.g. `aws_endpoint`
+    #[cfg_attr(not(any(feature = "s3", feature = "azure")), allow(unused_variables))]
     fn get_feature_store(
         &self,
         url: &Url,
+        options: &HashMap<String, String>,
     ) -> datafusion::error::Result<Arc<dyn ObjectStore>> {
         #[cfg(any(feature = "hdfs", feature = "hdfs3"))]
         {
@@ -114,6 +161,8 @@ impl BallistaObjectStoreRegistry {
                     let store = Arc::new(
                         AmazonS3Builder::from_env()
                             .with_bucket_name(bucket_name)
+                            .with_retry((&self.retry).into())
+                            .try_with_options(options)?
                             .build()?,
                     );
                     return Ok(store);
@@ -127,6 +176,8 @@ impl BallistaObjectStoreRegistry {
                         AmazonS3Builder::from_env()
                             .with_virtual_hosted_style_request(true)
                             .with_bucket_name(bucket_name)
+                            .with_retry((&self.retry).into())
+                            .try_with_options(options)?
                             .build()?,
                     );
                     return Ok(store);
@@ -141,6 +192,8 @@ impl BallistaObjectStoreRegistry {
                     let store = Arc::new(
                         MicrosoftAzureBuilder::from_env()
                             .with_container_name(bucket_name)
+                            .with_retry((&self.retry).into())
+                            .try_with_options(options)?
                             .build()?,
                     );
                     return Ok(store);
@@ -164,39 +217,83 @@ impl ObjectStoreRegistry for BallistaObjectStoreRegistry {
     }
 
     fn get_store(&self, url: &Url) -> datafusion::error::Result<Arc<dyn ObjectStore>> {
-        self.inner.get_store(url).or_else(|_| {
-            let store = self.get_feature_store(url)?;
-            self.inner.register_store(url, store.clone());
+        self.inner
+            .get_store(url)
+            .or_else(|_| self.detected.get_store(url))
+            .or_else(|_| {
+                let store = self.get_feature_store(url, &HashMap::new())?;
+                self.detected.register_store(url, store.clone());
+
+                Ok(store)
+            })
+    }
+}
 
-            Ok(store)
-        })
+/// Object stores configured with options, e.g. the endpoint of an S3 compatible store,
+/// which are registered with the runtime of every session instead of the object stores
+/// detected from the URLs of the tables
+#[derive(Debug, Clone, Default)]
+pub struct DefaultObjectStores {
+    stores: Vec<(Url, Arc<dyn ObjectStore>)>,
+}
+
+impl DefaultObjectStores {
+    /// Create the object stores of the space separated URLs of `stores`, with their options
+    /// as query parameters, e.g. `s3://warehouse?aws_endpoint=http://minio:9000`. Their
+    /// credentials are read from the environment.
+    pub fn try_new(stores: &str, retry: ObjectStoreRetryConfig) -> Result<Self> {
+        let registry = BallistaObjectStoreRegistry::new().with_retry(retry);
+        let stores = stores
+            .split_whitespace()
+            .map(|store| {
+                let url = Url::parse(store).map_err(|e| {
+                    BallistaError::General(format!(
+                        "Invalid object store URL {store:?}: {e}"
+                    ))
+                })?;
+                let options: HashMap<String, String> = url
+                    .query_pairs()
+                    .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                    .collect();
+                let mut store_url = url.clone();
+                store_url.set_path("");
+                store_url.set_query(None);
+                let store = registry.get_feature_store(&store_url, &options)?;
+                Ok((store_url, store))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { stores })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stores.is_empty()
+    }
+
+    /// Register the object stores with the runtime of a session
+    pub fn register(&self, runtime: &RuntimeEnv) {
+        for (url, store) in &self.stores {
+            runtime.register_object_store(url, store.clone());
+        }
     }
 }
 
-/// Stream data to disk in Arrow IPC format
+/// Stream data to disk in the format of the given shuffle codec
 pub async fn write_stream_to_disk(
     stream: &mut Pin<Box<dyn RecordBatchStream + Send>>,
     path: &str,
+    codec: ShuffleCodec,
     disk_write_metric: &metrics::Time,
 ) -> Result<PartitionStats> {
-    let file = File::create(path).map_err(|e| {
-        error!("Failed to create partition file at {}: {:?}", path, e);
-        BallistaError::IoError(e)
-    })?;
-
-    let mut num_rows = 0;
-    let mut num_batches = 0;
-    let mut num_bytes = 0;
-    let mut writer = FileWriter::try_new(file, stream.schema().as_ref())?;
+    let mut writer =
+        ShuffleFileWriter::try_new(codec, Path::new(path), stream.schema().as_ref())
+            .map_err(|e| {
+                error!("Failed to create partition file at {}: {:?}", path, e);
+                BallistaError::from(e)
+            })?;
 
     while let Some(result) = stream.next().await {
         let batch = result?;
 
-        let batch_size_bytes: usize = batch_byte_size(&batch);
-        num_batches += 1;
-        num_rows += batch.num_rows();
-        num_bytes += batch_size_bytes;
-
         let timer = disk_write_metric.timer();
         writer.write(&batch)?;
         timer.done();
@@ -205,12 +302,55 @@ pub async fn write_stream_to_disk(
     writer.finish()?;
     timer.done();
     Ok(PartitionStats::new(
-        Some(num_rows as u64),
-        Some(num_batches),
-        Some(num_bytes as u64),
+        Some(writer.num_rows()),
+        Some(writer.num_batches()),
+        Some(writer.num_bytes()),
     ))
 }
 
+/// Compute the CRC32 checksum of the content of a file
+pub fn file_checksum(path: &str) -> Result<u32> {
+    let mut file = File::open(path).map_err(|e| {
+        BallistaError::General(format!("Failed to open partition file at {path}: {e:?}"))
+    })?;
+
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Check that the content of a file matches the checksum recorded when it was written
+pub fn verify_file_checksum(path: &str, expected: u32) -> Result<()> {
+    let actual = file_checksum(path)?;
+    if actual != expected {
+        return Err(BallistaError::General(format!(
+            "Checksum mismatch for partition file at {path}: expected {expected:#010x} but found {actual:#010x}"
+        )));
+    }
+    Ok(())
+}
+
+/// Create a directory and its missing parents, accessible by the owner only on Unix, since
+/// the shuffle and spill files of the tasks may hold sensitive rows. The permissions of the
+/// directories which already exist are left as is.
+pub fn create_private_dir_all(path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(path)
+}
+
 pub async fn collect_stream(
     stream: &mut Pin<Box<dyn RecordBatchStream + Send>>,
 ) -> Result<Vec<RecordBatch>> {
@@ -352,8 +492,11 @@ pub fn create_df_ctx_with_ballista_query_planner<T: 'static + AsLogicalPlan>(
     let mut session_state = SessionState::with_config_rt(
         session_config,
         Arc::new(
-            RuntimeEnv::new(with_object_store_provider(RuntimeConfig::default()))
-                .unwrap(),
+            RuntimeEnv::new(with_object_store_retry(
+                RuntimeConfig::default(),
+                config.object_store_retry(),
+            ))
+            .unwrap(),
         ),
     )
     .with_query_planner(planner);
@@ -460,6 +603,28 @@ pub fn create_grpc_server() -> Server {
         .http2_keepalive_timeout(Option::Some(Duration::from_secs(20)))
 }
 
+/// gRPC reflection service describing the given services of the Ballista protocol, e.g.
+/// `ballista.protobuf.SchedulerGrpc`, to tools like grpcurl
+pub fn create_grpc_reflection_server(
+    service_names: &[&str],
+) -> Result<ServerReflectionServer<impl ServerReflection>> {
+    service_names
+        .iter()
+        .fold(
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(
+                    crate::serde::generated::FILE_DESCRIPTOR_SET,
+                ),
+            |builder, service_name| builder.with_service_name(*service_name),
+        )
+        .build()
+        .map_err(|e| {
+            BallistaError::Internal(format!(
+                "Failed to create gRPC reflection service: {e}"
+            ))
+        })
+}
+
 pub fn collect_plan_metrics(plan: &dyn ExecutionPlan) -> Vec<MetricsSet> {
     let mut metrics_array = Vec::<MetricsSet>::new();
     if let Some(metrics) = plan.metrics() {
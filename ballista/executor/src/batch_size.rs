@@ -0,0 +1,224 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Choice of the batch size of each task from the width of the rows of its stage and the
+//! memory pressure on the executor
+
+use std::collections::HashMap;
+
+use ballista_core::serde::protobuf::ShuffleWritePartition;
+use parking_lot::Mutex;
+
+/// Stages whose row width is remembered, the least recently updated ones are forgotten first
+const MAX_TRACKED_STAGES: usize = 1024;
+
+/// Bounds and target of the batch sizes chosen by [AdaptiveBatchSize]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdaptiveBatchSizeConfig {
+    /// Smallest batch size in rows
+    pub min_batch_size: usize,
+    /// Largest batch size in rows
+    pub max_batch_size: usize,
+    /// Size in bytes the batches should have, given the row width observed for the stage
+    pub target_batch_bytes: usize,
+}
+
+impl Default for AdaptiveBatchSizeConfig {
+    fn default() -> Self {
+        Self {
+            min_batch_size: 1024,
+            max_batch_size: 65536,
+            target_batch_bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Rows and bytes written by the completed tasks of a stage
+#[derive(Debug, Clone, Copy)]
+struct StageRowWidth {
+    rows: u64,
+    bytes: u64,
+    last_update: u64,
+}
+
+/// Chooses the batch size of the tasks run by an executor.
+///
+/// Once tasks of a stage completed, the next tasks of the stage, such as the remaining
+/// partitions or retries, use batches of about `target_batch_bytes` given the average width of
+/// the rows written so far: narrow rows get larger batches, wide rows smaller ones. Until then
+/// the batch size of the session is used. When more than half of the executor's memory is
+/// reserved, the batch size shrinks proportionally to the memory left, down to the minimum
+/// batch size once all of it is reserved.
+#[derive(Debug)]
+pub struct AdaptiveBatchSize {
+    config: AdaptiveBatchSizeConfig,
+    memory_bytes: u64,
+    row_widths: Mutex<(u64, HashMap<(String, usize), StageRowWidth>)>,
+}
+
+impl AdaptiveBatchSize {
+    /// Create a policy for an executor offering `memory_bytes` to its tasks, 0 if the memory
+    /// is not limited, in which case memory pressure is not considered
+    pub fn new(config: AdaptiveBatchSizeConfig, memory_bytes: u64) -> Self {
+        let max_batch_size = config.max_batch_size.max(config.min_batch_size).max(1);
+        let min_batch_size = config.min_batch_size.max(1);
+        Self {
+            config: AdaptiveBatchSizeConfig {
+                min_batch_size,
+                max_batch_size,
+                ..config
+            },
+            memory_bytes,
+            row_widths: Mutex::new((0, HashMap::new())),
+        }
+    }
+
+    /// Batch size for a task of a stage, given the batch size of its session and the memory
+    /// currently reserved on the executor
+    pub fn batch_size(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        session_batch_size: usize,
+        reserved_memory: usize,
+    ) -> usize {
+        let row_width = {
+            let row_widths = self.row_widths.lock();
+            row_widths
+                .1
+                .get(&(job_id.to_owned(), stage_id))
+                .filter(|width| width.rows > 0 && width.bytes > 0)
+                .map(|width| (width.bytes / width.rows).max(1))
+        };
+        let batch_size = match row_width {
+            Some(row_width) => self.config.target_batch_bytes / row_width as usize,
+            None => session_batch_size,
+        };
+
+        let batch_size = if self.memory_bytes > 0 {
+            let free = 1.0 - (reserved_memory as f64 / self.memory_bytes as f64).min(1.0);
+            if free < 0.5 {
+                (batch_size as f64 * free * 2.0) as usize
+            } else {
+                batch_size
+            }
+        } else {
+            batch_size
+        };
+
+        batch_size.clamp(self.config.min_batch_size, self.config.max_batch_size)
+    }
+
+    /// Record the partitions written by a completed task of a stage
+    pub fn record(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        partitions: &[ShuffleWritePartition],
+    ) {
+        let (rows, bytes) = partitions.iter().fold((0, 0), |(rows, bytes), partition| {
+            (rows + partition.num_rows, bytes + partition.num_bytes)
+        });
+        if rows == 0 {
+            return;
+        }
+
+        let mut row_widths = self.row_widths.lock();
+        let (clock, stages) = &mut *row_widths;
+        *clock += 1;
+        let key = (job_id.to_owned(), stage_id);
+        if !stages.contains_key(&key) && stages.len() >= MAX_TRACKED_STAGES {
+            if let Some(oldest) = stages
+                .iter()
+                .min_by_key(|(_, width)| width.last_update)
+                .map(|(key, _)| key.clone())
+            {
+                stages.remove(&oldest);
+            }
+        }
+        let width = stages.entry(key).or_insert(StageRowWidth {
+            rows: 0,
+            bytes: 0,
+            last_update: 0,
+        });
+        width.rows += rows;
+        width.bytes += bytes;
+        width.last_update = *clock;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn partition(num_rows: u64, num_bytes: u64) -> ShuffleWritePartition {
+        ShuffleWritePartition {
+            num_rows,
+            num_bytes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_adaptive_batch_size() {
+        let policy = AdaptiveBatchSize::new(
+            AdaptiveBatchSizeConfig {
+                min_batch_size: 1000,
+                max_batch_size: 100_000,
+                target_batch_bytes: 1_000_000,
+            },
+            10_000_000,
+        );
+
+        // The session batch size is used until tasks of the stage completed
+        assert_eq!(policy.batch_size("job", 1, 8192, 0), 8192);
+
+        // 10 bytes per row
+        policy.record("job", 1, &[partition(500, 5000), partition(500, 5000)]);
+        assert_eq!(policy.batch_size("job", 1, 8192, 0), 100_000);
+        // 500 bytes per row
+        policy.record("job", 2, &[partition(1000, 500_000)]);
+        assert_eq!(policy.batch_size("job", 2, 8192, 0), 2000);
+        // 100_000 bytes per row
+        policy.record("job", 3, &[partition(10, 1_000_000)]);
+        assert_eq!(policy.batch_size("job", 3, 8192, 0), 1000);
+        // Other stages keep the session batch size
+        assert_eq!(policy.batch_size("job", 4, 8192, 0), 8192);
+
+        // Up to half of the memory reserved does not change the batch size
+        assert_eq!(policy.batch_size("job", 2, 8192, 5_000_000), 2000);
+        // Then it shrinks proportionally to the memory left
+        assert_eq!(policy.batch_size("job", 2, 8192, 7_500_000), 1000);
+        assert_eq!(policy.batch_size("job", 4, 8192, 7_500_000), 4096);
+        assert_eq!(policy.batch_size("job", 4, 8192, 20_000_000), 1000);
+    }
+
+    #[test]
+    fn test_adaptive_batch_size_forgets_oldest_stages() {
+        let policy = AdaptiveBatchSize::new(AdaptiveBatchSizeConfig::default(), 0);
+        for stage_id in 0..=MAX_TRACKED_STAGES {
+            policy.record("job", stage_id, &[partition(1, 1_000_000)]);
+        }
+
+        assert_eq!(policy.row_widths.lock().1.len(), MAX_TRACKED_STAGES);
+        assert_eq!(policy.batch_size("job", 0, 8192, 0), 8192);
+        assert_eq!(
+            policy.batch_size("job", MAX_TRACKED_STAGES, 8192, 0),
+            AdaptiveBatchSizeConfig::default().min_batch_size
+        );
+    }
+}
@@ -19,11 +19,18 @@
 
 use anyhow::Result;
 use std::sync::Arc;
+use std::time::Duration;
 
+use ballista_core::config::ObjectStoreRetryConfig;
 use ballista_core::print_version;
+use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::DefaultObjectStores;
+use ballista_executor::batch_size::AdaptiveBatchSizeConfig;
+use ballista_executor::cgroup::CgroupConfig;
 use ballista_executor::executor_process::{
     start_executor_process, ExecutorProcessConfig,
 };
+use ballista_executor::scheduler_endpoints::SchedulerReconnectConfig;
 use config::prelude::*;
 
 #[macro_use]
@@ -60,6 +67,12 @@ async fn main() -> Result<()> {
         opt.bind_port
     );
 
+    let object_store_retry = ObjectStoreRetryConfig {
+        max_retries: opt.object_store_max_retries,
+        init_backoff: Duration::from_millis(opt.object_store_retry_init_backoff_ms),
+        max_backoff: Duration::from_millis(opt.object_store_retry_max_backoff_ms),
+        retry_timeout: Duration::from_secs(opt.object_store_retry_timeout_secs),
+    };
     let config = ExecutorProcessConfig {
         special_mod_log_level: opt.log_level_setting,
         external_host: opt.external_host,
@@ -69,17 +82,71 @@ async fn main() -> Result<()> {
         scheduler_host: opt.scheduler_host,
         scheduler_port: opt.scheduler_port,
         scheduler_connect_timeout_seconds: opt.scheduler_connect_timeout_seconds,
+        standby_schedulers: opt
+            .standby_schedulers
+            .split(',')
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        scheduler_reconnect: SchedulerReconnectConfig {
+            init_backoff: Duration::from_millis(opt.scheduler_reconnect_init_backoff_ms),
+            max_backoff: Duration::from_millis(opt.scheduler_reconnect_max_backoff_ms),
+        },
         concurrent_tasks: opt.concurrent_tasks,
+        task_slot_oversubscription: opt.task_slot_oversubscription,
+        memory_bytes: opt.memory_bytes,
+        task_memory_bytes: opt.task_memory_bytes,
+        vcores: opt.vcores,
+        gpus: opt.gpus,
+        pool: opt.pool,
         task_scheduling_policy: opt.task_scheduling_policy,
         work_dir: opt.work_dir,
+        work_dir_selection: opt.work_dir_selection,
+        spill_dir: opt.spill_dir,
+        export_profiles_file: opt.export_profiles_file,
+        export_local_root: opt.export_local_root,
         log_dir: opt.log_dir,
         log_file_name_prefix,
         log_rotation_policy: opt.log_rotation_policy,
         print_thread_info: opt.print_thread_info,
+        task_log_max_tasks: opt.task_log_max_tasks,
+        task_log_max_lines: opt.task_log_max_lines,
         job_data_ttl_seconds: opt.job_data_ttl_seconds,
         job_data_clean_up_interval_seconds: opt.job_data_clean_up_interval_seconds,
         grpc_server_max_decoding_message_size: opt.grpc_server_max_decoding_message_size,
+        object_store_retry: object_store_retry.clone(),
+        object_stores: DefaultObjectStores::try_new(
+            &opt.object_stores,
+            object_store_retry,
+        )?,
+        scan_prefetch_batches: opt.scan_prefetch_batches,
+        work_stealing: opt.work_stealing,
+        stage_result_cache_entries: opt.stage_result_cache_entries,
+        min_free_disk_bytes: opt.min_free_disk_bytes,
+        cgroup: (!opt.cgroup_path.is_empty()).then(|| CgroupConfig {
+            path: opt.cgroup_path.clone(),
+            cpus: if opt.cgroup_cpus > 0.0 {
+                opt.cgroup_cpus
+            } else {
+                opt.vcores as f64
+            },
+            memory_bytes: opt.cgroup_memory_bytes,
+        }),
+        adaptive_batch_size: opt.adaptive_batch_size.then_some(AdaptiveBatchSizeConfig {
+            min_batch_size: opt.adaptive_batch_size_min,
+            max_batch_size: opt.adaptive_batch_size_max,
+            target_batch_bytes: opt.adaptive_batch_target_bytes,
+        }),
+        grpc_compression: opt.grpc_compression,
+        task_status_batch_size: opt.task_status_batch_size,
+        task_status_flush_interval_ms: opt.task_status_flush_interval_ms,
         execution_engine: None,
+        task_context_extensions: vec![],
+        plugins: vec![],
+        plugin_dir: opt.plugin_dir,
+        codec: BallistaCodec::default(),
+        shutdown_grace_period_seconds: opt.shutdown_grace_period_seconds,
     };
 
     start_executor_process(Arc::new(config)).await
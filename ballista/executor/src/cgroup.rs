@@ -0,0 +1,219 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Enforcement of the CPU and memory limits of an executor by a cgroup v2 it moves itself
+//! into, instead of relying on the container runtime to limit it
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf;
+use log::{info, warn};
+
+/// Period of the CPU bandwidth limit in microseconds, the default of the kernel
+const CPU_PERIOD_US: u64 = 100_000;
+
+/// Limits of the cgroup of an executor
+#[derive(Debug, Clone, PartialEq)]
+pub struct CgroupConfig {
+    /// Path of the cgroup, e.g. `/sys/fs/cgroup/ballista-executor`, created if missing
+    pub path: String,
+    /// CPUs the executor may use, e.g. 2.5, 0 for no limit
+    pub cpus: f64,
+    /// Memory in bytes the executor may use before it is reclaimed or killed, 0 for no limit
+    pub memory_bytes: u64,
+}
+
+/// A cgroup v2 the executor process runs in. The tasks of the executor share its threads, so
+/// the limits apply to all of its tasks together; the memory of each task is bounded by the
+/// task memory limit of the executor instead.
+#[derive(Debug)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Create the cgroup with the limits of `config` and move the executor process into it
+    pub fn create(config: &CgroupConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        std::fs::create_dir_all(&path).map_err(|e| {
+            BallistaError::General(format!(
+                "Could not create cgroup {}: {e}",
+                config.path
+            ))
+        })?;
+        let cgroup = Self { path };
+        cgroup.enable_controllers();
+        cgroup.write("cpu.max", &cpu_max(config.cpus))?;
+        let memory_max = match config.memory_bytes {
+            0 => "max".to_owned(),
+            bytes => bytes.to_string(),
+        };
+        cgroup.write("memory.max", &memory_max)?;
+        cgroup.write("cgroup.procs", &std::process::id().to_string())?;
+        info!(
+            "Executor runs in cgroup {} limited to cpu.max {} and memory.max {}",
+            config.path,
+            cpu_max(config.cpus),
+            memory_max
+        );
+        Ok(cgroup)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Current CPU and memory usage of the cgroup, with the time it was throttled for
+    /// exceeding its CPU limit
+    pub fn stats(&self) -> Option<protobuf::CgroupStats> {
+        let cpu = parse_flat_keyed(&self.read("cpu.stat")?);
+        let memory_events = self
+            .read("memory.events")
+            .map(|events| parse_flat_keyed(&events))
+            .unwrap_or_default();
+        let stat = |stats: &HashMap<String, u64>, key: &str| {
+            stats.get(key).copied().unwrap_or_default()
+        };
+        Some(protobuf::CgroupStats {
+            cpu_usage_us: stat(&cpu, "usage_usec"),
+            cpu_periods: stat(&cpu, "nr_periods"),
+            cpu_throttled_periods: stat(&cpu, "nr_throttled"),
+            cpu_throttled_us: stat(&cpu, "throttled_usec"),
+            memory_bytes: self
+                .read("memory.current")
+                .and_then(|current| current.trim().parse().ok())
+                .unwrap_or_default(),
+            memory_max_bytes: self
+                .read("memory.max")
+                .and_then(|max| max.trim().parse().ok())
+                .unwrap_or_default(),
+            memory_high_events: stat(&memory_events, "high"),
+            memory_oom_kills: stat(&memory_events, "oom_kill"),
+        })
+    }
+
+    /// Enable the CPU and memory controllers for the cgroup in its parent. Where they are
+    /// already enabled, or delegated by the container runtime, this is not needed.
+    fn enable_controllers(&self) {
+        let subtree_control = match self.path.parent() {
+            Some(parent) => parent.join("cgroup.subtree_control"),
+            None => return,
+        };
+        if !subtree_control.exists() {
+            return;
+        }
+        if let Err(e) = std::fs::write(&subtree_control, "+cpu +memory") {
+            warn!("Could not enable the cpu and memory controllers in {subtree_control:?}: {e}");
+        }
+    }
+
+    fn write(&self, file: &str, value: &str) -> Result<()> {
+        std::fs::write(self.path.join(file), value).map_err(|e| {
+            BallistaError::General(format!(
+                "Could not write {value} to {}: {e}",
+                self.path.join(file).display()
+            ))
+        })
+    }
+
+    fn read(&self, file: &str) -> Option<String> {
+        std::fs::read_to_string(self.path.join(file)).ok()
+    }
+}
+
+/// Value of `cpu.max` limiting a cgroup to `cpus` CPUs
+pub fn cpu_max(cpus: f64) -> String {
+    if cpus > 0.0 {
+        let quota = (cpus * CPU_PERIOD_US as f64).round() as u64;
+        format!("{} {CPU_PERIOD_US}", quota.max(1000))
+    } else {
+        format!("max {CPU_PERIOD_US}")
+    }
+}
+
+/// Values of a flat keyed cgroup file such as `cpu.stat`, one `key value` pair per line
+fn parse_flat_keyed(contents: &str) -> HashMap<String, u64> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(' ')?;
+            Some((key.to_owned(), value.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_cpu_max() {
+        assert_eq!(cpu_max(2.5), "250000 100000");
+        assert_eq!(cpu_max(0.0), "max 100000");
+        // The kernel refuses quotas below 1ms
+        assert_eq!(cpu_max(0.001), "1000 100000");
+    }
+
+    #[test]
+    fn test_cgroup() -> Result<()> {
+        // A directory of regular files stands in for the cgroup file system
+        let dir = TempDir::new()?;
+        let path = dir.path().join("executor");
+        let cgroup = Cgroup::create(&CgroupConfig {
+            path: path.to_string_lossy().into_owned(),
+            cpus: 4.0,
+            memory_bytes: 1 << 30,
+        })?;
+        assert_eq!(
+            std::fs::read_to_string(path.join("cpu.max"))?,
+            "400000 100000"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("memory.max"))?,
+            "1073741824"
+        );
+        assert_eq!(
+            std::fs::read_to_string(path.join("cgroup.procs"))?,
+            std::process::id().to_string()
+        );
+
+        // No statistics are available outside of the cgroup file system
+        assert_eq!(cgroup.stats(), None);
+        std::fs::write(
+            path.join("cpu.stat"),
+            "usage_usec 5000\nuser_usec 4000\nsystem_usec 1000\nnr_periods 20\nnr_throttled 3\nthrottled_usec 1500\n",
+        )?;
+        std::fs::write(path.join("memory.current"), "52428800\n")?;
+        std::fs::write(
+            path.join("memory.events"),
+            "low 0\nhigh 2\nmax 1\noom 0\noom_kill 0\n",
+        )?;
+        let stats = cgroup.stats().unwrap();
+        assert_eq!(stats.cpu_usage_us, 5000);
+        assert_eq!(stats.cpu_periods, 20);
+        assert_eq!(stats.cpu_throttled_periods, 3);
+        assert_eq!(stats.cpu_throttled_us, 1500);
+        assert_eq!(stats.memory_bytes, 52428800);
+        assert_eq!(stats.memory_max_bytes, 1 << 30);
+        assert_eq!(stats.memory_high_events, 2);
+        assert_eq!(stats.memory_oom_kills, 0);
+        Ok(())
+    }
+}
@@ -142,12 +142,16 @@ impl DedicatedExecutor {
     {
         let (tx, rx) = tokio::sync::oneshot::channel();
 
+        // The task keeps the executor running until it completes, even once the executor is
+        // replaced by one of another size
+        let executor = self.clone();
         // create a execution plan to spawn
         let job = Box::pin(async move {
             let task_output = task.await;
             if tx.send(task_output).is_err() {
                 warn!("Spawned task output ignored: receiver dropped");
             }
+            drop(executor);
         });
 
         let mut state = self.state.lock();
@@ -327,6 +331,20 @@ mod tests {
         assert_eq!(dedicated_task.await.unwrap(), 42);
     }
 
+    #[tokio::test]
+    async fn executor_dropped_while_task_running() {
+        let barrier = Arc::new(Barrier::new(2));
+
+        let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
+        let dedicated_task = exec.spawn(do_work(42, Arc::clone(&barrier)));
+
+        // the executor keeps running its tasks once it is replaced by another one
+        drop(exec);
+        barrier.wait();
+
+        assert_eq!(dedicated_task.await.unwrap(), 42);
+    }
+
     #[tokio::test]
     async fn executor_submit_task_after_shutdown() {
         let exec = DedicatedExecutor::new("Test DedicatedExecutor", 1);
@@ -15,6 +15,8 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::prefetch::with_scan_prefetch;
+use crate::work_stealing::{with_work_stealing, WorkStealingPool};
 use arrow::datatypes::SchemaRef;
 use async_trait::async_trait;
 use ballista_core::execution_plans::ShuffleWriterExec;
@@ -23,7 +25,7 @@ use ballista_core::utils;
 use datafusion::error::{DataFusionError, Result};
 use datafusion::execution::context::TaskContext;
 use datafusion::physical_plan::metrics::MetricsSet;
-use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_plan::{displayable, ExecutionPlan};
 use std::fmt::Debug;
 use std::sync::Arc;
 
@@ -54,9 +56,41 @@ pub trait QueryStageExecutor: Sync + Send + Debug {
     fn collect_plan_metrics(&self) -> Vec<MetricsSet>;
 
     fn schema(&self) -> SchemaRef;
+
+    /// Schema of the batches the last execution of the query stage wrote, reported to the
+    /// scheduler which validates it against the stages reading the output
+    fn written_schema(&self) -> Option<SchemaRef> {
+        None
+    }
+
+    /// Name of the operator furthest from the scans of the plan which produced rows so far,
+    /// with the number of rows it produced, reported as the progress of the running task
+    fn operator_progress(&self) -> Option<(String, usize)> {
+        None
+    }
 }
 
-pub struct DefaultExecutionEngine {}
+#[derive(Default)]
+pub struct DefaultExecutionEngine {
+    scan_prefetch_batches: usize,
+    work_stealing: Option<Arc<WorkStealingPool>>,
+}
+
+impl DefaultExecutionEngine {
+    /// Read up to `batches` batches of the scans of query stages ahead while the batches
+    /// already read are processed, 0 disables prefetching
+    pub fn with_scan_prefetch_batches(mut self, batches: usize) -> Self {
+        self.scan_prefetch_batches = batches;
+        self
+    }
+
+    /// Share the files of the Parquet scans of query stages with the idle task slots of
+    /// the executor
+    pub fn with_work_stealing(mut self, pool: Arc<WorkStealingPool>) -> Self {
+        self.work_stealing = Some(pool);
+        self
+    }
+}
 
 impl ExecutionEngine for DefaultExecutionEngine {
     fn create_query_stage_exec(
@@ -74,10 +108,25 @@ impl ExecutionEngine for DefaultExecutionEngine {
             ShuffleWriterExec::try_new(
                 job_id,
                 stage_id,
-                plan.children()[0].clone(),
+                with_work_stealing(
+                    with_scan_prefetch(
+                        plan.children()[0].clone(),
+                        self.scan_prefetch_batches,
+                    )?,
+                    self.work_stealing.as_ref(),
+                )?,
                 work_dir.to_string(),
                 shuffle_writer.shuffle_output_partitioning().cloned(),
             )
+            .map(|exec| {
+                exec.with_shuffle_codec(shuffle_writer.shuffle_codec())
+                    .with_write_buffer_size(shuffle_writer.write_buffer_size())
+                    .with_checksum(shuffle_writer.checksum())
+                    .with_checkpoint_dir(
+                        shuffle_writer.checkpoint_dir().map(str::to_owned),
+                    )
+                    .with_result_export(shuffle_writer.result_export().cloned())
+            })
         } else {
             Err(DataFusionError::Internal(
                 "Plan passed to new_query_stage_exec is not a ShuffleWriterExec"
@@ -115,7 +164,31 @@ impl QueryStageExecutor for DefaultQueryStageExec {
         self.shuffle_writer.schema()
     }
 
+    fn written_schema(&self) -> Option<SchemaRef> {
+        self.shuffle_writer.written_schema()
+    }
+
     fn collect_plan_metrics(&self) -> Vec<MetricsSet> {
         utils::collect_plan_metrics(&self.shuffle_writer)
     }
+
+    fn operator_progress(&self) -> Option<(String, usize)> {
+        operator_progress(&self.shuffle_writer)
+    }
+}
+
+/// First operator with output rows in a depth-first traversal of the plan
+fn operator_progress(plan: &dyn ExecutionPlan) -> Option<(String, usize)> {
+    let output_rows = plan
+        .metrics()
+        .and_then(|metrics| metrics.output_rows())
+        .unwrap_or_default();
+    if output_rows > 0 {
+        let description = displayable(plan).one_line().to_string();
+        let name = description.split(':').next().unwrap_or_default().trim();
+        return Some((name.to_owned(), output_rows));
+    }
+    plan.children()
+        .iter()
+        .find_map(|child| operator_progress(child.as_ref()))
 }
@@ -15,21 +15,23 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use datafusion::config::ConfigOptions;
 use datafusion::physical_plan::ExecutionPlan;
 
 use ballista_core::serde::protobuf::{
     scheduler_grpc_client::SchedulerGrpcClient, PollWorkParams, PollWorkResult,
-    TaskDefinition, TaskStatus,
+    RunningTaskInfo, TaskDefinition, TaskStatus,
 };
-use datafusion::prelude::SessionConfig;
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 use crate::cpu_bound_executor::DedicatedExecutor;
-use crate::executor::Executor;
+use crate::executor::{task_props_config, Executor};
+use crate::executor_server::TERMINATING;
+use crate::scheduler_endpoints::{Backoff, SchedulerEndpoints};
+use crate::stage_result_cache::plan_fingerprint;
+use crate::task_logs::task_span;
 use crate::{as_task_status, TaskExecutionTimes};
 use ballista_core::error::BallistaError;
-use ballista_core::serde::scheduler::{ExecutorSpecification, PartitionId};
+use ballista_core::serde::scheduler::{ExecutorSpecification, PartitionId, TaskMetadata};
 use ballista_core::serde::BallistaCodec;
 use datafusion::execution::context::TaskContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
@@ -41,15 +43,29 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use std::error::Error;
 use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::{Receiver, Sender, TryRecvError};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::{sync::Arc, time::Duration};
 use tonic::transport::Channel;
-
+use tracing::Instrument;
+
+/// Poll the scheduler for tasks to run until the executor stops. When polling fails, the
+/// executor reconnects with backoff to the first reachable scheduler of `scheduler_endpoints`,
+/// if any, to fail over to a standby scheduler.
+///
+/// The statuses of the finished tasks are reported with the next poll. With a non-zero
+/// `task_status_flush_interval`, the executor waits up to that long for more tasks to finish
+/// so that their statuses and the task slots they free are reported in a single poll, and
+/// at most `task_status_batch_size` statuses are reported per poll, 0 for no limit.
 pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
     mut scheduler: SchedulerGrpcClient<Channel>,
+    scheduler_endpoints: Option<Arc<SchedulerEndpoints>>,
     executor: Arc<Executor>,
     codec: BallistaCodec<T, U>,
+    task_status_batch_size: usize,
+    task_status_flush_interval: Duration,
 ) -> Result<(), BallistaError> {
     let executor_specification: ExecutorSpecification = executor
         .metadata
@@ -58,43 +74,145 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         .unwrap()
         .clone()
         .into();
-    let available_task_slots =
-        Arc::new(Semaphore::new(executor_specification.task_slots as usize));
+    // Tasks of oversubscribed task slots share the threads of the task slots
+    let mut logical_task_slots = executor_specification.logical_task_slots();
+    let available_task_slots = Arc::new(Semaphore::new(logical_task_slots as usize));
 
     let (task_status_sender, mut task_status_receiver) =
         std::sync::mpsc::channel::<TaskStatus>();
     info!("Starting poll work loop with scheduler");
 
-    let dedicated_executor =
-        DedicatedExecutor::new("task_runner", executor_specification.task_slots as usize);
+    let mut task_slots = executor.task_slots();
+    let mut dedicated_executor =
+        DedicatedExecutor::new("task_runner", task_slots as usize);
+
+    let mut backoff = scheduler_endpoints
+        .as_ref()
+        .map(|endpoints| Backoff::new(endpoints.reconnect_config()));
+
+    // Statuses collected but not reported yet, and when the oldest of them was collected
+    let mut pending_task_status: Vec<TaskStatus> = vec![];
+    let mut pending_since: Option<Instant> = None;
 
     loop {
-        // Wait for task slots to be available before asking for new work
-        let permit = available_task_slots.acquire().await.unwrap();
-        // Make the slot available again
-        drop(permit);
+        // Wait for task slots to be available before asking for new work, unless there are
+        // statuses left to report
+        if pending_task_status.is_empty() {
+            let permit = available_task_slots.acquire().await.unwrap();
+            // Make the slot available again
+            drop(permit);
+        }
 
         // Keeps track of whether we received task in last iteration
         // to avoid going in sleep mode between polling
         let mut active_job = false;
 
-        let task_status: Vec<TaskStatus> =
-            sample_tasks_status(&mut task_status_receiver).await;
+        // The running tasks are listed before the statuses are taken so that no task
+        // finishing in between is missing from both
+        let mut running_tasks = executor.running_tasks();
+        let new_task_status = sample_tasks_status(&mut task_status_receiver).await;
+        if pending_task_status.is_empty() && !new_task_status.is_empty() {
+            pending_since = Some(Instant::now());
+        }
+        pending_task_status.extend(new_task_status);
+
+        // Wait for more tasks to finish before reporting the statuses collected so far
+        if let Some(since) = pending_since {
+            let flush_deadline = since + task_status_flush_interval;
+            let batch_full = task_status_batch_size > 0
+                && pending_task_status.len() >= task_status_batch_size;
+            if !batch_full
+                && Instant::now() < flush_deadline
+                && !TERMINATING.load(Ordering::Acquire)
+            {
+                tokio::time::sleep_until(flush_deadline.into()).await;
+                continue;
+            }
+        }
+
+        let task_status: Vec<TaskStatus> = if task_status_batch_size > 0
+            && pending_task_status.len() > task_status_batch_size
+        {
+            let task_status = pending_task_status
+                .drain(..task_status_batch_size)
+                .collect();
+            // The tasks whose statuses are left for the next poll are still reported as
+            // running, so that the scheduler does not consider them lost
+            running_tasks.extend(pending_task_status.iter().map(|status| {
+                RunningTaskInfo {
+                    task_id: status.task_id,
+                    job_id: status.job_id.clone(),
+                    stage_id: status.stage_id,
+                    partition_id: status.partition_id,
+                }
+            }));
+            task_status
+        } else {
+            pending_since = None;
+            std::mem::take(&mut pending_task_status)
+        };
+
+        // A terminating executor keeps reporting the statuses of its tasks without asking
+        // for new ones
+        // No tasks are taken while the work dirs are short of disk space
+        let num_free_slots = if TERMINATING.load(Ordering::Acquire)
+            || executor.disk_space_shortage().is_some()
+        {
+            0
+        } else {
+            available_task_slots.available_permits() as u32
+        };
+
+        // The task slots resized with `Executor::set_task_slots` are advertised to the
+        // scheduler, which hands out the logical task slots of the new size in its response,
+        // and run on as many threads. The previous threads stop once their tasks complete.
+        if executor.task_slots() != task_slots {
+            task_slots = executor.task_slots();
+            info!("Running the tasks on {task_slots} threads");
+            dedicated_executor =
+                DedicatedExecutor::new("task_runner", task_slots as usize);
+        }
+        let mut metadata = executor.metadata.clone();
+        metadata.specification = Some(
+            ExecutorSpecification {
+                task_slots,
+                ..executor_specification
+            }
+            .into(),
+        );
 
         let poll_work_result: anyhow::Result<
             tonic::Response<PollWorkResult>,
             tonic::Status,
         > = scheduler
             .poll_work(PollWorkParams {
-                metadata: Some(executor.metadata.clone()),
-                num_free_slots: available_task_slots.available_permits() as u32,
+                metadata: Some(metadata),
+                num_free_slots,
                 task_status,
+                running_tasks,
             })
             .await;
 
         match poll_work_result {
             Ok(result) => {
-                let tasks = result.into_inner().tasks;
+                if let Some(backoff) = backoff.as_mut() {
+                    backoff.reset();
+                }
+                let result = result.into_inner();
+                // The oversubscription of the task slots may be changed on the scheduler
+                if result.task_slots > 0 && result.task_slots != logical_task_slots {
+                    info!(
+                        "Handing out {} task slots instead of {}",
+                        result.task_slots, logical_task_slots
+                    );
+                    resize_task_slots(
+                        &available_task_slots,
+                        logical_task_slots,
+                        result.task_slots,
+                    );
+                    logical_task_slots = result.task_slots;
+                }
+                let tasks = result.tasks;
                 active_job = !tasks.is_empty();
 
                 for task in tasks {
@@ -104,34 +222,88 @@ pub async fn poll_loop<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     let permit =
                         available_task_slots.clone().acquire_owned().await.unwrap();
 
-                    match run_received_task(
+                    let task_id = task.task_id as usize;
+                    let stage_attempt_num = task.stage_attempt_num as usize;
+                    let launch_time = task.launch_time;
+                    let part = PartitionId {
+                        job_id: task.job_id.clone(),
+                        stage_id: task.stage_id as usize,
+                        partition_id: task.partition_id as usize,
+                    };
+                    let result = AssertUnwindSafe(run_received_task(
                         executor.clone(),
                         permit,
-                        task_status_sender,
+                        task_status_sender.clone(),
                         task,
                         &codec,
                         &dedicated_executor,
-                    )
+                    ))
+                    .catch_unwind()
                     .await
-                    {
-                        Ok(_) => {}
-                        Err(e) => {
-                            warn!("Failed to run task: {:?}", e);
-                        }
+                    .unwrap_or_else(|panic| Err(panic_error(&panic)));
+                    if let Err(e) = result {
+                        warn!("Failed to run task: {:?}", e);
+                        // Fail the task, so that the scheduler does not wait for it
+                        let now = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_millis() as u64;
+                        let _ = task_status_sender.send(as_task_status(
+                            Err(e),
+                            executor.metadata.id.clone(),
+                            task_id,
+                            stage_attempt_num,
+                            part,
+                            None,
+                            TaskExecutionTimes {
+                                launch_time,
+                                start_exec_time: now,
+                                end_exec_time: now,
+                            },
+                            None,
+                            None,
+                        ));
                     }
                 }
             }
             Err(error) => {
                 warn!("Executor poll work loop failed. If this continues to happen the Scheduler might be marked as dead. Error: {}", error);
+                if let (Some(endpoints), Some(backoff)) =
+                    (scheduler_endpoints.as_ref(), backoff.as_mut())
+                {
+                    // Reconnect to the scheduler once it is back, or fail over to a standby
+                    // scheduler while it is unreachable
+                    tokio::time::sleep(backoff.next_backoff()).await;
+                    match endpoints.connect_any().await {
+                        Ok(reconnected) => scheduler = reconnected,
+                        Err(e) => warn!("{}", e),
+                    }
+                    continue;
+                }
             }
         }
 
-        if !active_job {
+        if !active_job && pending_task_status.is_empty() {
             tokio::time::sleep(Duration::from_millis(100)).await;
         }
     }
 }
 
+/// Resize the task slots handed out to the tasks from `from` to `to` slots. Removed task
+/// slots are taken away once the tasks running on them complete.
+fn resize_task_slots(task_slots: &Arc<Semaphore>, from: u32, to: u32) {
+    if to > from {
+        task_slots.add_permits((to - from) as usize);
+    } else {
+        let task_slots = task_slots.clone();
+        tokio::spawn(async move {
+            if let Ok(permits) = task_slots.acquire_many_owned(from - to).await {
+                permits.forget();
+            }
+        });
+    }
+}
+
 /// Tries to get meaningful description from panic-error.
 pub(crate) fn any_to_string(any: &Box<dyn Any + Send>) -> String {
     if let Some(s) = any.downcast_ref::<&str>() {
@@ -145,6 +317,11 @@ pub(crate) fn any_to_string(any: &Box<dyn Any + Send>) -> String {
     }
 }
 
+/// Error failing a task which panicked
+pub(crate) fn panic_error(panic: &Box<dyn Any + Send>) -> BallistaError {
+    BallistaError::Internal(format!("Task panicked: {}", any_to_string(panic)))
+}
+
 async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
     executor: Arc<Executor>,
     permit: OwnedSemaphorePermit,
@@ -169,15 +346,20 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     );
     info!("Received task {}", task_identity);
 
-    let mut task_props = HashMap::new();
-    for kv_pair in task.props {
-        task_props.insert(kv_pair.key, kv_pair.value);
-    }
-    let mut config = ConfigOptions::new();
-    for (k, v) in task_props {
-        config.set(&k, &v)?;
-    }
-    let session_config = SessionConfig::from(config);
+    let config = task_props_config(
+        task.props
+            .into_iter()
+            .map(|kv_pair| (kv_pair.key, kv_pair.value)),
+    )?;
+    let task_metadata = TaskMetadata {
+        job_id: job_id.clone(),
+        stage_id: stage_id as usize,
+        stage_attempt_num: stage_attempt_num as usize,
+        partition_id: partition_id as usize,
+        task_id: task_id as usize,
+        task_attempt_num: task_attempt_num as usize,
+    };
+    let session_config = executor.task_session_config(task_metadata, config);
 
     let mut task_scalar_functions = HashMap::new();
     let mut task_aggregate_functions = HashMap::new();
@@ -188,17 +370,20 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     for agg_func in executor.aggregate_functions.clone() {
         task_aggregate_functions.insert(agg_func.0, agg_func.1);
     }
-    let runtime = executor.runtime.clone();
+    let resource_meter =
+        executor.task_resource_meter(&job_id, task_id as usize, &session_config)?;
+    let runtime = resource_meter.runtime();
     let session_id = task.session_id.clone();
     let task_context = Arc::new(TaskContext::new(
         Some(task_identity.clone()),
         session_id,
-        session_config,
+        session_config.with_extension(Arc::new(resource_meter.cpu_timer())),
         task_scalar_functions,
         task_aggregate_functions,
         runtime.clone(),
     ));
 
+    let plan_fingerprint = plan_fingerprint(&task.plan);
     let plan: Arc<dyn ExecutionPlan> =
         U::try_decode(task.plan.as_slice()).and_then(|proto| {
             proto.try_into_physical_plan(
@@ -212,9 +397,10 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
         job_id.clone(),
         stage_id as usize,
         plan,
-        &executor.work_dir,
+        executor.work_dirs.select(),
     )?;
-    dedicated_executor.spawn(async move {
+    let span = task_span(&job_id, task_id as usize, &task.owner);
+    let task = async move {
         use std::panic::AssertUnwindSafe;
         let part = PartitionId {
             job_id: job_id.clone(),
@@ -222,11 +408,14 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
             partition_id: partition_id as usize,
         };
 
-        let execution_result = match AssertUnwindSafe(executor.execute_query_stage(
-            task_id as usize,
-            part.clone(),
-            query_stage_exec.clone(),
-            task_context,
+        let execution_result = match AssertUnwindSafe(resource_meter.measure(
+            executor.execute_query_stage_with_cache(
+                task_id as usize,
+                part.clone(),
+                query_stage_exec.clone(),
+                task_context,
+                plan_fingerprint,
+            ),
         ))
         .catch_unwind()
         .await
@@ -243,6 +432,7 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
         debug!("Statistics: {:?}", execution_result);
 
         let plan_metrics = query_stage_exec.collect_plan_metrics();
+        let resource_usage = resource_meter.usage(&plan_metrics);
         let operator_metrics = plan_metrics
             .into_iter()
             .map(|m| m.try_into())
@@ -268,11 +458,14 @@ async fn run_received_task<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
             part,
             operator_metrics,
             task_execution_times,
+            Some(resource_usage),
+            query_stage_exec.written_schema(),
         ));
 
         // Release the permit after the work is done
         drop(permit);
-    });
+    };
+    dedicated_executor.spawn(task.instrument(span));
 
     Ok(())
 }
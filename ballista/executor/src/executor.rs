@@ -17,41 +17,95 @@
 
 //! Ballista executor logic
 
+use crate::batch_size::AdaptiveBatchSize;
+use crate::cgroup::Cgroup;
 use crate::execution_engine::DefaultExecutionEngine;
 use crate::execution_engine::ExecutionEngine;
 use crate::execution_engine::QueryStageExecutor;
+use crate::execution_loop::{any_to_string, panic_error};
 use crate::metrics::ExecutorMetricsCollector;
+use crate::plugin::ExecutorPlugin;
+use crate::resource_usage::TaskResourceMeter;
+use crate::stage_result_cache::StageResultCache;
+use crate::work_dirs::{spill_dir, WorkDirs};
+use crate::work_stealing::WorkStealingPool;
+use ballista_core::config::{
+    BallistaConfig, ObjectStoreRetryConfig, WorkDirSelection,
+    BALLISTA_OBJECT_STORE_PREFIX,
+};
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::ExportConfig;
 use ballista_core::serde::protobuf;
 use ballista_core::serde::protobuf::ExecutorRegistration;
-use ballista_core::serde::scheduler::PartitionId;
+use ballista_core::serde::scheduler::{ExecutorSpecification, PartitionId, TaskMetadata};
+use ballista_core::utils::BallistaObjectStoreRegistry;
 use dashmap::DashMap;
+use datafusion::config::ConfigOptions;
+use datafusion::datasource::object_store::ObjectStoreRegistry;
+use datafusion::datasource::provider::TableProviderFactory;
 use datafusion::execution::context::TaskContext;
 use datafusion::execution::runtime_env::RuntimeEnv;
 use datafusion::physical_plan::udaf::AggregateUDF;
 use datafusion::physical_plan::udf::ScalarUDF;
+use datafusion::prelude::SessionConfig;
 use futures::future::AbortHandle;
+use futures::FutureExt;
+use log::{error, info};
+use parking_lot::Mutex;
 use std::collections::HashMap;
-use std::future::Future;
-use std::pin::Pin;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-pub struct TasksDrainedFuture(pub Arc<Executor>);
+/// Interval at which a draining executor checks whether its tasks completed
+const TASKS_DRAINED_CHECK_INTERVAL: Duration = Duration::from_millis(100);
 
-impl Future for TasksDrainedFuture {
-    type Output = ();
+type AbortHandles = Arc<DashMap<(usize, PartitionId), AbortHandle>>;
 
-    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
-        if self.0.abort_handles.len() > 0 {
-            Poll::Pending
-        } else {
-            Poll::Ready(())
+/// Progress of a task, as the operator furthest from the scans which produced rows, its
+/// output rows and the bytes written to shuffle files
+type Progress = (String, u64, u64);
+
+/// A task running on the executor, whose progress is reported in the heartbeats
+struct RunningTask {
+    query_stage_exec: Arc<dyn QueryStageExecutor>,
+    started: Instant,
+    /// Last progress reported and when it changed
+    last_progress: Mutex<(Progress, Instant)>,
+}
+
+impl RunningTask {
+    fn new(query_stage_exec: Arc<dyn QueryStageExecutor>) -> Self {
+        let now = Instant::now();
+        Self {
+            query_stage_exec,
+            started: now,
+            last_progress: Mutex::new((Progress::default(), now)),
         }
     }
 }
 
-type AbortHandles = Arc<DashMap<(usize, PartitionId), AbortHandle>>;
+/// Extension of the `TaskContext` of the tasks run by an executor, registered when the
+/// executor starts. It provides the operators of the plans with handles to resources of the
+/// executor, such as rate limiters or hardware accelerators, which they read from the session
+/// config of the task with `task_ctx.session_config().get_extension::<T>()`.
+pub trait TaskContextExtension: Send + Sync {
+    /// Add extensions to the session config of a task before its plan is decoded
+    fn extend(&self, task: &TaskMetadata, config: SessionConfig) -> SessionConfig;
+}
+
+/// Table provider factories by file type registered by the plugins of an executor, which the
+/// operators and extension codecs of custom tables read from the session config of a task
+/// with `task_ctx.session_config().get_extension::<TableFactories>()`
+pub struct TableFactories(pub HashMap<String, Arc<dyn TableProviderFactory>>);
+
+impl TableFactories {
+    /// The factory of the tables of a file type
+    pub fn get(&self, file_type: &str) -> Option<&Arc<dyn TableProviderFactory>> {
+        self.0.get(file_type)
+    }
+}
 
 /// Ballista executor
 #[derive(Clone)]
@@ -59,15 +113,25 @@ pub struct Executor {
     /// Metadata
     pub metadata: ExecutorRegistration,
 
-    /// Directory for storing partial results
+    /// Directory for storing partial results, the first of the work dirs
     pub work_dir: String,
 
+    /// Directories the tasks write their shuffle files to, one per local disk
+    pub work_dirs: Arc<WorkDirs>,
+
+    /// Directories of the spill dirs of the tasks
+    pub spill_dirs: Arc<WorkDirs>,
+
     /// Scalar functions that are registered in the Executor
     pub scalar_functions: HashMap<String, Arc<ScalarUDF>>,
 
     /// Aggregate functions registered in the Executor
     pub aggregate_functions: HashMap<String, Arc<AggregateUDF>>,
 
+    /// Table provider factories by file type, registered by plugins and provided to the tasks
+    /// as [TableFactories]
+    pub table_factories: HashMap<String, Arc<dyn TableProviderFactory>>,
+
     /// Runtime environment for Executor
     pub runtime: Arc<RuntimeEnv>,
 
@@ -77,12 +141,52 @@ pub struct Executor {
     /// Concurrent tasks can run in executor
     pub concurrent_tasks: usize,
 
+    /// Task slots advertised to the scheduler, which may be resized while the executor runs
+    task_slots: AtomicU32,
+
     /// Handles to abort executing tasks
     abort_handles: AbortHandles,
 
+    /// Executing tasks, to report their progress
+    running_tasks: Arc<DashMap<(usize, PartitionId), RunningTask>>,
+
     /// Execution engine that the executor will delegate to
     /// for executing query stages
     pub(crate) execution_engine: Arc<dyn ExecutionEngine>,
+
+    /// Extensions of the task contexts, applied in the order they were registered
+    task_context_extensions: Vec<Arc<dyn TaskContextExtension>>,
+
+    /// Policy choosing the batch size of each task, the session's batch size if none
+    adaptive_batch_size: Option<Arc<AdaptiveBatchSize>>,
+
+    /// Maximum memory in bytes each task may reserve before spilling, 0 for no limit
+    task_memory_limit: usize,
+
+    /// Task slots sharing the files of scans, which are told when tasks start and complete
+    work_stealing: Option<Arc<WorkStealingPool>>,
+
+    /// Shuffle files written by the completed tasks, served to the tasks re-running them
+    stage_result_cache: Option<Arc<StageResultCache>>,
+
+    /// Free bytes of the work dirs under which new tasks are refused, 0 to accept tasks
+    /// whatever the free space
+    min_free_disk_bytes: u64,
+
+    /// Cgroup the executor runs in, whose usage is reported in the heartbeats
+    cgroup: Option<Arc<Cgroup>>,
+
+    /// Profiles and local root of the exports of results, provided to the tasks
+    export_config: Arc<ExportConfig>,
+
+    /// Object store registry of the runtime, whose object stores the tasks of the sessions
+    /// setting their own retry policy share
+    object_store_registry: Option<Arc<BallistaObjectStoreRegistry>>,
+
+    /// Object store registries of the tasks of the sessions setting their own retry policy,
+    /// by retry policy
+    object_store_registries:
+        Arc<Mutex<HashMap<ObjectStoreRetryConfig, Arc<dyn ObjectStoreRegistry>>>>,
 }
 
 impl Executor {
@@ -96,25 +200,279 @@ impl Executor {
         execution_engine: Option<Arc<dyn ExecutionEngine>>,
     ) -> Self {
         Self {
-            metadata,
             work_dir: work_dir.to_owned(),
-            // TODO add logic to dynamically load UDF/UDAFs libs from files
+            work_dirs: Arc::new(WorkDirs::new(
+                vec![work_dir.to_owned()],
+                WorkDirSelection::RoundRobin,
+            )),
+            spill_dirs: Arc::new(WorkDirs::new(
+                vec![spill_dir(work_dir)],
+                WorkDirSelection::RoundRobin,
+            )),
             scalar_functions: HashMap::new(),
             aggregate_functions: HashMap::new(),
+            table_factories: HashMap::new(),
             runtime,
+            task_slots: AtomicU32::new(
+                metadata
+                    .specification
+                    .as_ref()
+                    .map(|specification| {
+                        ExecutorSpecification::from(specification.clone()).task_slots
+                    })
+                    .unwrap_or(concurrent_tasks as u32),
+            ),
+            metadata,
             metrics_collector,
             concurrent_tasks,
             abort_handles: Default::default(),
+            running_tasks: Default::default(),
             execution_engine: execution_engine
-                .unwrap_or_else(|| Arc::new(DefaultExecutionEngine {})),
+                .unwrap_or_else(|| Arc::new(DefaultExecutionEngine::default())),
+            task_context_extensions: vec![],
+            adaptive_batch_size: None,
+            task_memory_limit: 0,
+            work_stealing: None,
+            stage_result_cache: None,
+            min_free_disk_bytes: 0,
+            cgroup: None,
+            export_config: Arc::new(ExportConfig::default()),
+            object_store_registry: None,
+            object_store_registries: Default::default(),
+        }
+    }
+
+    /// Limit the memory each task may reserve from the memory pool of the runtime, so that
+    /// the operators which can spill write to the work dir instead of exhausting the memory
+    /// of the executor
+    pub fn with_task_memory_limit(mut self, task_memory_limit: usize) -> Self {
+        self.task_memory_limit = task_memory_limit;
+        self
+    }
+
+    /// Spread the shuffle files of the tasks across `work_dirs`, such as one per local disk,
+    /// and their spill files across the spill subdirectories of the work dirs
+    pub fn with_work_dirs(mut self, work_dirs: WorkDirs) -> Self {
+        self.work_dir = work_dirs.dirs()[0].clone();
+        self.spill_dirs =
+            Arc::new(WorkDirs::new(work_dirs.spill_dirs(), work_dirs.selection()));
+        self.work_dirs = Arc::new(work_dirs);
+        self
+    }
+
+    /// Spill the files of the tasks to `spill_dir` instead of the spill subdirectories of
+    /// the work dirs. The spill files are written unencrypted, so `spill_dir` should be on an
+    /// encrypted volume when spilled rows may be sensitive.
+    pub fn with_spill_dir(mut self, spill_dir: &str) -> Self {
+        self.spill_dirs = Arc::new(WorkDirs::new(
+            vec![spill_dir.to_owned()],
+            WorkDirSelection::RoundRobin,
+        ));
+        self
+    }
+
+    /// Track the tasks occupying the task slots, so that the idle slots of `pool` help the
+    /// scans of the running tasks. The execution engine shares the scans with the same pool.
+    pub fn with_work_stealing(mut self, pool: Arc<WorkStealingPool>) -> Self {
+        self.work_stealing = Some(pool);
+        self
+    }
+
+    /// Serve the shuffle files written by a completed task to the tasks re-running the same
+    /// partition of its stage with the same plan, such as retries and speculative attempts
+    pub fn with_stage_result_cache(mut self, cache: Arc<StageResultCache>) -> Self {
+        self.stage_result_cache = Some(cache);
+        self
+    }
+
+    /// Refuse new tasks while the work dirs have less than `min_free_disk_bytes` free, so
+    /// that the scheduler runs them on other executors instead of failing them once the
+    /// disk is full
+    pub fn with_min_free_disk_bytes(mut self, min_free_disk_bytes: u64) -> Self {
+        self.min_free_disk_bytes = min_free_disk_bytes;
+        self
+    }
+
+    /// Report the CPU and memory usage of the cgroup the executor runs in
+    pub fn with_cgroup(mut self, cgroup: Arc<Cgroup>) -> Self {
+        self.cgroup = Some(cgroup);
+        self
+    }
+
+    /// Export the results of jobs with the credentials of the profiles of `export_config`,
+    /// and to `file://` URLs below its local root
+    pub fn with_export_config(mut self, export_config: ExportConfig) -> Self {
+        self.export_config = Arc::new(export_config);
+        self
+    }
+
+    /// Apply the retry policy of the object stores set by the session of each task, with the
+    /// object stores registered with `registry`, the object store registry of the runtime.
+    /// The tasks of the sessions which set no retry policy use the registry as is.
+    pub fn with_object_store_registry(
+        mut self,
+        registry: Arc<BallistaObjectStoreRegistry>,
+    ) -> Self {
+        self.object_store_registry = Some(registry);
+        self
+    }
+
+    /// Choose the batch size of each task from the width of the rows of its stage and the
+    /// memory reserved on the executor
+    pub fn with_adaptive_batch_size(
+        mut self,
+        adaptive_batch_size: Arc<AdaptiveBatchSize>,
+    ) -> Self {
+        self.adaptive_batch_size = Some(adaptive_batch_size);
+        self
+    }
+
+    /// Extend the task context of every task run by the executor
+    pub fn with_task_context_extension(
+        mut self,
+        extension: Arc<dyn TaskContextExtension>,
+    ) -> Self {
+        self.task_context_extensions.push(extension);
+        self
+    }
+
+    /// Register the functions, table factories and task context extensions of a plugin
+    pub fn with_plugin(mut self, plugin: &dyn ExecutorPlugin) -> Self {
+        for function in plugin.scalar_functions() {
+            self.scalar_functions
+                .insert(function.name.clone(), function);
+        }
+        for function in plugin.aggregate_functions() {
+            self.aggregate_functions
+                .insert(function.name.clone(), function);
+        }
+        self.table_factories.extend(plugin.table_factories());
+        self.task_context_extensions
+            .extend(plugin.task_context_extensions());
+        self
+    }
+
+    /// Task slots the executor advertises to the scheduler
+    pub fn task_slots(&self) -> u32 {
+        self.task_slots.load(Ordering::Acquire)
+    }
+
+    /// Advertise `task_slots` task slots to the scheduler from now on, returns the task slots
+    /// advertised before. The scheduler takes removed task slots away once the tasks running
+    /// on them complete. The next tasks run on as many threads, with push-based scheduling
+    /// and pull-based scheduling alike.
+    pub fn set_task_slots(&self, task_slots: u32) -> u32 {
+        self.task_slots.swap(task_slots, Ordering::AcqRel)
+    }
+
+    /// Session config of a task, with the batch size chosen for the task if the batch size is
+    /// adaptive, the metadata of the task, the registered extensions and table factories, and
+    /// the export config
+    pub fn task_session_config(
+        &self,
+        task: TaskMetadata,
+        config: SessionConfig,
+    ) -> SessionConfig {
+        let config = match &self.adaptive_batch_size {
+            Some(policy) => {
+                let batch_size = policy.batch_size(
+                    &task.job_id,
+                    task.stage_id,
+                    config.batch_size(),
+                    self.runtime.memory_pool.reserved(),
+                );
+                config.with_batch_size(batch_size)
+            }
+            None => config,
+        };
+        let config = self
+            .task_context_extensions
+            .iter()
+            .fold(config, |config, extension| extension.extend(&task, config));
+        let config = if self.table_factories.is_empty() {
+            config
+        } else {
+            config.with_extension(Arc::new(TableFactories(self.table_factories.clone())))
+        };
+        config
+            .with_extension(self.export_config.clone())
+            .with_extension(Arc::new(task))
+    }
+
+    /// Meter of the resources used by a task, which executes it within the memory limit of
+    /// the tasks and spills to a new directory of one of the spill dirs, removed once it
+    /// completes
+    pub fn task_resource_meter(
+        &self,
+        job_id: &str,
+        task_id: usize,
+        session_config: &SessionConfig,
+    ) -> Result<TaskResourceMeter, BallistaError> {
+        let spill_root = self.spill_dirs.select();
+        ballista_core::utils::create_private_dir_all(spill_root)?;
+        let spill_dir = tempfile::Builder::new()
+            .prefix(&format!("{job_id}-{task_id}-"))
+            .tempdir_in(spill_root)?;
+        Ok(TaskResourceMeter::new(&self.runtime)
+            .with_object_store_registry(self.object_store_registry(session_config))
+            .with_batch_size(session_config.batch_size())
+            .with_memory_limit(self.task_memory_limit)
+            .with_spill_dir(spill_dir)?)
+    }
+
+    /// Object store registry of a task, whose detected object stores retry failed requests
+    /// with the retry policy of its session. The object stores registered explicitly, such
+    /// as the ones configured with options, keep their retry policy.
+    fn object_store_registry(
+        &self,
+        session_config: &SessionConfig,
+    ) -> Arc<dyn ObjectStoreRegistry> {
+        let (registry, config) = match (
+            &self.object_store_registry,
+            session_config.get_extension::<BallistaConfig>(),
+        ) {
+            (Some(registry), Some(config)) => (registry, config),
+            _ => return self.runtime.object_store_registry.clone(),
+        };
+        let retry = config.object_store_retry_or(registry.retry());
+        if &retry == registry.retry() {
+            return self.runtime.object_store_registry.clone();
         }
+        self.object_store_registries
+            .lock()
+            .entry(retry.clone())
+            .or_insert_with(|| Arc::new(registry.with_shared_stores(retry)))
+            .clone()
     }
 }
 
+/// Session config of a task from its properties, the DataFusion settings of its job and the
+/// Ballista settings of its session applied to the task, such as the retry policy of the
+/// object stores
+pub fn task_props_config(
+    props: impl IntoIterator<Item = (String, String)>,
+) -> Result<SessionConfig, BallistaError> {
+    let mut config = ConfigOptions::new();
+    let mut ballista_settings = HashMap::new();
+    for (key, value) in props {
+        if key.starts_with(BALLISTA_OBJECT_STORE_PREFIX) {
+            ballista_settings.insert(key, value);
+        } else {
+            config.set(&key, &value)?;
+        }
+    }
+    let config = SessionConfig::from(config);
+    Ok(if ballista_settings.is_empty() {
+        config
+    } else {
+        config.with_extension(Arc::new(BallistaConfig::with_settings(ballista_settings)?))
+    })
+}
+
 impl Executor {
     /// Execute one partition of a query stage and persist the result to disk in IPC format. On
     /// success, return a RecordBatch containing metadata about the results, including path
-    /// and statistics.
+    /// and statistics. A panic of the task fails it without affecting the other tasks.
     pub async fn execute_query_stage(
         &self,
         task_id: usize,
@@ -128,10 +486,35 @@ impl Executor {
 
         self.abort_handles
             .insert((task_id, partition.clone()), abort_handle);
+        self.running_tasks.insert(
+            (task_id, partition.clone()),
+            RunningTask::new(query_stage_exec.clone()),
+        );
 
-        let partitions = task.await??;
-
+        if let Some(pool) = &self.work_stealing {
+            pool.task_started();
+        }
+        let result = AssertUnwindSafe(task).catch_unwind().await;
+        if let Some(pool) = &self.work_stealing {
+            pool.task_finished();
+        }
+        self.running_tasks.remove(&(task_id, partition.clone()));
         self.abort_handles.remove(&(task_id, partition.clone()));
+        let partitions = match result {
+            Ok(result) => result??,
+            Err(panic) => {
+                error!(
+                    "Task {task_id} of job {} panicked: {}",
+                    partition.job_id,
+                    any_to_string(&panic)
+                );
+                return Err(panic_error(&panic));
+            }
+        };
+
+        if let Some(policy) = &self.adaptive_batch_size {
+            policy.record(&partition.job_id, partition.stage_id, &partitions);
+        }
 
         self.metrics_collector.record_stage(
             &partition.job_id,
@@ -143,6 +526,45 @@ impl Executor {
         Ok(partitions)
     }
 
+    /// Execute one partition of a query stage like `execute_query_stage`, unless a previous
+    /// task wrote the shuffle files of the partition with a plan of the same fingerprint, in
+    /// which case these files are served instead
+    pub async fn execute_query_stage_with_cache(
+        &self,
+        task_id: usize,
+        partition: PartitionId,
+        query_stage_exec: Arc<dyn QueryStageExecutor>,
+        task_ctx: Arc<TaskContext>,
+        plan_fingerprint: u64,
+    ) -> Result<Vec<protobuf::ShuffleWritePartition>, BallistaError> {
+        let cache = match &self.stage_result_cache {
+            Some(cache) => cache,
+            None => {
+                return self
+                    .execute_query_stage(task_id, partition, query_stage_exec, task_ctx)
+                    .await
+            }
+        };
+        if let Some(partitions) = cache.get(&partition, plan_fingerprint) {
+            info!(
+                "Task {task_id} serves the shuffle files previously written for {partition:?}"
+            );
+            return Ok(partitions);
+        }
+        let partitions = self
+            .execute_query_stage(task_id, partition.clone(), query_stage_exec, task_ctx)
+            .await?;
+        cache.insert(partition, plan_fingerprint, &partitions);
+        Ok(partitions)
+    }
+
+    /// Forget the cached shuffle files of a job, whose data is removed
+    pub fn remove_cached_stage_results(&self, job_id: &str) {
+        if let Some(cache) = &self.stage_result_cache {
+            cache.remove_job(job_id);
+        }
+    }
+
     pub async fn cancel_task(
         &self,
         task_id: usize,
@@ -169,32 +591,157 @@ impl Executor {
         &self.work_dir
     }
 
+    /// Disk usage of the work dirs reported in the heartbeats, none if it is unknown
+    pub fn disk_usage(&self) -> Option<protobuf::DiskUsage> {
+        let space = self.work_dirs.disk_space()?;
+        Some(protobuf::DiskUsage {
+            available_bytes: space.available_bytes,
+            total_bytes: space.total_bytes,
+            below_watermark: space.available_bytes < self.min_free_disk_bytes,
+        })
+    }
+
+    /// CPU and memory usage of the cgroup of the executor, none if it runs in no cgroup of
+    /// its own
+    pub fn cgroup_stats(&self) -> Option<protobuf::CgroupStats> {
+        self.cgroup.as_ref()?.stats()
+    }
+
+    /// The reason new tasks are refused if the free space of the work dirs is below the
+    /// watermark
+    pub fn disk_space_shortage(&self) -> Option<String> {
+        if self.min_free_disk_bytes == 0 {
+            return None;
+        }
+        let usage = self.disk_usage()?;
+        usage.below_watermark.then(|| {
+            format!(
+                "The work dirs of the executor have {} bytes free, below the watermark of {} bytes",
+                usage.available_bytes, self.min_free_disk_bytes
+            )
+        })
+    }
+
     pub fn active_task_count(&self) -> usize {
         self.abort_handles.len()
     }
+
+    /// The tasks currently executing, which a restarted scheduler reconciles its jobs with
+    pub fn running_tasks(&self) -> Vec<protobuf::RunningTaskInfo> {
+        self.running_tasks
+            .iter()
+            .map(|entry| {
+                let (task_id, partition) = entry.key();
+                protobuf::RunningTaskInfo {
+                    task_id: *task_id as u32,
+                    job_id: partition.job_id.clone(),
+                    stage_id: partition.stage_id as u32,
+                    partition_id: partition.partition_id as u32,
+                }
+            })
+            .collect()
+    }
+
+    /// Progress of the executing tasks, reported in the heartbeats of the executor. The idle
+    /// time of a task is the time since its progress changed from one call to the next.
+    pub fn task_progress(&self) -> Vec<protobuf::TaskProgress> {
+        let now = Instant::now();
+        self.running_tasks
+            .iter()
+            .map(|entry| {
+                let ((task_id, partition), task) = entry.pair();
+                let (operator, output_rows) = task
+                    .query_stage_exec
+                    .operator_progress()
+                    .unwrap_or_default();
+                let output_bytes = task
+                    .query_stage_exec
+                    .collect_plan_metrics()
+                    .first()
+                    .and_then(|metrics| metrics.sum_by_name("output_bytes"))
+                    .map(|value| value.as_usize())
+                    .unwrap_or_default();
+                let progress = (operator, output_rows as u64, output_bytes as u64);
+
+                let mut last_progress = task.last_progress.lock();
+                if last_progress.0 != progress {
+                    *last_progress = (progress.clone(), now);
+                }
+                let (operator, output_rows, output_bytes) = progress;
+                protobuf::TaskProgress {
+                    task_id: *task_id as u32,
+                    job_id: partition.job_id.clone(),
+                    stage_id: partition.stage_id as u32,
+                    partition_id: partition.partition_id as u32,
+                    operator,
+                    output_rows,
+                    output_bytes,
+                    elapsed_ms: now.duration_since(task.started).as_millis() as u64,
+                    idle_ms: now.duration_since(last_progress.1).as_millis() as u64,
+                }
+            })
+            .collect()
+    }
+
+    /// Wait for the running tasks to complete, for at most `grace_period`. The tasks still
+    /// running afterwards are aborted and reported as failed, so that the scheduler runs
+    /// them again on other executors. Returns the number of aborted tasks.
+    pub async fn drain_tasks(&self, grace_period: Duration) -> usize {
+        let deadline = Instant::now() + grace_period;
+        while self.active_task_count() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(TASKS_DRAINED_CHECK_INTERVAL).await;
+        }
+        let running: Vec<(usize, PartitionId)> = self
+            .abort_handles
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        let mut aborted = 0;
+        for key in running {
+            if let Some((_, handle)) = self.abort_handles.remove(&key) {
+                handle.abort();
+                aborted += 1;
+            }
+        }
+        aborted
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::executor::Executor;
+    use crate::executor::{
+        task_props_config, Executor, TableFactories, TaskContextExtension,
+    };
     use crate::metrics::LoggingMetricsCollector;
-    use arrow::datatypes::{Schema, SchemaRef};
+    use crate::plugin::ExecutorPlugin;
+    use arrow::datatypes::{DataType, Schema, SchemaRef};
     use arrow::record_batch::RecordBatch;
+    use ballista_core::config::{BallistaConfig, BALLISTA_OBJECT_STORE_MAX_RETRIES};
+    use ballista_core::error::BallistaError;
     use ballista_core::execution_plans::ShuffleWriterExec;
     use ballista_core::serde::protobuf::ExecutorRegistration;
+    use ballista_core::utils::BallistaObjectStoreRegistry;
+    use datafusion::datasource::listing_table_factory::ListingTableFactory;
+    use datafusion::datasource::object_store::ObjectStoreRegistry;
+    use datafusion::datasource::provider::TableProviderFactory;
     use datafusion::execution::context::TaskContext;
+    use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
 
     use crate::execution_engine::DefaultQueryStageExec;
-    use ballista_core::serde::scheduler::PartitionId;
+    use ballista_core::serde::scheduler::{PartitionId, TaskMetadata};
     use datafusion::error::DataFusionError;
+    use datafusion::logical_expr::{create_udf, ColumnarValue, Volatility};
     use datafusion::physical_expr::PhysicalSortExpr;
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use datafusion::physical_plan::udf::ScalarUDF;
     use datafusion::physical_plan::{
         ExecutionPlan, Partitioning, RecordBatchStream, SendableRecordBatchStream,
         Statistics,
     };
-    use datafusion::prelude::SessionContext;
+    use datafusion::prelude::{SessionConfig, SessionContext};
     use futures::Stream;
     use std::any::Any;
+    use std::collections::HashMap;
     use std::pin::Pin;
     use std::sync::Arc;
     use std::task::{Context, Poll};
@@ -266,6 +813,59 @@ mod test {
         }
     }
 
+    /// An ExecutionPlan whose stream panics
+    #[derive(Debug)]
+    pub struct PanickingOperator;
+
+    impl ExecutionPlan for PanickingOperator {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn schema(&self) -> SchemaRef {
+            Arc::new(Schema::empty())
+        }
+
+        fn output_partitioning(&self) -> Partitioning {
+            Partitioning::UnknownPartitioning(1)
+        }
+
+        fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+            None
+        }
+
+        fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+            vec![]
+        }
+
+        fn with_new_children(
+            self: Arc<Self>,
+            _children: Vec<Arc<dyn ExecutionPlan>>,
+        ) -> datafusion::common::Result<Arc<dyn ExecutionPlan>> {
+            Ok(self)
+        }
+
+        fn execute(
+            &self,
+            _partition: usize,
+            _context: Arc<TaskContext>,
+        ) -> datafusion::common::Result<SendableRecordBatchStream> {
+            let stream = futures::stream::poll_fn(
+                |_| -> Poll<Option<Result<RecordBatch, DataFusionError>>> {
+                    panic!("Bad query")
+                },
+            );
+            Ok(Box::pin(RecordBatchStreamAdapter::new(
+                self.schema(),
+                stream,
+            )))
+        }
+
+        fn statistics(&self) -> Statistics {
+            Statistics::default()
+        }
+    }
+
     #[tokio::test]
     async fn test_task_cancellation() {
         let work_dir = TempDir::new()
@@ -292,6 +892,7 @@ mod test {
             grpc_port: 0,
             specification: None,
             optional_host: None,
+            pool: String::new(),
         };
 
         let ctx = SessionContext::new();
@@ -321,6 +922,23 @@ mod test {
             sender.send(task_result).expect("sending result");
         });
 
+        // The running task is reported without progress, as its plan produced no rows
+        let mut progress = vec![];
+        for _ in 0..20 {
+            progress = executor.task_progress();
+            if !progress.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].job_id, "job-id");
+        assert_eq!(progress[0].operator, "");
+        assert_eq!(progress[0].output_rows, 0);
+        let running_tasks = executor.running_tasks();
+        assert_eq!(running_tasks.len(), 1);
+        assert_eq!(running_tasks[0].task_id, 1);
+
         // Now cancel the task. We can only cancel once the task has been executed and has an `AbortHandle` registered, so
         // poll until that happens.
         for _ in 0..20 {
@@ -344,5 +962,291 @@ mod test {
         // Make sure the actual task failed
         let inner_result = result.unwrap().unwrap();
         assert!(inner_result.is_err());
+        assert!(executor.task_progress().is_empty());
+        assert!(executor.running_tasks().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_drain_tasks() {
+        let work_dir = TempDir::new()
+            .unwrap()
+            .into_path()
+            .into_os_string()
+            .into_string()
+            .unwrap();
+
+        let shuffle_write = ShuffleWriterExec::try_new(
+            "job-id".to_owned(),
+            1,
+            Arc::new(NeverendingOperator),
+            work_dir.clone(),
+            None,
+        )
+        .expect("creating shuffle writer");
+
+        let executor_registration = ExecutorRegistration {
+            id: "executor".to_string(),
+            port: 0,
+            grpc_port: 0,
+            specification: None,
+            optional_host: None,
+            pool: String::new(),
+        };
+
+        let ctx = SessionContext::new();
+
+        let executor = Executor::new(
+            executor_registration,
+            &work_dir,
+            ctx.runtime_env(),
+            Arc::new(LoggingMetricsCollector {}),
+            2,
+            None,
+        );
+
+        // Nothing to wait for without running tasks
+        assert_eq!(executor.drain_tasks(Duration::from_secs(60)).await, 0);
+
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let executor_clone = executor.clone();
+        tokio::task::spawn(async move {
+            let part = PartitionId {
+                job_id: "job-id".to_owned(),
+                stage_id: 1,
+                partition_id: 0,
+            };
+            let query_stage_exec = DefaultQueryStageExec::new(shuffle_write);
+            let task_result = executor_clone
+                .execute_query_stage(1, part, Arc::new(query_stage_exec), ctx.task_ctx())
+                .await;
+            sender.send(task_result).expect("sending result");
+        });
+        for _ in 0..20 {
+            if executor.active_task_count() > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // The task never completes, it is aborted once the grace period elapsed
+        assert_eq!(executor.drain_tasks(Duration::from_millis(200)).await, 1);
+        assert_eq!(executor.active_task_count(), 0);
+        let result = tokio::time::timeout(Duration::from_secs(5), receiver).await;
+        assert!(result.unwrap().unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_task_panic() {
+        let work_dir = TempDir::new()
+            .unwrap()
+            .into_path()
+            .into_os_string()
+            .into_string()
+            .unwrap();
+
+        let shuffle_write = ShuffleWriterExec::try_new(
+            "job-id".to_owned(),
+            1,
+            Arc::new(PanickingOperator),
+            work_dir.clone(),
+            None,
+        )
+        .expect("creating shuffle writer");
+
+        let executor_registration = ExecutorRegistration {
+            id: "executor".to_string(),
+            port: 0,
+            grpc_port: 0,
+            specification: None,
+            optional_host: None,
+            pool: String::new(),
+        };
+
+        let ctx = SessionContext::new();
+
+        let executor = Executor::new(
+            executor_registration,
+            &work_dir,
+            ctx.runtime_env(),
+            Arc::new(LoggingMetricsCollector {}),
+            2,
+            None,
+        );
+
+        let part = PartitionId {
+            job_id: "job-id".to_owned(),
+            stage_id: 1,
+            partition_id: 0,
+        };
+        let query_stage_exec = DefaultQueryStageExec::new(shuffle_write);
+        let result = executor
+            .execute_query_stage(1, part, Arc::new(query_stage_exec), ctx.task_ctx())
+            .await;
+
+        // The panic fails the task, which leaves nothing behind
+        let error = result.unwrap_err().to_string();
+        assert!(error.contains("Task panicked: Bad query"), "{error}");
+        assert_eq!(executor.active_task_count(), 0);
+        assert!(executor.task_progress().is_empty());
+    }
+
+    /// Handle of a resource shared by the tasks of the executor
+    struct RateLimiter {
+        permits: usize,
+    }
+
+    struct RateLimiterExtension;
+
+    impl TaskContextExtension for RateLimiterExtension {
+        fn extend(&self, task: &TaskMetadata, config: SessionConfig) -> SessionConfig {
+            config.with_extension(Arc::new(RateLimiter {
+                permits: task.partition_id + 10,
+            }))
+        }
+    }
+
+    #[test]
+    fn test_task_context_extension() {
+        let executor = Executor::new(
+            ExecutorRegistration::default(),
+            "",
+            SessionContext::new().runtime_env(),
+            Arc::new(LoggingMetricsCollector {}),
+            2,
+            None,
+        )
+        .with_task_context_extension(Arc::new(RateLimiterExtension));
+
+        let task = TaskMetadata {
+            job_id: "job-id".to_owned(),
+            stage_id: 1,
+            stage_attempt_num: 0,
+            partition_id: 2,
+            task_id: 3,
+            task_attempt_num: 0,
+        };
+        let config = executor.task_session_config(task.clone(), SessionConfig::new());
+        let task_ctx = SessionContext::with_config(config).task_ctx();
+
+        let rate_limiter = task_ctx
+            .session_config()
+            .get_extension::<RateLimiter>()
+            .expect("rate limiter extension");
+        assert_eq!(rate_limiter.permits, 12);
+        assert_eq!(
+            TaskMetadata::from_task_context(&task_ctx).as_deref(),
+            Some(&task)
+        );
+    }
+
+    struct TestPlugin;
+
+    impl ExecutorPlugin for TestPlugin {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn scalar_functions(&self) -> Vec<Arc<ScalarUDF>> {
+            vec![Arc::new(create_udf(
+                "identity",
+                vec![DataType::Int64],
+                Arc::new(DataType::Int64),
+                Volatility::Immutable,
+                Arc::new(|args: &[ColumnarValue]| Ok(args[0].clone())),
+            ))]
+        }
+
+        fn task_context_extensions(&self) -> Vec<Arc<dyn TaskContextExtension>> {
+            vec![Arc::new(RateLimiterExtension)]
+        }
+
+        fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+            HashMap::from([(
+                "TEST".to_owned(),
+                Arc::new(ListingTableFactory::new()) as Arc<dyn TableProviderFactory>,
+            )])
+        }
+    }
+
+    #[test]
+    fn test_executor_plugin() {
+        let executor = Executor::new(
+            ExecutorRegistration::default(),
+            "",
+            SessionContext::new().runtime_env(),
+            Arc::new(LoggingMetricsCollector {}),
+            2,
+            None,
+        )
+        .with_plugin(&TestPlugin);
+        assert!(executor.scalar_functions.contains_key("identity"));
+        assert!(executor.aggregate_functions.is_empty());
+
+        let task = TaskMetadata {
+            job_id: "job-id".to_owned(),
+            stage_id: 1,
+            stage_attempt_num: 0,
+            partition_id: 0,
+            task_id: 1,
+            task_attempt_num: 0,
+        };
+        let config = executor.task_session_config(task, SessionConfig::new());
+        assert!(config.get_extension::<RateLimiter>().is_some());
+        let table_factories = config
+            .get_extension::<TableFactories>()
+            .expect("table factories extension");
+        assert!(table_factories.get("TEST").is_some());
+    }
+
+    #[test]
+    fn test_task_object_store_retry() -> Result<(), BallistaError> {
+        let registry = Arc::new(BallistaObjectStoreRegistry::new());
+        let runtime = Arc::new(RuntimeEnv::new(
+            RuntimeConfig::new().with_object_store_registry(registry.clone()),
+        )?);
+        let executor = Executor::new(
+            ExecutorRegistration::default(),
+            "",
+            runtime.clone(),
+            Arc::new(LoggingMetricsCollector {}),
+            2,
+            None,
+        )
+        .with_object_store_registry(registry);
+        let same = |a: &Arc<dyn ObjectStoreRegistry>,
+                    b: &Arc<dyn ObjectStoreRegistry>| {
+            Arc::as_ptr(a) as *const () == Arc::as_ptr(b) as *const ()
+        };
+
+        // The tasks of sessions setting no retry policy use the registry of the runtime
+        let config = task_props_config(vec![(
+            "datafusion.execution.batch_size".to_owned(),
+            "100".to_owned(),
+        )])?;
+        assert_eq!(100, config.batch_size());
+        assert!(config.get_extension::<BallistaConfig>().is_none());
+        assert!(same(
+            &executor.object_store_registry(&config),
+            &runtime.object_store_registry
+        ));
+
+        // The tasks of sessions setting the same retry policy share a registry
+        let props = vec![(BALLISTA_OBJECT_STORE_MAX_RETRIES.to_owned(), "3".to_owned())];
+        let config = task_props_config(props.clone())?;
+        assert_eq!(
+            3,
+            config
+                .get_extension::<BallistaConfig>()
+                .expect("Ballista config extension")
+                .object_store_retry()
+                .max_retries
+        );
+        let task_registry = executor.object_store_registry(&config);
+        assert!(!same(&task_registry, &runtime.object_store_registry));
+        assert!(same(
+            &task_registry,
+            &executor.object_store_registry(&task_props_config(props)?)
+        ));
+        Ok(())
     }
 }
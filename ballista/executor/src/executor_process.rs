@@ -18,9 +18,10 @@
 //! Ballista Executor Process
 
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::{env, io};
 
 use anyhow::{Context, Result};
@@ -34,35 +35,56 @@ use tokio::signal;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 use tokio::{fs, time};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
 
+use datafusion::execution::disk_manager::DiskManagerConfig;
+use datafusion::execution::memory_pool::FairSpillPool;
 use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
-use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
 
-use ballista_core::config::{LogRotationPolicy, TaskSchedulingPolicy};
+use ballista_core::config::{
+    GrpcCompression, LogRotationPolicy, ObjectStoreRetryConfig, TaskSchedulingPolicy,
+    WorkDirSelection,
+};
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::ExportConfig;
 use ballista_core::serde::protobuf::executor_resource::Resource;
 use ballista_core::serde::protobuf::executor_status::Status;
 use ballista_core::serde::protobuf::{
-    executor_registration, scheduler_grpc_client::SchedulerGrpcClient,
-    ExecutorRegistration, ExecutorResource, ExecutorSpecification, ExecutorStatus,
-    ExecutorStoppedParams, HeartBeatParams,
+    executor_registration, ExecutorRegistration, ExecutorResource, ExecutorSpecification,
+    ExecutorStatus, ExecutorStoppedParams, HeartBeatParams,
 };
 use ballista_core::serde::BallistaCodec;
 use ballista_core::utils::{
-    create_grpc_client_connection, create_grpc_server, with_object_store_provider,
+    create_grpc_server, create_private_dir_all, BallistaObjectStoreRegistry,
+    DefaultObjectStores,
 };
 use ballista_core::BALLISTA_VERSION;
 
-use crate::execution_engine::ExecutionEngine;
-use crate::executor::{Executor, TasksDrainedFuture};
+use crate::batch_size::{AdaptiveBatchSize, AdaptiveBatchSizeConfig};
+use crate::cgroup::{Cgroup, CgroupConfig};
+use crate::execution_engine::{DefaultExecutionEngine, ExecutionEngine};
+use crate::executor::{Executor, TaskContextExtension};
 use crate::executor_server::TERMINATING;
 use crate::flight_service::BallistaFlightService;
 use crate::metrics::LoggingMetricsCollector;
+use crate::plugin::{load_plugins, ExecutorPlugin};
+use crate::scheduler_endpoints::{
+    scheduler_urls, SchedulerEndpoints, SchedulerReconnectConfig,
+};
 use crate::shutdown::Shutdown;
 use crate::shutdown::ShutdownNotifier;
+use crate::stage_result_cache::StageResultCache;
+use crate::task_logs::{TaskLogLayer, TaskLogs};
 use crate::terminate;
+use crate::work_dir_recovery::{
+    previous_executor_id, recover_work_dirs, save_executor_id, EXECUTOR_ID_FILE,
+};
+use crate::work_dirs::WorkDirs;
+use crate::work_stealing::WorkStealingPool;
 use crate::{execution_loop, executor_server};
 
 pub struct ExecutorProcessConfig {
@@ -73,29 +95,159 @@ pub struct ExecutorProcessConfig {
     pub scheduler_host: String,
     pub scheduler_port: u16,
     pub scheduler_connect_timeout_seconds: u16,
+    /// `host:port` addresses of the standby schedulers the executor fails over to when its
+    /// scheduler is unreachable
+    pub standby_schedulers: Vec<String>,
+    /// Backoff between the attempts to reconnect to an unreachable scheduler
+    pub scheduler_reconnect: SchedulerReconnectConfig,
     pub concurrent_tasks: usize,
+    /// Logical task slots offered to the scheduler per concurrent task, at least 1
+    pub task_slot_oversubscription: f64,
+    /// Memory in bytes this executor offers to tasks, 0 means not limited
+    pub memory_bytes: u64,
+    /// Memory in bytes each task may reserve before spilling, 0 to divide `memory_bytes`
+    /// evenly among the concurrent tasks
+    pub task_memory_bytes: u64,
+    /// Virtual cores this executor offers to tasks, 0 means not limited
+    pub vcores: u32,
+    /// GPUs this executor offers to the tasks of stages requiring GPUs, 0 if it has none
+    pub gpus: u32,
+    /// Pool of executors this executor joins, empty for the default pool
+    pub pool: String,
     pub task_scheduling_policy: TaskSchedulingPolicy,
     pub log_dir: Option<String>,
+    /// Comma separated work dirs, such as one per local disk, a temporary directory if none
     pub work_dir: Option<String>,
+    /// Selection of the work dir each task writes to when there are several
+    pub work_dir_selection: WorkDirSelection,
+    /// Directory the tasks spill to, each in its own subdirectory, none for the spill
+    /// subdirectories of the work dirs
+    pub spill_dir: Option<String>,
+    /// File of the export profiles holding the credentials of the export locations
+    pub export_profiles_file: Option<String>,
+    /// Directory the results may be exported to with `file://` URLs, none to reject them
+    pub export_local_root: Option<String>,
     pub special_mod_log_level: String,
     pub print_thread_info: bool,
     pub log_file_name_prefix: String,
     pub log_rotation_policy: LogRotationPolicy,
+    /// Recent task attempts whose log lines are kept for the scheduler, 0 disables it
+    pub task_log_max_tasks: usize,
+    /// Last log lines kept for each task attempt
+    pub task_log_max_lines: usize,
     pub job_data_ttl_seconds: u64,
     pub job_data_clean_up_interval_seconds: u64,
     /// The maximum size of a decoded message at the grpc server side.
     pub grpc_server_max_decoding_message_size: u32,
+    /// Retry policy for failed requests to object stores
+    pub object_store_retry: ObjectStoreRetryConfig,
+    /// Object stores configured with options registered with the runtime of the tasks
+    pub object_stores: DefaultObjectStores,
+    /// Batches of scans read ahead while the batches already read are processed, 0 disables
+    /// prefetching. Only applies to the default execution engine.
+    pub scan_prefetch_batches: usize,
+    /// Whether the idle task slots scan the remaining files of the Parquet scans of the
+    /// running tasks. Only applies to the default execution engine.
+    pub work_stealing: bool,
+    /// Number of task results whose shuffle files are served to the tasks re-running the same
+    /// partition of a stage with the same plan, 0 disables caching them
+    pub stage_result_cache_entries: usize,
+    /// Free bytes of the work dirs under which new tasks are refused, 0 to accept tasks
+    /// whatever the free space
+    pub min_free_disk_bytes: u64,
+    /// Cgroup v2 enforcing the CPU and memory limits of the executor, none to leave them to
+    /// the container runtime
+    pub cgroup: Option<CgroupConfig>,
+    /// Bounds of the batch sizes chosen for each task from the width of its rows and the memory
+    /// pressure, none to use the batch size of the session
+    pub adaptive_batch_size: Option<AdaptiveBatchSizeConfig>,
+    /// Compression of the messages the executor sends to schedulers, such as task statuses
+    pub grpc_compression: GrpcCompression,
+    /// Maximum number of task statuses reported in one update or poll, 0 for no limit
+    pub task_status_batch_size: usize,
+    /// Time in milliseconds to wait for more finished tasks before reporting the statuses
+    /// collected, 0 to report them right away
+    pub task_status_flush_interval_ms: u64,
     /// Optional execution engine to use to execute physical plans, will default to
     /// DataFusion if none is provided.
     pub execution_engine: Option<Arc<dyn ExecutionEngine>>,
+    /// Extensions of the task contexts of the tasks run by the executor
+    pub task_context_extensions: Vec<Arc<dyn TaskContextExtension>>,
+    /// Plugins registered with the executor, applied before the ones of the plugin dir
+    pub plugins: Vec<Arc<dyn ExecutorPlugin>>,
+    /// Directory of shared libraries with executor plugins to load, none if empty
+    pub plugin_dir: String,
+    /// Codec of the plans of the tasks, whose extension codecs decode the custom plan nodes
+    /// and tables of the plans
+    pub codec: BallistaCodec,
+    /// Time in seconds the running tasks are given to complete once the executor is asked to
+    /// shut down, after which they are aborted and run again on other executors
+    pub shutdown_grace_period_seconds: u64,
+}
+
+impl Default for ExecutorProcessConfig {
+    /// The defaults of the command-line parameters of the executor
+    fn default() -> Self {
+        Self {
+            bind_host: "0.0.0.0".to_owned(),
+            external_host: None,
+            port: 50051,
+            grpc_port: 50052,
+            scheduler_host: "localhost".to_owned(),
+            scheduler_port: 50050,
+            scheduler_connect_timeout_seconds: 0,
+            standby_schedulers: vec![],
+            scheduler_reconnect: SchedulerReconnectConfig::default(),
+            concurrent_tasks: 0,
+            task_slot_oversubscription: 1.0,
+            memory_bytes: 0,
+            task_memory_bytes: 0,
+            vcores: 0,
+            gpus: 0,
+            pool: String::new(),
+            task_scheduling_policy: TaskSchedulingPolicy::PullStaged,
+            log_dir: None,
+            work_dir: None,
+            work_dir_selection: WorkDirSelection::RoundRobin,
+            spill_dir: None,
+            export_profiles_file: None,
+            export_local_root: None,
+            special_mod_log_level: "INFO,datafusion=INFO".to_owned(),
+            print_thread_info: true,
+            log_file_name_prefix: "executor".to_owned(),
+            log_rotation_policy: LogRotationPolicy::Daily,
+            task_log_max_tasks: 1000,
+            task_log_max_lines: 10000,
+            job_data_ttl_seconds: 604800,
+            job_data_clean_up_interval_seconds: 0,
+            grpc_server_max_decoding_message_size: 16777216,
+            object_store_retry: ObjectStoreRetryConfig::default(),
+            object_stores: DefaultObjectStores::default(),
+            scan_prefetch_batches: 0,
+            work_stealing: false,
+            stage_result_cache_entries: 0,
+            min_free_disk_bytes: 0,
+            cgroup: None,
+            adaptive_batch_size: None,
+            grpc_compression: GrpcCompression::None,
+            task_status_batch_size: 0,
+            task_status_flush_interval_ms: 0,
+            execution_engine: None,
+            task_context_extensions: vec![],
+            plugins: vec![],
+            plugin_dir: String::new(),
+            codec: BallistaCodec::default(),
+            shutdown_grace_period_seconds: 30,
+        }
+    }
 }
 
 pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<()> {
     let rust_log = env::var(EnvFilter::DEFAULT_ENV);
     let log_filter =
         EnvFilter::new(rust_log.unwrap_or(opt.special_mod_log_level.clone()));
-    // File layer
-    if let Some(log_dir) = opt.log_dir.clone() {
+    // File layer, or console layer without a log dir
+    let log_writer = if let Some(log_dir) = opt.log_dir.clone() {
         let log_file = match opt.log_rotation_policy {
             LogRotationPolicy::Minutely => {
                 tracing_appender::rolling::minutely(log_dir, &opt.log_file_name_prefix)
@@ -110,40 +262,56 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                 tracing_appender::rolling::never(log_dir, &opt.log_file_name_prefix)
             }
         };
-        tracing_subscriber::fmt()
-            .with_ansi(false)
-            .with_thread_names(opt.print_thread_info)
-            .with_thread_ids(opt.print_thread_info)
-            .with_writer(log_file)
-            .with_env_filter(log_filter)
-            .init();
+        BoxMakeWriter::new(log_file)
     } else {
-        // Console layer
-        tracing_subscriber::fmt()
-            .with_ansi(false)
-            .with_thread_names(opt.print_thread_info)
-            .with_thread_ids(opt.print_thread_info)
-            .with_writer(io::stdout)
-            .with_env_filter(log_filter)
-            .init();
-    }
+        BoxMakeWriter::new(io::stdout)
+    };
+    let task_logs = (opt.task_log_max_tasks > 0).then(|| {
+        Arc::new(TaskLogs::new(
+            opt.task_log_max_tasks,
+            opt.task_log_max_lines,
+        ))
+    });
+    // The task logs only capture the lines passing the log filter. A binary embedding the
+    // executor may have set up its own logging already, which is kept without task logs.
+    let task_logs = tracing_subscriber::registry()
+        .with(log_filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_thread_names(opt.print_thread_info)
+                .with_thread_ids(opt.print_thread_info)
+                .with_writer(log_writer),
+        )
+        .with(task_logs.clone().map(TaskLogLayer::new))
+        .try_init()
+        .ok()
+        .and(task_logs);
 
     let addr = format!("{}:{}", opt.bind_host, opt.port);
     let addr = addr
         .parse()
         .with_context(|| format!("Could not parse address: {addr}"))?;
 
-    let scheduler_host = opt.scheduler_host.clone();
-    let scheduler_port = opt.scheduler_port;
-    let scheduler_url = format!("http://{scheduler_host}:{scheduler_port}");
+    let scheduler_endpoints = Arc::new(SchedulerEndpoints::new(
+        scheduler_urls(
+            &opt.scheduler_host,
+            opt.scheduler_port,
+            &opt.standby_schedulers,
+        )?,
+        opt.grpc_compression,
+        opt.scheduler_reconnect,
+    ));
 
-    let work_dir = opt.work_dir.clone().unwrap_or(
-        TempDir::new()?
+    let work_dirs = match opt.work_dir.as_deref().map(WorkDirs::parse) {
+        Some(work_dirs) if !work_dirs.is_empty() => work_dirs,
+        _ => vec![TempDir::new()?
             .into_path()
             .into_os_string()
             .into_string()
-            .unwrap(),
-    );
+            .unwrap()],
+    };
+    let work_dirs = WorkDirs::new(work_dirs, opt.work_dir_selection);
 
     let concurrent_tasks = if opt.concurrent_tasks == 0 {
         // use all available cores if no concurrency level is specified
@@ -152,12 +320,72 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
         opt.concurrent_tasks
     };
 
+    if opt.task_slot_oversubscription < 1.0 {
+        return Err(BallistaError::General(format!(
+            "Task slot oversubscription must be at least 1, got {}",
+            opt.task_slot_oversubscription
+        ))
+        .into());
+    }
+    let task_slot_oversubscription_percent =
+        (opt.task_slot_oversubscription * 100.0).round() as u32;
+
+    let spill_dirs = match &opt.spill_dir {
+        Some(spill_dir) => vec![spill_dir.clone()],
+        None => work_dirs.spill_dirs(),
+    };
+    for dir in work_dirs.dirs().iter().chain(&spill_dirs) {
+        create_private_dir_all(dir)?;
+    }
+
     info!("Running with config:");
-    info!("work_dir: {}", work_dir);
+    info!("work_dir: {}", work_dirs.dirs().join(","));
+    info!("work_dir_selection: {:?}", work_dirs.selection());
+    info!("spill_dir: {}", spill_dirs.join(","));
+    info!("min_free_disk_bytes: {}", opt.min_free_disk_bytes);
     info!("concurrent_tasks: {}", concurrent_tasks);
+    info!(
+        "task_slot_oversubscription: {}",
+        opt.task_slot_oversubscription
+    );
 
-    // assign this executor an unique ID
+    info!("memory_bytes: {}", opt.memory_bytes);
+    let task_memory_bytes = if opt.task_memory_bytes > 0 {
+        opt.task_memory_bytes
+    } else {
+        opt.memory_bytes / concurrent_tasks as u64
+    };
+    info!("task_memory_bytes: {}", task_memory_bytes);
+    info!("vcores: {}", opt.vcores);
+    info!("gpus: {}", opt.gpus);
+
+    // The whole process moves into the cgroup, with the threads of its runtime
+    let cgroup = match &opt.cgroup {
+        Some(config) => Some(Arc::new(Cgroup::create(config)?)),
+        None => None,
+    };
+
+    // memory and vcores of 0 are reported as is and treated as unlimited by the scheduler
+    let executor_specification = ExecutorSpecification {
+        resources: vec![
+            Resource::TaskSlots(concurrent_tasks as u32),
+            Resource::MemoryBytes(opt.memory_bytes),
+            Resource::Vcores(opt.vcores),
+            Resource::TaskSlotOversubscriptionPercent(task_slot_oversubscription_percent),
+            Resource::Gpus(opt.gpus),
+        ]
+        .into_iter()
+        .map(|resource| ExecutorResource {
+            resource: Some(resource),
+        })
+        .collect(),
+    };
+
+    // assign this executor an unique ID, remembering the one it had before a restart to
+    // recover the shuffle data left in its work dirs
     let executor_id = Uuid::new_v4().to_string();
+    let previous_executor_id = previous_executor_id(&work_dirs.dirs()[0]);
+    save_executor_id(&work_dirs.dirs()[0], &executor_id)?;
     let executor_meta = ExecutorRegistration {
         id: executor_id.clone(),
         optional_host: opt
@@ -166,75 +394,136 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
             .map(executor_registration::OptionalHost::Host),
         port: opt.port as u32,
         grpc_port: opt.grpc_port as u32,
-        specification: Some(ExecutorSpecification {
-            resources: vec![ExecutorResource {
-                resource: Some(Resource::TaskSlots(concurrent_tasks as u32)),
-            }],
-        }),
+        specification: Some(executor_specification.clone()),
+        pool: opt.pool.clone(),
     };
 
-    let config = with_object_store_provider(
-        RuntimeConfig::new().with_temp_file_path(work_dir.clone()),
+    // Tasks spill to the spill dirs once they exceed their share of the memory pool
+    let config = RuntimeConfig::new().with_disk_manager(DiskManagerConfig::NewSpecified(
+        spill_dirs.iter().map(PathBuf::from).collect(),
+    ));
+    let config = if opt.memory_bytes > 0 {
+        config.with_memory_pool(Arc::new(FairSpillPool::new(opt.memory_bytes as usize)))
+    } else {
+        config
+    };
+    let object_store_registry = Arc::new(
+        BallistaObjectStoreRegistry::new().with_retry(opt.object_store_retry.clone()),
     );
+    let config = config.with_object_store_registry(object_store_registry.clone());
     let runtime = Arc::new(RuntimeEnv::new(config).map_err(|_| {
         BallistaError::Internal("Failed to init Executor RuntimeEnv".to_owned())
     })?);
+    opt.object_stores.register(&runtime);
+
+    let mut plugins = opt.plugins.clone();
+    if !opt.plugin_dir.is_empty() {
+        plugins.extend(load_plugins(&opt.plugin_dir)?);
+    }
+    for plugin in &plugins {
+        info!("Registering executor plugin {}", plugin.name());
+        plugin.register_object_stores(&runtime)?;
+    }
 
     let metrics_collector = Arc::new(LoggingMetricsCollector::default());
 
-    let executor = Arc::new(Executor::new(
+    let work_stealing = opt
+        .work_stealing
+        .then(|| Arc::new(WorkStealingPool::new(concurrent_tasks)));
+    let execution_engine = opt
+        .execution_engine
+        .clone()
+        .or_else(|| plugins.iter().find_map(|plugin| plugin.execution_engine()))
+        .unwrap_or_else(|| {
+            let engine = DefaultExecutionEngine::default()
+                .with_scan_prefetch_batches(opt.scan_prefetch_batches);
+            let engine = match &work_stealing {
+                Some(pool) => engine.with_work_stealing(pool.clone()),
+                None => engine,
+            };
+            Arc::new(engine)
+        });
+    let work_dir = work_dirs.dirs()[0].clone();
+    let executor = Executor::new(
         executor_meta,
         &work_dir,
         runtime,
         metrics_collector,
         concurrent_tasks,
-        opt.execution_engine.clone(),
-    ));
-
-    let connect_timeout = opt.scheduler_connect_timeout_seconds as u64;
-    let connection = if connect_timeout == 0 {
-        create_grpc_client_connection(scheduler_url)
-            .await
-            .context("Could not connect to scheduler")
+        Some(execution_engine),
+    )
+    .with_task_memory_limit(task_memory_bytes as usize)
+    .with_object_store_registry(object_store_registry)
+    .with_work_dirs(work_dirs)
+    .with_min_free_disk_bytes(opt.min_free_disk_bytes);
+    let executor = match &opt.spill_dir {
+        Some(spill_dir) => executor.with_spill_dir(spill_dir),
+        None => executor,
+    };
+    let export_config = match &opt.export_profiles_file {
+        Some(path) => ExportConfig::default().with_profiles_file(path)?,
+        None => ExportConfig::default(),
+    };
+    let export_config = match &opt.export_local_root {
+        Some(local_root) => export_config.with_local_root(local_root),
+        None => export_config,
+    };
+    let executor = executor.with_export_config(export_config);
+    let executor = match work_stealing {
+        Some(pool) => executor.with_work_stealing(pool),
+        None => executor,
+    };
+    let executor = match cgroup {
+        Some(cgroup) => executor.with_cgroup(cgroup),
+        None => executor,
+    };
+    let executor = if opt.stage_result_cache_entries > 0 {
+        executor.with_stage_result_cache(Arc::new(StageResultCache::new(
+            opt.stage_result_cache_entries,
+        )))
     } else {
-        // this feature was added to support docker-compose so that we can have the executor
-        // wait for the scheduler to start, or at least run for 10 seconds before failing so
-        // that docker-compose's restart policy will restart the container.
-        let start_time = Instant::now().elapsed().as_secs();
-        let mut x = None;
-        while x.is_none()
-            && Instant::now().elapsed().as_secs() - start_time < connect_timeout
-        {
-            match create_grpc_client_connection(scheduler_url.clone())
-                .await
-                .context("Could not connect to scheduler")
-            {
-                Ok(conn) => {
-                    info!("Connected to scheduler at {}", scheduler_url);
-                    x = Some(conn);
-                }
-                Err(e) => {
-                    warn!(
-                        "Failed to connect to scheduler at {} ({}); retrying ...",
-                        scheduler_url, e
-                    );
-                    std::thread::sleep(time::Duration::from_millis(500));
-                }
-            }
-        }
-        match x {
-            Some(conn) => Ok(conn),
-            _ => Err(BallistaError::General(format!(
-                "Timed out attempting to connect to scheduler at {scheduler_url}"
-            ))
-            .into()),
-        }
-    }?;
-
-    let mut scheduler = SchedulerGrpcClient::new(connection);
+        executor
+    };
+    let executor = match &opt.adaptive_batch_size {
+        Some(config) => executor.with_adaptive_batch_size(Arc::new(
+            AdaptiveBatchSize::new(config.clone(), opt.memory_bytes),
+        )),
+        None => executor,
+    };
+    let executor = opt
+        .task_context_extensions
+        .iter()
+        .cloned()
+        .fold(executor, Executor::with_task_context_extension);
+    let executor = Arc::new(plugins.iter().fold(executor, |executor, plugin| {
+        executor.with_plugin(plugin.as_ref())
+    }));
+
+    // this feature was added to support docker-compose so that we can have the executor
+    // wait for the scheduler to start, or at least run for 10 seconds before failing so
+    // that docker-compose's restart policy will restart the container.
+    let connect_timeout =
+        Duration::from_secs(opt.scheduler_connect_timeout_seconds as u64);
+    let mut scheduler = scheduler_endpoints
+        .connect(connect_timeout)
+        .await
+        .context("Could not connect to scheduler")?;
+    let connected_url = scheduler_endpoints.current_url().to_owned();
+
+    // The shuffle files of active jobs left in the work dirs are served again instead of
+    // being recomputed, the data of the other jobs is removed
+    if let Err(e) = recover_work_dirs(
+        &mut scheduler,
+        executor.metadata.clone(),
+        previous_executor_id,
+        executor.work_dirs.dirs().to_vec(),
+    )
+    .await
+    {
+        warn!("Failed to recover the shuffle data of the work dirs: {e}");
+    }
 
-    let default_codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
-        BallistaCodec::default();
+    let codec = opt.codec.clone();
 
     let scheduler_policy = opt.task_scheduling_policy;
     let job_data_ttl_seconds = opt.job_data_ttl_seconds;
@@ -247,22 +536,27 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
             time::interval(Duration::from_secs(opt.job_data_clean_up_interval_seconds));
         let mut shuffle_cleaner_shutdown = shutdown_noti.subscribe_for_shutdown();
         let shuffle_cleaner_complete = shutdown_noti.shutdown_complete_tx.clone();
+        let work_dirs = executor.work_dirs.clone();
         tokio::spawn(async move {
             // As long as the shutdown notification has not been received
             while !shuffle_cleaner_shutdown.is_shutdown() {
                 tokio::select! {
                     _ = interval_time.tick() => {
-                            if let Err(e) = clean_shuffle_data_loop(&work_dir, job_data_ttl_seconds).await
-                        {
-                            error!("Ballista executor fail to clean_shuffle_data {:?}", e)
+                        for work_dir in work_dirs.dirs() {
+                            if let Err(e) = clean_shuffle_data_loop(work_dir, job_data_ttl_seconds).await
+                            {
+                                error!("Ballista executor fail to clean_shuffle_data {:?}", e)
+                            }
                         }
                         },
                     _ = shuffle_cleaner_shutdown.recv() => {
-                        if let Err(e) = clean_all_shuffle_data(&work_dir).await
-                        {
-                            error!("Ballista executor fail to clean_shuffle_data {:?}", e)
-                        } else {
-                            info!("Shuffle data cleaned.");
+                        for work_dir in work_dirs.dirs() {
+                            if let Err(e) = clean_all_shuffle_data(work_dir).await
+                            {
+                                error!("Ballista executor fail to clean_shuffle_data {:?}", e)
+                            } else {
+                                info!("Shuffle data of {work_dir} cleaned.");
+                            }
                         }
                         drop(shuffle_cleaner_complete);
                         return;
@@ -284,9 +578,10 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
                 //If there is executor registration error during startup, return the error and stop early.
                 executor_server::startup(
                     scheduler.clone(),
+                    scheduler_endpoints.clone(),
                     opt.clone(),
                     executor.clone(),
-                    default_codec,
+                    codec,
                     stop_send,
                     &shutdown_noti,
                 )
@@ -296,39 +591,43 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
         _ => {
             service_handlers.push(tokio::spawn(execution_loop::poll_loop(
                 scheduler.clone(),
+                Some(scheduler_endpoints.clone()),
                 executor.clone(),
-                default_codec,
+                codec,
+                opt.task_status_batch_size,
+                Duration::from_millis(opt.task_status_flush_interval_ms),
             )));
         }
     };
     service_handlers.push(tokio::spawn(flight_server_run(
         addr,
+        task_logs,
+        plugins,
         shutdown_noti.subscribe_for_shutdown(),
     )));
 
-    let tasks_drained = TasksDrainedFuture(executor);
-
     // Concurrently run the service checking and listen for the `shutdown` signal and wait for the stop request coming.
     // The check_services runs until an error is encountered, so under normal circumstances, this `select!` statement runs
     // until the `shutdown` signal is received or a stop request is coming.
-    let (notify_scheduler, stop_reason) = tokio::select! {
+    // The running tasks are drained unless the services failed or a forced stop is requested.
+    let (notify_scheduler, drain_tasks, stop_reason) = tokio::select! {
         service_val = check_services(&mut service_handlers) => {
             let msg = format!("executor services stopped with reason {service_val:?}");
             info!("{:?}", msg);
-            (true, msg)
+            (true, false, msg)
         },
         _ = signal::ctrl_c() => {
             let msg = "executor received ctrl-c event.".to_string();
              info!("{:?}", msg);
-            (true, msg)
+            (true, true, msg)
         },
         _ = terminate::sig_term() => {
             let msg = "executor received terminate signal.".to_string();
              info!("{:?}", msg);
-            (true, msg)
+            (true, true, msg)
         },
-        _ = stop_recv.recv() => {
-            (false, "".to_string())
+        force = stop_recv.recv() => {
+            (false, !force.unwrap_or(true), "".to_string())
         },
     };
 
@@ -336,35 +635,75 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
     info!("setting executor to TERMINATING status");
     TERMINATING.store(true, Ordering::Release);
 
+    let terminating_heartbeat = HeartBeatParams {
+        executor_id: executor_id.clone(),
+        metrics: vec![],
+        status: Some(ExecutorStatus {
+            status: Some(Status::Terminating(String::default())),
+        }),
+        metadata: Some(ExecutorRegistration {
+            id: executor_id.clone(),
+            optional_host: opt
+                .external_host
+                .clone()
+                .map(executor_registration::OptionalHost::Host),
+            port: opt.port as u32,
+            grpc_port: opt.grpc_port as u32,
+            specification: Some(executor_specification),
+            pool: opt.pool.clone(),
+        }),
+        task_status: vec![],
+        task_progress: vec![],
+        task_slots: 0,
+    };
+
+    // Notify the standby scheduler the executor failed over to, if any
+    if notify_scheduler && scheduler_endpoints.current_url() != connected_url {
+        match scheduler_endpoints.connect_any().await {
+            Ok(current) => scheduler = current,
+            Err(error) => {
+                error!("error connecting to the current scheduler: {:?}", error)
+            }
+        }
+    }
+
     if notify_scheduler {
         // Send a heartbeat to update status of executor to `Fenced`. This should signal to the
         // scheduler to no longer schedule tasks on this executor
         if let Err(error) = scheduler
-            .heart_beat_from_executor(HeartBeatParams {
-                executor_id: executor_id.clone(),
-                metrics: vec![],
-                status: Some(ExecutorStatus {
-                    status: Some(Status::Terminating(String::default())),
-                }),
-                metadata: Some(ExecutorRegistration {
-                    id: executor_id.clone(),
-                    optional_host: opt
-                        .external_host
-                        .clone()
-                        .map(executor_registration::OptionalHost::Host),
-                    port: opt.port as u32,
-                    grpc_port: opt.grpc_port as u32,
-                    specification: Some(ExecutorSpecification {
-                        resources: vec![ExecutorResource {
-                            resource: Some(Resource::TaskSlots(concurrent_tasks as u32)),
-                        }],
-                    }),
-                }),
-            })
+            .heart_beat_from_executor(terminating_heartbeat.clone())
             .await
         {
             error!("error sending heartbeat with fenced status: {:?}", error);
         }
+    }
+
+    if drain_tasks {
+        // New tasks are rejected while the running ones complete
+        let grace_period = Duration::from_secs(opt.shutdown_grace_period_seconds);
+        info!(
+            "waiting up to {:?} for {} running tasks to complete",
+            grace_period,
+            executor.active_task_count()
+        );
+        let aborted = executor.drain_tasks(grace_period).await;
+        if aborted > 0 {
+            warn!(
+                "aborted {} tasks still running after the shutdown grace period",
+                aborted
+            );
+        }
+    }
+
+    if notify_scheduler {
+        // A final heartbeat once the tasks are drained, so that the scheduler does not wait
+        // for the executor any longer
+        if let Err(error) = scheduler
+            .heart_beat_from_executor(terminating_heartbeat)
+            .await
+        {
+            error!("error sending final heartbeat: {:?}", error);
+        }
 
         // TODO we probably don't need a separate rpc call for this....
         if let Err(error) = scheduler
@@ -376,9 +715,6 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
         {
             error!("ExecutorStopped grpc failed: {:?}", error);
         }
-
-        // Wait for tasks to drain
-        tasks_drained.await;
     }
 
     // Extract the `shutdown_complete` receiver and transmitter
@@ -406,10 +742,19 @@ pub async fn start_executor_process(opt: Arc<ExecutorProcessConfig>) -> Result<(
 // Arrow flight service
 async fn flight_server_run(
     addr: SocketAddr,
+    task_logs: Option<Arc<TaskLogs>>,
+    plugins: Vec<Arc<dyn ExecutorPlugin>>,
     mut grpc_shutdown: Shutdown,
 ) -> Result<(), BallistaError> {
-    let service = BallistaFlightService::new();
+    let service = BallistaFlightService::new()
+        .with_task_logs(task_logs)
+        .with_plugins(plugins);
     let server = FlightServiceServer::new(service);
+    // Standard health checks, e.g. by Kubernetes gRPC probes
+    let (mut health_reporter, health_server) = tonic_health::server::health_reporter();
+    health_reporter
+        .set_serving::<FlightServiceServer<BallistaFlightService>>()
+        .await;
     info!(
         "Ballista v{} Rust Executor Flight Server listening on {:?}",
         BALLISTA_VERSION, addr
@@ -418,6 +763,7 @@ async fn flight_server_run(
     let shutdown_signal = grpc_shutdown.recv();
     let server_future = create_grpc_server()
         .add_service(server)
+        .add_service(health_server)
         .serve_with_shutdown(addr, shutdown_signal);
 
     server_future.await.map_err(|e| {
@@ -469,7 +815,7 @@ async fn clean_shuffle_data_loop(work_dir: &str, seconds: u64) -> Result<()> {
                     Ok(false) => to_deleted.push(child_path),
                     Ok(_) => {}
                 }
-            } else {
+            } else if child.file_name() != EXECUTOR_ID_FILE {
                 warn!("{:?} under the working directory is a not a directory and will be ignored when doing cleanup", child_path)
             }
         } else {
@@ -16,49 +16,65 @@
 // under the License.
 
 use ballista_core::BALLISTA_VERSION;
-use datafusion::config::ConfigOptions;
-use datafusion::prelude::SessionConfig;
+use futures::FutureExt;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::ops::Deref;
+use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 
 use log::{debug, error, info, warn};
+use tonic::codec::CompressionEncoding;
+use tonic::server::NamedService;
 use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
+use tracing::Instrument;
 
+use ballista_core::config::GrpcCompression;
 use ballista_core::error::BallistaError;
 use ballista_core::serde::protobuf::{
     executor_grpc_server::{ExecutorGrpc, ExecutorGrpcServer},
     executor_metric, executor_status,
     scheduler_grpc_client::SchedulerGrpcClient,
     CancelTasksParams, CancelTasksResult, ExecutorMetric, ExecutorStatus,
-    HeartBeatParams, LaunchMultiTaskParams, LaunchMultiTaskResult, LaunchTaskParams,
-    LaunchTaskResult, RegisterExecutorParams, RemoveJobDataParams, RemoveJobDataResult,
-    StopExecutorParams, StopExecutorResult, TaskStatus, UpdateTaskStatusParams,
+    GetRunningTasksParams, GetRunningTasksResult, HeartBeatParams, LaunchMultiTaskParams,
+    LaunchMultiTaskResult, LaunchTaskParams, LaunchTaskResult, RegisterExecutorParams,
+    RejectedTask, RemoveJobDataParams, RemoveJobDataResult, SetTaskSlotsParams,
+    SetTaskSlotsResult, StopExecutorParams, StopExecutorResult, TaskStatus,
 };
 use ballista_core::serde::scheduler::PartitionId;
 use ballista_core::serde::scheduler::TaskDefinition;
 use ballista_core::serde::BallistaCodec;
-use ballista_core::utils::{create_grpc_client_connection, create_grpc_server};
+use ballista_core::utils::{
+    create_grpc_client_connection, create_grpc_reflection_server, create_grpc_server,
+};
 use dashmap::DashMap;
 use datafusion::execution::context::TaskContext;
 use datafusion_proto::{logical_plan::AsLogicalPlan, physical_plan::AsExecutionPlan};
+use parking_lot::RwLock;
 use tokio::sync::mpsc::error::TryRecvError;
 use tokio::task::JoinHandle;
 
 use crate::cpu_bound_executor::DedicatedExecutor;
 use crate::execution_engine::QueryStageExecutor;
-use crate::executor::Executor;
+use crate::execution_loop::panic_error;
+use crate::executor::{task_props_config, Executor};
 use crate::executor_process::ExecutorProcessConfig;
+use crate::scheduler_endpoints::{Backoff, SchedulerEndpoints};
 use crate::shutdown::ShutdownNotifier;
+use crate::stage_result_cache::plan_fingerprint;
+use crate::task_logs::task_span;
 use crate::{as_task_status, TaskExecutionTimes};
 
 type ServerHandle = JoinHandle<Result<(), BallistaError>>;
+/// Interval at which heartbeats are sent to the scheduler. Heartbeats carrying task statuses
+/// postpone the next one.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(60000);
+
 type SchedulerClients = Arc<DashMap<String, SchedulerGrpcClient<Channel>>>;
 
 /// Wrap TaskDefinition with its curator scheduler id for task update to its specific curator scheduler later
@@ -78,6 +94,7 @@ struct CuratorTaskStatus {
 
 pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
     mut scheduler: SchedulerGrpcClient<Channel>,
+    scheduler_endpoints: Arc<SchedulerEndpoints>,
     config: Arc<ExecutorProcessConfig>,
     executor: Arc<Executor>,
     codec: BallistaCodec<T, U>,
@@ -91,6 +108,7 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
 
     let executor_server = ExecutorServer::new(
         scheduler.clone(),
+        scheduler_endpoints,
         executor.clone(),
         ExecutorEnv {
             tx_task,
@@ -98,6 +116,7 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
             tx_stop: stop_send,
         },
         codec,
+        config.grpc_compression,
     );
 
     // 1. Start executor grpc service
@@ -113,12 +132,24 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
         let server = ExecutorGrpcServer::new(executor_server.clone())
             .max_decoding_message_size(
                 config.grpc_server_max_decoding_message_size as usize,
-            );
+            )
+            .accept_compressed(CompressionEncoding::Gzip)
+            .send_compressed(CompressionEncoding::Gzip);
+        // Standard services for health checks and for tools like grpcurl
+        let service_name = ExecutorGrpcServer::<ExecutorServer<T, U>>::NAME;
+        let (mut health_reporter, health_server) =
+            tonic_health::server::health_reporter();
+        health_reporter
+            .set_service_status(service_name, tonic_health::ServingStatus::Serving)
+            .await;
+        let reflection_server = create_grpc_reflection_server(&[service_name])?;
         let mut grpc_shutdown = shutdown_noti.subscribe_for_shutdown();
         tokio::spawn(async move {
             let shutdown_signal = grpc_shutdown.recv();
             let grpc_server_future = create_grpc_server()
                 .add_service(server)
+                .add_service(health_server)
+                .add_service(reflection_server)
                 .serve_with_shutdown(addr, shutdown_signal);
             grpc_server_future.await.map_err(|e| {
                 error!("Tonic error, Could not start Executor Grpc Server.");
@@ -150,7 +181,11 @@ pub async fn startup<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
 
     // 4. Start TaskRunnerPool loop
     {
-        let task_runner_pool = TaskRunnerPool::new(executor_server.clone());
+        let task_runner_pool = TaskRunnerPool::new(
+            executor_server.clone(),
+            config.task_status_batch_size,
+            Duration::from_millis(config.task_status_flush_interval_ms),
+        );
         task_runner_pool.start(rx_task, rx_task_status, shutdown_noti);
     }
 
@@ -182,8 +217,14 @@ pub struct ExecutorServer<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPl
     executor: Arc<Executor>,
     executor_env: ExecutorEnv,
     codec: BallistaCodec<T, U>,
-    scheduler_to_register: SchedulerGrpcClient<Channel>,
+    /// Scheduler the executor registered with, which it sends its heartbeats to
+    scheduler_to_register: Arc<RwLock<SchedulerGrpcClient<Channel>>>,
+    /// Schedulers the executor registers with again when its scheduler is unreachable
+    scheduler_endpoints: Arc<SchedulerEndpoints>,
     schedulers: SchedulerClients,
+    grpc_compression: GrpcCompression,
+    /// Time of the last heartbeat in milliseconds since the epoch
+    last_heartbeat: Arc<AtomicU64>,
 }
 
 #[derive(Clone)]
@@ -205,9 +246,11 @@ pub static TERMINATING: AtomicBool = AtomicBool::new(false);
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T, U> {
     fn new(
         scheduler_to_register: SchedulerGrpcClient<Channel>,
+        scheduler_endpoints: Arc<SchedulerEndpoints>,
         executor: Arc<Executor>,
         executor_env: ExecutorEnv,
         codec: BallistaCodec<T, U>,
+        grpc_compression: GrpcCompression,
     ) -> Self {
         Self {
             _start_time: SystemTime::now()
@@ -217,8 +260,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             executor,
             executor_env,
             codec,
-            scheduler_to_register,
+            scheduler_to_register: Arc::new(RwLock::new(scheduler_to_register)),
+            scheduler_endpoints,
             schedulers: Default::default(),
+            grpc_compression,
+            last_heartbeat: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -233,7 +279,12 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         } else {
             let scheduler_url = format!("http://{scheduler_id}");
             let connection = create_grpc_client_connection(scheduler_url).await?;
-            let scheduler = SchedulerGrpcClient::new(connection);
+            let mut scheduler = SchedulerGrpcClient::new(connection);
+            if let Some(encoding) = self.grpc_compression.encoding() {
+                scheduler = scheduler
+                    .send_compressed(encoding)
+                    .accept_compressed(encoding);
+            }
 
             {
                 self.schedulers
@@ -244,30 +295,72 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         }
     }
 
-    /// 1. First Heartbeat to its registration scheduler, if successful then return; else go next.
-    /// 2. Heartbeat to schedulers which has launching tasks to this executor until one succeeds
-    async fn heartbeat(&self) {
+    fn heartbeat_params(&self, task_status: Vec<TaskStatus>) -> HeartBeatParams {
         let status = if TERMINATING.load(Ordering::Acquire) {
             executor_status::Status::Terminating(String::default())
         } else {
             executor_status::Status::Active(String::default())
         };
 
-        let heartbeat_params = HeartBeatParams {
+        HeartBeatParams {
             executor_id: self.executor.metadata.id.clone(),
             metrics: self.get_executor_metrics(),
             status: Some(ExecutorStatus {
                 status: Some(status),
             }),
             metadata: Some(self.executor.metadata.clone()),
-        };
-        let mut scheduler = self.scheduler_to_register.clone();
+            task_status,
+            task_progress: self.executor.task_progress(),
+            task_slots: self.executor.task_slots(),
+        }
+    }
+
+    fn record_heartbeat(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        self.last_heartbeat.store(now, Ordering::Release);
+    }
+
+    /// Time until the next heartbeat is due
+    fn heartbeat_due_in(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let elapsed = now.saturating_sub(self.last_heartbeat.load(Ordering::Acquire));
+        HEARTBEAT_INTERVAL.saturating_sub(Duration::from_millis(elapsed))
+    }
+
+    /// Register the executor with the first reachable scheduler, starting from the one it
+    /// registered with last, which it sends its heartbeats to from then on
+    async fn register(&self) -> Result<(), BallistaError> {
+        let mut scheduler = self.scheduler_endpoints.connect_any().await?;
+        register_executor(&mut scheduler, self.executor.clone()).await?;
+        *self.scheduler_to_register.write() = scheduler;
+        Ok(())
+    }
+
+    /// 1. First Heartbeat to its registration scheduler, if successful then return; else go next.
+    /// 2. Heartbeat to schedulers which has launching tasks to this executor until one succeeds
+    ///
+    /// Returns whether the registration scheduler accepted the heartbeat, the executor
+    /// registers again otherwise.
+    async fn heartbeat(&self) -> bool {
+        self.record_heartbeat();
+        let heartbeat_params = self.heartbeat_params(vec![]);
+        let mut scheduler = self.scheduler_to_register.read().clone();
         match scheduler
             .heart_beat_from_executor(heartbeat_params.clone())
             .await
         {
-            Ok(_) => {
-                return;
+            Ok(result) => {
+                if !result.into_inner().reregister {
+                    return true;
+                }
+                warn!("The registration scheduler asked the executor to register again");
+                return false;
             }
             Err(e) => {
                 warn!(
@@ -296,6 +389,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
                 }
             }
         }
+        false
     }
 
     async fn decode_task(
@@ -305,12 +399,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
     ) -> Result<Arc<dyn QueryStageExecutor>, BallistaError> {
         let task = curator_task;
         let task_identity = task_identity(&task);
-        let task_props = task.props;
-        let mut config = ConfigOptions::new();
-        for (k, v) in task_props {
-            config.set(&k, &v)?;
-        }
-        let session_config = SessionConfig::from(config);
+        let task_metadata = task.metadata();
+        let config = task_props_config(task.props)?;
+        let session_config = self.executor.task_session_config(task_metadata, config);
 
         let mut task_scalar_functions = HashMap::new();
         let mut task_aggregate_functions = HashMap::new();
@@ -342,7 +433,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             task.job_id,
             task.stage_id,
             plan,
-            &self.executor.work_dir,
+            self.executor.work_dirs.select(),
         )?)
     }
 
@@ -352,6 +443,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         scheduler_id: String,
         curator_task: TaskDefinition,
         query_stage_exec: Arc<dyn QueryStageExecutor>,
+        plan_fingerprint: u64,
     ) -> Result<(), BallistaError> {
         let start_exec_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -359,12 +451,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             .as_millis() as u64;
         info!("Start to run task {}", task_identity);
         let task = curator_task;
-        let task_props = task.props;
-        let mut config = ConfigOptions::new();
-        for (k, v) in task_props {
-            config.set(&k, &v)?;
-        }
-        let session_config = SessionConfig::from(config);
+        let task_metadata = task.metadata();
+        let config = task_props_config(task.props)?;
+        let session_config = self.executor.task_session_config(task_metadata, config);
 
         let mut task_scalar_functions = HashMap::new();
         let mut task_aggregate_functions = HashMap::new();
@@ -377,14 +466,18 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         }
 
         let session_id = task.session_id;
-        let runtime = self.executor.runtime.clone();
+        let resource_meter = self.executor.task_resource_meter(
+            &task.job_id,
+            task.task_id,
+            &session_config,
+        )?;
         let task_context = Arc::new(TaskContext::new(
             Some(task_identity.to_string()),
             session_id,
-            session_config,
+            session_config.with_extension(Arc::new(resource_meter.cpu_timer())),
             task_scalar_functions,
             task_aggregate_functions,
-            runtime.clone(),
+            resource_meter.runtime(),
         ));
 
         let task_id = task.task_id;
@@ -401,19 +494,20 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
 
         info!("Start to execute shuffle write for task {}", task_identity);
 
-        let execution_result = self
-            .executor
-            .execute_query_stage(
+        let execution_result = resource_meter
+            .measure(self.executor.execute_query_stage_with_cache(
                 task_id,
                 part.clone(),
                 query_stage_exec.clone(),
                 task_context,
-            )
+                plan_fingerprint,
+            ))
             .await;
         info!("Done with task {}", task_identity);
         debug!("Statistics: {:?}", execution_result);
 
         let plan_metrics = query_stage_exec.collect_plan_metrics();
+        let resource_usage = resource_meter.usage(&plan_metrics);
         let operator_metrics = plan_metrics
             .into_iter()
             .map(|m| m.try_into())
@@ -438,6 +532,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
             part,
             Some(operator_metrics),
             task_execution_times,
+            Some(resource_usage),
+            query_stage_exec.written_schema(),
         );
 
         let task_status_sender = self.executor_env.tx_task_status.clone();
@@ -451,12 +547,70 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorServer<T,
         Ok(())
     }
 
+    /// Report the failure of a task which did not run to completion, so that the scheduler
+    /// does not wait for its status
+    async fn report_task_failure(
+        &self,
+        scheduler_id: String,
+        task: &TaskDefinition,
+        error: BallistaError,
+    ) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let task_status = as_task_status(
+            Err(error),
+            self.executor.metadata.id.clone(),
+            task.task_id,
+            task.stage_attempt_num,
+            PartitionId {
+                job_id: task.job_id.clone(),
+                stage_id: task.stage_id,
+                partition_id: task.partition_id,
+            },
+            None,
+            TaskExecutionTimes {
+                launch_time: task.launch_time,
+                start_exec_time: now,
+                end_exec_time: now,
+            },
+            None,
+            None,
+        );
+        if let Err(e) = self
+            .executor_env
+            .tx_task_status
+            .send(CuratorTaskStatus {
+                scheduler_id,
+                task_status,
+            })
+            .await
+        {
+            error!(
+                "Failed to report the failure of task {}: {e:?}",
+                task.task_id
+            );
+        }
+    }
+
     // TODO populate with real metrics
     fn get_executor_metrics(&self) -> Vec<ExecutorMetric> {
         let available_memory = ExecutorMetric {
             metric: Some(executor_metric::Metric::AvailableMemory(u64::MAX)),
         };
-        let executor_metrics = vec![available_memory];
+        let mut executor_metrics = vec![available_memory];
+        // The scheduler stops offering tasks to an executor whose disk is short of space
+        if let Some(disk_usage) = self.executor.disk_usage() {
+            executor_metrics.push(ExecutorMetric {
+                metric: Some(executor_metric::Metric::DiskUsage(disk_usage)),
+            });
+        }
+        if let Some(cgroup_stats) = self.executor.cgroup_stats() {
+            executor_metrics.push(ExecutorMetric {
+                metric: Some(executor_metric::Metric::CgroupStats(cgroup_stats)),
+            });
+        }
         executor_metrics
     }
 }
@@ -477,11 +631,45 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> Heartbeater<T, U>
         let heartbeat_complete = shutdown_noti.shutdown_complete_tx.clone();
         tokio::spawn(async move {
             info!("Starting heartbeater to send heartbeat the scheduler periodically");
+            let mut backoff =
+                Backoff::new(executor_server.scheduler_endpoints.reconnect_config());
+            let mut last_accepted = true;
             // As long as the shutdown notification has not been received
             while !heartbeat_shutdown.is_shutdown() {
-                executor_server.heartbeat().await;
+                let due_in = executor_server.heartbeat_due_in();
+                let wait = if last_accepted && !due_in.is_zero() {
+                    due_in
+                } else {
+                    let mut accepted = executor_server.heartbeat().await;
+                    // The scheduler is still unreachable or lost the executor since the last
+                    // heartbeat, register again with the first reachable scheduler, which
+                    // is a standby scheduler if the scheduler stays unreachable
+                    if !accepted && !last_accepted && !TERMINATING.load(Ordering::Acquire)
+                    {
+                        accepted = match executor_server.register().await {
+                            Ok(()) => {
+                                info!(
+                                    "Executor registered again with scheduler at {}",
+                                    executor_server.scheduler_endpoints.current_url()
+                                );
+                                executor_server.heartbeat().await
+                            }
+                            Err(e) => {
+                                warn!("Executor registration failed due to: {}", e);
+                                false
+                            }
+                        };
+                    }
+                    last_accepted = accepted;
+                    if last_accepted {
+                        backoff.reset();
+                        HEARTBEAT_INTERVAL
+                    } else {
+                        backoff.next_backoff()
+                    }
+                };
                 tokio::select! {
-                    _ = tokio::time::sleep(Duration::from_millis(60000)) => {},
+                    _ = tokio::time::sleep(wait) => {},
                     _ = heartbeat_shutdown.recv() => {
                         info!("Stop heartbeater");
                         drop(heartbeat_complete);
@@ -499,6 +687,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> Heartbeater<T, U>
 /// The two loops will run forever until a shutdown notification received.
 struct TaskRunnerPool<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
     executor_server: Arc<ExecutorServer<T, U>>,
+    /// Maximum number of task statuses reported in one update, 0 for no limit
+    status_batch_size: usize,
+    /// Time to wait for more finished tasks before reporting the statuses collected
+    flush_interval: Duration,
 }
 
 fn task_identity(task: &TaskDefinition) -> String {
@@ -514,8 +706,16 @@ fn task_identity(task: &TaskDefinition) -> String {
 }
 
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T, U> {
-    fn new(executor_server: Arc<ExecutorServer<T, U>>) -> Self {
-        Self { executor_server }
+    fn new(
+        executor_server: Arc<ExecutorServer<T, U>>,
+        status_batch_size: usize,
+        flush_interval: Duration,
+    ) -> Self {
+        Self {
+            executor_server,
+            status_batch_size,
+            flush_interval,
+        }
     }
 
     fn start(
@@ -526,14 +726,14 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
     ) {
         //1. loop for task status reporting
         let executor_server = self.executor_server.clone();
+        let status_batch_size = self.status_batch_size;
+        let flush_interval = self.flush_interval;
         let mut tasks_status_shutdown = shutdown_noti.subscribe_for_shutdown();
         let tasks_status_complete = shutdown_noti.shutdown_complete_tx.clone();
         tokio::spawn(async move {
             info!("Starting the task status reporter");
             // As long as the shutdown notification has not been received
             while !tasks_status_shutdown.is_shutdown() {
-                let mut curator_task_status_map: HashMap<String, Vec<TaskStatus>> =
-                    HashMap::new();
                 // First try to fetch task status from the channel in *blocking* mode
                 let maybe_task_status: Option<CuratorTaskStatus> = tokio::select! {
                      task_status = rx_task_status.recv() => task_status,
@@ -544,33 +744,37 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                     }
                 };
 
-                let mut fetched_task_num = 0usize;
+                let mut fetched_tasks_status = vec![];
                 if let Some(task_status) = maybe_task_status {
-                    let task_status_vec = curator_task_status_map
-                        .entry(task_status.scheduler_id)
-                        .or_insert_with(Vec::new);
-                    task_status_vec.push(task_status.task_status);
-                    fetched_task_num += 1;
+                    fetched_tasks_status.push(task_status);
                 } else {
                     info!("Channel is closed and will exit the task status report loop.");
                     drop(tasks_status_complete);
                     return;
                 }
 
-                // Then try to fetch by non-blocking mode to fetch as much finished tasks as possible
-                loop {
+                // Then try to fetch by non-blocking mode to fetch as much finished tasks as possible,
+                // waiting for more of them up to the flush interval until the batch is full
+                let flush_deadline = tokio::time::Instant::now() + flush_interval;
+                while status_batch_size == 0
+                    || fetched_tasks_status.len() < status_batch_size
+                {
                     match rx_task_status.try_recv() {
-                        Ok(task_status) => {
-                            let task_status_vec = curator_task_status_map
-                                .entry(task_status.scheduler_id)
-                                .or_insert_with(Vec::new);
-                            task_status_vec.push(task_status.task_status);
-                            fetched_task_num += 1;
-                        }
-                        Err(TryRecvError::Empty) => {
-                            info!("Fetched {} tasks status to report", fetched_task_num);
-                            break;
+                        Ok(task_status) => fetched_tasks_status.push(task_status),
+                        Err(TryRecvError::Empty) if !flush_interval.is_zero() => {
+                            tokio::select! {
+                                task_status = rx_task_status.recv() => match task_status {
+                                    Some(task_status) => fetched_tasks_status.push(task_status),
+                                    None => {
+                                        info!("Channel is closed and will exit the task status report loop");
+                                        drop(tasks_status_complete);
+                                        return;
+                                    }
+                                },
+                                _ = tokio::time::sleep_until(flush_deadline) => break,
+                            }
                         }
+                        Err(TryRecvError::Empty) => break,
                         Err(TryRecvError::Disconnected) => {
                             info!("Channel is closed and will exit the task status report loop");
                             drop(tasks_status_complete);
@@ -578,25 +782,39 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                         }
                     }
                 }
+                info!(
+                    "Fetched {} tasks status to report",
+                    fetched_tasks_status.len()
+                );
+
+                // The statuses of each scheduler keep the order in which the tasks reported
+                // them, and batches are sent one after the other
+                let mut curator_task_status_map: HashMap<String, Vec<TaskStatus>> =
+                    HashMap::new();
+                for task_status in fetched_tasks_status {
+                    curator_task_status_map
+                        .entry(task_status.scheduler_id)
+                        .or_insert_with(Vec::new)
+                        .push(task_status.task_status);
+                }
 
+                // Finished tasks are reported right away along with a heartbeat, which
+                // postpones the next periodic one
                 for (scheduler_id, tasks_status) in curator_task_status_map.into_iter() {
                     match executor_server.get_scheduler_client(&scheduler_id).await {
                         Ok(mut scheduler) => {
-                            if let Err(e) = scheduler
-                                .update_task_status(UpdateTaskStatusParams {
-                                    executor_id: executor_server
-                                        .executor
-                                        .metadata
-                                        .id
-                                        .clone(),
-                                    task_status: tasks_status.clone(),
-                                })
+                            match scheduler
+                                .heart_beat_from_executor(
+                                    executor_server
+                                        .heartbeat_params(tasks_status.clone()),
+                                )
                                 .await
                             {
-                                error!(
+                                Ok(_) => executor_server.record_heartbeat(),
+                                Err(e) => error!(
                                     "Fail to update tasks {:?} due to {:?}",
                                     tasks_status, e
-                                );
+                                ),
                             }
                         }
                         Err(e) => {
@@ -619,7 +837,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
 
             // Use a dedicated executor for CPU bound tasks so that the main tokio
             // executor can still answer requests even when under load
-            let dedicated_executor = DedicatedExecutor::new(
+            let mut task_slots = executor_server.executor.task_slots();
+            let mut dedicated_executor = DedicatedExecutor::new(
                 "task_runner",
                 executor_server.executor.concurrent_tasks,
             );
@@ -635,34 +854,54 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                     }
                 };
                 if let Some(task) = maybe_task {
+                    // The tasks of the task slots resized with `SetTaskSlots` run on as many
+                    // threads. The previous threads stop once their tasks complete.
+                    if executor_server.executor.task_slots() != task_slots {
+                        task_slots = executor_server.executor.task_slots();
+                        info!("Running the tasks on {task_slots} threads");
+                        dedicated_executor =
+                            DedicatedExecutor::new("task_runner", task_slots as usize);
+                    }
                     let server = executor_server.clone();
                     let plan = task.plan;
+                    let plan_fingerprint = plan_fingerprint(&plan);
                     let curator_task = task.tasks[0].clone();
                     let out: tokio::sync::oneshot::Receiver<
                         Result<Arc<dyn QueryStageExecutor>, BallistaError>,
                     > = dedicated_executor.spawn(async move {
-                        server.decode_task(curator_task, &plan).await
+                        AssertUnwindSafe(server.decode_task(curator_task, &plan))
+                            .catch_unwind()
+                            .await
+                            .unwrap_or_else(|panic| Err(panic_error(&panic)))
                     });
 
-                    let plan = out.await;
+                    let plan = out.await.map_err(|e| {
+                        BallistaError::Internal(format!(
+                            "Failed to receive the decoded plan: {e:?}"
+                        ))
+                    });
 
-                    let plan = match plan {
-                        Ok(Ok(plan)) => plan,
-                        Ok(Err(e)) => {
-                            error!(
-                                "Failed to decode the plan of task {:?} due to {:?}",
-                                task_identity(&task.tasks[0]),
-                                e
-                            );
-                            return;
-                        }
+                    // The tasks whose plan cannot be decoded fail, the other tasks still run
+                    let plan = match plan.and_then(|plan| plan) {
+                        Ok(plan) => plan,
                         Err(e) => {
                             error!(
-                                "Failed to receive error plan of task {:?} due to {:?}",
+                                "Failed to decode the plan of task {:?} due to {:?}",
                                 task_identity(&task.tasks[0]),
                                 e
                             );
-                            return;
+                            for curator_task in &task.tasks {
+                                executor_server
+                                    .report_task_failure(
+                                        task.scheduler_id.clone(),
+                                        curator_task,
+                                        BallistaError::General(format!(
+                                            "Failed to decode the plan of the task: {e}"
+                                        )),
+                                    )
+                                    .await;
+                            }
+                            continue;
                         }
                     };
                     let scheduler_id = task.scheduler_id.clone();
@@ -675,22 +914,34 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskRunnerPool<T,
                         info!("Received task {:?}", &task_identity);
 
                         let server = executor_server.clone();
-                        dedicated_executor.spawn(async move {
-                            server
-                                .run_task(
-                                    &task_identity,
-                                    scheduler_id,
-                                    curator_task,
-                                    plan,
-                                )
-                                .await
-                                .unwrap_or_else(|e| {
-                                    error!(
-                                        "Fail to run the task {:?} due to {:?}",
-                                        task_identity, e
-                                    );
-                                });
-                        });
+                        let span = task_span(
+                            &curator_task.job_id,
+                            curator_task.task_id as usize,
+                            &curator_task.owner,
+                        );
+                        let task = async move {
+                            let failed_task = curator_task.clone();
+                            let result = AssertUnwindSafe(server.run_task(
+                                &task_identity,
+                                scheduler_id.clone(),
+                                curator_task,
+                                plan,
+                                plan_fingerprint,
+                            ))
+                            .catch_unwind()
+                            .await
+                            .unwrap_or_else(|panic| Err(panic_error(&panic)));
+                            if let Err(e) = result {
+                                error!(
+                                    "Fail to run the task {:?} due to {:?}",
+                                    task_identity, e
+                                );
+                                server
+                                    .report_task_failure(scheduler_id, &failed_task, e)
+                                    .await;
+                            }
+                        };
+                        dedicated_executor.spawn(task.instrument(span));
                     }
                 } else {
                     info!("Channel is closed and will exit the task receive loop");
@@ -714,6 +965,12 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
             tasks,
             scheduler_id,
         } = request.into_inner();
+        if TERMINATING.load(Ordering::Acquire) {
+            return Err(Status::unavailable("The executor is shutting down"));
+        }
+        if let Some(reason) = self.executor.disk_space_shortage() {
+            return Err(Status::resource_exhausted(reason));
+        }
         let task_sender = self.executor_env.tx_task.clone();
         for task in tasks {
             let (task_def, plan) = task
@@ -743,20 +1000,61 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
             scheduler_id,
         } = request.into_inner();
         let task_sender = self.executor_env.tx_task.clone();
+        // The tasks of a stage which cannot be run are rejected, the other tasks still run
+        let mut rejected_tasks = vec![];
+        // Checked once for all the tasks, which the scheduler then launches elsewhere
+        let disk_space_shortage = self.executor.disk_space_shortage();
         for multi_task in multi_tasks {
-            let (multi_task, plan): (Vec<TaskDefinition>, Vec<u8>) = multi_task
-                .try_into()
-                .map_err(|e| Status::invalid_argument(format!("{e}")))?;
-            task_sender
-                .send(CuratorTaskDefinition {
-                    scheduler_id: scheduler_id.clone(),
-                    plan,
-                    tasks: multi_task,
-                })
-                .await
-                .unwrap();
+            let job_id = multi_task.job_id.clone();
+            let stage_id = multi_task.stage_id;
+            let task_ids = multi_task.task_ids.clone();
+            // A terminating executor only completes the tasks it already runs
+            let reason = if TERMINATING.load(Ordering::Acquire) {
+                "The executor is shutting down".to_owned()
+            } else if let Some(reason) = &disk_space_shortage {
+                reason.clone()
+            } else {
+                let converted: Result<(Vec<TaskDefinition>, Vec<u8>), BallistaError> =
+                    multi_task.try_into();
+                match converted {
+                    Ok((tasks, plan)) => {
+                        let sent = task_sender
+                            .send(CuratorTaskDefinition {
+                                scheduler_id: scheduler_id.clone(),
+                                plan,
+                                tasks,
+                            })
+                            .await;
+                        match sent {
+                            Ok(()) => continue,
+                            Err(_) => "The executor is shutting down".to_owned(),
+                        }
+                    }
+                    Err(e) => format!("Invalid task definition: {e}"),
+                }
+            };
+            warn!(
+                "Rejecting {} tasks of job stage {}/{}: {}",
+                task_ids.len(),
+                job_id,
+                stage_id,
+                reason
+            );
+            rejected_tasks.extend(task_ids.into_iter().map(|task_id| RejectedTask {
+                job_id: job_id.clone(),
+                stage_id,
+                task_id: Some(task_id),
+                reason: reason.clone(),
+            }));
         }
-        Ok(Response::new(LaunchMultiTaskResult { success: true }))
+        if disk_space_shortage.is_some() {
+            // Tell the scheduler right away, so that it stops offering tasks to the executor
+            self.heartbeat().await;
+        }
+        Ok(Response::new(LaunchMultiTaskResult {
+            success: rejected_tasks.is_empty(),
+            rejected_tasks,
+        }))
     }
 
     async fn stop_executor(
@@ -814,38 +1112,112 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> ExecutorGrpc
         &self,
         request: Request<RemoveJobDataParams>,
     ) -> Result<Response<RemoveJobDataResult>, Status> {
-        let job_id = request.into_inner().job_id;
+        let RemoveJobDataParams {
+            job_id,
+            checkpoint_dir,
+        } = request.into_inner();
 
-        let work_dir = PathBuf::from(&self.executor.work_dir);
-        let mut path = work_dir.clone();
-        path.push(&job_id);
+        if !checkpoint_dir.is_empty() {
+            remove_job_checkpoints(Path::new(&checkpoint_dir), &job_id);
+        }
 
-        // Verify it's an existing directory
-        if !path.is_dir() {
-            return if !path.exists() {
-                Ok(Response::new(RemoveJobDataResult {}))
-            } else {
-                Err(Status::invalid_argument(format!(
+        // The tasks of the job may have written to any of the work dirs
+        let mut job_dirs = vec![];
+        for work_dir in self.executor.work_dirs.dirs() {
+            let work_dir = PathBuf::from(work_dir);
+            let mut path = work_dir.clone();
+            path.push(&job_id);
+
+            // Verify it's an existing directory
+            if !path.is_dir() {
+                if !path.exists() {
+                    continue;
+                }
+                return Err(Status::invalid_argument(format!(
                     "Path {path:?} is not for a directory!!!"
-                )))
-            };
+                )));
+            }
+
+            if !is_subdirectory(path.as_path(), work_dir.as_path()) {
+                return Err(Status::invalid_argument(format!(
+                    "Path {path:?} is not a subdirectory of {work_dir:?}!!!"
+                )));
+            }
+            job_dirs.push(path);
+        }
+        if job_dirs.is_empty() {
+            return Ok(Response::new(RemoveJobDataResult {}));
         }
 
-        if !is_subdirectory(path.as_path(), work_dir.as_path()) {
+        info!("Remove data for job {:?}", job_id);
+
+        self.executor.remove_cached_stage_results(&job_id);
+        for path in job_dirs {
+            std::fs::remove_dir_all(&path)?;
+        }
+
+        Ok(Response::new(RemoveJobDataResult {}))
+    }
+
+    async fn set_task_slots(
+        &self,
+        request: Request<SetTaskSlotsParams>,
+    ) -> Result<Response<SetTaskSlotsResult>, Status> {
+        let SetTaskSlotsParams {
+            executor_id,
+            task_slots,
+        } = request.into_inner();
+        if executor_id != self.executor.metadata.id {
             return Err(Status::invalid_argument(format!(
-                "Path {path:?} is not a subdirectory of {work_dir:?}!!!"
+                "The executor id {executor_id} in request is different from {}",
+                self.executor.metadata.id
             )));
         }
+        if task_slots == 0 {
+            return Err(Status::invalid_argument(
+                "An executor needs at least one task slot",
+            ));
+        }
 
-        info!("Remove data for job {:?}", job_id);
+        let previous_task_slots = self.executor.set_task_slots(task_slots);
+        info!("Task slots resized from {previous_task_slots} to {task_slots}");
+        // Advertise the task slots to the scheduler right away
+        self.heartbeat().await;
 
-        std::fs::remove_dir_all(&path)?;
+        Ok(Response::new(SetTaskSlotsResult {
+            previous_task_slots,
+        }))
+    }
 
-        Ok(Response::new(RemoveJobDataResult {}))
+    async fn get_running_tasks(
+        &self,
+        _request: Request<GetRunningTasksParams>,
+    ) -> Result<Response<GetRunningTasksResult>, Status> {
+        Ok(Response::new(GetRunningTasksResult {
+            task_infos: self.executor.running_tasks(),
+        }))
     }
 }
 
 // Check whether the path is the subdirectory of the base directory
+/// Delete the checkpoints of the stage outputs of a job. They are shared by the executors,
+/// any of which may have deleted them already.
+fn remove_job_checkpoints(checkpoint_dir: &Path, job_id: &str) {
+    let path = checkpoint_dir.join(job_id);
+    if !path.is_dir() {
+        return;
+    }
+    if !is_subdirectory(&path, checkpoint_dir) {
+        warn!("Checkpoints {path:?} of job {job_id} are not in {checkpoint_dir:?}");
+        return;
+    }
+    match std::fs::remove_dir_all(&path) {
+        Ok(()) => info!("Removed the checkpoints of job {job_id}"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to remove the checkpoints of job {job_id}: {e}"),
+    }
+}
+
 fn is_subdirectory(path: &Path, base_path: &Path) -> bool {
     if let (Ok(path), Ok(base_path)) = (path.canonicalize(), base_path.canonicalize()) {
         if let Some(parent_path) = path.parent() {
@@ -860,7 +1232,7 @@ fn is_subdirectory(path: &Path, base_path: &Path) -> bool {
 
 #[cfg(test)]
 mod test {
-    use crate::executor_server::is_subdirectory;
+    use crate::executor_server::{is_subdirectory, remove_job_checkpoints};
     use std::fs;
     use std::path::{Path, PathBuf};
     use tempfile::TempDir;
@@ -891,6 +1263,24 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_remove_job_checkpoints() {
+        let checkpoint_dir = TempDir::new().unwrap().into_path();
+        let job_path = prepare_testing_job_directory(&checkpoint_dir, "job_a");
+        fs::create_dir(job_path.join("1")).unwrap();
+        fs::write(job_path.join("1").join("data-0.arrow"), b"data").unwrap();
+
+        // Directories outside of the checkpoint directory are kept
+        remove_job_checkpoints(&checkpoint_dir, "..");
+        assert!(checkpoint_dir.exists());
+
+        remove_job_checkpoints(&checkpoint_dir, "job_a");
+        assert!(!job_path.exists());
+
+        // Another executor deleting them again is fine
+        remove_job_checkpoints(&checkpoint_dir, "job_a");
+    }
+
     fn prepare_testing_job_directory(base_dir: &Path, job_id: &str) -> PathBuf {
         let mut path = base_dir.to_path_buf();
         path.push(job_id);
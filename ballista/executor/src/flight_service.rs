@@ -19,12 +19,18 @@
 
 use std::convert::TryFrom;
 use std::fs::File;
+use std::path::Path;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use arrow_flight::SchemaAsIpc;
 use ballista_core::error::BallistaError;
+use ballista_core::execution_plans::{ShuffleCodec, ShuffleFileReader};
 use ballista_core::serde::decode_protobuf;
+use ballista_core::serde::protobuf::TaskLogLines;
 use ballista_core::serde::scheduler::Action as BallistaAction;
+use ballista_core::utils::verify_file_checksum;
+use prost::Message;
 
 use arrow::ipc::writer::{DictionaryTracker, IpcDataGenerator, IpcWriteOptions};
 use arrow_flight::{
@@ -32,12 +38,9 @@ use arrow_flight::{
     FlightData, FlightDescriptor, FlightInfo, HandshakeRequest, HandshakeResponse,
     PutResult, SchemaResult, Ticket,
 };
-use datafusion::arrow::{
-    error::ArrowError, ipc::reader::FileReader, record_batch::RecordBatch,
-};
+use datafusion::arrow::{error::ArrowError, record_batch::RecordBatch};
 use futures::{Stream, StreamExt};
 use log::{debug, info, warn};
-use std::io::{Read, Seek};
 use tokio::sync::mpsc::channel;
 use tokio::{
     sync::mpsc::{Receiver, Sender},
@@ -47,16 +50,38 @@ use tokio_stream::wrappers::ReceiverStream;
 use tonic::metadata::MetadataValue;
 use tonic::{Request, Response, Status, Streaming};
 
+use crate::plugin::{authenticate, ExecutorPlugin};
+use crate::task_logs::TaskLogs;
+
 type FlightDataSender = Sender<Result<FlightData, Status>>;
 type FlightDataReceiver = Receiver<Result<FlightData, Status>>;
 
 /// Service implementing the Apache Arrow Flight Protocol
 #[derive(Clone)]
-pub struct BallistaFlightService {}
+pub struct BallistaFlightService {
+    /// Logs of the recent task attempts served to the scheduler, none if they are not captured
+    task_logs: Option<Arc<TaskLogs>>,
+    /// Plugins authenticating the clients fetching the logs of tasks
+    plugins: Vec<Arc<dyn ExecutorPlugin>>,
+}
 
 impl BallistaFlightService {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            task_logs: None,
+            plugins: vec![],
+        }
+    }
+
+    pub fn with_task_logs(mut self, task_logs: Option<Arc<TaskLogs>>) -> Self {
+        self.task_logs = task_logs;
+        self
+    }
+
+    /// Authenticate the clients fetching the logs of tasks with `plugins`
+    pub fn with_plugins(mut self, plugins: Vec<Arc<dyn ExecutorPlugin>>) -> Self {
+        self.plugins = plugins;
+        self
     }
 }
 
@@ -89,8 +114,12 @@ impl FlightService for BallistaFlightService {
             decode_protobuf(&ticket.ticket).map_err(|e| from_ballista_err(&e))?;
 
         match &action {
-            BallistaAction::FetchPartition { path, .. } => {
+            BallistaAction::FetchPartition { path, checksum, .. } => {
                 debug!("FetchPartition reading {}", path);
+                if let Some(checksum) = checksum {
+                    verify_file_checksum(path, *checksum)
+                        .map_err(|e| from_ballista_err(&e))?;
+                }
                 let file = File::open(path)
                     .map_err(|e| {
                         BallistaError::General(format!(
@@ -98,8 +127,9 @@ impl FlightService for BallistaFlightService {
                         ))
                     })
                     .map_err(|e| from_ballista_err(&e))?;
-                let reader =
-                    FileReader::try_new(file, None).map_err(|e| from_arrow_err(&e))?;
+                let codec = ShuffleCodec::from_path(Path::new(path));
+                let reader = ShuffleFileReader::try_new(file, codec)
+                    .map_err(|e| from_arrow_err(&e))?;
 
                 let (tx, rx): (FlightDataSender, FlightDataReceiver) = channel(2);
 
@@ -116,6 +146,9 @@ impl FlightService for BallistaFlightService {
                     Box::pin(ReceiverStream::new(rx)) as Self::DoGetStream
                 ))
             }
+            _ => Err(Status::invalid_argument(format!(
+                "Action {action:?} does not fetch data"
+            ))),
         }
     }
 
@@ -180,11 +213,68 @@ impl FlightService for BallistaFlightService {
         &self,
         request: Request<Action>,
     ) -> Result<Response<Self::DoActionStream>, Status> {
-        let action = request.into_inner();
-
-        let _action = decode_protobuf(&action.body).map_err(|e| from_ballista_err(&e))?;
+        let (metadata, _, action) = request.into_parts();
+
+        let action = decode_protobuf(&action.body).map_err(|e| from_ballista_err(&e))?;
+
+        match action {
+            BallistaAction::FetchTaskLogs {
+                job_id,
+                task_id,
+                from_line,
+                follow,
+            } => {
+                let task_logs = self.task_logs.clone().ok_or_else(|| {
+                    Status::unavailable("The executor does not capture the logs of tasks")
+                })?;
+                // The logs may hold literals of the queries and credentials
+                let user = authenticate(&self.plugins, &metadata).map_err(|e| {
+                    Status::unauthenticated(format!(
+                        "Failed to authenticate access to the logs of job {job_id}: {e}"
+                    ))
+                })?;
+                if !task_logs.authorize(&job_id, task_id, user.as_deref()) {
+                    let msg = format!(
+                        "{} may not read the logs of task {task_id} of job {job_id}",
+                        user.as_deref().unwrap_or("Anonymous client")
+                    );
+                    warn!("{}", msg);
+                    return Err(Status::permission_denied(msg));
+                }
+                let lines =
+                    task_logs
+                        .lines(&job_id, task_id, from_line)
+                        .ok_or_else(|| {
+                            Status::not_found(format!(
+                                "No logs of task {task_id} of job {job_id}"
+                            ))
+                        })?;
+
+                let (tx, rx) = channel::<Result<TaskLogLines, Status>>(2);
+                task::spawn(async move {
+                    let mut lines = Some(lines);
+                    while let Some(chunk) = lines.take() {
+                        let next_line = chunk.first_line + chunk.lines.len() as u64;
+                        let finished = chunk.finished;
+                        if tx.send(Ok(chunk)).await.is_err() || !follow || finished {
+                            break;
+                        }
+                        // None once the executor dropped the log of the task
+                        lines = task_logs.next_lines(&job_id, task_id, next_line).await;
+                    }
+                });
 
-        Err(Status::unimplemented("do_action"))
+                let results = ReceiverStream::new(rx).map(|lines| {
+                    lines.map(|lines: TaskLogLines| arrow_flight::Result {
+                        body: lines.encode_to_vec().into(),
+                    })
+                });
+                Ok(Response::new(Box::pin(results) as Self::DoActionStream))
+            }
+            action => Err(Status::unimplemented(format!(
+                "do_action does not support {action:?}"
+            ))),
+        }
     }
 
     async fn list_actions(
@@ -203,14 +293,15 @@ impl FlightService for BallistaFlightService {
 }
 
 /// Convert a single RecordBatch into an iterator of FlightData (containing
-/// dictionaries and batches)
+/// dictionaries and batches). Only the dictionaries which were not sent yet by the stream
+/// tracked by `dictionary_tracker` are included
 fn create_flight_iter(
     batch: &RecordBatch,
+    dictionary_tracker: &mut DictionaryTracker,
     options: &IpcWriteOptions,
 ) -> Box<dyn Iterator<Item = Result<FlightData, Status>>> {
     let data_gen = IpcDataGenerator::default();
-    let mut dictionary_tracker = DictionaryTracker::new(false);
-    let res = data_gen.encoded_batch(batch, &mut dictionary_tracker, options);
+    let res = data_gen.encoded_batch(batch, dictionary_tracker, options);
     match res {
         Ok((dicts, batch)) => {
             let flights = dicts
@@ -223,25 +314,25 @@ fn create_flight_iter(
     }
 }
 
-async fn stream_flight_data<T>(
+async fn stream_flight_data(
     file_path: String,
-    reader: FileReader<T>,
+    reader: ShuffleFileReader,
     tx: FlightDataSender,
-) -> Result<(), Status>
-where
-    T: Read + Seek,
-{
+) -> Result<(), Status> {
     let options = arrow::ipc::writer::IpcWriteOptions::default();
     let schema_flight_data = SchemaAsIpc::new(reader.schema().as_ref(), &options).into();
     send_response(&tx, Ok(schema_flight_data)).await?;
 
+    // The batches of a partition mostly share their dictionaries, which are sent once and
+    // again only when they are replaced
+    let mut dictionary_tracker = DictionaryTracker::new(false);
     let mut row_count = 0;
     for batch in reader {
         if let Ok(x) = &batch {
             row_count += x.num_rows();
         }
         let batch_flight_data: Vec<_> = batch
-            .map(|b| create_flight_iter(&b, &options).collect())
+            .map(|b| create_flight_iter(&b, &mut dictionary_tracker, &options).collect())
             .map_err(|e| from_arrow_err(&e))?;
         for batch in batch_flight_data.into_iter() {
             send_response(&tx, batch).await?;
@@ -17,6 +17,8 @@
 
 #![doc = include_str!("../README.md")]
 
+pub mod batch_size;
+pub mod cgroup;
 pub mod collect;
 pub mod execution_engine;
 pub mod execution_loop;
@@ -25,19 +27,29 @@ pub mod executor_process;
 pub mod executor_server;
 pub mod flight_service;
 pub mod metrics;
+pub mod plugin;
+pub mod prefetch;
+pub mod scheduler_endpoints;
 pub mod shutdown;
+pub mod stage_result_cache;
+pub mod task_logs;
 pub mod terminate;
+pub mod work_dir_recovery;
+pub mod work_dirs;
+pub mod work_stealing;
 
 mod cpu_bound_executor;
+mod resource_usage;
 mod standalone;
 
 pub use standalone::new_standalone_executor;
 
+use datafusion::arrow::datatypes::SchemaRef;
 use log::info;
 
 use ballista_core::serde::protobuf::{
     task_status, FailedTask, OperatorMetricsSet, ShuffleWritePartition, SuccessfulTask,
-    TaskStatus,
+    TaskResourceUsage, TaskStatus,
 };
 use ballista_core::serde::scheduler::PartitionId;
 
@@ -56,6 +68,8 @@ pub fn as_task_status(
     partition_id: PartitionId,
     operator_metrics: Option<Vec<OperatorMetricsSet>>,
     execution_times: TaskExecutionTimes,
+    resource_usage: Option<TaskResourceUsage>,
+    output_schema: Option<SchemaRef>,
 ) -> TaskStatus {
     let metrics = operator_metrics.unwrap_or_default();
     match execution_result {
@@ -75,9 +89,12 @@ pub fn as_task_status(
                 start_exec_time: execution_times.start_exec_time,
                 end_exec_time: execution_times.end_exec_time,
                 metrics,
+                resource_usage,
                 status: Some(task_status::Status::Successful(SuccessfulTask {
                     executor_id,
                     partitions,
+                    output_schema: output_schema
+                        .and_then(|schema| schema.as_ref().try_into().ok()),
                 })),
             }
         }
@@ -95,6 +112,7 @@ pub fn as_task_status(
                 start_exec_time: execution_times.start_exec_time,
                 end_exec_time: execution_times.end_exec_time,
                 metrics,
+                resource_usage,
                 status: Some(task_status::Status::Failed(FailedTask::from(e))),
             }
         }
@@ -0,0 +1,187 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Plugins extending the executor at startup, either registered with the
+//! [`ExecutorProcessConfig`](crate::executor_process::ExecutorProcessConfig) of a custom executor
+//! binary or, with the `dynamic-plugins` feature, loaded from the shared libraries in the
+//! `--plugin-dir` of the executor. A shared library declares its plugin with
+//! [`declare_executor_plugin!`](crate::declare_executor_plugin).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use datafusion::datasource::provider::TableProviderFactory;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::physical_plan::udaf::AggregateUDF;
+use datafusion::physical_plan::udf::ScalarUDF;
+use tonic::metadata::MetadataMap;
+
+pub use ballista_core::declare_plugin;
+use ballista_core::error::Result;
+pub use ballista_core::plugin::{PluginEnum, CORE_VERSION, RUSTC_VERSION};
+
+use crate::execution_engine::ExecutionEngine;
+use crate::executor::TaskContextExtension;
+
+/// Plugin contributing object stores, functions, table factories, execution hooks and the
+/// authentication of the clients of the executor. All methods but
+/// [`name`](ExecutorPlugin::name) contribute nothing by default.
+pub trait ExecutorPlugin: Send + Sync {
+    /// Name of the plugin, used in logs
+    fn name(&self) -> &str;
+
+    /// Register object stores with the runtime shared by the tasks of the executor
+    fn register_object_stores(&self, _runtime: &RuntimeEnv) -> Result<()> {
+        Ok(())
+    }
+
+    /// Scalar functions which the plans run by the executor may call
+    fn scalar_functions(&self) -> Vec<Arc<ScalarUDF>> {
+        vec![]
+    }
+
+    /// Aggregate functions which the plans run by the executor may call
+    fn aggregate_functions(&self) -> Vec<Arc<AggregateUDF>> {
+        vec![]
+    }
+
+    /// Table provider factories by file type, for the extension codecs of custom tables
+    fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+        HashMap::new()
+    }
+
+    /// Extensions of the task contexts of the tasks run by the executor
+    fn task_context_extensions(&self) -> Vec<Arc<dyn TaskContextExtension>> {
+        vec![]
+    }
+
+    /// Execution engine replacing the default one. The engine of the first plugin providing
+    /// one is used, unless the executor is configured with an engine already.
+    fn execution_engine(&self) -> Option<Arc<dyn ExecutionEngine>> {
+        None
+    }
+
+    /// Authenticate the client fetching the logs of a task from the metadata of its request,
+    /// which the scheduler forwards from its own client. Returns the identity of the client if
+    /// the plugin recognizes its credentials, None to leave them to the other plugins, or an
+    /// error to reject the request.
+    fn authenticate(&self, _metadata: &MetadataMap) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// Identity of the client of a request given by the first plugin recognizing its
+/// credentials, None if no plugin does
+pub fn authenticate(
+    plugins: &[Arc<dyn ExecutorPlugin>],
+    metadata: &MetadataMap,
+) -> Result<Option<String>> {
+    for plugin in plugins {
+        if let Some(user) = plugin.authenticate(metadata)? {
+            return Ok(Some(user));
+        }
+    }
+    Ok(None)
+}
+
+/// Declare the executor plugin of a shared library, created by `$constructor`. Only one
+/// plugin can be declared per library.
+#[macro_export]
+macro_rules! declare_executor_plugin {
+    ($plugin_type:ty, $constructor:path) => {
+        #[no_mangle]
+        pub fn registrar_executor_plugin() -> Box<dyn $crate::plugin::ExecutorPlugin> {
+            // make sure the constructor is the correct type
+            let constructor: fn() -> $plugin_type = $constructor;
+            Box::new(constructor())
+        }
+
+        $crate::plugin::declare_plugin!($crate::plugin::PluginEnum::Executor);
+    };
+}
+
+/// Load the plugins of the shared libraries in `plugin_dir` with the plugin manager of
+/// ballista-core. Libraries which declare no executor plugin are skipped.
+#[cfg(feature = "dynamic-plugins")]
+pub fn load_plugins(plugin_dir: &str) -> Result<Vec<Arc<dyn ExecutorPlugin>>> {
+    use ballista_core::plugin::plugin_manager::load_global_plugins;
+    use ballista_core::plugin::LoadedPlugin;
+    use log::info;
+
+    /// A plugin along with the library holding its code
+    struct LibraryPlugin(LoadedPlugin<Box<dyn ExecutorPlugin>>);
+
+    impl ExecutorPlugin for LibraryPlugin {
+        fn name(&self) -> &str {
+            self.0.plugin.name()
+        }
+
+        fn register_object_stores(&self, runtime: &RuntimeEnv) -> Result<()> {
+            self.0.plugin.register_object_stores(runtime)
+        }
+
+        fn scalar_functions(&self) -> Vec<Arc<ScalarUDF>> {
+            self.0.plugin.scalar_functions()
+        }
+
+        fn aggregate_functions(&self) -> Vec<Arc<AggregateUDF>> {
+            self.0.plugin.aggregate_functions()
+        }
+
+        fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+            self.0.plugin.table_factories()
+        }
+
+        fn task_context_extensions(&self) -> Vec<Arc<dyn TaskContextExtension>> {
+            self.0.plugin.task_context_extensions()
+        }
+
+        fn execution_engine(&self) -> Option<Arc<dyn ExecutionEngine>> {
+            self.0.plugin.execution_engine()
+        }
+
+        fn authenticate(&self, metadata: &MetadataMap) -> Result<Option<String>> {
+            self.0.plugin.authenticate(metadata)
+        }
+    }
+
+    let libraries = load_global_plugins(plugin_dir)?
+        .lock()
+        .unwrap()
+        .plugin_libraries(PluginEnum::Executor);
+    // Safety: the libraries declaring an executor plugin export its registrar, declared with
+    // `declare_executor_plugin!`
+    let plugins = unsafe { libraries.create_plugins(b"registrar_executor_plugin\0")? };
+    Ok(plugins
+        .into_iter()
+        .map(|plugin| {
+            info!("Loaded executor plugin {}", plugin.plugin.name());
+            Arc::new(LibraryPlugin(plugin)) as Arc<dyn ExecutorPlugin>
+        })
+        .collect())
+}
+
+/// Loading plugins from shared libraries requires the `dynamic-plugins` feature, without
+/// which the plugin dir is ignored
+#[cfg(not(feature = "dynamic-plugins"))]
+pub fn load_plugins(plugin_dir: &str) -> Result<Vec<Arc<dyn ExecutorPlugin>>> {
+    log::warn!(
+        "Ignoring the plugins in {plugin_dir}, the executor was built without the \
+         dynamic-plugins feature"
+    );
+    Ok(vec![])
+}
@@ -0,0 +1,353 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Prefetching of the batches of scans so that fetching data from object stores overlaps with
+//! processing the batches already fetched
+
+use std::any::Any;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_plan::expressions::PhysicalSortExpr;
+use datafusion::physical_plan::{
+    DisplayFormatType, ExecutionPlan, Partitioning, RecordBatchStream,
+    SendableRecordBatchStream, Statistics,
+};
+use futures::{ready, Stream, StreamExt};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::resource_usage::CpuTimer;
+
+/// Wrap the leaves of a plan, which scan files or fetch shuffle partitions, in a
+/// [PrefetchExec] buffering up to `prefetch_batches` batches. Returns the plan unchanged if
+/// `prefetch_batches` is 0.
+pub fn with_scan_prefetch(
+    plan: Arc<dyn ExecutionPlan>,
+    prefetch_batches: usize,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    if prefetch_batches == 0 {
+        return Ok(plan);
+    }
+    plan.transform_up(&|plan| {
+        if plan.children().is_empty() {
+            Ok(Transformed::Yes(Arc::new(PrefetchExec::new(
+                plan,
+                prefetch_batches,
+            ))))
+        } else {
+            Ok(Transformed::No(plan))
+        }
+    })
+}
+
+/// Reads the batches of its input ahead on a separate tokio task while they are consumed,
+/// buffering at most `prefetch_batches` of them. While a batch is processed the next
+/// file or byte range of the input is already being fetched. The CPU time of the reading
+/// task counts towards the task executing the plan if its context has a [CpuTimer].
+#[derive(Debug)]
+pub struct PrefetchExec {
+    input: Arc<dyn ExecutionPlan>,
+    prefetch_batches: usize,
+}
+
+impl PrefetchExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, prefetch_batches: usize) -> Self {
+        Self {
+            input,
+            prefetch_batches,
+        }
+    }
+}
+
+impl ExecutionPlan for PrefetchExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(PrefetchExec::new(
+            children[0].clone(),
+            self.prefetch_batches,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let cpu_timer = context.session_config().get_extension::<CpuTimer>();
+        let input = self.input.execute(partition, context)?;
+        Ok(Box::pin(PrefetchStream::new(
+            input,
+            self.prefetch_batches,
+            cpu_timer,
+        )))
+    }
+
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(f, "PrefetchExec: batches={}", self.prefetch_batches)
+            }
+        }
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+}
+
+/// Stream of the batches read ahead by a [PrefetchExec]. Reading stops when it is dropped.
+struct PrefetchStream {
+    schema: SchemaRef,
+    batches: mpsc::Receiver<Result<RecordBatch>>,
+    task: JoinHandle<()>,
+    /// Whether the task reading the batches ended, after which the stream ends
+    task_ended: bool,
+}
+
+impl PrefetchStream {
+    /// Read up to `prefetch_batches` batches of `input` ahead on a task of its own, whose
+    /// CPU time is measured with `cpu_timer`
+    fn new(
+        mut input: SendableRecordBatchStream,
+        prefetch_batches: usize,
+        cpu_timer: Option<Arc<CpuTimer>>,
+    ) -> Self {
+        let schema = input.schema();
+        let (tx, rx) = mpsc::channel(prefetch_batches.max(1));
+        let read = async move {
+            while let Some(batch) = input.next().await {
+                let failed = batch.is_err();
+                // stop reading once the stream was dropped or failed
+                if tx.send(batch).await.is_err() || failed {
+                    break;
+                }
+            }
+        };
+        let task = match cpu_timer {
+            Some(cpu_timer) => tokio::spawn(cpu_timer.measure(read)),
+            None => tokio::spawn(read),
+        };
+        Self {
+            schema,
+            batches: rx,
+            task,
+            task_ended: false,
+        }
+    }
+}
+
+impl Stream for PrefetchStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.task_ended {
+            return Poll::Ready(None);
+        }
+        if let Some(batch) = ready!(this.batches.poll_recv(cx)) {
+            return Poll::Ready(Some(batch));
+        }
+        // The batches end once the task reading them ended, which fails the stream if the
+        // task panicked instead of leaving the batches silently truncated
+        let result = ready!(Pin::new(&mut this.task).poll(cx));
+        this.task_ended = true;
+        match result {
+            Err(e) if e.is_panic() => Poll::Ready(Some(Err(DataFusionError::Execution(
+                format!("Prefetching the batches of the scan panicked: {e}"),
+            )))),
+            _ => Poll::Ready(None),
+        }
+    }
+}
+
+impl RecordBatchStream for PrefetchStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl Drop for PrefetchStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
+    use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use datafusion::prelude::{SessionConfig, SessionContext};
+    use std::collections::HashMap;
+
+    use crate::resource_usage::TaskResourceMeter;
+
+    fn test_input() -> Result<Arc<dyn ExecutionPlan>> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, false)]));
+        let batches = (0..10)
+            .map(|i| {
+                RecordBatch::try_new(
+                    schema.clone(),
+                    vec![Arc::new(Int32Array::from(vec![i, i + 1]))],
+                )
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Arc::new(MemoryExec::try_new(&[batches], schema, None)?))
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_keeps_batches() -> Result<()> {
+        let input = test_input()?;
+        let task_ctx = SessionContext::new().task_ctx();
+        let expected = collect(input.execute(0, task_ctx.clone())?).await?;
+
+        let prefetch = PrefetchExec::new(input, 2);
+        let batches = collect(prefetch.execute(0, task_ctx)?).await?;
+        assert_eq!(batches, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_reader_panic() -> Result<()> {
+        let input = test_input()?;
+        let task_ctx = SessionContext::new().task_ctx();
+        let mut batches = input.execute(0, task_ctx)?;
+        let first = batches.next().await.unwrap()?;
+        // The reader panics after the first batch
+        let panicking = futures::stream::once(async move { Ok(first) })
+            .chain(futures::stream::once(async { panic!("lost connection") }));
+        let stream = RecordBatchStreamAdapter::new(input.schema(), Box::pin(panicking));
+
+        let result =
+            collect(Box::pin(PrefetchStream::new(Box::pin(stream), 2, None))).await;
+        assert!(result.unwrap_err().to_string().contains("panicked"));
+
+        Ok(())
+    }
+
+    // The CPU time of the reading task counts towards the task executing the plan
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_prefetch_cpu_time() -> Result<()> {
+        let input = test_input()?;
+        let meter = TaskResourceMeter::new(&RuntimeEnv::new(RuntimeConfig::new())?);
+        let session_config =
+            SessionConfig::new().with_extension(Arc::new(meter.cpu_timer()));
+        let task_ctx = Arc::new(TaskContext::new(
+            None,
+            "session".to_owned(),
+            session_config,
+            HashMap::new(),
+            HashMap::new(),
+            meter.runtime(),
+        ));
+        let batches = input.execute(0, task_ctx.clone())?;
+        // The reader spends CPU time before each batch
+        let busy = batches.map(|batch| {
+            let mut sum = 0u64;
+            for i in 0..5_000_000u64 {
+                sum = sum.wrapping_add(std::hint::black_box(i));
+            }
+            assert!(sum > 0);
+            batch
+        });
+        let stream = RecordBatchStreamAdapter::new(input.schema(), Box::pin(busy));
+        let cpu_timer = task_ctx.session_config().get_extension::<CpuTimer>();
+
+        // Only the reading task is measured
+        collect(Box::pin(PrefetchStream::new(
+            Box::pin(stream),
+            2,
+            cpu_timer,
+        )))
+        .await?;
+        let usage = meter.usage(&[]);
+        assert!(usage.user_cpu_time_us + usage.sys_cpu_time_us > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_scan_prefetch_wraps_leaves() -> Result<()> {
+        let plan: Arc<dyn ExecutionPlan> =
+            Arc::new(CoalesceBatchesExec::new(test_input()?, 1024));
+
+        let unchanged = with_scan_prefetch(plan.clone(), 0)?;
+        assert!(unchanged.children()[0]
+            .as_any()
+            .downcast_ref::<MemoryExec>()
+            .is_some());
+
+        let prefetched = with_scan_prefetch(plan, 4)?;
+        assert!(prefetched
+            .as_any()
+            .downcast_ref::<CoalesceBatchesExec>()
+            .is_some());
+        let prefetch = prefetched.children()[0].clone();
+        assert!(prefetch.as_any().downcast_ref::<PrefetchExec>().is_some());
+        assert!(prefetch.children()[0]
+            .as_any()
+            .downcast_ref::<MemoryExec>()
+            .is_some());
+
+        Ok(())
+    }
+}
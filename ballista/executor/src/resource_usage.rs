@@ -0,0 +1,409 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Measurement of the CPU time, memory and spill space used by a task
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use ballista_core::serde::protobuf::TaskResourceUsage;
+use datafusion::datasource::object_store::ObjectStoreRegistry;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::disk_manager::{DiskManager, DiskManagerConfig};
+use datafusion::execution::memory_pool::{MemoryConsumer, MemoryPool, MemoryReservation};
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::physical_plan::metrics::{MetricValue, MetricsSet};
+use tempfile::TempDir;
+
+/// Measures the resources used by a task while it is executed.
+///
+/// CPU time is measured on the threads polling the task future. Work the task's plan spawns
+/// onto other tokio tasks is only accounted if it is measured with the [CpuTimer] of the
+/// task, which the executor adds to the session config of the task context. Memory is the
+/// peak of the memory reserved from the executor's memory pool through the runtime returned
+/// by [Self::runtime]. With a memory limit, reservations of the task beyond the limit fail,
+/// so that the operators which can spill, such as sorts, spill to the disk manager of the
+/// runtime instead. With a spill dir, the task spills to its own directory, removed once the
+/// meter and its clones are dropped.
+#[derive(Debug, Clone)]
+pub struct TaskResourceMeter {
+    runtime: Arc<RuntimeEnv>,
+    memory_pool: Arc<TrackedMemoryPool>,
+    spill_dir: Option<Arc<TempDir>>,
+    cpu_timer: CpuTimer,
+    batch_size: usize,
+    started: Instant,
+}
+
+impl TaskResourceMeter {
+    /// Create a meter for a task executed with the memory pool, disk manager and object
+    /// stores of `runtime`
+    pub fn new(runtime: &RuntimeEnv) -> Self {
+        let memory_pool = Arc::new(TrackedMemoryPool::new(runtime.memory_pool.clone()));
+        let task_runtime = RuntimeEnv {
+            memory_pool: memory_pool.clone(),
+            disk_manager: runtime.disk_manager.clone(),
+            object_store_registry: runtime.object_store_registry.clone(),
+        };
+        Self {
+            runtime: Arc::new(task_runtime),
+            memory_pool,
+            spill_dir: None,
+            cpu_timer: CpuTimer::default(),
+            batch_size: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Report the batch size the task is executed with
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Limit the memory the task may reserve to `memory_limit` bytes, 0 for no limit
+    pub fn with_memory_limit(self, memory_limit: usize) -> Self {
+        self.memory_pool.limit.store(memory_limit, Ordering::SeqCst);
+        self
+    }
+
+    /// Resolve the object stores of the task with `object_store_registry` instead of the
+    /// registry of the executor's runtime
+    pub fn with_object_store_registry(
+        mut self,
+        object_store_registry: Arc<dyn ObjectStoreRegistry>,
+    ) -> Self {
+        self.runtime = Arc::new(RuntimeEnv {
+            memory_pool: self.memory_pool.clone(),
+            disk_manager: self.runtime.disk_manager.clone(),
+            object_store_registry,
+        });
+        self
+    }
+
+    /// Spill the files of the task to `spill_dir` instead of the directories of the disk
+    /// manager of the executor. The directory is readable by the owner only on Unix.
+    pub fn with_spill_dir(mut self, spill_dir: TempDir) -> Result<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(
+                spill_dir.path(),
+                std::fs::Permissions::from_mode(0o700),
+            )?;
+        }
+        let disk_manager =
+            DiskManager::try_new(DiskManagerConfig::NewSpecified(vec![spill_dir
+                .path()
+                .to_path_buf()]))?;
+        self.runtime = Arc::new(RuntimeEnv {
+            memory_pool: self.memory_pool.clone(),
+            disk_manager,
+            object_store_registry: self.runtime.object_store_registry.clone(),
+        });
+        self.spill_dir = Some(Arc::new(spill_dir));
+        Ok(self)
+    }
+
+    /// Runtime to execute the task with, so that the memory it reserves is tracked
+    pub fn runtime(&self) -> Arc<RuntimeEnv> {
+        self.runtime.clone()
+    }
+
+    /// Timer accumulating the CPU time of the task, to measure the futures the task spawns
+    pub fn cpu_timer(&self) -> CpuTimer {
+        self.cpu_timer.clone()
+    }
+
+    /// Wrap the future executing the task to measure the CPU time spent polling it
+    pub fn measure<F: Future>(&self, future: F) -> CpuTimed<F> {
+        self.cpu_timer.measure(future)
+    }
+
+    /// Resources used by the task so far. Spilled bytes and output rows are taken from the
+    /// metrics of the task's plan, whose first metrics are the ones of its root.
+    pub fn usage(&self, plan_metrics: &[MetricsSet]) -> TaskResourceUsage {
+        let spilled_bytes: usize = plan_metrics
+            .iter()
+            .flat_map(|metrics| metrics.iter())
+            .map(|metric| match metric.value() {
+                MetricValue::SpilledBytes(count) => count.value(),
+                _ => 0,
+            })
+            .sum();
+        TaskResourceUsage {
+            user_cpu_time_us: self.cpu_timer.user_cpu_time_us.load(Ordering::Relaxed),
+            sys_cpu_time_us: self.cpu_timer.sys_cpu_time_us.load(Ordering::Relaxed),
+            peak_memory_bytes: self.memory_pool.peak() as u64,
+            spilled_bytes: spilled_bytes as u64,
+            batch_size: self.batch_size as u64,
+            wall_clock_time_us: self.started.elapsed().as_micros() as u64,
+            output_rows: plan_metrics
+                .first()
+                .and_then(|metrics| metrics.output_rows())
+                .unwrap_or_default() as u64,
+        }
+    }
+}
+
+/// CPU time used by a task, accumulated by the futures it measures
+#[derive(Debug, Clone, Default)]
+pub struct CpuTimer {
+    user_cpu_time_us: Arc<AtomicU64>,
+    sys_cpu_time_us: Arc<AtomicU64>,
+}
+
+impl CpuTimer {
+    /// Wrap a future of the task to add the CPU time spent polling it to the CPU time of the
+    /// task
+    pub fn measure<F: Future>(&self, future: F) -> CpuTimed<F> {
+        CpuTimed {
+            inner: Box::pin(future),
+            user_cpu_time_us: self.user_cpu_time_us.clone(),
+            sys_cpu_time_us: self.sys_cpu_time_us.clone(),
+        }
+    }
+}
+
+/// Future accumulating the CPU time of the current thread spent polling the inner future
+pub struct CpuTimed<F: Future> {
+    inner: Pin<Box<F>>,
+    user_cpu_time_us: Arc<AtomicU64>,
+    sys_cpu_time_us: Arc<AtomicU64>,
+}
+
+impl<F: Future> Future for CpuTimed<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let start = thread_cpu_time();
+        let poll = self.inner.as_mut().poll(cx);
+        if let (Some((start_user, start_sys)), Some((end_user, end_sys))) =
+            (start, thread_cpu_time())
+        {
+            self.user_cpu_time_us
+                .fetch_add(end_user.saturating_sub(start_user), Ordering::Relaxed);
+            self.sys_cpu_time_us
+                .fetch_add(end_sys.saturating_sub(start_sys), Ordering::Relaxed);
+        }
+        poll
+    }
+}
+
+/// User and system CPU time in microseconds consumed by the current thread
+#[cfg(target_os = "linux")]
+fn thread_cpu_time() -> Option<(u64, u64)> {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // Safety: getrusage only writes to the rusage struct it is given
+    if unsafe { libc::getrusage(libc::RUSAGE_THREAD, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // Safety: the struct was initialized by the successful getrusage call
+    let usage = unsafe { usage.assume_init() };
+    let micros =
+        |time: libc::timeval| time.tv_sec as u64 * 1_000_000 + time.tv_usec as u64;
+    Some((micros(usage.ru_utime), micros(usage.ru_stime)))
+}
+
+/// Per thread CPU time is only measured on Linux
+#[cfg(not(target_os = "linux"))]
+fn thread_cpu_time() -> Option<(u64, u64)> {
+    None
+}
+
+/// Memory pool reserving from the executor's memory pool, tracking the peak memory reserved
+/// through it and bounding the memory reserved through it to a limit
+#[derive(Debug)]
+struct TrackedMemoryPool {
+    inner: Arc<dyn MemoryPool>,
+    reserved: AtomicUsize,
+    peak: AtomicUsize,
+    /// Maximum memory reserved through the pool, 0 for no limit
+    limit: AtomicUsize,
+}
+
+impl TrackedMemoryPool {
+    fn new(inner: Arc<dyn MemoryPool>) -> Self {
+        Self {
+            inner,
+            reserved: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            limit: AtomicUsize::new(0),
+        }
+    }
+
+    fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    fn add_reserved(&self, additional: usize) {
+        let reserved = self.reserved.fetch_add(additional, Ordering::SeqCst) + additional;
+        self.peak.fetch_max(reserved, Ordering::SeqCst);
+    }
+}
+
+impl MemoryPool for TrackedMemoryPool {
+    fn register(&self, consumer: &MemoryConsumer) {
+        self.inner.register(consumer)
+    }
+
+    fn unregister(&self, consumer: &MemoryConsumer) {
+        self.inner.unregister(consumer)
+    }
+
+    fn grow(&self, reservation: &MemoryReservation, additional: usize) {
+        self.inner.grow(reservation, additional);
+        self.add_reserved(additional);
+    }
+
+    fn shrink(&self, reservation: &MemoryReservation, shrink: usize) {
+        self.inner.shrink(reservation, shrink);
+        self.reserved.fetch_sub(shrink, Ordering::SeqCst);
+    }
+
+    fn try_grow(&self, reservation: &MemoryReservation, additional: usize) -> Result<()> {
+        let limit = self.limit.load(Ordering::SeqCst);
+        let reserved = self
+            .reserved
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |reserved| {
+                (limit == 0 || reserved + additional <= limit)
+                    .then_some(reserved + additional)
+            })
+            .map_err(|reserved| {
+                DataFusionError::ResourcesExhausted(format!(
+                    "Failed to allocate additional {additional} bytes for the task, which \
+                     reserved {reserved} bytes of its memory limit of {limit} bytes"
+                ))
+            })?;
+        if let Err(e) = self.inner.try_grow(reservation, additional) {
+            self.reserved.fetch_sub(additional, Ordering::SeqCst);
+            return Err(e);
+        }
+        self.peak.fetch_max(reserved + additional, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn reserved(&self) -> usize {
+        self.inner.reserved()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::execution::runtime_env::RuntimeConfig;
+
+    #[tokio::test]
+    async fn test_peak_memory() -> Result<()> {
+        let runtime = RuntimeEnv::new(RuntimeConfig::new())?;
+        let meter = TaskResourceMeter::new(&runtime);
+        let task_runtime = meter.runtime();
+
+        let mut reservation =
+            MemoryConsumer::new("test").register(&task_runtime.memory_pool);
+        reservation.try_grow(300)?;
+        reservation.shrink(200);
+        reservation.try_grow(50)?;
+
+        // the executor's pool sees the memory reserved by the task
+        assert_eq!(runtime.memory_pool.reserved(), 150);
+        drop(reservation);
+        assert_eq!(runtime.memory_pool.reserved(), 0);
+
+        let usage = meter.usage(&[]);
+        assert_eq!(usage.peak_memory_bytes, 300);
+        assert_eq!(usage.spilled_bytes, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit() -> Result<()> {
+        let runtime = RuntimeEnv::new(RuntimeConfig::new())?;
+        let meter = TaskResourceMeter::new(&runtime).with_memory_limit(100);
+        let task_runtime = meter.runtime();
+
+        let mut reservation =
+            MemoryConsumer::new("test").register(&task_runtime.memory_pool);
+        reservation.try_grow(60)?;
+        let err = reservation.try_grow(60).unwrap_err();
+        assert!(matches!(err, DataFusionError::ResourcesExhausted(_)));
+        // the failed reservation reserved nothing
+        assert_eq!(runtime.memory_pool.reserved(), 60);
+        reservation.try_grow(40)?;
+        reservation.shrink(50);
+        reservation.try_grow(50)?;
+        assert_eq!(runtime.memory_pool.reserved(), 100);
+        drop(reservation);
+
+        assert_eq!(meter.usage(&[]).peak_memory_bytes, 100);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spill_dir() -> Result<()> {
+        let work_dir = TempDir::new()?;
+        let spill_dir = TempDir::new_in(work_dir.path())?;
+        let spill_path = spill_dir.path().to_path_buf();
+        let meter = TaskResourceMeter::new(&RuntimeEnv::new(RuntimeConfig::new())?)
+            .with_spill_dir(spill_dir)?;
+
+        let file = meter.runtime().disk_manager.create_tmp_file("test")?;
+        assert!(file.path().starts_with(&spill_path));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&spill_path)?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        // the spill dir is removed once the task completes
+        let clone = meter.clone();
+        drop(meter);
+        assert!(spill_path.exists());
+        drop(file);
+        drop(clone);
+        assert!(!spill_path.exists());
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_cpu_time() -> Result<()> {
+        let meter = TaskResourceMeter::new(&RuntimeEnv::new(RuntimeConfig::new())?);
+        let sum = meter
+            .measure(async {
+                let mut sum = 0u64;
+                for i in 0..50_000_000u64 {
+                    sum = sum.wrapping_add(std::hint::black_box(i));
+                }
+                sum
+            })
+            .await;
+        assert!(sum > 0);
+
+        let usage = meter.usage(&[]);
+        assert!(usage.user_cpu_time_us + usage.sys_cpu_time_us > 0);
+        assert!(usage.wall_clock_time_us >= usage.user_cpu_time_us);
+
+        Ok(())
+    }
+}
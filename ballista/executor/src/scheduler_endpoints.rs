@@ -0,0 +1,236 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Connections of an executor to its scheduler and to the standby schedulers it fails over
+//! to when its scheduler is unreachable
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use ballista_core::config::GrpcCompression;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::utils::create_grpc_client_connection;
+use log::{info, warn};
+use tonic::transport::Channel;
+
+/// Backoff between the attempts of an executor to reach a scheduler, doubling from
+/// `init_backoff` up to `max_backoff`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchedulerReconnectConfig {
+    pub init_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for SchedulerReconnectConfig {
+    fn default() -> Self {
+        Self {
+            init_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Exponential backoff between failed attempts, reset once an attempt succeeds
+#[derive(Debug)]
+pub struct Backoff {
+    config: SchedulerReconnectConfig,
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new(config: SchedulerReconnectConfig) -> Self {
+        Self {
+            config,
+            next: config.init_backoff,
+        }
+    }
+
+    /// The time to wait before the next attempt, which doubles the one after it
+    pub fn next_backoff(&mut self) -> Duration {
+        let backoff = self.next;
+        self.next = (self.next * 2).min(self.config.max_backoff);
+        backoff
+    }
+
+    pub fn reset(&mut self) {
+        self.next = self.config.init_backoff;
+    }
+}
+
+/// The URLs of the schedulers of an executor: the scheduler at `host:port` first, then its
+/// standby schedulers given by their `host:port` addresses
+pub fn scheduler_urls(
+    host: &str,
+    port: u16,
+    standby_schedulers: &[String],
+) -> Result<Vec<String>> {
+    let standby_urls = standby_schedulers.iter().map(|address| {
+        let address = address.trim();
+        match address.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(format!("http://{address}"))
+            }
+            _ => Err(BallistaError::General(format!(
+                "Invalid standby scheduler address {address:?}, expected host:port"
+            ))),
+        }
+    });
+    std::iter::once(Ok(format!("http://{host}:{port}")))
+        .chain(standby_urls)
+        .collect()
+}
+
+/// The schedulers an executor registers with. The executor connects to the first one which
+/// is reachable, starting from the one it connected to last, so that it stays with its
+/// scheduler while it is up and fails over to a standby scheduler otherwise.
+#[derive(Debug)]
+pub struct SchedulerEndpoints {
+    urls: Vec<String>,
+    /// Index of the scheduler connected to last
+    current: AtomicUsize,
+    grpc_compression: GrpcCompression,
+    reconnect: SchedulerReconnectConfig,
+}
+
+impl SchedulerEndpoints {
+    pub fn new(
+        urls: Vec<String>,
+        grpc_compression: GrpcCompression,
+        reconnect: SchedulerReconnectConfig,
+    ) -> Self {
+        assert!(!urls.is_empty(), "An executor needs at least one scheduler");
+        Self {
+            urls,
+            current: AtomicUsize::new(0),
+            grpc_compression,
+            reconnect,
+        }
+    }
+
+    /// URL of the scheduler connected to last
+    pub fn current_url(&self) -> &str {
+        &self.urls[self.current.load(Ordering::Acquire)]
+    }
+
+    pub fn reconnect_config(&self) -> SchedulerReconnectConfig {
+        self.reconnect
+    }
+
+    /// Connect to the first reachable scheduler, trying each of them once
+    pub async fn connect_any(&self) -> Result<SchedulerGrpcClient<Channel>> {
+        let start = self.current.load(Ordering::Acquire);
+        let mut errors = vec![];
+        for offset in 0..self.urls.len() {
+            let index = (start + offset) % self.urls.len();
+            let url = &self.urls[index];
+            match create_grpc_client_connection(url.clone()).await {
+                Ok(connection) => {
+                    if index == start {
+                        info!("Connected to scheduler at {url}");
+                    } else {
+                        info!("Failed over to scheduler at {url}");
+                    }
+                    self.current.store(index, Ordering::Release);
+                    let mut scheduler = SchedulerGrpcClient::new(connection);
+                    if let Some(encoding) = self.grpc_compression.encoding() {
+                        scheduler = scheduler
+                            .send_compressed(encoding)
+                            .accept_compressed(encoding);
+                    }
+                    return Ok(scheduler);
+                }
+                Err(e) => {
+                    warn!("Failed to connect to scheduler at {url} ({e})");
+                    errors.push(format!("{url}: {e}"));
+                }
+            }
+        }
+        Err(BallistaError::General(format!(
+            "Could not connect to any scheduler: {}",
+            errors.join(", ")
+        )))
+    }
+
+    /// Connect to the first reachable scheduler, trying all of them again with backoff
+    /// until `timeout` elapsed. Each scheduler is tried once with a zero timeout.
+    pub async fn connect(
+        &self,
+        timeout: Duration,
+    ) -> Result<SchedulerGrpcClient<Channel>> {
+        let start = Instant::now();
+        let mut backoff = Backoff::new(self.reconnect);
+        loop {
+            match self.connect_any().await {
+                Ok(scheduler) => return Ok(scheduler),
+                Err(e) => {
+                    let wait = backoff.next_backoff();
+                    if start.elapsed() + wait > timeout {
+                        return Err(BallistaError::General(format!(
+                            "Timed out attempting to connect to scheduler. {e}"
+                        )));
+                    }
+                    warn!("{e}; retrying in {wait:?} ...");
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(SchedulerReconnectConfig {
+            init_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+        });
+        let backoffs: Vec<_> = (0..4).map(|_| backoff.next_backoff()).collect();
+        assert_eq!(
+            backoffs,
+            [100, 200, 350, 350].map(Duration::from_millis).to_vec()
+        );
+        backoff.reset();
+        assert_eq!(backoff.next_backoff(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_scheduler_urls() -> Result<()> {
+        assert_eq!(
+            scheduler_urls(
+                "scheduler-1",
+                50050,
+                &["scheduler-2:50050".to_owned(), " 10.0.0.3:50051".to_owned()]
+            )?,
+            vec![
+                "http://scheduler-1:50050",
+                "http://scheduler-2:50050",
+                "http://10.0.0.3:50051"
+            ]
+        );
+        assert_eq!(
+            scheduler_urls("localhost", 50050, &[])?,
+            vec!["http://localhost:50050"]
+        );
+        assert!(scheduler_urls("localhost", 50050, &["scheduler-2".to_owned()]).is_err());
+        assert!(scheduler_urls("localhost", 50050, &[":50050".to_owned()]).is_err());
+        Ok(())
+    }
+}
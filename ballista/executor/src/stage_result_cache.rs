@@ -0,0 +1,236 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Cache of the shuffle files written by the tasks of an executor, so that a task re-running
+//! a partition of a stage whose plan and inputs did not change is served the files written
+//! by a previous attempt instead of recomputing them
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+use ballista_core::serde::protobuf::ShuffleWritePartition;
+use ballista_core::serde::scheduler::PartitionId;
+use log::debug;
+use parking_lot::Mutex;
+
+/// Fingerprint of the encoded plan of a task. The plan of a stage reading the output of
+/// other stages holds the locations of its input partitions, so the fingerprint changes when
+/// the inputs are recomputed.
+pub fn plan_fingerprint(plan: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    plan.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Key of a cached result: the partition of the stage and the fingerprint of its plan
+type ResultKey = (PartitionId, u64);
+
+/// Shuffle partitions written by a task, with the size of each file when it was written
+type CachedResult = Vec<(ShuffleWritePartition, u64)>;
+
+#[derive(Debug, Default)]
+struct CacheState {
+    results: HashMap<ResultKey, CachedResult>,
+    /// Keys of the results, the oldest first
+    order: VecDeque<ResultKey>,
+}
+
+/// Results of the tasks which completed on the executor, by stage partition and plan
+/// fingerprint. A result is only served while all its shuffle files still exist with the size
+/// they were written with; the oldest results are forgotten once `max_entries` are cached.
+#[derive(Debug)]
+pub struct StageResultCache {
+    max_entries: usize,
+    state: Mutex<CacheState>,
+}
+
+impl StageResultCache {
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// The shuffle partitions previously written for the partition of a stage with the same
+    /// plan, if their files are intact
+    pub fn get(
+        &self,
+        partition: &PartitionId,
+        fingerprint: u64,
+    ) -> Option<Vec<ShuffleWritePartition>> {
+        let key = (partition.clone(), fingerprint);
+        let mut state = self.state.lock();
+        let result = state.results.get(&key)?;
+        let intact = result.iter().all(|(partition, len)| {
+            std::fs::metadata(&partition.path)
+                .map(|metadata| metadata.len() == *len)
+                .unwrap_or(false)
+        });
+        if intact {
+            Some(
+                result
+                    .iter()
+                    .map(|(partition, _)| partition.clone())
+                    .collect(),
+            )
+        } else {
+            debug!("Shuffle files of the cached result of {partition:?} changed");
+            state.results.remove(&key);
+            state.order.retain(|cached| cached != &key);
+            None
+        }
+    }
+
+    /// Cache the shuffle partitions written for the partition of a stage
+    pub fn insert(
+        &self,
+        partition: PartitionId,
+        fingerprint: u64,
+        partitions: &[ShuffleWritePartition],
+    ) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let result: Option<CachedResult> = partitions
+            .iter()
+            .map(|partition| {
+                let len = std::fs::metadata(&partition.path).ok()?.len();
+                Some((partition.clone(), len))
+            })
+            .collect();
+        // The files may already be gone, such as when the job data was removed
+        let result = match result {
+            Some(result) => result,
+            None => return,
+        };
+        let key = (partition, fingerprint);
+        let mut state = self.state.lock();
+        if state.results.insert(key.clone(), result).is_none() {
+            state.order.push_back(key);
+        }
+        while state.order.len() > self.max_entries {
+            if let Some(oldest) = state.order.pop_front() {
+                state.results.remove(&oldest);
+            }
+        }
+    }
+
+    /// Forget the results of a job, whose data is removed
+    pub fn remove_job(&self, job_id: &str) {
+        let mut state = self.state.lock();
+        state
+            .results
+            .retain(|(partition, _), _| partition.job_id != job_id);
+        state
+            .order
+            .retain(|(partition, _)| partition.job_id != job_id);
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.lock().results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write_partition(
+        dir: &TempDir,
+        name: &str,
+        content: &str,
+    ) -> ShuffleWritePartition {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        ShuffleWritePartition {
+            partition_id: 0,
+            path: path.to_string_lossy().into_owned(),
+            num_batches: 1,
+            num_rows: 1,
+            num_bytes: content.len() as u64,
+            ..Default::default()
+        }
+    }
+
+    fn partition(job_id: &str, partition_id: usize) -> PartitionId {
+        PartitionId {
+            job_id: job_id.to_owned(),
+            stage_id: 1,
+            partition_id,
+        }
+    }
+
+    #[test]
+    fn test_stage_result_cache() {
+        let dir = TempDir::new().unwrap();
+        let cache = StageResultCache::new(2);
+        let fingerprint = plan_fingerprint(b"plan");
+        let written = vec![write_partition(&dir, "data-0.arrow", "batches")];
+
+        cache.insert(partition("job", 0), fingerprint, &written);
+        assert_eq!(cache.get(&partition("job", 0), fingerprint), Some(written));
+
+        // A different plan, such as one reading recomputed inputs, is not served
+        assert_eq!(
+            cache.get(&partition("job", 0), plan_fingerprint(b"other plan")),
+            None
+        );
+        assert_eq!(cache.get(&partition("job", 1), fingerprint), None);
+
+        // Results whose files changed are forgotten
+        std::fs::write(dir.path().join("data-0.arrow"), "truncated").unwrap();
+        assert_eq!(cache.get(&partition("job", 0), fingerprint), None);
+        assert!(cache.is_empty());
+
+        // Results of missing files are not cached
+        let mut missing = write_partition(&dir, "data-1.arrow", "batches");
+        missing.path = dir.path().join("missing").to_string_lossy().into_owned();
+        cache.insert(partition("job", 1), fingerprint, &[missing]);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_stage_result_cache_eviction() {
+        let dir = TempDir::new().unwrap();
+        let cache = StageResultCache::new(2);
+        let written = vec![write_partition(&dir, "data-0.arrow", "batches")];
+
+        for partition_id in 0..3 {
+            cache.insert(partition("job-1", partition_id), 1, &written);
+        }
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&partition("job-1", 0), 1), None);
+        assert!(cache.get(&partition("job-1", 2), 1).is_some());
+
+        cache.insert(partition("job-2", 0), 1, &written);
+        cache.remove_job("job-1");
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&partition("job-2", 0), 1).is_some());
+
+        // Nothing is cached with no entries
+        let cache = StageResultCache::new(0);
+        cache.insert(partition("job-1", 0), 1, &written);
+        assert!(cache.is_empty());
+    }
+}
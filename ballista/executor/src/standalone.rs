@@ -32,6 +32,7 @@ use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 use log::info;
 use std::sync::Arc;
+use std::time::Duration;
 use tempfile::TempDir;
 use tokio::net::TcpListener;
 use tonic::transport::Channel;
@@ -62,9 +63,11 @@ pub async fn new_standalone_executor<
         specification: Some(
             ExecutorSpecification {
                 task_slots: concurrent_tasks as u32,
+                ..Default::default()
             }
             .into(),
         ),
+        pool: String::new(),
     };
     let work_dir = TempDir::new()?
         .into_path()
@@ -96,6 +99,13 @@ pub async fn new_standalone_executor<
             )),
     );
 
-    tokio::spawn(execution_loop::poll_loop(scheduler, executor, codec));
+    tokio::spawn(execution_loop::poll_loop(
+        scheduler,
+        None,
+        executor,
+        codec,
+        0,
+        Duration::ZERO,
+    ));
     Ok(())
 }
@@ -0,0 +1,380 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Capture of the log lines emitted while each task attempt runs, which the scheduler fetches
+//! from the executor so that the logs of a failed task can be read without logging in to the
+//! executor it ran on. The events of a task are the ones emitted within its [task_span], which
+//! includes the log records of the `log` crate; the tasks a task spawns on its own are not
+//! captured. The logs of the tasks of a job with an owner are only served to the owner.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{self, Write};
+use std::sync::Arc;
+
+use ballista_core::serde::protobuf::TaskLogLines;
+use parking_lot::Mutex;
+use tokio::sync::Notify;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Span, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Name of the spans the task attempts run in
+pub const TASK_SPAN: &str = "ballista_task";
+
+/// Span a task attempt runs in, whose events are captured in the log of the task. `owner`
+/// is the user owning the job of the task, empty for none.
+pub fn task_span(job_id: &str, task_id: usize, owner: &str) -> Span {
+    tracing::info_span!(
+        TASK_SPAN,
+        job_id = job_id,
+        task_id = task_id as u64,
+        owner = owner
+    )
+}
+
+/// Job ID and task ID of a task attempt
+type TaskKey = (String, usize);
+
+#[derive(Debug)]
+struct TaskLog {
+    /// User owning the job of the task, empty for none
+    owner: String,
+    lines: VecDeque<String>,
+    /// Number of the first line kept, the first lines of long logs being dropped
+    first_line: u64,
+    finished: bool,
+}
+
+#[derive(Debug, Default)]
+struct TaskLogsState {
+    logs: HashMap<TaskKey, TaskLog>,
+    /// Tasks in the order they started, whose logs are dropped first
+    started: VecDeque<TaskKey>,
+}
+
+/// The last lines logged by the recent task attempts of the executor
+#[derive(Debug)]
+pub struct TaskLogs {
+    max_tasks: usize,
+    max_lines: usize,
+    state: Mutex<TaskLogsState>,
+    /// Woken whenever a task logs a line, finishes or its log is dropped
+    updated: Notify,
+}
+
+impl TaskLogs {
+    /// Keep the logs of the last `max_tasks` tasks, up to their last `max_lines` lines each
+    pub fn new(max_tasks: usize, max_lines: usize) -> Self {
+        Self {
+            max_tasks,
+            max_lines,
+            state: Mutex::new(TaskLogsState::default()),
+            updated: Notify::new(),
+        }
+    }
+
+    /// Start the log of a task attempt of a job owned by `owner`, dropping the logs of the
+    /// oldest tasks
+    pub fn start(&self, job_id: &str, task_id: usize, owner: &str) {
+        let key = (job_id.to_owned(), task_id);
+        let mut state = self.state.lock();
+        let log = TaskLog {
+            owner: owner.to_owned(),
+            lines: VecDeque::new(),
+            first_line: 0,
+            finished: false,
+        };
+        if state.logs.insert(key.clone(), log).is_none() {
+            state.started.push_back(key);
+        }
+        while state.started.len() > self.max_tasks {
+            if let Some(oldest) = state.started.pop_front() {
+                state.logs.remove(&oldest);
+            }
+        }
+        drop(state);
+        self.updated.notify_waiters();
+    }
+
+    pub fn append(&self, job_id: &str, task_id: usize, line: String) {
+        let mut state = self.state.lock();
+        if let Some(log) = state.logs.get_mut(&(job_id.to_owned(), task_id)) {
+            log.lines.push_back(line);
+            if log.lines.len() > self.max_lines {
+                log.lines.pop_front();
+                log.first_line += 1;
+            }
+        }
+        drop(state);
+        self.updated.notify_waiters();
+    }
+
+    pub fn finish(&self, job_id: &str, task_id: usize) {
+        let mut state = self.state.lock();
+        if let Some(log) = state.logs.get_mut(&(job_id.to_owned(), task_id)) {
+            log.finished = true;
+        }
+        drop(state);
+        self.updated.notify_waiters();
+    }
+
+    /// Check that `user`, authenticated by the plugins of the executor, may read the log of a
+    /// task attempt: the logs of the tasks of jobs with an owner are only served to the owner.
+    /// Tasks without a log are left to [Self::lines].
+    pub fn authorize(&self, job_id: &str, task_id: usize, user: Option<&str>) -> bool {
+        let state = self.state.lock();
+        match state.logs.get(&(job_id.to_owned(), task_id)) {
+            Some(log) if !log.owner.is_empty() => user == Some(log.owner.as_str()),
+            _ => true,
+        }
+    }
+
+    /// The lines of a task attempt from line `from_line`, none if the executor has no log of
+    /// the task
+    pub fn lines(
+        &self,
+        job_id: &str,
+        task_id: usize,
+        from_line: u64,
+    ) -> Option<TaskLogLines> {
+        let state = self.state.lock();
+        let log = state.logs.get(&(job_id.to_owned(), task_id))?;
+        let skipped = from_line.saturating_sub(log.first_line) as usize;
+        Some(TaskLogLines {
+            first_line: from_line.max(log.first_line),
+            lines: log.lines.iter().skip(skipped).cloned().collect(),
+            finished: log.finished,
+        })
+    }
+
+    /// Wait for the lines a task attempt logs from line `from_line`, or for the task to
+    /// finish. None if the executor has no log of the task, or dropped it in between.
+    pub async fn next_lines(
+        &self,
+        job_id: &str,
+        task_id: usize,
+        from_line: u64,
+    ) -> Option<TaskLogLines> {
+        loop {
+            // Registered before the lines are read so that no update is missed
+            let updated = self.updated.notified();
+            let lines = self.lines(job_id, task_id, from_line)?;
+            if !lines.lines.is_empty() || lines.finished {
+                return Some(lines);
+            }
+            updated.await;
+        }
+    }
+}
+
+/// Tracing layer capturing the events of the task spans in the [TaskLogs]
+pub struct TaskLogLayer {
+    logs: Arc<TaskLogs>,
+}
+
+impl TaskLogLayer {
+    pub fn new(logs: Arc<TaskLogs>) -> Self {
+        Self { logs }
+    }
+}
+
+/// Task of a task span, kept in the extensions of the span
+struct TaskSpanKey(TaskKey);
+
+impl<S> Layer<S> for TaskLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if attrs.metadata().name() != TASK_SPAN {
+            return;
+        }
+        let mut visitor = TaskSpanVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(job_id), Some(task_id), Some(span)) =
+            (visitor.job_id, visitor.task_id, ctx.span(id))
+        {
+            self.logs.start(&job_id, task_id as usize, &visitor.owner);
+            span.extensions_mut()
+                .insert(TaskSpanKey((job_id, task_id as usize)));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let key = ctx.event_scope(event).and_then(|scope| {
+            scope.into_iter().find_map(|span| {
+                span.extensions()
+                    .get::<TaskSpanKey>()
+                    .map(|key| key.0.clone())
+            })
+        });
+        let (job_id, task_id) = match key {
+            Some(key) => key,
+            None => return,
+        };
+        let mut visitor = EventVisitor::default();
+        event.record(&mut visitor);
+        let metadata = event.metadata();
+        let target = visitor.log_target.as_deref().unwrap_or(metadata.target());
+        let line = format!(
+            "{} {:>5} {target}: {}{}",
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.6fZ"),
+            metadata.level(),
+            visitor.message,
+            visitor.fields
+        );
+        self.logs.append(&job_id, task_id, line);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(TaskSpanKey((job_id, task_id))) = span.extensions().get() {
+                self.logs.finish(job_id, *task_id);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct TaskSpanVisitor {
+    job_id: Option<String>,
+    task_id: Option<u64>,
+    owner: String,
+}
+
+impl Visit for TaskSpanVisitor {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "task_id" {
+            self.task_id = Some(value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "job_id" => self.job_id = Some(value.to_owned()),
+            "owner" => self.owner = value.to_owned(),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Message and fields of an event. The records of the `log` crate carry their target in the
+/// `log.target` field.
+#[derive(Default)]
+struct EventVisitor {
+    message: String,
+    fields: String,
+    log_target: Option<String>,
+}
+
+impl Visit for EventVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_owned(),
+            "log.target" => self.log_target = Some(value.to_owned()),
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {name}={value}");
+            }
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{value:?}"),
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {name}={value:?}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[tokio::test]
+    async fn test_task_logs() {
+        let logs = Arc::new(TaskLogs::new(2, 3));
+        logs.start("job", 1, "");
+        for i in 0..5 {
+            logs.append("job", 1, format!("line {i}"));
+        }
+        // Only the last lines of long logs are kept
+        let lines = logs.lines("job", 1, 0).unwrap();
+        assert_eq!(lines.first_line, 2);
+        assert_eq!(lines.lines, vec!["line 2", "line 3", "line 4"]);
+        assert!(!lines.finished);
+        assert_eq!(logs.lines("job", 1, 4).unwrap().lines, vec!["line 4"]);
+
+        // Following the log waits for the next lines
+        let follower = logs.clone();
+        let next = tokio::spawn(async move { follower.next_lines("job", 1, 5).await });
+        tokio::task::yield_now().await;
+        assert!(!next.is_finished());
+        logs.append("job", 1, "line 5".to_owned());
+        let next = next.await.unwrap().unwrap();
+        assert_eq!(next.first_line, 5);
+        assert_eq!(next.lines, vec!["line 5"]);
+
+        logs.finish("job", 1);
+        assert!(logs.next_lines("job", 1, 6).await.unwrap().finished);
+
+        // The logs of the oldest tasks are dropped first
+        logs.start("job", 2, "");
+        logs.start("job", 3, "");
+        assert!(logs.lines("job", 1, 0).is_none());
+        assert!(logs.next_lines("job", 1, 0).await.is_none());
+        assert!(logs.lines("job", 3, 0).is_some());
+    }
+
+    #[test]
+    fn test_task_log_layer() {
+        let logs = Arc::new(TaskLogs::new(10, 100));
+        let subscriber =
+            tracing_subscriber::registry().with(TaskLogLayer::new(logs.clone()));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not logged by a task");
+            task_span("job", 1, "alice").in_scope(|| {
+                tracing::info!(rows = 3, "wrote batch");
+                tracing::debug_span!("operator").in_scope(|| tracing::warn!("spilling"));
+            });
+        });
+
+        let lines = logs.lines("job", 1, 0).unwrap();
+        assert!(lines.finished);
+        assert_eq!(lines.lines.len(), 2);
+        assert!(lines.lines[0]
+            .ends_with(" INFO ballista_executor::task_logs::tests: wrote batch rows=3"));
+        assert!(lines.lines[1]
+            .ends_with(" WARN ballista_executor::task_logs::tests: spilling"));
+        assert!(logs.lines("job", 2, 0).is_none());
+
+        // Only the owner of the job reads the logs of its tasks
+        assert!(logs.authorize("job", 1, Some("alice")));
+        assert!(!logs.authorize("job", 1, Some("bob")));
+        assert!(!logs.authorize("job", 1, None));
+        logs.start("public", 1, "");
+        assert!(logs.authorize("public", 1, None));
+    }
+}
@@ -0,0 +1,302 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Recovery of the shuffle data left in the work dirs of a restarted executor. The intact
+//! shuffle files of active jobs are reported to the scheduler, which reads them from the
+//! restarted executor instead of recomputing them, and the data of the other jobs is removed.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use ballista_core::error::Result;
+use ballista_core::execution_plans::ShuffleCodec;
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::protobuf::{ExecutorRegistration, RecoverExecutorDataParams};
+use log::{info, warn};
+use tonic::transport::Channel;
+
+use crate::work_dirs::spill_dir;
+
+/// File of a work dir holding the ID of the executor which last used it
+pub const EXECUTOR_ID_FILE: &str = ".executor_id";
+
+/// The ID of the executor which last used the work dir, none if it is unknown
+pub fn previous_executor_id(work_dir: &str) -> Option<String> {
+    let id = std::fs::read_to_string(Path::new(work_dir).join(EXECUTOR_ID_FILE)).ok()?;
+    let id = id.trim();
+    (!id.is_empty()).then(|| id.to_owned())
+}
+
+/// Record the ID of the executor using the work dir, read back once it restarts
+pub fn save_executor_id(work_dir: &str, executor_id: &str) -> Result<()> {
+    std::fs::write(Path::new(work_dir).join(EXECUTOR_ID_FILE), executor_id)?;
+    Ok(())
+}
+
+/// Shuffle data left in the work dirs of an executor
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct WorkDirData {
+    /// Jobs with a directory in any of the work dirs
+    pub job_ids: Vec<String>,
+    /// Paths of the intact shuffle files
+    pub shuffle_files: Vec<String>,
+}
+
+/// Scan the job dirs of the work dirs for shuffle files. The shuffle files which were not
+/// completely written, such as the ones a task was writing when the executor stopped, are
+/// removed.
+pub fn scan_work_dirs(work_dirs: &[String]) -> Result<WorkDirData> {
+    let mut data = WorkDirData::default();
+    for work_dir in work_dirs {
+        let spill_dir = PathBuf::from(spill_dir(work_dir));
+        let entries = match std::fs::read_dir(work_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if !path.is_dir() || path == spill_dir {
+                continue;
+            }
+            if let Some(job_id) = path.file_name().and_then(|name| name.to_str()) {
+                if !data.job_ids.iter().any(|id| id == job_id) {
+                    data.job_ids.push(job_id.to_owned());
+                }
+                scan_shuffle_files(&path, &mut data.shuffle_files)?;
+            }
+        }
+    }
+    data.shuffle_files.sort();
+    Ok(data)
+}
+
+/// Collect the intact shuffle files under `dir`, removing the corrupt ones
+fn scan_shuffle_files(dir: &Path, shuffle_files: &mut Vec<String>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            scan_shuffle_files(&path, shuffle_files)?;
+            continue;
+        }
+        let is_shuffle_file = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("arrow" | "arrows" | "rows")
+        );
+        if !is_shuffle_file {
+            continue;
+        }
+        if is_intact(&path) {
+            shuffle_files.push(path.to_string_lossy().into_owned());
+        } else {
+            warn!("Removing corrupt shuffle file {path:?}");
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Length of the end of the shuffle files checked by [is_intact]
+const TRAILER_LEN: usize = 10;
+
+/// Magic bytes closing the Arrow IPC files
+const IPC_FILE_MAGIC: &[u8] = b"ARROW1";
+
+/// End of stream marker closing the Arrow IPC streams
+const IPC_STREAM_END: [u8; 8] = [0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0];
+
+/// Whether a shuffle file was completely written, from its size and the end written by the
+/// writer of its format when it finishes, rather than by reading all its batches, which
+/// would read all the shuffle data left in the work dirs when the executor starts
+fn is_intact(path: &Path) -> bool {
+    let mut trailer = [0u8; TRAILER_LEN];
+    let len = match read_trailer(path, &mut trailer) {
+        Ok(len) => len,
+        Err(_) => return false,
+    };
+    match ShuffleCodec::from_path(path) {
+        // The footer, whose length precedes the closing magic, follows the opening magic
+        // and the batches
+        ShuffleCodec::ArrowIpc => {
+            let footer_len =
+                i32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+            &trailer[4..] == IPC_FILE_MAGIC
+                && footer_len > 0
+                && footer_len as u64 + (TRAILER_LEN + IPC_FILE_MAGIC.len()) as u64 <= len
+        }
+        ShuffleCodec::ArrowIpcStream => trailer[2..] == IPC_STREAM_END,
+        // A batch of 0 rows marks the end of the file
+        ShuffleCodec::Row => trailer[6..] == [0; 4],
+    }
+}
+
+/// Read the last bytes of a file into `trailer`, returns the length of the file
+fn read_trailer(path: &Path, trailer: &mut [u8; TRAILER_LEN]) -> std::io::Result<u64> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if len < TRAILER_LEN as u64 {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    file.seek(SeekFrom::End(-(TRAILER_LEN as i64)))?;
+    file.read_exact(trailer)?;
+    Ok(len)
+}
+
+/// Remove the directories of the jobs from all the work dirs
+pub fn remove_job_dirs(work_dirs: &[String], job_ids: &[String]) {
+    for job_id in job_ids {
+        for work_dir in work_dirs {
+            let path = Path::new(work_dir).join(job_id);
+            if path.is_dir() {
+                if let Err(e) = std::fs::remove_dir_all(&path) {
+                    warn!("Failed to remove the directory {path:?} due to {e}");
+                }
+            }
+        }
+    }
+}
+
+/// Report the shuffle data left in the work dirs by the executor `previous_executor_id` to
+/// the scheduler and remove the data of the jobs which are no longer active
+pub async fn recover_work_dirs(
+    scheduler: &mut SchedulerGrpcClient<Channel>,
+    metadata: ExecutorRegistration,
+    previous_executor_id: Option<String>,
+    work_dirs: Vec<String>,
+) -> Result<()> {
+    let scan_dirs = work_dirs.clone();
+    let data = tokio::task::spawn_blocking(move || scan_work_dirs(&scan_dirs)).await??;
+    if data.job_ids.is_empty() {
+        return Ok(());
+    }
+    info!(
+        "Found {} shuffle files of {} jobs in the work dirs",
+        data.shuffle_files.len(),
+        data.job_ids.len()
+    );
+
+    let orphaned_job_ids = scheduler
+        .recover_executor_data(RecoverExecutorDataParams {
+            metadata: Some(metadata),
+            previous_executor_id: previous_executor_id.unwrap_or_default(),
+            job_ids: data.job_ids,
+            shuffle_files: data.shuffle_files,
+        })
+        .await?
+        .into_inner()
+        .orphaned_job_ids;
+    if !orphaned_job_ids.is_empty() {
+        info!("Removing the data of inactive jobs {orphaned_job_ids:?}");
+        tokio::task::spawn_blocking(move || {
+            remove_job_dirs(&work_dirs, &orphaned_job_ids)
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::execution_plans::ShuffleFileWriter;
+    use datafusion::arrow::array::Int32Array;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn write_shuffle_file(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let schema = Schema::new(vec![Field::new("a", DataType::Int32, false)]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema.clone()),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap();
+        let mut writer =
+            ShuffleFileWriter::try_new(ShuffleCodec::from_path(path), path, &schema)
+                .unwrap();
+        writer.write(&batch).unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_executor_id_file() -> Result<()> {
+        let dir = TempDir::new()?;
+        let work_dir = dir.path().to_str().unwrap();
+        assert_eq!(previous_executor_id(work_dir), None);
+
+        save_executor_id(work_dir, "executor-1")?;
+        assert_eq!(
+            previous_executor_id(work_dir),
+            Some("executor-1".to_owned())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_work_dirs() -> Result<()> {
+        let dir_1 = TempDir::new()?;
+        let dir_2 = TempDir::new()?;
+        let work_dirs = vec![
+            dir_1.path().to_str().unwrap().to_owned(),
+            dir_2.path().to_str().unwrap().to_owned(),
+        ];
+
+        let intact = [
+            dir_1.path().join("job-1/1/0/data.arrow"),
+            dir_1.path().join("job-1/1/1/data-0.rows"),
+            dir_2.path().join("job-1/1/2/data.arrows"),
+        ];
+        for path in &intact {
+            write_shuffle_file(path);
+        }
+        // Files tasks were writing when the executor stopped
+        let truncated = [
+            dir_2.path().join("job-2/3/0/data.arrow"),
+            dir_2.path().join("job-2/3/1/data.arrows"),
+            dir_2.path().join("job-2/3/2/data-0.rows"),
+        ];
+        for path in &truncated {
+            write_shuffle_file(path);
+            let len = std::fs::metadata(path)?.len();
+            File::options().write(true).open(path)?.set_len(len - 3)?;
+        }
+        // Spill files are not shuffle data
+        write_shuffle_file(&dir_1.path().join("spill/data.arrow"));
+        save_executor_id(&work_dirs[0], "executor-1")?;
+
+        let data = scan_work_dirs(&work_dirs)?;
+        let mut job_ids = data.job_ids.clone();
+        job_ids.sort();
+        assert_eq!(job_ids, vec!["job-1", "job-2"]);
+        let mut expected: Vec<String> = intact
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        expected.sort();
+        assert_eq!(data.shuffle_files, expected);
+        assert!(truncated.iter().all(|path| !path.exists()));
+
+        remove_job_dirs(&work_dirs, &["job-1".to_owned()]);
+        assert!(!dir_1.path().join("job-1").exists());
+        assert!(!dir_2.path().join("job-1").exists());
+        assert!(dir_2.path().join("job-2").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Work directories of an executor, typically one per local disk, among which the shuffle
+//! and spill files of its tasks are spread
+
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ballista_core::config::WorkDirSelection;
+
+/// Directories on separate disks, one of which is chosen for each task, such as the work dirs
+/// each task writes its shuffle files to or the spill dirs each task spills to
+#[derive(Debug)]
+pub struct WorkDirs {
+    dirs: Vec<String>,
+    selection: WorkDirSelection,
+    /// Index of the work dir chosen next in round robin
+    next: AtomicUsize,
+}
+
+impl WorkDirs {
+    pub fn new(dirs: Vec<String>, selection: WorkDirSelection) -> Self {
+        assert!(!dirs.is_empty(), "An executor needs at least one work dir");
+        Self {
+            dirs,
+            selection,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// The directories of a comma separated list of work dirs
+    pub fn parse(work_dirs: &str) -> Vec<String> {
+        work_dirs
+            .split(',')
+            .map(str::trim)
+            .filter(|dir| !dir.is_empty())
+            .map(str::to_owned)
+            .collect()
+    }
+
+    pub fn dirs(&self) -> &[String] {
+        &self.dirs
+    }
+
+    pub fn selection(&self) -> WorkDirSelection {
+        self.selection
+    }
+
+    /// The spill subdirectory of each work dir
+    pub fn spill_dirs(&self) -> Vec<String> {
+        self.dirs.iter().map(|dir| spill_dir(dir)).collect()
+    }
+
+    /// Choose the directory the next task writes to
+    pub fn select(&self) -> &str {
+        if self.dirs.len() == 1 {
+            return &self.dirs[0];
+        }
+        if self.selection == WorkDirSelection::FreeSpace {
+            let most_free = self
+                .dirs
+                .iter()
+                .filter_map(|dir| Some((dir, disk_space(dir)?.available_bytes)))
+                .max_by_key(|(_, available)| *available);
+            if let Some((dir, _)) = most_free {
+                return dir;
+            }
+        }
+        // Also used when the free space of the work dirs is unknown
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        &self.dirs[next % self.dirs.len()]
+    }
+
+    /// Disk space of the directories, none if it is unknown. The bytes available are the
+    /// ones of the directory the next task may write to with the least free space: any of
+    /// them in round robin, the one with the most free space otherwise.
+    pub fn disk_space(&self) -> Option<DiskSpace> {
+        let spaces: Vec<DiskSpace> =
+            self.dirs.iter().filter_map(|dir| disk_space(dir)).collect();
+        let available = spaces.iter().map(|space| space.available_bytes);
+        let available_bytes = match self.selection {
+            WorkDirSelection::RoundRobin => available.min()?,
+            WorkDirSelection::FreeSpace => available.max()?,
+        };
+        Some(DiskSpace {
+            available_bytes,
+            total_bytes: spaces.iter().map(|space| space.total_bytes).sum(),
+        })
+    }
+}
+
+/// Free and total bytes of a file system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskSpace {
+    pub available_bytes: u64,
+    pub total_bytes: u64,
+}
+
+/// The directory of the spill dirs of the tasks writing to a work dir
+pub fn spill_dir(work_dir: &str) -> String {
+    Path::new(work_dir)
+        .join("spill")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Disk space of the file system of `dir`, the bytes available being the ones available to
+/// the executor
+#[cfg(unix)]
+fn disk_space(dir: &str) -> Option<DiskSpace> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(Path::new(dir).as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(DiskSpace {
+        available_bytes: stat.f_bavail as u64 * stat.f_frsize as u64,
+        total_bytes: stat.f_blocks as u64 * stat.f_frsize as u64,
+    })
+}
+
+#[cfg(not(unix))]
+fn disk_space(_dir: &str) -> Option<DiskSpace> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_work_dirs() {
+        assert_eq!(
+            WorkDirs::parse("/mnt/nvme0/ballista, /mnt/nvme1/ballista,"),
+            vec!["/mnt/nvme0/ballista", "/mnt/nvme1/ballista"]
+        );
+
+        let work_dirs = WorkDirs::new(
+            vec!["/mnt/nvme0".to_owned(), "/mnt/nvme1".to_owned()],
+            WorkDirSelection::RoundRobin,
+        );
+        let selected: Vec<_> = (0..3).map(|_| work_dirs.select().to_owned()).collect();
+        assert_eq!(selected, vec!["/mnt/nvme0", "/mnt/nvme1", "/mnt/nvme0"]);
+        assert_eq!(
+            work_dirs.spill_dirs(),
+            vec!["/mnt/nvme0/spill", "/mnt/nvme1/spill"]
+        );
+    }
+
+    #[test]
+    fn test_free_space_selection() {
+        let dir = TempDir::new().unwrap();
+        let existing = dir.path().to_string_lossy().into_owned();
+        // The free space of a missing dir is unknown, so the existing one is chosen
+        let work_dirs = WorkDirs::new(
+            vec![
+                dir.path().join("missing").to_string_lossy().into_owned(),
+                existing,
+            ],
+            WorkDirSelection::FreeSpace,
+        );
+        if cfg!(unix) {
+            for _ in 0..3 {
+                assert_eq!(work_dirs.select(), work_dirs.dirs()[1]);
+            }
+            let space = work_dirs.disk_space().unwrap();
+            assert!(space.available_bytes <= space.total_bytes);
+        } else {
+            assert_eq!(work_dirs.disk_space(), None);
+        }
+    }
+}
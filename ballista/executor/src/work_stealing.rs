@@ -0,0 +1,477 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Work stealing between the task slots of an executor: the slots left idle once their
+//! tasks complete scan the remaining files of the Parquet scans of the running tasks, so
+//! that a task with many files left does not run alone while the other slots wait for the
+//! scheduler.
+
+use std::any::Any;
+use std::collections::VecDeque;
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::task::{Context, Poll};
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::datasource::listing::PartitionedFile;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_plan::expressions::PhysicalSortExpr;
+use datafusion::physical_plan::file_format::ParquetExec;
+use datafusion::physical_plan::metrics::{
+    Count, ExecutionPlanMetricsSet, MetricBuilder, MetricsSet,
+};
+use datafusion::physical_plan::{
+    DisplayFormatType, ExecutionPlan, Partitioning, RecordBatchStream,
+    SendableRecordBatchStream, Statistics,
+};
+use futures::{Stream, StreamExt};
+use parking_lot::Mutex;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Metric of the number of files of a task scanned by the other task slots
+pub const STOLEN_FILES_METRIC: &str = "stolen_files";
+
+/// Wrap the Parquet scans of a plan in a [WorkStealingScanExec] sharing their remaining
+/// files with the idle task slots of `pool`. Returns the plan unchanged without a pool.
+///
+/// Scans whose files are sorted or which have a limit are left as they are, as the files of
+/// their partition have to be read in order.
+pub fn with_work_stealing(
+    plan: Arc<dyn ExecutionPlan>,
+    pool: Option<&Arc<WorkStealingPool>>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let pool = match pool {
+        Some(pool) => pool,
+        None => return Ok(plan),
+    };
+    plan.transform_up(&|plan| {
+        let shareable = plan
+            .as_any()
+            .downcast_ref::<ParquetExec>()
+            .map(|scan| {
+                scan.output_ordering().is_none() && scan.base_config().limit.is_none()
+            })
+            .unwrap_or(false);
+        if shareable {
+            Ok(Transformed::Yes(Arc::new(WorkStealingScanExec::new(
+                plan,
+                pool.clone(),
+            ))))
+        } else {
+            Ok(Transformed::No(plan))
+        }
+    })
+}
+
+/// Task slots of an executor. The running tasks are tracked by the executor, and the slots
+/// which no task occupies help the running scans until a new task is started.
+pub struct WorkStealingPool {
+    slots: usize,
+    running_tasks: AtomicUsize,
+    /// Slots scanning the files of other tasks
+    helpers: AtomicUsize,
+    scans: Mutex<Vec<Weak<FileScanWork>>>,
+}
+
+impl fmt::Debug for WorkStealingPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkStealingPool")
+            .field("slots", &self.slots)
+            .field("running_tasks", &self.running_tasks)
+            .field("helpers", &self.helpers)
+            .finish()
+    }
+}
+
+impl WorkStealingPool {
+    pub fn new(slots: usize) -> Self {
+        Self {
+            slots,
+            running_tasks: AtomicUsize::new(0),
+            helpers: AtomicUsize::new(0),
+            scans: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn task_started(&self) {
+        self.running_tasks.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Record that a task completed, its slot helping the running scans
+    pub fn task_finished(self: &Arc<Self>) {
+        self.running_tasks.fetch_sub(1, Ordering::SeqCst);
+        self.help();
+    }
+
+    /// Number of slots scanning the files of other tasks
+    pub fn helpers(&self) -> usize {
+        self.helpers.load(Ordering::SeqCst)
+    }
+
+    fn register(self: &Arc<Self>, scan: &Arc<FileScanWork>) {
+        self.scans.lock().push(Arc::downgrade(scan));
+        self.help();
+    }
+
+    /// Whether a task was started in a slot occupied by a helper
+    fn oversubscribed(&self) -> bool {
+        self.running_tasks.load(Ordering::SeqCst) + self.helpers() > self.slots
+    }
+
+    /// Start a helper in each idle slot while the running scans have files left
+    fn help(self: &Arc<Self>) {
+        loop {
+            let reserved = self
+                .helpers
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |helpers| {
+                    let running_tasks = self.running_tasks.load(Ordering::SeqCst);
+                    (running_tasks + helpers < self.slots).then_some(helpers + 1)
+                })
+                .is_ok();
+            if !reserved {
+                return;
+            }
+            match self.next_scan() {
+                Some(scan) => {
+                    let pool = self.clone();
+                    tokio::spawn(async move { pool.run_helper(scan).await });
+                }
+                None => {
+                    self.helpers.fetch_sub(1, Ordering::SeqCst);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// The running scan with the most files left
+    fn next_scan(&self) -> Option<Arc<FileScanWork>> {
+        let mut scans = self.scans.lock();
+        scans.retain(|scan| scan.strong_count() > 0);
+        scans
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|scan| scan.pending() > 0)
+            .max_by_key(|scan| scan.pending())
+    }
+
+    /// Scan the files of the running scans one at a time, until none is left or a task
+    /// needs the slot
+    async fn run_helper(self: Arc<Self>, mut scan: Arc<FileScanWork>) {
+        loop {
+            if let Some(file) = scan.pop() {
+                scan.stolen_files.add(1);
+                scan.scan_file(file).await;
+            }
+            if self.oversubscribed() {
+                break;
+            }
+            scan = match self.next_scan() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+        // the stream of the task ends once its scans released their sender
+        drop(scan);
+        self.helpers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Files of a partition of a Parquet scan left to scan, by its task or by idle slots
+struct FileScanWork {
+    scan: Arc<dyn ExecutionPlan>,
+    files: Mutex<VecDeque<PartitionedFile>>,
+    context: Arc<TaskContext>,
+    batches: mpsc::Sender<Result<RecordBatch>>,
+    stolen_files: Count,
+}
+
+impl FileScanWork {
+    fn pending(&self) -> usize {
+        self.files.lock().len()
+    }
+
+    fn pop(&self) -> Option<PartitionedFile> {
+        self.files.lock().pop_front()
+    }
+
+    /// Scan a file, sending its batches to the task. Returns false if the scan failed or
+    /// the task no longer reads the batches, in which case the remaining files are dropped.
+    async fn scan_file(&self, file: PartitionedFile) -> bool {
+        let stream = single_file_scan(self.scan.as_ref(), file)
+            .and_then(|plan| plan.execute(0, self.context.clone()));
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                self.files.lock().clear();
+                let _ = self.batches.send(Err(e)).await;
+                return false;
+            }
+        };
+        while let Some(batch) = stream.next().await {
+            let failed = batch.is_err();
+            if self.batches.send(batch).await.is_err() || failed {
+                self.files.lock().clear();
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The Parquet scan of a single file, with the projection and predicate of `scan`
+fn single_file_scan(
+    scan: &dyn ExecutionPlan,
+    file: PartitionedFile,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let scan = scan.as_any().downcast_ref::<ParquetExec>().ok_or_else(|| {
+        DataFusionError::Internal("Work stealing requires a ParquetExec".to_owned())
+    })?;
+    let mut config = scan.base_config().clone();
+    config.file_groups = vec![vec![file]];
+    Ok(Arc::new(ParquetExec::new(
+        config,
+        scan.predicate().cloned(),
+        None,
+    )))
+}
+
+/// Parquet scan whose partitions share the files they did not scan yet with the idle task
+/// slots of the executor. The batches of a partition are those of its files, in any order.
+#[derive(Debug)]
+pub struct WorkStealingScanExec {
+    input: Arc<dyn ExecutionPlan>,
+    pool: Arc<WorkStealingPool>,
+    metrics: ExecutionPlanMetricsSet,
+}
+
+impl WorkStealingScanExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, pool: Arc<WorkStealingPool>) -> Self {
+        Self {
+            input,
+            pool,
+            metrics: ExecutionPlanMetricsSet::new(),
+        }
+    }
+}
+
+impl ExecutionPlan for WorkStealingScanExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(WorkStealingScanExec::new(
+            children[0].clone(),
+            self.pool.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let files = self
+            .input
+            .as_any()
+            .downcast_ref::<ParquetExec>()
+            .and_then(|scan| scan.base_config().file_groups.get(partition).cloned())
+            .unwrap_or_default();
+        // nothing to share
+        if files.len() < 2 {
+            return self.input.execute(partition, context);
+        }
+
+        let (tx, rx) = mpsc::channel(2);
+        let work = Arc::new(FileScanWork {
+            scan: self.input.clone(),
+            files: Mutex::new(files.into()),
+            context,
+            batches: tx,
+            stolen_files: MetricBuilder::new(&self.metrics)
+                .counter(STOLEN_FILES_METRIC, partition),
+        });
+        self.pool.register(&work);
+        let task = tokio::spawn(async move {
+            while let Some(file) = work.pop() {
+                if !work.scan_file(file).await {
+                    break;
+                }
+            }
+        });
+        Ok(Box::pin(WorkStealingStream {
+            schema: self.schema(),
+            batches: rx,
+            task,
+            output_rows: MetricBuilder::new(&self.metrics).output_rows(partition),
+        }))
+    }
+
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(f, "WorkStealingScanExec: slots={}", self.pool.slots)
+            }
+        }
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+}
+
+/// Stream of the batches of the files of a partition, scanned by its task and the slots
+/// helping it. Scanning stops when it is dropped.
+struct WorkStealingStream {
+    schema: SchemaRef,
+    batches: mpsc::Receiver<Result<RecordBatch>>,
+    task: JoinHandle<()>,
+    output_rows: Count,
+}
+
+impl Stream for WorkStealingStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let poll = self.batches.poll_recv(cx);
+        if let Poll::Ready(Some(Ok(batch))) = &poll {
+            self.output_rows.add(batch.num_rows());
+        }
+        poll
+    }
+}
+
+impl RecordBatchStream for WorkStealingStream {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl Drop for WorkStealingStream {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::physical_plan::common::collect;
+    use datafusion::physical_plan::displayable;
+    use datafusion::prelude::{ParquetReadOptions, SessionConfig, SessionContext};
+    use tempfile::TempDir;
+
+    /// Plan scanning a single partition of three copies of a Parquet file of 8 rows
+    async fn test_plan(
+        ctx: &SessionContext,
+    ) -> Result<(Arc<dyn ExecutionPlan>, TempDir)> {
+        let dir = TempDir::new()?;
+        for i in 0..3 {
+            std::fs::copy(
+                "../client/testdata/alltypes_plain.parquet",
+                dir.path().join(format!("part-{i}.parquet")),
+            )?;
+        }
+        ctx.register_parquet(
+            "t",
+            dir.path().to_str().unwrap(),
+            ParquetReadOptions::default(),
+        )
+        .await?;
+        let plan = ctx
+            .sql("SELECT id FROM t")
+            .await?
+            .create_physical_plan()
+            .await?;
+        Ok((plan, dir))
+    }
+
+    #[tokio::test]
+    async fn test_work_stealing_scan() -> Result<()> {
+        let ctx =
+            SessionContext::with_config(SessionConfig::new().with_target_partitions(1));
+        let (plan, _dir) = test_plan(&ctx).await?;
+        let describe = |plan: &Arc<dyn ExecutionPlan>| {
+            format!("{}", displayable(plan.as_ref()).indent())
+        };
+        let unchanged = with_work_stealing(plan.clone(), None)?;
+        assert!(!describe(&unchanged).contains("WorkStealingScanExec"));
+
+        let pool = Arc::new(WorkStealingPool::new(4));
+        pool.task_started();
+        let plan = with_work_stealing(plan, Some(&pool))?;
+        assert!(describe(&plan).contains("WorkStealingScanExec: slots=4"));
+
+        let batches = collect(plan.execute(0, ctx.task_ctx())?).await?;
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 24);
+        pool.task_finished();
+        assert_eq!(pool.running_tasks.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_helpers_leave_slots_to_tasks() {
+        let pool = Arc::new(WorkStealingPool::new(2));
+        pool.task_started();
+        pool.task_started();
+        // no scan to help
+        pool.task_finished();
+        assert_eq!(pool.helpers(), 0);
+        assert!(!pool.oversubscribed());
+
+        pool.helpers.fetch_add(1, Ordering::SeqCst);
+        pool.task_started();
+        assert!(pool.oversubscribed());
+    }
+}
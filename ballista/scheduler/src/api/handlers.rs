@@ -10,21 +10,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::plugin::AdminAction;
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::SchedulerServer;
 use crate::state::execution_graph::ExecutionStage;
 use crate::state::execution_graph_dot::ExecutionGraphDot;
 use ballista_core::serde::protobuf::job_status::Status;
+use ballista_core::serde::protobuf::{CgroupStats, TaskAttemptState, TaskResourceUsage};
 use ballista_core::BALLISTA_VERSION;
 use datafusion::physical_plan::metrics::{MetricValue, MetricsSet, Time};
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
+use futures::StreamExt;
 use graphviz_rust::cmd::{CommandArg, Format};
 use graphviz_rust::exec;
 use graphviz_rust::printer::PrinterContext;
 use http::header::CONTENT_TYPE;
+use http::HeaderMap;
 
+use std::collections::HashMap;
 use std::time::Duration;
+use tonic::metadata::MetadataMap;
 use warp::Rejection;
 
 #[derive(Debug, serde::Serialize)]
@@ -44,6 +50,47 @@ pub struct ExecutorMetaResponse {
     pub host: String,
     pub port: u16,
     pub last_seen: u128,
+    pub decommissioning: bool,
+    /// Usage of the cgroup the executor runs in, if it runs in one of its own
+    pub cgroup: Option<CgroupStatsResponse>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct CgroupStatsResponse {
+    pub cpu_usage_us: u64,
+    pub cpu_throttled_us: u64,
+    /// Share of the enforcement periods of the CPU limit in which the executor was throttled
+    pub cpu_throttled_ratio: f64,
+    pub memory_bytes: u64,
+    pub memory_max_bytes: u64,
+    pub memory_oom_kills: u64,
+}
+
+impl From<CgroupStats> for CgroupStatsResponse {
+    fn from(stats: CgroupStats) -> Self {
+        Self {
+            cpu_usage_us: stats.cpu_usage_us,
+            cpu_throttled_us: stats.cpu_throttled_us,
+            cpu_throttled_ratio: if stats.cpu_periods > 0 {
+                stats.cpu_throttled_periods as f64 / stats.cpu_periods as f64
+            } else {
+                0.0
+            },
+            memory_bytes: stats.memory_bytes,
+            memory_max_bytes: stats.memory_max_bytes,
+            memory_oom_kills: stats.memory_oom_kills,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct PatchExecutorResponse {
+    pub task_slot_oversubscription: f64,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DecommissionExecutorResponse {
+    pub active_jobs: usize,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -54,6 +101,7 @@ pub struct JobResponse {
     pub num_stages: usize,
     pub completed_stages: usize,
     pub percent_complete: u8,
+    pub labels: HashMap<String, String>,
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -61,6 +109,30 @@ struct CancelJobResponse {
     pub cancelled: bool,
 }
 
+#[derive(Debug, serde::Serialize)]
+struct ClearQuarantineResponse {
+    pub cleared: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct JobGroupResponse {
+    pub group_id: String,
+    pub job_ids: Vec<String>,
+    pub queued_jobs: u32,
+    pub running_jobs: u32,
+    pub successful_jobs: u32,
+    pub failed_jobs: u32,
+    pub cancelled_jobs: u32,
+    pub num_stages: u32,
+    pub completed_stages: u32,
+    pub percent_complete: u8,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct CancelJobGroupResponse {
+    pub job_ids: Vec<String>,
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct QueryStageSummary {
     pub stage_id: String,
@@ -68,6 +140,70 @@ pub struct QueryStageSummary {
     pub input_rows: usize,
     pub output_rows: usize,
     pub elapsed_compute: String,
+    pub user_cpu_time: String,
+    pub sys_cpu_time: String,
+    pub wall_clock_time: String,
+    pub peak_memory_bytes: u64,
+    pub spilled_bytes: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskAttemptsResponse {
+    pub attempts: Vec<TaskAttemptSummary>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskAttemptSummary {
+    pub task_id: u32,
+    pub stage_id: u32,
+    pub stage_attempt_num: u32,
+    pub partition_id: u32,
+    pub executor_id: String,
+    pub state: String,
+    pub launch_time: u64,
+    pub start_exec_time: u64,
+    pub end_exec_time: u64,
+    pub finish_time: u64,
+    pub error: String,
+    pub retryable: bool,
+    pub input_rows: usize,
+    pub output_rows: usize,
+    pub elapsed_compute: String,
+    pub user_cpu_time: String,
+    pub sys_cpu_time: String,
+    pub wall_clock_time: String,
+    pub peak_memory_bytes: u64,
+    pub spilled_bytes: u64,
+    pub batch_size: u64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskLogsResponse {
+    /// Number of the first line returned, the first lines of long logs being dropped by the
+    /// executor
+    pub first_line: u64,
+    pub lines: Vec<String>,
+    /// Whether the task finished, after which it logs no more lines
+    pub finished: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskProgressResponse {
+    pub tasks: Vec<TaskProgressSummary>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct TaskProgressSummary {
+    pub task_id: u32,
+    pub stage_id: u32,
+    pub partition_id: u32,
+    pub executor_id: String,
+    pub operator: String,
+    pub output_rows: u64,
+    pub output_bytes: u64,
+    pub elapsed_ms: u64,
+    pub idle_ms: u64,
+    pub stalled: bool,
 }
 
 /// Return current scheduler state
@@ -93,6 +229,11 @@ pub(crate) async fn get_executors<T: AsLogicalPlan, U: AsExecutionPlan>(
         .unwrap_or_default()
         .into_iter()
         .map(|(metadata, duration)| ExecutorMetaResponse {
+            decommissioning: state.executor_manager.is_decommissioning(&metadata.id),
+            cgroup: state
+                .executor_manager
+                .cgroup_stats(&metadata.id)
+                .map(CgroupStatsResponse::from),
             id: metadata.id,
             host: metadata.host,
             port: metadata.port,
@@ -103,12 +244,92 @@ pub(crate) async fn get_executors<T: AsLogicalPlan, U: AsExecutionPlan>(
     Ok(warp::reply::json(&executors))
 }
 
+/// Change the settings of an executor, currently its task slot oversubscription, e.g.
+/// `/api/executor/{executor_id}?task_slot_oversubscription=1.5`
+pub(crate) async fn patch_executor<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    executor_id: String,
+    query: HashMap<String, String>,
+) -> Result<impl warp::Reply, Rejection> {
+    let task_slot_oversubscription: f64 = query
+        .get("task_slot_oversubscription")
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(warp::reject)?;
+    if task_slot_oversubscription < 1.0 {
+        return Err(warp::reject());
+    }
+
+    // 404 if the executor doesn't exist
+    data_server
+        .state
+        .executor_manager
+        .get_executor_metadata(&executor_id)
+        .await
+        .map_err(|_| warp::reject())?;
+
+    data_server
+        .set_task_slot_oversubscription(
+            &executor_id,
+            (task_slot_oversubscription * 100.0).round() as u32,
+        )
+        .await
+        .map_err(|_| warp::reject())?;
+
+    Ok(warp::reply::json(&PatchExecutorResponse {
+        task_slot_oversubscription,
+    }))
+}
+
+/// Decommission an executor, removing it once the jobs using it completed or after an
+/// optional timeout in seconds, e.g. `/api/executor/{executor_id}?timeout_seconds=600`. Only
+/// the clients the plugins authorize to decommission executors may do so.
+pub(crate) async fn decommission_executor<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    executor_id: String,
+    query: HashMap<String, String>,
+    headers: HeaderMap,
+) -> Result<impl warp::Reply, Rejection> {
+    data_server
+        .authorize_admin(
+            &MetadataMap::from_headers(headers),
+            AdminAction::DecommissionExecutor,
+        )
+        .map_err(|_| warp::reject())?;
+    let timeout_seconds: u64 = match query.get("timeout_seconds") {
+        Some(value) => value.parse().map_err(|_| warp::reject())?,
+        None => 0,
+    };
+    let timeout = (timeout_seconds > 0).then(|| Duration::from_secs(timeout_seconds));
+
+    // 404 if the executor doesn't exist
+    let active_jobs = data_server
+        .decommission(&executor_id, timeout)
+        .await
+        .map_err(|_| warp::reject())?;
+
+    Ok(warp::reply::json(&DecommissionExecutorResponse {
+        active_jobs,
+    }))
+}
+
+/// Prefix of the query parameters filtering the listed jobs by label, e.g.
+/// `/api/jobs?label.team=analytics`
+const LABEL_QUERY_PREFIX: &str = "label.";
+
 /// Return list of jobs
 pub(crate) async fn get_jobs<T: AsLogicalPlan, U: AsExecutionPlan>(
     data_server: SchedulerServer<T, U>,
+    query: HashMap<String, String>,
 ) -> Result<impl warp::Reply, Rejection> {
     // TODO: Display last seen information in UI
     let state = data_server.state;
+    let labels: HashMap<String, String> = query
+        .into_iter()
+        .filter_map(|(key, value)| {
+            key.strip_prefix(LABEL_QUERY_PREFIX)
+                .map(|label| (label.to_owned(), value))
+        })
+        .collect();
 
     let jobs = state
         .task_manager
@@ -118,12 +339,20 @@ pub(crate) async fn get_jobs<T: AsLogicalPlan, U: AsExecutionPlan>(
 
     let jobs: Vec<JobResponse> = jobs
         .iter()
+        .filter(|job| job.has_labels(&labels))
         .map(|job| {
             let status = &job.status;
             let job_status = match &status.status {
                 Some(Status::Queued(_)) => "Queued".to_string(),
                 Some(Status::Running(_)) => "Running".to_string(),
                 Some(Status::Failed(error)) => format!("Failed: {}", error.error),
+                Some(Status::Cancelled(_)) => "Cancelled".to_string(),
+                Some(Status::Successful(completed)) if completed.results_expired => {
+                    format!(
+                        "Completed. Results expired. Elapsed time: {} ms.",
+                        job.end_time - job.start_time
+                    )
+                }
                 Some(Status::Successful(completed)) => {
                     let num_rows = completed
                         .partition_location
@@ -139,10 +368,15 @@ pub(crate) async fn get_jobs<T: AsLogicalPlan, U: AsExecutionPlan>(
                     } else {
                         "partitions"
                     };
+                    let shared = if completed.shared_job_id.is_empty() {
+                        String::new()
+                    } else {
+                        format!(" Shared the results of job {}.", completed.shared_job_id)
+                    };
                     format!(
-                        "Completed. Produced {} {} containing {} {}. Elapsed time: {} ms.",
+                        "Completed. Produced {} {} containing {} {}. Elapsed time: {} ms.{}",
                         num_partitions, num_partitions_term, num_rows, num_rows_term,
-                        job.end_time - job.start_time
+                        job.end_time - job.start_time, shared
                     )
                 }
                 _ => "Invalid State".to_string(),
@@ -159,6 +393,7 @@ pub(crate) async fn get_jobs<T: AsLogicalPlan, U: AsExecutionPlan>(
                 num_stages: job.num_stages,
                 completed_stages: job.completed_stages,
                 percent_complete,
+                labels: job.labels.clone(),
             }
         })
         .collect();
@@ -190,6 +425,52 @@ pub(crate) async fn cancel_job<T: AsLogicalPlan, U: AsExecutionPlan>(
     Ok(warp::reply::json(&CancelJobResponse { cancelled: true }))
 }
 
+/// Return the aggregated status of the jobs of a group
+pub(crate) async fn get_job_group<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    group_id: String,
+) -> Result<impl warp::Reply, Rejection> {
+    let status = data_server
+        .job_group_status(&group_id)
+        .await
+        .map_err(|_| warp::reject())?;
+    // 404 if the group has no job
+    if status.jobs.is_empty() {
+        return Err(warp::reject());
+    }
+
+    let percent_complete = if status.num_stages == 0 {
+        0
+    } else {
+        ((status.completed_stages as f32 / status.num_stages as f32) * 100_f32) as u8
+    };
+    Ok(warp::reply::json(&JobGroupResponse {
+        group_id,
+        job_ids: status.jobs.into_iter().map(|job| job.job_id).collect(),
+        queued_jobs: status.queued_jobs,
+        running_jobs: status.running_jobs,
+        successful_jobs: status.successful_jobs,
+        failed_jobs: status.failed_jobs,
+        cancelled_jobs: status.cancelled_jobs,
+        num_stages: status.num_stages,
+        completed_stages: status.completed_stages,
+        percent_complete,
+    }))
+}
+
+/// Cancel the queued and running jobs of a group
+pub(crate) async fn cancel_job_group<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    group_id: String,
+) -> Result<impl warp::Reply, Rejection> {
+    let job_ids = data_server
+        .cancel_group_jobs(&group_id)
+        .await
+        .map_err(|_| warp::reject())?;
+
+    Ok(warp::reply::json(&CancelJobGroupResponse { job_ids }))
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct QueryStagesResponse {
     pub stages: Vec<QueryStageSummary>,
@@ -219,6 +500,11 @@ pub(crate) async fn get_query_stages<T: AsLogicalPlan, U: AsExecutionPlan>(
                         input_rows: 0,
                         output_rows: 0,
                         elapsed_compute: "".to_string(),
+                        user_cpu_time: "".to_string(),
+                        sys_cpu_time: "".to_string(),
+                        wall_clock_time: "".to_string(),
+                        peak_memory_bytes: 0,
+                        spilled_bytes: 0,
                     };
                     match stage {
                         ExecutionStage::Running(running_stage) => {
@@ -237,6 +523,7 @@ pub(crate) async fn get_query_stages<T: AsLogicalPlan, U: AsExecutionPlan>(
                                 .as_ref()
                                 .map(|m| get_elapsed_compute_nanos(m.as_slice()))
                                 .unwrap_or_default();
+                            summary.set_resource_usage(running_stage.resource_usage());
                         }
                         ExecutionStage::Successful(completed_stage) => {
                             summary.input_rows = get_combined_count(
@@ -249,6 +536,7 @@ pub(crate) async fn get_query_stages<T: AsLogicalPlan, U: AsExecutionPlan>(
                             );
                             summary.elapsed_compute =
                                 get_elapsed_compute_nanos(&completed_stage.stage_metrics);
+                            summary.set_resource_usage(completed_stage.resource_usage());
                         }
                         _ => {}
                     }
@@ -261,6 +549,158 @@ pub(crate) async fn get_query_stages<T: AsLogicalPlan, U: AsExecutionPlan>(
     }
 }
 
+/// Return the progress of the running tasks of a job, as reported in the last heartbeats of
+/// the executors
+pub(crate) async fn get_task_progress<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    job_id: String,
+) -> Result<impl warp::Reply, Rejection> {
+    let stalled_task_ms = data_server.state.config.stalled_task_seconds * 1000;
+    Ok(warp::reply::json(&TaskProgressResponse {
+        tasks: data_server
+            .state
+            .executor_manager
+            .get_task_progress(&job_id)
+            .into_iter()
+            .map(|(executor_id, progress)| TaskProgressSummary {
+                task_id: progress.task_id,
+                stage_id: progress.stage_id,
+                partition_id: progress.partition_id,
+                executor_id,
+                operator: progress.operator,
+                output_rows: progress.output_rows,
+                output_bytes: progress.output_bytes,
+                elapsed_ms: progress.elapsed_ms,
+                idle_ms: progress.idle_ms,
+                stalled: stalled_task_ms > 0 && progress.idle_ms >= stalled_task_ms,
+            })
+            .collect(),
+    }))
+}
+
+/// Return the attempts of the tasks of a job, optionally of a single stage given by the `stage`
+/// query parameter
+pub(crate) async fn get_task_attempts<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    job_id: String,
+    query: HashMap<String, String>,
+) -> Result<impl warp::Reply, Rejection> {
+    let stage_id = query
+        .get("stage")
+        .map(|stage| stage.parse::<usize>())
+        .transpose()
+        .map_err(|_| warp::reject())?;
+    let attempts = data_server
+        .state
+        .task_manager
+        .get_task_attempts(&job_id, stage_id)
+        .await
+        .map_err(|_| warp::reject())?
+        .ok_or_else(warp::reject)?;
+
+    Ok(warp::reply::json(&TaskAttemptsResponse {
+        attempts: attempts
+            .into_iter()
+            .map(|attempt| {
+                let state = match attempt.state() {
+                    TaskAttemptState::AttemptRunning => "Running",
+                    TaskAttemptState::AttemptSuccessful => "Successful",
+                    TaskAttemptState::AttemptFailed => "Failed",
+                };
+                let metrics: Vec<MetricsSet> = attempt
+                    .metrics
+                    .into_iter()
+                    .filter_map(|metrics| metrics.try_into().ok())
+                    .collect();
+                let resource_usage = attempt.resource_usage.unwrap_or_default();
+                TaskAttemptSummary {
+                    task_id: attempt.task_id,
+                    stage_id: attempt.stage_id,
+                    stage_attempt_num: attempt.stage_attempt_num,
+                    partition_id: attempt.partition_id,
+                    executor_id: attempt.executor_id,
+                    state: state.to_string(),
+                    launch_time: attempt.launch_time,
+                    start_exec_time: attempt.start_exec_time,
+                    end_exec_time: attempt.end_exec_time,
+                    finish_time: attempt.finish_time,
+                    error: attempt.error,
+                    retryable: attempt.retryable,
+                    input_rows: get_combined_count(&metrics, "input_rows"),
+                    output_rows: get_combined_count(&metrics, "output_rows"),
+                    elapsed_compute: get_elapsed_compute_nanos(&metrics),
+                    user_cpu_time: format_micros(resource_usage.user_cpu_time_us),
+                    sys_cpu_time: format_micros(resource_usage.sys_cpu_time_us),
+                    wall_clock_time: format_micros(resource_usage.wall_clock_time_us),
+                    peak_memory_bytes: resource_usage.peak_memory_bytes,
+                    spilled_bytes: resource_usage.spilled_bytes,
+                    batch_size: resource_usage.batch_size,
+                }
+            })
+            .collect(),
+    }))
+}
+
+/// Return the log lines of a task attempt kept by the executor it ran on, from the line given
+/// by the `from_line` query parameter. Only the owner of the job may read them.
+pub(crate) async fn get_task_logs<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    job_id: String,
+    task_id: usize,
+    query: HashMap<String, String>,
+    headers: HeaderMap,
+) -> Result<impl warp::Reply, Rejection> {
+    let metadata = MetadataMap::from_headers(headers);
+    data_server
+        .authorize_job_access(&metadata, &job_id)
+        .await
+        .map_err(|_| warp::reject())?;
+    let authorization = metadata.get("authorization").cloned();
+    let from_line = query
+        .get("from_line")
+        .map(|line| line.parse::<u64>())
+        .transpose()
+        .map_err(|_| warp::reject())?
+        .unwrap_or_default();
+    let mut chunks = data_server
+        .state
+        .fetch_task_logs(&job_id, task_id, from_line, false, authorization)
+        .await
+        .map_err(|_| warp::reject())?
+        .ok_or_else(warp::reject)?;
+
+    let mut response = TaskLogsResponse {
+        first_line: from_line,
+        lines: vec![],
+        finished: false,
+    };
+    while let Some(chunk) = chunks.next().await {
+        let chunk = chunk.map_err(|_| warp::reject())?;
+        if response.lines.is_empty() {
+            response.first_line = chunk.first_line;
+        }
+        response.lines.extend(chunk.lines);
+        response.finished = chunk.finished;
+    }
+    Ok(warp::reply::json(&response))
+}
+
+impl QueryStageSummary {
+    fn set_resource_usage(&mut self, usage: TaskResourceUsage) {
+        self.user_cpu_time = format_micros(usage.user_cpu_time_us);
+        self.sys_cpu_time = format_micros(usage.sys_cpu_time_us);
+        self.wall_clock_time = format_micros(usage.wall_clock_time_us);
+        self.peak_memory_bytes = usage.peak_memory_bytes;
+        self.spilled_bytes = usage.spilled_bytes;
+    }
+}
+
+fn format_micros(micros: u64) -> String {
+    let t = Time::new();
+    t.add_duration(Duration::from_micros(micros));
+    t.to_string()
+}
+
 fn get_elapsed_compute_nanos(metrics: &[MetricsSet]) -> String {
     let nanos: usize = metrics
         .iter()
@@ -354,6 +794,35 @@ pub(crate) async fn get_job_svg_graph<T: AsLogicalPlan, U: AsExecutionPlan>(
     }
 }
 
+/// Return the SQL queries which are quarantined after their jobs repeatedly failed
+pub(crate) async fn get_quarantined_queries<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+) -> Result<impl warp::Reply, Rejection> {
+    let queries = data_server
+        .state
+        .task_manager
+        .query_quarantine()
+        .quarantined();
+    Ok(warp::reply::json(&queries))
+}
+
+/// Clear the failures of a SQL query so that it is accepted again
+pub(crate) async fn clear_quarantined_query<T: AsLogicalPlan, U: AsExecutionPlan>(
+    data_server: SchedulerServer<T, U>,
+    fingerprint: String,
+) -> Result<impl warp::Reply, Rejection> {
+    let cleared = data_server
+        .state
+        .task_manager
+        .query_quarantine()
+        .clear(&fingerprint);
+    // 404 if the query has no failures
+    if !cleared {
+        return Err(warp::reject());
+    }
+    Ok(warp::reply::json(&ClearQuarantineResponse { cleared }))
+}
+
 pub(crate) async fn get_scheduler_metrics<T: AsLogicalPlan, U: AsExecutionPlan>(
     data_server: SchedulerServer<T, U>,
 ) -> Result<impl warp::Reply, Rejection> {
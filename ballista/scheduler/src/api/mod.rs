@@ -10,18 +10,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "rest-api")]
 mod handlers;
 
+#[cfg(feature = "rest-api")]
 use crate::scheduler_server::SchedulerServer;
 use anyhow::Result;
+#[cfg(feature = "rest-api")]
 use datafusion_proto::logical_plan::AsLogicalPlan;
+#[cfg(feature = "rest-api")]
 use datafusion_proto::physical_plan::AsExecutionPlan;
+use hyper::body::Buf;
+use hyper::{Body, Response, StatusCode};
+#[cfg(feature = "rest-api")]
+use std::collections::HashMap;
 use std::{
     pin::Pin,
     task::{Context as TaskContext, Poll},
 };
+#[cfg(feature = "rest-api")]
 use warp::filters::BoxedFilter;
-use warp::{Buf, Filter, Reply};
+#[cfg(feature = "rest-api")]
+use warp::{Filter, Reply};
 
 pub enum EitherBody<A, B> {
     Left(A),
@@ -75,6 +85,17 @@ fn map_option_err<T, U: Into<Error>>(
     err.map(|e| e.map_err(Into::into))
 }
 
+/// Response to the requests of the REST API of a scheduler which does not serve it, so that
+/// clients and the web UI can tell it apart from a missing route
+pub fn rest_api_disabled() -> Response<Body> {
+    let mut response = Response::new(Body::from(
+        "The REST API is disabled on this scheduler, use its gRPC services",
+    ));
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response
+}
+
+#[cfg(feature = "rest-api")]
 fn with_data_server<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
     db: SchedulerServer<T, U>,
 ) -> impl Filter<Extract = (SchedulerServer<T, U>,), Error = std::convert::Infallible> + Clone
@@ -82,6 +103,7 @@ fn with_data_server<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
     warp::any().map(move || db.clone())
 }
 
+#[cfg(feature = "rest-api")]
 pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
     scheduler_server: SchedulerServer<T, U>,
 ) -> BoxedFilter<(impl Reply,)> {
@@ -93,19 +115,68 @@ pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
         .and(with_data_server(scheduler_server.clone()))
         .and_then(handlers::get_executors);
 
+    let route_patch_executor = warp::path!("api" / "executor" / String)
+        .and(warp::patch())
+        .and(with_data_server(scheduler_server.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(|executor_id, data_server, query| {
+            handlers::patch_executor(data_server, executor_id, query)
+        });
+
+    let route_decommission_executor = warp::path!("api" / "executor" / String)
+        .and(warp::delete())
+        .and(with_data_server(scheduler_server.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::header::headers_cloned())
+        .and_then(|executor_id, data_server, query, headers| {
+            handlers::decommission_executor(data_server, executor_id, query, headers)
+        });
+
     let route_jobs = warp::path!("api" / "jobs")
         .and(with_data_server(scheduler_server.clone()))
-        .and_then(|data_server| handlers::get_jobs(data_server));
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(|data_server, query| handlers::get_jobs(data_server, query));
 
     let route_cancel_job = warp::path!("api" / "job" / String)
         .and(warp::patch())
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::cancel_job(data_server, job_id));
 
+    let route_job_group = warp::path!("api" / "job_group" / String)
+        .and(warp::get())
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(|group_id, data_server| handlers::get_job_group(data_server, group_id));
+
+    let route_cancel_job_group = warp::path!("api" / "job_group" / String)
+        .and(warp::patch())
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(|group_id, data_server| {
+            handlers::cancel_job_group(data_server, group_id)
+        });
+
     let route_query_stages = warp::path!("api" / "job" / String / "stages")
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::get_query_stages(data_server, job_id));
 
+    let route_task_attempts = warp::path!("api" / "job" / String / "attempts")
+        .and(with_data_server(scheduler_server.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(|job_id, data_server, query| {
+            handlers::get_task_attempts(data_server, job_id, query)
+        });
+
+    let route_task_logs = warp::path!("api" / "job" / String / "task" / usize / "logs")
+        .and(with_data_server(scheduler_server.clone()))
+        .and(warp::query::<HashMap<String, String>>())
+        .and(warp::header::headers_cloned())
+        .and_then(|job_id, task_id, data_server, query, headers| {
+            handlers::get_task_logs(data_server, job_id, task_id, query, headers)
+        });
+
+    let route_task_progress = warp::path!("api" / "job" / String / "progress")
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(|job_id, data_server| handlers::get_task_progress(data_server, job_id));
+
     let route_job_dot = warp::path!("api" / "job" / String / "dot")
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::get_job_dot_graph(data_server, job_id));
@@ -121,18 +192,39 @@ pub fn get_routes<T: AsLogicalPlan + Clone, U: 'static + AsExecutionPlan>(
         .and(with_data_server(scheduler_server.clone()))
         .and_then(|job_id, data_server| handlers::get_job_svg_graph(data_server, job_id));
 
+    let route_quarantine = warp::path!("api" / "quarantine")
+        .and(warp::get())
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(|data_server| handlers::get_quarantined_queries(data_server));
+
+    let route_clear_quarantine = warp::path!("api" / "quarantine" / String)
+        .and(warp::delete())
+        .and(with_data_server(scheduler_server.clone()))
+        .and_then(|fingerprint, data_server| {
+            handlers::clear_quarantined_query(data_server, fingerprint)
+        });
+
     let route_scheduler_metrics = warp::path!("api" / "metrics")
         .and(with_data_server(scheduler_server))
         .and_then(|data_server| handlers::get_scheduler_metrics(data_server));
 
     let routes = route_scheduler_state
         .or(route_executors)
+        .or(route_patch_executor)
+        .or(route_decommission_executor)
         .or(route_jobs)
         .or(route_cancel_job)
+        .or(route_job_group)
+        .or(route_cancel_job_group)
         .or(route_query_stages)
+        .or(route_task_attempts)
+        .or(route_task_logs)
+        .or(route_task_progress)
         .or(route_job_dot)
         .or(route_query_stage_dot)
         .or(route_job_dot_svg)
+        .or(route_quarantine)
+        .or(route_clear_quarantine)
         .or(route_scheduler_metrics);
     routes.boxed()
 }
@@ -17,17 +17,26 @@
 
 //! Ballista Rust scheduler binary.
 
+use std::sync::Arc;
 use std::{env, io};
 
 use anyhow::Result;
 
 use crate::config::{Config, ResultExt};
-use ballista_core::config::LogRotationPolicy;
+use ballista_core::config::{LogRotationPolicy, ObjectStoreRetryConfig};
+use ballista_core::execution_plans::ExportConfig;
 use ballista_core::print_version;
+use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::{default_session_builder, DefaultObjectStores};
+use ballista_scheduler::clock::system_clock;
 use ballista_scheduler::cluster::BallistaCluster;
 use ballista_scheduler::cluster::ClusterStorage;
-use ballista_scheduler::config::{ClusterStorageConfig, SchedulerConfig};
+use ballista_scheduler::config::{ClusterStorageConfig, OptimizerRules, SchedulerConfig};
+use ballista_scheduler::plugin::{load_plugins, SchedulerPlugins};
 use ballista_scheduler::scheduler_process::start_server;
+use ballista_scheduler::state::pool_routing::parse_pool_routing_rules;
+use ballista_scheduler::state::scheduling_recorder::FileSchedulingRecorder;
+use ballista_scheduler::state::session_manager::parse_session_settings;
 use tracing_subscriber::EnvFilter;
 
 #[macro_use]
@@ -132,6 +141,7 @@ async fn main() -> Result<()> {
         finished_job_state_clean_up_interval_seconds: opt
             .finished_job_state_clean_up_interval_seconds,
         advertise_flight_sql_endpoint: opt.advertise_flight_sql_endpoint,
+        rest_api: opt.rest_api,
         cluster_storage: cluster_storage_config,
         job_resubmit_interval_ms: (opt.job_resubmit_interval_ms > 0)
             .then_some(opt.job_resubmit_interval_ms),
@@ -139,6 +149,68 @@ async fn main() -> Result<()> {
         scheduler_event_expected_processing_duration: opt
             .scheduler_event_expected_processing_duration,
         grpc_server_max_decoding_message_size: opt.grpc_server_max_decoding_message_size,
+        row_codec_max_columns: opt.row_codec_max_columns,
+        task_memory_bytes: opt.task_memory_bytes,
+        task_vcores: opt.task_vcores,
+        shuffle_write_buffer_size: opt.shuffle_write_buffer_size,
+        shuffle_checksums: opt.shuffle_checksums,
+        shuffle_dictionary_sharing: opt.shuffle_dictionary_sharing,
+        job_preemption: opt.job_preemption,
+        max_concurrent_jobs: opt.max_concurrent_jobs,
+        drain_timeout_seconds: opt.drain_timeout_seconds,
+        grpc_compression: opt.grpc_compression,
+        result_cache_ttl_seconds: opt.result_cache_ttl_seconds,
+        result_cache_max_bytes: opt.result_cache_max_bytes,
+        query_deduplication: opt.query_deduplication,
+        query_quarantine_failures: opt.query_quarantine_failures,
+        query_quarantine_window_seconds: opt.query_quarantine_window_seconds,
+        max_queued_events: opt.max_queued_events,
+        max_held_jobs: opt.max_held_jobs,
+        max_event_loop_lag_ms: opt.max_event_loop_lag_ms,
+        overload_wait_ms: opt.overload_wait_ms,
+        job_sharding: opt.job_sharding,
+        session_idle_timeout_seconds: opt.session_idle_timeout_seconds,
+        dropped_table_retention_seconds: opt.dropped_table_retention_seconds,
+        stalled_task_seconds: opt.stalled_task_seconds,
+        reservation_lease_seconds: opt.reservation_lease_seconds,
+        session_affinity: opt.session_affinity,
+        scheduling_recorder: None,
+        session_settings: parse_session_settings(&opt.session_settings)?,
+        session_builder: default_session_builder,
+        object_stores: DefaultObjectStores::try_new(
+            &opt.object_stores,
+            ObjectStoreRetryConfig::default(),
+        )?,
+        export_config: ExportConfig::default(),
+        pool_routing: parse_pool_routing_rules(&opt.pool_routing_rules)?,
+        optimizer_rules: OptimizerRules::default(),
+        plugins: SchedulerPlugins::default(),
+        codec: BallistaCodec::default(),
+        clock: system_clock(),
+    };
+    let config = if opt.scheduling_record_file.is_empty() {
+        config
+    } else {
+        config.with_scheduling_recorder(Arc::new(FileSchedulingRecorder::try_new(
+            &opt.scheduling_record_file,
+        )?))
+    };
+    let export_config = match &opt.export_profiles_file {
+        Some(path) => ExportConfig::default().with_profiles_file(path)?,
+        None => ExportConfig::default(),
+    };
+    let config = match &opt.export_local_root {
+        Some(local_root) => {
+            config.with_export_config(export_config.with_local_root(local_root))
+        }
+        None => config.with_export_config(export_config),
+    };
+    let config = if opt.plugin_dir.is_empty() {
+        config
+    } else {
+        load_plugins(&opt.plugin_dir)?
+            .into_iter()
+            .fold(config, SchedulerConfig::with_plugin)
     };
 
     let cluster = BallistaCluster::new_from_config(&config).await?;
@@ -0,0 +1,134 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Source of the time of the scheduler. The scheduler reads the time, and waits for it to
+//! pass, through a [Clock] so that tests can drive heartbeat expiry, leases of reservations
+//! and delayed clean ups with a [ManualClock] instead of sleeping.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use tokio::sync::Notify;
+
+#[async_trait]
+pub trait Clock: Debug + Send + Sync {
+    /// Milliseconds since the Unix epoch
+    fn now_millis(&self) -> u64;
+
+    /// Seconds since the Unix epoch
+    fn now_secs(&self) -> u64 {
+        self.now_millis() / 1000
+    }
+
+    /// Wait until `duration` passed on the clock
+    async fn sleep(&self, duration: Duration);
+}
+
+/// The wall clock of the system
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_millis() as u64
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// The clock of the scheduler unless another one is configured
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+/// A clock whose time only passes when it is advanced, waking the sleeps which are then due
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now_millis: AtomicU64,
+    advanced: Notify,
+}
+
+impl ManualClock {
+    /// A clock stopped at `now_millis` milliseconds since the Unix epoch
+    pub fn new(now_millis: u64) -> Self {
+        Self {
+            now_millis: AtomicU64::new(now_millis),
+            advanced: Notify::new(),
+        }
+    }
+
+    /// Let `duration` pass
+    pub fn advance(&self, duration: Duration) {
+        self.now_millis
+            .fetch_add(duration.as_millis() as u64, Ordering::SeqCst);
+        self.advanced.notify_waiters();
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now_millis(&self) -> u64 {
+        self.now_millis.load(Ordering::SeqCst)
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let deadline = self.now_millis() + duration.as_millis() as u64;
+        loop {
+            // Registered before the time is checked so that no advance is missed
+            let advanced = self.advanced.notified();
+            if self.now_millis() >= deadline {
+                return;
+            }
+            advanced.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_manual_clock() {
+        let clock = Arc::new(ManualClock::new(10_000));
+        assert_eq!(clock.now_secs(), 10);
+
+        let sleeping = clock.clone();
+        let sleep = tokio::spawn(async move {
+            sleeping.sleep(Duration::from_secs(5)).await;
+            sleeping.now_millis()
+        });
+        // The sleep measures its duration from the time it is first polled
+        tokio::task::yield_now().await;
+        clock.advance(Duration::from_secs(3));
+        tokio::task::yield_now().await;
+        assert!(!sleep.is_finished());
+
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(sleep.await.unwrap(), 15_000);
+        assert_eq!(clock.now_secs(), 15);
+    }
+}
@@ -15,27 +15,36 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::clock::{system_clock, Clock};
 use crate::cluster::storage::{KeyValueStore, Keyspace, Lock, Operation, WatchEvent};
 use crate::cluster::{
-    reserve_slots_bias, reserve_slots_round_robin, ClusterState, ExecutorHeartbeatStream,
-    JobState, JobStateEvent, JobStateEventStream, JobStatus, TaskDistribution,
+    distribute_slots, expire_leases, lease_reservations, release_leases,
+    release_reservations, remove_executor_leases, reservable_slots,
+    resize_executor_slots, ClusterState, ExecutorHeartbeatStream, JobState,
+    JobStateEvent, JobStateEventStream, JobStatus, SessionObjectsUpdate,
+    TaskDistribution,
 };
-use crate::scheduler_server::{timestamp_secs, SessionBuilder};
+use crate::scheduler_server::{timestamp_millis, timestamp_secs, SessionBuilder};
 use crate::state::execution_graph::ExecutionGraph;
 use crate::state::executor_manager::ExecutorReservation;
-use crate::state::session_manager::create_datafusion_context;
+use crate::state::session_manager::{
+    create_datafusion_context, SESSION_ACCESS_INTERVAL_SECS,
+};
 use crate::state::{decode_into, decode_protobuf};
 use async_trait::async_trait;
 use ballista_core::config::BallistaConfig;
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::serde::protobuf::job_status::Status;
 use ballista_core::serde::protobuf::{
-    self, AvailableTaskSlots, ExecutorHeartbeat, ExecutorTaskSlots, FailedJob,
-    KeyValuePair, QueuedJob,
+    self, AvailableTaskSlots, CancelledJob, ExecutorHeartbeat, ExecutorTaskSlots,
+    FailedJob, HeldJob, KeyValuePair, PartitionLocation, QueuedJob, RunningJob,
+    SchedulerHeartbeat, SessionObjects, SuccessfulJob, TableDefinition,
 };
-use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
+use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata, TaskResources};
 use ballista_core::serde::BallistaCodec;
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use datafusion::datasource::provider::TableProviderFactory;
 use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
@@ -47,6 +56,7 @@ use prost::Message;
 use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// State implementation based on underlying `KeyValueStore`
 pub struct KeyValueState<
@@ -63,12 +73,18 @@ pub struct KeyValueState<
     /// Codec used to serialize/deserialize execution plan
     codec: BallistaCodec<T, U>,
     /// Name of current scheduler. Should be `{host}:{port}`
-    #[allow(dead_code)]
     scheduler: String,
     /// In-memory store of queued jobs. Map from Job ID -> (Job Name, queued_at timestamp)
     queued_jobs: DashMap<String, (String, u64)>,
     //// `SessionBuilder` for constructing `SessionContext` from stored `BallistaConfig`
     session_builder: SessionBuilder,
+    /// Table factories by file type registered with every session
+    table_factories: HashMap<String, Arc<dyn TableProviderFactory>>,
+    /// Time after which reserved task slots return to the available task slots unless they
+    /// are filled with tasks or cancelled, `None` for reservations without lease
+    reservation_lease: Option<Duration>,
+    /// Clock of the heartbeats of registered executors and of the leases of reservations
+    clock: Arc<dyn Clock>,
 }
 
 impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
@@ -88,9 +104,34 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             codec,
             queued_jobs: DashMap::new(),
             session_builder,
+            table_factories: HashMap::new(),
+            reservation_lease: None,
+            clock: system_clock(),
         }
     }
 
+    /// Expire the reservations of task slots after `reservation_lease`. The leases are saved
+    /// with the task slots, so that any scheduler sharing the cluster state reclaims the task
+    /// slots reserved by a scheduler which crashed
+    pub fn with_reservation_lease(mut self, reservation_lease: Option<Duration>) -> Self {
+        self.reservation_lease = reservation_lease;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Create the tables of the file types of `table_factories` with them in every session
+    pub fn with_table_factories(
+        mut self,
+        table_factories: HashMap<String, Arc<dyn TableProviderFactory>>,
+    ) -> Self {
+        self.table_factories = table_factories;
+        self
+    }
+
     /// Initialize the set of active executor heartbeats from storage
     async fn init_active_executor_heartbeats(&self) -> Result<()> {
         let heartbeats = self.store.scan(Keyspace::Heartbeats, None).await?;
@@ -134,6 +175,25 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             })
             .boxed())
     }
+
+    /// Save the final status of a job which was not submitted, removing it from the held
+    /// jobs
+    async fn complete_unscheduled_job(
+        &self,
+        job_id: String,
+        status: JobStatus,
+    ) -> Result<()> {
+        self.store
+            .apply_txn(vec![
+                (
+                    Operation::Put(status.encode_to_vec()),
+                    Keyspace::JobStatus,
+                    job_id.clone(),
+                ),
+                (Operation::Delete, Keyspace::HeldJobs, job_id),
+            ])
+            .await
+    }
 }
 
 #[async_trait]
@@ -177,26 +237,27 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     async fn reserve_slots(
         &self,
         num_slots: u32,
+        resources: TaskResources,
         distribution: TaskDistribution,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<ExecutorReservation>> {
         let lock = self.store.lock(Keyspace::Slots, "global").await?;
 
         with_lock(lock, async {
-            let resources = self.store.get(Keyspace::Slots, "all").await?;
+            let value = self.store.get(Keyspace::Slots, "all").await?;
 
             let mut slots =
-                ExecutorTaskSlots::decode(resources.as_slice()).map_err(|err| {
+                ExecutorTaskSlots::decode(value.as_slice()).map_err(|err| {
                     BallistaError::Internal(format!(
                         "Unexpected value in executor slots state: {err:?}"
                     ))
                 })?;
 
-            let mut available_slots: Vec<&mut AvailableTaskSlots> = slots
+            let available_slots: Vec<&mut AvailableTaskSlots> = slots
                 .task_slots
                 .iter_mut()
                 .filter_map(|data| {
-                    (data.slots > 0
+                    (reservable_slots(data, &resources) > 0
                         && executors
                             .as_ref()
                             .map(|executors| executors.contains(&data.executor_id))
@@ -205,16 +266,16 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 })
                 .collect();
 
-            available_slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
-
-            let reservations = match distribution {
-                TaskDistribution::Bias => reserve_slots_bias(available_slots, num_slots),
-                TaskDistribution::RoundRobin => {
-                    reserve_slots_round_robin(available_slots, num_slots)
-                }
-            };
+            let mut reservations =
+                distribute_slots(available_slots, resources, num_slots, distribution);
 
             if !reservations.is_empty() {
+                lease_reservations(
+                    &mut slots,
+                    &mut reservations,
+                    self.reservation_lease,
+                    self.clock.now_millis(),
+                );
                 self.store
                     .put(Keyspace::Slots, "all".to_owned(), slots.encode_to_vec())
                     .await?
@@ -228,26 +289,27 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     async fn reserve_slots_exact(
         &self,
         num_slots: u32,
+        resources: TaskResources,
         distribution: TaskDistribution,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<ExecutorReservation>> {
         let lock = self.store.lock(Keyspace::Slots, "global").await?;
 
         with_lock(lock, async {
-            let resources = self.store.get(Keyspace::Slots, "all").await?;
+            let value = self.store.get(Keyspace::Slots, "all").await?;
 
             let mut slots =
-                ExecutorTaskSlots::decode(resources.as_slice()).map_err(|err| {
+                ExecutorTaskSlots::decode(value.as_slice()).map_err(|err| {
                     BallistaError::Internal(format!(
                         "Unexpected value in executor slots state: {err:?}"
                     ))
                 })?;
 
-            let mut available_slots: Vec<&mut AvailableTaskSlots> = slots
+            let available_slots: Vec<&mut AvailableTaskSlots> = slots
                 .task_slots
                 .iter_mut()
                 .filter_map(|data| {
-                    (data.slots > 0
+                    (reservable_slots(data, &resources) > 0
                         && executors
                             .as_ref()
                             .map(|executors| executors.contains(&data.executor_id))
@@ -256,16 +318,16 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 })
                 .collect();
 
-            available_slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
-
-            let reservations = match distribution {
-                TaskDistribution::Bias => reserve_slots_bias(available_slots, num_slots),
-                TaskDistribution::RoundRobin => {
-                    reserve_slots_round_robin(available_slots, num_slots)
-                }
-            };
+            let mut reservations =
+                distribute_slots(available_slots, resources, num_slots, distribution);
 
             if reservations.len() == num_slots as usize {
+                lease_reservations(
+                    &mut slots,
+                    &mut reservations,
+                    self.reservation_lease,
+                    self.clock.now_millis(),
+                );
                 self.store
                     .put(Keyspace::Slots, "all".to_owned(), slots.encode_to_vec())
                     .await?;
@@ -293,20 +355,24 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     ))
                 })?;
 
-            let mut increments = HashMap::new();
-            for ExecutorReservation { executor_id, .. } in reservations {
-                if let Some(inc) = increments.get_mut(&executor_id) {
-                    *inc += 1;
-                } else {
-                    increments.insert(executor_id, 1usize);
-                }
-            }
+            release_reservations(&mut slots, reservations);
 
-            for executor_slots in slots.task_slots.iter_mut() {
-                if let Some(slots) = increments.get(&executor_slots.executor_id) {
-                    executor_slots.slots += *slots as u32;
-                }
-            }
+            self.store
+                .put(Keyspace::Slots, "all".to_string(), slots.encode_to_vec())
+                .await
+        })
+        .await
+    }
+
+    async fn release_reservation_leases(&self, lease_ids: HashSet<String>) -> Result<()> {
+        let lock = self.store.lock(Keyspace::Slots, "all").await?;
+
+        with_lock(lock, async {
+            let slots = self.store.get(Keyspace::Slots, "all").await?;
+
+            let mut slots: ExecutorTaskSlots = decode_protobuf(slots.as_slice())?;
+
+            release_leases(&mut slots, &lease_ids);
 
             self.store
                 .put(Keyspace::Slots, "all".to_string(), slots.encode_to_vec())
@@ -315,6 +381,26 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         .await
     }
 
+    async fn expire_reservation_leases(&self) -> Result<usize> {
+        let lock = self.store.lock(Keyspace::Slots, "all").await?;
+
+        with_lock(lock, async {
+            let slots = self.store.get(Keyspace::Slots, "all").await?;
+
+            let mut slots: ExecutorTaskSlots = decode_protobuf(slots.as_slice())?;
+
+            let expired = expire_leases(&mut slots, self.clock.now_millis());
+            if expired > 0 {
+                self.store
+                    .put(Keyspace::Slots, "all".to_string(), slots.encode_to_vec())
+                    .await?;
+            }
+
+            Ok(expired)
+        })
+        .await
+    }
+
     async fn register_executor(
         &self,
         metadata: ExecutorMetadata,
@@ -322,19 +408,21 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         reserve: bool,
     ) -> Result<Vec<ExecutorReservation>> {
         let executor_id = metadata.id.clone();
+        let resources = metadata.specification.available_resources();
 
         //TODO this should be in a transaction
         // Now that we know we can connect, save the metadata and slots
         self.save_executor_metadata(metadata).await?;
         self.save_executor_heartbeat(ExecutorHeartbeat {
             executor_id: executor_id.clone(),
-            timestamp: timestamp_secs(),
+            timestamp: self.clock.now_secs(),
             metrics: vec![],
             status: Some(protobuf::ExecutorStatus {
                 status: Some(
                     protobuf::executor_status::Status::Active(String::default()),
                 ),
             }),
+            task_progress: vec![],
         })
         .await?;
 
@@ -342,6 +430,10 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             let available_slots = AvailableTaskSlots {
                 executor_id,
                 slots: spec.available_task_slots,
+                memory_bytes: resources.memory_bytes,
+                vcores: resources.vcores,
+                withheld_slots: 0,
+                gpus: resources.gpus,
             };
 
             let lock = self.store.lock(Keyspace::Slots, "all").await?;
@@ -351,6 +443,7 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
 
                 let mut current_slots: ExecutorTaskSlots =
                     decode_protobuf(current_slots.as_slice())?;
+                remove_executor_leases(&mut current_slots, &available_slots.executor_id);
 
                 if let Some((idx, _)) =
                     current_slots.task_slots.iter().find_position(|slots| {
@@ -383,6 +476,10 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             let available_slots = AvailableTaskSlots {
                 executor_id,
                 slots: 0,
+                memory_bytes: resources.memory_bytes,
+                vcores: resources.vcores,
+                withheld_slots: 0,
+                gpus: resources.gpus,
             };
 
             let lock = self.store.lock(Keyspace::Slots, "all").await?;
@@ -392,6 +489,7 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
 
                 let mut current_slots: ExecutorTaskSlots =
                     decode_protobuf(current_slots.as_slice())?;
+                remove_executor_leases(&mut current_slots, &available_slots.executor_id);
 
                 if let Some((idx, _)) =
                     current_slots.task_slots.iter().find_position(|slots| {
@@ -402,6 +500,12 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 } else {
                     current_slots.task_slots.push(available_slots);
                 }
+                lease_reservations(
+                    &mut current_slots,
+                    &mut reservations,
+                    self.reservation_lease,
+                    self.clock.now_millis(),
+                );
 
                 self.store
                     .put(
@@ -463,14 +567,32 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         Ok(())
     }
 
+    async fn resize_executor(&self, executor_id: &str, delta: i32) -> Result<()> {
+        let lock = self.store.lock(Keyspace::Slots, "all").await?;
+
+        with_lock(lock, async {
+            let slots = self.store.get(Keyspace::Slots, "all").await?;
+
+            let mut slots: ExecutorTaskSlots = decode_protobuf(slots.as_slice())?;
+
+            resize_executor_slots(&mut slots, executor_id, delta)?;
+
+            self.store
+                .put(Keyspace::Slots, "all".to_string(), slots.encode_to_vec())
+                .await
+        })
+        .await
+    }
+
     async fn remove_executor(&self, executor_id: &str) -> Result<()> {
         let value = ExecutorHeartbeat {
             executor_id: executor_id.to_owned(),
-            timestamp: timestamp_secs(),
+            timestamp: self.clock.now_secs(),
             metrics: vec![],
             status: Some(protobuf::ExecutorStatus {
                 status: Some(protobuf::executor_status::Status::Dead("".to_string())),
             }),
+            task_progress: vec![],
         }
         .encode_to_vec();
 
@@ -478,12 +600,67 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             .put(Keyspace::Heartbeats, executor_id.to_owned(), value)
             .await?;
         self.executor_heartbeats.remove(executor_id);
+        self.store
+            .delete(Keyspace::Decommissioning, executor_id)
+            .await?;
 
         // TODO Check the Executor reservation logic for push-based scheduling
 
         Ok(())
     }
 
+    async fn decommission_executor(
+        &self,
+        executor_id: &str,
+        deadline: u64,
+    ) -> Result<bool> {
+        let lock = self
+            .store
+            .lock(Keyspace::Decommissioning, executor_id)
+            .await?;
+
+        with_lock(lock, async {
+            if !self
+                .store
+                .get(Keyspace::Decommissioning, executor_id)
+                .await?
+                .is_empty()
+            {
+                return Ok(false);
+            }
+            self.store
+                .put(
+                    Keyspace::Decommissioning,
+                    executor_id.to_owned(),
+                    deadline.to_be_bytes().to_vec(),
+                )
+                .await?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn decommissioning_executors(&self) -> Result<HashMap<String, u64>> {
+        let mut decommissioning = HashMap::new();
+        for executor_id in self.store.scan_keys(Keyspace::Decommissioning).await? {
+            let value = self
+                .store
+                .get(Keyspace::Decommissioning, &executor_id)
+                .await?;
+            // The executor was removed in the meantime
+            if value.is_empty() {
+                continue;
+            }
+            let deadline = value.try_into().map_err(|_| {
+                BallistaError::Internal(format!(
+                    "Invalid decommissioning deadline of executor {executor_id}"
+                ))
+            })?;
+            decommissioning.insert(executor_id, u64::from_be_bytes(deadline));
+        }
+        Ok(decommissioning)
+    }
+
     fn executor_heartbeats(&self) -> HashMap<String, ExecutorHeartbeat> {
         self.executor_heartbeats
             .iter()
@@ -496,6 +673,28 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             .get(executor_id)
             .map(|r| r.value().clone())
     }
+
+    async fn save_scheduler_heartbeat(
+        &self,
+        heartbeat: SchedulerHeartbeat,
+    ) -> Result<()> {
+        self.store
+            .put(
+                Keyspace::Schedulers,
+                heartbeat.scheduler_id.clone(),
+                heartbeat.encode_to_vec(),
+            )
+            .await
+    }
+
+    async fn scheduler_heartbeats(&self) -> Result<Vec<SchedulerHeartbeat>> {
+        self.store
+            .scan(Keyspace::Schedulers, None)
+            .await?
+            .into_iter()
+            .map(|(_, value)| decode_protobuf(&value))
+            .collect()
+    }
 }
 
 #[async_trait]
@@ -508,10 +707,44 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         job_name: &str,
         queued_at: u64,
     ) -> Result<()> {
-        self.queued_jobs
-            .insert(job_id.to_string(), (job_name.to_string(), queued_at));
+        // The ID is reserved in the store under its lock, so that no other scheduler
+        // accepts a job with the same ID concurrently
+        let lock = self.store.lock(Keyspace::JobStatus, job_id).await?;
 
-        Ok(())
+        with_lock(lock, async {
+            if !self
+                .store
+                .get(Keyspace::JobStatus, job_id)
+                .await?
+                .is_empty()
+            {
+                return Err(BallistaError::General(format!(
+                    "Job {job_id} already exists"
+                )));
+            }
+            match self.queued_jobs.entry(job_id.to_string()) {
+                Entry::Occupied(_) => Err(BallistaError::General(format!(
+                    "Job {job_id} already exists"
+                ))),
+                Entry::Vacant(entry) => {
+                    let status = JobStatus {
+                        job_id: job_id.to_string(),
+                        job_name: job_name.to_string(),
+                        status: Some(Status::Queued(QueuedJob { queued_at })),
+                    };
+                    self.store
+                        .put(
+                            Keyspace::JobStatus,
+                            job_id.to_string(),
+                            status.encode_to_vec(),
+                        )
+                        .await?;
+                    entry.insert((job_name.to_string(), queued_at));
+                    Ok(())
+                }
+            }
+        })
+        .await
     }
 
     async fn submit_job(&self, job_id: String, graph: &ExecutionGraph) -> Result<()> {
@@ -532,6 +765,7 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                         Keyspace::ExecutionGraph,
                         job_id.clone(),
                     ),
+                    (Operation::Delete, Keyspace::HeldJobs, job_id.clone()),
                 ])
                 .await?;
 
@@ -618,9 +852,7 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 })),
             };
 
-            self.store
-                .put(Keyspace::JobStatus, job_id, status.encode_to_vec())
-                .await
+            self.complete_unscheduled_job(job_id, status).await
         } else {
             Err(BallistaError::Internal(format!(
                 "Could not fail unscheduled job {job_id}, not found in queued jobs"
@@ -628,6 +860,56 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         }
     }
 
+    async fn cancel_unscheduled_job(&self, job_id: &str) -> Result<()> {
+        if let Some((job_id, (job_name, queued_at))) = self.queued_jobs.remove(job_id) {
+            let status = JobStatus {
+                job_id: job_id.clone(),
+                job_name,
+                status: Some(Status::Cancelled(CancelledJob {
+                    queued_at,
+                    started_at: 0,
+                    ended_at: timestamp_millis(),
+                })),
+            };
+
+            self.complete_unscheduled_job(job_id, status).await
+        } else {
+            Err(BallistaError::Internal(format!(
+                "Could not cancel unscheduled job {job_id}, not found in queued jobs"
+            )))
+        }
+    }
+
+    async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        partition_location: Vec<PartitionLocation>,
+        shared_job_id: &str,
+    ) -> Result<()> {
+        if let Some((job_id, (job_name, queued_at))) = self.queued_jobs.remove(job_id) {
+            let now = timestamp_millis();
+            let status = JobStatus {
+                job_id: job_id.clone(),
+                job_name,
+                status: Some(Status::Successful(SuccessfulJob {
+                    partition_location,
+                    queued_at,
+                    started_at: now,
+                    ended_at: now,
+                    analyzed_plan: String::new(),
+                    results_expired: false,
+                    shared_job_id: shared_job_id.to_owned(),
+                })),
+            };
+
+            self.complete_unscheduled_job(job_id, status).await
+        } else {
+            Err(BallistaError::Internal(format!(
+                "Could not complete unscheduled job {job_id}, not found in queued jobs"
+            )))
+        }
+    }
+
     async fn remove_job(&self, job_id: &str) -> Result<()> {
         if self.queued_jobs.remove(job_id).is_none() {
             self.store
@@ -641,14 +923,70 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 ])
                 .await
         } else {
-            Ok(())
+            // Releases the ID reserved when the job was accepted
+            self.store
+                .apply_txn(vec![
+                    (Operation::Delete, Keyspace::JobStatus, job_id.to_string()),
+                    (Operation::Delete, Keyspace::HeldJobs, job_id.to_string()),
+                ])
+                .await
         }
     }
 
-    async fn try_acquire_job(&self, _job_id: &str) -> Result<Option<ExecutionGraph>> {
-        Err(BallistaError::NotImplemented(
-            "Work stealing is not currently implemented".to_string(),
-        ))
+    async fn save_held_job(&self, mut job: HeldJob) -> Result<()> {
+        job.scheduler = self.scheduler.clone();
+        self.store
+            .put(Keyspace::HeldJobs, job.job_id.clone(), job.encode_to_vec())
+            .await
+    }
+
+    async fn recover_held_jobs(&self) -> Result<Vec<HeldJob>> {
+        let mut held_jobs = vec![];
+        for (_, value) in self.store.scan(Keyspace::HeldJobs, None).await? {
+            let job: HeldJob = decode_protobuf(&value)?;
+            if job.scheduler != self.scheduler {
+                continue;
+            }
+            let status = self.store.get(Keyspace::JobStatus, &job.job_id).await?;
+            let queued = !status.is_empty()
+                && matches!(
+                    decode_protobuf::<JobStatus>(&status)?.status,
+                    Some(Status::Queued(_))
+                );
+            if queued {
+                self.queued_jobs
+                    .insert(job.job_id.clone(), (job.job_name.clone(), job.queued_at));
+                held_jobs.push(job);
+            } else {
+                // The job completed or was removed before the held job was removed
+                self.store.delete(Keyspace::HeldJobs, &job.job_id).await?;
+            }
+        }
+        held_jobs.sort_by_key(|job| job.queued_at);
+        Ok(held_jobs)
+    }
+
+    async fn try_acquire_job(&self, job_id: &str) -> Result<Option<ExecutionGraph>> {
+        let lock = self.store.lock(Keyspace::JobStatus, job_id).await?;
+
+        with_lock(lock, async {
+            let value = self.store.get(Keyspace::JobStatus, job_id).await?;
+            if value.is_empty() {
+                return Ok(None);
+            }
+
+            // Only jobs which were running on this scheduler before it restarted are acquired
+            let status: JobStatus = decode_protobuf(value.as_slice())?;
+            match status.status {
+                Some(Status::Running(RunningJob { scheduler, .. }))
+                    if scheduler == self.scheduler =>
+                {
+                    self.get_execution_graph(job_id).await
+                }
+                _ => Ok(None),
+            }
+        })
+        .await
     }
 
     async fn job_state_events(&self) -> Result<JobStateEventStream> {
@@ -689,7 +1027,7 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>> {
         let value = self.store.get(Keyspace::Sessions, session_id).await?;
 
-        let settings: protobuf::SessionSettings = decode_protobuf(&value)?;
+        let mut settings: protobuf::SessionSettings = decode_protobuf(&value)?;
 
         let mut config_builder = BallistaConfig::builder();
         for kv_pair in &settings.configs {
@@ -697,31 +1035,52 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         }
         let config = config_builder.build()?;
 
-        Ok(create_datafusion_context(&config, self.session_builder))
+        // Record the access of a saved session, at most once per interval so that every
+        // query of a session does not write to the store
+        let now = timestamp_secs();
+        if !value.is_empty()
+            && now.saturating_sub(settings.last_accessed) >= SESSION_ACCESS_INTERVAL_SECS
+        {
+            settings.last_accessed = now;
+            self.store
+                .put(
+                    Keyspace::Sessions,
+                    session_id.to_owned(),
+                    settings.encode_to_vec(),
+                )
+                .await?;
+        }
+
+        Ok(create_datafusion_context(
+            &config,
+            self.session_builder,
+            &self.table_factories,
+        ))
+    }
+
+    fn default_session(&self) -> Result<Arc<SessionContext>> {
+        Ok(create_datafusion_context(
+            &BallistaConfig::new()?,
+            self.session_builder,
+            &self.table_factories,
+        ))
     }
 
     async fn create_session(
         &self,
         config: &BallistaConfig,
     ) -> Result<Arc<SessionContext>> {
-        let mut settings: Vec<KeyValuePair> = vec![];
-
-        for (key, value) in config.settings() {
-            settings.push(KeyValuePair {
-                key: key.clone(),
-                value: value.clone(),
-            })
-        }
-
-        let value = protobuf::SessionSettings { configs: settings };
-
-        let session = create_datafusion_context(config, self.session_builder);
+        let session = create_datafusion_context(
+            config,
+            self.session_builder,
+            &self.table_factories,
+        );
 
         self.store
             .put(
                 Keyspace::Sessions,
                 session.session_id(),
-                value.encode_to_vec(),
+                session_settings(config).encode_to_vec(),
             )
             .await?;
 
@@ -733,25 +1092,135 @@ impl<S: KeyValueStore, T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         session_id: &str,
         config: &BallistaConfig,
     ) -> Result<Arc<SessionContext>> {
-        let mut settings: Vec<KeyValuePair> = vec![];
-
-        for (key, value) in config.settings() {
-            settings.push(KeyValuePair {
-                key: key.clone(),
-                value: value.clone(),
-            })
-        }
-
-        let value = protobuf::SessionSettings { configs: settings };
         self.store
             .put(
                 Keyspace::Sessions,
                 session_id.to_owned(),
-                value.encode_to_vec(),
+                session_settings(config).encode_to_vec(),
             )
             .await?;
 
-        Ok(create_datafusion_context(config, self.session_builder))
+        Ok(create_datafusion_context(
+            config,
+            self.session_builder,
+            &self.table_factories,
+        ))
+    }
+
+    async fn remove_session(&self, session_id: &str) -> Result<bool> {
+        let value = self.store.get(Keyspace::Sessions, session_id).await?;
+        if value.is_empty() {
+            return Ok(false);
+        }
+        self.store.delete(Keyspace::Sessions, session_id).await?;
+        self.store
+            .delete(Keyspace::SessionObjects, session_id)
+            .await?;
+        Ok(true)
+    }
+
+    async fn remove_idle_sessions(
+        &self,
+        accessed_before: u64,
+        keep: &HashSet<String>,
+    ) -> Result<Vec<String>> {
+        let mut removed = vec![];
+        for session_id in self.store.scan_keys(Keyspace::Sessions).await? {
+            let value = self.store.get(Keyspace::Sessions, &session_id).await?;
+            let settings: protobuf::SessionSettings = decode_protobuf(&value)?;
+            if settings.last_accessed < accessed_before && !keep.contains(&session_id) {
+                self.store.delete(Keyspace::Sessions, &session_id).await?;
+                self.store
+                    .delete(Keyspace::SessionObjects, &session_id)
+                    .await?;
+                removed.push(session_id);
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn save_table(&self, table: TableDefinition) -> Result<()> {
+        self.store
+            .put(Keyspace::Tables, table.name.clone(), table.encode_to_vec())
+            .await
+    }
+
+    async fn save_new_table(&self, table: TableDefinition) -> Result<bool> {
+        let lock = self.store.lock(Keyspace::Tables, &table.name).await?;
+
+        with_lock(lock, async {
+            let value = self.store.get(Keyspace::Tables, &table.name).await?;
+            if !value.is_empty() {
+                let saved: TableDefinition = decode_protobuf(&value)?;
+                if saved.dropped_at == 0 {
+                    return Ok(false);
+                }
+            }
+            self.save_table(table).await?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn get_tables(&self) -> Result<Vec<TableDefinition>> {
+        self.store
+            .scan(Keyspace::Tables, None)
+            .await?
+            .into_iter()
+            .map(|(_, value)| decode_protobuf(&value))
+            .collect()
+    }
+
+    async fn remove_table(&self, name: &str) -> Result<()> {
+        self.store.delete(Keyspace::Tables, name).await
+    }
+
+    async fn update_session_objects(
+        &self,
+        session_id: &str,
+        update: SessionObjectsUpdate,
+    ) -> Result<bool> {
+        let lock = self
+            .store
+            .lock(Keyspace::SessionObjects, session_id)
+            .await?;
+
+        with_lock(lock, async {
+            let value = self.store.get(Keyspace::SessionObjects, session_id).await?;
+            let mut objects: SessionObjects = decode_protobuf(&value)?;
+            if !update(&mut objects) {
+                return Ok(false);
+            }
+            self.store
+                .put(
+                    Keyspace::SessionObjects,
+                    session_id.to_owned(),
+                    objects.encode_to_vec(),
+                )
+                .await?;
+            Ok(true)
+        })
+        .await
+    }
+
+    async fn get_session_objects(&self, session_id: &str) -> Result<SessionObjects> {
+        let value = self.store.get(Keyspace::SessionObjects, session_id).await?;
+        decode_protobuf(&value)
+    }
+}
+
+/// Settings of a session saved to the store, accessed now
+fn session_settings(config: &BallistaConfig) -> protobuf::SessionSettings {
+    protobuf::SessionSettings {
+        configs: config
+            .settings()
+            .iter()
+            .map(|(key, value)| KeyValuePair {
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect(),
+        last_accessed: timestamp_secs(),
     }
 }
 
@@ -766,21 +1235,29 @@ mod test {
     use crate::cluster::kv::KeyValueState;
     use crate::cluster::storage::sled::SledClient;
     use crate::cluster::test::{
-        test_executor_registration, test_fuzz_reservations, test_job_lifecycle,
-        test_job_planning_failure, test_reservation,
+        test_duplicate_job_id, test_executor_decommissioning, test_executor_registration,
+        test_executor_resize, test_fuzz_reservations, test_job_lifecycle,
+        test_job_planning_failure, test_job_recovery, test_reservation,
+        test_reservation_leases, test_resource_reservation, test_session_expiry,
+        test_session_objects, test_shared_tables,
     };
+    use crate::cluster::JobState;
     use crate::cluster::TaskDistribution;
+    use crate::scheduler_server::timestamp_millis;
     use crate::test_utils::{
         test_aggregation_plan, test_join_plan, test_two_aggregations_plan,
     };
     use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::job_status::Status;
+    use ballista_core::serde::protobuf::HeldJob;
     use ballista_core::serde::BallistaCodec;
     use ballista_core::utils::default_session_builder;
+    use std::time::Duration;
 
     #[cfg(feature = "sled")]
     fn make_sled_state() -> Result<KeyValueState<SledClient>> {
         Ok(KeyValueState::new(
-            "",
+            "localhost:50050",
             SledClient::try_new_temporary()?,
             BallistaCodec::default(),
             default_session_builder,
@@ -802,6 +1279,37 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_resource_reserve() -> Result<()> {
+        test_resource_reservation(make_sled_state()?, TaskDistribution::Bias).await?;
+        test_resource_reservation(make_sled_state()?, TaskDistribution::RoundRobin)
+            .await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_reservation_leases() -> Result<()> {
+        test_reservation_leases(
+            make_sled_state()?.with_reservation_lease(Some(Duration::ZERO)),
+        )
+        .await
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_executor_resize() -> Result<()> {
+        test_executor_resize(make_sled_state()?).await
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_executor_decommissioning() -> Result<()> {
+        test_executor_decommissioning(make_sled_state()?).await
+    }
+
     #[cfg(feature = "sled")]
     #[tokio::test]
     async fn test_sled_fuzz_reserve() -> Result<()> {
@@ -819,6 +1327,104 @@ mod test {
         Ok(())
     }
 
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_duplicate_job_id() -> Result<()> {
+        test_duplicate_job_id(make_sled_state()?, test_aggregation_plan(4).await).await
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_duplicate_job_id_across_schedulers() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let state1 = KeyValueState::new(
+            "localhost:50050",
+            store.clone(),
+            BallistaCodec::default(),
+            default_session_builder,
+        );
+        let state2 = KeyValueState::new(
+            "localhost:50051",
+            store,
+            BallistaCodec::default(),
+            default_session_builder,
+        );
+
+        // Both schedulers accept the job at once, only one of them may queue it
+        let (accepted1, accepted2) = futures::join!(
+            state1.accept_job("job", "", timestamp_millis()),
+            state2.accept_job("job", "", timestamp_millis())
+        );
+        assert!(accepted1.is_ok() ^ accepted2.is_ok());
+
+        // The ID is released with the job
+        let accepted = if accepted1.is_ok() { &state1 } else { &state2 };
+        accepted.remove_job("job").await?;
+        state2.accept_job("job", "", timestamp_millis()).await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_held_job_recovery() -> Result<()> {
+        let store = SledClient::try_new_temporary()?;
+        let new_state = |scheduler: &str| {
+            KeyValueState::<SledClient>::new(
+                scheduler,
+                store.clone(),
+                BallistaCodec::default(),
+                default_session_builder,
+            )
+        };
+
+        let state = new_state("localhost:50050");
+        for (job_id, queued_at) in [("job-1", 2), ("job-2", 1), ("job-3", 3)] {
+            state.accept_job(job_id, "", queued_at).await?;
+            state
+                .save_held_job(HeldJob {
+                    job_id: job_id.to_owned(),
+                    queued_at,
+                    ..Default::default()
+                })
+                .await?;
+        }
+        state.cancel_unscheduled_job("job-3").await?;
+        assert!(matches!(
+            state.get_job_status("job-3").await?.and_then(|s| s.status),
+            Some(Status::Cancelled(_))
+        ));
+
+        // The held jobs are only recovered by the scheduler holding them
+        assert!(new_state("localhost:50051")
+            .recover_held_jobs()
+            .await?
+            .is_empty());
+
+        // The restarted scheduler queues the held jobs again, in the order they were queued in
+        let state = new_state("localhost:50050");
+        let recovered: Vec<String> = state
+            .recover_held_jobs()
+            .await?
+            .into_iter()
+            .map(|job| job.job_id)
+            .collect();
+        assert_eq!(recovered, vec!["job-2", "job-1"]);
+        assert!(matches!(
+            state.get_job_status("job-1").await?.and_then(|s| s.status),
+            Some(Status::Queued(_))
+        ));
+
+        // Completed jobs are no longer recovered
+        state
+            .fail_unscheduled_job("job-2", "failed".to_owned())
+            .await?;
+        let state = new_state("localhost:50050");
+        assert_eq!(state.recover_held_jobs().await?.len(), 1);
+
+        Ok(())
+    }
+
     #[cfg(feature = "sled")]
     #[tokio::test]
     async fn test_sled_job_lifecycle() -> Result<()> {
@@ -843,4 +1449,31 @@ mod test {
 
         Ok(())
     }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_job_recovery() -> Result<()> {
+        test_job_recovery(make_sled_state()?, test_aggregation_plan(4).await).await?;
+        test_job_recovery(make_sled_state()?, test_join_plan(4).await).await?;
+
+        Ok(())
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_session_expiry() -> Result<()> {
+        test_session_expiry(make_sled_state()?).await
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_shared_tables() -> Result<()> {
+        test_shared_tables(make_sled_state()?).await
+    }
+
+    #[cfg(feature = "sled")]
+    #[tokio::test]
+    async fn test_sled_session_objects() -> Result<()> {
+        test_session_objects(make_sled_state()?).await
+    }
 }
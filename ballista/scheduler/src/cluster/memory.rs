@@ -16,8 +16,10 @@
 // under the License.
 
 use crate::cluster::{
-    reserve_slots_bias, reserve_slots_round_robin, ClusterState, JobState, JobStateEvent,
-    JobStateEventStream, JobStatus, TaskDistribution,
+    distribute_slots, expire_leases, lease_reservations, release_leases,
+    release_reservations, remove_executor_leases, reservable_slots,
+    resize_executor_slots, ClusterState, JobState, JobStateEvent, JobStateEventStream,
+    JobStatus, SessionObjectsUpdate, TaskDistribution,
 };
 use crate::state::execution_graph::ExecutionGraph;
 use crate::state::executor_manager::ExecutorReservation;
@@ -25,13 +27,17 @@ use async_trait::async_trait;
 use ballista_core::config::BallistaConfig;
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::serde::protobuf::{
-    executor_status, AvailableTaskSlots, ExecutorHeartbeat, ExecutorStatus,
-    ExecutorTaskSlots, FailedJob, QueuedJob,
+    executor_status, AvailableTaskSlots, CancelledJob, ExecutorHeartbeat, ExecutorStatus,
+    ExecutorTaskSlots, FailedJob, HeldJob, PartitionLocation, QueuedJob,
+    SchedulerHeartbeat, SessionObjects, SuccessfulJob, TableDefinition,
 };
-use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
+use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata, TaskResources};
+use dashmap::mapref::entry::Entry;
 use dashmap::DashMap;
+use datafusion::datasource::provider::TableProviderFactory;
 use datafusion::prelude::SessionContext;
 
+use crate::clock::{system_clock, Clock};
 use crate::cluster::event::ClusterEventSender;
 use crate::scheduler_server::{timestamp_millis, timestamp_secs, SessionBuilder};
 use crate::state::session_manager::create_datafusion_context;
@@ -43,9 +49,9 @@ use std::collections::{HashMap, HashSet};
 use std::ops::DerefMut;
 
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::debug;
 
-#[derive(Default)]
 pub struct InMemoryClusterState {
     /// Current available task slots for each executor
     task_slots: Mutex<ExecutorTaskSlots>,
@@ -53,6 +59,42 @@ pub struct InMemoryClusterState {
     executors: DashMap<String, ExecutorMetadata>,
     /// Last heartbeat received for each executor
     heartbeats: DashMap<String, ExecutorHeartbeat>,
+    /// Last heartbeat of each scheduler
+    scheduler_heartbeats: DashMap<String, SchedulerHeartbeat>,
+    /// Deadlines of the executors being decommissioned
+    decommissioning: DashMap<String, u64>,
+    /// Time after which reserved task slots return to the available task slots unless they
+    /// are filled with tasks or cancelled, `None` for reservations without lease
+    reservation_lease: Option<Duration>,
+    /// Clock of the heartbeats of registered executors and of the leases of reservations
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for InMemoryClusterState {
+    fn default() -> Self {
+        Self {
+            task_slots: Default::default(),
+            executors: Default::default(),
+            heartbeats: Default::default(),
+            scheduler_heartbeats: Default::default(),
+            decommissioning: Default::default(),
+            reservation_lease: None,
+            clock: system_clock(),
+        }
+    }
+}
+
+impl InMemoryClusterState {
+    /// Expire the reservations of task slots after `reservation_lease`
+    pub fn with_reservation_lease(mut self, reservation_lease: Option<Duration>) -> Self {
+        self.reservation_lease = reservation_lease;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
 }
 
 #[async_trait]
@@ -60,16 +102,17 @@ impl ClusterState for InMemoryClusterState {
     async fn reserve_slots(
         &self,
         num_slots: u32,
+        resources: TaskResources,
         distribution: TaskDistribution,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<ExecutorReservation>> {
         let mut guard = self.task_slots.lock();
 
-        let mut available_slots: Vec<&mut AvailableTaskSlots> = guard
+        let available_slots: Vec<&mut AvailableTaskSlots> = guard
             .task_slots
             .iter_mut()
             .filter_map(|data| {
-                (data.slots > 0
+                (reservable_slots(data, &resources) > 0
                     && executors
                         .as_ref()
                         .map(|executors| executors.contains(&data.executor_id))
@@ -78,14 +121,14 @@ impl ClusterState for InMemoryClusterState {
             })
             .collect();
 
-        available_slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
-
-        let reservations = match distribution {
-            TaskDistribution::Bias => reserve_slots_bias(available_slots, num_slots),
-            TaskDistribution::RoundRobin => {
-                reserve_slots_round_robin(available_slots, num_slots)
-            }
-        };
+        let mut reservations =
+            distribute_slots(available_slots, resources, num_slots, distribution);
+        lease_reservations(
+            &mut guard,
+            &mut reservations,
+            self.reservation_lease,
+            self.clock.now_millis(),
+        );
 
         Ok(reservations)
     }
@@ -93,6 +136,7 @@ impl ClusterState for InMemoryClusterState {
     async fn reserve_slots_exact(
         &self,
         num_slots: u32,
+        resources: TaskResources,
         distribution: TaskDistribution,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<ExecutorReservation>> {
@@ -100,11 +144,11 @@ impl ClusterState for InMemoryClusterState {
 
         let rollback = guard.clone();
 
-        let mut available_slots: Vec<&mut AvailableTaskSlots> = guard
+        let available_slots: Vec<&mut AvailableTaskSlots> = guard
             .task_slots
             .iter_mut()
             .filter_map(|data| {
-                (data.slots > 0
+                (reservable_slots(data, &resources) > 0
                     && executors
                         .as_ref()
                         .map(|executors| executors.contains(&data.executor_id))
@@ -113,19 +157,19 @@ impl ClusterState for InMemoryClusterState {
             })
             .collect();
 
-        available_slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
-
-        let reservations = match distribution {
-            TaskDistribution::Bias => reserve_slots_bias(available_slots, num_slots),
-            TaskDistribution::RoundRobin => {
-                reserve_slots_round_robin(available_slots, num_slots)
-            }
-        };
+        let mut reservations =
+            distribute_slots(available_slots, resources, num_slots, distribution);
 
         if reservations.len() as u32 != num_slots {
             *guard = rollback;
             Ok(vec![])
         } else {
+            lease_reservations(
+                &mut guard,
+                &mut reservations,
+                self.reservation_lease,
+                self.clock.now_millis(),
+            );
             Ok(reservations)
         }
     }
@@ -134,26 +178,27 @@ impl ClusterState for InMemoryClusterState {
         &self,
         reservations: Vec<ExecutorReservation>,
     ) -> Result<()> {
-        let mut increments = HashMap::new();
-        for ExecutorReservation { executor_id, .. } in reservations {
-            if let Some(inc) = increments.get_mut(&executor_id) {
-                *inc += 1;
-            } else {
-                increments.insert(executor_id, 1usize);
-            }
-        }
+        let mut guard = self.task_slots.lock();
 
+        release_reservations(&mut guard, reservations);
+
+        Ok(())
+    }
+
+    async fn release_reservation_leases(&self, lease_ids: HashSet<String>) -> Result<()> {
         let mut guard = self.task_slots.lock();
 
-        for executor_slots in guard.task_slots.iter_mut() {
-            if let Some(slots) = increments.get(&executor_slots.executor_id) {
-                executor_slots.slots += *slots as u32;
-            }
-        }
+        release_leases(&mut guard, &lease_ids);
 
         Ok(())
     }
 
+    async fn expire_reservation_leases(&self) -> Result<usize> {
+        let mut guard = self.task_slots.lock();
+
+        Ok(expire_leases(&mut guard, self.clock.now_millis()))
+    }
+
     async fn register_executor(
         &self,
         metadata: ExecutorMetadata,
@@ -161,15 +206,17 @@ impl ClusterState for InMemoryClusterState {
         reserve: bool,
     ) -> Result<Vec<ExecutorReservation>> {
         let executor_id = metadata.id.clone();
+        let resources = metadata.specification.available_resources();
 
         self.save_executor_metadata(metadata).await?;
         self.save_executor_heartbeat(ExecutorHeartbeat {
             executor_id: executor_id.clone(),
-            timestamp: timestamp_secs(),
+            timestamp: self.clock.now_secs(),
             metrics: vec![],
             status: Some(ExecutorStatus {
                 status: Some(executor_status::Status::Active(String::default())),
             }),
+            task_progress: vec![],
         })
         .await?;
 
@@ -183,16 +230,27 @@ impl ClusterState for InMemoryClusterState {
         {
             guard.task_slots.swap_remove(idx);
         }
+        remove_executor_leases(&mut guard, &executor_id);
 
         if reserve {
             let slots = std::mem::take(&mut spec.available_task_slots) as usize;
-            let reservations = (0..slots)
+            let mut reservations: Vec<ExecutorReservation> = (0..slots)
                 .map(|_| ExecutorReservation::new_free(executor_id.clone()))
                 .collect();
+            lease_reservations(
+                &mut guard,
+                &mut reservations,
+                self.reservation_lease,
+                self.clock.now_millis(),
+            );
 
             guard.task_slots.push(AvailableTaskSlots {
                 executor_id,
                 slots: 0,
+                memory_bytes: resources.memory_bytes,
+                vcores: resources.vcores,
+                withheld_slots: 0,
+                gpus: resources.gpus,
             });
 
             Ok(reservations)
@@ -200,6 +258,10 @@ impl ClusterState for InMemoryClusterState {
             guard.task_slots.push(AvailableTaskSlots {
                 executor_id,
                 slots: spec.available_task_slots,
+                memory_bytes: resources.memory_bytes,
+                vcores: resources.vcores,
+                withheld_slots: 0,
+                gpus: resources.gpus,
             });
 
             Ok(vec![])
@@ -233,6 +295,12 @@ impl ClusterState for InMemoryClusterState {
         Ok(())
     }
 
+    async fn resize_executor(&self, executor_id: &str, delta: i32) -> Result<()> {
+        let mut guard = self.task_slots.lock();
+
+        resize_executor_slots(&mut guard, executor_id, delta)
+    }
+
     async fn remove_executor(&self, executor_id: &str) -> Result<()> {
         {
             let mut guard = self.task_slots.lock();
@@ -244,13 +312,37 @@ impl ClusterState for InMemoryClusterState {
             {
                 guard.task_slots.swap_remove(idx);
             }
+            remove_executor_leases(&mut guard, executor_id);
         }
 
         self.heartbeats.remove(executor_id);
+        self.decommissioning.remove(executor_id);
 
         Ok(())
     }
 
+    async fn decommission_executor(
+        &self,
+        executor_id: &str,
+        deadline: u64,
+    ) -> Result<bool> {
+        Ok(match self.decommissioning.entry(executor_id.to_owned()) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(deadline);
+                true
+            }
+        })
+    }
+
+    async fn decommissioning_executors(&self) -> Result<HashMap<String, u64>> {
+        Ok(self
+            .decommissioning
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect())
+    }
+
     fn executor_heartbeats(&self) -> HashMap<String, ExecutorHeartbeat> {
         self.heartbeats
             .iter()
@@ -261,6 +353,23 @@ impl ClusterState for InMemoryClusterState {
     fn get_executor_heartbeat(&self, executor_id: &str) -> Option<ExecutorHeartbeat> {
         self.heartbeats.get(executor_id).map(|r| r.value().clone())
     }
+
+    async fn save_scheduler_heartbeat(
+        &self,
+        heartbeat: SchedulerHeartbeat,
+    ) -> Result<()> {
+        self.scheduler_heartbeats
+            .insert(heartbeat.scheduler_id.clone(), heartbeat);
+        Ok(())
+    }
+
+    async fn scheduler_heartbeats(&self) -> Result<Vec<SchedulerHeartbeat>> {
+        Ok(self
+            .scheduler_heartbeats
+            .iter()
+            .map(|r| r.value().clone())
+            .collect())
+    }
 }
 
 /// Implementation of `JobState` which keeps all state in memory. If using `InMemoryJobState`
@@ -273,10 +382,16 @@ pub struct InMemoryJobState {
     queued_jobs: DashMap<String, (String, u64)>,
     /// In-memory store of running job statuses. Map from Job ID -> JobStatus
     running_jobs: DashMap<String, JobStatus>,
-    /// Active ballista sessions
-    sessions: DashMap<String, Arc<SessionContext>>,
+    /// Active ballista sessions with the time they were last accessed in seconds
+    sessions: DashMap<String, (Arc<SessionContext>, u64)>,
+    /// Tables shared by all sessions by name
+    tables: DashMap<String, TableDefinition>,
+    /// Tables and prepared statements of the sessions
+    session_objects: DashMap<String, SessionObjects>,
     /// `SessionBuilder` for building DataFusion `SessionContext` from `BallistaConfig`
     session_builder: SessionBuilder,
+    /// Table factories by file type registered with every session
+    table_factories: HashMap<String, Arc<dyn TableProviderFactory>>,
     /// Sender of job events
     job_event_sender: ClusterEventSender<JobStateEvent>,
 }
@@ -289,10 +404,22 @@ impl InMemoryJobState {
             queued_jobs: Default::default(),
             running_jobs: Default::default(),
             sessions: Default::default(),
+            tables: Default::default(),
+            session_objects: Default::default(),
             session_builder,
+            table_factories: HashMap::new(),
             job_event_sender: ClusterEventSender::new(100),
         }
     }
+
+    /// Create the tables of the file types of `table_factories` with them in every session
+    pub fn with_table_factories(
+        mut self,
+        table_factories: HashMap<String, Arc<dyn TableProviderFactory>>,
+    ) -> Self {
+        self.table_factories = table_factories;
+        self
+    }
 }
 
 #[async_trait]
@@ -359,7 +486,9 @@ impl JobState for InMemoryJobState {
         // If job is either successful or failed, save to completed jobs
         if matches!(
             status.status,
-            Some(Status::Successful(_)) | Some(Status::Failed(_))
+            Some(Status::Successful(_))
+                | Some(Status::Failed(_))
+                | Some(Status::Cancelled(_))
         ) {
             self.completed_jobs
                 .insert(job_id.to_string(), (status, Some(graph.clone())));
@@ -378,19 +507,36 @@ impl JobState for InMemoryJobState {
 
     async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>> {
         self.sessions
-            .get(session_id)
-            .map(|sess| sess.clone())
+            .get_mut(session_id)
+            .map(|mut entry| {
+                let (session, last_accessed) = entry.value_mut();
+                *last_accessed = timestamp_secs();
+                session.clone()
+            })
             .ok_or_else(|| {
                 BallistaError::General(format!("No session for {session_id} found"))
             })
     }
 
+    fn default_session(&self) -> Result<Arc<SessionContext>> {
+        Ok(create_datafusion_context(
+            &BallistaConfig::new()?,
+            self.session_builder,
+            &self.table_factories,
+        ))
+    }
+
     async fn create_session(
         &self,
         config: &BallistaConfig,
     ) -> Result<Arc<SessionContext>> {
-        let session = create_datafusion_context(config, self.session_builder);
-        self.sessions.insert(session.session_id(), session.clone());
+        let session = create_datafusion_context(
+            config,
+            self.session_builder,
+            &self.table_factories,
+        );
+        self.sessions
+            .insert(session.session_id(), (session.clone(), timestamp_secs()));
 
         Ok(session)
     }
@@ -400,13 +546,95 @@ impl JobState for InMemoryJobState {
         session_id: &str,
         config: &BallistaConfig,
     ) -> Result<Arc<SessionContext>> {
-        let session = create_datafusion_context(config, self.session_builder);
+        let session = create_datafusion_context(
+            config,
+            self.session_builder,
+            &self.table_factories,
+        );
         self.sessions
-            .insert(session_id.to_string(), session.clone());
+            .insert(session_id.to_string(), (session.clone(), timestamp_secs()));
 
         Ok(session)
     }
 
+    async fn remove_session(&self, session_id: &str) -> Result<bool> {
+        self.session_objects.remove(session_id);
+        Ok(self.sessions.remove(session_id).is_some())
+    }
+
+    async fn remove_idle_sessions(
+        &self,
+        accessed_before: u64,
+        keep: &HashSet<String>,
+    ) -> Result<Vec<String>> {
+        let mut removed = vec![];
+        self.sessions.retain(|session_id, (_, last_accessed)| {
+            let idle = *last_accessed < accessed_before && !keep.contains(session_id);
+            if idle {
+                removed.push(session_id.clone());
+            }
+            !idle
+        });
+        for session_id in &removed {
+            self.session_objects.remove(session_id);
+        }
+        Ok(removed)
+    }
+
+    async fn save_table(&self, table: TableDefinition) -> Result<()> {
+        self.tables.insert(table.name.clone(), table);
+        Ok(())
+    }
+
+    async fn save_new_table(&self, table: TableDefinition) -> Result<bool> {
+        // The entry is locked until the table is saved
+        match self.tables.entry(table.name.clone()) {
+            Entry::Occupied(entry) if entry.get().dropped_at == 0 => Ok(false),
+            Entry::Occupied(mut entry) => {
+                entry.insert(table);
+                Ok(true)
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(table);
+                Ok(true)
+            }
+        }
+    }
+
+    async fn get_tables(&self) -> Result<Vec<TableDefinition>> {
+        Ok(self
+            .tables
+            .iter()
+            .map(|table| table.value().clone())
+            .collect())
+    }
+
+    async fn remove_table(&self, name: &str) -> Result<()> {
+        self.tables.remove(name);
+        Ok(())
+    }
+
+    async fn update_session_objects(
+        &self,
+        session_id: &str,
+        update: SessionObjectsUpdate,
+    ) -> Result<bool> {
+        // The entry is locked until the update completes
+        let mut objects = self
+            .session_objects
+            .entry(session_id.to_owned())
+            .or_default();
+        Ok(update(objects.value_mut()))
+    }
+
+    async fn get_session_objects(&self, session_id: &str) -> Result<SessionObjects> {
+        Ok(self
+            .session_objects
+            .get(session_id)
+            .map(|objects| objects.value().clone())
+            .unwrap_or_default())
+    }
+
     async fn job_state_events(&self) -> Result<JobStateEventStream> {
         Ok(Box::pin(self.job_event_sender.subscribe()))
     }
@@ -432,10 +660,22 @@ impl JobState for InMemoryJobState {
         job_name: &str,
         queued_at: u64,
     ) -> Result<()> {
-        self.queued_jobs
-            .insert(job_id.to_string(), (job_name.to_string(), queued_at));
-
-        Ok(())
+        if self.running_jobs.contains_key(job_id)
+            || self.completed_jobs.contains_key(job_id)
+        {
+            return Err(BallistaError::General(format!(
+                "Job {job_id} already exists"
+            )));
+        }
+        match self.queued_jobs.entry(job_id.to_string()) {
+            Entry::Occupied(_) => Err(BallistaError::General(format!(
+                "Job {job_id} already exists"
+            ))),
+            Entry::Vacant(entry) => {
+                entry.insert((job_name.to_string(), queued_at));
+                Ok(())
+            }
+        }
     }
 
     async fn fail_unscheduled_job(&self, job_id: &str, reason: String) -> Result<()> {
@@ -464,14 +704,88 @@ impl JobState for InMemoryJobState {
             )))
         }
     }
+
+    async fn cancel_unscheduled_job(&self, job_id: &str) -> Result<()> {
+        if let Some((job_id, (job_name, queued_at))) = self.queued_jobs.remove(job_id) {
+            self.completed_jobs.insert(
+                job_id.clone(),
+                (
+                    JobStatus {
+                        job_id,
+                        job_name,
+                        status: Some(Status::Cancelled(CancelledJob {
+                            queued_at,
+                            started_at: 0,
+                            ended_at: timestamp_millis(),
+                        })),
+                    },
+                    None,
+                ),
+            );
+
+            Ok(())
+        } else {
+            Err(BallistaError::Internal(format!(
+                "Could not cancel unscheduled job {job_id}, job not found in queued jobs"
+            )))
+        }
+    }
+
+    async fn save_held_job(&self, _job: HeldJob) -> Result<()> {
+        // The held jobs are lost with the state when the scheduler restarts
+        Ok(())
+    }
+
+    async fn recover_held_jobs(&self) -> Result<Vec<HeldJob>> {
+        Ok(vec![])
+    }
+
+    async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        partition_location: Vec<PartitionLocation>,
+        shared_job_id: &str,
+    ) -> Result<()> {
+        if let Some((job_id, (job_name, queued_at))) = self.queued_jobs.remove(job_id) {
+            let now = timestamp_millis();
+            self.completed_jobs.insert(
+                job_id.clone(),
+                (
+                    JobStatus {
+                        job_id,
+                        job_name,
+                        status: Some(Status::Successful(SuccessfulJob {
+                            partition_location,
+                            queued_at,
+                            started_at: now,
+                            ended_at: now,
+                            analyzed_plan: String::new(),
+                            results_expired: false,
+                            shared_job_id: shared_job_id.to_owned(),
+                        })),
+                    },
+                    None,
+                ),
+            );
+
+            Ok(())
+        } else {
+            Err(BallistaError::Internal(format!(
+                "Could not complete unscheduled job {job_id}, job not found in queued jobs"
+            )))
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::cluster::memory::{InMemoryClusterState, InMemoryJobState};
     use crate::cluster::test::{
-        test_executor_registration, test_fuzz_reservations, test_job_lifecycle,
-        test_job_planning_failure, test_reservation,
+        test_duplicate_job_id, test_executor_decommissioning, test_executor_registration,
+        test_executor_resize, test_fuzz_reservations, test_job_lifecycle,
+        test_job_planning_failure, test_reservation, test_reservation_leases,
+        test_resource_reservation, test_session_expiry, test_session_objects,
+        test_shared_tables,
     };
     use crate::cluster::TaskDistribution;
     use crate::test_utils::{
@@ -479,6 +793,7 @@ mod test {
     };
     use ballista_core::error::Result;
     use ballista_core::utils::default_session_builder;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_in_memory_registration() -> Result<()> {
@@ -497,6 +812,40 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_in_memory_resource_reserve() -> Result<()> {
+        test_resource_reservation(
+            InMemoryClusterState::default(),
+            TaskDistribution::Bias,
+        )
+        .await?;
+        test_resource_reservation(
+            InMemoryClusterState::default(),
+            TaskDistribution::RoundRobin,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_reservation_leases() -> Result<()> {
+        test_reservation_leases(
+            InMemoryClusterState::default().with_reservation_lease(Some(Duration::ZERO)),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_executor_resize() -> Result<()> {
+        test_executor_resize(InMemoryClusterState::default()).await
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_executor_decommissioning() -> Result<()> {
+        test_executor_decommissioning(InMemoryClusterState::default()).await
+    }
+
     #[tokio::test]
     async fn test_in_memory_fuzz_reserve() -> Result<()> {
         test_fuzz_reservations(
@@ -540,6 +889,15 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_in_memory_duplicate_job_id() -> Result<()> {
+        test_duplicate_job_id(
+            InMemoryJobState::new("", default_session_builder),
+            test_aggregation_plan(4).await,
+        )
+        .await
+    }
+
     #[tokio::test]
     async fn test_in_memory_job_planning_failure() -> Result<()> {
         test_job_planning_failure(
@@ -560,4 +918,19 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_in_memory_session_expiry() -> Result<()> {
+        test_session_expiry(InMemoryJobState::new("", default_session_builder)).await
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_shared_tables() -> Result<()> {
+        test_shared_tables(InMemoryJobState::new("", default_session_builder)).await
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_session_objects() -> Result<()> {
+        test_session_objects(InMemoryJobState::new("", default_session_builder)).await
+    }
 }
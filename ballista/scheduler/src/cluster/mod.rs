@@ -24,6 +24,7 @@ pub mod storage;
 #[allow(clippy::uninlined_format_args)]
 pub mod test;
 
+use crate::clock::{system_clock, Clock};
 use crate::cluster::kv::KeyValueState;
 use crate::cluster::memory::{InMemoryClusterState, InMemoryJobState};
 use crate::cluster::storage::etcd::EtcdClient;
@@ -35,10 +36,13 @@ use crate::state::execution_graph::ExecutionGraph;
 use crate::state::executor_manager::ExecutorReservation;
 use ballista_core::config::BallistaConfig;
 use ballista_core::error::{BallistaError, Result};
-use ballista_core::serde::protobuf::{AvailableTaskSlots, ExecutorHeartbeat, JobStatus};
-use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
+use ballista_core::serde::protobuf::{
+    AvailableTaskSlots, ExecutorHeartbeat, ExecutorTaskSlots, HeldJob, JobStatus,
+    PartitionLocation, ReservationLease, SchedulerHeartbeat, SessionObjects,
+    TableDefinition,
+};
+use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata, TaskResources};
 use ballista_core::serde::BallistaCodec;
-use ballista_core::utils::default_session_builder;
 use clap::ArgEnum;
 use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
@@ -49,6 +53,7 @@ use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Duration;
 
 // an enum used to configure the backend
 // needs to be visible to code generated by configure_me
@@ -110,8 +115,35 @@ impl BallistaCluster {
         session_builder: SessionBuilder,
         codec: BallistaCodec<T, U>,
     ) -> Self {
-        let kv_state =
-            Arc::new(KeyValueState::new(scheduler, store, codec, session_builder));
+        Self::new_kv_with_lease(
+            store,
+            scheduler,
+            session_builder,
+            codec,
+            None,
+            system_clock(),
+        )
+    }
+
+    /// Cluster state kept in a `KeyValueStore` whose reservations of task slots expire after
+    /// `reservation_lease` on `clock` unless they are filled with tasks or cancelled before
+    pub fn new_kv_with_lease<
+        S: KeyValueStore,
+        T: 'static + AsLogicalPlan,
+        U: 'static + AsExecutionPlan,
+    >(
+        store: S,
+        scheduler: impl Into<String>,
+        session_builder: SessionBuilder,
+        codec: BallistaCodec<T, U>,
+        reservation_lease: Option<Duration>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let kv_state = Arc::new(
+            KeyValueState::new(scheduler, store, codec, session_builder)
+                .with_reservation_lease(reservation_lease)
+                .with_clock(clock),
+        );
         Self {
             cluster_state: kv_state.clone(),
             job_state: kv_state,
@@ -120,6 +152,7 @@ impl BallistaCluster {
 
     pub async fn new_from_config(config: &SchedulerConfig) -> Result<Self> {
         let scheduler = config.scheduler_name();
+        let reservation_lease = config.reservation_lease();
 
         match &config.cluster_storage {
             #[cfg(feature = "etcd")]
@@ -132,11 +165,9 @@ impl BallistaCluster {
                         ))
                     })?;
 
-                Ok(Self::new_kv(
+                Ok(Self::new_kv_from_config(
                     EtcdClient::new(config.namespace.clone(), etcd),
-                    scheduler,
-                    default_session_builder,
-                    BallistaCodec::default(),
+                    config,
                 ))
             }
             #[cfg(not(feature = "etcd"))]
@@ -151,22 +182,12 @@ impl BallistaCluster {
                     info!("Initializing Sled database in directory {}", dir);
                     let sled = SledClient::try_new(dir)?;
 
-                    Ok(Self::new_kv(
-                        sled,
-                        scheduler,
-                        default_session_builder,
-                        BallistaCodec::default(),
-                    ))
+                    Ok(Self::new_kv_from_config(sled, config))
                 } else {
                     info!("Initializing Sled database in temp directory");
                     let sled = SledClient::try_new_temporary()?;
 
-                    Ok(Self::new_kv(
-                        sled,
-                        scheduler,
-                        default_session_builder,
-                        BallistaCodec::default(),
-                    ))
+                    Ok(Self::new_kv_from_config(sled, config))
                 }
             }
             #[cfg(not(feature = "sled"))]
@@ -175,13 +196,40 @@ impl BallistaCluster {
                     "build the scheduler with the `sled` feature to use the sled config backend"
                 )
             }
-            ClusterStorageConfig::Memory => Ok(BallistaCluster::new_memory(
-                scheduler,
-                default_session_builder,
+            ClusterStorageConfig::Memory => Ok(BallistaCluster::new(
+                Arc::new(
+                    InMemoryClusterState::default()
+                        .with_reservation_lease(reservation_lease)
+                        .with_clock(config.clock.clone()),
+                ),
+                Arc::new(
+                    InMemoryJobState::new(scheduler, config.session_builder)
+                        .with_table_factories(config.plugins.table_factories()),
+                ),
             )),
         }
     }
 
+    /// Cluster state kept in `store`, building the sessions with the session builder and
+    /// the table factories of the plugins of `config`
+    fn new_kv_from_config<S: KeyValueStore>(store: S, config: &SchedulerConfig) -> Self {
+        let kv_state = Arc::new(
+            KeyValueState::new(
+                config.scheduler_name(),
+                store,
+                config.codec.clone(),
+                config.session_builder,
+            )
+            .with_table_factories(config.plugins.table_factories())
+            .with_reservation_lease(config.reservation_lease())
+            .with_clock(config.clock.clone()),
+        );
+        Self {
+            cluster_state: kv_state.clone(),
+            job_state: kv_state,
+        }
+    }
+
     pub fn cluster_state(&self) -> Arc<dyn ClusterState> {
         self.cluster_state.clone()
     }
@@ -206,21 +254,26 @@ pub trait ClusterState: Send + Sync + 'static {
     /// Reserve up to `num_slots` executor task slots. If not enough task slots are available, reserve
     /// as many as possible.
     ///
+    /// Each reserved slot also reserves `resources` on its executor, so a slot is only available
+    /// on executors which still have enough memory and cores left.
+    ///
     /// If `executors` is provided, only reserve slots of the specified executor IDs
     async fn reserve_slots(
         &self,
         num_slots: u32,
+        resources: TaskResources,
         distribution: TaskDistribution,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<ExecutorReservation>>;
 
-    /// Reserve exactly `num_slots` executor task slots. If not enough task slots are available,
-    /// returns an empty vec
+    /// Reserve exactly `num_slots` executor task slots, each of them reserving `resources` as well.
+    /// If not enough task slots are available, returns an empty vec
     ///
     /// If `executors` is provided, only reserve slots of the specified executor IDs
     async fn reserve_slots_exact(
         &self,
         num_slots: u32,
+        resources: TaskResources,
         distribution: TaskDistribution,
         executors: Option<HashSet<String>>,
     ) -> Result<Vec<ExecutorReservation>>;
@@ -228,11 +281,22 @@ pub trait ClusterState: Send + Sync + 'static {
     /// Cancel the specified reservations. This will make reserved executor slots available to other
     /// tasks.
     /// This operations should be atomic. Either all reservations are cancelled or none are
+    ///
+    /// The slots of reservations whose lease expired were already returned and are skipped
     async fn cancel_reservations(
         &self,
         reservations: Vec<ExecutorReservation>,
     ) -> Result<()>;
 
+    /// Release the leases of reservations which were filled with tasks. Their task slots stay
+    /// reserved until the tasks complete
+    async fn release_reservation_leases(&self, lease_ids: HashSet<String>) -> Result<()>;
+
+    /// Return the task slots of the reservations whose lease expired to the available task
+    /// slots, as the scheduler holding them neither launched tasks on them nor cancelled them
+    /// in time, e.g. because it crashed. Returns the number of expired reservations
+    async fn expire_reservation_leases(&self) -> Result<usize>;
+
     /// Register a new executor in the cluster. If `reserve` is true, then the executors task slots
     /// will be reserved and returned in the response and none of the new executors task slots will be
     /// available to other tasks.
@@ -252,14 +316,40 @@ pub trait ClusterState: Send + Sync + 'static {
     /// Save the executor heartbeat
     async fn save_executor_heartbeat(&self, heartbeat: ExecutorHeartbeat) -> Result<()>;
 
-    /// Remove the executor from the cluster
+    /// Add `delta` task slots to a registered executor, or remove task slots if `delta` is
+    /// negative. Task slots removed while they are reserved are not made available again when
+    /// their reservations are cancelled
+    async fn resize_executor(&self, executor_id: &str, delta: i32) -> Result<()>;
+
+    /// Remove the executor from the cluster, which ends its decommissioning
     async fn remove_executor(&self, executor_id: &str) -> Result<()>;
 
+    /// Mark an executor as being decommissioned, so that no scheduler sharing the cluster
+    /// state offers it tasks anymore. `deadline` is the time in seconds since the epoch after
+    /// which it is removed even if jobs still use it, 0 for none. Returns false if it was
+    /// being decommissioned already
+    async fn decommission_executor(
+        &self,
+        executor_id: &str,
+        deadline: u64,
+    ) -> Result<bool>;
+
+    /// The executors being decommissioned, with their deadlines
+    async fn decommissioning_executors(&self) -> Result<HashMap<String, u64>>;
+
     /// Return a map of the last seen heartbeat for all active executors
     fn executor_heartbeats(&self) -> HashMap<String, ExecutorHeartbeat>;
 
     /// Get executor heartbeat for the provided executor ID. Return None if the executor does not exist
     fn get_executor_heartbeat(&self, executor_id: &str) -> Option<ExecutorHeartbeat>;
+
+    /// Save the heartbeat of a scheduler sharing the cluster with other schedulers. This will
+    /// overwrite the previous heartbeat of the scheduler
+    async fn save_scheduler_heartbeat(&self, heartbeat: SchedulerHeartbeat)
+        -> Result<()>;
+
+    /// Return the last heartbeat of every scheduler which has saved one
+    async fn scheduler_heartbeats(&self) -> Result<Vec<SchedulerHeartbeat>>;
 }
 
 /// Events related to the state of jobs. Implementations may or may not support all event types.
@@ -303,12 +393,16 @@ pub enum JobStateEvent {
 /// by any schedulers with a shared `ClusterState`
 pub type JobStateEventStream = Pin<Box<dyn Stream<Item = JobStateEvent> + Send>>;
 
+/// Update of the tables and prepared statements of a session, returns whether it changed them
+pub type SessionObjectsUpdate = Box<dyn FnOnce(&mut SessionObjects) -> bool + Send>;
+
 /// A trait that contains the necessary methods for persisting state related to executing jobs
 #[tonic::async_trait]
 pub trait JobState: Send + Sync {
     /// Accept job into  a scheduler's job queue. This should be called when a job is
     /// received by the scheduler but before it is planned and may or may not be saved
-    /// in global state
+    /// in global state. Returns an error if a job with the same ID exists already, on this
+    /// or any other scheduler sharing the state.
     async fn accept_job(
         &self,
         job_id: &str,
@@ -341,9 +435,33 @@ pub trait JobState: Send + Sync {
     /// during planning (and does not yet have an `ExecutionGraph`)
     async fn fail_unscheduled_job(&self, job_id: &str, reason: String) -> Result<()>;
 
+    /// Mark a job which has not been submitted as cancelled. This should be called if a job
+    /// is cancelled while it is queued (and does not yet have an `ExecutionGraph`)
+    async fn cancel_unscheduled_job(&self, job_id: &str) -> Result<()>;
+
+    /// Mark a job which has not been submitted as successful with the given result partitions.
+    /// This should be called if the results of a job are served without executing it (and it
+    /// does not have an `ExecutionGraph`)
+    async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        partition_location: Vec<PartitionLocation>,
+        shared_job_id: &str,
+    ) -> Result<()>;
+
     /// Delete a job from the global state
     async fn remove_job(&self, job_id: &str) -> Result<()>;
 
+    /// Save a queued job held back by the maximum number of concurrent jobs, so that the
+    /// scheduler holds it again after a restart. The saved job is removed once the job is
+    /// submitted or completes without being submitted.
+    async fn save_held_job(&self, job: HeldJob) -> Result<()>;
+
+    /// Get the held jobs saved by this scheduler whose status is still queued, which are
+    /// queued again, ordered by the time they were queued at. The saved jobs which are no
+    /// longer queued are removed.
+    async fn recover_held_jobs(&self) -> Result<Vec<HeldJob>>;
+
     /// Attempt to acquire ownership of the given job. If the job is still in a running state
     /// and is successfully acquired by the caller, return the current `ExecutionGraph`,
     /// otherwise return `None`
@@ -357,6 +475,10 @@ pub trait JobState: Send + Sync {
     /// session does not exist
     async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>>;
 
+    /// Create a session with the default configuration which is not saved, showing the
+    /// settings every session starts from
+    fn default_session(&self) -> Result<Arc<SessionContext>>;
+
     /// Create a new saved session
     async fn create_session(
         &self,
@@ -369,10 +491,251 @@ pub trait JobState: Send + Sync {
         session_id: &str,
         config: &BallistaConfig,
     ) -> Result<Arc<SessionContext>>;
+
+    /// Remove a saved session, returns whether it existed
+    async fn remove_session(&self, session_id: &str) -> Result<bool>;
+
+    /// Remove the saved sessions last accessed before `accessed_before`, in seconds since the
+    /// epoch, except for the sessions in `keep`. Returns the IDs of the removed sessions.
+    async fn remove_idle_sessions(
+        &self,
+        accessed_before: u64,
+        keep: &HashSet<String>,
+    ) -> Result<Vec<String>>;
+
+    /// Save a table shared by all sessions, replacing any saved table of the same name
+    async fn save_table(&self, table: TableDefinition) -> Result<()>;
+
+    /// Save a new table shared by all sessions unless a table of the same name which was
+    /// not dropped is saved. The check and the save are atomic, so that only one of the
+    /// schedulers creating a table of the same name concurrently saves it. Returns whether
+    /// the table was saved.
+    async fn save_new_table(&self, table: TableDefinition) -> Result<bool>;
+
+    /// Get the saved tables shared by all sessions
+    async fn get_tables(&self) -> Result<Vec<TableDefinition>>;
+
+    /// Remove a saved table, if it exists
+    async fn remove_table(&self, name: &str) -> Result<()>;
+
+    /// Update the tables and prepared statements of a saved session, which are removed with
+    /// the session. The update is atomic, so that the concurrent updates of the schedulers
+    /// serving the session are not lost. Returns whether `update` changed them.
+    async fn update_session_objects(
+        &self,
+        session_id: &str,
+        update: SessionObjectsUpdate,
+    ) -> Result<bool>;
+
+    /// Get the tables and prepared statements of a session, none if it saved none
+    async fn get_session_objects(&self, session_id: &str) -> Result<SessionObjects>;
+}
+
+/// Number of task slots requiring `resources` which can still be reserved on an executor
+pub(crate) fn reservable_slots(
+    slots: &AvailableTaskSlots,
+    resources: &TaskResources,
+) -> u32 {
+    let by_memory = slots
+        .memory_bytes
+        .checked_div(resources.memory_bytes)
+        .map(|n| n.min(u32::MAX as u64) as u32)
+        .unwrap_or(u32::MAX);
+    let by_vcores = slots
+        .vcores
+        .checked_div(resources.vcores)
+        .unwrap_or(u32::MAX);
+    let by_gpus = slots.gpus.checked_div(resources.gpus).unwrap_or(u32::MAX);
+
+    slots.slots.min(by_memory).min(by_vcores).min(by_gpus)
+}
+
+fn take_slots(slots: &mut AvailableTaskSlots, resources: &TaskResources, n: u32) {
+    slots.slots -= n;
+    slots.memory_bytes = slots
+        .memory_bytes
+        .saturating_sub(resources.memory_bytes.saturating_mul(n as u64));
+    slots.vcores = slots
+        .vcores
+        .saturating_sub(resources.vcores.saturating_mul(n));
+    slots.gpus = slots.gpus.saturating_sub(resources.gpus.saturating_mul(n));
+}
+
+/// Give each reservation taken from the available task slots a lease expiring
+/// `reservation_lease` after `now` (in milliseconds), if any
+pub(crate) fn lease_reservations(
+    slots: &mut ExecutorTaskSlots,
+    reservations: &mut [ExecutorReservation],
+    reservation_lease: Option<Duration>,
+    now: u64,
+) {
+    let reservation_lease = match reservation_lease {
+        Some(reservation_lease) => reservation_lease,
+        None => return,
+    };
+    let expires_at = now + reservation_lease.as_millis() as u64;
+    for reservation in reservations.iter_mut() {
+        let lease_id = uuid::Uuid::new_v4().to_string();
+        slots.leases.push(ReservationLease {
+            lease_id: lease_id.clone(),
+            executor_id: reservation.executor_id.clone(),
+            memory_bytes: reservation.resources.memory_bytes,
+            vcores: reservation.resources.vcores,
+            expires_at,
+            gpus: reservation.resources.gpus,
+        });
+        reservation.lease_id = Some(lease_id);
+    }
+}
+
+/// Drop the leases of the reservations filled with tasks
+pub(crate) fn release_leases(slots: &mut ExecutorTaskSlots, lease_ids: &HashSet<String>) {
+    slots
+        .leases
+        .retain(|lease| !lease_ids.contains(&lease.lease_id));
+}
+
+/// Return the task slots and resources of the leases expired at `now` (in milliseconds) to
+/// the available task slots. Returns the number of expired leases
+pub(crate) fn expire_leases(slots: &mut ExecutorTaskSlots, now: u64) -> usize {
+    let (expired, leases): (Vec<ReservationLease>, Vec<ReservationLease>) =
+        std::mem::take(&mut slots.leases)
+            .into_iter()
+            .partition(|lease| lease.expires_at <= now);
+    slots.leases = leases;
+
+    let num_expired = expired.len();
+    let reservations = expired
+        .into_iter()
+        .map(|lease| {
+            ExecutorReservation::new_free(lease.executor_id).with_resources(
+                TaskResources::new(lease.memory_bytes, lease.vcores)
+                    .with_gpus(lease.gpus),
+            )
+        })
+        .collect();
+    release_reservations(slots, reservations);
+
+    num_expired
+}
+
+/// Drop the leases of the reservations of an executor whose task slots are reset
+pub(crate) fn remove_executor_leases(slots: &mut ExecutorTaskSlots, executor_id: &str) {
+    slots
+        .leases
+        .retain(|lease| lease.executor_id != executor_id);
+}
+
+/// Return the task slots and resources held by `reservations` to the available task slots.
+/// Reservations whose lease is gone already returned their task slots when it expired.
+pub(crate) fn release_reservations(
+    slots: &mut ExecutorTaskSlots,
+    reservations: Vec<ExecutorReservation>,
+) {
+    let mut increments: HashMap<String, (u32, u64, u32, u32)> = HashMap::new();
+    for ExecutorReservation {
+        executor_id,
+        resources,
+        lease_id,
+        ..
+    } in reservations
+    {
+        if let Some(lease_id) = lease_id {
+            match slots
+                .leases
+                .iter()
+                .position(|lease| lease.lease_id == lease_id)
+            {
+                Some(idx) => {
+                    slots.leases.swap_remove(idx);
+                }
+                None => continue,
+            }
+        }
+        let inc = increments.entry(executor_id).or_default();
+        inc.0 += 1;
+        inc.1 = inc.1.saturating_add(resources.memory_bytes);
+        inc.2 = inc.2.saturating_add(resources.vcores);
+        inc.3 = inc.3.saturating_add(resources.gpus);
+    }
+
+    for executor_slots in slots.task_slots.iter_mut() {
+        if let Some((slots, memory_bytes, vcores, gpus)) =
+            increments.get(&executor_slots.executor_id)
+        {
+            let withheld = (*slots).min(executor_slots.withheld_slots);
+            executor_slots.withheld_slots -= withheld;
+            executor_slots.slots += *slots - withheld;
+            executor_slots.memory_bytes =
+                executor_slots.memory_bytes.saturating_add(*memory_bytes);
+            executor_slots.vcores = executor_slots.vcores.saturating_add(*vcores);
+            executor_slots.gpus = executor_slots.gpus.saturating_add(*gpus);
+        }
+    }
+}
+
+/// Add `delta` task slots to the available task slots of an executor, or remove them if
+/// `delta` is negative. Task slots which cannot be removed as they are reserved are withheld
+/// from the reservations returning them, the task slots added first make up for these.
+pub(crate) fn resize_executor_slots(
+    slots: &mut ExecutorTaskSlots,
+    executor_id: &str,
+    delta: i32,
+) -> Result<()> {
+    let executor_slots = slots
+        .task_slots
+        .iter_mut()
+        .find(|slots| slots.executor_id == executor_id)
+        .ok_or_else(|| {
+            BallistaError::Internal(format!(
+                "No task slots found for executor {executor_id}"
+            ))
+        })?;
+
+    if delta >= 0 {
+        let added = delta as u32;
+        let restored = added.min(executor_slots.withheld_slots);
+        executor_slots.withheld_slots -= restored;
+        executor_slots.slots += added - restored;
+    } else {
+        let removed = delta.unsigned_abs();
+        let taken = removed.min(executor_slots.slots);
+        executor_slots.slots -= taken;
+        executor_slots.withheld_slots += removed - taken;
+    }
+
+    Ok(())
+}
+
+/// Reserve up to `n` task slots requiring `resources` of the available task slots of the
+/// executors, following the task distribution policy
+pub(crate) fn distribute_slots(
+    mut slots: Vec<&mut AvailableTaskSlots>,
+    resources: TaskResources,
+    n: u32,
+    distribution: TaskDistribution,
+) -> Vec<ExecutorReservation> {
+    match distribution {
+        TaskDistribution::Bias => {
+            slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
+            reserve_slots_bias(slots, resources, n)
+        }
+        TaskDistribution::RoundRobin => {
+            slots.sort_by(|a, b| Ord::cmp(&b.slots, &a.slots));
+            reserve_slots_round_robin(slots, resources, n)
+        }
+        TaskDistribution::BinPack => {
+            // The busiest executors first, so that idle executors stay idle
+            slots.sort_by_key(|slots| reservable_slots(slots, &resources));
+            reserve_slots_bias(slots, resources, n)
+        }
+        TaskDistribution::Spread => reserve_slots_spread(slots, resources, n),
+    }
 }
 
-pub(crate) fn reserve_slots_bias(
+fn reserve_slots_bias(
     mut slots: Vec<&mut AvailableTaskSlots>,
+    resources: TaskResources,
     mut n: u32,
 ) -> Vec<ExecutorReservation> {
     let mut reservations = Vec::with_capacity(n as usize);
@@ -381,13 +744,15 @@ pub(crate) fn reserve_slots_bias(
 
     while n > 0 {
         if let Some(executor) = iter.next() {
-            let take = executor.slots.min(n);
+            let take = reservable_slots(executor, &resources).min(n);
             for _ in 0..take {
-                reservations
-                    .push(ExecutorReservation::new_free(executor.executor_id.clone()));
+                reservations.push(
+                    ExecutorReservation::new_free(executor.executor_id.clone())
+                        .with_resources(resources),
+                );
             }
 
-            executor.slots -= take;
+            take_slots(executor, &resources, take);
             n -= take;
         } else {
             break;
@@ -397,8 +762,9 @@ pub(crate) fn reserve_slots_bias(
     reservations
 }
 
-pub(crate) fn reserve_slots_round_robin(
+fn reserve_slots_round_robin(
     mut slots: Vec<&mut AvailableTaskSlots>,
+    resources: TaskResources,
     mut n: u32,
 ) -> Vec<ExecutorReservation> {
     let mut reservations = Vec::with_capacity(n as usize);
@@ -412,14 +778,17 @@ pub(crate) fn reserve_slots_round_robin(
                 break;
             }
 
-            // Since the vector is sorted in descending order,
-            // if finding one executor has not enough slots, the following will have not enough, either
-            if data.slots == 0 {
-                break;
+            // Executors are sorted by task slots only, an executor with fewer free slots
+            // may still have enough memory and cores left
+            if reservable_slots(data, &resources) == 0 {
+                continue;
             }
 
-            reservations.push(ExecutorReservation::new_free(data.executor_id.clone()));
-            data.slots -= 1;
+            reservations.push(
+                ExecutorReservation::new_free(data.executor_id.clone())
+                    .with_resources(resources),
+            );
+            take_slots(data, &resources, 1);
             n -= 1;
 
             if idx >= last_updated_idx {
@@ -434,3 +803,38 @@ pub(crate) fn reserve_slots_round_robin(
 
     reservations
 }
+
+/// Reserve each task slot on the executor with the most task slots left, so that the tasks
+/// are spread evenly over the executors whatever their number of slots
+fn reserve_slots_spread(
+    mut slots: Vec<&mut AvailableTaskSlots>,
+    resources: TaskResources,
+    n: u32,
+) -> Vec<ExecutorReservation> {
+    let mut reservations = Vec::with_capacity(n as usize);
+
+    while (reservations.len() as u32) < n {
+        // The first executor wins ties, so that equal executors take turns
+        let mut most: Option<(usize, u32)> = None;
+        for (idx, executor) in slots.iter().enumerate() {
+            let reservable = reservable_slots(executor, &resources);
+            if reservable > most.map(|(_, most)| most).unwrap_or(0) {
+                most = Some((idx, reservable));
+            }
+        }
+
+        match most {
+            Some((idx, _)) => {
+                let executor = &mut slots[idx];
+                reservations.push(
+                    ExecutorReservation::new_free(executor.executor_id.clone())
+                        .with_resources(resources),
+                );
+                take_slots(executor, &resources, 1);
+            }
+            None => break,
+        }
+    }
+
+    reservations
+}
@@ -34,6 +34,11 @@ pub enum Keyspace {
     Slots,
     Sessions,
     Heartbeats,
+    Schedulers,
+    Tables,
+    SessionObjects,
+    Decommissioning,
+    HeldJobs,
 }
 
 impl Keyspace {
@@ -16,19 +16,23 @@
 // under the License.
 
 use crate::cluster::{ClusterState, JobState, JobStateEvent, TaskDistribution};
-use crate::scheduler_server::timestamp_millis;
+use crate::scheduler_server::{timestamp_millis, timestamp_secs};
 use crate::state::execution_graph::ExecutionGraph;
 use crate::state::executor_manager::ExecutorReservation;
 use crate::test_utils::{await_condition, mock_completed_task, mock_executor};
+use ballista_core::config::BallistaConfig;
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::serde::protobuf::job_status::Status;
-use ballista_core::serde::protobuf::{executor_status, JobStatus};
+use ballista_core::serde::protobuf::{
+    executor_status, JobStatus, PreparedStatementDefinition, SessionObjects,
+    TableDefinition,
+};
 use ballista_core::serde::scheduler::{
-    ExecutorData, ExecutorMetadata, ExecutorSpecification,
+    ExecutorData, ExecutorMetadata, ExecutorSpecification, TaskResources,
 };
 use futures::StreamExt;
 use itertools::Itertools;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::RwLock;
@@ -49,10 +53,26 @@ impl<S: ClusterState> ClusterStateTest<S> {
     }
 
     pub async fn register_executor(
-        mut self,
+        self,
         executor_id: &str,
         task_slots: u32,
     ) -> Result<Self> {
+        self.register_executor_with_spec(
+            executor_id,
+            ExecutorSpecification {
+                task_slots,
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
+    pub async fn register_executor_with_spec(
+        mut self,
+        executor_id: &str,
+        specification: ExecutorSpecification,
+    ) -> Result<Self> {
+        let task_slots = specification.task_slots;
         self.state
             .register_executor(
                 ExecutorMetadata {
@@ -60,7 +80,8 @@ impl<S: ClusterState> ClusterStateTest<S> {
                     host: executor_id.to_string(),
                     port: 0,
                     grpc_port: 0,
-                    specification: ExecutorSpecification { task_slots },
+                    specification,
+                    pool: String::new(),
                 },
                 ExecutorData {
                     executor_id: executor_id.to_string(),
@@ -143,8 +164,26 @@ impl<S: ClusterState> ClusterStateTest<S> {
     }
 
     pub async fn try_reserve_slots(
+        self,
+        num_slots: u32,
+        distribution: TaskDistribution,
+        filter: Option<Vec<String>>,
+        exact: bool,
+    ) -> Result<Self> {
+        self.try_reserve_resources(
+            num_slots,
+            TaskResources::default(),
+            distribution,
+            filter,
+            exact,
+        )
+        .await
+    }
+
+    pub async fn try_reserve_resources(
         mut self,
         num_slots: u32,
+        resources: TaskResources,
         distribution: TaskDistribution,
         filter: Option<Vec<String>>,
         exact: bool,
@@ -152,11 +191,11 @@ impl<S: ClusterState> ClusterStateTest<S> {
         let filter = filter.map(|f| f.into_iter().collect::<HashSet<String>>());
         let reservations = if exact {
             self.state
-                .reserve_slots_exact(num_slots, distribution, filter)
+                .reserve_slots_exact(num_slots, resources, distribution, filter)
                 .await?
         } else {
             self.state
-                .reserve_slots(num_slots, distribution, filter)
+                .reserve_slots(num_slots, resources, distribution, filter)
                 .await?
         };
 
@@ -165,6 +204,12 @@ impl<S: ClusterState> ClusterStateTest<S> {
         Ok(self)
     }
 
+    pub async fn resize_executor(self, executor_id: &str, delta: i32) -> Result<Self> {
+        self.state.resize_executor(executor_id, delta).await?;
+
+        Ok(self)
+    }
+
     pub async fn cancel_reservations(mut self, num_slots: usize) -> Result<Self> {
         if self.reservations.len() < num_slots {
             return Err(BallistaError::General(format!(
@@ -185,6 +230,32 @@ impl<S: ClusterState> ClusterStateTest<S> {
         Ok(self)
     }
 
+    /// Release the leases of the first `num_slots` open reservations, as if they were filled
+    /// with tasks
+    pub async fn release_reservation_leases(mut self, num_slots: usize) -> Result<Self> {
+        let to_keep = self.reservations.split_off(num_slots);
+
+        let lease_ids = std::mem::take(&mut self.reservations)
+            .into_iter()
+            .filter_map(|reservation| reservation.lease_id)
+            .collect();
+        self.state.release_reservation_leases(lease_ids).await?;
+
+        self.reservations = to_keep;
+
+        Ok(self)
+    }
+
+    pub async fn assert_expired_leases(self, n: usize) -> Result<Self> {
+        let expired = self.state.expire_reservation_leases().await?;
+        assert_eq!(
+            expired, n,
+            "Expected {n} expired leases but found {expired}"
+        );
+
+        Ok(self)
+    }
+
     pub fn assert_open_reservations(self, n: usize) -> Self {
         assert_eq!(
             self.reservations.len(),
@@ -233,7 +304,12 @@ impl<S: ClusterState> ClusterStateTest<S> {
                         let to_reserve = rand::random::<u32>() % total_slots;
 
                         let reservations = state
-                            .reserve_slots(to_reserve, distribution, None)
+                            .reserve_slots(
+                                to_reserve,
+                                TaskResources::default(),
+                                distribution,
+                                None,
+                            )
                             .await
                             .unwrap();
 
@@ -396,6 +472,171 @@ pub async fn test_reservation<S: ClusterState>(
     Ok(())
 }
 
+/// Test the leases of reservations with a state whose reservations expire immediately
+pub async fn test_reservation_leases<S: ClusterState>(state: S) -> Result<()> {
+    let test = ClusterStateTest::new(state).await?;
+
+    test.register_executor("1", 10)
+        .await?
+        .try_reserve_slots(10, TaskDistribution::Bias, None, false)
+        .await?
+        .assert_open_reservations_with(10, |res| res.lease_id.is_some())
+        // The slots of filled reservations stay reserved
+        .release_reservation_leases(4)
+        .await?
+        .assert_expired_leases(6)
+        .await?
+        .assert_expired_leases(0)
+        .await?
+        // The slots of the expired reservations were returned already
+        .cancel_reservations(6)
+        .await?
+        .try_reserve_slots(10, TaskDistribution::Bias, None, false)
+        .await?
+        .assert_open_reservations(6)
+        .cancel_reservations(6)
+        .await?
+        .assert_expired_leases(0)
+        .await?
+        .try_reserve_slots(10, TaskDistribution::Bias, None, true)
+        .await?
+        .assert_open_reservations(0)
+        .try_reserve_slots(6, TaskDistribution::Bias, None, true)
+        .await?
+        .assert_open_reservations(6);
+
+    Ok(())
+}
+
+/// Test adding task slots to and removing task slots from a registered executor
+pub async fn test_executor_resize<S: ClusterState>(state: S) -> Result<()> {
+    let test = ClusterStateTest::new(state).await?;
+
+    test.register_executor("1", 4)
+        .await?
+        .try_reserve_slots(4, TaskDistribution::Bias, None, false)
+        .await?
+        .assert_open_reservations(4)
+        .resize_executor("1", 2)
+        .await?
+        .try_reserve_slots(10, TaskDistribution::Bias, None, false)
+        .await?
+        .assert_open_reservations(6)
+        // The removed task slots are all reserved, they are withheld once cancelled
+        .resize_executor("1", -4)
+        .await?
+        .cancel_reservations(6)
+        .await?
+        .try_reserve_slots(10, TaskDistribution::Bias, None, false)
+        .await?
+        .assert_open_reservations(2)
+        .cancel_reservations(2)
+        .await?
+        .resize_executor("1", 1)
+        .await?
+        .try_reserve_slots(10, TaskDistribution::Bias, None, false)
+        .await?
+        .assert_open_reservations(3);
+
+    Ok(())
+}
+
+/// Test that executors stay decommissioned until they are removed
+pub async fn test_executor_decommissioning<S: ClusterState>(state: S) -> Result<()> {
+    let test = ClusterStateTest::new(state)
+        .await?
+        .register_executor("1", 4)
+        .await?
+        .register_executor("2", 4)
+        .await?;
+    let state = test.state.clone();
+
+    assert!(state.decommission_executor("1", 0).await?);
+    assert!(state.decommission_executor("2", 100).await?);
+    assert!(!state.decommission_executor("2", 200).await?);
+    assert_eq!(
+        state.decommissioning_executors().await?,
+        HashMap::from([("1".to_owned(), 0), ("2".to_owned(), 100)])
+    );
+
+    test.remove_executor("1").await?;
+    assert_eq!(
+        state.decommissioning_executors().await?,
+        HashMap::from([("2".to_owned(), 100)])
+    );
+
+    Ok(())
+}
+
+pub async fn test_resource_reservation<S: ClusterState>(
+    state: S,
+    distribution: TaskDistribution,
+) -> Result<()> {
+    let test = ClusterStateTest::new(state).await?;
+
+    let heavy_task = TaskResources::new(1024, 1);
+
+    test.register_executor_with_spec(
+        "1",
+        ExecutorSpecification {
+            task_slots: 10,
+            memory_bytes: 4096,
+            vcores: 8,
+            task_slot_oversubscription_percent: 0,
+            gpus: 0,
+        },
+    )
+    .await?
+    .register_executor("2", 10)
+    .await?
+    // Executor 1 runs out of memory before it runs out of task slots
+    .try_reserve_resources(
+        10,
+        heavy_task,
+        distribution,
+        Some(vec!["1".to_string()]),
+        false,
+    )
+    .await?
+    .assert_open_reservations_with(4, |res| {
+        res.executor_id == "1" && res.resources == heavy_task
+    })
+    .try_reserve_resources(
+        1,
+        heavy_task,
+        distribution,
+        Some(vec!["1".to_string()]),
+        true,
+    )
+    .await?
+    .assert_open_reservations(4)
+    // Cancelling returns the memory and cores as well
+    .cancel_reservations(4)
+    .await?
+    .try_reserve_resources(
+        10,
+        TaskResources::new(0, 2),
+        distribution,
+        Some(vec!["1".to_string()]),
+        false,
+    )
+    .await?
+    .assert_open_reservations(4)
+    .cancel_reservations(4)
+    .await?
+    // Executor 2 does not limit its resources
+    .try_reserve_resources(12, heavy_task, distribution, None, false)
+    .await?
+    .assert_open_reservations_with(12, |res| res.resources == heavy_task)
+    .cancel_reservations(12)
+    .await?
+    .try_reserve_slots(20, distribution, None, true)
+    .await?
+    .assert_open_reservations(20);
+
+    Ok(())
+}
+
 pub struct JobStateTest<S: JobState> {
     state: Arc<S>,
     events: Arc<RwLock<Vec<JobStateEvent>>>,
@@ -514,6 +755,23 @@ impl<S: JobState> JobStateTest<S> {
         Ok(self)
     }
 
+    pub async fn assert_job_acquired(self, job_id: &str, acquired: bool) -> Result<Self> {
+        let graph = self.state.try_acquire_job(job_id).await?;
+
+        assert_eq!(
+            graph.is_some(),
+            acquired,
+            "Expected job {} to be acquired: {}",
+            job_id,
+            acquired
+        );
+        if let Some(graph) = graph {
+            assert_eq!(graph.job_id(), job_id);
+        }
+
+        Ok(self)
+    }
+
     pub async fn assert_event(self, event: JobStateEvent) -> Result<Self> {
         let events = self.events.clone();
         let found = await_condition(Duration::from_millis(50), 10, || async {
@@ -558,6 +816,78 @@ pub async fn test_job_lifecycle<S: JobState>(
     Ok(())
 }
 
+pub async fn test_duplicate_job_id<S: JobState>(
+    state: S,
+    mut graph: ExecutionGraph,
+) -> Result<()> {
+    let test = JobStateTest::new(state).await?;
+
+    let job_id = graph.job_id().to_string();
+
+    // The ID of a job cannot be reused while it is queued, running or completed
+    let test = test.queue_job(&job_id).await?;
+    assert!(test
+        .state
+        .accept_job(&job_id, "", timestamp_millis())
+        .await
+        .is_err());
+
+    let test = test.submit_job(&graph).await?;
+    assert!(test
+        .state
+        .accept_job(&job_id, "", timestamp_millis())
+        .await
+        .is_err());
+
+    drain_tasks(&mut graph)?;
+    graph.succeed_job()?;
+
+    let test = test
+        .update_job(&graph)
+        .await?
+        .assert_job_successful(&job_id)
+        .await?;
+    assert!(test
+        .state
+        .accept_job(&job_id, "", timestamp_millis())
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+pub async fn test_job_recovery<S: JobState>(
+    state: S,
+    mut graph: ExecutionGraph,
+) -> Result<()> {
+    let test = JobStateTest::new(state).await?;
+
+    let job_id = graph.job_id().to_string();
+
+    let test = test
+        .assert_job_acquired(&job_id, false)
+        .await?
+        .queue_job(&job_id)
+        .await?
+        .assert_job_acquired(&job_id, false)
+        .await?
+        .submit_job(&graph)
+        .await?
+        .assert_job_acquired(&job_id, true)
+        .await?;
+
+    drain_tasks(&mut graph)?;
+    graph.succeed_job()?;
+
+    // Completed jobs are not recovered
+    test.update_job(&graph)
+        .await?
+        .assert_job_acquired(&job_id, false)
+        .await?;
+
+    Ok(())
+}
+
 pub async fn test_job_planning_failure<S: JobState>(
     state: S,
     graph: ExecutionGraph,
@@ -576,6 +906,147 @@ pub async fn test_job_planning_failure<S: JobState>(
     Ok(())
 }
 
+pub async fn test_session_expiry<S: JobState>(state: S) -> Result<()> {
+    let config = BallistaConfig::new()?;
+    let idle = state.create_session(&config).await?.session_id();
+    let active = state.create_session(&config).await?.session_id();
+    let keep: HashSet<String> = [active.clone()].into_iter().collect();
+
+    // Sessions accessed since are kept
+    let removed = state
+        .remove_idle_sessions(timestamp_secs() - 10, &keep)
+        .await?;
+    assert!(removed.is_empty());
+
+    let removed = state
+        .remove_idle_sessions(timestamp_secs() + 1, &keep)
+        .await?;
+    assert_eq!(removed, vec![idle.clone()]);
+    assert!(!state.remove_session(&idle).await?);
+
+    state.get_session(&active).await?;
+    assert!(state.remove_session(&active).await?);
+    assert!(!state.remove_session(&active).await?);
+
+    Ok(())
+}
+
+pub async fn test_shared_tables<S: JobState>(state: S) -> Result<()> {
+    assert!(state.get_tables().await?.is_empty());
+    for (name, plan) in [("a", vec![1]), ("b", vec![2]), ("a", vec![3])] {
+        state
+            .save_table(TableDefinition {
+                name: name.to_owned(),
+                plan,
+                dropped_at: 0,
+            })
+            .await?;
+    }
+
+    let tables = state
+        .get_tables()
+        .await?
+        .into_iter()
+        .map(|table| (table.name, table.plan))
+        .sorted()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tables,
+        vec![("a".to_owned(), vec![3]), ("b".to_owned(), vec![2])]
+    );
+
+    // A new table is only saved if no table of the same name which was not dropped is
+    let table = |name: &str, dropped_at| TableDefinition {
+        name: name.to_owned(),
+        plan: vec![4],
+        dropped_at,
+    };
+    assert!(!state.save_new_table(table("a", 0)).await?);
+    assert!(state.save_new_table(table("c", 0)).await?);
+    state.save_table(table("c", 1)).await?;
+    assert!(state.save_new_table(table("c", 0)).await?);
+    assert_eq!(state.get_tables().await?.len(), 3);
+
+    state.remove_table("a").await?;
+    state.remove_table("c").await?;
+    let tables = state.get_tables().await?;
+    assert_eq!(tables.len(), 1);
+    assert_eq!(tables[0].name, "b");
+
+    Ok(())
+}
+
+pub async fn test_session_objects<S: JobState>(state: S) -> Result<()> {
+    let config = BallistaConfig::new()?;
+    let session_id = state.create_session(&config).await?.session_id();
+    assert_eq!(
+        state.get_session_objects(&session_id).await?,
+        SessionObjects::default()
+    );
+
+    let objects = SessionObjects {
+        tables: vec![TableDefinition {
+            name: "t".to_owned(),
+            plan: vec![1],
+            dropped_at: 0,
+        }],
+        prepared_statements: vec![PreparedStatementDefinition {
+            handle: vec![2],
+            query: "SELECT * FROM t".to_owned(),
+        }],
+        ..Default::default()
+    };
+    let saved = objects.clone();
+    assert!(
+        state
+            .update_session_objects(
+                &session_id,
+                Box::new(move |objects| {
+                    *objects = saved;
+                    true
+                })
+            )
+            .await?
+    );
+    assert_eq!(state.get_session_objects(&session_id).await?, objects);
+
+    // Concurrent updates of the objects are not lost
+    let updates = (0..10u8).map(|handle| {
+        state.update_session_objects(
+            &session_id,
+            Box::new(move |objects| {
+                objects
+                    .prepared_statements
+                    .push(PreparedStatementDefinition {
+                        handle: vec![10 + handle],
+                        query: "SELECT 1".to_owned(),
+                    });
+                true
+            }),
+        )
+    });
+    for update in futures::future::join_all(updates).await {
+        assert!(update?);
+    }
+    assert_eq!(
+        state
+            .get_session_objects(&session_id)
+            .await?
+            .prepared_statements
+            .len(),
+        11
+    );
+
+    // The objects of a session are removed with it
+    assert!(state.remove_session(&session_id).await?);
+    assert_eq!(
+        state.get_session_objects(&session_id).await?,
+        SessionObjects::default()
+    );
+
+    Ok(())
+}
+
 fn drain_tasks(graph: &mut ExecutionGraph) -> Result<()> {
     let executor = mock_executor("executor-id1".to_string());
     while let Some(task) = graph.pop_next_task(&executor.id)? {
@@ -18,9 +18,23 @@
 
 //! Ballista scheduler specific configuration
 
-use ballista_core::config::TaskSchedulingPolicy;
+use crate::clock::{system_clock, Clock};
+use crate::plugin::{SchedulerPlugin, SchedulerPlugins};
+use crate::scheduler_server::SessionBuilder;
+use crate::state::pool_routing::PoolRoutingRules;
+use crate::state::scheduling_recorder::SchedulingRecorder;
+use ballista_core::config::{GrpcCompression, TaskSchedulingPolicy};
+use ballista_core::execution_plans::ExportConfig;
+use ballista_core::serde::scheduler::TaskResources;
+use ballista_core::serde::BallistaCodec;
+use ballista_core::utils::{default_session_builder, DefaultObjectStores};
 use clap::ArgEnum;
+use datafusion::optimizer::OptimizerRule;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Configurations for the ballista scheduler of scheduling jobs and tasks
 #[derive(Debug, Clone)]
@@ -44,6 +58,9 @@ pub struct SchedulerConfig {
     pub finished_job_state_clean_up_interval_seconds: u64,
     /// The route endpoint for proxying flight sql results via scheduler
     pub advertise_flight_sql_endpoint: Option<String>,
+    /// Serve the REST API of the web UI next to the gRPC services, if the scheduler is
+    /// built with the `rest-api` feature
+    pub rest_api: bool,
     /// If provided, submitted jobs which do not have tasks scheduled will be resubmitted after `job_resubmit_interval_ms`
     /// milliseconds
     pub job_resubmit_interval_ms: Option<u64>,
@@ -56,6 +73,106 @@ pub struct SchedulerConfig {
     pub scheduler_event_expected_processing_duration: u64,
     /// The maximum size of a decoded message at the grpc server side.
     pub grpc_server_max_decoding_message_size: u32,
+    /// Hash shuffles with at most this many columns are written using the row codec instead of Arrow IPC.
+    /// Zero means disable.
+    pub row_codec_max_columns: usize,
+    /// Memory in bytes required by a task which does not buffer its input. Tasks of stages with
+    /// aggregations, joins, sorts or windows require a multiple of it. Zero means not accounted.
+    pub task_memory_bytes: u64,
+    /// Virtual cores required by each task. Zero means not accounted.
+    pub task_vcores: u32,
+    /// Bytes of rows buffered per output partition by hash shuffle writes before they are written.
+    /// Zero means write every batch size rows.
+    pub shuffle_write_buffer_size: usize,
+    /// Record the CRC32 checksum of shuffle files, verified before they are read so that a
+    /// corrupted file fails the fetch and the partition is recomputed
+    pub shuffle_checksums: bool,
+    /// Write the shuffle files of schemas with dictionary encoded columns in the Arrow IPC
+    /// streaming format, so that each dictionary is written once for the batches sharing it
+    pub shuffle_dictionary_sharing: bool,
+    /// Preempt running tasks of lower priority jobs when a job with a higher priority is
+    /// submitted and not all of its tasks can be scheduled. Only applies to push-based scheduling.
+    pub job_preemption: bool,
+    /// Maximum number of jobs planned or running at once. Further jobs are held in the queued
+    /// state until a running job completes. Zero means unlimited.
+    pub max_concurrent_jobs: usize,
+    /// Seconds a draining scheduler waits for unfinished jobs to complete before it exits
+    pub drain_timeout_seconds: u64,
+    /// Compression of the messages the scheduler sends to executors
+    pub grpc_compression: GrpcCompression,
+    /// Seconds the results of a job are served to identical queries of sessions enabling the
+    /// result cache. Zero disables the result cache.
+    pub result_cache_ttl_seconds: u64,
+    /// Maximum bytes of the results held in the result cache
+    pub result_cache_max_bytes: u64,
+    /// Run the jobs of identical queries over the same files submitted while one of them
+    /// runs once, sharing its results with all of them
+    pub query_deduplication: bool,
+    /// Number of failures of a SQL query within the quarantine window after which it is
+    /// rejected. Zero disables the quarantine.
+    pub query_quarantine_failures: usize,
+    /// Seconds within which the failures of a SQL query count towards its quarantine
+    pub query_quarantine_window_seconds: u64,
+    /// Number of events queued for the scheduler's event loop above which new queries are
+    /// rejected, leaving the remaining capacity to task status updates. Zero means unlimited.
+    pub max_queued_events: usize,
+    /// Number of jobs held in the queue by `max_concurrent_jobs` above which new queries are
+    /// rejected. Zero means unlimited.
+    pub max_held_jobs: usize,
+    /// Estimated time in milliseconds before the scheduler's event loop processes a new
+    /// event above which new queries are rejected. Zero means unlimited.
+    pub max_event_loop_lag_ms: u64,
+    /// Time in milliseconds a query received while the scheduler is overloaded waits for the
+    /// load to drop before it is rejected. Zero rejects it immediately.
+    pub overload_wait_ms: u64,
+    /// Share the cluster with the other schedulers using the same state backend. Each job is
+    /// owned by one of the live schedulers, chosen by the hash of its ID, which plans and
+    /// schedules it. Queries and task status updates received for the jobs of another
+    /// scheduler are forwarded to it.
+    pub job_sharding: bool,
+    /// Time in seconds after which sessions which were not used by any query are removed,
+    /// 0 means sessions are never removed
+    pub session_idle_timeout_seconds: u64,
+    /// Time in seconds for which dropped shared tables can be restored with UNDROP TABLE
+    /// before they are purged
+    pub dropped_table_retention_seconds: u64,
+    /// Time in seconds without progress after which a running task is reported as stalled,
+    /// 0 disables the reports
+    pub stalled_task_seconds: u64,
+    /// Time in seconds after which reserved task slots return to the available task slots
+    /// unless they were filled with tasks or cancelled, so that the slots reserved by a
+    /// scheduler which crashed are not lost. 0 disables the leases
+    pub reservation_lease_seconds: u64,
+    /// Share of the task slots of a job, between 0 and 1, reserved on the executors which
+    /// already ran tasks of its session so that their caches stay warm, 0 disables the affinity
+    pub session_affinity: f64,
+    /// Recorder of the inputs and decisions of the scheduler about task slots, to replay a
+    /// problematic scheduling sequence
+    pub scheduling_recorder: Option<Arc<dyn SchedulingRecorder>>,
+    /// Settings of every session which the settings of the session do not override, e.g.
+    /// `datafusion.catalog.information_schema` or `datafusion.optimizer.*` settings
+    pub session_settings: HashMap<String, String>,
+    /// Function building the DataFusion state of every session, such as one registering
+    /// functions or table factories with it
+    pub session_builder: SessionBuilder,
+    /// Object stores configured with options registered with every session
+    pub object_stores: DefaultObjectStores,
+    /// Profiles holding the credentials of the exports of results and the local directory
+    /// results may be exported to, to delete the expired exports
+    pub export_config: ExportConfig,
+    /// Rules routing the jobs which do not select a pool of executors to one by their labels
+    /// or SQL text
+    pub pool_routing: PoolRoutingRules,
+    /// Optimizer rules applied to the plan of every job in addition to the rules of its session
+    pub optimizer_rules: OptimizerRules,
+    /// Plugins contributing catalogs, table factories, authentication and authorization of
+    /// queries
+    pub plugins: SchedulerPlugins,
+    /// Codec of the plans of jobs, whose extension codecs encode the custom plan nodes and
+    /// tables of the plans
+    pub codec: BallistaCodec,
+    /// Source of the time of the scheduler, the system clock except in tests
+    pub clock: Arc<dyn Clock>,
 }
 
 impl Default for SchedulerConfig {
@@ -70,11 +187,47 @@ impl Default for SchedulerConfig {
             finished_job_data_clean_up_interval_seconds: 300,
             finished_job_state_clean_up_interval_seconds: 3600,
             advertise_flight_sql_endpoint: None,
+            rest_api: true,
             cluster_storage: ClusterStorageConfig::Memory,
             job_resubmit_interval_ms: None,
             executor_termination_grace_period: 0,
             scheduler_event_expected_processing_duration: 0,
             grpc_server_max_decoding_message_size: 16777216,
+            row_codec_max_columns: 0,
+            task_memory_bytes: 0,
+            task_vcores: 0,
+            shuffle_write_buffer_size: 0,
+            shuffle_checksums: false,
+            shuffle_dictionary_sharing: true,
+            job_preemption: false,
+            max_concurrent_jobs: 0,
+            drain_timeout_seconds: 300,
+            grpc_compression: GrpcCompression::None,
+            result_cache_ttl_seconds: 0,
+            result_cache_max_bytes: 1073741824,
+            query_deduplication: false,
+            query_quarantine_failures: 0,
+            query_quarantine_window_seconds: 600,
+            max_queued_events: 0,
+            max_held_jobs: 0,
+            max_event_loop_lag_ms: 0,
+            overload_wait_ms: 0,
+            job_sharding: false,
+            session_idle_timeout_seconds: 0,
+            dropped_table_retention_seconds: 604800,
+            stalled_task_seconds: 600,
+            reservation_lease_seconds: 600,
+            session_affinity: 0.0,
+            scheduling_recorder: None,
+            session_settings: HashMap::new(),
+            session_builder: default_session_builder,
+            object_stores: DefaultObjectStores::default(),
+            export_config: ExportConfig::default(),
+            pool_routing: PoolRoutingRules::default(),
+            optimizer_rules: OptimizerRules::default(),
+            plugins: SchedulerPlugins::default(),
+            codec: BallistaCodec::default(),
+            clock: system_clock(),
         }
     }
 }
@@ -137,6 +290,16 @@ impl SchedulerConfig {
         self
     }
 
+    pub fn with_rest_api(mut self, rest_api: bool) -> Self {
+        self.rest_api = rest_api;
+        self
+    }
+
+    /// Whether the scheduler serves the REST API, which needs the `rest-api` feature
+    pub fn rest_api_enabled(&self) -> bool {
+        cfg!(feature = "rest-api") && self.rest_api
+    }
+
     pub fn with_task_distribution(mut self, policy: TaskDistribution) -> Self {
         self.task_distribution = policy;
         self
@@ -161,6 +324,249 @@ impl SchedulerConfig {
         self.grpc_server_max_decoding_message_size = value;
         self
     }
+
+    pub fn with_row_codec_max_columns(mut self, max_columns: usize) -> Self {
+        self.row_codec_max_columns = max_columns;
+        self
+    }
+
+    pub fn with_task_memory_bytes(mut self, memory_bytes: u64) -> Self {
+        self.task_memory_bytes = memory_bytes;
+        self
+    }
+
+    pub fn with_task_vcores(mut self, vcores: u32) -> Self {
+        self.task_vcores = vcores;
+        self
+    }
+
+    pub fn with_shuffle_write_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.shuffle_write_buffer_size = buffer_size;
+        self
+    }
+
+    pub fn with_shuffle_checksums(mut self, enabled: bool) -> Self {
+        self.shuffle_checksums = enabled;
+        self
+    }
+
+    pub fn with_shuffle_dictionary_sharing(mut self, enabled: bool) -> Self {
+        self.shuffle_dictionary_sharing = enabled;
+        self
+    }
+
+    pub fn with_job_preemption(mut self, enabled: bool) -> Self {
+        self.job_preemption = enabled;
+        self
+    }
+
+    pub fn with_max_concurrent_jobs(mut self, max_jobs: usize) -> Self {
+        self.max_concurrent_jobs = max_jobs;
+        self
+    }
+
+    pub fn with_drain_timeout_seconds(mut self, timeout_seconds: u64) -> Self {
+        self.drain_timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn with_grpc_compression(mut self, compression: GrpcCompression) -> Self {
+        self.grpc_compression = compression;
+        self
+    }
+
+    pub fn with_result_cache(mut self, ttl_seconds: u64, max_bytes: u64) -> Self {
+        self.result_cache_ttl_seconds = ttl_seconds;
+        self.result_cache_max_bytes = max_bytes;
+        self
+    }
+
+    pub fn with_query_deduplication(mut self, query_deduplication: bool) -> Self {
+        self.query_deduplication = query_deduplication;
+        self
+    }
+
+    pub fn with_query_quarantine(mut self, failures: usize, window_seconds: u64) -> Self {
+        self.query_quarantine_failures = failures;
+        self.query_quarantine_window_seconds = window_seconds;
+        self
+    }
+
+    pub fn with_max_queued_events(mut self, max_queued_events: usize) -> Self {
+        self.max_queued_events = max_queued_events;
+        self
+    }
+
+    pub fn with_max_held_jobs(mut self, max_held_jobs: usize) -> Self {
+        self.max_held_jobs = max_held_jobs;
+        self
+    }
+
+    pub fn with_max_event_loop_lag_ms(mut self, max_event_loop_lag_ms: u64) -> Self {
+        self.max_event_loop_lag_ms = max_event_loop_lag_ms;
+        self
+    }
+
+    pub fn with_overload_wait_ms(mut self, overload_wait_ms: u64) -> Self {
+        self.overload_wait_ms = overload_wait_ms;
+        self
+    }
+
+    pub fn with_job_sharding(mut self, job_sharding: bool) -> Self {
+        self.job_sharding = job_sharding;
+        self
+    }
+
+    pub fn with_session_idle_timeout_seconds(mut self, timeout_seconds: u64) -> Self {
+        self.session_idle_timeout_seconds = timeout_seconds;
+        self
+    }
+
+    pub fn with_dropped_table_retention_seconds(
+        mut self,
+        retention_seconds: u64,
+    ) -> Self {
+        self.dropped_table_retention_seconds = retention_seconds;
+        self
+    }
+
+    pub fn with_stalled_task_seconds(mut self, stalled_task_seconds: u64) -> Self {
+        self.stalled_task_seconds = stalled_task_seconds;
+        self
+    }
+
+    pub fn with_reservation_lease_seconds(mut self, lease_seconds: u64) -> Self {
+        self.reservation_lease_seconds = lease_seconds;
+        self
+    }
+
+    pub fn with_session_affinity(mut self, session_affinity: f64) -> Self {
+        self.session_affinity = session_affinity;
+        self
+    }
+
+    /// Apply `session_settings` to every session which does not set them
+    pub fn with_session_settings(
+        mut self,
+        session_settings: HashMap<String, String>,
+    ) -> Self {
+        self.session_settings = session_settings;
+        self
+    }
+
+    /// Build the DataFusion state of every session with `session_builder`
+    pub fn with_session_builder(mut self, session_builder: SessionBuilder) -> Self {
+        self.session_builder = session_builder;
+        self
+    }
+
+    /// Register `object_stores` with every session
+    pub fn with_object_stores(mut self, object_stores: DefaultObjectStores) -> Self {
+        self.object_stores = object_stores;
+        self
+    }
+
+    /// Delete the expired exports of results with the profiles of `export_config`, which
+    /// should match the export configs of the executors
+    pub fn with_export_config(mut self, export_config: ExportConfig) -> Self {
+        self.export_config = export_config;
+        self
+    }
+
+    pub fn with_pool_routing(mut self, pool_routing: PoolRoutingRules) -> Self {
+        self.pool_routing = pool_routing;
+        self
+    }
+
+    pub fn with_scheduling_recorder(
+        mut self,
+        recorder: Arc<dyn SchedulingRecorder>,
+    ) -> Self {
+        self.scheduling_recorder = Some(recorder);
+        self
+    }
+
+    /// Apply a logical optimizer rule to every job after the rules of its session
+    pub fn with_optimizer_rule(
+        mut self,
+        rule: Arc<dyn OptimizerRule + Send + Sync>,
+    ) -> Self {
+        self.optimizer_rules.logical.push(rule);
+        self
+    }
+
+    /// Apply a physical optimizer rule to every job after the rules of its session
+    pub fn with_physical_optimizer_rule(
+        mut self,
+        rule: Arc<dyn PhysicalOptimizerRule + Send + Sync>,
+    ) -> Self {
+        self.optimizer_rules.physical.push(rule);
+        self
+    }
+
+    /// Register a plugin, whose optimizer rules are applied to every job after the rules
+    /// registered before
+    pub fn with_plugin(mut self, plugin: Arc<dyn SchedulerPlugin>) -> Self {
+        self.optimizer_rules
+            .logical
+            .extend(plugin.optimizer_rules());
+        self.plugins.push(plugin);
+        self
+    }
+
+    pub fn with_codec(mut self, codec: BallistaCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Read the time from `clock`, such as a [crate::clock::ManualClock] advanced by a test
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Base resources required by every task
+    pub fn task_resources(&self) -> TaskResources {
+        TaskResources::new(self.task_memory_bytes, self.task_vcores)
+    }
+
+    /// Lease of the reserved task slots, `None` if they do not expire
+    pub fn reservation_lease(&self) -> Option<Duration> {
+        (self.reservation_lease_seconds > 0)
+            .then(|| Duration::from_secs(self.reservation_lease_seconds))
+    }
+}
+
+/// Optimizer rules registered with the scheduler, which apply to the plans of all jobs
+#[derive(Clone, Default)]
+pub struct OptimizerRules {
+    /// Logical optimizer rules, run after the logical optimizer rules of the job's session
+    pub logical: Vec<Arc<dyn OptimizerRule + Send + Sync>>,
+    /// Physical optimizer rules, run after the physical optimizer rules of the job's session
+    pub physical: Vec<Arc<dyn PhysicalOptimizerRule + Send + Sync>>,
+}
+
+impl fmt::Debug for OptimizerRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OptimizerRules")
+            .field(
+                "logical",
+                &self
+                    .logical
+                    .iter()
+                    .map(|rule| rule.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "physical",
+                &self
+                    .physical
+                    .iter()
+                    .map(|rule| rule.name())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -183,6 +589,12 @@ pub enum TaskDistribution {
     /// Distributed tasks evenly across executors. This will try and iterate through available executors
     /// and assign one task to each executor until all tasks are assigned.
     RoundRobin,
+    /// Pack tasks onto the fewest executors. This will assign as many task slots as possible
+    /// of the busiest executors first, so that idle executors stay idle and can be scaled down
+    BinPack,
+    /// Spread tasks evenly over the executors. This will assign each task to the executor with
+    /// the most available task slots, to avoid hot spots
+    Spread,
 }
 
 impl std::str::FromStr for TaskDistribution {
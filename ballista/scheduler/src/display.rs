@@ -19,14 +19,24 @@
 //! [`crate::physical_plan::displayable`] for examples of how to
 //! format
 
+use crate::planner::find_unresolved_shuffles;
+use ballista_core::error::Result;
+use ballista_core::execution_plans::{ShuffleWriterExec, UnresolvedShuffleExec};
 use ballista_core::utils::collect_plan_metrics;
 use datafusion::logical_expr::{StringifiedPlan, ToStringifiedPlan};
+use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+use datafusion::physical_plan::joins::{CrossJoinExec, HashJoinExec, PartitionMode};
 use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
 use datafusion::physical_plan::{
-    accept, DisplayFormatType, ExecutionPlan, ExecutionPlanVisitor,
+    accept, displayable, DisplayFormatType, ExecutionPlan, ExecutionPlanVisitor,
+    Partitioning,
 };
+use itertools::Itertools;
 use log::{error, info};
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::Arc;
 
 pub fn print_stage_metrics(
     job_id: &str,
@@ -58,6 +68,96 @@ pub fn print_stage_metrics(
     }
 }
 
+/// Display the query stages a plan is broken into, as planned by the `DistributedPlanner`.
+/// Each stage is annotated with its number of tasks, the stages whose output it reads and the
+/// exchange of its own output with the stage reading it, which is one of:
+/// * `hash(exprs, n)`: repartitioned by the hash of the expressions into n partitions
+/// * `broadcast`: gathered and read in full by every task of the next stage
+/// * `gather`: gathered into a single partition
+/// * `merge`: merged into a single partition preserving the sort order
+/// * `forward`: read with the same partitioning
+/// * `result`: output of the job
+pub fn display_query_stages(stages: &[Arc<ShuffleWriterExec>]) -> Result<String> {
+    let mut exchanges: HashMap<usize, &'static str> = HashMap::new();
+    for stage in stages {
+        collect_exchanges(
+            &(stage.clone() as Arc<dyn ExecutionPlan>),
+            None,
+            &mut exchanges,
+        );
+    }
+
+    let mut output = String::new();
+    for (idx, stage) in stages.iter().enumerate() {
+        let plan: Arc<dyn ExecutionPlan> = stage.clone();
+        let inputs = find_unresolved_shuffles(&plan)?
+            .iter()
+            .map(|shuffle| shuffle.stage_id)
+            .sorted()
+            .join(", ");
+        let exchange = match (
+            exchanges.get(&stage.stage_id()),
+            stage.shuffle_output_partitioning(),
+        ) {
+            (Some(exchange), _) => exchange.to_string(),
+            (None, Some(Partitioning::Hash(exprs, partitions))) => format!(
+                "hash([{}], {partitions})",
+                exprs.iter().map(|expr| expr.to_string()).join(", ")
+            ),
+            (None, _) if idx + 1 == stages.len() => "result".to_owned(),
+            (None, _) => "forward".to_owned(),
+        };
+        output.push_str(&format!(
+            "=========Stage {}[tasks={}, inputs=[{}], exchange={}]=========\n{}\n",
+            stage.stage_id(),
+            stage.output_partitioning().partition_count(),
+            inputs,
+            exchange,
+            displayable(plan.as_ref()).indent()
+        ));
+    }
+
+    Ok(output)
+}
+
+/// Collect how the stages read by the operators of a plan are exchanged, for the operators
+/// which do not read the partitions of their input stages as they were written
+fn collect_exchanges(
+    plan: &Arc<dyn ExecutionPlan>,
+    exchange: Option<&'static str>,
+    exchanges: &mut HashMap<usize, &'static str>,
+) {
+    if let Some(shuffle) = plan.as_any().downcast_ref::<UnresolvedShuffleExec>() {
+        if let Some(exchange) = exchange {
+            exchanges.insert(shuffle.stage_id, exchange);
+        }
+        return;
+    }
+
+    let children = plan.children();
+    let any = plan.as_any();
+    let child_exchanges: Vec<Option<&'static str>> =
+        if let Some(join) = any.downcast_ref::<HashJoinExec>() {
+            if *join.partition_mode() == PartitionMode::CollectLeft {
+                vec![Some("broadcast"), None]
+            } else {
+                vec![None, None]
+            }
+        } else if any.is::<CrossJoinExec>() {
+            vec![Some("broadcast"), None]
+        } else if any.is::<CoalescePartitionsExec>() {
+            vec![exchange.or(Some("gather"))]
+        } else if any.is::<SortPreservingMergeExec>() {
+            vec![exchange.or(Some("merge"))]
+        } else {
+            vec![None; children.len()]
+        };
+
+    for (child, exchange) in children.iter().zip(child_exchanges) {
+        collect_exchanges(child, exchange, exchanges);
+    }
+}
+
 /// Wraps an `ExecutionPlan` to display this plan with metrics collected/aggregated.
 /// The metrics must be collected in the same order as how we visit and display the plan.
 pub struct DisplayableBallistaExecutionPlan<'a> {
@@ -28,8 +28,8 @@ use arrow_flight::sql::{
     SqlInfo, TicketStatementQuery,
 };
 use arrow_flight::{
-    Action, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest,
-    HandshakeResponse, Location, Ticket,
+    Action, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, Location, SchemaResult, Ticket,
 };
 use log::{debug, error, warn};
 use std::convert::TryFrom;
@@ -40,17 +40,27 @@ use std::sync::Arc;
 use std::time::Duration;
 use tonic::{Request, Response, Status, Streaming};
 
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::SchedulerServer;
+use crate::state::session_manager::{create_owned_job_context, session_ballista_config};
+use crate::state::task_manager::JobOverview;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::error::FlightError;
 use arrow_flight::flight_service_client::FlightServiceClient;
 use arrow_flight::sql::ProstMessageExt;
-use arrow_flight::utils::batches_to_flight_data;
+use arrow_flight::utils::{batches_to_flight_data, flight_data_from_arrow_batch};
 use arrow_flight::SchemaAsIpc;
 use ballista_core::config::BallistaConfig;
+use ballista_core::execution_plans::with_unique_dict_ids;
 use ballista_core::serde::protobuf;
 use ballista_core::serde::protobuf::action::ActionType::FetchPartition;
+use ballista_core::serde::protobuf::interactive_event::Event;
 use ballista_core::serde::protobuf::job_status;
-use ballista_core::serde::protobuf::JobStatus;
 use ballista_core::serde::protobuf::SuccessfulJob;
+use ballista_core::serde::protobuf::{
+    InteractiveEvent, JobStatus, StatementFailed, StatementFinished, StatementProgress,
+    StatementStarted,
+};
 use ballista_core::utils::create_grpc_client_connection;
 use dashmap::DashMap;
 use datafusion::arrow;
@@ -60,10 +70,14 @@ use datafusion::arrow::error::ArrowError;
 use datafusion::arrow::ipc::writer::{IpcDataGenerator, IpcWriteOptions};
 use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::common::DFSchemaRef;
-use datafusion::logical_expr::LogicalPlan;
+use datafusion::logical_expr::{
+    DdlStatement, LogicalPlan, LogicalPlanBuilder, SetVariable,
+    Statement as LogicalStatement,
+};
 use datafusion::physical_plan::common::batch_byte_size;
 use datafusion::prelude::SessionContext;
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+use futures::{StreamExt, TryStreamExt};
 use prost::Message;
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::time::sleep;
@@ -72,20 +86,47 @@ use tonic::codegen::futures_core::Stream;
 use tonic::metadata::MetadataValue;
 use uuid::Uuid;
 
+/// Flight SQL service of a scheduler. The bearer token of a client is a random secret mapped
+/// to its session, whose settings, tables and prepared statements are saved to the state
+/// backend with the secret so that any scheduler behind a load balancer serves any token.
+#[derive(Clone)]
 pub struct FlightSqlServiceImpl {
     server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode>,
+    /// Plans of the prepared statements, planned again from their SQL saved to the state
+    /// backend when they were prepared by another scheduler
     statements: Arc<DashMap<Uuid, LogicalPlan>>,
-    contexts: Arc<DashMap<Uuid, Arc<SessionContext>>>,
+    /// Plans of the `EXPLAIN` statements, until they are fetched, their prepared statement
+    /// is closed or their session is removed
+    explained: Arc<DashMap<Uuid, ExplainedPlan>>,
+}
+
+/// Plans of an `EXPLAIN` statement waiting to be fetched
+struct ExplainedPlan {
+    session_id: String,
+    /// Handle of the prepared statement, if the statement was prepared
+    statement: Option<Uuid>,
+    plans: RecordBatch,
 }
 
 const TABLE_TYPES: [&str; 2] = ["TABLE", "VIEW"];
 
+/// Prefix of the well-known job IDs of the tickets fetching the plans of `EXPLAIN` statements
+const EXPLAIN_JOB_PREFIX: &str = "explain-";
+
 impl FlightSqlServiceImpl {
     pub fn new(server: SchedulerServer<LogicalPlanNode, PhysicalPlanNode>) -> Self {
+        let explained: Arc<DashMap<Uuid, ExplainedPlan>> = Default::default();
+        let mut removed_sessions = server.state.session_manager.removed_sessions();
+        let session_plans = explained.clone();
+        tokio::spawn(async move {
+            while let Some(session_id) = removed_sessions.next().await {
+                session_plans.retain(|_, explained| explained.session_id != session_id);
+            }
+        });
         Self {
             server,
             statements: Default::default(),
-            contexts: Default::default(),
+            explained,
         }
     }
 
@@ -127,26 +168,26 @@ impl FlightSqlServiceImpl {
         )
     }
 
-    async fn create_ctx(&self) -> Result<Uuid, Status> {
+    /// Create a session saved to the state backend, returns the bearer token of the client
+    /// mapped to it
+    async fn create_ctx(&self) -> Result<String, Status> {
         let config_builder = BallistaConfig::builder();
         let config = config_builder
             .build()
             .map_err(|e| Status::internal(format!("Error building config: {e}")))?;
-        let ctx = self
-            .server
-            .state
-            .session_manager
-            .create_session(&config)
+        let session_manager = &self.server.state.session_manager;
+        let ctx = session_manager.create_session(&config).await.map_err(|e| {
+            Status::internal(format!("Failed to create SessionContext: {e:?}"))
+        })?;
+        session_manager
+            .issue_token(&ctx.session_id())
             .await
-            .map_err(|e| {
-                Status::internal(format!("Failed to create SessionContext: {e:?}"))
-            })?;
-        let handle = Uuid::new_v4();
-        self.contexts.insert(handle, ctx);
-        Ok(handle)
+            .map_err(|e| Status::internal(format!("Failed to issue token: {e:?}")))
     }
 
-    fn get_ctx<T>(&self, req: &Request<T>) -> Result<Arc<SessionContext>, Status> {
+    /// The session of the bearer token of a request, with the settings and tables saved by
+    /// any scheduler
+    async fn get_ctx<T>(&self, req: &Request<T>) -> Result<Arc<SessionContext>, Status> {
         let auth = req
             .metadata()
             .get("authorization")
@@ -159,31 +200,146 @@ impl FlightSqlServiceImpl {
         if !authorization.starts_with(bearer) {
             Err(Status::internal("Invalid auth header!"))?;
         }
-        let auth = authorization[bearer.len()..].to_string();
+        let token = &authorization[bearer.len()..];
 
-        let handle = Uuid::from_str(auth.as_str())
-            .map_err(|e| Status::internal(format!("Error locking contexts: {e}")))?;
-        if let Some(context) = self.contexts.get(&handle) {
-            Ok(context.clone())
-        } else {
-            Err(Status::internal(format!(
-                "Context handle not found: {handle}"
-            )))?
-        }
+        let session_manager = &self.server.state.session_manager;
+        let session_id = session_manager
+            .token_session(token)
+            .await
+            .map_err(|e| Status::internal(format!("Error checking token: {e}")))?
+            .ok_or_else(|| Status::unauthenticated("Invalid or expired bearer token"))?;
+        session_manager
+            .get_session_with_objects(&session_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("Context handle not found: {session_id}: {e}"))
+            })
+    }
+
+    /// Authenticate the client of a request with the plugins of the scheduler. Returns the
+    /// user the client runs queries as, the one set by `ballista.job.run_as` in the session
+    /// if the plugins let the client impersonate it.
+    fn authenticate<T>(
+        &self,
+        req: &Request<T>,
+        ctx: &SessionContext,
+    ) -> Result<Option<String>, Status> {
+        let plugins = &self.server.state.config.plugins;
+        let user = plugins.authenticate(req.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate query: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let run_as = session_ballista_config(ctx)
+            .map(|config| config.job_run_as())
+            .unwrap_or_default();
+        self.server.impersonate(user, &run_as)
+    }
+
+    /// Check that the plugins of the scheduler let the client run a plan
+    fn authorize(&self, user: Option<&str>, plan: &LogicalPlan) -> Result<(), Status> {
+        let plugins = &self.server.state.config.plugins;
+        plugins.authorize(user, plan).map_err(|e| {
+            let msg = format!("Query is not authorized: {e}");
+            warn!("{}", msg);
+            Status::permission_denied(msg)
+        })
     }
 
+    /// Plan a SQL statement. Statements changing the session, i.e. SET, CREATE EXTERNAL
+    /// TABLE and DROP TABLE, are authorized and executed right away and saved to the state
+    /// backend, so that the session is the same on any scheduler.
     async fn prepare_statement(
+        &self,
         query: &str,
         ctx: &Arc<SessionContext>,
+        user: Option<&str>,
     ) -> Result<LogicalPlan, Status> {
+        let statement = ctx
+            .state()
+            .create_logical_plan(query)
+            .await
+            .map_err(|e| Status::internal(format!("Error building plan: {e}")))?;
+        if matches!(statement, LogicalPlan::Ddl(_) | LogicalPlan::Statement(_)) {
+            self.authorize(user, &statement)?;
+        }
         let plan = ctx
-            .sql(query)
+            .execute_logical_plan(statement.clone())
             .await
             .and_then(|df| df.into_optimized_plan())
             .map_err(|e| Status::internal(format!("Error building plan: {e}")))?;
+        self.save_session_change(ctx, statement)
+            .await
+            .map_err(|e| Status::internal(format!("Error saving session: {e}")))?;
         Ok(plan)
     }
 
+    /// Save the change of an executed statement to the session of `ctx`, if any
+    async fn save_session_change(
+        &self,
+        ctx: &SessionContext,
+        statement: LogicalPlan,
+    ) -> ballista_core::error::Result<()> {
+        let session_manager = &self.server.state.session_manager;
+        let session_id = ctx.session_id();
+        match statement {
+            LogicalPlan::Statement(LogicalStatement::SetVariable(SetVariable {
+                variable,
+                value,
+                ..
+            })) => {
+                let mut settings = session_ballista_config(ctx)
+                    .map(|config| config.settings().clone())
+                    .unwrap_or_default();
+                settings.insert(variable, value);
+                let config = BallistaConfig::with_settings(settings)?;
+                session_manager.update_session(&session_id, &config).await?;
+            }
+            LogicalPlan::Ddl(DdlStatement::CreateExternalTable(cmd)) => {
+                session_manager.save_session_table(ctx, cmd).await?;
+            }
+            LogicalPlan::Ddl(DdlStatement::DropTable(drop)) => {
+                session_manager
+                    .remove_session_table(&session_id, &drop.name.to_string())
+                    .await?;
+            }
+            // Views and the tables created from queries are not saved to the state backend,
+            // their plans and data living in this scheduler only
+            LogicalPlan::Ddl(DdlStatement::CreateView(cmd)) => {
+                warn!(
+                    "View {} of session {session_id} is only available on this scheduler",
+                    cmd.name
+                );
+            }
+            LogicalPlan::Ddl(DdlStatement::CreateMemoryTable(cmd)) => {
+                warn!("Table {} of session {session_id} is only available on this scheduler", cmd.name);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Plan a prepared statement again from its SQL, without executing the statements
+    /// changing the session which were executed when it was prepared
+    async fn restore_statement(
+        query: &str,
+        ctx: &SessionContext,
+    ) -> Result<LogicalPlan, Status> {
+        let plan_error = |e| Status::internal(format!("Error building plan: {e}"));
+        let statement = ctx
+            .state()
+            .create_logical_plan(query)
+            .await
+            .map_err(plan_error)?;
+        if matches!(statement, LogicalPlan::Ddl(_) | LogicalPlan::Statement(_)) {
+            return LogicalPlanBuilder::empty(false).build().map_err(plan_error);
+        }
+        ctx.execute_logical_plan(statement)
+            .await
+            .and_then(|df| df.into_optimized_plan())
+            .map_err(plan_error)
+    }
+
     async fn check_job(&self, job_id: &String) -> Result<Option<SuccessfulJob>, Status> {
         let status = self
             .server
@@ -222,7 +378,13 @@ impl FlightSqlServiceImpl {
                     e.error
                 )))?
             }
+            job_status::Status::Successful(comp) if comp.results_expired => Err(
+                Status::failed_precondition(format!("Results of job {job_id} expired")),
+            ),
             job_status::Status::Successful(comp) => Ok(Some(comp)),
+            job_status::Status::Cancelled(_) => {
+                Err(Status::cancelled(format!("Job {job_id} was cancelled")))
+            }
         }
     }
 
@@ -271,6 +433,7 @@ impl FlightSqlServiceImpl {
                     // Use executor ip:port for routing to flight result
                     host: exec_host.clone(),
                     port: exec_port,
+                    checksum: loc.checksum.clone(),
                 };
                 protobuf::Action {
                     action_type: Some(FetchPartition(fetch)),
@@ -309,6 +472,7 @@ impl FlightSqlServiceImpl {
             path: job_id.to_string(),
             host: host.clone(),
             port,
+            checksum: None,
         };
         let fetch = protobuf::Action {
             action_type: Some(FetchPartition(fetch)),
@@ -328,25 +492,69 @@ impl FlightSqlServiceImpl {
         Ok(fieps)
     }
 
-    fn cache_plan(&self, plan: LogicalPlan) -> Result<Uuid, Status> {
+    /// Cache the plan of a prepared statement and save its SQL to the session of `ctx`
+    async fn cache_plan(
+        &self,
+        ctx: &SessionContext,
+        query: &str,
+        plan: LogicalPlan,
+    ) -> Result<Uuid, Status> {
         let handle = Uuid::new_v4();
+        self.server
+            .state
+            .session_manager
+            .save_prepared_statement(&ctx.session_id(), handle.as_bytes(), query)
+            .await
+            .map_err(|e| {
+                Status::internal(format!("Error saving prepared statement: {e}"))
+            })?;
         self.statements.insert(handle, plan);
         Ok(handle)
     }
 
-    fn get_plan(&self, handle: &Uuid) -> Result<LogicalPlan, Status> {
+    /// The plan of a prepared statement of the session of `ctx`, planned again if another
+    /// scheduler prepared it
+    async fn get_plan(
+        &self,
+        ctx: &SessionContext,
+        handle: &Uuid,
+    ) -> Result<LogicalPlan, Status> {
         if let Some(plan) = self.statements.get(handle) {
-            Ok(plan.clone())
-        } else {
-            Err(Status::internal(format!(
-                "Statement handle not found: {handle}"
-            )))?
+            return Ok(plan.clone());
         }
+        let query = self
+            .server
+            .state
+            .session_manager
+            .prepared_statement(&ctx.session_id(), handle.as_bytes())
+            .await
+            .map_err(|e| {
+                Status::internal(format!("Error loading prepared statement: {e}"))
+            })?
+            .ok_or_else(|| {
+                Status::internal(format!("Statement handle not found: {handle}"))
+            })?;
+        let plan = Self::restore_statement(&query, ctx).await?;
+        self.statements.insert(*handle, plan.clone());
+        Ok(plan)
     }
 
-    fn remove_plan(&self, handle: Uuid) -> Result<(), Status> {
+    /// Remove a closed prepared statement, from the session of `ctx` if it is known
+    async fn remove_plan(&self, ctx: Option<&SessionContext>, handle: Uuid) {
         self.statements.remove(&handle);
-        Ok(())
+        self.explained
+            .retain(|_, explained| explained.statement != Some(handle));
+        if let Some(ctx) = ctx {
+            if let Err(e) = self
+                .server
+                .state
+                .session_manager
+                .remove_prepared_statement(&ctx.session_id(), handle.as_bytes())
+                .await
+            {
+                warn!("Failed to remove prepared statement {handle}: {e}");
+            }
+        }
     }
 
     fn df_schema_to_arrow(&self, schema: &DFSchemaRef) -> Result<Vec<u8>, Status> {
@@ -356,6 +564,8 @@ impl FlightSqlServiceImpl {
     }
 
     fn schema_to_arrow(&self, arrow_schema: SchemaRef) -> Result<Vec<u8>, Status> {
+        // dictionary ids as in the result files the executors serve
+        let arrow_schema = with_unique_dict_ids(&arrow_schema);
         let options = IpcWriteOptions::default();
         let pair = SchemaAsIpc::new(&arrow_schema, &options);
         let data_gen = IpcDataGenerator::default();
@@ -366,17 +576,47 @@ impl FlightSqlServiceImpl {
         Ok(schema_bytes)
     }
 
+    /// Submit the job of a plan which the client is authorized to run, owned by the client
     async fn enqueue_job(
         &self,
         ctx: Arc<SessionContext>,
         plan: &LogicalPlan,
+        sql: Option<&str>,
+        user: Option<&str>,
     ) -> Result<String, Status> {
+        self.authorize(user, plan)?;
+        if self.server.is_draining() {
+            return Err(Status::unavailable(
+                "Scheduler is draining and does not accept new queries",
+            ));
+        }
+        if let Some(reason) = self.server.await_capacity().await {
+            warn!("Rejecting query, the scheduler is overloaded: {reason}");
+            self.server.metrics_collector().record_rejected();
+            return Err(Status::resource_exhausted(format!(
+                "Scheduler is overloaded, retry later: {reason}"
+            )));
+        }
         let job_id = self.server.state.task_manager.generate_job_id();
         let job_name = format!("Flight SQL job {job_id}");
+        // Flight SQL clients fetch the results of jobs once they completed
+        let ctx = create_owned_job_context(&ctx, user, false).map_err(|e| {
+            Status::internal(format!("Failed to create job context: {e:?}"))
+        })?;
+        let ctx = self.server.route_job(&job_id, ctx, sql).map_err(|e| {
+            let msg = format!("Failed to route job {job_id} to a pool: {e}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        let quarantine = self.server.state.task_manager.query_quarantine();
+        if let Some(sql) = sql {
+            quarantine.add_job(&job_id, sql);
+        }
         self.server
             .submit_job(&job_id, &job_name, ctx, plan)
             .await
             .map_err(|e| {
+                quarantine.remove_job(&job_id);
                 let msg = format!("Failed to send JobQueued event for {job_id}: {e:?}");
                 error!("{}", msg);
                 Status::internal(msg)
@@ -409,8 +649,10 @@ impl FlightSqlServiceImpl {
         &self,
         ctx: Arc<SessionContext>,
         plan: &LogicalPlan,
+        sql: Option<&str>,
+        user: Option<&str>,
     ) -> Result<Response<FlightInfo>, Status> {
-        let job_id = self.enqueue_job(ctx, plan).await?;
+        let job_id = self.enqueue_job(ctx, plan, sql, user).await?;
 
         // poll for job completion
         let mut num_rows = 0;
@@ -434,6 +676,39 @@ impl FlightSqlServiceImpl {
         Ok(resp)
     }
 
+    /// Answer an `EXPLAIN` statement with its plans, which are planned by the scheduler and
+    /// fetched from it instead of running a job
+    async fn explain_plan(
+        &self,
+        ctx: Arc<SessionContext>,
+        plan: &LogicalPlan,
+        statement: Option<Uuid>,
+        user: Option<&str>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let session_id = ctx.session_id();
+        self.authorize(user, plan)?;
+        let job_id = self.server.state.task_manager.generate_job_id();
+        let data = self
+            .server
+            .state
+            .explain_job(&job_id, ctx, plan)
+            .await
+            .map_err(|e| Status::internal(format!("Error explaining plan: {e:?}")))?;
+
+        let handle = Uuid::new_v4();
+        let resp =
+            self.batch_to_schema_resp(&data, &format!("{EXPLAIN_JOB_PREFIX}{handle}"))?;
+        self.explained.insert(
+            handle,
+            ExplainedPlan {
+                session_id,
+                statement,
+                plans: data,
+            },
+        );
+        Ok(resp)
+    }
+
     async fn record_batch_to_resp(
         rb: RecordBatch,
     ) -> Result<
@@ -469,6 +744,283 @@ impl FlightSqlServiceImpl {
         let resp = Self::create_resp(schema_bytes, fieps, num_rows, num_bytes);
         Ok(resp)
     }
+
+    /// Fetch the flight of an action from the executor or scheduler at `host:port`
+    async fn fetch_flight(
+        action: &protobuf::Action,
+        host: &str,
+        port: u32,
+    ) -> Result<Streaming<FlightData>, Status> {
+        let addr = format!("http://{host}:{port}");
+        debug!("Scheduler proxying flight for to {}", addr);
+        let connection =
+            create_grpc_client_connection(addr.clone())
+                .await
+                .map_err(|e| {
+                    Status::internal(format!(
+                    "Error connecting to Ballista scheduler or executor at {addr}: {e:?}"
+                ))
+                })?;
+        let mut flight_client = FlightServiceClient::new(connection);
+        let buf = action.encode_to_vec();
+        let request = Request::new(Ticket { ticket: buf.into() });
+
+        let stream = flight_client
+            .do_get(request)
+            .await
+            .map_err(|e| Status::internal(format!("{e:?}")))?
+            .into_inner();
+        Ok(stream)
+    }
+
+    /// Run the statements of an interactive session one after another, until the client
+    /// closes its side of the exchange
+    async fn run_interactive_session(
+        self,
+        ctx: Arc<SessionContext>,
+        user: Option<String>,
+        mut statements: Streaming<FlightData>,
+        tx: Sender<Result<FlightData, Status>>,
+    ) {
+        let mut statement_id = 0;
+        loop {
+            let data = match statements.message().await {
+                Ok(Some(data)) => data,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(
+                        "Error receiving the statements of an interactive session: {e}"
+                    );
+                    break;
+                }
+            };
+            statement_id += 1;
+            let event = match self
+                .run_interactive_statement(
+                    &ctx,
+                    user.as_deref(),
+                    statement_id,
+                    &data,
+                    &tx,
+                )
+                .await
+            {
+                Ok(num_rows) => Event::Finished(StatementFinished { num_rows }),
+                Err(e) => {
+                    warn!("Interactive statement {statement_id} failed: {e}");
+                    Event::Failed(StatementFailed {
+                        error: e.message().to_owned(),
+                    })
+                }
+            };
+            if send_flight(&tx, interactive_event(statement_id, event))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+        debug!("Interactive session ended after {statement_id} statements");
+    }
+
+    /// Run a statement of an interactive session, streaming its progress and then its
+    /// results to the client. Returns the number of rows of the results.
+    async fn run_interactive_statement(
+        &self,
+        ctx: &Arc<SessionContext>,
+        user: Option<&str>,
+        statement_id: u64,
+        data: &FlightData,
+        tx: &Sender<Result<FlightData, Status>>,
+    ) -> Result<u64, Status> {
+        let sql = interactive_statement(data)?;
+        debug!("Interactive statement {statement_id}:\n{sql}");
+
+        // SQL queries whose jobs repeatedly failed are rejected right away
+        let quarantine = self.server.state.task_manager.query_quarantine();
+        if let Some(reason) = quarantine.check(&sql) {
+            warn!("Rejecting query: {reason}");
+            return Err(Status::failed_precondition(reason));
+        }
+
+        let plan = self.prepare_statement(&sql, ctx, user).await?;
+        if matches!(plan, LogicalPlan::Explain(_)) {
+            self.authorize(user, &plan)?;
+            let job_id = self.server.state.task_manager.generate_job_id();
+            let started = Event::Started(StatementStarted {
+                job_id: job_id.clone(),
+            });
+            send_flight(tx, interactive_event(statement_id, started)).await?;
+            let explained = self
+                .server
+                .state
+                .explain_job(&job_id, ctx.clone(), &plan)
+                .await
+                .map_err(|e| Status::internal(format!("Error explaining plan: {e:?}")))?;
+            let num_rows = explained.num_rows() as u64;
+            let schema = (*explained.schema()).clone();
+            let flights = batches_to_flight_data(schema, vec![explained])
+                .map_err(|e| Status::internal(format!("Error encoding batches: {e}")))?;
+            for flight in flights {
+                send_flight(tx, flight).await?;
+            }
+            return Ok(num_rows);
+        }
+
+        let job_id = self
+            .enqueue_job(ctx.clone(), &plan, Some(&sql), user)
+            .await?;
+        let result = self
+            .stream_job_results(statement_id, &job_id, &plan, tx)
+            .await;
+        if result.is_err() && tx.is_closed() {
+            // Nobody is waiting for the results anymore
+            debug!("Cancelling job {job_id} of a closed interactive session");
+            if let Ok(sender) = self.server.query_stage_event_loop.get_sender() {
+                let _ = sender
+                    .post_event(QueryStageSchedulerEvent::JobCancel(job_id))
+                    .await;
+            }
+        }
+        result
+    }
+
+    /// Stream the progress of a job until it completes, then its results
+    async fn stream_job_results(
+        &self,
+        statement_id: u64,
+        job_id: &str,
+        plan: &LogicalPlan,
+        tx: &Sender<Result<FlightData, Status>>,
+    ) -> Result<u64, Status> {
+        let started = Event::Started(StatementStarted {
+            job_id: job_id.to_owned(),
+        });
+        send_flight(tx, interactive_event(statement_id, started)).await?;
+
+        // poll for job completion, sending the progress whenever a stage completes
+        let job_id = job_id.to_owned();
+        let mut progress = None;
+        let completed = loop {
+            sleep(Duration::from_millis(100)).await;
+            if tx.is_closed() {
+                return Err(Status::cancelled(
+                    "Interactive session closed by the client",
+                ));
+            }
+            let stages = self
+                .server
+                .state
+                .task_manager
+                .with_active_graph(&job_id, |graph| {
+                    let overview = JobOverview::from(&*graph);
+                    (overview.num_stages, overview.completed_stages)
+                })
+                .await;
+            if stages.is_some() && stages != progress {
+                progress = stages;
+                let (num_stages, completed_stages) = stages.unwrap_or_default();
+                let event = Event::Progress(StatementProgress {
+                    num_stages: num_stages as u32,
+                    completed_stages: completed_stages as u32,
+                });
+                send_flight(tx, interactive_event(statement_id, event)).await?;
+            }
+            if let Some(comp) = self.check_job(&job_id).await? {
+                break comp;
+            }
+        };
+
+        let schema: Schema = (&**plan.schema()).into();
+        // dictionary ids as in the result files the executors serve
+        let schema = with_unique_dict_ids(&Arc::new(schema));
+        let options = IpcWriteOptions::default();
+        send_flight(tx, SchemaAsIpc::new(&schema, &options).into()).await?;
+
+        let mut num_rows = 0;
+        for loc in completed.partition_location {
+            let (host, port) = match &loc.executor_meta {
+                Some(md) => (md.host.clone(), md.port),
+                None => Err(Status::internal(
+                    "Invalid partition location, missing executor metadata",
+                ))?,
+            };
+            let id = loc
+                .partition_id
+                .ok_or_else(|| Status::internal("Error getting partition ID"))?;
+            let action = protobuf::Action {
+                action_type: Some(FetchPartition(protobuf::FetchPartition {
+                    job_id: id.job_id,
+                    stage_id: id.stage_id,
+                    partition_id: id.partition_id,
+                    path: loc.path,
+                    host: host.clone(),
+                    port,
+                    checksum: loc.checksum,
+                })),
+                settings: vec![],
+            };
+            let flight = Self::fetch_flight(&action, &host, port).await?;
+            let mut batches = FlightRecordBatchStream::new_from_flight_data(
+                flight.map_err(FlightError::Tonic),
+            );
+            while let Some(batch) = batches.next().await {
+                let batch = batch.map_err(|e| {
+                    Status::internal(format!("Error fetching results: {e}"))
+                })?;
+                num_rows += batch.num_rows() as u64;
+                let (dictionaries, data) = flight_data_from_arrow_batch(&batch, &options);
+                for flight in dictionaries {
+                    send_flight(tx, flight).await?;
+                }
+                send_flight(tx, data).await?;
+            }
+        }
+        Ok(num_rows)
+    }
+}
+
+/// Decode the `CommandStatementQuery` of a message sent by the client of an interactive
+/// session
+fn interactive_statement(data: &FlightData) -> Result<String, Status> {
+    let cmd = data
+        .flight_descriptor
+        .as_ref()
+        .filter(|descriptor| descriptor.r#type == DescriptorType::Cmd as i32)
+        .map(|descriptor| descriptor.cmd.clone())
+        .ok_or_else(|| Status::invalid_argument("Expected a command descriptor"))?;
+    let message = arrow_flight::sql::Any::decode(cmd)
+        .map_err(|e| Status::invalid_argument(format!("Error decoding command: {e}")))?;
+    let query: CommandStatementQuery = message
+        .unpack()
+        .map_err(|e| Status::invalid_argument(format!("{e:?}")))?
+        .ok_or_else(|| {
+            Status::invalid_argument(format!(
+                "Expected a CommandStatementQuery but got {}",
+                message.type_url
+            ))
+        })?;
+    Ok(query.query)
+}
+
+fn interactive_event(statement_id: u64, event: Event) -> FlightData {
+    let event = InteractiveEvent {
+        statement_id,
+        event: Some(event),
+    };
+    FlightData {
+        app_metadata: event.encode_to_vec().into(),
+        ..Default::default()
+    }
+}
+
+async fn send_flight(
+    tx: &Sender<Result<FlightData, Status>>,
+    data: FlightData,
+) -> Result<(), Status> {
+    tx.send(Ok(data))
+        .await
+        .map_err(|_| Status::cancelled("Interactive session closed by the client"))
 }
 
 #[tonic::async_trait]
@@ -537,7 +1089,7 @@ impl FlightSqlService for FlightSqlServiceImpl {
         message: arrow_flight::sql::Any,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>, Status> {
         debug!("do_get_fallback type_url: {}", message.type_url);
-        let ctx = self.get_ctx(&request)?;
+        let ctx = self.get_ctx(&request).await?;
         if !message.is::<protobuf::Action>() {
             Err(Status::unimplemented(format!(
                 "do_get: The defined request is invalid: {}",
@@ -572,29 +1124,23 @@ impl FlightSqlService for FlightSqlServiceImpl {
                 let resp = Self::record_batch_to_resp(rb).await?;
                 return Ok(resp);
             }
+            job_id if job_id.starts_with(EXPLAIN_JOB_PREFIX) => {
+                debug!("Responding with explained plan");
+                let handle = Uuid::from_str(&job_id[EXPLAIN_JOB_PREFIX.len()..])
+                    .map_err(|e| {
+                        Status::internal(format!("Error decoding handle: {e}"))
+                    })?;
+                let (_, explained) = self.explained.remove(&handle).ok_or_else(|| {
+                    Status::not_found(format!("Explained plan not found: {handle}"))
+                })?;
+                let resp = Self::record_batch_to_resp(explained.plans).await?;
+                return Ok(resp);
+            }
             _ => {}
         }
 
         // Proxy the flight
-        let addr = format!("http://{}:{}", fp.host, fp.port);
-        debug!("Scheduler proxying flight for to {}", addr);
-        let connection =
-            create_grpc_client_connection(addr.clone())
-                .await
-                .map_err(|e| {
-                    Status::internal(format!(
-                    "Error connecting to Ballista scheduler or executor at {addr}: {e:?}"
-                ))
-                })?;
-        let mut flight_client = FlightServiceClient::new(connection);
-        let buf = action.encode_to_vec();
-        let request = Request::new(Ticket { ticket: buf.into() });
-
-        let stream = flight_client
-            .do_get(request)
-            .await
-            .map_err(|e| Status::internal(format!("{e:?}")))?
-            .into_inner();
+        let stream = Self::fetch_flight(&action, &fp.host, fp.port).await?;
         Ok(Response::new(Box::pin(stream)))
     }
 
@@ -615,9 +1161,23 @@ impl FlightSqlService for FlightSqlServiceImpl {
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_statement query:\n{}", query.query);
 
-        let ctx = self.get_ctx(&request)?;
-        let plan = Self::prepare_statement(&query.query, &ctx).await?;
-        let resp = self.execute_plan(ctx, &plan).await?;
+        // SQL queries whose jobs repeatedly failed are rejected right away
+        let quarantine = self.server.state.task_manager.query_quarantine();
+        if let Some(reason) = quarantine.check(&query.query) {
+            warn!("Rejecting query: {reason}");
+            return Err(Status::failed_precondition(reason));
+        }
+
+        let ctx = self.get_ctx(&request).await?;
+        let user = self.authenticate(&request, &ctx)?;
+        let user = user.as_deref();
+        let plan = self.prepare_statement(&query.query, &ctx, user).await?;
+        let resp = if matches!(plan, LogicalPlan::Explain(_)) {
+            self.explain_plan(ctx, &plan, None, user).await?
+        } else {
+            self.execute_plan(ctx, &plan, Some(&query.query), user)
+                .await?
+        };
 
         debug!("Returning flight info...");
         Ok(resp)
@@ -629,11 +1189,17 @@ impl FlightSqlService for FlightSqlServiceImpl {
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_prepared_statement");
-        let ctx = self.get_ctx(&request)?;
+        let ctx = self.get_ctx(&request).await?;
         let handle = Uuid::from_slice(handle.prepared_statement_handle.as_ref())
             .map_err(|e| Status::internal(format!("Error decoding handle: {e}")))?;
-        let plan = self.get_plan(&handle)?;
-        let resp = self.execute_plan(ctx, &plan).await?;
+        let plan = self.get_plan(&ctx, &handle).await?;
+        let user = self.authenticate(&request, &ctx)?;
+        let resp = if matches!(plan, LogicalPlan::Explain(_)) {
+            self.explain_plan(ctx, &plan, Some(handle), user.as_deref())
+                .await?
+        } else {
+            self.execute_plan(ctx, &plan, None, user.as_deref()).await?
+        };
 
         debug!("Responding to query {}...", handle);
         Ok(resp)
@@ -662,7 +1228,7 @@ impl FlightSqlService for FlightSqlServiceImpl {
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>, Status> {
         debug!("get_flight_info_tables");
-        let ctx = self.get_ctx(&request)?;
+        let ctx = self.get_ctx(&request).await?;
         let data = self
             .tables(ctx)
             .map_err(|e| Status::internal(format!("Error getting tables: {e}")))?;
@@ -863,11 +1429,12 @@ impl FlightSqlService for FlightSqlServiceImpl {
         request: Request<Streaming<FlightData>>,
     ) -> Result<i64, Status> {
         debug!("do_put_prepared_statement_update");
-        let ctx = self.get_ctx(&request)?;
+        let ctx = self.get_ctx(&request).await?;
         let handle = Uuid::from_slice(handle.prepared_statement_handle.as_ref())
             .map_err(|e| Status::internal(format!("Error decoding handle: {e}")))?;
-        let plan = self.get_plan(&handle)?;
-        let _ = self.execute_plan(ctx, &plan).await?;
+        let plan = self.get_plan(&ctx, &handle).await?;
+        let user = self.authenticate(&request, &ctx)?;
+        let _ = self.execute_plan(ctx, &plan, None, user.as_deref()).await?;
         debug!("Sending -1 rows affected");
         Ok(-1)
     }
@@ -878,10 +1445,13 @@ impl FlightSqlService for FlightSqlServiceImpl {
         request: Request<Action>,
     ) -> Result<ActionCreatePreparedStatementResult, Status> {
         debug!("do_action_create_prepared_statement");
-        let ctx = self.get_ctx(&request)?;
-        let plan = Self::prepare_statement(&query.query, &ctx).await?;
+        let ctx = self.get_ctx(&request).await?;
+        let user = self.authenticate(&request, &ctx)?;
+        let plan = self
+            .prepare_statement(&query.query, &ctx, user.as_deref())
+            .await?;
         let schema_bytes = self.df_schema_to_arrow(plan.schema())?;
-        let handle = self.cache_plan(plan)?;
+        let handle = self.cache_plan(&ctx, &query.query, plan).await?;
         debug!("Prepared statement {}:\n{}", handle, query.query);
         let res = ActionCreatePreparedStatementResult {
             prepared_statement_handle: handle.as_bytes().to_vec().into(),
@@ -894,7 +1464,7 @@ impl FlightSqlService for FlightSqlServiceImpl {
     async fn do_action_close_prepared_statement(
         &self,
         handle: ActionClosePreparedStatementRequest,
-        _request: Request<Action>,
+        request: Request<Action>,
     ) {
         debug!("do_action_close_prepared_statement");
         let handle = Uuid::from_slice(handle.prepared_statement_handle.as_ref());
@@ -904,8 +1474,159 @@ impl FlightSqlService for FlightSqlServiceImpl {
         } else {
             return;
         };
-        let _ = self.remove_plan(handle);
+        let ctx = self.get_ctx(&request).await.ok();
+        self.remove_plan(ctx.as_deref(), handle).await;
     }
 
     async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
 }
+
+/// Flight service of the scheduler, which serves Flight SQL and interactive sessions.
+///
+/// In an interactive session a client keeps a DoExchange stream open and sends SQL
+/// statements as `CommandStatementQuery` commands in the descriptor of its messages. The
+/// statements run one after another in the session of the client, and for each the scheduler
+/// sends a `started` event, `progress` events as the stages of its job complete, the schema
+/// and record batches of its results, and finally a `finished` or `failed` event. Events are
+/// `InteractiveEvent`s in the app_metadata of messages without data.
+#[derive(Clone)]
+pub struct BallistaFlightService {
+    flight_sql: FlightSqlServiceImpl,
+}
+
+impl BallistaFlightService {
+    pub fn new(flight_sql: FlightSqlServiceImpl) -> Self {
+        Self { flight_sql }
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for BallistaFlightService {
+    type HandshakeStream = <FlightSqlServiceImpl as FlightService>::HandshakeStream;
+    type ListFlightsStream = <FlightSqlServiceImpl as FlightService>::ListFlightsStream;
+    type DoGetStream = <FlightSqlServiceImpl as FlightService>::DoGetStream;
+    type DoPutStream = <FlightSqlServiceImpl as FlightService>::DoPutStream;
+    type DoActionStream = <FlightSqlServiceImpl as FlightService>::DoActionStream;
+    type ListActionsStream = <FlightSqlServiceImpl as FlightService>::ListActionsStream;
+    type DoExchangeStream = <FlightSqlServiceImpl as FlightService>::DoExchangeStream;
+
+    async fn handshake(
+        &self,
+        request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        FlightService::handshake(&self.flight_sql, request).await
+    }
+
+    async fn list_flights(
+        &self,
+        request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        FlightService::list_flights(&self.flight_sql, request).await
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        FlightService::get_flight_info(&self.flight_sql, request).await
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        FlightService::get_schema(&self.flight_sql, request).await
+    }
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        FlightService::do_get(&self.flight_sql, request).await
+    }
+
+    async fn do_put(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        FlightService::do_put(&self.flight_sql, request).await
+    }
+
+    async fn do_action(
+        &self,
+        request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        FlightService::do_action(&self.flight_sql, request).await
+    }
+
+    async fn list_actions(
+        &self,
+        request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        FlightService::list_actions(&self.flight_sql, request).await
+    }
+
+    async fn do_exchange(
+        &self,
+        request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        debug!("do_exchange");
+        let ctx = self.flight_sql.get_ctx(&request).await?;
+        let user = self.flight_sql.authenticate(&request, &ctx)?;
+        let (tx, rx) = channel(16);
+        tokio::spawn(self.flight_sql.clone().run_interactive_session(
+            ctx,
+            user,
+            request.into_inner(),
+            tx,
+        ));
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interactive_statement() {
+        let query = CommandStatementQuery {
+            query: "SELECT 1".to_owned(),
+        };
+        let data = FlightData {
+            flight_descriptor: Some(FlightDescriptor::new_cmd(
+                query.as_any().encode_to_vec(),
+            )),
+            ..Default::default()
+        };
+        assert_eq!(interactive_statement(&data).unwrap(), "SELECT 1");
+
+        // Only statement queries are accepted
+        let data = FlightData {
+            flight_descriptor: Some(FlightDescriptor::new_cmd(
+                CommandGetTableTypes {}.as_any().encode_to_vec(),
+            )),
+            ..Default::default()
+        };
+        assert!(interactive_statement(&data).is_err());
+        assert!(interactive_statement(&FlightData::default()).is_err());
+
+        let event = interactive_event(
+            2,
+            Event::Progress(StatementProgress {
+                num_stages: 3,
+                completed_stages: 1,
+            }),
+        );
+        assert!(event.data_header.is_empty());
+        let event = InteractiveEvent::decode(event.app_metadata).unwrap();
+        assert_eq!(event.statement_id, 2);
+        assert!(matches!(
+            event.event,
+            Some(Event::Progress(StatementProgress {
+                num_stages: 3,
+                completed_stages: 1
+            }))
+        ));
+    }
+}
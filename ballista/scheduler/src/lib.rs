@@ -18,11 +18,13 @@
 #![doc = include_str ! ("../README.md")]
 
 pub mod api;
+pub mod clock;
 pub mod cluster;
 pub mod config;
 pub mod display;
 pub mod metrics;
 pub mod planner;
+pub mod plugin;
 pub mod scheduler_process;
 pub mod scheduler_server;
 #[cfg(feature = "sled")]
@@ -19,8 +19,10 @@
 pub mod prometheus;
 
 use crate::metrics::prometheus::PrometheusMetricsCollector;
+use crate::state::executor_manager::SlotMetrics;
 use ballista_core::error::Result;
 use std::sync::Arc;
+use std::time::Duration;
 
 /// Interface for recording metrics events in the scheduler. An instance of `Arc<dyn SchedulerMetricsCollector>`
 /// will be passed when constructing the `QueryStageScheduler` which is the core event loop of the scheduler.
@@ -47,10 +49,21 @@ pub trait SchedulerMetricsCollector: Send + Sync {
     /// Record that job with `job_id` was cancelled.
     fn record_cancelled(&self, job_id: &str);
 
+    /// Record that a query was rejected because the scheduler is overloaded
+    fn record_rejected(&self) {}
+
     /// Set the current number of pending tasks in scheduler. A pending task is a task that is available
     /// to schedule on an executor but cannot be scheduled because no resources are available.
     fn set_pending_tasks_queue_size(&self, value: u64);
 
+    /// Set the current utilization of the task slots of the executors, which tells whether the
+    /// cluster is bound by its task slots
+    fn set_slot_metrics(&self, metrics: &SlotMetrics);
+
+    /// Set the current number of events queued for the event loop of the scheduler, and the
+    /// estimated time before a new event is processed, which tell whether it falls behind
+    fn set_event_loop_metrics(&self, queued_events: u64, lag: Duration);
+
     /// Gather current metric set that should be returned when calling the scheduler's metrics API
     /// Should return a tuple containing the content of the metric set and the content type (e.g. `application/json`, `text/plain`, etc)
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>>;
@@ -67,6 +80,8 @@ impl SchedulerMetricsCollector for NoopMetricsCollector {
     fn record_failed(&self, _job_id: &str, _queued_at: u64, _failed_at: u64) {}
     fn record_cancelled(&self, _job_id: &str) {}
     fn set_pending_tasks_queue_size(&self, _value: u64) {}
+    fn set_slot_metrics(&self, _metrics: &SlotMetrics) {}
+    fn set_event_loop_metrics(&self, _queued_events: u64, _lag: Duration) {}
 
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         Ok(None)
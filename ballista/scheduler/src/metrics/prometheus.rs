@@ -16,6 +16,7 @@
 // under the License.
 
 use crate::metrics::SchedulerMetricsCollector;
+use crate::state::executor_manager::SlotMetrics;
 use ballista_core::error::{BallistaError, Result};
 
 use once_cell::sync::OnceCell;
@@ -25,11 +26,12 @@ use prometheus::{
 };
 use prometheus::{Encoder, TextEncoder};
 use std::sync::Arc;
+use std::time::Duration;
 
 static COLLECTOR: OnceCell<Arc<dyn SchedulerMetricsCollector>> = OnceCell::new();
 
 /// SchedulerMetricsCollector implementation based on Prometheus. By default this will track
-/// 7 metrics:
+/// 15 metrics:
 /// *job_exec_time_seconds* - Histogram of successful job execution time in seconds
 /// *planning_time_ms* - Histogram of job planning time in milliseconds
 /// *failed* - Counter of failed jobs
@@ -37,7 +39,15 @@ static COLLECTOR: OnceCell<Arc<dyn SchedulerMetricsCollector>> = OnceCell::new()
 /// *job_cancelled_total* - Counter of cancelled jobs
 /// *job_completed_total* - Counter of completed jobs
 /// *job_submitted_total* - Counter of submitted jobs
+/// *job_rejected_total* - Counter of queries rejected because the scheduler is overloaded
 /// *pending_task_queue_size* - Number of pending tasks
+/// *task_slots* - Number of task slots of the registered executors
+/// *reserved_task_slots* - Number of reserved task slots
+/// *cancelled_reservations* - Number of task slot reservations returned to the pool
+/// *slot_reservation_requests* - Number of requests to reserve task slots
+/// *slot_reservation_seconds* - Total time spent reserving task slots in seconds
+/// *event_loop_queued_events* - Number of events queued for the event loop
+/// *event_loop_lag_seconds* - Estimated time before a new event is processed in seconds
 pub struct PrometheusMetricsCollector {
     execution_time: Histogram,
     planning_time: Histogram,
@@ -45,7 +55,15 @@ pub struct PrometheusMetricsCollector {
     cancelled: Counter,
     completed: Counter,
     submitted: Counter,
+    rejected: Counter,
     pending_queue_size: Gauge,
+    task_slots: Gauge,
+    reserved_task_slots: Gauge,
+    cancelled_reservations: Gauge,
+    reservation_requests: Gauge,
+    reservation_seconds: Gauge,
+    queued_events: Gauge,
+    event_loop_lag: Gauge,
 }
 
 impl PrometheusMetricsCollector {
@@ -106,6 +124,15 @@ impl PrometheusMetricsCollector {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
+        let rejected = register_counter_with_registry!(
+            "job_rejected_total",
+            "Counter of queries rejected because the scheduler is overloaded",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
         let pending_queue_size = register_gauge_with_registry!(
             "pending_task_queue_size",
             "Number of pending tasks",
@@ -115,6 +142,69 @@ impl PrometheusMetricsCollector {
             BallistaError::Internal(format!("Error registering metric: {e:?}"))
         })?;
 
+        let task_slots = register_gauge_with_registry!(
+            "task_slots",
+            "Number of task slots of the registered executors",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let reserved_task_slots = register_gauge_with_registry!(
+            "reserved_task_slots",
+            "Number of reserved task slots",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let cancelled_reservations = register_gauge_with_registry!(
+            "cancelled_reservations",
+            "Number of task slot reservations returned to the pool",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let reservation_requests = register_gauge_with_registry!(
+            "slot_reservation_requests",
+            "Number of requests to reserve task slots",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let reservation_seconds = register_gauge_with_registry!(
+            "slot_reservation_seconds",
+            "Total time spent reserving task slots in seconds",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let queued_events = register_gauge_with_registry!(
+            "event_loop_queued_events",
+            "Number of events queued for the event loop",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
+        let event_loop_lag = register_gauge_with_registry!(
+            "event_loop_lag_seconds",
+            "Estimated time before a new event is processed in seconds",
+            registry
+        )
+        .map_err(|e| {
+            BallistaError::Internal(format!("Error registering metric: {e:?}"))
+        })?;
+
         Ok(Self {
             execution_time,
             planning_time,
@@ -122,7 +212,15 @@ impl PrometheusMetricsCollector {
             cancelled,
             completed,
             submitted,
+            rejected,
             pending_queue_size,
+            task_slots,
+            reserved_task_slots,
+            cancelled_reservations,
+            reservation_requests,
+            reservation_seconds,
+            queued_events,
+            event_loop_lag,
         })
     }
 
@@ -158,10 +256,30 @@ impl SchedulerMetricsCollector for PrometheusMetricsCollector {
         self.cancelled.inc();
     }
 
+    fn record_rejected(&self) {
+        self.rejected.inc();
+    }
+
     fn set_pending_tasks_queue_size(&self, value: u64) {
         self.pending_queue_size.set(value as f64);
     }
 
+    fn set_slot_metrics(&self, metrics: &SlotMetrics) {
+        self.task_slots.set(metrics.total_slots as f64);
+        self.reserved_task_slots.set(metrics.reserved_slots as f64);
+        self.cancelled_reservations
+            .set(metrics.cancelled_reservations as f64);
+        self.reservation_requests
+            .set(metrics.reservation_requests as f64);
+        self.reservation_seconds
+            .set(metrics.reservation_latency.as_secs_f64());
+    }
+
+    fn set_event_loop_metrics(&self, queued_events: u64, lag: Duration) {
+        self.queued_events.set(queued_events as f64);
+        self.event_loop_lag.set(lag.as_secs_f64());
+    }
+
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         let encoder = TextEncoder::new();
 
@@ -17,20 +17,30 @@
 
 //! Distributed query execution
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
 use std::sync::Arc;
 
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::{
-    execution_plans::{ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec},
-    serde::scheduler::PartitionLocation,
+    execution_plans::{
+        ConcurrencyLimitExec, GpuRequirementExec, ResultExport, ShuffleCodec,
+        ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec,
+    },
+    serde::scheduler::{PartitionLocation, TaskResources},
 };
+use datafusion::physical_plan::aggregates::AggregateExec;
 use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+use datafusion::physical_plan::empty::EmptyExec;
+use datafusion::physical_plan::file_format::{CsvExec, NdJsonExec, ParquetExec};
+use datafusion::physical_plan::joins::{CrossJoinExec, HashJoinExec, SortMergeJoinExec};
 use datafusion::physical_plan::repartition::RepartitionExec;
+use datafusion::physical_plan::sorts::sort::SortExec;
 use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
-use datafusion::physical_plan::windows::WindowAggExec;
+use datafusion::physical_plan::windows::{BoundedWindowAggExec, WindowAggExec};
 use datafusion::physical_plan::{
-    with_new_children_if_necessary, ExecutionPlan, Partitioning,
+    with_new_children_if_necessary, DisplayFormatType, Distribution, ExecutionPlan,
+    Partitioning,
 };
 
 use log::{debug, info};
@@ -39,12 +49,274 @@ type PartialQueryStageResult = (Arc<dyn ExecutionPlan>, Vec<Arc<ShuffleWriterExe
 
 pub struct DistributedPlanner {
     next_stage_id: usize,
+    /// Maximum number of columns for which hash shuffles use the row codec, 0 to disable
+    row_codec_max_columns: usize,
+    /// Bytes of rows buffered per output partition by hash shuffle writes, 0 to disable
+    shuffle_write_buffer_size: usize,
+    /// Record the CRC32 checksum of shuffle files so that their readers detect corruption
+    shuffle_checksums: bool,
+    /// Write the shuffle files of schemas with dictionary encoded columns in the Arrow IPC
+    /// streaming format, which writes each dictionary once for the batches sharing it
+    shuffle_dictionary_sharing: bool,
+    /// Directory shared by the executors the shuffle files of every stage are copied to
+    checkpoint_dir: Option<String>,
+    /// Object store prefix of the client the result files of the final stage are exported to
+    result_export: Option<ResultExport>,
+    /// Plan the whole query as a single stage with a single task, without any shuffle
+    single_task: bool,
+    /// Resources required by a task which does not buffer its input
+    task_resources: TaskResources,
+    /// Maximum number of tasks running at once of the stages annotated with a
+    /// [ConcurrencyLimitExec], keyed by stage ID
+    max_concurrent_tasks: HashMap<usize, usize>,
+    /// GPUs required by each task of the stages annotated with a [GpuRequirementExec], keyed
+    /// by stage ID
+    stage_gpus: HashMap<usize, u32>,
+    /// Stages planned so far whose output can be read by any shuffle of the same input with
+    /// the same partitioning, keyed by [shared_stage_key]
+    shared_stages: HashMap<String, Arc<ShuffleWriterExec>>,
 }
 
 impl DistributedPlanner {
     pub fn new() -> Self {
-        Self { next_stage_id: 0 }
+        Self {
+            next_stage_id: 0,
+            row_codec_max_columns: 0,
+            shuffle_write_buffer_size: 0,
+            shuffle_checksums: false,
+            shuffle_dictionary_sharing: true,
+            checkpoint_dir: None,
+            result_export: None,
+            single_task: false,
+            task_resources: TaskResources::default(),
+            max_concurrent_tasks: HashMap::new(),
+            stage_gpus: HashMap::new(),
+            shared_stages: HashMap::new(),
+        }
+    }
+
+    /// Use the row codec for hash shuffles with at most `row_codec_max_columns` columns
+    pub fn with_row_codec_max_columns(mut self, row_codec_max_columns: usize) -> Self {
+        self.row_codec_max_columns = row_codec_max_columns;
+        self
+    }
+
+    /// Buffer `shuffle_write_buffer_size` bytes of rows per output partition in hash shuffle
+    /// writes
+    pub fn with_shuffle_write_buffer_size(
+        mut self,
+        shuffle_write_buffer_size: usize,
+    ) -> Self {
+        self.shuffle_write_buffer_size = shuffle_write_buffer_size;
+        self
+    }
+
+    /// Record the CRC32 checksum of the shuffle files written by every stage
+    pub fn with_shuffle_checksums(mut self, shuffle_checksums: bool) -> Self {
+        self.shuffle_checksums = shuffle_checksums;
+        self
+    }
+
+    /// Write the shuffle files of stages whose output has dictionary encoded columns in the
+    /// Arrow IPC streaming format, so that their dictionaries are not repeated or expanded
+    pub fn with_shuffle_dictionary_sharing(
+        mut self,
+        shuffle_dictionary_sharing: bool,
+    ) -> Self {
+        self.shuffle_dictionary_sharing = shuffle_dictionary_sharing;
+        self
+    }
+
+    /// Copy the shuffle files written by every stage to `checkpoint_dir`, so that the
+    /// outputs of completed stages survive the loss of the executors which wrote them
+    pub fn with_checkpoint_dir(mut self, checkpoint_dir: Option<String>) -> Self {
+        self.checkpoint_dir = checkpoint_dir;
+        self
+    }
+
+    /// Export the result files of the final stage to an object store prefix of the client
+    /// instead of keeping them on the executors for the client to fetch
+    pub fn with_result_export(mut self, result_export: Option<ResultExport>) -> Self {
+        self.result_export = result_export;
+        self
+    }
+
+    /// Plan queries as a single stage running as a single task, which executes the exchanges
+    /// of the query within the task instead of shuffling data between stages. Meant for
+    /// queries small enough for a single executor, whose latency is then dominated by the
+    /// scheduling of stages.
+    pub fn with_single_task(mut self, single_task: bool) -> Self {
+        self.single_task = single_task;
+        self
+    }
+
+    /// Base resources required by every task, used by [Self::estimate_task_resources]
+    pub fn with_task_resources(mut self, task_resources: TaskResources) -> Self {
+        self.task_resources = task_resources;
+        self
+    }
+
+    /// Estimate the resources required by each task of a query stage. Every operator which
+    /// buffers its input in memory (aggregations, joins, sorts and windows) adds the base memory
+    /// requirement once more, cores are not scaled.
+    pub fn estimate_task_resources(
+        &self,
+        stage: &Arc<dyn ExecutionPlan>,
+    ) -> TaskResources {
+        let memory_intensive = memory_intensive_operators(stage) as u64;
+        TaskResources::new(
+            self.task_resources
+                .memory_bytes
+                .saturating_mul(1 + memory_intensive),
+            self.task_resources.vcores,
+        )
+    }
+
+    /// Maximum number of tasks of a planned stage running at once, set by the
+    /// [ConcurrencyLimitExec]s of the stage. 0 means no limit.
+    pub fn max_concurrent_tasks(&self, stage_id: usize) -> usize {
+        self.max_concurrent_tasks
+            .get(&stage_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// GPUs required by each task of a planned stage, set by the [GpuRequirementExec]s of the
+    /// stage. 0 means the stage does not require GPUs.
+    pub fn stage_gpus(&self, stage_id: usize) -> u32 {
+        self.stage_gpus.get(&stage_id).copied().unwrap_or_default()
+    }
+
+    /// Remove the [ConcurrencyLimitExec]s of a stage, which executors do not run, and record
+    /// the lowest of their limits as the limit of the stage
+    fn remove_concurrency_limits(
+        &mut self,
+        stage: Arc<ShuffleWriterExec>,
+    ) -> Result<Arc<ShuffleWriterExec>> {
+        let (input, limit) = remove_concurrency_limits(stage.children()[0].clone())?;
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return Ok(stage),
+        };
+        self.max_concurrent_tasks.insert(stage.stage_id(), limit);
+        with_stage_input(stage, input)
+    }
+
+    /// Remove the [GpuRequirementExec]s of a stage, which executors do not run, and record
+    /// the highest of their GPUs as the GPUs required by each task of the stage
+    fn remove_gpu_requirements(
+        &mut self,
+        stage: Arc<ShuffleWriterExec>,
+    ) -> Result<Arc<ShuffleWriterExec>> {
+        let (input, gpus) = remove_gpu_requirements(stage.children()[0].clone())?;
+        let gpus = match gpus {
+            Some(gpus) => gpus,
+            None => return Ok(stage),
+        };
+        if gpus > 0 {
+            self.stage_gpus.insert(stage.stage_id(), gpus);
+        }
+        with_stage_input(stage, input)
+    }
+}
+
+/// Replace the input of a stage
+fn with_stage_input(
+    stage: Arc<ShuffleWriterExec>,
+    input: Arc<dyn ExecutionPlan>,
+) -> Result<Arc<ShuffleWriterExec>> {
+    let stage = stage.with_new_children(vec![input])?;
+    stage
+        .as_any()
+        .downcast_ref::<ShuffleWriterExec>()
+        .map(|stage| Arc::new(stage.clone()))
+        .ok_or_else(|| {
+            BallistaError::Internal(
+                "Expected the stage to remain a ShuffleWriterExec".to_owned(),
+            )
+        })
+}
+
+/// Remove the [ConcurrencyLimitExec]s of a plan, returns the plan without them and the lowest
+/// of their limits, if any
+fn remove_concurrency_limits(
+    plan: Arc<dyn ExecutionPlan>,
+) -> Result<(Arc<dyn ExecutionPlan>, Option<usize>)> {
+    if let Some(limit_exec) = plan.as_any().downcast_ref::<ConcurrencyLimitExec>() {
+        let (input, input_limit) = remove_concurrency_limits(limit_exec.input().clone())?;
+        let limit = Some(limit_exec.max_concurrent_tasks()).filter(|limit| *limit > 0);
+        return Ok((input, min_limit(limit, input_limit)));
+    }
+
+    let children = plan.children();
+    if children.is_empty() {
+        return Ok((plan, None));
+    }
+    let mut limit = None;
+    let mut new_children = Vec::with_capacity(children.len());
+    for child in children {
+        let (child, child_limit) = remove_concurrency_limits(child)?;
+        limit = min_limit(limit, child_limit);
+        new_children.push(child);
+    }
+    Ok((
+        with_new_children_if_necessary(plan, new_children)?.into(),
+        limit,
+    ))
+}
+
+/// Remove the [GpuRequirementExec]s of a plan, returns the plan without them and the highest
+/// of their GPUs, if any
+fn remove_gpu_requirements(
+    plan: Arc<dyn ExecutionPlan>,
+) -> Result<(Arc<dyn ExecutionPlan>, Option<u32>)> {
+    if let Some(gpu_exec) = plan.as_any().downcast_ref::<GpuRequirementExec>() {
+        let (input, input_gpus) = remove_gpu_requirements(gpu_exec.input().clone())?;
+        let gpus = input_gpus.unwrap_or_default().max(gpu_exec.gpus());
+        return Ok((input, Some(gpus)));
+    }
+
+    let children = plan.children();
+    if children.is_empty() {
+        return Ok((plan, None));
+    }
+    let mut gpus = None;
+    let mut new_children = Vec::with_capacity(children.len());
+    for child in children {
+        let (child, child_gpus) = remove_gpu_requirements(child)?;
+        gpus = gpus.max(child_gpus);
+        new_children.push(child);
     }
+    Ok((
+        with_new_children_if_necessary(plan, new_children)?.into(),
+        gpus,
+    ))
+}
+
+fn min_limit(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn memory_intensive_operators(plan: &Arc<dyn ExecutionPlan>) -> usize {
+    let any = plan.as_any();
+    let own = usize::from(
+        any.is::<AggregateExec>()
+            || any.is::<HashJoinExec>()
+            || any.is::<SortMergeJoinExec>()
+            || any.is::<CrossJoinExec>()
+            || any.is::<SortExec>()
+            || any.is::<WindowAggExec>()
+            || any.is::<BoundedWindowAggExec>(),
+    );
+
+    own + plan
+        .children()
+        .iter()
+        .map(memory_intensive_operators)
+        .sum::<usize>()
 }
 
 impl Default for DistributedPlanner {
@@ -63,15 +335,39 @@ impl DistributedPlanner {
         execution_plan: Arc<dyn ExecutionPlan>,
     ) -> Result<Vec<Arc<ShuffleWriterExec>>> {
         info!("planning query stages for job {}", job_id);
-        let (new_plan, mut stages) =
-            self.plan_query_stages_internal(job_id, execution_plan)?;
-        stages.push(create_shuffle_writer(
+        self.shared_stages.clear();
+        let (new_plan, mut stages) = if self.single_task {
+            (single_partition(execution_plan), vec![])
+        } else {
+            self.plan_query_stages_internal(job_id, execution_plan)?
+        };
+        let shuffle_codec = self.shuffle_codec(&new_plan);
+        let final_stage = create_shuffle_writer(
             job_id,
             self.next_stage_id(),
             new_plan,
             None,
-        )?);
-        Ok(stages)
+            shuffle_codec,
+            self.shuffle_checksums,
+            self.checkpoint_dir.clone(),
+        )?;
+        // Only the results of the query are exported, not the outputs of the other stages
+        stages.push(match &self.result_export {
+            Some(result_export) => Arc::new(
+                final_stage
+                    .as_ref()
+                    .clone()
+                    .with_result_export(Some(result_export.clone())),
+            ),
+            None => final_stage,
+        });
+        stages
+            .into_iter()
+            .map(|stage| {
+                let stage = self.remove_concurrency_limits(stage)?;
+                self.remove_gpu_requirements(stage)
+            })
+            .collect()
     }
 
     /// Returns a potentially modified version of the input execution_plan along with the resulting query stages.
@@ -96,18 +392,28 @@ impl DistributedPlanner {
             stages.append(&mut child_stages);
         }
 
-        if let Some(_coalesce) = execution_plan
+        if is_noop_exchange(&execution_plan, &children[0]) {
+            // the input of the exchange stays in the current stage instead of being
+            // written by a stage of its own
+            debug!("Removing no-op exchange {:?}", execution_plan);
+            if execution_plan
+                .as_any()
+                .downcast_ref::<RepartitionExec>()
+                .is_some()
+            {
+                Ok((children[0].clone(), stages))
+            } else {
+                Ok((
+                    with_new_children_if_necessary(execution_plan, children)?.into(),
+                    stages,
+                ))
+            }
+        } else if let Some(_coalesce) = execution_plan
             .as_any()
             .downcast_ref::<CoalescePartitionsExec>()
         {
-            let shuffle_writer = create_shuffle_writer(
-                job_id,
-                self.next_stage_id(),
-                children[0].clone(),
-                None,
-            )?;
-            let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
-            stages.push(shuffle_writer);
+            let unresolved_shuffle =
+                self.shuffle(job_id, children[0].clone(), None, &mut stages)?;
             Ok((
                 with_new_children_if_necessary(execution_plan, vec![unresolved_shuffle])?
                     .into(),
@@ -117,14 +423,8 @@ impl DistributedPlanner {
             .as_any()
             .downcast_ref::<SortPreservingMergeExec>(
         ) {
-            let shuffle_writer = create_shuffle_writer(
-                job_id,
-                self.next_stage_id(),
-                children[0].clone(),
-                None,
-            )?;
-            let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
-            stages.push(shuffle_writer);
+            let unresolved_shuffle =
+                self.shuffle(job_id, children[0].clone(), None, &mut stages)?;
             Ok((
                 with_new_children_if_necessary(execution_plan, vec![unresolved_shuffle])?
                     .into(),
@@ -135,14 +435,12 @@ impl DistributedPlanner {
         {
             match repart.output_partitioning() {
                 Partitioning::Hash(_, _) => {
-                    let shuffle_writer = create_shuffle_writer(
+                    let unresolved_shuffle = self.shuffle(
                         job_id,
-                        self.next_stage_id(),
                         children[0].clone(),
                         Some(repart.partitioning().to_owned()),
+                        &mut stages,
                     )?;
-                    let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
-                    stages.push(shuffle_writer);
                     Ok((unresolved_shuffle, stages))
                 }
                 _ => {
@@ -164,6 +462,77 @@ impl DistributedPlanner {
         }
     }
 
+    /// Shuffle reading the output of a new stage writing `input`, repartitioned by hash if
+    /// `partitioning` is set. A query computing the same input with the same partitioning
+    /// more than once, e.g. a common table expression referenced twice, reads the output of
+    /// the stage computing it the first time instead.
+    fn shuffle(
+        &mut self,
+        job_id: &str,
+        input: Arc<dyn ExecutionPlan>,
+        partitioning: Option<Partitioning>,
+        stages: &mut Vec<Arc<ShuffleWriterExec>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let key = shared_stage_key(&input, partitioning.as_ref());
+        if let Some(stage) = key.as_ref().and_then(|key| self.shared_stages.get(key)) {
+            debug!(
+                "Reusing the output of stage {} of job {job_id}",
+                stage.stage_id()
+            );
+            return Ok(create_unresolved_shuffle(stage));
+        }
+
+        let shuffle_writer = match partitioning {
+            Some(partitioning) => {
+                let shuffle_codec = ShuffleCodec::for_schema(
+                    input.schema().as_ref(),
+                    self.row_codec_max_columns,
+                    self.shuffle_dictionary_sharing,
+                );
+                Arc::new(
+                    ShuffleWriterExec::try_new(
+                        job_id.to_owned(),
+                        self.next_stage_id(),
+                        input,
+                        "".to_owned(), // executor will decide on the work_dir path
+                        Some(partitioning),
+                    )?
+                    .with_shuffle_codec(shuffle_codec)
+                    .with_write_buffer_size(self.shuffle_write_buffer_size)
+                    .with_checksum(self.shuffle_checksums)
+                    .with_checkpoint_dir(self.checkpoint_dir.clone()),
+                )
+            }
+            None => {
+                let shuffle_codec = self.shuffle_codec(&input);
+                create_shuffle_writer(
+                    job_id,
+                    self.next_stage_id(),
+                    input,
+                    None,
+                    shuffle_codec,
+                    self.shuffle_checksums,
+                    self.checkpoint_dir.clone(),
+                )?
+            }
+        };
+        if let Some(key) = key {
+            self.shared_stages.insert(key, shuffle_writer.clone());
+        }
+        let unresolved_shuffle = create_unresolved_shuffle(&shuffle_writer);
+        stages.push(shuffle_writer);
+        Ok(unresolved_shuffle)
+    }
+
+    /// Codec of the shuffle files of a stage whose output is not repartitioned
+    fn shuffle_codec(&self, plan: &Arc<dyn ExecutionPlan>) -> ShuffleCodec {
+        ShuffleCodec::for_schema(
+            plan.schema().as_ref(),
+            0,
+            self.shuffle_dictionary_sharing,
+        )
+    }
+
     /// Generate a new stage ID
     fn next_stage_id(&mut self) -> usize {
         self.next_stage_id += 1;
@@ -171,6 +540,99 @@ impl DistributedPlanner {
     }
 }
 
+/// Whether an exchange would only pass the partitions of its input on to a new stage:
+/// merging partitions which already are a single one, or hash partitioning an input which
+/// is already partitioned by the same expressions into as many partitions.
+fn is_noop_exchange(
+    exchange: &Arc<dyn ExecutionPlan>,
+    input: &Arc<dyn ExecutionPlan>,
+) -> bool {
+    let any = exchange.as_any();
+    if any.downcast_ref::<CoalescePartitionsExec>().is_some()
+        || any.downcast_ref::<SortPreservingMergeExec>().is_some()
+    {
+        input.output_partitioning().partition_count() == 1
+    } else if let Some(repart) = any.downcast_ref::<RepartitionExec>() {
+        // the partitioning of a planned input reading a shuffle is unknown, so compare
+        // with the partitioning of the input before planning
+        matches!(repart.partitioning(), Partitioning::Hash(_, _))
+            && repart.input().output_partitioning() == *repart.partitioning()
+    } else {
+        false
+    }
+}
+
+/// Key identifying the output of a stage, the same for the identical stages of different
+/// jobs. None if the stage reads other stages or data which is not scanned from files.
+pub(crate) fn shared_scan_key(stage: &dyn ExecutionPlan) -> Option<String> {
+    let shuffle_writer = stage.as_any().downcast_ref::<ShuffleWriterExec>()?;
+    let input = shuffle_writer.children()[0].clone();
+    if find_unresolved_shuffles(&input).ok()?.is_empty() {
+        shared_stage_key(&input, shuffle_writer.shuffle_output_partitioning())
+    } else {
+        None
+    }
+}
+
+/// Key identifying the output of a planned plan partitioned by `partitioning`, made of the
+/// description of each of its operators. None if the plan has leaves which could output other
+/// data than the leaves described the same, such as in-memory tables.
+fn shared_stage_key(
+    plan: &Arc<dyn ExecutionPlan>,
+    partitioning: Option<&Partitioning>,
+) -> Option<String> {
+    let mut key = format!("{partitioning:?}");
+    describe_operators(plan, 0, &mut key).then_some(key)
+}
+
+fn describe_operators(
+    plan: &Arc<dyn ExecutionPlan>,
+    depth: usize,
+    out: &mut String,
+) -> bool {
+    struct Operator<'a>(&'a dyn ExecutionPlan);
+
+    impl fmt::Display for Operator<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.fmt_as(DisplayFormatType::Default, f)
+        }
+    }
+
+    let any = plan.as_any();
+    if let Some(unresolved_shuffle) = any.downcast_ref::<UnresolvedShuffleExec>() {
+        // stages reading different stages are described alike otherwise
+        let _ = write!(
+            out,
+            "\n{depth}: UnresolvedShuffleExec: stage_id={}",
+            unresolved_shuffle.stage_id
+        );
+        return true;
+    }
+
+    let children = plan.children();
+    if children.is_empty()
+        && !(any.is::<CsvExec>()
+            || any.is::<ParquetExec>()
+            || any.is::<NdJsonExec>()
+            || any.is::<EmptyExec>())
+    {
+        return false;
+    }
+    let _ = write!(out, "\n{depth}: {}", Operator(plan.as_ref()));
+    children
+        .iter()
+        .all(|child| describe_operators(child, depth + 1, out))
+}
+
+/// The plan with its partitions merged into a single one, if it has several
+fn single_partition(plan: Arc<dyn ExecutionPlan>) -> Arc<dyn ExecutionPlan> {
+    if plan.output_partitioning().partition_count() > 1 {
+        Arc::new(CoalescePartitionsExec::new(plan))
+    } else {
+        plan
+    }
+}
+
 fn create_unresolved_shuffle(
     shuffle_writer: &ShuffleWriterExec,
 ) -> Arc<UnresolvedShuffleExec> {
@@ -205,6 +667,39 @@ pub fn find_unresolved_shuffles(
     }
 }
 
+/// Returns the IDs of the stages read by the unresolved shuffles of a stage plan whose
+/// partition `p` is only read by the task of partition `p` of the stage. If such a stage does
+/// not repartition its output, each task of the stage only depends on the task of the same
+/// partition of the input stage and may run as soon as that one completes.
+pub fn pipelined_shuffles(plan: &Arc<dyn ExecutionPlan>) -> HashSet<usize> {
+    if let Some(unresolved_shuffle) =
+        plan.as_any().downcast_ref::<UnresolvedShuffleExec>()
+    {
+        return HashSet::from([unresolved_shuffle.stage_id]);
+    }
+    // Operators reading all the partitions of (some of) their inputs in each partition
+    if plan.as_any().is::<RepartitionExec>()
+        || plan.as_any().is::<CoalescePartitionsExec>()
+        || plan.as_any().is::<SortPreservingMergeExec>()
+    {
+        return HashSet::new();
+    }
+
+    let partitions = plan.output_partitioning().partition_count();
+    let distributions = plan.required_input_distribution();
+    plan.children()
+        .iter()
+        .enumerate()
+        .filter(|(i, child)| {
+            // The left side of a cross join is collected in every partition
+            !(*i == 0 && plan.as_any().is::<CrossJoinExec>())
+                && !matches!(distributions.get(*i), Some(Distribution::SinglePartition))
+                && child.output_partitioning().partition_count() == partitions
+        })
+        .flat_map(|(_, child)| pipelined_shuffles(child))
+        .collect()
+}
+
 pub fn remove_unresolved_shuffles(
     stage: Arc<dyn ExecutionPlan>,
     partition_locations: &HashMap<usize, HashMap<usize, Vec<PartitionLocation>>>,
@@ -268,7 +763,19 @@ pub fn rollback_resolved_shuffles(
             let partition_locations = &shuffle_reader.partition;
             let output_partition_count = partition_locations.len();
             let input_partition_count = partition_locations[0].len();
-            let stage_id = partition_locations[0][0].partition_id.stage_id;
+            // The first partitions of stages resolved before their pipelined inputs
+            // complete may not have locations yet
+            let stage_id = partition_locations
+                .iter()
+                .flatten()
+                .next()
+                .map(|location| location.partition_id.stage_id)
+                .ok_or_else(|| {
+                    BallistaError::Internal(
+                        "Cannot roll back a shuffle reader without partition locations"
+                            .to_owned(),
+                    )
+                })?;
 
             let unresolved_shuffle = Arc::new(UnresolvedShuffleExec::new(
                 stage_id,
@@ -289,34 +796,53 @@ fn create_shuffle_writer(
     stage_id: usize,
     plan: Arc<dyn ExecutionPlan>,
     partitioning: Option<Partitioning>,
+    shuffle_codec: ShuffleCodec,
+    checksum: bool,
+    checkpoint_dir: Option<String>,
 ) -> Result<Arc<ShuffleWriterExec>> {
-    Ok(Arc::new(ShuffleWriterExec::try_new(
-        job_id.to_owned(),
-        stage_id,
-        plan,
-        "".to_owned(), // executor will decide on the work_dir path
-        partitioning,
-    )?))
+    Ok(Arc::new(
+        ShuffleWriterExec::try_new(
+            job_id.to_owned(),
+            stage_id,
+            plan,
+            "".to_owned(), // executor will decide on the work_dir path
+            partitioning,
+        )?
+        .with_shuffle_codec(shuffle_codec)
+        .with_checksum(checksum)
+        .with_checkpoint_dir(checkpoint_dir),
+    ))
 }
 
 #[cfg(test)]
 mod test {
-    use crate::planner::DistributedPlanner;
+    use crate::display::display_query_stages;
+    use crate::planner::{find_unresolved_shuffles, DistributedPlanner};
     use crate::test_utils::datafusion_test_context;
     use ballista_core::error::BallistaError;
-    use ballista_core::execution_plans::UnresolvedShuffleExec;
+    use ballista_core::execution_plans::{
+        ConcurrencyLimitExec, GpuRequirementExec, ResultExport, ShuffleCodec,
+        ShuffleWriterExec, UnresolvedShuffleExec,
+    };
+    use ballista_core::serde::scheduler::TaskResources;
     use ballista_core::serde::BallistaCodec;
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
     use datafusion::physical_plan::aggregates::{AggregateExec, AggregateMode};
     use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+    use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+    use datafusion::physical_plan::expressions::Column;
     use datafusion::physical_plan::joins::HashJoinExec;
+    use datafusion::physical_plan::memory::MemoryExec;
     use datafusion::physical_plan::projection::ProjectionExec;
+    use datafusion::physical_plan::repartition::RepartitionExec;
     use datafusion::physical_plan::sorts::sort::SortExec;
     use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
-    use datafusion::physical_plan::{displayable, ExecutionPlan};
+    use datafusion::physical_plan::{displayable, ExecutionPlan, Partitioning};
     use datafusion::prelude::SessionContext;
     use datafusion_proto::physical_plan::AsExecutionPlan;
     use datafusion_proto::protobuf::LogicalPlanNode;
     use datafusion_proto::protobuf::PhysicalPlanNode;
+    use std::collections::{HashMap, HashSet};
     use std::ops::Deref;
     use std::sync::Arc;
     use uuid::Uuid;
@@ -408,6 +934,39 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn display_distributed_aggregate_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag
+            order by l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+        let display = display_query_stages(&stages)?;
+        println!("{display}");
+
+        assert!(display
+            .contains("Stage 1[tasks=2, inputs=[], exchange=hash([l_returnflag@0], 2)]"));
+        assert!(display.contains("Stage 2[tasks=2, inputs=[1], exchange=merge]"));
+        assert!(display.contains("Stage 3[tasks=1, inputs=[2], exchange=result]"));
+        assert!(display.contains("SortPreservingMergeExec"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn distributed_join_plan() -> Result<(), BallistaError> {
         let ctx = datafusion_test_context("testdata").await?;
@@ -624,6 +1183,411 @@ order by
         Ok(())
     }
 
+    #[tokio::test]
+    async fn distributed_row_codec_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let stages =
+            planner.plan_query_stages(&Uuid::new_v4().to_string(), plan.clone())?;
+        assert!(stages
+            .iter()
+            .all(|stage| stage.shuffle_codec() == ShuffleCodec::ArrowIpc));
+
+        let mut planner = DistributedPlanner::new()
+            .with_row_codec_max_columns(2)
+            .with_shuffle_write_buffer_size(1024);
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert_eq!(2, stages.len());
+
+        // only the hash shuffle between the partial and final aggregate uses the row codec
+        assert_eq!(stages[0].shuffle_codec(), ShuffleCodec::Row);
+        assert_eq!(stages[1].shuffle_codec(), ShuffleCodec::ArrowIpc);
+
+        let stage0: Arc<dyn ExecutionPlan> = stages[0].clone();
+        let stage0_serde = roundtrip_operator(&ctx, stage0)?;
+        let stage0_serde = downcast_exec!(stage0_serde, ShuffleWriterExec);
+        assert_eq!(stage0_serde.shuffle_codec(), ShuffleCodec::Row);
+        assert_eq!(stage0_serde.write_buffer_size(), 1024);
+
+        Ok(())
+    }
+
+    #[test]
+    fn distributed_dictionary_sharing_plan() -> Result<(), BallistaError> {
+        let schema = Arc::new(Schema::new(vec![Field::new(
+            "d",
+            DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+            true,
+        )]));
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(CoalescePartitionsExec::new(
+            Arc::new(MemoryExec::try_new(&[vec![], vec![]], schema, None)?),
+        ));
+
+        let mut planner = DistributedPlanner::new();
+        let stages =
+            planner.plan_query_stages(&Uuid::new_v4().to_string(), plan.clone())?;
+        assert_eq!(2, stages.len());
+        assert!(stages
+            .iter()
+            .all(|stage| stage.shuffle_codec() == ShuffleCodec::ArrowIpcStream));
+
+        let mut planner =
+            DistributedPlanner::new().with_shuffle_dictionary_sharing(false);
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert!(stages
+            .iter()
+            .all(|stage| stage.shuffle_codec() == ShuffleCodec::ArrowIpc));
+
+        Ok(())
+    }
+
+    #[test]
+    fn distributed_noop_exchange_plan() -> Result<(), BallistaError> {
+        let schema = Arc::new(Schema::new(vec![Field::new("a", DataType::Int32, true)]));
+        let hash = || Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 2);
+        let input: Arc<dyn ExecutionPlan> = Arc::new(RepartitionExec::try_new(
+            Arc::new(MemoryExec::try_new(
+                &[vec![], vec![]],
+                schema.clone(),
+                None,
+            )?),
+            hash(),
+        )?);
+
+        // the second hash repartition keeps the partitioning of its input
+        let plan: Arc<dyn ExecutionPlan> =
+            Arc::new(RepartitionExec::try_new(input.clone(), hash())?);
+        let mut planner = DistributedPlanner::new();
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert_eq!(2, stages.len());
+        assert!(stages[1]
+            .children()
+            .iter()
+            .all(|child| child.as_any().is::<UnresolvedShuffleExec>()));
+
+        // a different partition count needs a shuffle
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(RepartitionExec::try_new(
+            input,
+            Partitioning::Hash(vec![Arc::new(Column::new("a", 0))], 3),
+        )?);
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert_eq!(3, stages.len());
+
+        // merging a single partition needs no shuffle
+        let plan: Arc<dyn ExecutionPlan> = Arc::new(CoalescePartitionsExec::new(
+            Arc::new(MemoryExec::try_new(&[vec![]], schema, None)?),
+        ));
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert_eq!(1, stages.len());
+        assert!(stages[0]
+            .children()
+            .iter()
+            .all(|child| child.as_any().is::<CoalescePartitionsExec>()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_single_task_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        // simplified form of TPC-H query 1
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag
+            order by l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new().with_single_task(true);
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        for stage in &stages {
+            println!("{}", displayable(stage.as_ref()).indent());
+        }
+        assert_eq!(1, stages.len());
+        assert_eq!(1, stages[0].output_partitioning().partition_count());
+        assert!(stages[0].shuffle_output_partitioning().is_none());
+
+        // the exchanges of the query run within the task
+        let displayed = displayable(stages[0].as_ref()).indent().to_string();
+        assert!(displayed.contains("RepartitionExec"));
+        assert!(!displayed.contains("UnresolvedShuffleExec"));
+
+        let stage: Arc<dyn ExecutionPlan> = stages[0].clone();
+        roundtrip_operator(&ctx, stage)?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_result_export_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let result_export = ResultExport::new(
+            "s3://bucket/exports",
+            HashMap::from([("aws_region".to_owned(), "us-east-1".to_owned())]),
+        )
+        .with_profile("analysts");
+        let mut planner =
+            DistributedPlanner::new().with_result_export(Some(result_export.clone()));
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert!(stages.len() > 1);
+
+        // Only the final stage exports its results
+        let (final_stage, other_stages) = stages.split_last().unwrap();
+        assert_eq!(final_stage.result_export(), Some(&result_export));
+        assert!(other_stages
+            .iter()
+            .all(|stage| stage.result_export().is_none()));
+
+        let stage: Arc<dyn ExecutionPlan> = final_stage.clone();
+        let stage = roundtrip_operator(&ctx, stage)?;
+        assert_eq!(
+            downcast_exec!(stage, ShuffleWriterExec).result_export(),
+            Some(&result_export)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_shared_stage_plan() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "with totals as (
+                select l_returnflag, sum(l_extendedprice) as total
+                from lineitem
+                group by l_returnflag
+            )
+            select a.l_returnflag, a.total, b.total
+            from totals a join totals b on a.l_returnflag = b.l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner = DistributedPlanner::new();
+        let job_uuid = Uuid::new_v4();
+        let stages = planner.plan_query_stages(&job_uuid.to_string(), plan)?;
+        for stage in &stages {
+            println!("{}", displayable(stage.as_ref()).indent());
+        }
+
+        // both sides of the join read the partial aggregate of the same stage
+        let shuffles = stages
+            .iter()
+            .map(|stage| {
+                let stage: Arc<dyn ExecutionPlan> = stage.clone();
+                find_unresolved_shuffles(&stage)
+            })
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let read_stages = shuffles
+            .iter()
+            .map(|shuffle| shuffle.stage_id)
+            .collect::<HashSet<_>>();
+        assert!(read_stages.len() < shuffles.len());
+        assert_eq!(read_stages.len() + 1, stages.len());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_concurrency_limits() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        // Annotate the scan of the table
+        fn limit_scans(
+            plan: Arc<dyn ExecutionPlan>,
+        ) -> Result<Arc<dyn ExecutionPlan>, BallistaError> {
+            if plan.children().is_empty() {
+                return Ok(Arc::new(ConcurrencyLimitExec::new(plan, 2)));
+            }
+            let children = plan
+                .children()
+                .into_iter()
+                .map(limit_scans)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(plan.with_new_children(children)?)
+        }
+        let plan = limit_scans(plan)?;
+
+        let mut planner = DistributedPlanner::new();
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert_eq!(2, stages.len());
+
+        // Only the stage scanning the table is limited, and executors do not see the annotation
+        assert_eq!(planner.max_concurrent_tasks(stages[0].stage_id()), 2);
+        assert_eq!(planner.max_concurrent_tasks(stages[1].stage_id()), 0);
+        for stage in stages {
+            let stage: Arc<dyn ExecutionPlan> = stage;
+            assert!(!displayable(stage.as_ref())
+                .indent()
+                .to_string()
+                .contains("ConcurrencyLimitExec"));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_gpu_requirements() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        // Annotate the scan of the table, the highest of nested requirements wins
+        fn gpu_scans(
+            plan: Arc<dyn ExecutionPlan>,
+        ) -> Result<Arc<dyn ExecutionPlan>, BallistaError> {
+            if plan.children().is_empty() {
+                let plan = Arc::new(GpuRequirementExec::new(plan, 2));
+                return Ok(Arc::new(GpuRequirementExec::new(plan, 1)));
+            }
+            let children = plan
+                .children()
+                .into_iter()
+                .map(gpu_scans)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(plan.with_new_children(children)?)
+        }
+        let plan = gpu_scans(plan)?;
+
+        let mut planner = DistributedPlanner::new();
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert_eq!(2, stages.len());
+
+        // Only the stage scanning the table requires GPUs, and executors do not see the
+        // annotation
+        assert_eq!(planner.stage_gpus(stages[0].stage_id()), 2);
+        assert_eq!(planner.stage_gpus(stages[1].stage_id()), 0);
+        for stage in stages {
+            let stage: Arc<dyn ExecutionPlan> = stage;
+            assert!(!displayable(stage.as_ref())
+                .indent()
+                .to_string()
+                .contains("GpuRequirementExec"));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn distributed_task_resources() -> Result<(), BallistaError> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let session_state = ctx.state();
+
+        let df = ctx
+            .sql(
+                "select l_returnflag, sum(l_extendedprice * 1) as sum_disc_price
+            from lineitem
+            group by l_returnflag
+            order by l_returnflag",
+            )
+            .await?;
+
+        let plan = df.into_optimized_plan()?;
+        let plan = session_state.optimize(&plan)?;
+        let plan = session_state.create_physical_plan(&plan).await?;
+
+        let mut planner =
+            DistributedPlanner::new().with_task_resources(TaskResources::new(100, 1));
+        let stages = planner.plan_query_stages(&Uuid::new_v4().to_string(), plan)?;
+        assert_eq!(3, stages.len());
+
+        let stages = stages
+            .into_iter()
+            .map(|stage| stage as Arc<dyn ExecutionPlan>)
+            .collect::<Vec<_>>();
+        let resources = stages
+            .iter()
+            .map(|stage| planner.estimate_task_resources(stage))
+            .collect::<Vec<_>>();
+
+        // partial aggregate, final aggregate with sort, sort preserving merge
+        assert_eq!(
+            resources,
+            vec![
+                TaskResources::new(200, 1),
+                TaskResources::new(300, 1),
+                TaskResources::new(100, 1),
+            ]
+        );
+
+        // without base resources no task requires any resources
+        let planner = DistributedPlanner::new();
+        assert!(stages.iter().all(|stage| {
+            planner.estimate_task_resources(stage) == TaskResources::default()
+        }));
+
+        Ok(())
+    }
+
     fn roundtrip_operator(
         ctx: &SessionContext,
         plan: Arc<dyn ExecutionPlan>,
@@ -0,0 +1,379 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Plugins extending the scheduler, either registered with the
+//! [`SchedulerConfig`](crate::config::SchedulerConfig) of a custom scheduler binary or, with the
+//! `dynamic-plugins` feature, loaded from the shared libraries in the `--plugin-dir` of the
+//! scheduler. A shared library declares its plugin with
+//! [`declare_scheduler_plugin!`](crate::declare_scheduler_plugin).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use datafusion::catalog::catalog::CatalogProvider;
+use datafusion::datasource::provider::TableProviderFactory;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::optimizer::OptimizerRule;
+use tonic::metadata::MetadataMap;
+
+pub use ballista_core::declare_plugin;
+use ballista_core::error::{BallistaError, Result};
+pub use ballista_core::plugin::{PluginEnum, CORE_VERSION, RUSTC_VERSION};
+
+/// Plugin contributing catalogs, table factories, authentication, authorization and query rewrite rules to the
+/// scheduler. All methods but [`name`](SchedulerPlugin::name) contribute nothing by default.
+pub trait SchedulerPlugin: Send + Sync {
+    /// Name of the plugin, used in logs
+    fn name(&self) -> &str;
+
+    /// Catalogs registered with every session by name
+    fn catalogs(&self) -> HashMap<String, Arc<dyn CatalogProvider>> {
+        HashMap::new()
+    }
+
+    /// Table provider factories by file type, creating the tables `STORED AS` the file type
+    /// in every session
+    fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+        HashMap::new()
+    }
+
+    /// Authenticate the client submitting a query from the metadata of its request. Returns
+    /// the identity of the client if the plugin recognizes its credentials, None to leave them
+    /// to the other plugins, or an error to reject the query.
+    fn authenticate(&self, _metadata: &MetadataMap) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Check whether a client, such as the service account of a query gateway, may submit
+    /// queries on behalf of the user `run_as`. Returns true to allow it, false to leave the
+    /// decision to the other plugins, or an error to reject the query.
+    fn impersonate(&self, _user: Option<&str>, _run_as: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Check that a client may run the plan of a query, `user` being the identity of the
+    /// client if a plugin authenticated it, or the user the client runs the query on behalf
+    /// of. An error rejects the query.
+    fn authorize(&self, _user: Option<&str>, _plan: &LogicalPlan) -> Result<()> {
+        Ok(())
+    }
+
+    /// Check that a client may perform an administrative `action` on the scheduler, such as
+    /// draining it. An error rejects the request.
+    fn authorize_admin(&self, _user: Option<&str>, _action: AdminAction) -> Result<()> {
+        Ok(())
+    }
+
+    /// Rules rewriting the logical plan of every job, run after the optimizer rules of the
+    /// job's session
+    fn optimizer_rules(&self) -> Vec<Arc<dyn OptimizerRule + Send + Sync>> {
+        vec![]
+    }
+}
+
+/// Administrative actions on the scheduler which plugins authorize
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AdminAction {
+    /// Drain the scheduler before shutting it down
+    Drain,
+    /// Decommission an executor
+    DecommissionExecutor,
+}
+
+impl fmt::Display for AdminAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminAction::Drain => write!(f, "drain the scheduler"),
+            AdminAction::DecommissionExecutor => write!(f, "decommission executors"),
+        }
+    }
+}
+
+/// Plugins registered with the scheduler, in the order they were registered
+#[derive(Clone, Default)]
+pub struct SchedulerPlugins {
+    plugins: Vec<Arc<dyn SchedulerPlugin>>,
+}
+
+impl fmt::Debug for SchedulerPlugins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.plugins.iter().map(|plugin| plugin.name()))
+            .finish()
+    }
+}
+
+impl SchedulerPlugins {
+    pub fn push(&mut self, plugin: Arc<dyn SchedulerPlugin>) {
+        self.plugins.push(plugin);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    /// Catalogs of all plugins, the ones of later plugins replacing earlier ones of the same
+    /// name
+    pub fn catalogs(&self) -> HashMap<String, Arc<dyn CatalogProvider>> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.catalogs())
+            .collect()
+    }
+
+    /// Table factories of all plugins, the ones of later plugins replacing earlier ones of
+    /// the same file type
+    pub fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+        self.plugins
+            .iter()
+            .flat_map(|plugin| plugin.table_factories())
+            .collect()
+    }
+
+    /// Identity of the client of a request given by the first plugin recognizing its
+    /// credentials, None if no plugin does
+    pub fn authenticate(&self, metadata: &MetadataMap) -> Result<Option<String>> {
+        for plugin in &self.plugins {
+            if let Some(user) = plugin.authenticate(metadata)? {
+                return Ok(Some(user));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Check that a plugin lets the client submit queries on behalf of the user `run_as`
+    pub fn impersonate(&self, user: Option<&str>, run_as: &str) -> Result<()> {
+        for plugin in &self.plugins {
+            if plugin.impersonate(user, run_as)? {
+                return Ok(());
+            }
+        }
+        Err(BallistaError::General(format!(
+            "{} may not run queries as {run_as}",
+            user.unwrap_or("Anonymous client")
+        )))
+    }
+
+    /// Check that all plugins let the client run the plan of a query
+    pub fn authorize(&self, user: Option<&str>, plan: &LogicalPlan) -> Result<()> {
+        self.plugins
+            .iter()
+            .try_for_each(|plugin| plugin.authorize(user, plan))
+    }
+
+    /// Check that all plugins let the client perform an administrative action
+    pub fn authorize_admin(&self, user: Option<&str>, action: AdminAction) -> Result<()> {
+        self.plugins
+            .iter()
+            .try_for_each(|plugin| plugin.authorize_admin(user, action))
+    }
+
+    /// Check that the client may access a job owned by `owner`, e.g. read its results. Jobs
+    /// without an owner are accessible to any client, the others to their owner and to the
+    /// clients allowed to run queries as the owner.
+    pub fn authorize_job_access(
+        &self,
+        user: Option<&str>,
+        owner: Option<&str>,
+    ) -> Result<()> {
+        match owner {
+            Some(owner) if user != Some(owner) => {
+                self.impersonate(user, owner).map_err(|_| {
+                    BallistaError::General(format!(
+                        "{} may not access the jobs of {owner}",
+                        user.unwrap_or("Anonymous client")
+                    ))
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Declare the scheduler plugin of a shared library, created by `$constructor`. Only one
+/// plugin can be declared per library.
+#[macro_export]
+macro_rules! declare_scheduler_plugin {
+    ($plugin_type:ty, $constructor:path) => {
+        #[no_mangle]
+        pub fn registrar_scheduler_plugin() -> Box<dyn $crate::plugin::SchedulerPlugin> {
+            // make sure the constructor is the correct type
+            let constructor: fn() -> $plugin_type = $constructor;
+            Box::new(constructor())
+        }
+
+        $crate::plugin::declare_plugin!($crate::plugin::PluginEnum::Scheduler);
+    };
+}
+
+/// Load the plugins of the shared libraries in `plugin_dir` with the plugin manager of
+/// ballista-core. Libraries which declare no scheduler plugin are skipped.
+#[cfg(feature = "dynamic-plugins")]
+pub fn load_plugins(plugin_dir: &str) -> Result<Vec<Arc<dyn SchedulerPlugin>>> {
+    use ballista_core::plugin::plugin_manager::load_global_plugins;
+    use ballista_core::plugin::LoadedPlugin;
+    use log::info;
+
+    /// A plugin along with the library holding its code
+    struct LibraryPlugin(LoadedPlugin<Box<dyn SchedulerPlugin>>);
+
+    impl SchedulerPlugin for LibraryPlugin {
+        fn name(&self) -> &str {
+            self.0.plugin.name()
+        }
+
+        fn catalogs(&self) -> HashMap<String, Arc<dyn CatalogProvider>> {
+            self.0.plugin.catalogs()
+        }
+
+        fn table_factories(&self) -> HashMap<String, Arc<dyn TableProviderFactory>> {
+            self.0.plugin.table_factories()
+        }
+
+        fn authenticate(&self, metadata: &MetadataMap) -> Result<Option<String>> {
+            self.0.plugin.authenticate(metadata)
+        }
+
+        fn impersonate(&self, user: Option<&str>, run_as: &str) -> Result<bool> {
+            self.0.plugin.impersonate(user, run_as)
+        }
+
+        fn authorize(&self, user: Option<&str>, plan: &LogicalPlan) -> Result<()> {
+            self.0.plugin.authorize(user, plan)
+        }
+
+        fn authorize_admin(&self, user: Option<&str>, action: AdminAction) -> Result<()> {
+            self.0.plugin.authorize_admin(user, action)
+        }
+
+        fn optimizer_rules(&self) -> Vec<Arc<dyn OptimizerRule + Send + Sync>> {
+            self.0.plugin.optimizer_rules()
+        }
+    }
+
+    let libraries = load_global_plugins(plugin_dir)?
+        .lock()
+        .unwrap()
+        .plugin_libraries(PluginEnum::Scheduler);
+    // Safety: the libraries declaring a scheduler plugin export its registrar, declared
+    // with `declare_scheduler_plugin!`
+    let plugins = unsafe { libraries.create_plugins(b"registrar_scheduler_plugin\0")? };
+    Ok(plugins
+        .into_iter()
+        .map(|plugin| {
+            info!("Loaded scheduler plugin {}", plugin.plugin.name());
+            Arc::new(LibraryPlugin(plugin)) as Arc<dyn SchedulerPlugin>
+        })
+        .collect())
+}
+
+/// Loading plugins from shared libraries requires the `dynamic-plugins` feature, without
+/// which the plugin dir is ignored
+#[cfg(not(feature = "dynamic-plugins"))]
+pub fn load_plugins(plugin_dir: &str) -> Result<Vec<Arc<dyn SchedulerPlugin>>> {
+    log::warn!(
+        "Ignoring the plugins in {plugin_dir}, the scheduler was built without the \
+         dynamic-plugins feature"
+    );
+    Ok(vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::catalog::catalog::MemoryCatalogProvider;
+    use datafusion::logical_expr::LogicalPlanBuilder;
+
+    /// Lets the clients with a token run queries, and the gateway run them as other users
+    struct TokenPlugin;
+
+    impl SchedulerPlugin for TokenPlugin {
+        fn name(&self) -> &str {
+            "token"
+        }
+
+        fn catalogs(&self) -> HashMap<String, Arc<dyn CatalogProvider>> {
+            let catalog: Arc<dyn CatalogProvider> =
+                Arc::new(MemoryCatalogProvider::new());
+            [("shared".to_owned(), catalog)].into_iter().collect()
+        }
+
+        fn authenticate(&self, metadata: &MetadataMap) -> Result<Option<String>> {
+            match metadata.get("authorization") {
+                Some(token) if token == "Bearer secret" => Ok(Some("alice".to_owned())),
+                Some(token) if token == "Bearer gateway" => {
+                    Ok(Some("gateway".to_owned()))
+                }
+                Some(_) => Err(BallistaError::General("Invalid token".to_owned())),
+                None => Ok(None),
+            }
+        }
+
+        fn impersonate(&self, user: Option<&str>, _run_as: &str) -> Result<bool> {
+            Ok(user == Some("gateway"))
+        }
+
+        fn authorize(&self, user: Option<&str>, _plan: &LogicalPlan) -> Result<()> {
+            match user {
+                Some(_) => Ok(()),
+                None => Err(BallistaError::General("Not authenticated".to_owned())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_scheduler_plugins() -> Result<()> {
+        let mut plugins = SchedulerPlugins::default();
+        let plan = LogicalPlanBuilder::empty(false).build()?;
+        assert_eq!(plugins.authenticate(&MetadataMap::new())?, None);
+        plugins.authorize(None, &plan)?;
+
+        plugins.push(Arc::new(TokenPlugin));
+        assert_eq!(format!("{plugins:?}"), "[\"token\"]");
+        assert!(plugins.catalogs().contains_key("shared"));
+
+        let mut metadata = MetadataMap::new();
+        assert_eq!(plugins.authenticate(&metadata)?, None);
+        assert!(plugins.authorize(None, &plan).is_err());
+
+        metadata.insert("authorization", "Bearer secret".parse().unwrap());
+        let user = plugins.authenticate(&metadata)?;
+        assert_eq!(user.as_deref(), Some("alice"));
+        plugins.authorize(user.as_deref(), &plan)?;
+
+        assert!(plugins.impersonate(user.as_deref(), "bob").is_err());
+
+        metadata.insert("authorization", "Bearer gateway".parse().unwrap());
+        let user = plugins.authenticate(&metadata)?;
+        plugins.impersonate(user.as_deref(), "bob")?;
+        assert!(plugins.impersonate(None, "bob").is_err());
+
+        metadata.insert("authorization", "Bearer guess".parse().unwrap());
+        assert!(plugins.authenticate(&metadata).is_err());
+
+        // Jobs are accessible to their owner and to the clients running queries as the owner
+        plugins.authorize_job_access(None, None)?;
+        plugins.authorize_job_access(Some("alice"), Some("alice"))?;
+        plugins.authorize_job_access(Some("gateway"), Some("alice"))?;
+        assert!(plugins
+            .authorize_job_access(Some("bob"), Some("alice"))
+            .is_err());
+        assert!(plugins.authorize_job_access(None, Some("alice")).is_err());
+        Ok(())
+    }
+}
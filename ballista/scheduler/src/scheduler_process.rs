@@ -18,29 +18,41 @@
 use anyhow::{Context, Result};
 #[cfg(feature = "flight-sql")]
 use arrow_flight::flight_service_server::FlightServiceServer;
-use futures::future::{self, Either, TryFutureExt};
+use futures::future::{self, Either, FutureExt, TryFutureExt};
 use hyper::{server::conn::AddrStream, service::make_service_fn, Server};
+#[cfg(unix)]
+use log::error;
 use log::info;
 use std::convert::Infallible;
 use std::net::SocketAddr;
+#[cfg(unix)]
+use std::time::Duration;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tonic::codec::CompressionEncoding;
+use tonic::server::NamedService;
 use tonic::transport::server::Connected;
 use tower::Service;
 
 use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
 
 use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpcServer;
-use ballista_core::serde::BallistaCodec;
-use ballista_core::utils::create_grpc_server;
+use ballista_core::utils::{create_grpc_reflection_server, create_grpc_server};
 use ballista_core::BALLISTA_VERSION;
 
-use crate::api::{get_routes, EitherBody, Error};
+#[cfg(feature = "rest-api")]
+use crate::api::get_routes;
+use crate::api::{rest_api_disabled, EitherBody, Error};
 use crate::cluster::BallistaCluster;
 use crate::config::SchedulerConfig;
-use crate::flight_sql::FlightSqlServiceImpl;
+use crate::flight_sql::{BallistaFlightService, FlightSqlServiceImpl};
 use crate::metrics::default_metrics_collector;
 use crate::scheduler_server::externalscaler::external_scaler_server::ExternalScalerServer;
 use crate::scheduler_server::SchedulerServer;
 
+type SchedulerGrpcService =
+    SchedulerGrpcServer<SchedulerServer<LogicalPlanNode, PhysicalPlanNode>>;
+
 pub async fn start_server(
     cluster: BallistaCluster,
     addr: SocketAddr,
@@ -62,13 +74,48 @@ pub async fn start_server(
         SchedulerServer::new(
             config.scheduler_name(),
             cluster,
-            BallistaCodec::default(),
+            config.codec.clone(),
             config,
             metrics_collector,
         );
 
     scheduler_server.init().await?;
 
+    if !scheduler_server.state.config.rest_api_enabled() {
+        info!("REST API disabled, serving the gRPC services only");
+    }
+
+    // Standard services for health checks, e.g. by Kubernetes gRPC probes, and for tools
+    // like grpcurl to list the services of the scheduler
+    let (mut health_reporter, health_server) = tonic_health::server::health_reporter();
+    health_reporter.set_serving::<SchedulerGrpcService>().await;
+    let reflection_server = create_grpc_reflection_server(&[SchedulerGrpcService::NAME])?;
+
+    // Drain the scheduler on SIGTERM so that it can be replaced without failing running jobs
+    #[cfg(unix)]
+    {
+        let scheduler_server = scheduler_server.clone();
+        let drain_timeout =
+            Duration::from_secs(scheduler_server.state.config.drain_timeout_seconds);
+        tokio::spawn(async move {
+            match signal(SignalKind::terminate()) {
+                Ok(mut sig_term) => {
+                    sig_term.recv().await;
+                    info!("Scheduler received terminate signal, draining");
+                    // Stop routing new queries to the scheduler
+                    health_reporter
+                        .set_not_serving::<SchedulerGrpcService>()
+                        .await;
+                    if let Err(e) = scheduler_server.drain_jobs(drain_timeout).await {
+                        error!("Error draining scheduler: {e:?}");
+                    }
+                }
+                Err(e) => error!("Failed to listen for the terminate signal: {e:?}"),
+            }
+        });
+    }
+
+    let drained_server = scheduler_server.clone();
     Server::bind(&addr)
         .serve(make_service_fn(move |request: &AddrStream| {
             let config = &scheduler_server.state.config;
@@ -76,22 +123,31 @@ pub async fn start_server(
                 SchedulerGrpcServer::new(scheduler_server.clone())
                     .max_decoding_message_size(
                         config.grpc_server_max_decoding_message_size as usize,
-                    );
+                    )
+                    .accept_compressed(CompressionEncoding::Gzip)
+                    .send_compressed(CompressionEncoding::Gzip);
 
             let keda_scaler = ExternalScalerServer::new(scheduler_server.clone());
 
             let tonic_builder = create_grpc_server()
                 .add_service(scheduler_grpc_server)
-                .add_service(keda_scaler);
+                .add_service(keda_scaler)
+                .add_service(health_server.clone())
+                .add_service(reflection_server.clone());
 
             #[cfg(feature = "flight-sql")]
             let tonic_builder = tonic_builder.add_service(FlightServiceServer::new(
-                FlightSqlServiceImpl::new(scheduler_server.clone()),
+                BallistaFlightService::new(FlightSqlServiceImpl::new(
+                    scheduler_server.clone(),
+                )),
             ));
 
             let mut tonic = tonic_builder.into_service();
 
-            let mut warp = warp::service(get_routes(scheduler_server.clone()));
+            #[cfg(feature = "rest-api")]
+            let mut rest_api = config
+                .rest_api
+                .then(|| warp::service(get_routes(scheduler_server.clone())));
 
             let connect_info = request.connect_info();
             future::ok::<_, Infallible>(tower::service_fn(
@@ -102,10 +158,21 @@ pub async fn start_server(
                     let req = http::Request::from_parts(parts, body);
 
                     if req.uri().path().starts_with("/api") {
+                        #[cfg(feature = "rest-api")]
+                        if let Some(rest_api) = rest_api.as_mut() {
+                            return Either::Left(
+                                rest_api
+                                    .call(req)
+                                    .map_ok(|res| res.map(EitherBody::Left))
+                                    .map_err(Error::from)
+                                    .boxed(),
+                            );
+                        }
                         return Either::Left(
-                            warp.call(req)
-                                .map_ok(|res| res.map(EitherBody::Left))
-                                .map_err(Error::from),
+                            future::ok::<_, Error>(
+                                rest_api_disabled().map(EitherBody::Left),
+                            )
+                            .boxed(),
                         );
                     }
 
@@ -118,6 +185,10 @@ pub async fn start_server(
                 },
             ))
         }))
+        .with_graceful_shutdown(async move {
+            drained_server.drained().await;
+            info!("Scheduler drained, shutting down");
+        })
         .await
         .context("Could not start grpc server")
 }
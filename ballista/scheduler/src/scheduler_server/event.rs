@@ -21,9 +21,10 @@ use std::fmt::{Debug, Formatter};
 use datafusion::logical_expr::LogicalPlan;
 
 use crate::state::execution_graph::RunningTaskInfo;
-use ballista_core::serde::protobuf::TaskStatus;
+use ballista_core::serde::protobuf::{PartitionLocation, TaskStatus};
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::prelude::SessionContext;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[derive(Clone)]
@@ -39,11 +40,15 @@ pub enum QueryStageSchedulerEvent {
         job_id: String,
         job_name: String,
         session_id: String,
+        // Settings of the job overriding the ones of its session
+        job_settings: HashMap<String, String>,
         queued_at: u64,
         submitted_at: u64,
         resubmit: bool,
         plan: Arc<dyn ExecutionPlan>,
     },
+    // For a running job reloaded from the backend after the scheduler restarted
+    JobRecovered(String),
     // For a job which failed during planning
     JobPlanningFailed {
         job_id: String,
@@ -56,14 +61,31 @@ pub enum QueryStageSchedulerEvent {
         queued_at: u64,
         completed_at: u64,
     },
+    // For a job whose results are served from the result cache without executing it
+    JobResultCached {
+        job_id: String,
+        cached_job_id: String,
+        partition_location: Vec<PartitionLocation>,
+        queued_at: u64,
+        completed_at: u64,
+    },
+    // For a job waiting for the results of the running job of an identical query
+    JobShared {
+        job_id: String,
+        leader_job_id: String,
+    },
     // For a job fails with its execution graph setting failed
     JobRunningFailed {
         job_id: String,
         fail_message: String,
+        /// Whether the job failed because of its query, rather than lost executors or data
+        query_error: bool,
         queued_at: u64,
         failed_at: u64,
     },
     JobUpdated(String),
+    // For a job whose client fetched results, which makes room for held back tasks
+    JobResultsConsumed(String),
     JobCancel(String),
     JobDataClean(String),
     TaskUpdating(String, Vec<TaskStatus>),
@@ -85,6 +107,9 @@ impl Debug for QueryStageSchedulerEvent {
             } => {
                 write!(f, "JobSubmitted : job_id={job_id}, resubmit={resubmit}.")
             }
+            QueryStageSchedulerEvent::JobRecovered(job_id) => {
+                write!(f, "JobRecovered : job_id={job_id}.")
+            }
             QueryStageSchedulerEvent::JobPlanningFailed {
                 job_id,
                 fail_message,
@@ -106,20 +131,45 @@ impl Debug for QueryStageSchedulerEvent {
                     "JobFinished : job_id={job_id}, queued_at={queued_at}, completed_at={completed_at}.",
                 )
             }
+            QueryStageSchedulerEvent::JobResultCached {
+                job_id,
+                cached_job_id,
+                queued_at,
+                completed_at,
+                ..
+            } => {
+                write!(
+                    f,
+                    "JobResultCached : job_id={job_id}, cached_job_id={cached_job_id}, queued_at={queued_at}, completed_at={completed_at}.",
+                )
+            }
+            QueryStageSchedulerEvent::JobShared {
+                job_id,
+                leader_job_id,
+            } => {
+                write!(
+                    f,
+                    "JobShared : job_id={job_id}, leader_job_id={leader_job_id}.",
+                )
+            }
             QueryStageSchedulerEvent::JobRunningFailed {
                 job_id,
                 fail_message,
+                query_error,
                 queued_at,
                 failed_at,
             } => {
                 write!(
                     f,
-                    "JobRunningFailed : job_id={job_id}, fail_message={fail_message}, queued_at={queued_at}, failed_at={failed_at}.",
+                    "JobRunningFailed : job_id={job_id}, fail_message={fail_message}, query_error={query_error}, queued_at={queued_at}, failed_at={failed_at}.",
                 )
             }
             QueryStageSchedulerEvent::JobUpdated(job_id) => {
                 write!(f, "JobUpdated : job_id={job_id}.")
             }
+            QueryStageSchedulerEvent::JobResultsConsumed(job_id) => {
+                write!(f, "JobResultsConsumed : job_id={job_id}.")
+            }
             QueryStageSchedulerEvent::JobCancel(job_id) => {
                 write!(f, "JobCancel : job_id={job_id}.")
             }
@@ -16,39 +16,61 @@
 // under the License.
 
 use ballista_core::config::{BallistaConfig, BALLISTA_JOB_NAME};
-use ballista_core::serde::protobuf::execute_query_params::{OptionalSessionId, Query};
+use ballista_core::serde::protobuf::estimate_query_params;
+use ballista_core::serde::protobuf::execute_query_params::{
+    OptionalJobId, OptionalSessionId, Query,
+};
 use std::convert::TryInto;
 
 use ballista_core::serde::protobuf::executor_registration::OptionalHost;
+use ballista_core::serde::protobuf::job_status;
 use ballista_core::serde::protobuf::scheduler_grpc_server::SchedulerGrpc;
 use ballista_core::serde::protobuf::{
-    CancelJobParams, CancelJobResult, CleanJobDataParams, CleanJobDataResult,
+    CancelJobGroupParams, CancelJobGroupResult, CancelJobParams, CancelJobResult,
+    CleanJobDataParams, CleanJobDataResult, CloseSessionParams, CloseSessionResult,
+    DecommissionExecutorParams, DecommissionExecutorResult, DrainParams, DrainResult,
+    DropTableParams, DropTableResult, EstimateQueryParams, EstimateQueryResult,
     ExecuteQueryParams, ExecuteQueryResult, ExecutorHeartbeat, ExecutorStoppedParams,
-    ExecutorStoppedResult, GetFileMetadataParams, GetFileMetadataResult,
-    GetJobStatusParams, GetJobStatusResult, HeartBeatParams, HeartBeatResult,
-    PollWorkParams, PollWorkResult, RegisterExecutorParams, RegisterExecutorResult,
-    UpdateTaskStatusParams, UpdateTaskStatusResult,
+    ExecutorStoppedResult, GetClusterConfigParams, GetClusterConfigResult,
+    GetFileMetadataParams, GetFileMetadataResult, GetJobGroupStatusParams,
+    GetJobGroupStatusResult, GetJobStatusParams, GetJobStatusResult,
+    GetResultsPageParams, GetResultsPageResult, GetTablesParams, GetTablesResult,
+    GetTaskAttemptsParams, GetTaskAttemptsResult, GetTaskLogsParams, HeartBeatParams,
+    HeartBeatResult, JobEvent, JobSummary, ListJobsParams, ListJobsResult,
+    PollWorkParams, PollWorkResult, RecoverExecutorDataParams, RecoverExecutorDataResult,
+    RegisterExecutorParams, RegisterExecutorResult, RegisterTableParams,
+    RegisterTableResult, SuccessfulJob, TaskLogLines, UndropTableParams,
+    UndropTableResult, UpdateTaskStatusParams, UpdateTaskStatusResult,
 };
-use ballista_core::serde::scheduler::ExecutorMetadata;
+use ballista_core::serde::scheduler::{ExecutorMetadata, TaskResources};
 
+use datafusion::common::{DFSchema, TableReference};
 use datafusion::datasource::file_format::parquet::ParquetFormat;
 use datafusion::datasource::file_format::FileFormat;
+use datafusion::logical_expr::{DdlStatement, DropTable, LogicalPlan};
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
-use futures::TryStreamExt;
+use futures::{Stream, StreamExt, TryStreamExt};
 use log::{debug, error, info, trace, warn};
 use object_store::{local::LocalFileSystem, path::Path, ObjectStore};
 
 use std::ops::Deref;
+use std::pin::Pin;
 use std::sync::Arc;
 
+use crate::plugin::AdminAction;
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use datafusion::prelude::SessionContext;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
+use tonic::metadata::MetadataMap;
 use tonic::{Request, Response, Status};
 
-use crate::scheduler_server::SchedulerServer;
+use crate::scheduler_server::result_pages::read_results_page;
+use crate::scheduler_server::{SchedulerServer, AUDIT_LOG_TARGET};
 use crate::state::executor_manager::ExecutorReservation;
+use crate::state::scheduler_peers::JOB_ID_METADATA_KEY;
+use crate::state::session_manager::{create_job_context, create_owned_job_context};
+use crate::state::task_manager::validate_job_id;
 
 #[tonic::async_trait]
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
@@ -69,10 +91,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             metadata: Some(metadata),
             num_free_slots,
             task_status,
+            running_tasks,
         } = request.into_inner()
         {
             trace!("Received poll_work request for {:?}", metadata);
-            let metadata = ExecutorMetadata {
+            let mut metadata = ExecutorMetadata {
                 id: metadata.id,
                 host: metadata
                     .optional_host
@@ -83,7 +106,20 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 port: metadata.port as u16,
                 grpc_port: metadata.grpc_port as u16,
                 specification: metadata.specification.unwrap().into(),
+                pool: metadata.pool,
             };
+            // The oversubscription changed at runtime is kept over the one the executor
+            // started with, which resizes its task slots accordingly
+            if let Ok(saved) = self
+                .state
+                .executor_manager
+                .get_executor_metadata(&metadata.id)
+                .await
+            {
+                metadata.specification.task_slot_oversubscription_percent =
+                    saved.specification.task_slot_oversubscription_percent;
+            }
+            let task_slots = metadata.specification.logical_task_slots();
 
             self.state
                 .executor_manager
@@ -105,30 +141,82 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                     error!("{}", msg);
                     Status::internal(msg)
                 })?;
-
-            // Find `num_free_slots` next tasks when available
-            let mut next_tasks = vec![];
-            let reservations = vec![
-                ExecutorReservation::new_free(metadata.id.clone());
-                num_free_slots as usize
-            ];
-            if let Ok((mut assignments, _, _)) = self
-                .state
+            self.state
                 .task_manager
-                .fill_reservations(&reservations)
-                .await
-            {
-                while let Some((_, task)) = assignments.pop() {
-                    match self.state.task_manager.prepare_task_definition(task) {
-                        Ok(task_definition) => next_tasks.push(task_definition),
-                        Err(e) => {
-                            error!("Error preparing task definition: {:?}", e);
-                        }
+                .reconcile_executor_tasks(&metadata.id, &running_tasks)
+                .await;
+
+            // Find `num_free_slots` next tasks when available. Task slots are managed by the
+            // executor itself in pull mode, so the reservations accept tasks of any resources
+            // but GPUs. The GPUs not used by the running tasks of the executor are handed out
+            // one task at a time, so that each GPU goes to a single task.
+            let mut num_free_slots =
+                if self.state.executor_manager.is_decommissioning(&metadata.id) {
+                    0
+                } else {
+                    num_free_slots
+                };
+            let mut free_gpus = metadata.specification.gpus.saturating_sub(
+                self.state.task_manager.gpus_in_use(&running_tasks).await,
+            );
+            let reservation = |gpus: u32| {
+                ExecutorReservation::new_free(metadata.id.clone())
+                    .with_resources(TaskResources::unbounded().with_gpus(gpus))
+                    .with_pool(metadata.pool.clone())
+            };
+            let mut assignments = vec![];
+            let mut exhausted = false;
+            while num_free_slots > 0 && free_gpus > 0 {
+                let task = match self
+                    .state
+                    .task_manager
+                    .fill_reservations(&[reservation(free_gpus)])
+                    .await
+                {
+                    Ok((mut filled, _, _)) => filled.pop().map(|(_, task)| task),
+                    Err(_) => None,
+                };
+                let task = match task {
+                    Some(task) => task,
+                    None => {
+                        exhausted = true;
+                        break;
+                    }
+                };
+                let gpus = self
+                    .state
+                    .task_manager
+                    .stage_gpus(&task.partition.job_id, task.partition.stage_id)
+                    .await;
+                free_gpus = free_gpus.saturating_sub(gpus);
+                num_free_slots -= 1;
+                assignments.push(task);
+            }
+            if num_free_slots > 0 && !exhausted {
+                let reservations = vec![reservation(0); num_free_slots as usize];
+                if let Ok((filled, _, _)) = self
+                    .state
+                    .task_manager
+                    .fill_reservations(&reservations)
+                    .await
+                {
+                    assignments.extend(filled.into_iter().map(|(_, task)| task));
+                }
+            }
+            let mut next_tasks = vec![];
+            for task in assignments {
+                match self.state.task_manager.prepare_task_definition(task) {
+                    Ok(task_definition) => next_tasks.push(task_definition),
+                    Err(e) => {
+                        error!("Error preparing task definition: {:?}", e);
                     }
                 }
             }
 
-            Ok(Response::new(PollWorkResult { tasks: next_tasks }))
+            Ok(Response::new(PollWorkResult {
+                tasks: next_tasks,
+                task_slots,
+            }))
         } else {
             warn!("Received invalid executor poll_work request");
             Err(Status::invalid_argument("Missing metadata in request"))
@@ -156,6 +244,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 port: metadata.port as u16,
                 grpc_port: metadata.grpc_port as u16,
                 specification: metadata.specification.unwrap().into(),
+                pool: metadata.pool,
             };
 
             self.do_register_executor(metadata).await.map_err(|e| {
@@ -181,52 +270,76 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
             metrics,
             status,
             metadata,
+            task_status,
+            task_progress,
+            task_slots,
         } = request.into_inner();
         debug!("Received heart beat request for {:?}", executor_id);
 
         // If not registered, do registration first before saving heart beat
-        if let Err(e) = self
+        let registered_slots = match self
             .state
             .executor_manager
             .get_executor_metadata(&executor_id)
             .await
         {
-            warn!("Fail to get executor metadata: {}", e);
-            if let Some(metadata) = metadata {
-                let metadata = ExecutorMetadata {
-                    id: metadata.id,
-                    host: metadata
-                        .optional_host
-                        .map(|h| match h {
-                            OptionalHost::Host(host) => host,
-                        })
-                        .unwrap_or_else(|| remote_addr.unwrap().ip().to_string()),
-                    port: metadata.port as u16,
-                    grpc_port: metadata.grpc_port as u16,
-                    specification: metadata.specification.unwrap().into(),
-                };
+            Ok(registered) => registered.specification.task_slots,
+            Err(e) => {
+                warn!("Fail to get executor metadata: {}", e);
+                if let Some(metadata) = metadata {
+                    let metadata = ExecutorMetadata {
+                        id: metadata.id,
+                        host: metadata
+                            .optional_host
+                            .map(|h| match h {
+                                OptionalHost::Host(host) => host,
+                            })
+                            .unwrap_or_else(|| remote_addr.unwrap().ip().to_string()),
+                        port: metadata.port as u16,
+                        grpc_port: metadata.grpc_port as u16,
+                        specification: metadata.specification.unwrap().into(),
+                        pool: metadata.pool,
+                    };
+                    let registered_slots = metadata.specification.task_slots;
+
+                    self.do_register_executor(metadata).await.map_err(|e| {
+                        let msg = format!("Fail to do executor registration due to: {e}");
+                        error!("{}", msg);
+                        Status::internal(msg)
+                    })?;
+                    registered_slots
+                } else {
+                    return Err(Status::invalid_argument(format!(
+                        "The registration spec for executor {executor_id} is not included"
+                    )));
+                }
+            }
+        };
 
-                self.do_register_executor(metadata).await.map_err(|e| {
-                    let msg = format!("Fail to do executor registration due to: {e}");
+        // The executor's task slots were resized while it runs
+        if task_slots > 0 && task_slots != registered_slots {
+            info!(
+                "Executor {executor_id} resized its task slots from {registered_slots} to {task_slots}"
+            );
+            self.set_task_slots(&executor_id, task_slots)
+                .await
+                .map_err(|e| {
+                    let msg = format!(
+                        "Fail to resize the task slots of executor {executor_id}: {e}"
+                    );
                     error!("{}", msg);
                     Status::internal(msg)
                 })?;
-            } else {
-                return Err(Status::invalid_argument(format!(
-                    "The registration spec for executor {executor_id} is not included"
-                )));
-            }
         }
 
         let executor_heartbeat = ExecutorHeartbeat {
-            executor_id,
-            timestamp: SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs(),
+            executor_id: executor_id.clone(),
+            timestamp: self.state.executor_manager.clock().now_secs(),
             metrics,
             status,
+            task_progress,
         };
+        self.warn_stalled_tasks(&executor_heartbeat);
 
         self.state
             .executor_manager
@@ -237,6 +350,20 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 error!("{}", msg);
                 Status::internal(msg)
             })?;
+
+        if !task_status.is_empty() {
+            self.update_task_status(&executor_id, task_status)
+                .await
+                .map_err(|e| {
+                    let msg = format!(
+                        "Fail to update tasks status from executor {:?} due to {:?}",
+                        &executor_id, e
+                    );
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?;
+        }
+
         Ok(Response::new(HeartBeatResult { reregister: false }))
     }
 
@@ -328,13 +455,164 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<ExecuteQueryParams>,
     ) -> Result<Response<ExecuteQueryResult>, Status> {
-        let query_params = request.into_inner();
+        if self.is_draining() {
+            return Err(Status::unavailable(
+                "Scheduler is draining and does not accept new queries",
+            ));
+        }
+        if let Some(reason) = self.await_capacity().await {
+            warn!("Rejecting query, the scheduler is overloaded: {reason}");
+            self.metrics_collector().record_rejected();
+            return Err(Status::resource_exhausted(format!(
+                "Scheduler is overloaded, retry later: {reason}"
+            )));
+        }
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(request.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate query: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        // Queries forwarded by another scheduler come with the ID of the job they are run as
+        let forwarded_job_id = request
+            .metadata()
+            .get(JOB_ID_METADATA_KEY)
+            .and_then(|job_id| job_id.to_str().ok())
+            .map(str::to_owned);
+        let (metadata, _, query_params) = request.into_parts();
         if let ExecuteQueryParams {
             query: Some(query),
             settings,
             optional_session_id,
+            optional_job_id,
+            job_settings,
+            idempotent,
+            run_as,
+            reports_consumed_partitions,
         } = query_params
         {
+            // Service accounts may run queries on behalf of their users, who are then the ones
+            // the queries are authorized for
+            let effective_user = self.impersonate(user.clone(), &run_as)?;
+            let job_id = match forwarded_job_id {
+                Some(job_id) => {
+                    // The job IDs of forwarded queries are checked like the ones supplied by
+                    // clients, and only accepted by the scheduler owning the job
+                    validate_job_id(&job_id).map_err(|e| {
+                        let msg = format!("Invalid forwarded job ID: {e}");
+                        warn!("{}", msg);
+                        Status::invalid_argument(msg)
+                    })?;
+                    let peers = &self.state.scheduler_peers;
+                    let owner = peers.remote_job_owner(&job_id).await.map_err(|e| {
+                        Status::internal(format!(
+                            "Failed to find the scheduler owning job {job_id}: {e:?}"
+                        ))
+                    })?;
+                    if !peers.is_job_sharding() || owner.is_some() {
+                        let msg = format!(
+                            "Forwarded job {job_id} is not owned by this scheduler"
+                        );
+                        warn!("{}", msg);
+                        return Err(Status::invalid_argument(msg));
+                    }
+                    job_id
+                }
+                None => {
+                    let job_id = match optional_job_id {
+                        Some(OptionalJobId::JobId(job_id)) => {
+                            validate_job_id(&job_id).map_err(|e| {
+                                let msg = format!("Invalid job ID: {e}");
+                                warn!("{}", msg);
+                                Status::invalid_argument(msg)
+                            })?;
+                            job_id
+                        }
+                        None => self.state.task_manager.generate_job_id(),
+                    };
+                    let peers = &self.state.scheduler_peers;
+                    let owner = peers.remote_job_owner(&job_id).await.map_err(|e| {
+                        Status::internal(format!(
+                            "Failed to find the scheduler owning job {job_id}: {e:?}"
+                        ))
+                    })?;
+                    if let Some(owner) = owner {
+                        debug!("Forwarding job {job_id} to scheduler {owner}");
+                        let params = ExecuteQueryParams {
+                            query: Some(query),
+                            settings,
+                            optional_session_id,
+                            optional_job_id: None,
+                            job_settings,
+                            idempotent,
+                            run_as,
+                            reports_consumed_partitions,
+                        };
+                        let result = peers
+                            .forward_query(&owner, &job_id, params, metadata)
+                            .await
+                            .map_err(|e| {
+                                let msg = format!(
+                                    "Failed to forward job {job_id} to scheduler {owner}: {e:?}"
+                                );
+                                error!("{}", msg);
+                                Status::unavailable(msg)
+                            })?;
+                        return Ok(Response::new(result));
+                    }
+                    job_id
+                }
+            };
+
+            // Job IDs supplied by clients have to be unique in the cluster
+            let exists =
+                self.state
+                    .task_manager
+                    .job_exists(&job_id)
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!(
+                            "Failed to check whether job {job_id} exists: {e:?}"
+                        ))
+                    })?;
+            if exists && idempotent {
+                // A retry of the submission of the job, which is not run again
+                let graph = self
+                    .state
+                    .task_manager
+                    .get_job_execution_graph(&job_id)
+                    .await
+                    .map_err(|e| {
+                        Status::internal(format!(
+                            "Failed to get execution graph of job {job_id}: {e:?}"
+                        ))
+                    })?;
+                // Jobs still being planned have no graph yet
+                let session_id = match (graph, optional_session_id) {
+                    (Some(graph), _) => graph.session_id().to_owned(),
+                    (None, Some(OptionalSessionId::SessionId(session_id))) => session_id,
+                    (None, None) => String::new(),
+                };
+                info!("Job {job_id} already exists, returning it to the client");
+                return Ok(Response::new(ExecuteQueryResult { job_id, session_id }));
+            }
+            if exists {
+                let msg = format!("Job {job_id} already exists");
+                warn!("{}", msg);
+                return Err(Status::already_exists(msg));
+            }
+
+            // SQL queries whose jobs repeatedly failed are rejected right away
+            let quarantine = self.state.task_manager.query_quarantine();
+            let sql = match &query {
+                Query::Sql(sql) => Some(sql.clone()),
+                Query::LogicalPlan(_) => None,
+            };
+            if let Some(reason) = sql.as_deref().and_then(|sql| quarantine.check(sql)) {
+                warn!("Rejecting job {job_id}: {reason}");
+                return Err(Status::failed_precondition(reason));
+            }
+
             // parse config
             let mut config_builder = BallistaConfig::builder();
             for kv_pair in &settings {
@@ -362,20 +640,35 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 }
                 _ => {
                     let ctx = self
-                        .state
-                        .session_manager
-                        .create_session(&config)
-                        .await
-                        .map_err(|e| {
-                            Status::internal(format!(
-                                "Failed to create SessionContext: {e:?}"
-                            ))
-                        })?;
-
+                        .create_owned_session(&config, effective_user.as_deref())
+                        .await?;
                     (ctx.session_id(), ctx)
                 }
             };
 
+            // Settings of the query apply to its job only and leave the session unchanged
+            let session_ctx = if job_settings.is_empty() {
+                session_ctx
+            } else {
+                let job_settings = job_settings
+                    .into_iter()
+                    .map(|kv_pair| (kv_pair.key, kv_pair.value))
+                    .collect();
+                create_job_context(&session_ctx, job_settings).map_err(|e| {
+                    let msg = format!("Could not parse job settings: {e}");
+                    error!("{}", msg);
+                    Status::invalid_argument(msg)
+                })?
+            };
+            let session_ctx = create_owned_job_context(
+                &session_ctx,
+                effective_user.as_deref(),
+                reports_consumed_partitions,
+            )
+            .map_err(|e| {
+                Status::internal(format!("Failed to create job context: {e:?}"))
+            })?;
+
             let plan = match query {
                 Query::LogicalPlan(message) => T::try_decode(message.as_slice())
                     .and_then(|m| {
@@ -402,28 +695,54 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
 
             debug!("Received plan for execution: {:?}", plan);
 
-            let job_id = self.state.task_manager.generate_job_id();
+            plugins
+                .authorize(effective_user.as_deref(), &plan)
+                .map_err(|e| {
+                    let msg = format!("Query of job {job_id} is not authorized: {e}");
+                    warn!("{}", msg);
+                    Status::permission_denied(msg)
+                })?;
+
             let job_name = config
                 .settings()
                 .get(BALLISTA_JOB_NAME)
                 .cloned()
                 .unwrap_or_default();
 
+            let session_ctx = self
+                .route_job(&job_id, session_ctx, sql.as_deref())
+                .map_err(|e| {
+                    let msg = format!("Failed to route job {job_id} to a pool: {e}");
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?;
+            if let Some(sql) = &sql {
+                quarantine.add_job(&job_id, sql);
+            }
             self.submit_job(&job_id, &job_name, session_ctx, &plan)
                 .await
                 .map_err(|e| {
+                    quarantine.remove_job(&job_id);
                     let msg =
                         format!("Failed to send JobQueued event for {job_id}: {e:?}");
                     error!("{}", msg);
 
                     Status::internal(msg)
                 })?;
+            if !run_as.is_empty() {
+                info!(
+                    target: AUDIT_LOG_TARGET,
+                    "Job {job_id} submitted by {} runs as {run_as}",
+                    user.as_deref().unwrap_or("anonymous client")
+                );
+            }
 
             Ok(Response::new(ExecuteQueryResult { job_id, session_id }))
         } else if let ExecuteQueryParams {
             query: None,
             settings,
             optional_session_id: None,
+            ..
         } = query_params
         {
             // parse config for new session
@@ -436,16 +755,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
                 error!("{}", msg);
                 Status::internal(msg)
             })?;
-            let session = self
-                .state
-                .session_manager
-                .create_session(&config)
-                .await
-                .map_err(|e| {
-                    Status::internal(format!(
-                        "Failed to create new SessionContext: {e:?}"
-                    ))
-                })?;
+            let session = self.create_owned_session(&config, user.as_deref()).await?;
 
             Ok(Response::new(ExecuteQueryResult {
                 job_id: "NA".to_owned(),
@@ -460,8 +770,54 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         &self,
         request: Request<GetJobStatusParams>,
     ) -> Result<Response<GetJobStatusResult>, Status> {
-        let job_id = request.into_inner().job_id;
+        let (metadata, _, params) = request.into_parts();
+        let GetJobStatusParams {
+            job_id,
+            consumed_partitions,
+        } = params;
         trace!("Received get_job_status request for job {}", job_id);
+        self.authorize_job_access(&metadata, &job_id).await?;
+
+        // The client reports the results it fetched so that the tasks held back by the
+        // result buffer of the job are scheduled
+        if !consumed_partitions.is_empty() {
+            let partitions = consumed_partitions.iter().map(|p| *p as usize).collect();
+            match self
+                .state
+                .task_manager
+                .consume_job_results(&job_id, partitions)
+                .await
+            {
+                Ok(true) => {
+                    self.query_stage_event_loop
+                        .get_sender()
+                        .map_err(|e| {
+                            let msg =
+                                format!("Get query stage event loop error due to {e:?}");
+                            error!("{}", msg);
+                            Status::internal(msg)
+                        })?
+                        .post_event(QueryStageSchedulerEvent::JobResultsConsumed(
+                            job_id.clone(),
+                        ))
+                        .await
+                        .map_err(|e| {
+                            let msg = format!(
+                                "Post to query stage event loop error due to {e:?}"
+                            );
+                            error!("{}", msg);
+                            Status::internal(msg)
+                        })?;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    let msg = format!("Error consuming results of job {job_id}: {e:?}");
+                    error!("{}", msg);
+                    return Err(Status::internal(msg));
+                }
+            }
+        }
+
         match self.state.task_manager.get_job_status(&job_id).await {
             Ok(status) => Ok(Response::new(GetJobStatusResult { status })),
             Err(e) => {
@@ -472,112 +828,1006 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerGrpc
         }
     }
 
-    async fn executor_stopped(
+    async fn get_task_attempts(
         &self,
-        request: Request<ExecutorStoppedParams>,
-    ) -> Result<Response<ExecutorStoppedResult>, Status> {
-        let ExecutorStoppedParams {
-            executor_id,
-            reason,
-        } = request.into_inner();
-        info!(
-            "Received executor stopped request from Executor {} with reason '{}'",
-            executor_id, reason
-        );
+        request: Request<GetTaskAttemptsParams>,
+    ) -> Result<Response<GetTaskAttemptsResult>, Status> {
+        let (metadata, _, params) = request.into_parts();
+        let GetTaskAttemptsParams { job_id, stage_id } = params;
+        trace!("Received get_task_attempts request for job {}", job_id);
+        self.authorize_job_access(&metadata, &job_id).await?;
+        let stage_id = (stage_id > 0).then_some(stage_id as usize);
+        match self
+            .state
+            .task_manager
+            .get_task_attempts(&job_id, stage_id)
+            .await
+        {
+            Ok(Some(attempts)) => Ok(Response::new(GetTaskAttemptsResult { attempts })),
+            Ok(None) => Err(Status::not_found(format!("Job {job_id} not found"))),
+            Err(e) => {
+                let msg = format!("Error getting task attempts for job {job_id}: {e:?}");
+                error!("{}", msg);
+                Err(Status::internal(msg))
+            }
+        }
+    }
 
-        let executor_manager = self.state.executor_manager.clone();
-        let event_sender = self.query_stage_event_loop.get_sender().map_err(|e| {
-            let msg = format!("Get query stage event loop error due to {e:?}");
+    async fn list_jobs(
+        &self,
+        request: Request<ListJobsParams>,
+    ) -> Result<Response<ListJobsResult>, Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(request.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate job listing: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let labels = request.into_inner().labels;
+        let jobs = self.state.task_manager.get_jobs().await.map_err(|e| {
+            let msg = format!("Error listing jobs: {e:?}");
             error!("{}", msg);
             Status::internal(msg)
         })?;
-
-        Self::remove_executor(
-            executor_manager,
-            event_sender,
-            &executor_id,
-            Some(reason),
-            self.executor_termination_grace_period,
-        );
-
-        Ok(Response::new(ExecutorStoppedResult {}))
+        // Clients only see the jobs they may access
+        let jobs = jobs
+            .into_iter()
+            .filter(|job| job.has_labels(&labels))
+            .filter(|job| {
+                plugins
+                    .authorize_job_access(user.as_deref(), job.owner.as_deref())
+                    .is_ok()
+            })
+            .map(|job| JobSummary {
+                job_id: job.job_id,
+                job_name: job.job_name,
+                status: Some(job.status),
+                labels: job.labels,
+            })
+            .collect();
+        Ok(Response::new(ListJobsResult { jobs }))
     }
 
-    async fn cancel_job(
+    async fn estimate_query(
         &self,
-        request: Request<CancelJobParams>,
-    ) -> Result<Response<CancelJobResult>, Status> {
-        let job_id = request.into_inner().job_id;
-        info!("Received cancellation request for job {}", job_id);
+        request: Request<EstimateQueryParams>,
+    ) -> Result<Response<EstimateQueryResult>, Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(request.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate query estimate: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let EstimateQueryParams {
+            sql,
+            optional_session_id,
+            settings,
+        } = request.into_inner();
 
-        self.query_stage_event_loop
-            .get_sender()
+        // parse config
+        let mut config_builder = BallistaConfig::builder();
+        for kv_pair in &settings {
+            config_builder = config_builder.set(&kv_pair.key, &kv_pair.value);
+        }
+        let config = config_builder.build().map_err(|e| {
+            let msg = format!("Could not parse configs: {e}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+
+        let session_ctx = match optional_session_id {
+            Some(estimate_query_params::OptionalSessionId::SessionId(session_id)) => self
+                .state
+                .session_manager
+                .update_session(&session_id, &config)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!(
+                        "Failed to load SessionContext for session ID {session_id}: {e:?}"
+                    ))
+                })?,
+            None => self.create_owned_session(&config, user.as_deref()).await?,
+        };
+
+        let plan = session_ctx
+            .sql(&sql)
+            .await
+            .and_then(|df| df.into_optimized_plan())
             .map_err(|e| {
-                let msg = format!("Get query stage event loop error due to {e:?}");
+                let msg = format!("Error parsing SQL: {e}");
                 error!("{}", msg);
                 Status::internal(msg)
-            })?
-            .post_event(QueryStageSchedulerEvent::JobCancel(job_id))
+            })?;
+        plugins.authorize(user.as_deref(), &plan).map_err(|e| {
+            let msg = format!("Query estimate is not authorized: {e}");
+            warn!("{}", msg);
+            Status::permission_denied(msg)
+        })?;
+
+        let estimate = self
+            .state
+            .plan_job("estimate", session_ctx.clone(), &plan)
             .await
+            .and_then(|plan| self.state.task_manager.estimate_query(plan))
             .map_err(|e| {
-                let msg = format!("Post to query stage event loop error due to {e:?}");
+                let msg = format!("Error estimating query: {e:?}");
                 error!("{}", msg);
                 Status::internal(msg)
             })?;
-        Ok(Response::new(CancelJobResult { cancelled: true }))
+
+        Ok(Response::new(estimate.into_proto(session_ctx.session_id())))
     }
 
-    async fn clean_job_data(
+    async fn get_results_page(
         &self,
-        request: Request<CleanJobDataParams>,
-    ) -> Result<Response<CleanJobDataResult>, Status> {
-        let job_id = request.into_inner().job_id;
-        info!("Received clean data request for job {}", job_id);
+        request: Request<GetResultsPageParams>,
+    ) -> Result<Response<GetResultsPageResult>, Status> {
+        let (metadata, _, params) = request.into_parts();
+        let GetResultsPageParams {
+            job_id,
+            offset,
+            limit,
+        } = params;
+        trace!(
+            "Received get_results_page request for job {} with offset {} and limit {}",
+            job_id,
+            offset,
+            limit
+        );
+        self.authorize_job_access(&metadata, &job_id).await?;
 
-        self.query_stage_event_loop
-            .get_sender()
-            .map_err(|e| {
-                let msg = format!("Get query stage event loop error due to {e:?}");
-                error!("{}", msg);
-                Status::internal(msg)
-            })?
-            .post_event(QueryStageSchedulerEvent::JobDataClean(job_id))
+        let status = self
+            .state
+            .task_manager
+            .get_job_status(&job_id)
             .await
             .map_err(|e| {
-                let msg = format!("Post to query stage event loop error due to {e:?}");
+                let msg = format!("Error getting status for job {job_id}: {e:?}");
                 error!("{}", msg);
                 Status::internal(msg)
-            })?;
-        Ok(Response::new(CleanJobDataResult {}))
+            })?
+            .ok_or_else(|| Status::not_found(format!("Job {job_id} not found")))?;
+        let partition_location = match status.status {
+            Some(job_status::Status::Successful(SuccessfulJob {
+                results_expired: true,
+                ..
+            })) => {
+                return Err(Status::failed_precondition(format!(
+                    "Results of job {job_id} expired"
+                )))
+            }
+            Some(job_status::Status::Successful(SuccessfulJob {
+                partition_location,
+                ..
+            })) => partition_location,
+            _ => {
+                return Err(Status::failed_precondition(format!(
+                    "Job {job_id} has not completed successfully"
+                )))
+            }
+        };
+
+        let (data, total_rows) = read_results_page(
+            self.result_partition_reader.as_ref(),
+            &partition_location,
+            offset as usize,
+            limit as usize,
+        )
+        .await
+        .map_err(|e| {
+            let msg = format!("Error reading results page of job {job_id}: {e:?}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+
+        Ok(Response::new(GetResultsPageResult {
+            data,
+            total_rows: total_rows as u64,
+        }))
     }
-}
 
-#[cfg(all(test, feature = "sled"))]
-mod test {
+    type SubscribeJobEventsStream =
+        Pin<Box<dyn Stream<Item = Result<JobEvent, Status>> + Send>>;
 
-    use std::time::Duration;
+    async fn subscribe_job_events(
+        &self,
+        request: Request<SubscribeJobEventsParams>,
+    ) -> Result<Response<Self::SubscribeJobEventsStream>, Status> {
+        let (metadata, _, params) = request.into_parts();
+        let job_id = params.job_id;
+        debug!("Received subscribe_job_events request for job {}", job_id);
+        self.authorize_job_access(&metadata, &job_id).await?;
+
+        let get_job_status = || async {
+            self.state
+                .task_manager
+                .get_job_status(&job_id)
+                .await
+                .map_err(|e| {
+                    let msg = format!("Error getting status for job {job_id}: {e:?}");
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })
+        };
+        if get_job_status().await?.is_none() {
+            return Err(Status::not_found(format!("Job {job_id} not found")));
+        }
 
-    use datafusion_proto::protobuf::LogicalPlanNode;
-    use datafusion_proto::protobuf::PhysicalPlanNode;
-    use tonic::Request;
+        // Look the job up again after subscribing, it may have completed in between
+        let events = self.query_stage_scheduler.job_events().subscribe(&job_id);
+        match get_job_status().await?.and_then(|status| status.status) {
+            Some(
+                job_status::Status::Successful(_)
+                | job_status::Status::Failed(_)
+                | job_status::Status::Cancelled(_),
+            ) => {
+                self.query_stage_scheduler
+                    .publish_job_completed(&job_id)
+                    .await
+                    .map_err(|e| {
+                        let msg =
+                            format!("Error publishing status of job {job_id}: {e:?}");
+                        error!("{}", msg);
+                        Status::internal(msg)
+                    })?;
+            }
+            _ => {
+                self.query_stage_scheduler
+                    .publish_stage_events(&job_id)
+                    .await
+            }
+        }
 
-    use crate::config::SchedulerConfig;
-    use crate::metrics::default_metrics_collector;
-    use ballista_core::error::BallistaError;
-    use ballista_core::serde::protobuf::{
-        executor_registration::OptionalHost, executor_status, ExecutorRegistration,
-        ExecutorStatus, ExecutorStoppedParams, HeartBeatParams, PollWorkParams,
-        RegisterExecutorParams,
-    };
-    use ballista_core::serde::scheduler::ExecutorSpecification;
-    use ballista_core::serde::BallistaCodec;
+        Ok(Response::new(Box::pin(events.map(Ok))))
+    }
 
-    use crate::state::executor_manager::DEFAULT_EXECUTOR_TIMEOUT_SECONDS;
-    use crate::state::SchedulerState;
-    use crate::test_utils::await_condition;
-    use crate::test_utils::test_cluster_context;
+    type GetTaskLogsStream =
+        Pin<Box<dyn Stream<Item = Result<TaskLogLines, Status>> + Send>>;
 
-    use super::{SchedulerGrpc, SchedulerServer};
+    async fn get_task_logs(
+        &self,
+        request: Request<GetTaskLogsParams>,
+    ) -> Result<Response<Self::GetTaskLogsStream>, Status> {
+        self.authorize_job_access(request.metadata(), &request.get_ref().job_id)
+            .await?;
+        let authorization = request.metadata().get("authorization").cloned();
+        let GetTaskLogsParams {
+            job_id,
+            task_id,
+            from_line,
+            follow,
+        } = request.into_inner();
+        debug!("Received get_task_logs request for task {task_id} of job {job_id}");
+
+        let lines = self
+            .state
+            .fetch_task_logs(&job_id, task_id as usize, from_line, follow, authorization)
+            .await
+            .map_err(|e| {
+                let msg = format!(
+                    "Error fetching the logs of task {task_id} of job {job_id}: {e:?}"
+                );
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .ok_or_else(|| {
+                Status::not_found(format!("Task {task_id} of job {job_id} not found"))
+            })?;
+
+        Ok(Response::new(Box::pin(lines.map(|lines| {
+            lines.map_err(|e| Status::internal(format!("{e:?}")))
+        }))))
+    }
+
+    async fn executor_stopped(
+        &self,
+        request: Request<ExecutorStoppedParams>,
+    ) -> Result<Response<ExecutorStoppedResult>, Status> {
+        let ExecutorStoppedParams {
+            executor_id,
+            reason,
+        } = request.into_inner();
+        info!(
+            "Received executor stopped request from Executor {} with reason '{}'",
+            executor_id, reason
+        );
+
+        let executor_manager = self.state.executor_manager.clone();
+        let event_sender = self.query_stage_event_loop.get_sender().map_err(|e| {
+            let msg = format!("Get query stage event loop error due to {e:?}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+
+        Self::remove_executor(
+            executor_manager,
+            event_sender,
+            &executor_id,
+            Some(reason),
+            self.executor_termination_grace_period,
+        );
+
+        Ok(Response::new(ExecutorStoppedResult {}))
+    }
+
+    async fn recover_executor_data(
+        &self,
+        request: Request<RecoverExecutorDataParams>,
+    ) -> Result<Response<RecoverExecutorDataResult>, Status> {
+        let remote_addr = request.remote_addr();
+        let RecoverExecutorDataParams {
+            metadata,
+            previous_executor_id,
+            job_ids,
+            shuffle_files,
+        } = request.into_inner();
+        let metadata = metadata
+            .ok_or_else(|| Status::invalid_argument("Missing metadata in request"))?;
+        let host = match metadata.optional_host {
+            Some(OptionalHost::Host(host)) => host,
+            None => remote_addr
+                .ok_or_else(|| {
+                    Status::invalid_argument(
+                        "Missing host of the executor, which has no remote address",
+                    )
+                })?
+                .ip()
+                .to_string(),
+        };
+        let specification = metadata.specification.ok_or_else(|| {
+            Status::invalid_argument("Missing specification in executor metadata")
+        })?;
+        let metadata = ExecutorMetadata {
+            id: metadata.id,
+            host,
+            port: metadata.port as u16,
+            grpc_port: metadata.grpc_port as u16,
+            specification: specification.into(),
+            pool: metadata.pool,
+        };
+        info!(
+            "Received data recovery request from executor {} with {} shuffle files of {} jobs",
+            metadata.id,
+            shuffle_files.len(),
+            job_ids.len()
+        );
+
+        // The data of the jobs which are neither running nor fetched by their clients is
+        // removed by the executor. The jobs unknown to this scheduler may be run by the
+        // other schedulers sharing the cluster.
+        let mut orphaned_job_ids = vec![];
+        for job_id in job_ids {
+            let status = self
+                .state
+                .task_manager
+                .get_job_status(&job_id)
+                .await
+                .map_err(|e| {
+                    let msg = format!("Failed to get status of job {job_id}: {e:?}");
+                    error!("{}", msg);
+                    Status::internal(msg)
+                })?;
+            let status = match status {
+                Some(status) => Some(status),
+                None => match self.state.scheduler_peers.peer_job_status(&job_id).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        warn!("Keeping the data of job {job_id} whose status the other schedulers failed to report: {e:?}");
+                        continue;
+                    }
+                },
+            };
+            let active = matches!(
+                status.and_then(|status| status.status),
+                Some(
+                    job_status::Status::Queued(_)
+                        | job_status::Status::Running(_)
+                        | job_status::Status::Successful(_)
+                )
+            );
+            if !active {
+                orphaned_job_ids.push(job_id);
+            }
+        }
+
+        // The stages of the executor before it restarted are reset now rather than once its
+        // heartbeats expire, reading its intact shuffle files from the restarted executor
+        if !previous_executor_id.is_empty() && previous_executor_id != metadata.id {
+            let event_sender = self.query_stage_event_loop.get_sender().map_err(|e| {
+                let msg = format!("Get query stage event loop error due to {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+            let reason = format!("executor restarted as {}", metadata.id);
+            self.state.task_manager.executor_restarted(
+                &previous_executor_id,
+                metadata,
+                shuffle_files.into_iter().collect(),
+            );
+            Self::remove_executor(
+                self.state.executor_manager.clone(),
+                event_sender,
+                &previous_executor_id,
+                Some(reason),
+                0,
+            );
+        }
+
+        Ok(Response::new(RecoverExecutorDataResult {
+            orphaned_job_ids,
+        }))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<CancelJobParams>,
+    ) -> Result<Response<CancelJobResult>, Status> {
+        let (metadata, _, params) = request.into_parts();
+        let job_id = params.job_id;
+        info!("Received cancellation request for job {}", job_id);
+        self.authorize_job_access(&metadata, &job_id).await?;
+
+        // Jobs owned by another scheduler sharing the cluster are cancelled by it, unless the
+        // cancellation was already forwarded
+        if metadata.get(JOB_ID_METADATA_KEY).is_none()
+            && !self.state.task_manager.is_active_job(&job_id)
+        {
+            let status = self
+                .state
+                .task_manager
+                .get_job_status(&job_id)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!(
+                        "Failed to get status of job {job_id}: {e:?}"
+                    ))
+                })?;
+            let peers = &self.state.scheduler_peers;
+            let owner = peers
+                .remote_owner_of(&job_id, status.as_ref())
+                .await
+                .map_err(|e| {
+                    Status::internal(format!(
+                        "Failed to find the scheduler owning job {job_id}: {e:?}"
+                    ))
+                })?;
+            if let Some(owner) = owner {
+                debug!(
+                    "Forwarding the cancellation of job {job_id} to scheduler {owner}"
+                );
+                let cancelled = peers
+                    .forward_cancel_job(&owner, &job_id, metadata)
+                    .await
+                    .map_err(|e| {
+                        let msg = format!(
+                            "Failed to forward the cancellation of job {job_id} to scheduler {owner}: {e:?}"
+                        );
+                        error!("{}", msg);
+                        Status::unavailable(msg)
+                    })?;
+                return Ok(Response::new(CancelJobResult { cancelled }));
+            }
+        }
+
+        self.query_stage_event_loop
+            .get_sender()
+            .map_err(|e| {
+                let msg = format!("Get query stage event loop error due to {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .post_event(QueryStageSchedulerEvent::JobCancel(job_id))
+            .await
+            .map_err(|e| {
+                let msg = format!("Post to query stage event loop error due to {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        Ok(Response::new(CancelJobResult { cancelled: true }))
+    }
+
+    async fn get_job_group_status(
+        &self,
+        request: Request<GetJobGroupStatusParams>,
+    ) -> Result<Response<GetJobGroupStatusResult>, Status> {
+        let (metadata, _, params) = request.into_parts();
+        let group_id = params.group_id;
+        if group_id.is_empty() {
+            return Err(Status::invalid_argument("Job group ID is empty"));
+        }
+        let result = self
+            .authorize_job_group_access(&metadata, &group_id)
+            .await?;
+        Ok(Response::new(result))
+    }
+
+    async fn cancel_job_group(
+        &self,
+        request: Request<CancelJobGroupParams>,
+    ) -> Result<Response<CancelJobGroupResult>, Status> {
+        let (metadata, _, params) = request.into_parts();
+        let group_id = params.group_id;
+        if group_id.is_empty() {
+            return Err(Status::invalid_argument("Job group ID is empty"));
+        }
+        info!("Received cancellation request for job group {}", group_id);
+        self.authorize_job_group_access(&metadata, &group_id)
+            .await?;
+
+        let job_ids = self.cancel_group_jobs(&group_id).await.map_err(|e| {
+            let msg = format!("Error cancelling job group {group_id}: {e:?}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        Ok(Response::new(CancelJobGroupResult { job_ids }))
+    }
+
+    async fn clean_job_data(
+        &self,
+        request: Request<CleanJobDataParams>,
+    ) -> Result<Response<CleanJobDataResult>, Status> {
+        let (metadata, _, params) = request.into_parts();
+        let job_id = params.job_id;
+        info!("Received clean data request for job {}", job_id);
+        self.authorize_job_access(&metadata, &job_id).await?;
+
+        self.query_stage_event_loop
+            .get_sender()
+            .map_err(|e| {
+                let msg = format!("Get query stage event loop error due to {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?
+            .post_event(QueryStageSchedulerEvent::JobDataClean(job_id))
+            .await
+            .map_err(|e| {
+                let msg = format!("Post to query stage event loop error due to {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        Ok(Response::new(CleanJobDataResult {}))
+    }
+
+    async fn drain(
+        &self,
+        request: Request<DrainParams>,
+    ) -> Result<Response<DrainResult>, Status> {
+        self.authorize_admin(request.metadata(), AdminAction::Drain)?;
+        let DrainParams { timeout_seconds } = request.into_inner();
+        let timeout_seconds = if timeout_seconds > 0 {
+            timeout_seconds
+        } else {
+            self.state.config.drain_timeout_seconds
+        };
+        info!(
+            "Received drain request with a timeout of {} seconds",
+            timeout_seconds
+        );
+
+        let unfinished_jobs = self.unfinished_jobs() as u64;
+        let scheduler_server = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = scheduler_server
+                .drain_jobs(Duration::from_secs(timeout_seconds))
+                .await
+            {
+                error!("Error draining scheduler: {e:?}");
+            }
+        });
+
+        Ok(Response::new(DrainResult { unfinished_jobs }))
+    }
+
+    async fn decommission_executor(
+        &self,
+        request: Request<DecommissionExecutorParams>,
+    ) -> Result<Response<DecommissionExecutorResult>, Status> {
+        self.authorize_admin(request.metadata(), AdminAction::DecommissionExecutor)?;
+        let DecommissionExecutorParams {
+            executor_id,
+            timeout_seconds,
+        } = request.into_inner();
+        info!(
+            "Received decommission request for executor {} with a timeout of {} seconds",
+            executor_id, timeout_seconds
+        );
+
+        let timeout = (timeout_seconds > 0).then(|| Duration::from_secs(timeout_seconds));
+        let active_jobs =
+            self.decommission(&executor_id, timeout)
+                .await
+                .map_err(|e| {
+                    let msg =
+                        format!("Could not decommission executor {executor_id}: {e}");
+                    error!("{}", msg);
+                    Status::not_found(msg)
+                })?;
+
+        Ok(Response::new(DecommissionExecutorResult {
+            active_jobs: active_jobs as u64,
+        }))
+    }
+
+    async fn close_session(
+        &self,
+        request: Request<CloseSessionParams>,
+    ) -> Result<Response<CloseSessionResult>, Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(request.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate session closing: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let session_id = request.into_inner().session_id;
+        info!("Received close session request for session {}", session_id);
+
+        let session_manager = &self.state.session_manager;
+        let owner = session_manager
+            .session_owner(&session_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "Failed to get the owner of session {session_id}: {e:?}"
+                ))
+            })?;
+        // Sessions are closed by their owner like jobs are accessed by theirs
+        plugins
+            .authorize_job_access(user.as_deref(), owner.as_deref())
+            .map_err(|e| {
+                let msg = format!("Closing session {session_id} is not authorized: {e}");
+                warn!("{}", msg);
+                Status::permission_denied(msg)
+            })?;
+
+        let closed = self
+            .state
+            .session_manager
+            .remove_session(&session_id)
+            .await
+            .map_err(|e| {
+                let msg = format!("Failed to close session {session_id}: {e:?}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        self.state
+            .executor_manager
+            .remove_session_affinity(&session_id);
+        Ok(Response::new(CloseSessionResult { closed }))
+    }
+
+    async fn register_table(
+        &self,
+        request: Request<RegisterTableParams>,
+    ) -> Result<Response<RegisterTableResult>, Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(request.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate table registration: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let RegisterTableParams {
+            session_id,
+            plan,
+            settings,
+        } = request.into_inner();
+
+        let mut config_builder = BallistaConfig::builder();
+        for kv_pair in &settings {
+            config_builder = config_builder.set(&kv_pair.key, &kv_pair.value);
+        }
+        let config = config_builder.build().map_err(|e| {
+            let msg = format!("Could not parse configs: {e}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        let session_manager = &self.state.session_manager;
+        let session_ctx = session_manager
+            .update_session(&session_id, &config)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "Failed to load SessionContext for session ID {session_id}: {e:?}"
+                ))
+            })?;
+
+        let plan = T::try_decode(plan.as_slice())
+            .and_then(|m| {
+                m.try_into_logical_plan(
+                    session_ctx.deref(),
+                    self.state.codec.logical_extension_codec(),
+                )
+            })
+            .map_err(|e| {
+                let msg = format!("Could not parse logical plan protobuf: {e}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        plugins.authorize(user.as_deref(), &plan).map_err(|e| {
+            let msg = format!("Table registration is not authorized: {e}");
+            warn!("{}", msg);
+            Status::permission_denied(msg)
+        })?;
+
+        let registered = session_manager
+            .register_table(&session_ctx, plan)
+            .await
+            .map_err(|e| {
+                let msg = format!("Failed to register table: {e}");
+                error!("{}", msg);
+                Status::internal(msg)
+            })?;
+        Ok(Response::new(RegisterTableResult { registered }))
+    }
+
+    async fn get_tables(
+        &self,
+        _request: Request<GetTablesParams>,
+    ) -> Result<Response<GetTablesResult>, Status> {
+        let tables = self.state.session_manager.tables().await.map_err(|e| {
+            let msg = format!("Failed to get the shared tables: {e:?}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        Ok(Response::new(GetTablesResult { tables }))
+    }
+
+    async fn drop_table(
+        &self,
+        request: Request<DropTableParams>,
+    ) -> Result<Response<DropTableResult>, Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(request.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate table drop: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let DropTableParams { name, if_exists } = request.into_inner();
+        plugins
+            .authorize(user.as_deref(), &drop_table_plan(&name, if_exists))
+            .map_err(|e| {
+                let msg = format!("Table drop is not authorized: {e}");
+                warn!("{}", msg);
+                Status::permission_denied(msg)
+            })?;
+
+        let dropped = self
+            .state
+            .session_manager
+            .drop_table(&name, if_exists)
+            .await
+            .map_err(|e| {
+                let msg = format!("Failed to drop table {name}: {e}");
+                error!("{}", msg);
+                Status::not_found(msg)
+            })?;
+        if dropped {
+            info!("Dropped shared table {name}");
+        }
+        Ok(Response::new(DropTableResult { dropped }))
+    }
+
+    async fn undrop_table(
+        &self,
+        request: Request<UndropTableParams>,
+    ) -> Result<Response<UndropTableResult>, Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(request.metadata()).map_err(|e| {
+            let msg = format!("Failed to authenticate table undrop: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let UndropTableParams { name } = request.into_inner();
+        // Restoring a table is authorized like dropping it
+        plugins
+            .authorize(user.as_deref(), &drop_table_plan(&name, false))
+            .map_err(|e| {
+                let msg = format!("Table undrop is not authorized: {e}");
+                warn!("{}", msg);
+                Status::permission_denied(msg)
+            })?;
+
+        self.state
+            .session_manager
+            .undrop_table(&name)
+            .await
+            .map_err(|e| {
+                let msg = format!("Failed to undrop table {name}: {e}");
+                error!("{}", msg);
+                Status::not_found(msg)
+            })?;
+        info!("Restored dropped shared table {name}");
+        Ok(Response::new(UndropTableResult {}))
+    }
+
+    async fn get_cluster_config(
+        &self,
+        _request: Request<GetClusterConfigParams>,
+    ) -> Result<Response<GetClusterConfigResult>, Status> {
+        let mut config = self.state.session_manager.cluster_config().map_err(|e| {
+            let msg = format!("Failed to get the cluster config: {e:?}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        config.rest_api = self.state.config.rest_api_enabled();
+        Ok(Response::new(GetClusterConfigResult {
+            config: Some(config),
+        }))
+    }
+}
+
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
+    /// Check that the authenticated client `user` may run queries on behalf of `run_as`, and
+    /// return the user the queries are then authorized for and owned by, the client itself if
+    /// `run_as` is empty. Allowed and denied impersonations are both written to the audit log.
+    pub(crate) fn impersonate(
+        &self,
+        user: Option<String>,
+        run_as: &str,
+    ) -> Result<Option<String>, Status> {
+        if run_as.is_empty() {
+            return Ok(user);
+        }
+        let client = user.as_deref().unwrap_or("anonymous client");
+        let plugins = &self.state.config.plugins;
+        if let Err(e) = plugins.impersonate(user.as_deref(), run_as) {
+            info!(target: AUDIT_LOG_TARGET, "Denied {client} to run queries as {run_as}");
+            let msg = format!("Failed to run query as {run_as}: {e}");
+            warn!("{}", msg);
+            return Err(Status::permission_denied(msg));
+        }
+        info!(target: AUDIT_LOG_TARGET, "Allowed {client} to run queries as {run_as}");
+        Ok(Some(run_as.to_owned()))
+    }
+
+    /// Authenticate the client of a request and check that it may access a job, owned by the
+    /// user who submitted it
+    pub(crate) async fn authorize_job_access(
+        &self,
+        metadata: &MetadataMap,
+        job_id: &str,
+    ) -> Result<(), Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(metadata).map_err(|e| {
+            let msg = format!("Failed to authenticate access to job {job_id}: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let owner = self
+            .state
+            .task_manager
+            .job_owner(job_id)
+            .await
+            .map_err(|e| {
+                Status::internal(format!(
+                    "Failed to get the owner of job {job_id}: {e:?}"
+                ))
+            })?;
+        plugins
+            .authorize_job_access(user.as_deref(), owner.as_deref())
+            .map_err(|e| {
+                let msg = format!("Access to job {job_id} is not authorized: {e}");
+                warn!("{}", msg);
+                Status::permission_denied(msg)
+            })
+    }
+
+    /// Create a session owned by the user the client of a request runs queries as, if the
+    /// client was authenticated
+    async fn create_owned_session(
+        &self,
+        config: &BallistaConfig,
+        owner: Option<&str>,
+    ) -> Result<Arc<SessionContext>, Status> {
+        let session_manager = &self.state.session_manager;
+        let ctx = session_manager.create_session(config).await.map_err(|e| {
+            Status::internal(format!("Failed to create SessionContext: {e:?}"))
+        })?;
+        if let Some(owner) = owner {
+            session_manager
+                .claim_session(&ctx.session_id(), owner)
+                .await
+                .map_err(|e| {
+                    Status::internal(format!(
+                        "Failed to save the owner of session {}: {e:?}",
+                        ctx.session_id()
+                    ))
+                })?;
+        }
+        Ok(ctx)
+    }
+
+    /// Authenticate the client of a request and check that it may perform an administrative
+    /// action
+    pub(crate) fn authorize_admin(
+        &self,
+        metadata: &MetadataMap,
+        action: AdminAction,
+    ) -> Result<(), Status> {
+        let plugins = &self.state.config.plugins;
+        let user = plugins.authenticate(metadata).map_err(|e| {
+            let msg = format!("Failed to authenticate request to {action}: {e}");
+            warn!("{}", msg);
+            Status::unauthenticated(msg)
+        })?;
+        let client = user.as_deref().unwrap_or("anonymous client");
+        if let Err(e) = plugins.authorize_admin(user.as_deref(), action) {
+            info!(target: AUDIT_LOG_TARGET, "Denied {client} to {action}");
+            let msg = format!("Request to {action} is not authorized: {e}");
+            warn!("{}", msg);
+            return Err(Status::permission_denied(msg));
+        }
+        info!(target: AUDIT_LOG_TARGET, "Allowed {client} to {action}");
+        Ok(())
+    }
+
+    /// Authenticate the client of a request and check that it may access all the jobs of a
+    /// group, returns the status of the group
+    async fn authorize_job_group_access(
+        &self,
+        metadata: &MetadataMap,
+        group_id: &str,
+    ) -> Result<GetJobGroupStatusResult, Status> {
+        let result = self.job_group_status(group_id).await.map_err(|e| {
+            let msg = format!("Error getting status of job group {group_id}: {e:?}");
+            error!("{}", msg);
+            Status::internal(msg)
+        })?;
+        for job in &result.jobs {
+            self.authorize_job_access(metadata, &job.job_id).await?;
+        }
+        Ok(result)
+    }
+}
+
+/// The DROP TABLE plan of a shared table, which the plugins authorize
+fn drop_table_plan(name: &str, if_exists: bool) -> LogicalPlan {
+    LogicalPlan::Ddl(DdlStatement::DropTable(DropTable {
+        name: TableReference::from(name).to_owned_reference(),
+        if_exists,
+        schema: Arc::new(DFSchema::empty()),
+    }))
+}
+
+#[cfg(all(test, feature = "sled"))]
+mod test {
+
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use datafusion_proto::protobuf::LogicalPlanNode;
+    use datafusion_proto::protobuf::PhysicalPlanNode;
+    use tonic::metadata::MetadataMap;
+    use tonic::{Code, Request};
+
+    use crate::config::SchedulerConfig;
+    use crate::metrics::default_metrics_collector;
+    use crate::plugin::{AdminAction, SchedulerPlugin};
+    use ballista_core::config::BALLISTA_JOB_GROUP;
+    use ballista_core::error::BallistaError;
+    use ballista_core::serde::protobuf::{
+        execute_query_params::{OptionalJobId, Query},
+        executor_registration::OptionalHost,
+        executor_status, CancelJobGroupParams, CancelJobParams, CleanJobDataParams,
+        CloseSessionParams, DecommissionExecutorParams, DrainParams, EstimateQueryParams,
+        ExecuteQueryParams, ExecutorRegistration, ExecutorStatus, ExecutorStoppedParams,
+        GetJobGroupStatusParams, GetJobStatusParams, GetTaskAttemptsParams,
+        HeartBeatParams, KeyValuePair, ListJobsParams, PollWorkParams,
+        RecoverExecutorDataParams, RegisterExecutorParams, RunningTaskInfo,
+        SubscribeJobEventsParams,
+    };
+    use ballista_core::serde::scheduler::ExecutorSpecification;
+    use ballista_core::serde::BallistaCodec;
+
+    use crate::scheduler_server::timestamp_millis;
+    use crate::state::executor_manager::DEFAULT_EXECUTOR_TIMEOUT_SECONDS;
+    use crate::state::SchedulerState;
+    use crate::test_utils::await_condition;
+    use crate::test_utils::{datafusion_test_context, test_cluster_context};
+    use ballista_core::config::BallistaConfig;
+    use ballista_core::execution_plans::GpuRequirementExec;
+    use datafusion::physical_plan::ExecutionPlan;
+
+    use super::{SchedulerGrpc, SchedulerServer};
 
     #[tokio::test]
     async fn test_poll_work() -> Result<(), BallistaError> {
@@ -597,12 +1847,20 @@ mod test {
             optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
             port: 0,
             grpc_port: 0,
-            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 2,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
         };
         let request: Request<PollWorkParams> = Request::new(PollWorkParams {
             metadata: Some(exec_meta.clone()),
             num_free_slots: 0,
             task_status: vec![],
+            running_tasks: vec![],
         });
         let response = scheduler
             .poll_work(request)
@@ -634,6 +1892,7 @@ mod test {
             metadata: Some(exec_meta.clone()),
             num_free_slots: 1,
             task_status: vec![],
+            running_tasks: vec![],
         });
         let response = scheduler
             .poll_work(request)
@@ -648,19 +1907,568 @@ mod test {
                 cluster.clone(),
                 BallistaCodec::default(),
             );
-        state.init().await?;
+        state.init().await?;
+
+        // executor should be registered
+        let stored_executor = state
+            .executor_manager
+            .get_executor_metadata("abc")
+            .await
+            .expect("getting executor");
+
+        assert_eq!(stored_executor.grpc_port, 0);
+        assert_eq!(stored_executor.port, 0);
+        assert_eq!(stored_executor.specification.task_slots, 2);
+        assert_eq!(stored_executor.host, "http://localhost:8080".to_owned());
+        assert_eq!(response.task_slots, 2);
+
+        // The oversubscription changed at runtime resizes the task slots of the executor
+        let mut oversubscribed = stored_executor;
+        oversubscribed
+            .specification
+            .task_slot_oversubscription_percent = 150;
+        state
+            .executor_manager
+            .save_executor_metadata(oversubscribed)
+            .await?;
+        let request: Request<PollWorkParams> = Request::new(PollWorkParams {
+            metadata: Some(exec_meta),
+            num_free_slots: 1,
+            task_status: vec![],
+            running_tasks: vec![],
+        });
+        let response = scheduler
+            .poll_work(request)
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert_eq!(response.task_slots, 3);
+        let stored_executor = state
+            .executor_manager
+            .get_executor_metadata("abc")
+            .await
+            .expect("getting executor");
+        assert_eq!(
+            stored_executor
+                .specification
+                .task_slot_oversubscription_percent,
+            150
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_poll_work_gpus() -> Result<(), BallistaError> {
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                SchedulerConfig::default(),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        // Each task scanning the table requires a GPU
+        fn gpu_scans(
+            plan: Arc<dyn ExecutionPlan>,
+        ) -> Result<Arc<dyn ExecutionPlan>, BallistaError> {
+            if plan.children().is_empty() {
+                return Ok(Arc::new(GpuRequirementExec::new(plan, 1)));
+            }
+            let children = plan
+                .children()
+                .into_iter()
+                .map(gpu_scans)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(plan.with_new_children(children)?)
+        }
+        let ctx = datafusion_test_context("testdata").await?;
+        let plan = ctx
+            .sql("select l_returnflag, count(*) from lineitem group by l_returnflag")
+            .await?
+            .create_physical_plan()
+            .await?;
+        let plan = gpu_scans(plan)?;
+
+        let session = scheduler
+            .state
+            .session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+        let task_manager = &scheduler.state.task_manager;
+        task_manager
+            .queue_job("job", "", timestamp_millis())
+            .await?;
+        task_manager
+            .submit_job("job", "", &session.session_id(), plan, 0)
+            .await?;
+
+        // An executor with more task slots than GPUs
+        let exec_meta = ExecutorRegistration {
+            id: "abc".to_owned(),
+            optional_host: Some(OptionalHost::Host("localhost".to_owned())),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 4,
+                    gpus: 1,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
+        };
+        let poll = |running_tasks: Vec<RunningTaskInfo>| {
+            scheduler.poll_work(Request::new(PollWorkParams {
+                metadata: Some(exec_meta.clone()),
+                num_free_slots: 4,
+                task_status: vec![],
+                running_tasks,
+            }))
+        };
+
+        // Only one of the scan tasks gets the only GPU
+        let tasks = poll(vec![])
+            .await
+            .expect("Received error response")
+            .into_inner()
+            .tasks;
+        assert_eq!(tasks.len(), 1);
+        let running = RunningTaskInfo {
+            task_id: tasks[0].task_id,
+            job_id: tasks[0].job_id.clone(),
+            stage_id: tasks[0].stage_id,
+            partition_id: 0,
+        };
+
+        // No other scan task is handed out while the first one runs
+        let tasks = poll(vec![running])
+            .await
+            .expect("Received error response")
+            .into_inner()
+            .tasks;
+        assert!(tasks.is_empty());
+
+        // The GPU is free again once the first scan task is no longer running
+        let tasks = poll(vec![])
+            .await
+            .expect("Received error response")
+            .into_inner()
+            .tasks;
+        assert_eq!(tasks.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_estimate_query() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default(),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let response = scheduler
+            .estimate_query(Request::new(EstimateQueryParams {
+                sql: "SELECT 1".to_owned(),
+                optional_session_id: None,
+                settings: vec![],
+            }))
+            .await
+            .expect("Received error response")
+            .into_inner();
+
+        assert_eq!(response.stage_count, 1);
+        assert!(response.task_count >= 1);
+        // no job completed yet to base the slot seconds on
+        assert_eq!(response.similar_jobs, 0);
+        assert_eq!(response.slot_seconds, 0.0);
+        assert!(!response.session_id.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_job_id() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default(),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let execute_query = |job_id: &str| {
+            Request::new(ExecuteQueryParams {
+                query: Some(Query::Sql("SELECT 1".to_owned())),
+                optional_session_id: None,
+                settings: vec![],
+                optional_job_id: Some(OptionalJobId::JobId(job_id.to_owned())),
+                job_settings: vec![],
+                idempotent: false,
+                run_as: String::new(),
+                reports_consumed_partitions: false,
+            })
+        };
+        let response = scheduler
+            .execute_query(execute_query("pipeline-42"))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert_eq!(response.job_id, "pipeline-42");
+
+        let status = scheduler
+            .execute_query(execute_query("pipeline-42"))
+            .await
+            .expect_err("Expected job IDs to be unique");
+        assert_eq!(status.code(), Code::AlreadyExists);
+
+        let status = scheduler
+            .execute_query(execute_query("../pipeline"))
+            .await
+            .expect_err("Expected invalid job IDs to be rejected");
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_job_submission() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default(),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let execute_query = |sql: &str| {
+            Request::new(ExecuteQueryParams {
+                query: Some(Query::Sql(sql.to_owned())),
+                optional_session_id: None,
+                settings: vec![],
+                optional_job_id: Some(OptionalJobId::JobId("nightly-7".to_owned())),
+                job_settings: vec![],
+                idempotent: true,
+                run_as: String::new(),
+                reports_consumed_partitions: false,
+            })
+        };
+        let first = scheduler
+            .execute_query(execute_query("SELECT 1"))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert_eq!(first.job_id, "nightly-7");
+
+        // the retry returns the job submitted first without planning the query again
+        let retry = scheduler
+            .execute_query(execute_query("SELECT * FROM missing_table"))
+            .await
+            .expect("Expected the existing job to be returned")
+            .into_inner();
+        assert_eq!(retry.job_id, "nightly-7");
+
+        let jobs = scheduler.state.task_manager.get_jobs().await?;
+        assert_eq!(jobs.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_drain() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default(),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let execute_query = || {
+            Request::new(ExecuteQueryParams {
+                query: Some(Query::Sql("SELECT 1".to_owned())),
+                optional_session_id: None,
+                settings: vec![],
+                optional_job_id: None,
+                job_settings: vec![],
+                idempotent: false,
+                run_as: String::new(),
+                reports_consumed_partitions: false,
+            })
+        };
+        scheduler
+            .execute_query(execute_query())
+            .await
+            .expect("Received error response");
+
+        // without executors the job cannot complete
+        let scheduler_ref = &scheduler;
+        let job_queued = await_condition(Duration::from_millis(10), 100, || async move {
+            Ok(scheduler_ref.unfinished_jobs() == 1)
+        })
+        .await?;
+        assert!(job_queued, "Expected the job to be unfinished");
+
+        let response = scheduler
+            .drain(Request::new(DrainParams { timeout_seconds: 1 }))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        assert_eq!(response.unfinished_jobs, 1);
+        assert!(scheduler.is_draining());
+
+        let status = scheduler
+            .execute_query(execute_query())
+            .await
+            .expect_err("Expected queries to be rejected while draining");
+        assert_eq!(status.code(), Code::Unavailable);
+
+        tokio::time::timeout(Duration::from_secs(5), scheduler.drained())
+            .await
+            .expect("Expected the scheduler to be drained after the timeout");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_drain_saves_held_jobs() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default()
+                    .with_max_concurrent_jobs(1)
+                    .with_plugin(Arc::new(GatewayPlugin)),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        // without executors the first job cannot complete and the second is held
+        let mut job_ids = vec![];
+        for _ in 0..2 {
+            let response = scheduler
+                .execute_query(Request::new(ExecuteQueryParams {
+                    query: Some(Query::Sql("SELECT 1".to_owned())),
+                    optional_session_id: None,
+                    settings: vec![],
+                    optional_job_id: None,
+                    job_settings: vec![],
+                    idempotent: false,
+                    run_as: String::new(),
+                    reports_consumed_partitions: false,
+                }))
+                .await
+                .expect("Received error response")
+                .into_inner();
+            job_ids.push(response.job_id);
+        }
+        let scheduler_ref = &scheduler;
+        let job_held = await_condition(Duration::from_millis(10), 100, || async move {
+            Ok(scheduler_ref.query_stage_scheduler.held_jobs() == 1)
+        })
+        .await?;
+        assert!(job_held, "Expected the second job to be held");
+
+        let drain = |user: &str| {
+            let mut request = Request::new(DrainParams { timeout_seconds: 1 });
+            request
+                .metadata_mut()
+                .insert("authorization", format!("Bearer {user}").parse().unwrap());
+            request
+        };
+        let status = scheduler
+            .drain(drain("bob"))
+            .await
+            .expect_err("Expected only admins to drain the scheduler");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert!(!scheduler.is_draining());
+
+        scheduler
+            .drain(drain("admin"))
+            .await
+            .expect("Expected admins to drain the scheduler");
+        tokio::time::timeout(Duration::from_secs(15), scheduler.drained())
+            .await
+            .expect("Expected the scheduler to be drained after the timeout");
+
+        // Both jobs are saved for the scheduler recovering them
+        for job_id in &job_ids {
+            assert!(
+                cluster
+                    .job_state()
+                    .get_execution_graph(job_id)
+                    .await?
+                    .is_some(),
+                "Expected job {job_id} to be saved"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_load_shedding() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default()
+                    .with_max_concurrent_jobs(1)
+                    .with_max_held_jobs(1)
+                    .with_overload_wait_ms(100),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let execute_query = || {
+            Request::new(ExecuteQueryParams {
+                query: Some(Query::Sql("SELECT 1".to_owned())),
+                optional_session_id: None,
+                settings: vec![],
+                optional_job_id: None,
+                job_settings: vec![],
+                idempotent: false,
+                run_as: String::new(),
+                reports_consumed_partitions: false,
+            })
+        };
+        // without executors the first job cannot complete and the others are held
+        for _ in 0..3 {
+            scheduler
+                .execute_query(execute_query())
+                .await
+                .expect("Received error response");
+        }
+        let scheduler_ref = &scheduler;
+        let jobs_held = await_condition(Duration::from_millis(10), 100, || async move {
+            Ok(scheduler_ref.unfinished_jobs() == 3)
+        })
+        .await?;
+        assert!(jobs_held, "Expected two jobs to be held");
+        assert!(scheduler.overloaded().is_some());
+
+        // Queries wait for the load to drop before they are rejected
+        let started = std::time::Instant::now();
+        let status = scheduler
+            .execute_query(execute_query())
+            .await
+            .expect_err("Expected queries to be rejected while overloaded");
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_groups() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default().with_max_concurrent_jobs(1),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
 
-        // executor should be registered
-        let stored_executor = state
-            .executor_manager
-            .get_executor_metadata("abc")
-            .await
-            .expect("getting executor");
+        let execute_query = |group_id: &str| {
+            Request::new(ExecuteQueryParams {
+                query: Some(Query::Sql("SELECT 1".to_owned())),
+                optional_session_id: None,
+                settings: vec![],
+                optional_job_id: None,
+                job_settings: vec![KeyValuePair {
+                    key: BALLISTA_JOB_GROUP.to_owned(),
+                    value: group_id.to_owned(),
+                }],
+                idempotent: false,
+                run_as: String::new(),
+                reports_consumed_partitions: false,
+            })
+        };
+        // without executors the first job cannot complete and the others are held
+        let mut job_ids = vec![];
+        for group_id in ["refresh", "refresh", "other"] {
+            let response = scheduler
+                .execute_query(execute_query(group_id))
+                .await
+                .expect("Received error response")
+                .into_inner();
+            job_ids.push(response.job_id);
+        }
 
-        assert_eq!(stored_executor.grpc_port, 0);
-        assert_eq!(stored_executor.port, 0);
-        assert_eq!(stored_executor.specification.task_slots, 2);
-        assert_eq!(stored_executor.host, "http://localhost:8080".to_owned());
+        let scheduler_ref = &scheduler;
+        let group_status = || async move {
+            scheduler_ref
+                .get_job_group_status(Request::new(GetJobGroupStatusParams {
+                    group_id: "refresh".to_owned(),
+                }))
+                .await
+                .expect("Received error response")
+                .into_inner()
+        };
+        let planned = await_condition(Duration::from_millis(10), 100, || async move {
+            Ok(group_status().await.jobs.len() == 2)
+        })
+        .await?;
+        assert!(
+            planned,
+            "Expected the planned and the held job of the group"
+        );
+        let status = group_status().await;
+        assert_eq!(status.queued_jobs + status.running_jobs, 2);
+        assert_eq!(status.successful_jobs + status.failed_jobs, 0);
+
+        let mut cancelled = scheduler
+            .cancel_job_group(Request::new(CancelJobGroupParams {
+                group_id: "refresh".to_owned(),
+            }))
+            .await
+            .expect("Received error response")
+            .into_inner()
+            .job_ids;
+        cancelled.sort();
+        let mut expected = job_ids[..2].to_vec();
+        expected.sort();
+        assert_eq!(cancelled, expected);
+
+        let status = scheduler
+            .get_job_group_status(Request::new(GetJobGroupStatusParams {
+                group_id: String::new(),
+            }))
+            .await
+            .expect_err("Expected the empty group ID to be rejected");
+        assert_eq!(status.code(), Code::InvalidArgument);
 
         Ok(())
     }
@@ -684,7 +2492,14 @@ mod test {
             optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
             port: 0,
             grpc_port: 0,
-            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 2,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
         };
 
         let request: Request<RegisterExecutorParams> =
@@ -753,6 +2568,173 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_decommission_executor() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default().with_plugin(Arc::new(GatewayPlugin)),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let exec_meta = ExecutorRegistration {
+            id: "abc".to_owned(),
+            optional_host: Some(OptionalHost::Host("localhost".to_owned())),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 2,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
+        };
+        scheduler
+            .register_executor(Request::new(RegisterExecutorParams {
+                metadata: Some(exec_meta),
+            }))
+            .await
+            .expect("Received error response");
+
+        let status = scheduler
+            .decommission_executor(Request::new(DecommissionExecutorParams {
+                executor_id: "unknown".to_owned(),
+                timeout_seconds: 0,
+            }))
+            .await
+            .expect_err("Expected unknown executors to be rejected");
+        assert_eq!(status.code(), Code::NotFound);
+
+        let mut request = Request::new(DecommissionExecutorParams {
+            executor_id: "abc".to_owned(),
+            timeout_seconds: 0,
+        });
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer bob".parse().unwrap());
+        let status = scheduler
+            .decommission_executor(request)
+            .await
+            .expect_err("Expected only admins to decommission executors");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert!(!scheduler.state.executor_manager.is_decommissioning("abc"));
+
+        let response = scheduler
+            .decommission_executor(Request::new(DecommissionExecutorParams {
+                executor_id: "abc".to_owned(),
+                timeout_seconds: 0,
+            }))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        // No job uses the executor, it is removed right away
+        assert_eq!(response.active_jobs, 0);
+
+        let state = scheduler.state.clone();
+        let is_removed = await_condition(Duration::from_millis(50), 100, || {
+            futures::future::ready(Ok(state.executor_manager.is_dead_executor("abc")))
+        })
+        .await?;
+        assert!(
+            is_removed,
+            "Expected the decommissioned executor to be removed"
+        );
+        assert!(!state.executor_manager.is_decommissioning("abc"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recover_executor_data() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default(),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let exec_meta = |id: &str| ExecutorRegistration {
+            id: id.to_owned(),
+            optional_host: Some(OptionalHost::Host("localhost".to_owned())),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 2,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
+        };
+        scheduler
+            .register_executor(Request::new(RegisterExecutorParams {
+                metadata: Some(exec_meta("abc")),
+            }))
+            .await
+            .expect("Received error response");
+
+        let response = scheduler
+            .recover_executor_data(Request::new(RecoverExecutorDataParams {
+                metadata: Some(exec_meta("def")),
+                previous_executor_id: "abc".to_owned(),
+                job_ids: vec!["unknown".to_owned()],
+                shuffle_files: vec!["/work/unknown/1/0/data.arrow".to_owned()],
+            }))
+            .await
+            .expect("Received error response")
+            .into_inner();
+        // The scheduler knows of no such job, its data is orphaned
+        assert_eq!(response.orphaned_job_ids, vec!["unknown".to_owned()]);
+
+        // Requests without the host or the specification of the executor are rejected
+        // rather than panicking
+        for metadata in [
+            ExecutorRegistration {
+                optional_host: None,
+                ..exec_meta("ghi")
+            },
+            ExecutorRegistration {
+                specification: None,
+                ..exec_meta("ghi")
+            },
+        ] {
+            let status = scheduler
+                .recover_executor_data(Request::new(RecoverExecutorDataParams {
+                    metadata: Some(metadata),
+                    previous_executor_id: String::new(),
+                    job_ids: vec!["unknown".to_owned()],
+                    shuffle_files: vec![],
+                }))
+                .await
+                .expect_err("Expected an invalid request");
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+
+        // The executor before the restart is removed without waiting for its heartbeats to
+        // expire
+        let state = scheduler.state.clone();
+        let is_removed = await_condition(Duration::from_millis(50), 100, || {
+            futures::future::ready(Ok(state.executor_manager.is_dead_executor("abc")))
+        })
+        .await?;
+        assert!(is_removed, "Expected the previous executor to be removed");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_register_executor_in_heartbeat_service() -> Result<(), BallistaError> {
         let cluster = test_cluster_context();
@@ -772,7 +2754,14 @@ mod test {
             optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
             port: 0,
             grpc_port: 0,
-            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 2,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
         };
 
         let request: Request<HeartBeatParams> = Request::new(HeartBeatParams {
@@ -782,6 +2771,9 @@ mod test {
                 status: Some(executor_status::Status::Active("".to_string())),
             }),
             metadata: Some(exec_meta.clone()),
+            task_status: vec![],
+            task_progress: vec![],
+            task_slots: 0,
         });
         scheduler
             .heart_beat_from_executor(request)
@@ -824,7 +2816,14 @@ mod test {
             optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
             port: 0,
             grpc_port: 0,
-            specification: Some(ExecutorSpecification { task_slots: 2 }.into()),
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 2,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
         };
 
         let request: Request<RegisterExecutorParams> =
@@ -861,6 +2860,9 @@ mod test {
                 status: Some(executor_status::Status::Active("".to_string())),
             }),
             metadata: Some(exec_meta.clone()),
+            task_status: vec![],
+            task_progress: vec![],
+            task_slots: 0,
         });
 
         let _response = scheduler
@@ -892,4 +2894,404 @@ mod test {
         assert!(active_executors.is_empty());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_resize_executor_in_heartbeat() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default(),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        let exec_meta = ExecutorRegistration {
+            id: "abc".to_owned(),
+            optional_host: Some(OptionalHost::Host("http://localhost:8080".to_owned())),
+            port: 0,
+            grpc_port: 0,
+            specification: Some(
+                ExecutorSpecification {
+                    task_slots: 2,
+                    ..Default::default()
+                }
+                .into(),
+            ),
+            pool: String::new(),
+        };
+        scheduler
+            .register_executor(Request::new(RegisterExecutorParams {
+                metadata: Some(exec_meta.clone()),
+            }))
+            .await
+            .expect("Received error response");
+
+        let heartbeat = |task_slots| {
+            Request::new(HeartBeatParams {
+                executor_id: "abc".to_owned(),
+                metrics: vec![],
+                status: Some(ExecutorStatus {
+                    status: Some(executor_status::Status::Active("".to_string())),
+                }),
+                metadata: Some(exec_meta.clone()),
+                task_status: vec![],
+                task_progress: vec![],
+                task_slots,
+            })
+        };
+        let state = scheduler.state.clone();
+        let task_slots = || {
+            let state = state.clone();
+            async move {
+                state
+                    .executor_manager
+                    .get_executor_metadata("abc")
+                    .await
+                    .expect("getting executor")
+                    .specification
+                    .task_slots
+            }
+        };
+
+        // The executor advertises the task slots it registered with
+        scheduler
+            .heart_beat_from_executor(heartbeat(0))
+            .await
+            .expect("Received error response");
+        assert_eq!(task_slots().await, 2);
+
+        scheduler
+            .heart_beat_from_executor(heartbeat(4))
+            .await
+            .expect("Received error response");
+        assert_eq!(task_slots().await, 4);
+
+        scheduler
+            .heart_beat_from_executor(heartbeat(1))
+            .await
+            .expect("Received error response");
+        assert_eq!(task_slots().await, 1);
+        Ok(())
+    }
+
+    /// Lets the gateway run queries on behalf of other users
+    struct GatewayPlugin;
+
+    impl SchedulerPlugin for GatewayPlugin {
+        fn name(&self) -> &str {
+            "gateway"
+        }
+
+        fn authenticate(
+            &self,
+            metadata: &MetadataMap,
+        ) -> ballista_core::error::Result<Option<String>> {
+            Ok(metadata
+                .get("authorization")
+                .and_then(|token| token.to_str().ok())
+                .and_then(|token| token.strip_prefix("Bearer "))
+                .map(str::to_owned))
+        }
+
+        fn impersonate(
+            &self,
+            user: Option<&str>,
+            _run_as: &str,
+        ) -> ballista_core::error::Result<bool> {
+            Ok(user == Some("gateway"))
+        }
+
+        fn authorize_admin(
+            &self,
+            user: Option<&str>,
+            action: AdminAction,
+        ) -> ballista_core::error::Result<()> {
+            if user == Some("admin") {
+                Ok(())
+            } else {
+                Err(BallistaError::General(format!("Only admins may {action}")))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_impersonate() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default().with_plugin(Arc::new(GatewayPlugin)),
+                default_metrics_collector().unwrap(),
+            );
+
+        // Clients not impersonating anyone run queries as themselves
+        let user = scheduler
+            .impersonate(Some("alice".to_owned()), "")
+            .expect("Received error response");
+        assert_eq!(user.as_deref(), Some("alice"));
+
+        let user = scheduler
+            .impersonate(Some("gateway".to_owned()), "alice")
+            .expect("Expected the gateway to impersonate alice");
+        assert_eq!(user.as_deref(), Some("alice"));
+
+        let status = scheduler
+            .impersonate(Some("bob".to_owned()), "alice")
+            .expect_err("Expected bob not to impersonate alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = scheduler
+            .impersonate(None, "alice")
+            .expect_err("Expected anonymous clients not to impersonate alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_session() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default().with_plugin(Arc::new(GatewayPlugin)),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        fn request<T>(user: &str, params: T) -> Request<T> {
+            let mut request = Request::new(params);
+            request
+                .metadata_mut()
+                .insert("authorization", format!("Bearer {user}").parse().unwrap());
+            request
+        }
+
+        let session_id = scheduler
+            .execute_query(request(
+                "alice",
+                ExecuteQueryParams {
+                    query: None,
+                    optional_session_id: None,
+                    settings: vec![],
+                    optional_job_id: None,
+                    job_settings: vec![],
+                    idempotent: false,
+                    run_as: String::new(),
+                    reports_consumed_partitions: false,
+                },
+            ))
+            .await
+            .expect("Received error response")
+            .into_inner()
+            .session_id;
+        let close_session = |user: &str| {
+            request(
+                user,
+                CloseSessionParams {
+                    session_id: session_id.clone(),
+                },
+            )
+        };
+
+        let status = scheduler
+            .close_session(close_session("bob"))
+            .await
+            .expect_err("Expected bob not to close the session of alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        let closed = scheduler
+            .close_session(close_session("alice"))
+            .await
+            .expect("Expected alice to close her session")
+            .into_inner()
+            .closed;
+        assert!(closed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_access() -> Result<(), BallistaError> {
+        let cluster = test_cluster_context();
+
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                cluster.clone(),
+                BallistaCodec::default(),
+                SchedulerConfig::default().with_plugin(Arc::new(GatewayPlugin)),
+                default_metrics_collector().unwrap(),
+            );
+        scheduler.init().await?;
+
+        fn request<T>(user: &str, params: T) -> Request<T> {
+            let mut request = Request::new(params);
+            request
+                .metadata_mut()
+                .insert("authorization", format!("Bearer {user}").parse().unwrap());
+            request
+        }
+
+        let job_id = scheduler
+            .execute_query(request(
+                "alice",
+                ExecuteQueryParams {
+                    query: Some(Query::Sql("SELECT 1".to_owned())),
+                    optional_session_id: None,
+                    settings: vec![],
+                    optional_job_id: None,
+                    job_settings: vec![KeyValuePair {
+                        key: BALLISTA_JOB_GROUP.to_owned(),
+                        value: "reports".to_owned(),
+                    }],
+                    idempotent: false,
+                    run_as: String::new(),
+                    reports_consumed_partitions: false,
+                },
+            ))
+            .await
+            .expect("Received error response")
+            .into_inner()
+            .job_id;
+
+        // The owner of the job is known once it is planned
+        let task_manager = &scheduler.state.task_manager;
+        let job_id_ref = &job_id;
+        let planned = await_condition(Duration::from_millis(10), 100, || async move {
+            Ok(task_manager.job_owner(job_id_ref).await?.is_some())
+        })
+        .await?;
+        assert!(planned, "Expected the job to be planned");
+
+        let status = scheduler
+            .get_job_status(request(
+                "alice",
+                GetJobStatusParams {
+                    job_id: job_id.clone(),
+                    consumed_partitions: vec![],
+                },
+            ))
+            .await
+            .expect("Expected alice to get the status of her job")
+            .into_inner()
+            .status;
+        assert!(status.is_some());
+        // The gateway may run queries as alice, so it may access her jobs
+        scheduler
+            .get_task_attempts(request(
+                "gateway",
+                GetTaskAttemptsParams {
+                    job_id: job_id.clone(),
+                    stage_id: 0,
+                },
+            ))
+            .await
+            .expect("Expected the gateway to access the jobs of alice");
+
+        let status = scheduler
+            .get_job_status(request(
+                "bob",
+                GetJobStatusParams {
+                    job_id: job_id.clone(),
+                    consumed_partitions: vec![],
+                },
+            ))
+            .await
+            .expect_err("Expected bob not to get the status of the job of alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = scheduler
+            .subscribe_job_events(request(
+                "bob",
+                SubscribeJobEventsParams {
+                    job_id: job_id.clone(),
+                },
+            ))
+            .await
+            .err()
+            .expect("Expected bob not to subscribe to the events of the job of alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = scheduler
+            .get_job_group_status(request(
+                "bob",
+                GetJobGroupStatusParams {
+                    group_id: "reports".to_owned(),
+                },
+            ))
+            .await
+            .expect_err("Expected bob not to get the status of the jobs of alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = scheduler
+            .cancel_job_group(request(
+                "bob",
+                CancelJobGroupParams {
+                    group_id: "reports".to_owned(),
+                },
+            ))
+            .await
+            .expect_err("Expected bob not to cancel the jobs of alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = scheduler
+            .clean_job_data(request(
+                "bob",
+                CleanJobDataParams {
+                    job_id: job_id.clone(),
+                },
+            ))
+            .await
+            .expect_err("Expected bob not to clean the data of the job of alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = scheduler
+            .cancel_job(request(
+                "bob",
+                CancelJobParams {
+                    job_id: job_id.clone(),
+                },
+            ))
+            .await
+            .expect_err("Expected bob not to cancel the job of alice");
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        // Clients only list the jobs they may access
+        let list_jobs = |user: &'static str| {
+            let scheduler = &scheduler;
+            async move {
+                scheduler
+                    .list_jobs(request(user, ListJobsParams::default()))
+                    .await
+                    .expect("Received error response")
+                    .into_inner()
+                    .jobs
+                    .into_iter()
+                    .map(|job| job.job_id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(list_jobs("alice").await, vec![job_id.clone()]);
+        assert!(list_jobs("bob").await.is_empty());
+
+        let cancelled = scheduler
+            .cancel_job(request(
+                "alice",
+                CancelJobParams {
+                    job_id: job_id.clone(),
+                },
+            ))
+            .await
+            .expect("Expected alice to cancel her job")
+            .into_inner()
+            .cancelled;
+        assert!(cancelled);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,247 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Publishing of the progress of jobs to the clients subscribed to them
+
+use std::collections::HashMap;
+
+use dashmap::DashMap;
+use futures::Stream;
+use log::warn;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+
+use ballista_core::serde::protobuf::{
+    job_event, JobEvent, JobStatus, StageCompletedEvent, StageStartedEvent,
+    TaskFailedEvent,
+};
+
+use crate::scheduler_server::timestamp_millis;
+
+/// Number of events buffered for each subscribed job. Subscribers lagging further behind miss
+/// the oldest events.
+pub const DEFAULT_JOB_EVENT_BUFFER_SIZE: usize = 1024;
+
+/// Progress of a stage reported to subscribers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageProgress {
+    Running,
+    Successful,
+}
+
+struct JobSubscription {
+    sender: broadcast::Sender<JobEvent>,
+    /// Progress of the stages last published to the subscribers
+    stages: HashMap<usize, StageProgress>,
+}
+
+/// Broadcasts the events of jobs to their subscribers. Events of jobs nobody subscribed to are
+/// dropped without being built.
+pub struct JobEventBus {
+    buffer_size: usize,
+    jobs: DashMap<String, JobSubscription>,
+}
+
+impl Default for JobEventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_JOB_EVENT_BUFFER_SIZE)
+    }
+}
+
+impl JobEventBus {
+    pub fn new(buffer_size: usize) -> Self {
+        Self {
+            buffer_size,
+            jobs: DashMap::new(),
+        }
+    }
+
+    /// Subscribe to the events of a job. The stream ends after the job completed event.
+    pub fn subscribe(
+        &self,
+        job_id: &str,
+    ) -> impl Stream<Item = JobEvent> + Send + 'static {
+        let receiver = self
+            .jobs
+            .entry(job_id.to_owned())
+            .or_insert_with(|| JobSubscription {
+                sender: broadcast::channel(self.buffer_size).0,
+                stages: HashMap::new(),
+            })
+            .sender
+            .subscribe();
+
+        futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Job event subscriber lagged behind by {missed} events");
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Whether anybody subscribed to the events of the job
+    pub fn is_subscribed(&self, job_id: &str) -> bool {
+        self.jobs.contains_key(job_id)
+    }
+
+    /// Publish the stages whose progress changed since it was last published
+    pub fn publish_stage_progress(
+        &self,
+        job_id: &str,
+        stages: impl IntoIterator<Item = (usize, StageProgress, usize)>,
+    ) {
+        if let Some(mut subscription) = self.jobs.get_mut(job_id) {
+            for (stage_id, progress, partitions) in stages {
+                if subscription.stages.insert(stage_id, progress) == Some(progress) {
+                    continue;
+                }
+                let event = match progress {
+                    StageProgress::Running => {
+                        job_event::Event::StageStarted(StageStartedEvent {
+                            stage_id: stage_id as u32,
+                            partitions: partitions as u32,
+                        })
+                    }
+                    StageProgress::Successful => {
+                        job_event::Event::StageCompleted(StageCompletedEvent {
+                            stage_id: stage_id as u32,
+                        })
+                    }
+                };
+                send(&subscription.sender, job_id, event);
+            }
+        }
+    }
+
+    /// Publish the failure of a task of the job
+    pub fn publish_task_failed(&self, job_id: &str, event: TaskFailedEvent) {
+        if let Some(subscription) = self.jobs.get(job_id) {
+            send(
+                &subscription.sender,
+                job_id,
+                job_event::Event::TaskFailed(event),
+            );
+        }
+    }
+
+    /// Publish the final status of the job and end its subscriptions
+    pub fn publish_job_completed(&self, job_id: &str, status: JobStatus) {
+        if let Some((_, subscription)) = self.jobs.remove(job_id) {
+            send(
+                &subscription.sender,
+                job_id,
+                job_event::Event::JobCompleted(status),
+            );
+        }
+    }
+}
+
+fn send(sender: &broadcast::Sender<JobEvent>, job_id: &str, event: job_event::Event) {
+    // Fails only if all subscribers went away, which is fine
+    let _ = sender.send(JobEvent {
+        job_id: job_id.to_owned(),
+        timestamp: timestamp_millis(),
+        event: Some(event),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::{job_status, SuccessfulJob};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_job_events() {
+        let bus = JobEventBus::default();
+        assert!(!bus.is_subscribed("job"));
+        // events of jobs without subscribers are dropped
+        bus.publish_stage_progress("job", vec![(1, StageProgress::Running, 2)]);
+
+        let events = bus.subscribe("job");
+        assert!(bus.is_subscribed("job"));
+
+        bus.publish_stage_progress(
+            "job",
+            vec![
+                (1, StageProgress::Running, 2),
+                (2, StageProgress::Running, 4),
+            ],
+        );
+        // unchanged stages are not published again
+        bus.publish_stage_progress(
+            "job",
+            vec![
+                (1, StageProgress::Successful, 2),
+                (2, StageProgress::Running, 4),
+            ],
+        );
+        bus.publish_task_failed(
+            "job",
+            TaskFailedEvent {
+                stage_id: 2,
+                partition_id: 3,
+                task_id: 7,
+                executor_id: "executor".to_owned(),
+                error: "error".to_owned(),
+            },
+        );
+        bus.publish_job_completed(
+            "job",
+            JobStatus {
+                job_id: "job".to_owned(),
+                job_name: "".to_owned(),
+                status: Some(job_status::Status::Successful(SuccessfulJob::default())),
+            },
+        );
+        assert!(!bus.is_subscribed("job"));
+
+        let events: Vec<job_event::Event> = events
+            .filter_map(|event| async { event.event })
+            .collect()
+            .await;
+        assert_eq!(events.len(), 5);
+        assert!(matches!(
+            events[0],
+            job_event::Event::StageStarted(StageStartedEvent {
+                stage_id: 1,
+                partitions: 2
+            })
+        ));
+        assert!(matches!(
+            events[1],
+            job_event::Event::StageStarted(StageStartedEvent {
+                stage_id: 2,
+                partitions: 4
+            })
+        ));
+        assert!(matches!(
+            events[2],
+            job_event::Event::StageCompleted(StageCompletedEvent { stage_id: 1 })
+        ));
+        assert!(matches!(
+            &events[3],
+            job_event::Event::TaskFailed(TaskFailedEvent { task_id: 7, .. })
+        ));
+        assert!(matches!(events[4], job_event::Event::JobCompleted(_)));
+    }
+}
@@ -15,12 +15,18 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use ballista_core::config::BALLISTA_JOB_POOL;
 use ballista_core::error::Result;
 use ballista_core::event_loop::{EventLoop, EventSender};
-use ballista_core::serde::protobuf::{StopExecutorParams, TaskStatus};
+use ballista_core::serde::protobuf::{
+    job_status, ExecutorHeartbeat, GetJobGroupStatusResult, HeldJob, JobStatus,
+    JobSummary, RunningJob, StopExecutorParams, TaskStatus,
+};
 use ballista_core::serde::BallistaCodec;
 
 use datafusion::execution::context::SessionState;
@@ -32,17 +38,26 @@ use datafusion_proto::physical_plan::AsExecutionPlan;
 use crate::cluster::BallistaCluster;
 use crate::config::SchedulerConfig;
 use crate::metrics::SchedulerMetricsCollector;
-use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
-use log::{error, warn};
+use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata, TaskResources};
+use log::{debug, error, info, warn};
+use tokio::sync::Notify;
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::query_stage_scheduler::QueryStageScheduler;
+use crate::scheduler_server::result_pages::{
+    FlightResultPartitionReader, ResultPartitionReader,
+};
 
 use crate::state::executor_manager::{
     ExecutorManager, ExecutorReservation, DEFAULT_EXECUTOR_TIMEOUT_SECONDS,
-    EXPIRE_DEAD_EXECUTOR_INTERVAL_SECS,
+    EXPIRE_DEAD_EXECUTOR_INTERVAL_SECS, EXPIRE_RESERVATION_LEASES_INTERVAL_SECS,
 };
 
+use crate::state::scheduler_peers::SCHEDULER_HEARTBEAT_INTERVAL_SECS;
+use crate::state::session_manager::{
+    create_job_context, job_ballista_config, job_settings, session_ballista_config,
+    DROPPED_TABLES_PURGE_INTERVAL_SECS, SESSION_CLEAN_UP_INTERVAL_SECS,
+};
 use crate::state::task_manager::TaskLauncher;
 use crate::state::SchedulerState;
 
@@ -55,9 +70,22 @@ pub mod externalscaler {
 pub mod event;
 mod external_scaler;
 mod grpc;
+pub mod job_events;
 pub(crate) mod query_stage_scheduler;
+pub mod result_pages;
+pub(crate) mod shared_queries;
+
+/// Function building the DataFusion state of a session from its configuration, such as
+/// [ballista_core::utils::default_session_builder]
+pub type SessionBuilder = fn(SessionConfig) -> SessionState;
+
+/// Log target of the audit records, such as the clients running queries on behalf of other
+/// users, so that they can be routed to a dedicated sink
+pub const AUDIT_LOG_TARGET: &str = "ballista_scheduler::audit";
 
-pub(crate) type SessionBuilder = fn(SessionConfig) -> SessionState;
+/// Time a drained scheduler waits for the jobs being planned to be submitted, and so saved,
+/// after the drain timeout
+const DRAIN_PLANNING_TIMEOUT_MS: u64 = 10_000;
 
 #[derive(Clone)]
 pub struct SchedulerServer<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> {
@@ -67,6 +95,9 @@ pub struct SchedulerServer<T: 'static + AsLogicalPlan, U: 'static + AsExecutionP
     pub(crate) query_stage_event_loop: EventLoop<QueryStageSchedulerEvent>,
     query_stage_scheduler: Arc<QueryStageScheduler<T, U>>,
     executor_termination_grace_period: u64,
+    result_partition_reader: Arc<dyn ResultPartitionReader>,
+    draining: Arc<AtomicBool>,
+    drained: Arc<Notify>,
 }
 
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T, U> {
@@ -88,6 +119,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             metrics_collector,
             config.job_resubmit_interval_ms,
             config.scheduler_event_expected_processing_duration,
+            config.max_concurrent_jobs,
         ));
         let query_stage_event_loop = EventLoop::new(
             "query_stage".to_owned(),
@@ -102,6 +134,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             query_stage_event_loop,
             query_stage_scheduler,
             executor_termination_grace_period: config.executor_termination_grace_period,
+            result_partition_reader: Arc::new(FlightResultPartitionReader::default()),
+            draining: Arc::new(AtomicBool::new(false)),
+            drained: Arc::new(Notify::new()),
         }
     }
 
@@ -126,6 +161,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             metrics_collector,
             config.job_resubmit_interval_ms,
             config.scheduler_event_expected_processing_duration,
+            config.max_concurrent_jobs,
         ));
         let query_stage_event_loop = EventLoop::new(
             "query_stage".to_owned(),
@@ -140,22 +176,342 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             query_stage_event_loop,
             query_stage_scheduler,
             executor_termination_grace_period: config.executor_termination_grace_period,
+            result_partition_reader: Arc::new(FlightResultPartitionReader::default()),
+            draining: Arc::new(AtomicBool::new(false)),
+            drained: Arc::new(Notify::new()),
         }
     }
 
+    /// Read the result partitions of jobs served in pages with the given reader instead of
+    /// fetching them from the executors
+    pub fn with_result_partition_reader(
+        mut self,
+        reader: Arc<dyn ResultPartitionReader>,
+    ) -> Self {
+        self.result_partition_reader = reader;
+        self
+    }
+
     pub async fn init(&mut self) -> Result<()> {
-        self.state.init().await?;
+        let recovered_jobs = self.state.init().await?;
         self.query_stage_event_loop.start()?;
+        let sender = self.query_stage_event_loop.get_sender()?;
+        for job_id in recovered_jobs {
+            sender
+                .post_event(QueryStageSchedulerEvent::JobRecovered(job_id))
+                .await?;
+        }
+        self.recover_held_jobs(&sender).await?;
         self.expire_dead_executors()?;
+        self.resume_decommissioning().await?;
+        if self.state.scheduler_peers.is_job_sharding() {
+            self.state.scheduler_peers.heartbeat().await?;
+            self.send_scheduler_heartbeats();
+        }
+        if self.state.config.session_idle_timeout_seconds > 0 {
+            self.remove_idle_sessions();
+        }
+        self.purge_dropped_tables();
+        if self.state.config.reservation_lease().is_some() {
+            self.expire_reservation_leases();
+        }
 
         Ok(())
     }
 
+    /// Queue again the jobs held in the queue by this scheduler before it restarted, in the
+    /// order they were queued in. The jobs whose plan cannot be decoded anymore fail.
+    async fn recover_held_jobs(
+        &self,
+        sender: &EventSender<QueryStageSchedulerEvent>,
+    ) -> Result<()> {
+        for job in self.state.task_manager.recover_held_jobs().await? {
+            let job_id = job.job_id.clone();
+            match self.decode_held_job(&job).await {
+                Ok((session_ctx, plan)) => {
+                    info!("Recovered held job {job_id}");
+                    sender
+                        .post_event(QueryStageSchedulerEvent::JobQueued {
+                            job_id,
+                            job_name: job.job_name,
+                            session_ctx,
+                            plan: Box::new(plan),
+                            queued_at: job.queued_at,
+                        })
+                        .await?;
+                }
+                Err(e) => {
+                    let fail_message =
+                        format!("Error recovering held job {job_id}: {e:?}");
+                    error!("{}", &fail_message);
+                    self.state
+                        .task_manager
+                        .fail_unscheduled_job(&job_id, fail_message)
+                        .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode the plan of a held job in a context planning the job with its settings
+    async fn decode_held_job(
+        &self,
+        job: &HeldJob,
+    ) -> Result<(Arc<SessionContext>, LogicalPlan)> {
+        let session = self
+            .state
+            .session_manager
+            .get_session_with_objects(&job.session_id)
+            .await?;
+        let settings = job
+            .job_settings
+            .iter()
+            .map(|kv| (kv.key.clone(), kv.value.clone()))
+            .collect();
+        let session_ctx = create_job_context(&session, settings)?;
+        let plan = T::try_decode(&job.logical_plan)?.try_into_logical_plan(
+            session_ctx.as_ref(),
+            self.state.codec.logical_extension_codec(),
+        )?;
+        Ok((session_ctx, plan))
+    }
+
     #[cfg(test)]
     pub(crate) fn query_stage_scheduler(&self) -> Arc<QueryStageScheduler<T, U>> {
         self.query_stage_scheduler.clone()
     }
 
+    /// Whether the scheduler is draining and no longer accepts new queries
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Why new queries are rejected, if the internal queues of the scheduler exceed their
+    /// thresholds. Rejecting queries while the event loop is behind leaves it to process task
+    /// status updates, and keeps it from delaying heartbeats.
+    pub fn overloaded(&self) -> Option<String> {
+        let config = &self.state.config;
+        if let Ok(sender) = self.query_stage_event_loop.get_sender() {
+            let queued_events = sender.queued_events();
+            if config.max_queued_events > 0 && queued_events > config.max_queued_events {
+                return Some(format!(
+                    "{queued_events} events are queued for the event loop"
+                ));
+            }
+            if config.max_event_loop_lag_ms > 0 {
+                let lag = self.query_stage_scheduler.event_loop_lag(queued_events);
+                if lag > Duration::from_millis(config.max_event_loop_lag_ms) {
+                    return Some(format!(
+                        "the event loop is {}ms behind",
+                        lag.as_millis()
+                    ));
+                }
+            }
+        }
+        if config.max_held_jobs > 0 {
+            let held_jobs = self.query_stage_scheduler.held_jobs();
+            if held_jobs > config.max_held_jobs {
+                return Some(format!("{held_jobs} jobs are held in the queue"));
+            }
+        }
+        None
+    }
+
+    /// Wait up to `overload_wait_ms` for the scheduler to no longer be overloaded. Returns
+    /// why a new query is rejected if it still is, see [Self::overloaded].
+    pub async fn await_capacity(&self) -> Option<String> {
+        let clock = &self.state.config.clock;
+        let deadline = clock.now_millis() + self.state.config.overload_wait_ms;
+        loop {
+            let reason = self.overloaded()?;
+            let now = clock.now_millis();
+            if now >= deadline {
+                return Some(reason);
+            }
+            clock
+                .sleep(Duration::from_millis((deadline - now).min(50)))
+                .await;
+        }
+    }
+
+    /// Number of submitted jobs which have not completed yet
+    pub fn unfinished_jobs(&self) -> usize {
+        self.query_stage_scheduler.unfinished_jobs()
+    }
+
+    /// Stop accepting new queries and wait up to `timeout` for the unfinished jobs to
+    /// complete. The execution graphs of the jobs still running, held or being planned are
+    /// then saved to the backend and the scheduler is signalled to shut down. Returns
+    /// immediately if the scheduler is already draining.
+    pub async fn drain_jobs(&self, timeout: Duration) -> Result<()> {
+        if self.draining.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        info!(
+            "Draining scheduler {} with {} unfinished jobs, {} of them held in the queue",
+            self.scheduler_name,
+            self.unfinished_jobs(),
+            self.query_stage_scheduler.held_jobs()
+        );
+
+        let clock = &self.state.config.clock;
+        let deadline = clock.now_millis() + timeout.as_millis() as u64;
+        while self.unfinished_jobs() > 0 && clock.now_millis() < deadline {
+            clock.sleep(Duration::from_millis(100)).await;
+        }
+
+        let unfinished_jobs = self.unfinished_jobs();
+        let result = if unfinished_jobs > 0 {
+            warn!(
+                "Timed out draining scheduler {} with {} unfinished jobs",
+                self.scheduler_name, unfinished_jobs
+            );
+            // Held jobs are only known to this scheduler, so they are submitted to be saved
+            // with the running jobs, and the jobs being planned are saved once submitted
+            let submitted = self.query_stage_scheduler.submit_held_jobs().await;
+            if submitted > 0 {
+                info!("Submitted {submitted} held jobs to save them");
+            }
+            let deadline = clock.now_millis() + DRAIN_PLANNING_TIMEOUT_MS;
+            while self.query_stage_scheduler.planning_jobs() > 0
+                && clock.now_millis() < deadline
+            {
+                clock.sleep(Duration::from_millis(100)).await;
+            }
+            self.state
+                .task_manager
+                .save_active_jobs()
+                .await
+                .map(|saved| {
+                    info!("Saved the state of {saved} running jobs");
+                })
+        } else {
+            info!("Scheduler {} drained", self.scheduler_name);
+            Ok(())
+        };
+        self.drained.notify_one();
+        result
+    }
+
+    /// Completes once the scheduler has been drained and can shut down
+    pub async fn drained(&self) {
+        self.drained.notified().await
+    }
+
+    /// Decommission an executor. Its task slots are no longer offered to jobs, and once the
+    /// active jobs running tasks on it or reading its shuffle data completed, or `timeout`
+    /// elapsed, the executor is stopped and removed from the cluster. Returns the number of
+    /// active jobs the executor is waiting for.
+    pub async fn decommission(
+        &self,
+        executor_id: &str,
+        timeout: Option<Duration>,
+    ) -> Result<usize> {
+        let executor_manager = &self.state.executor_manager;
+        // Fails if the executor does not exist
+        executor_manager.get_executor_metadata(executor_id).await?;
+
+        let jobs = self
+            .state
+            .task_manager
+            .executor_jobs(executor_id)
+            .await
+            .len();
+        // The deadline is persisted as seconds since the epoch, 0 standing for no deadline
+        let deadline = timeout
+            .map(|timeout| self.state.config.clock.now_secs() + timeout.as_secs().max(1))
+            .unwrap_or_default();
+        if executor_manager
+            .decommission_executor(executor_id, deadline)
+            .await?
+        {
+            info!("Decommissioning executor {executor_id} used by {jobs} active jobs");
+            self.spawn_remove_decommissioned_executor(executor_id.to_owned(), deadline);
+        }
+        Ok(jobs)
+    }
+
+    /// Resume removing the executors which were being decommissioned when the scheduler
+    /// stopped, with their persisted deadlines
+    async fn resume_decommissioning(&self) -> Result<()> {
+        let decommissioning = self
+            .state
+            .executor_manager
+            .refresh_decommissioning()
+            .await?;
+        for (executor_id, deadline) in decommissioning {
+            info!("Resuming the decommissioning of executor {executor_id}");
+            self.spawn_remove_decommissioned_executor(executor_id, deadline);
+        }
+        Ok(())
+    }
+
+    fn spawn_remove_decommissioned_executor(&self, executor_id: String, deadline: u64) {
+        let scheduler_server = self.clone();
+        tokio::spawn(async move {
+            scheduler_server
+                .remove_decommissioned_executor(&executor_id, deadline)
+                .await
+        });
+    }
+
+    /// Wait for the active jobs to no longer use a decommissioned executor, then stop it and
+    /// remove it. `deadline` is in seconds since the epoch, 0 for none.
+    async fn remove_decommissioned_executor(&self, executor_id: &str, deadline: u64) {
+        let executor_manager = self.state.executor_manager.clone();
+        let clock = &self.state.config.clock;
+        loop {
+            // The executor was lost or stopped in the meantime
+            if !executor_manager.is_decommissioning(executor_id) {
+                return;
+            }
+            let jobs = self.state.task_manager.executor_jobs(executor_id).await;
+            if jobs.is_empty() {
+                break;
+            }
+            if deadline > 0 && clock.now_secs() >= deadline {
+                warn!(
+                    "Timed out decommissioning executor {executor_id} still used by jobs {jobs:?}"
+                );
+                break;
+            }
+            clock.sleep(Duration::from_secs(1)).await;
+        }
+
+        let reason = "executor decommissioned".to_owned();
+        match executor_manager.get_client(executor_id).await {
+            Ok(mut client) => {
+                if let Err(e) = client
+                    .stop_executor(StopExecutorParams {
+                        executor_id: executor_id.to_owned(),
+                        reason: reason.clone(),
+                        force: false,
+                    })
+                    .await
+                {
+                    warn!("Failed to stop decommissioned executor {executor_id}: {e:?}");
+                }
+            }
+            Err(e) => {
+                warn!("Failed to connect to decommissioned executor {executor_id}: {e:?}")
+            }
+        }
+
+        match self.query_stage_event_loop.get_sender() {
+            Ok(event_sender) => Self::remove_executor(
+                executor_manager,
+                event_sender,
+                executor_id,
+                Some(reason),
+                0,
+            ),
+            Err(e) => {
+                error!("Error removing decommissioned executor {executor_id}: {e:?}")
+            }
+        }
+    }
+
     pub(crate) fn pending_tasks(&self) -> usize {
         self.query_stage_scheduler.pending_tasks()
     }
@@ -164,6 +520,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         self.query_stage_scheduler.metrics_collector()
     }
 
+    /// Queue a job, which fails if a job with the same ID exists already, and send it to the
+    /// event loop to be planned
     pub(crate) async fn submit_job(
         &self,
         job_id: &str,
@@ -171,6 +529,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         ctx: Arc<SessionContext>,
         plan: &LogicalPlan,
     ) -> Result<()> {
+        let queued_at = timestamp_millis();
+        self.state
+            .task_manager
+            .queue_job(job_id, job_name, queued_at)
+            .await?;
         self.query_stage_event_loop
             .get_sender()?
             .post_event(QueryStageSchedulerEvent::JobQueued {
@@ -178,11 +541,132 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
                 job_name: job_name.to_owned(),
                 session_ctx: ctx,
                 plan: Box::new(plan.clone()),
-                queued_at: timestamp_millis(),
+                queued_at,
             })
             .await
     }
 
+    /// Route a job which does not select a pool of executors to the pool of the first
+    /// routing rule it matches, by its labels or its SQL text. Returns the context planning
+    /// the job, with the pool in its job settings if the job was routed.
+    pub(crate) fn route_job(
+        &self,
+        job_id: &str,
+        ctx: Arc<SessionContext>,
+        sql: Option<&str>,
+    ) -> Result<Arc<SessionContext>> {
+        let pool_routing = &self.state.config.pool_routing;
+        if pool_routing.is_empty() {
+            return Ok(ctx);
+        }
+        let mut settings = job_settings(&ctx);
+        let config =
+            job_ballista_config(session_ballista_config(&ctx).as_deref(), &settings)?;
+        match pool_routing.route(&config, sql) {
+            Some(pool) => {
+                info!("Routing job {job_id} to executor pool {pool:?}");
+                settings.insert(BALLISTA_JOB_POOL.to_owned(), pool.to_owned());
+                create_job_context(&ctx, settings)
+            }
+            None => Ok(ctx),
+        }
+    }
+
+    /// Status of the jobs of a group, including the queued jobs held back by the maximum
+    /// number of concurrent jobs
+    pub(crate) async fn job_group_status(
+        &self,
+        group_id: &str,
+    ) -> Result<GetJobGroupStatusResult> {
+        let task_manager = &self.state.task_manager;
+        let mut result = GetJobGroupStatusResult::default();
+        for job in task_manager.get_job_group(group_id).await? {
+            result.num_stages += job.num_stages as u32;
+            result.completed_stages += job.completed_stages as u32;
+            result.jobs.push(JobSummary {
+                job_id: job.job_id,
+                job_name: job.job_name,
+                status: Some(job.status),
+                labels: job.labels,
+            });
+        }
+        for job_id in self.query_stage_scheduler.held_jobs_in_group(group_id) {
+            if let Some(status) = task_manager.get_job_status(&job_id).await? {
+                result.jobs.push(JobSummary {
+                    job_id,
+                    job_name: status.job_name.clone(),
+                    status: Some(status),
+                    labels: HashMap::new(),
+                });
+            }
+        }
+        for job in &result.jobs {
+            match job
+                .status
+                .as_ref()
+                .and_then(|status| status.status.as_ref())
+            {
+                Some(job_status::Status::Queued(_)) | None => result.queued_jobs += 1,
+                Some(job_status::Status::Running(_)) => result.running_jobs += 1,
+                Some(job_status::Status::Successful(_)) => result.successful_jobs += 1,
+                Some(job_status::Status::Failed(_)) => result.failed_jobs += 1,
+                Some(job_status::Status::Cancelled(_)) => result.cancelled_jobs += 1,
+            }
+        }
+        Ok(result)
+    }
+
+    /// Cancel the queued and running jobs of a group, returns the IDs of the cancelled jobs
+    pub(crate) async fn cancel_group_jobs(&self, group_id: &str) -> Result<Vec<String>> {
+        let job_ids: Vec<String> = self
+            .job_group_status(group_id)
+            .await?
+            .jobs
+            .into_iter()
+            .filter(|job| {
+                matches!(
+                    job.status
+                        .as_ref()
+                        .and_then(|status| status.status.as_ref()),
+                    Some(job_status::Status::Queued(_) | job_status::Status::Running(_))
+                        | None
+                )
+            })
+            .map(|job| job.job_id)
+            .collect();
+        let sender = self.query_stage_event_loop.get_sender()?;
+        for job_id in &job_ids {
+            info!("Cancelling job {job_id} of group {group_id}");
+            sender
+                .post_event(QueryStageSchedulerEvent::JobCancel(job_id.clone()))
+                .await?;
+        }
+        Ok(job_ids)
+    }
+
+    /// Warn about the tasks of a heartbeat which made no progress for `stalled_task_seconds`
+    pub(crate) fn warn_stalled_tasks(&self, heartbeat: &ExecutorHeartbeat) {
+        let stalled_task_ms = self.state.config.stalled_task_seconds * 1000;
+        if stalled_task_ms == 0 {
+            return;
+        }
+        for progress in &heartbeat.task_progress {
+            if progress.idle_ms >= stalled_task_ms {
+                warn!(
+                    "Task {} of job {} stage {} partition {} on executor {} made no progress for {} seconds, its last progress was {} rows of {}",
+                    progress.task_id,
+                    progress.job_id,
+                    progress.stage_id,
+                    progress.partition_id,
+                    heartbeat.executor_id,
+                    progress.idle_ms / 1000,
+                    progress.output_rows,
+                    progress.operator
+                );
+            }
+        }
+    }
+
     /// It just send task status update event to the channel,
     /// and will not guarantee the event processing completed after return
     pub(crate) async fn update_task_status(
@@ -200,6 +684,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             warn!("{}", error_msg);
             return Ok(());
         }
+        let tasks_status = if self.state.scheduler_peers.is_job_sharding() {
+            self.forward_remote_task_status(executor_id, tasks_status)
+                .await
+        } else {
+            tasks_status
+        };
+        if tasks_status.is_empty() {
+            return Ok(());
+        }
         self.query_stage_event_loop
             .get_sender()?
             .post_event(QueryStageSchedulerEvent::TaskUpdating(
@@ -209,6 +702,119 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             .await
     }
 
+    /// Forward the status updates of tasks of jobs owned by other schedulers to their owners,
+    /// returns the status updates to be handled by this scheduler
+    async fn forward_remote_task_status(
+        &self,
+        executor_id: &str,
+        tasks_status: Vec<TaskStatus>,
+    ) -> Vec<TaskStatus> {
+        let mut local = vec![];
+        let mut remote: HashMap<String, Vec<TaskStatus>> = HashMap::new();
+        let mut owners: HashMap<String, Option<String>> = HashMap::new();
+        for status in tasks_status {
+            if self.state.task_manager.is_active_job(&status.job_id) {
+                local.push(status);
+                continue;
+            }
+            if !owners.contains_key(&status.job_id) {
+                let owner = self.remote_job_scheduler(&status.job_id).await;
+                owners.insert(status.job_id.clone(), owner);
+            }
+            match &owners[&status.job_id] {
+                Some(owner) => remote.entry(owner.clone()).or_default().push(status),
+                None => local.push(status),
+            }
+        }
+
+        for (owner, tasks_status) in remote {
+            debug!(
+                "Forwarding {} task status updates from executor {executor_id} to scheduler {owner}",
+                tasks_status.len()
+            );
+            if let Err(e) = self
+                .state
+                .scheduler_peers
+                .forward_task_status(&owner, executor_id, tasks_status)
+                .await
+            {
+                error!(
+                    "Failed to forward task status updates to scheduler {owner}: {e:?}"
+                );
+            }
+        }
+        local
+    }
+
+    /// The other scheduler running a job, if any
+    async fn remote_job_scheduler(&self, job_id: &str) -> Option<String> {
+        match self.state.task_manager.get_job_status(job_id).await {
+            Ok(Some(JobStatus {
+                status: Some(job_status::Status::Running(RunningJob { scheduler, .. })),
+                ..
+            })) if scheduler != self.scheduler_name => Some(scheduler),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Failed to get the status of job {job_id}: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Hand out `task_slot_oversubscription_percent` logical task slots per hundred task
+    /// slots of an executor from now on. With push-based scheduling, the task slots added are
+    /// offered to the pending tasks right away.
+    pub async fn set_task_slot_oversubscription(
+        &self,
+        executor_id: &str,
+        task_slot_oversubscription_percent: u32,
+    ) -> Result<()> {
+        let added = self
+            .state
+            .executor_manager
+            .set_task_slot_oversubscription(
+                executor_id,
+                task_slot_oversubscription_percent,
+            )
+            .await?;
+        self.offer_added_slots(executor_id, added).await
+    }
+
+    /// Resize the task slots of an executor to the task slots it advertises. With push-based
+    /// scheduling, the task slots added are offered to the pending tasks right away.
+    pub async fn set_task_slots(&self, executor_id: &str, task_slots: u32) -> Result<()> {
+        let added = self
+            .state
+            .executor_manager
+            .set_task_slots(executor_id, task_slots)
+            .await?;
+        self.offer_added_slots(executor_id, added).await
+    }
+
+    async fn offer_added_slots(&self, executor_id: &str, added: u32) -> Result<()> {
+        if added > 0 && self.state.config.is_push_staged_scheduling() {
+            let pool = self
+                .state
+                .executor_manager
+                .executor_pool(executor_id)
+                .await
+                .unwrap_or_default();
+            let reservations = self
+                .state
+                .executor_manager
+                .reserve_slots_on_executors(
+                    &pool,
+                    &HashSet::from([executor_id.to_owned()]),
+                    added,
+                    TaskResources::default(),
+                )
+                .await?;
+            self.offer_reservation(reservations).await?;
+        }
+
+        Ok(())
+    }
+
     pub(crate) async fn offer_reservation(
         &self,
         reservations: Vec<ExecutorReservation>,
@@ -219,6 +825,111 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
             .await
     }
 
+    /// Periodically save the heartbeat of this scheduler, so that it keeps being assigned new
+    /// jobs by the other schedulers sharing the cluster
+    fn send_scheduler_heartbeats(&self) {
+        let state = self.state.clone();
+        tokio::task::spawn(async move {
+            loop {
+                state
+                    .config
+                    .clock
+                    .sleep(Duration::from_secs(SCHEDULER_HEARTBEAT_INTERVAL_SECS))
+                    .await;
+                if let Err(e) = state.scheduler_peers.heartbeat().await {
+                    warn!("Failed to save the heartbeat of the scheduler: {e:?}");
+                }
+            }
+        });
+    }
+
+    /// Periodically remove the sessions which were idle for longer than the configured
+    /// timeout, except for the sessions of the unfinished jobs of any scheduler
+    fn remove_idle_sessions(&self) {
+        let state = self.state.clone();
+        let idle_timeout_seconds = state.config.session_idle_timeout_seconds;
+        tokio::task::spawn(async move {
+            loop {
+                state
+                    .config
+                    .clock
+                    .sleep(Duration::from_secs(SESSION_CLEAN_UP_INTERVAL_SECS))
+                    .await;
+                // Other schedulers sharing the backend may run jobs of sessions this
+                // scheduler did not access, so they are looked up in the backend
+                let active_sessions =
+                    match state.task_manager.unfinished_job_sessions().await {
+                        Ok(sessions) => sessions,
+                        Err(e) => {
+                            warn!("Failed to get the sessions of unfinished jobs: {e:?}");
+                            continue;
+                        }
+                    };
+                match state
+                    .session_manager
+                    .remove_idle_sessions(idle_timeout_seconds, &active_sessions)
+                    .await
+                {
+                    Ok(removed) if !removed.is_empty() => {
+                        info!("Removed {} idle sessions", removed.len());
+                        for session_id in removed.iter() {
+                            state.executor_manager.remove_session_affinity(session_id);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to remove idle sessions: {e:?}"),
+                }
+            }
+        });
+    }
+
+    /// Periodically purge the shared tables dropped for longer than the retention window
+    fn purge_dropped_tables(&self) {
+        let session_manager = self.state.session_manager.clone();
+        let retention_seconds = self.state.config.dropped_table_retention_seconds;
+        let clock = self.state.config.clock.clone();
+        tokio::task::spawn(async move {
+            loop {
+                clock
+                    .sleep(Duration::from_secs(DROPPED_TABLES_PURGE_INTERVAL_SECS))
+                    .await;
+                match session_manager
+                    .purge_dropped_tables(retention_seconds)
+                    .await
+                {
+                    Ok(purged) if !purged.is_empty() => {
+                        info!("Purged dropped tables {}", purged.join(", "));
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to purge dropped tables: {e:?}"),
+                }
+            }
+        });
+    }
+
+    /// Spawn an async task which periodically returns the task slots of the reservations
+    /// whose lease expired to the available task slots
+    fn expire_reservation_leases(&self) {
+        let executor_manager = self.state.executor_manager.clone();
+        tokio::task::spawn(async move {
+            loop {
+                executor_manager
+                    .clock()
+                    .sleep(Duration::from_secs(EXPIRE_RESERVATION_LEASES_INTERVAL_SECS))
+                    .await;
+                match executor_manager.expire_reservation_leases().await {
+                    Ok(expired) if expired > 0 => {
+                        warn!(
+                            "Reclaimed {expired} reserved task slots whose lease expired"
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("Failed to expire reservation leases: {e:?}"),
+                }
+            }
+        });
+    }
+
     /// Spawn an async task which periodically check the active executors' status and
     /// expire the dead executors
     fn expire_dead_executors(&self) -> Result<()> {
@@ -227,6 +938,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         let termination_grace_period = self.executor_termination_grace_period;
         tokio::task::spawn(async move {
             loop {
+                // Pick up the executors decommissioned through the other schedulers
+                if let Err(e) = state.executor_manager.refresh_decommissioning().await {
+                    warn!("Failed to refresh the decommissioning executors: {e:?}");
+                }
                 let expired_executors = state
                     .executor_manager
                     .get_expired_executors(termination_grace_period);
@@ -295,10 +1010,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
                         }
                     }
                 }
-                tokio::time::sleep(Duration::from_secs(
-                    EXPIRE_DEAD_EXECUTOR_INTERVAL_SECS,
-                ))
-                .await;
+                state
+                    .config
+                    .clock
+                    .sleep(Duration::from_secs(EXPIRE_DEAD_EXECUTOR_INTERVAL_SECS))
+                    .await;
             }
         });
         Ok(())
@@ -314,7 +1030,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
         let executor_id = executor_id.to_owned();
         tokio::spawn(async move {
             // Wait for `wait_secs` before removing executor
-            tokio::time::sleep(Duration::from_secs(wait_secs)).await;
+            executor_manager
+                .clock()
+                .sleep(Duration::from_secs(wait_secs))
+                .await;
 
             // Update the executor manager immediately here
             if let Err(e) = executor_manager
@@ -336,8 +1055,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerServer<T
     async fn do_register_executor(&self, metadata: ExecutorMetadata) -> Result<()> {
         let executor_data = ExecutorData {
             executor_id: metadata.id.clone(),
-            total_task_slots: metadata.specification.task_slots,
-            available_task_slots: metadata.specification.task_slots,
+            total_task_slots: metadata.specification.logical_task_slots(),
+            available_task_slots: metadata.specification.logical_task_slots(),
         };
 
         // Save the executor to state
@@ -374,6 +1093,7 @@ pub fn timestamp_millis() -> u64 {
 #[cfg(all(test, feature = "sled"))]
 mod test {
     use std::sync::Arc;
+    use std::time::Duration;
 
     use datafusion::arrow::datatypes::{DataType, Field, Schema};
     use datafusion::logical_expr::{col, sum, LogicalPlan};
@@ -384,6 +1104,7 @@ mod test {
 
     use ballista_core::config::{
         BallistaConfig, TaskSchedulingPolicy, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS,
+        BALLISTA_JOB_LABELS, BALLISTA_JOB_POOL,
     };
     use ballista_core::error::Result;
 
@@ -400,11 +1121,16 @@ mod test {
     use ballista_core::serde::BallistaCodec;
 
     use crate::scheduler_server::{timestamp_millis, SchedulerServer};
+    use crate::state::pool_routing::parse_pool_routing_rules;
+    use crate::state::session_manager::{
+        create_job_context, job_settings, session_ballista_config,
+    };
 
     use crate::test_utils::{
-        assert_completed_event, assert_failed_event, assert_no_submitted_event,
-        assert_submitted_event, test_cluster_context, ExplodingTableProvider,
-        SchedulerTest, TaskRunnerFn, TestMetricsCollector,
+        assert_cancelled_event, assert_completed_event, assert_failed_event,
+        assert_no_submitted_event, assert_submitted_event, await_condition,
+        test_cluster_context, ExplodingTableProvider, SchedulerTest, TaskRunnerFn,
+        TestMetricsCollector,
     };
 
     #[tokio::test]
@@ -455,16 +1181,13 @@ mod test {
             .await?;
 
         // Refresh the ExecutionGraph
-        while let Some(graph) = scheduler
+        while let Some(task) = scheduler
             .state
             .task_manager
-            .get_active_execution_graph(job_id)
+            .with_active_graph(job_id, |graph| graph.pop_next_task("executor-1"))
+            .await
         {
-            let task = {
-                let mut graph = graph.write().await;
-                graph.pop_next_task("executor-1")?
-            };
-            if let Some(task) = task {
+            if let Some(task) = task? {
                 let mut partitions: Vec<ShuffleWritePartition> = vec![];
 
                 let num_partitions = task
@@ -479,6 +1202,8 @@ mod test {
                         num_batches: 1,
                         num_rows: 1,
                         num_bytes: 1,
+                        checksum: None,
+                        checkpoint_path: String::new(),
                     })
                 }
 
@@ -493,9 +1218,11 @@ mod test {
                     start_exec_time: 0,
                     end_exec_time: 0,
                     metrics: vec![],
+                    resource_usage: None,
                     status: Some(task_status::Status::Successful(SuccessfulTask {
                         executor_id: "executor-1".to_owned(),
                         partitions,
+                        output_schema: None,
                     })),
                 };
 
@@ -511,10 +1238,10 @@ mod test {
         let final_graph = scheduler
             .state
             .task_manager
-            .get_active_execution_graph(job_id)
+            .with_active_graph(job_id, |graph| graph.clone())
+            .await
             .expect("Fail to find graph in the cache");
 
-        let final_graph = final_graph.read().await;
         assert!(final_graph.is_successful());
         assert_eq!(final_graph.output_locations().len(), 4);
 
@@ -559,6 +1286,15 @@ mod test {
         assert_submitted_event("job", &metrics_collector);
         assert_completed_event("job", &metrics_collector);
 
+        // All the reservations are returned once the job completed
+        let test_ref = &test;
+        let released = await_condition(Duration::from_millis(10), 100, || async move {
+            Ok(test_ref.slot_metrics().reserved_slots == 0)
+        })
+        .await?;
+        assert!(released, "Expected no reserved task slot to be leaked");
+        assert_eq!(test.slot_metrics().total_slots, 4);
+
         Ok(())
     }
 
@@ -588,6 +1324,7 @@ mod test {
                         start_exec_time: timestamp,
                         end_exec_time: timestamp,
                         metrics: vec![],
+                        resource_usage: None,
                         status: Some(task_status::Status::Failed(FailedTask {
                             error: "ERROR".to_string(),
                             retryable: false,
@@ -682,6 +1419,178 @@ mod test {
         Ok(())
     }
 
+    // Jobs over the maximum number of concurrent jobs should stay queued until a running job completes
+    #[tokio::test]
+    async fn test_max_concurrent_jobs() -> Result<()> {
+        let plan = test_plan();
+
+        let metrics_collector = Arc::new(TestMetricsCollector::default());
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_max_concurrent_jobs(1),
+            metrics_collector.clone(),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        test.submit("job-1", "", &plan).await?;
+        test.submit("job-2", "", &plan).await?;
+
+        let test_ref = &test;
+        let job_1_running =
+            await_condition(Duration::from_millis(10), 100, || async move {
+                Ok(matches!(
+                    test_ref.job_status("job-1").await?,
+                    Some(JobStatus {
+                        status: Some(job_status::Status::Running(_)),
+                        ..
+                    })
+                ))
+            })
+            .await?;
+        assert!(job_1_running, "Expected job-1 to be running");
+
+        let status = test.job_status("job-2").await?;
+        assert!(
+            matches!(
+                status,
+                Some(JobStatus {
+                    status: Some(job_status::Status::Queued(_)),
+                    ..
+                })
+            ),
+            "Expected job-2 to be queued but it was {status:?}"
+        );
+        assert_no_submitted_event("job-2", &metrics_collector);
+
+        // job-3 is admitted after job-1 and job-2 completed
+        let status = test.run("job-3", "", &plan).await.expect("running plan");
+        assert!(matches!(
+            status.status,
+            Some(job_status::Status::Successful(_))
+        ));
+
+        for job_id in ["job-1", "job-2", "job-3"] {
+            assert_submitted_event(job_id, &metrics_collector);
+            assert_completed_event(job_id, &metrics_collector);
+        }
+
+        Ok(())
+    }
+
+    // Cancelled jobs should be reported as cancelled, whether they were held in the queue or
+    // running
+    #[tokio::test]
+    async fn test_cancel_held_job() -> Result<()> {
+        let plan = test_plan();
+
+        let metrics_collector = Arc::new(TestMetricsCollector::default());
+
+        let mut test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged)
+                .with_max_concurrent_jobs(1),
+            metrics_collector.clone(),
+            4,
+            1,
+            None,
+        )
+        .await?;
+
+        test.submit("job-1", "", &plan).await?;
+        test.submit("job-2", "", &plan).await?;
+
+        let test_ref = &test;
+        let job_1_running =
+            await_condition(Duration::from_millis(10), 100, || async move {
+                Ok(matches!(
+                    test_ref.job_status("job-1").await?,
+                    Some(JobStatus {
+                        status: Some(job_status::Status::Running(_)),
+                        ..
+                    })
+                ))
+            })
+            .await?;
+        assert!(job_1_running, "Expected job-1 to be running");
+
+        for job_id in ["job-2", "job-1"] {
+            test.cancel(job_id).await?;
+            let status = test.await_completion(job_id).await?;
+            assert!(
+                matches!(status.status, Some(job_status::Status::Cancelled(_))),
+                "Expected {job_id} to be cancelled but it was {status:?}"
+            );
+            assert_cancelled_event(job_id, &metrics_collector);
+        }
+        assert_no_submitted_event("job-2", &metrics_collector);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_route_job() -> Result<()> {
+        let mut scheduler: SchedulerServer<LogicalPlanNode, PhysicalPlanNode> =
+            SchedulerServer::new(
+                "localhost:50050".to_owned(),
+                test_cluster_context(),
+                BallistaCodec::default(),
+                SchedulerConfig::default().with_pool_routing(parse_pool_routing_rules(
+                    "label:team=etl->batch; sql:select * from dashboard_*->interactive",
+                )?),
+                Arc::new(TestMetricsCollector::default()),
+            );
+        scheduler.init().await?;
+        let session_manager = &scheduler.state.session_manager;
+
+        let ctx = session_manager
+            .create_session(
+                &BallistaConfig::builder()
+                    .set(BALLISTA_JOB_LABELS, "team=etl")
+                    .build()?,
+            )
+            .await?;
+        let routed = scheduler.route_job("job-1", ctx, Some("SELECT 1"))?;
+        assert_eq!(
+            job_settings(&routed).get(BALLISTA_JOB_POOL).unwrap(),
+            "batch"
+        );
+
+        let ctx = session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+        let routed = scheduler.route_job(
+            "job-2",
+            ctx.clone(),
+            Some("SELECT * FROM dashboard_sales"),
+        )?;
+        assert_eq!(
+            session_ballista_config(&routed).unwrap().job_pool(),
+            "interactive"
+        );
+        let routed = scheduler.route_job("job-3", ctx.clone(), Some("SELECT 1"))?;
+        assert!(job_settings(&routed).is_empty());
+
+        // The pool selected by a job is kept
+        let job_ctx = create_job_context(
+            &ctx,
+            [(BALLISTA_JOB_POOL.to_owned(), "adhoc".to_owned())]
+                .into_iter()
+                .collect(),
+        )?;
+        let routed =
+            scheduler.route_job("job-4", job_ctx, Some("SELECT * FROM dashboard_x"))?;
+        assert_eq!(
+            session_ballista_config(&routed).unwrap().job_pool(),
+            "adhoc"
+        );
+        Ok(())
+    }
+
     async fn test_scheduler(
         scheduling_policy: TaskSchedulingPolicy,
     ) -> Result<SchedulerServer<LogicalPlanNode, PhysicalPlanNode>> {
@@ -710,7 +1619,11 @@ mod test {
                     host: "localhost1".to_string(),
                     port: 8080,
                     grpc_port: 9090,
-                    specification: ExecutorSpecification { task_slots },
+                    specification: ExecutorSpecification {
+                        task_slots,
+                        ..Default::default()
+                    },
+                    pool: String::new(),
                 },
                 ExecutorData {
                     executor_id: "executor-1".to_owned(),
@@ -726,7 +1639,9 @@ mod test {
                     grpc_port: 9090,
                     specification: ExecutorSpecification {
                         task_slots: num_partitions as u32 - task_slots,
+                        ..Default::default()
                     },
+                    pool: String::new(),
                 },
                 ExecutorData {
                     executor_id: "executor-2".to_owned(),
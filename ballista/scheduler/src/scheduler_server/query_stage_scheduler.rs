@@ -15,7 +15,8 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -24,17 +25,28 @@ use log::{debug, error, info, warn};
 
 use ballista_core::error::{BallistaError, Result};
 use ballista_core::event_loop::{EventAction, EventSender};
+use ballista_core::serde::protobuf::{
+    job_status, task_status, FailedJob, HeldJob, KeyValuePair, SuccessfulJob,
+    TaskFailedEvent, TaskStatus,
+};
 
 use crate::metrics::SchedulerMetricsCollector;
 use crate::scheduler_server::timestamp_millis;
+use datafusion::logical_expr::LogicalPlan;
+use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
+use parking_lot::Mutex;
 use tokio::sync::mpsc;
 use tokio::time::Instant;
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::job_events::{JobEventBus, StageProgress};
+use crate::scheduler_server::shared_queries::{SharedQueries, SharedQuery};
+use crate::state::execution_graph::ExecutionStage;
 
 use crate::state::executor_manager::ExecutorReservation;
+use crate::state::session_manager::{job_settings, session_ballista_config};
 use crate::state::SchedulerState;
 
 pub(crate) struct QueryStageScheduler<
@@ -46,6 +58,31 @@ pub(crate) struct QueryStageScheduler<
     pending_tasks: AtomicUsize,
     job_resubmit_interval_ms: Option<u64>,
     event_expected_processing_duration: u64,
+    // Maximum number of jobs planned or running at once, 0 means unlimited
+    max_concurrent_jobs: usize,
+    job_admission: Mutex<JobAdmission>,
+    // Jobs of identical queries sharing the results of the one which runs
+    shared_queries: Arc<SharedQueries<QueuedJob>>,
+    job_events: JobEventBus,
+    // Moving average of the time taken to process an event, in microseconds
+    event_processing_micros: AtomicU64,
+}
+
+/// A queued job waiting to be planned
+struct QueuedJob {
+    job_id: String,
+    job_name: String,
+    session_ctx: Arc<SessionContext>,
+    plan: Box<LogicalPlan>,
+    queued_at: u64,
+}
+
+/// Jobs admitted for planning and running, and the queued jobs held back until one of
+/// them completes
+#[derive(Default)]
+struct JobAdmission {
+    admitted_jobs: HashSet<String>,
+    held_jobs: VecDeque<QueuedJob>,
 }
 
 impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> QueryStageScheduler<T, U> {
@@ -54,6 +91,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> QueryStageSchedul
         metrics_collector: Arc<dyn SchedulerMetricsCollector>,
         job_resubmit_interval_ms: Option<u64>,
         event_expected_processing_duration: u64,
+        max_concurrent_jobs: usize,
     ) -> Self {
         Self {
             state,
@@ -61,13 +99,566 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> QueryStageSchedul
             pending_tasks: AtomicUsize::default(),
             job_resubmit_interval_ms,
             event_expected_processing_duration,
+            max_concurrent_jobs,
+            job_admission: Mutex::new(JobAdmission::default()),
+            shared_queries: Arc::new(SharedQueries::default()),
+            job_events: JobEventBus::default(),
+            event_processing_micros: AtomicU64::default(),
+        }
+    }
+
+    /// Estimated time before an event posted behind `queued_events` events is processed,
+    /// from the time recently taken to process events. It grows when the event loop falls
+    /// behind, e.g. because the state backend is slow.
+    pub(crate) fn event_loop_lag(&self, queued_events: usize) -> Duration {
+        Duration::from_micros(
+            self.event_processing_micros
+                .load(Ordering::SeqCst)
+                .saturating_mul(queued_events as u64),
+        )
+    }
+
+    fn record_event_processing(&self, elapsed: Duration, queued_events: usize) {
+        let sample = elapsed.as_micros() as u64;
+        let _ = self.event_processing_micros.fetch_update(
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+            |average| {
+                if average == 0 {
+                    Some(sample)
+                } else {
+                    // Weigh the latest event by 1/8 to smooth out single slow events
+                    Some(average - average / 8 + sample / 8)
+                }
+            },
+        );
+        self.metrics_collector.set_event_loop_metrics(
+            queued_events as u64,
+            self.event_loop_lag(queued_events),
+        );
+    }
+
+    /// Admit a queued job for planning if fewer than `max_concurrent_jobs` jobs are admitted,
+    /// otherwise hold it in the queue and save it to the backend
+    async fn admit_job(&self, job: QueuedJob) -> Option<QueuedJob> {
+        let held_job = {
+            let mut admission = self.job_admission.lock();
+            if self.max_concurrent_jobs == 0
+                || admission.admitted_jobs.len() < self.max_concurrent_jobs
+            {
+                admission.admitted_jobs.insert(job.job_id.clone());
+                return Some(job);
+            }
+            info!(
+                "Job {} held in queue, {} jobs are already running",
+                job.job_id,
+                admission.admitted_jobs.len()
+            );
+            let held_job = self.held_job(&job);
+            admission.held_jobs.push_back(job);
+            held_job
+        };
+        if let Some(held_job) = held_job {
+            let job_id = held_job.job_id.clone();
+            if let Err(e) = self.state.task_manager.save_held_job(held_job).await {
+                warn!("Fail to save held job {job_id}: {e:?}");
+            }
+        }
+        None
+    }
+
+    /// The held job saved to the backend for a queued job, none if its plan cannot be
+    /// encoded, in which case the job is lost if the scheduler restarts
+    fn held_job(&self, job: &QueuedJob) -> Option<HeldJob> {
+        let mut logical_plan = vec![];
+        let encoded = T::try_from_logical_plan(
+            &job.plan,
+            self.state.codec.logical_extension_codec(),
+        )
+        .and_then(|node| node.try_encode(&mut logical_plan));
+        if let Err(e) = encoded {
+            warn!("Fail to encode the plan of held job {}: {e:?}", job.job_id);
+            return None;
+        }
+        Some(HeldJob {
+            job_id: job.job_id.clone(),
+            job_name: job.job_name.clone(),
+            session_id: job.session_ctx.session_id(),
+            logical_plan,
+            queued_at: job.queued_at,
+            job_settings: job_settings(&job.session_ctx)
+                .into_iter()
+                .map(|(key, value)| KeyValuePair { key, value })
+                .collect(),
+            scheduler: String::new(),
+        })
+    }
+
+    /// Release the admission of a completed job and admit the next held job, if any
+    fn release_job(&self, job_id: &str) -> Option<QueuedJob> {
+        let mut admission = self.job_admission.lock();
+        if !admission.admitted_jobs.remove(job_id) {
+            return None;
+        }
+        let job = admission.held_jobs.pop_front()?;
+        admission.admitted_jobs.insert(job.job_id.clone());
+        Some(job)
+    }
+
+    /// Remove a job from the held jobs, returns whether it was held
+    fn remove_held_job(&self, job_id: &str) -> bool {
+        let mut admission = self.job_admission.lock();
+        let num_held = admission.held_jobs.len();
+        admission.held_jobs.retain(|job| job.job_id != job_id);
+        admission.held_jobs.len() < num_held
+    }
+
+    /// IDs of the queued jobs of a group held back by the maximum number of concurrent jobs
+    pub(crate) fn held_jobs_in_group(&self, group_id: &str) -> Vec<String> {
+        self.job_admission
+            .lock()
+            .held_jobs
+            .iter()
+            .filter(|job| {
+                session_ballista_config(&job.session_ctx)
+                    .map(|config| config.job_group() == group_id)
+                    .unwrap_or_default()
+            })
+            .map(|job| job.job_id.clone())
+            .collect()
+    }
+
+    /// Number of queued jobs held back by the maximum number of concurrent jobs
+    pub(crate) fn held_jobs(&self) -> usize {
+        self.job_admission.lock().held_jobs.len()
+    }
+
+    /// Number of submitted jobs which have not completed yet, including held jobs
+    pub(crate) fn unfinished_jobs(&self) -> usize {
+        let admission = self.job_admission.lock();
+        admission.admitted_jobs.len() + admission.held_jobs.len()
+    }
+
+    /// Number of admitted jobs which are still being planned, or wait for the results of an
+    /// identical query
+    pub(crate) fn planning_jobs(&self) -> usize {
+        let admitted_jobs = self.job_admission.lock().admitted_jobs.clone();
+        admitted_jobs
+            .iter()
+            .filter(|job_id| !self.state.task_manager.is_active_job(job_id))
+            .count()
+    }
+
+    /// Plan and submit the held jobs regardless of the maximum number of concurrent jobs,
+    /// which saves them to the backend for the scheduler recovering the jobs of a drained
+    /// scheduler. Returns the number of submitted jobs.
+    pub(crate) async fn submit_held_jobs(&self) -> usize {
+        let held_jobs: Vec<QueuedJob> = {
+            let mut admission = self.job_admission.lock();
+            let held_jobs: Vec<QueuedJob> = admission.held_jobs.drain(..).collect();
+            admission
+                .admitted_jobs
+                .extend(held_jobs.iter().map(|job| job.job_id.clone()));
+            held_jobs
+        };
+
+        let mut submitted = 0;
+        for job in held_jobs {
+            let job_id = job.job_id.as_str();
+            let result = match self
+                .state
+                .plan_job(job_id, job.session_ctx.clone(), &job.plan)
+                .await
+            {
+                Ok(plan) => {
+                    self.metrics_collector.record_submitted(
+                        job_id,
+                        job.queued_at,
+                        timestamp_millis(),
+                    );
+                    self.state
+                        .task_manager
+                        .submit_job_with_settings(
+                            job_id,
+                            &job.job_name,
+                            &job.session_ctx.session_id(),
+                            plan,
+                            job.queued_at,
+                            job_settings(&job.session_ctx),
+                        )
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => submitted += 1,
+                Err(e) => {
+                    let fail_message =
+                        format!("Error submitting held job {job_id}: {e:?}");
+                    error!("{}", &fail_message);
+                    self.release_job(job_id);
+                    if let Err(e) = self
+                        .state
+                        .task_manager
+                        .fail_unscheduled_job(job_id, fail_message)
+                        .await
+                    {
+                        error!("Fail to update the status of job {job_id}: {e:?}");
+                    }
+                }
+            }
+        }
+        submitted
+    }
+
+    /// Plan an admitted job in the background and post the outcome as an event
+    fn spawn_job_planning(
+        &self,
+        job: QueuedJob,
+        tx_event: EventSender<QueryStageSchedulerEvent>,
+    ) {
+        let state = self.state.clone();
+        let shared_queries = self.shared_queries.clone();
+        tokio::spawn(async move {
+            let job_id = job.job_id.clone();
+            let planned_job_id = job_id.clone();
+            let queued_at = job.queued_at;
+            let event = match state
+                .plan_job(&job_id, job.session_ctx.clone(), &job.plan)
+                .await
+            {
+                Ok(plan) => match state.cached_result(
+                    &job_id,
+                    job.session_ctx.as_ref(),
+                    plan.as_ref(),
+                ) {
+                    Some(result) => QueryStageSchedulerEvent::JobResultCached {
+                        job_id,
+                        cached_job_id: result.job_id,
+                        partition_location: result.partition_location,
+                        queued_at,
+                        completed_at: timestamp_millis(),
+                    },
+                    None => {
+                        let shared = match state.shared_query_fingerprint(
+                            job.session_ctx.as_ref(),
+                            plan.as_ref(),
+                        ) {
+                            Some(fingerprint) => {
+                                shared_queries.join(&job_id, fingerprint, job)
+                            }
+                            None => SharedQuery::Leader(job),
+                        };
+                        match shared {
+                            SharedQuery::Leader(job) => {
+                                QueryStageSchedulerEvent::JobSubmitted {
+                                    job_id,
+                                    job_name: job.job_name,
+                                    session_id: job.session_ctx.session_id(),
+                                    job_settings: job_settings(&job.session_ctx),
+                                    queued_at,
+                                    submitted_at: timestamp_millis(),
+                                    resubmit: false,
+                                    plan,
+                                }
+                            }
+                            SharedQuery::Follower { leader_job_id } => {
+                                QueryStageSchedulerEvent::JobShared {
+                                    job_id,
+                                    leader_job_id,
+                                }
+                            }
+                        }
+                    }
+                },
+                Err(error) => {
+                    let fail_message = format!("Error planning job {job_id}: {error:?}");
+                    error!("{}", &fail_message);
+                    QueryStageSchedulerEvent::JobPlanningFailed {
+                        job_id,
+                        fail_message,
+                        queued_at,
+                        failed_at: timestamp_millis(),
+                    }
+                }
+            };
+            if let Err(e) = tx_event.post_event(event).await {
+                error!("Fail to send event due to {}", e);
+                // The job is never submitted, so no job may wait for its results
+                shared_queries.complete(&planned_job_id);
+                shared_queries.remove_follower(&planned_job_id);
+            }
+        });
+    }
+
+    /// Plan again the jobs which waited for the results of jobs no longer admitted, e.g.
+    /// because processing their completion failed, so that they do not wait forever
+    async fn release_stale_shared_queries(
+        &self,
+        tx_event: &EventSender<QueryStageSchedulerEvent>,
+    ) {
+        let admitted_jobs = self.job_admission.lock().admitted_jobs.clone();
+        let followers = self
+            .shared_queries
+            .release_stale_leaders(|job_id| admitted_jobs.contains(job_id));
+        for (follower_id, job) in followers {
+            warn!(
+                "Planning job {} again, the job whose results it waited for is gone",
+                follower_id
+            );
+            if let Some(job) = self.admit_job(job).await {
+                self.spawn_job_planning(job, tx_event.clone());
+            }
+        }
+    }
+
+    /// Admit the next held job once the given job completes
+    fn on_job_completed(
+        &self,
+        job_id: &str,
+        tx_event: &EventSender<QueryStageSchedulerEvent>,
+    ) {
+        if let Some(job) = self.release_job(job_id) {
+            info!("Job {} admitted after job {} completed", job.job_id, job_id);
+            self.spawn_job_planning(job, tx_event.clone());
         }
     }
 
+    /// Forget the scans shared by a completed job. The stages of other jobs which waited for
+    /// its output run their own tasks.
+    async fn complete_shared_scans(
+        &self,
+        job_id: &str,
+        tx_event: &EventSender<QueryStageSchedulerEvent>,
+    ) -> Result<()> {
+        for released_job_id in self.state.complete_shared_scans(job_id).await {
+            tx_event
+                .post_event(QueryStageSchedulerEvent::JobUpdated(released_job_id))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Complete the jobs of identical queries which waited for the results of a completed
+    /// job. They share the results of a successful job and fail with a failed job, while the
+    /// jobs which waited for a cancelled job are planned again.
+    async fn complete_shared_queries(
+        &self,
+        job_id: &str,
+        cancelled: bool,
+        tx_event: &EventSender<QueryStageSchedulerEvent>,
+    ) -> Result<()> {
+        let followers = self.shared_queries.complete(job_id);
+        self.release_stale_shared_queries(tx_event).await;
+        if followers.is_empty() {
+            return Ok(());
+        }
+        let status = if cancelled {
+            None
+        } else {
+            self.state
+                .task_manager
+                .get_job_status(job_id)
+                .await?
+                .and_then(|status| status.status)
+        };
+        for (follower_id, job) in followers {
+            // The follower may not have made room for a held job yet
+            self.on_job_completed(&follower_id, tx_event);
+            match &status {
+                Some(job_status::Status::Successful(SuccessfulJob {
+                    partition_location,
+                    ..
+                })) => {
+                    info!("Job {} shared the results of job {}", follower_id, job_id);
+                    self.metrics_collector.record_completed(
+                        &follower_id,
+                        job.queued_at,
+                        timestamp_millis(),
+                    );
+                    self.state
+                        .task_manager
+                        .query_quarantine()
+                        .record_success(&follower_id);
+                    self.state
+                        .task_manager
+                        .succeed_unscheduled_job(
+                            &follower_id,
+                            partition_location.clone(),
+                            job_id,
+                        )
+                        .await?;
+                    self.publish_job_completed(&follower_id).await?;
+                    // The result partitions belong to the shared job, so only the job state
+                    // is cleaned up
+                    self.state.task_manager.clean_up_job_delayed(
+                        follower_id,
+                        self.state
+                            .config
+                            .finished_job_state_clean_up_interval_seconds,
+                    );
+                }
+                Some(job_status::Status::Failed(FailedJob { error, .. })) => {
+                    error!(
+                        "Job {} failed with job {} whose results it shared",
+                        follower_id, job_id
+                    );
+                    self.metrics_collector.record_failed(
+                        &follower_id,
+                        job.queued_at,
+                        timestamp_millis(),
+                    );
+                    // Only the failure of the job which ran counts towards the quarantine
+                    self.state
+                        .task_manager
+                        .query_quarantine()
+                        .remove_job(&follower_id);
+                    self.state
+                        .task_manager
+                        .fail_unscheduled_job(
+                            &follower_id,
+                            format!("Job {job_id} whose results the job shared failed: {error}"),
+                        )
+                        .await?;
+                    self.publish_job_completed(&follower_id).await?;
+                }
+                _ => {
+                    info!(
+                        "Planning job {} again, job {} whose results it waited for was cancelled",
+                        follower_id, job_id
+                    );
+                    if let Some(job) = self.admit_job(job).await {
+                        self.spawn_job_planning(job, tx_event.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reserve task slots for the available tasks of a job, as many as the quota of its
+    /// session leaves. Returns the reservations and the number of tasks they were requested
+    /// for.
+    async fn reserve_available_tasks(
+        &self,
+        job_id: &str,
+    ) -> Result<(Vec<ExecutorReservation>, usize)> {
+        let available_tasks = self
+            .state
+            .task_manager
+            .get_available_tasks_by_resources(job_id)
+            .await?;
+
+        let session_id = self
+            .state
+            .task_manager
+            .with_active_graph(job_id, |graph| graph.session_id().to_owned())
+            .await
+            .unwrap_or_default();
+        let pool = self.state.task_manager.get_job_pool(job_id);
+        // Slots beyond the quota of the session are neither reserved nor freed by
+        // preempting the tasks of other jobs
+        let mut free_slots = self
+            .state
+            .task_manager
+            .get_free_session_task_slots(job_id)
+            .await
+            .unwrap_or(usize::MAX);
+
+        let mut total_tasks = 0;
+        let mut reservations: Vec<ExecutorReservation> = vec![];
+        for (resources, tasks) in available_tasks {
+            let tasks = tasks.min(free_slots);
+            free_slots -= tasks;
+            total_tasks += tasks;
+            if tasks == 0 {
+                continue;
+            }
+            reservations.extend(
+                self.state
+                    .executor_manager
+                    .reserve_slots_for_session(
+                        &session_id,
+                        &pool,
+                        tasks as u32,
+                        resources,
+                    )
+                    .await?
+                    .into_iter()
+                    .map(|res| res.assign(job_id.to_owned())),
+            );
+        }
+        Ok((reservations, total_tasks))
+    }
+
+    /// Events of the jobs for the clients subscribed to them
+    pub(crate) fn job_events(&self) -> &JobEventBus {
+        &self.job_events
+    }
+
+    /// Publish the stages of a job which started or completed since they were last published
+    pub(crate) async fn publish_stage_events(&self, job_id: &str) {
+        if !self.job_events.is_subscribed(job_id) {
+            return;
+        }
+        let stages = self
+            .state
+            .task_manager
+            .with_active_graph(job_id, |graph| {
+                graph
+                    .stages()
+                    .iter()
+                    .filter_map(|(stage_id, stage)| match stage {
+                        ExecutionStage::Running(stage) => {
+                            Some((*stage_id, StageProgress::Running, stage.partitions))
+                        }
+                        ExecutionStage::Successful(stage) => {
+                            Some((*stage_id, StageProgress::Successful, stage.partitions))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<(usize, StageProgress, usize)>>()
+            })
+            .await;
+        if let Some(stages) = stages {
+            self.job_events.publish_stage_progress(job_id, stages);
+        }
+    }
+
+    fn publish_task_failures(&self, executor_id: &str, tasks_status: &[TaskStatus]) {
+        for status in tasks_status {
+            if let Some(task_status::Status::Failed(failed)) = &status.status {
+                self.job_events.publish_task_failed(
+                    &status.job_id,
+                    TaskFailedEvent {
+                        stage_id: status.stage_id,
+                        partition_id: status.partition_id,
+                        task_id: status.task_id,
+                        executor_id: executor_id.to_owned(),
+                        error: failed.error.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Publish the final status of a completed job, ending its subscriptions
+    pub(crate) async fn publish_job_completed(&self, job_id: &str) -> Result<()> {
+        if !self.job_events.is_subscribed(job_id) {
+            return Ok(());
+        }
+        if let Some(status) = self.state.task_manager.get_job_status(job_id).await? {
+            self.job_events.publish_job_completed(job_id, status);
+        }
+        Ok(())
+    }
+
     pub(crate) fn set_pending_tasks(&self, tasks: usize) {
         self.pending_tasks.store(tasks, Ordering::SeqCst);
         self.metrics_collector
             .set_pending_tasks_queue_size(tasks as u64);
+        self.metrics_collector
+            .set_slot_metrics(&self.state.executor_manager.slot_metrics());
     }
 
     pub(crate) fn pending_tasks(&self) -> usize {
@@ -77,25 +668,12 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> QueryStageSchedul
     pub(crate) fn metrics_collector(&self) -> &dyn SchedulerMetricsCollector {
         self.metrics_collector.as_ref()
     }
-}
-
-#[async_trait]
-impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
-    EventAction<QueryStageSchedulerEvent> for QueryStageScheduler<T, U>
-{
-    fn on_start(&self) {
-        info!("Starting QueryStageScheduler");
-    }
-
-    fn on_stop(&self) {
-        info!("Stopping QueryStageScheduler")
-    }
 
-    async fn on_receive(
+    /// Process an event received by the event loop
+    async fn process_event(
         &self,
         event: QueryStageSchedulerEvent,
         tx_event: &mpsc::Sender<QueryStageSchedulerEvent>,
-        _rx_event: &mpsc::Receiver<QueryStageSchedulerEvent>,
     ) -> Result<()> {
         let mut time_recorder = None;
         if self.event_expected_processing_duration > 0 {
@@ -112,45 +690,25 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
             } => {
                 info!("Job {} queued with name {:?}", job_id, job_name);
 
-                self.state
-                    .task_manager
-                    .queue_job(&job_id, &job_name, queued_at)
-                    .await?;
-
-                let state = self.state.clone();
-                tokio::spawn(async move {
-                    let event =
-                        match state.plan_job(&job_id, session_ctx.clone(), &plan).await {
-                            Ok(plan) => QueryStageSchedulerEvent::JobSubmitted {
-                                job_id,
-                                job_name,
-                                session_id: session_ctx.session_id(),
-                                queued_at,
-                                submitted_at: timestamp_millis(),
-                                resubmit: false,
-                                plan,
-                            },
-                            Err(error) => {
-                                let fail_message =
-                                    format!("Error planning job {job_id}: {error:?}");
-                                error!("{}", &fail_message);
-                                QueryStageSchedulerEvent::JobPlanningFailed {
-                                    job_id,
-                                    fail_message,
-                                    queued_at,
-                                    failed_at: timestamp_millis(),
-                                }
-                            }
-                        };
-                    if let Err(e) = tx_event.post_event(event).await {
-                        error!("Fail to send event due to {}", e);
-                    }
-                });
+                // Jobs over the concurrency limit stay queued until a running job completes
+                if let Some(job) = self
+                    .admit_job(QueuedJob {
+                        job_id,
+                        job_name,
+                        session_ctx,
+                        plan,
+                        queued_at,
+                    })
+                    .await
+                {
+                    self.spawn_job_planning(job, tx_event);
+                }
             }
             QueryStageSchedulerEvent::JobSubmitted {
                 job_id,
                 job_name,
                 session_id,
+                job_settings,
                 queued_at,
                 submitted_at,
                 resubmit,
@@ -162,36 +720,81 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                         queued_at,
                         submitted_at,
                     );
-                    self.state
+                    if let Err(e) = self
+                        .state
                         .task_manager
-                        .submit_job(
+                        .submit_job_with_settings(
                             job_id.as_str(),
                             job_name.as_str(),
                             session_id.as_str(),
                             plan.clone(),
                             queued_at,
+                            job_settings.clone(),
                         )
-                        .await?;
+                        .await
+                    {
+                        let fail_message =
+                            format!("Error submitting job {job_id}: {e:?}");
+                        error!("{}", &fail_message);
+                        self.metrics_collector.record_failed(
+                            &job_id,
+                            queued_at,
+                            timestamp_millis(),
+                        );
+                        self.on_job_completed(&job_id, &tx_event);
+                        self.state
+                            .task_manager
+                            .query_quarantine()
+                            .remove_job(&job_id);
+                        self.state
+                            .task_manager
+                            .result_cache()
+                            .remove_pending_job(&job_id);
+                        // The jobs waiting for the results of the job are planned again
+                        self.complete_shared_queries(&job_id, true, &tx_event)
+                            .await?;
+                        self.state
+                            .task_manager
+                            .fail_unscheduled_job(&job_id, fail_message)
+                            .await?;
+                        self.publish_job_completed(&job_id).await?;
+                        return Ok(());
+                    }
                     info!("Job {} submitted", job_id);
                 } else {
                     debug!("Job {} resubmitted", job_id);
                 }
+                self.publish_stage_events(&job_id).await;
 
                 if self.state.config.is_push_staged_scheduling() {
-                    let available_tasks = self
-                        .state
-                        .task_manager
-                        .get_available_task_count(&job_id)
-                        .await?;
-
-                    let reservations: Vec<ExecutorReservation> = self
-                        .state
-                        .executor_manager
-                        .reserve_slots(available_tasks as u32)
-                        .await?
-                        .into_iter()
-                        .map(|res| res.assign(job_id.clone()))
-                        .collect();
+                    let (reservations, total_tasks) =
+                        self.reserve_available_tasks(&job_id).await?;
+
+                    // Free task slots for the tasks which could not be reserved by killing tasks
+                    // of lower priority jobs. The slots are offered back once the killed tasks
+                    // report their status, to the jobs with the highest priority first.
+                    if !resubmit
+                        && self.state.config.job_preemption
+                        && reservations.len() < total_tasks
+                    {
+                        let preempted = self
+                            .state
+                            .task_manager
+                            .preempt_tasks(&job_id, total_tasks - reservations.len())
+                            .await?;
+                        if !preempted.is_empty() {
+                            info!(
+                                "Preempted {} running tasks for job {}",
+                                preempted.len(),
+                                job_id
+                            );
+                            tx_event
+                                .post_event(QueryStageSchedulerEvent::CancelTasks(
+                                    preempted,
+                                ))
+                                .await?;
+                        }
+                    }
 
                     if reservations.is_empty() && self.job_resubmit_interval_ms.is_some()
                     {
@@ -201,14 +804,16 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                             "No task slots reserved for job {job_id}, resubmitting after {wait_ms}ms"
                         );
 
+                        let clock = self.state.config.clock.clone();
                         tokio::task::spawn(async move {
-                            tokio::time::sleep(Duration::from_millis(wait_ms)).await;
+                            clock.sleep(Duration::from_millis(wait_ms)).await;
 
                             if let Err(e) = tx_event
                                 .post_event(QueryStageSchedulerEvent::JobSubmitted {
                                     job_id,
                                     job_name,
                                     session_id,
+                                    job_settings,
                                     queued_at,
                                     submitted_at,
                                     resubmit: true,
@@ -234,6 +839,26 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     }
                 }
             }
+            QueryStageSchedulerEvent::JobRecovered(job_id) => {
+                // The job was admitted before the scheduler restarted
+                self.job_admission
+                    .lock()
+                    .admitted_jobs
+                    .insert(job_id.clone());
+                info!("Job {} recovered", job_id);
+                self.publish_stage_events(&job_id).await;
+
+                if self.state.config.is_push_staged_scheduling() {
+                    let (reservations, _) = self.reserve_available_tasks(&job_id).await?;
+                    if !reservations.is_empty() {
+                        tx_event
+                            .post_event(QueryStageSchedulerEvent::ReservationOffering(
+                                reservations,
+                            ))
+                            .await?;
+                    }
+                }
+            }
             QueryStageSchedulerEvent::JobPlanningFailed {
                 job_id,
                 fail_message,
@@ -244,10 +869,20 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .record_failed(&job_id, queued_at, failed_at);
 
                 error!("Job {} failed: {}", job_id, fail_message);
+                self.on_job_completed(&job_id, &tx_event);
+                self.state
+                    .task_manager
+                    .query_quarantine()
+                    .record_failure(&job_id, &fail_message);
+                self.state
+                    .task_manager
+                    .result_cache()
+                    .remove_pending_job(&job_id);
                 self.state
                     .task_manager
                     .fail_unscheduled_job(&job_id, fail_message)
                     .await?;
+                self.publish_job_completed(&job_id).await?;
             }
             QueryStageSchedulerEvent::JobFinished {
                 job_id,
@@ -258,12 +893,92 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .record_completed(&job_id, queued_at, completed_at);
 
                 info!("Job {} success", job_id);
+                self.on_job_completed(&job_id, &tx_event);
+                self.state
+                    .task_manager
+                    .query_quarantine()
+                    .record_success(&job_id);
+                let result_ttl_seconds = self
+                    .state
+                    .task_manager
+                    .get_result_ttl_seconds(&job_id)
+                    .await;
+                let checkpoint_dir =
+                    self.state.task_manager.get_checkpoint_dir(&job_id).await;
+                let result_export =
+                    self.state.task_manager.get_result_export(&job_id).await;
                 self.state.task_manager.succeed_job(&job_id).await?;
-                self.state.clean_up_successful_job(job_id);
+                self.complete_shared_scans(&job_id, &tx_event).await?;
+                self.publish_job_completed(&job_id).await?;
+                self.complete_shared_queries(&job_id, false, &tx_event)
+                    .await?;
+                let result_cached = self.state.cache_job_result(&job_id).await?;
+                self.state.clean_up_successful_job(
+                    job_id,
+                    result_cached,
+                    result_ttl_seconds,
+                    checkpoint_dir,
+                    result_export,
+                );
+            }
+            QueryStageSchedulerEvent::JobResultCached {
+                job_id,
+                cached_job_id,
+                partition_location,
+                queued_at,
+                completed_at,
+            } => {
+                self.metrics_collector
+                    .record_completed(&job_id, queued_at, completed_at);
+
+                info!(
+                    "Job {} served the cached results of job {}",
+                    job_id, cached_job_id
+                );
+                self.on_job_completed(&job_id, &tx_event);
+                self.state
+                    .task_manager
+                    .query_quarantine()
+                    .record_success(&job_id);
+                self.state
+                    .task_manager
+                    .succeed_unscheduled_job(&job_id, partition_location, &cached_job_id)
+                    .await?;
+                self.publish_job_completed(&job_id).await?;
+                // The result partitions belong to the cached job, so only the job state is
+                // cleaned up
+                self.state.task_manager.clean_up_job_delayed(
+                    job_id,
+                    self.state
+                        .config
+                        .finished_job_state_clean_up_interval_seconds,
+                );
+            }
+            QueryStageSchedulerEvent::JobShared {
+                job_id,
+                leader_job_id,
+            } => {
+                info!(
+                    "Job {} waits for the results of the identical running job {}",
+                    job_id, leader_job_id
+                );
+                // The results of the job are the ones of the running job, which caches them
+                self.state
+                    .task_manager
+                    .result_cache()
+                    .remove_pending_job(&job_id);
+                // The job runs no tasks, so it makes room for a held job unless the running
+                // job already completed it
+                if self.shared_queries.is_follower(&job_id) {
+                    self.on_job_completed(&job_id, &tx_event);
+                }
+                // The running job may have been lost in the meantime
+                self.release_stale_shared_queries(&tx_event).await;
             }
             QueryStageSchedulerEvent::JobRunningFailed {
                 job_id,
                 fail_message,
+                query_error,
                 queued_at,
                 failed_at,
             } => {
@@ -271,34 +986,96 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .record_failed(&job_id, queued_at, failed_at);
 
                 error!("Job {} running failed", job_id);
+                self.on_job_completed(&job_id, &tx_event);
+                // Jobs failing because of lost executors or data do not count towards the
+                // quarantine of their query
+                let quarantine = self.state.task_manager.query_quarantine();
+                if query_error {
+                    quarantine.record_failure(&job_id, &fail_message);
+                } else {
+                    quarantine.remove_job(&job_id);
+                }
+                self.state
+                    .task_manager
+                    .result_cache()
+                    .remove_pending_job(&job_id);
+                let checkpoint_dir =
+                    self.state.task_manager.get_checkpoint_dir(&job_id).await;
                 let (running_tasks, _pending_tasks) = self
                     .state
                     .task_manager
                     .abort_job(&job_id, fail_message)
                     .await?;
+                self.complete_shared_scans(&job_id, &tx_event).await?;
+                self.publish_job_completed(&job_id).await?;
+                self.complete_shared_queries(&job_id, false, &tx_event)
+                    .await?;
 
                 if !running_tasks.is_empty() {
                     tx_event
                         .post_event(QueryStageSchedulerEvent::CancelTasks(running_tasks))
                         .await?;
                 }
-                self.state.clean_up_failed_job(job_id);
+                self.state.clean_up_failed_job(job_id, checkpoint_dir);
             }
             QueryStageSchedulerEvent::JobUpdated(job_id) => {
                 info!("Job {} Updated", job_id);
                 self.state.task_manager.update_job(&job_id).await?;
+                self.publish_stage_events(&job_id).await;
+            }
+            QueryStageSchedulerEvent::JobResultsConsumed(job_id) => {
+                debug!("Results of job {} consumed", job_id);
+                if self.state.config.is_push_staged_scheduling() {
+                    let (reservations, _) = self.reserve_available_tasks(&job_id).await?;
+                    if !reservations.is_empty() {
+                        tx_event
+                            .post_event(QueryStageSchedulerEvent::ReservationOffering(
+                                reservations,
+                            ))
+                            .await?;
+                    }
+                }
             }
             QueryStageSchedulerEvent::JobCancel(job_id) => {
                 self.metrics_collector.record_cancelled(&job_id);
 
                 info!("Job {} Cancelled", job_id);
-                let (running_tasks, _pending_tasks) =
-                    self.state.task_manager.cancel_job(&job_id).await?;
-                self.state.clean_up_failed_job(job_id);
+                self.state
+                    .task_manager
+                    .query_quarantine()
+                    .remove_job(&job_id);
+                // A held job was never planned and a job waiting for the results of an
+                // identical query never ran, so there is nothing to clean up
+                let follower = self.shared_queries.remove_follower(&job_id).is_some();
+                if follower || self.remove_held_job(&job_id) {
+                    if follower {
+                        self.on_job_completed(&job_id, &tx_event);
+                    }
+                    self.state
+                        .task_manager
+                        .cancel_unscheduled_job(&job_id)
+                        .await?;
+                    self.publish_job_completed(&job_id).await?;
+                } else {
+                    self.on_job_completed(&job_id, &tx_event);
+                    self.state
+                        .task_manager
+                        .result_cache()
+                        .remove_pending_job(&job_id);
+                    let checkpoint_dir =
+                        self.state.task_manager.get_checkpoint_dir(&job_id).await;
+                    let (running_tasks, _pending_tasks) =
+                        self.state.task_manager.cancel_job(&job_id).await?;
+                    self.complete_shared_scans(&job_id, &tx_event).await?;
+                    self.publish_job_completed(&job_id).await?;
+                    self.complete_shared_queries(&job_id, true, &tx_event)
+                        .await?;
+                    self.state.clean_up_failed_job(job_id, checkpoint_dir);
 
-                tx_event
-                    .post_event(QueryStageSchedulerEvent::CancelTasks(running_tasks))
-                    .await?;
+                    tx_event
+                        .post_event(QueryStageSchedulerEvent::CancelTasks(running_tasks))
+                        .await?;
+                }
             }
             QueryStageSchedulerEvent::TaskUpdating(executor_id, tasks_status) => {
                 debug!(
@@ -307,12 +1084,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 );
 
                 let num_status = tasks_status.len();
+                self.publish_task_failures(&executor_id, &tasks_status);
+                let job_ids: HashSet<String> = tasks_status
+                    .iter()
+                    .map(|status| status.job_id.clone())
+                    .collect();
                 match self
                     .state
                     .update_task_statuses(&executor_id, tasks_status)
                     .await
                 {
                     Ok((stage_events, offers)) => {
+                        for job_id in &job_ids {
+                            self.publish_stage_events(job_id).await;
+                        }
+
                         if self.state.config.is_push_staged_scheduling() {
                             tx_event
                                 .post_event(
@@ -349,6 +1135,10 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                 }
             }
             QueryStageSchedulerEvent::ExecutorLost(executor_id, _) => {
+                self.state
+                    .task_manager
+                    .result_cache()
+                    .remove_executor(&executor_id);
                 match self.state.task_manager.executor_lost(&executor_id).await {
                     Ok(tasks) => {
                         if !tasks.is_empty() {
@@ -372,7 +1162,11 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
                     .await?;
             }
             QueryStageSchedulerEvent::JobDataClean(job_id) => {
-                self.state.executor_manager.clean_up_job_data(job_id);
+                let checkpoint_dir =
+                    self.state.task_manager.get_checkpoint_dir(&job_id).await;
+                self.state
+                    .executor_manager
+                    .clean_up_job_data(job_id, checkpoint_dir);
             }
         }
         if let Some((start, ec)) = time_recorder {
@@ -389,6 +1183,33 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
         }
         Ok(())
     }
+}
+
+#[async_trait]
+impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
+    EventAction<QueryStageSchedulerEvent> for QueryStageScheduler<T, U>
+{
+    fn on_start(&self) {
+        info!("Starting QueryStageScheduler");
+    }
+
+    fn on_stop(&self) {
+        info!("Stopping QueryStageScheduler")
+    }
+
+    async fn on_receive(
+        &self,
+        event: QueryStageSchedulerEvent,
+        tx_event: &mpsc::Sender<QueryStageSchedulerEvent>,
+        _rx_event: &mpsc::Receiver<QueryStageSchedulerEvent>,
+    ) -> Result<()> {
+        let received_at = Instant::now();
+        let queued_events = tx_event.max_capacity() - tx_event.capacity();
+        // Events failing half way took time as well
+        let result = self.process_event(event, tx_event).await;
+        self.record_event_processing(received_at.elapsed(), queued_events);
+        result
+    }
 
     fn on_error(&self, error: BallistaError) {
         error!("Error received by QueryStageScheduler: {:?}", error);
@@ -400,13 +1221,16 @@ mod tests {
     use crate::config::SchedulerConfig;
     use crate::scheduler_server::event::QueryStageSchedulerEvent;
     use crate::test_utils::{await_condition, SchedulerTest, TestMetricsCollector};
-    use ballista_core::config::TaskSchedulingPolicy;
+    use ballista_core::config::{
+        BallistaConfig, TaskSchedulingPolicy, BALLISTA_SESSION_MAX_TASK_SLOTS,
+    };
     use ballista_core::error::Result;
     use ballista_core::event_loop::EventAction;
     use datafusion::arrow::datatypes::{DataType, Field, Schema};
     use datafusion::logical_expr::{col, sum, LogicalPlan};
     use datafusion::physical_plan::empty::EmptyExec;
     use datafusion::test_util::scan_empty_with_partitions;
+    use std::collections::HashMap;
     use std::sync::Arc;
     use std::time::Duration;
     use tracing_subscriber::EnvFilter;
@@ -436,20 +1260,21 @@ mod tests {
         let (tx, mut rx) = tokio::sync::mpsc::channel::<QueryStageSchedulerEvent>(10);
 
         let event = QueryStageSchedulerEvent::JobSubmitted {
-            job_id: "job-id".to_string(),
+            job_id: "resubmitted-job".to_string(),
             job_name: "job-name".to_string(),
             session_id: "session-id".to_string(),
+            job_settings: HashMap::new(),
             queued_at: 0,
             submitted_at: 0,
             resubmit: false,
             plan: Arc::new(EmptyExec::new(false, Arc::new(test_schema()))),
         };
 
-        // Mock the JobQueued work.
+        // Mock the JobQueued work of another job, the submitted one is queued already
         query_stage_scheduler
             .state
             .task_manager
-            .queue_job("job-id", "job-name", 0)
+            .queue_job("resubmitted-job", "job-name", 0)
             .await?;
 
         query_stage_scheduler.on_receive(event, &tx, &rx).await?;
@@ -459,8 +1284,59 @@ mod tests {
         dbg!(next_event.clone());
         assert!(matches!(
             next_event,
-            QueryStageSchedulerEvent::JobSubmitted { job_id, resubmit, .. } if job_id == "job-id" && resubmit
+            QueryStageSchedulerEvent::JobSubmitted { job_id, resubmit, .. } if job_id == "resubmitted-job" && resubmit
+        ));
+
+        Ok(())
+    }
+
+    // A submitted job only reserves the task slots the quota of its session leaves
+    #[tokio::test]
+    async fn test_job_submitted_session_max_task_slots() -> Result<()> {
+        let test = SchedulerTest::new(
+            SchedulerConfig::default()
+                .with_scheduler_policy(TaskSchedulingPolicy::PushStaged),
+            Arc::new(TestMetricsCollector::default()),
+            1,
+            4,
+            None,
+        )
+        .await?;
+        let query_stage_scheduler = test.query_stage_scheduler();
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_SESSION_MAX_TASK_SLOTS, "2")
+            .build()?;
+        let ctx = query_stage_scheduler
+            .state
+            .session_manager
+            .create_session(&config)
+            .await?;
+        let plan = ctx.state().create_physical_plan(&test_plan(10)).await?;
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<QueryStageSchedulerEvent>(10);
+        let event = QueryStageSchedulerEvent::JobSubmitted {
+            job_id: "job-1".to_string(),
+            job_name: "job-name".to_string(),
+            session_id: ctx.session_id(),
+            job_settings: HashMap::new(),
+            queued_at: 0,
+            submitted_at: 0,
+            resubmit: false,
+            plan,
+        };
+        query_stage_scheduler
+            .state
+            .task_manager
+            .queue_job("job-1", "job-name", 0)
+            .await?;
+        query_stage_scheduler.on_receive(event, &tx, &rx).await?;
+
+        let next_event = rx.recv().await.unwrap();
+        assert!(matches!(
+            next_event,
+            QueryStageSchedulerEvent::ReservationOffering(ref reservations) if reservations.len() == 2
         ));
+        assert_eq!(test.slot_metrics().reserved_slots, 2);
 
         Ok(())
     }
@@ -0,0 +1,331 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Paged retrieval of the results of a job from its persisted result partitions
+
+use async_trait::async_trait;
+use ballista_core::client::BallistaClient;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::PartitionLocation;
+use datafusion::arrow::ipc::writer::StreamWriter;
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::StreamExt;
+
+/// Reads the result partitions of a job from where they were persisted
+#[async_trait]
+pub trait ResultPartitionReader: Send + Sync {
+    async fn read_partition(
+        &self,
+        location: &PartitionLocation,
+    ) -> Result<SendableRecordBatchStream>;
+}
+
+/// Fetches result partitions from the executors which wrote them over Arrow Flight
+#[derive(Default)]
+pub struct FlightResultPartitionReader {}
+
+#[async_trait]
+impl ResultPartitionReader for FlightResultPartitionReader {
+    async fn read_partition(
+        &self,
+        location: &PartitionLocation,
+    ) -> Result<SendableRecordBatchStream> {
+        let metadata = location.executor_meta.as_ref().ok_or_else(|| {
+            BallistaError::Internal("Received empty executor metadata".to_owned())
+        })?;
+        let partition_id = location.partition_id.as_ref().ok_or_else(|| {
+            BallistaError::Internal("Received empty partition id".to_owned())
+        })?;
+        let host = metadata.host.as_str();
+        let port = metadata.port as u16;
+        let mut client = BallistaClient::try_new(host, port).await?;
+        client
+            .fetch_partition(
+                &metadata.id,
+                &partition_id.clone().into(),
+                &location.path,
+                host,
+                port,
+                location.checksum.as_ref().map(|checksum| checksum.crc32),
+            )
+            .await
+    }
+}
+
+/// Rows of a result partition which fall into a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageSlice {
+    /// Index of the partition in the job's result partitions
+    pub partition: usize,
+    /// Rows of the partition to skip before the page starts
+    pub skip: usize,
+    /// Rows of the partition in the page
+    pub take: usize,
+}
+
+/// Index of the first row of each result partition of a job, built from the row counts the
+/// partitions were written with
+#[derive(Debug, Clone)]
+pub struct ResultRowIndex {
+    /// Index of the first row and number of rows of each partition
+    partitions: Vec<(usize, usize)>,
+    total_rows: usize,
+}
+
+impl ResultRowIndex {
+    pub fn try_new(locations: &[PartitionLocation]) -> Result<Self> {
+        let mut partitions = Vec::with_capacity(locations.len());
+        let mut total_rows = 0;
+        for location in locations {
+            let num_rows = location
+                .partition_stats
+                .as_ref()
+                .map(|stats| stats.num_rows)
+                .filter(|num_rows| *num_rows >= 0)
+                .ok_or_else(|| {
+                    BallistaError::General(format!(
+                        "Unknown row count of result partition {}",
+                        location.path
+                    ))
+                })? as usize;
+            partitions.push((total_rows, num_rows));
+            total_rows += num_rows;
+        }
+        Ok(Self {
+            partitions,
+            total_rows,
+        })
+    }
+
+    /// Number of rows in all the result partitions
+    pub fn total_rows(&self) -> usize {
+        self.total_rows
+    }
+
+    /// The rows of each partition which fall into the page of at most `limit` rows starting
+    /// at row `offset`
+    pub fn page(&self, offset: usize, limit: usize) -> Vec<PageSlice> {
+        let end = offset.saturating_add(limit).min(self.total_rows);
+        self.partitions
+            .iter()
+            .enumerate()
+            .filter_map(|(partition, (first_row, num_rows))| {
+                let start = offset.max(*first_row);
+                let stop = end.min(first_row + num_rows);
+                (start < stop).then_some(PageSlice {
+                    partition,
+                    skip: start - first_row,
+                    take: stop - start,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Read a page of the results of a job and encode it as an Arrow IPC stream. Only the
+/// partitions holding rows of the page are read.
+pub async fn read_results_page(
+    reader: &dyn ResultPartitionReader,
+    locations: &[PartitionLocation],
+    offset: usize,
+    limit: usize,
+) -> Result<(Vec<u8>, usize)> {
+    let index = ResultRowIndex::try_new(locations)?;
+
+    let mut writer: Option<StreamWriter<Vec<u8>>> = None;
+    for slice in index.page(offset, limit) {
+        let mut stream = reader.read_partition(&locations[slice.partition]).await?;
+        let writer = match writer.as_mut() {
+            Some(writer) => writer,
+            None => writer.insert(StreamWriter::try_new(vec![], &stream.schema())?),
+        };
+
+        let mut skip = slice.skip;
+        let mut take = slice.take;
+        while take > 0 {
+            let batch: RecordBatch = match stream.next().await {
+                Some(batch) => batch?,
+                None => break,
+            };
+            if skip >= batch.num_rows() {
+                skip -= batch.num_rows();
+                continue;
+            }
+            let length = (batch.num_rows() - skip).min(take);
+            writer.write(&batch.slice(skip, length))?;
+            skip = 0;
+            take -= length;
+        }
+    }
+    let data = match writer {
+        Some(mut writer) => {
+            writer.finish()?;
+            writer.into_inner()?
+        }
+        None => vec![],
+    };
+
+    Ok((data, index.total_rows()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::serde::protobuf::PartitionStats;
+    use datafusion::arrow::array::{Int32Array, UInt32Array};
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::arrow::ipc::reader::StreamReader;
+    use datafusion::physical_plan::memory::MemoryStream;
+    use std::io::Cursor;
+    use std::sync::Arc;
+
+    /// Reads partitions of batches with the partition in the first and the row in the
+    /// second column
+    struct TestPartitionReader {
+        batches: Vec<Vec<RecordBatch>>,
+    }
+
+    #[async_trait]
+    impl ResultPartitionReader for TestPartitionReader {
+        async fn read_partition(
+            &self,
+            location: &PartitionLocation,
+        ) -> Result<SendableRecordBatchStream> {
+            let partition = location.map_partition_id as usize;
+            Ok(Box::pin(MemoryStream::try_new(
+                self.batches[partition].clone(),
+                test_schema(),
+                None,
+            )?))
+        }
+    }
+
+    fn test_schema() -> Arc<Schema> {
+        Arc::new(Schema::new(vec![
+            Field::new("partition", DataType::UInt32, false),
+            Field::new("row", DataType::Int32, false),
+        ]))
+    }
+
+    fn test_batch(partition: u32, rows: std::ops::Range<i32>) -> RecordBatch {
+        RecordBatch::try_new(
+            test_schema(),
+            vec![
+                Arc::new(UInt32Array::from(vec![partition; rows.len()])),
+                Arc::new(Int32Array::from_iter_values(rows)),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn test_location(partition: u32, num_rows: i64) -> PartitionLocation {
+        PartitionLocation {
+            map_partition_id: partition,
+            partition_stats: Some(PartitionStats {
+                num_rows,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_row_index_page() -> Result<()> {
+        let index = ResultRowIndex::try_new(&[
+            test_location(0, 10),
+            test_location(1, 0),
+            test_location(2, 5),
+        ])?;
+        assert_eq!(index.total_rows(), 15);
+
+        assert_eq!(
+            index.page(8, 4),
+            vec![
+                PageSlice {
+                    partition: 0,
+                    skip: 8,
+                    take: 2
+                },
+                PageSlice {
+                    partition: 2,
+                    skip: 0,
+                    take: 2
+                }
+            ]
+        );
+        assert_eq!(
+            index.page(12, 10),
+            vec![PageSlice {
+                partition: 2,
+                skip: 2,
+                take: 3
+            }]
+        );
+        assert!(index.page(15, 10).is_empty());
+
+        assert!(ResultRowIndex::try_new(&[test_location(0, -1)]).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_results_page() -> Result<()> {
+        let reader = TestPartitionReader {
+            batches: vec![
+                vec![test_batch(0, 0..4), test_batch(0, 4..10)],
+                vec![test_batch(1, 0..5)],
+            ],
+        };
+        let locations = vec![test_location(0, 10), test_location(1, 5)];
+
+        let (data, total_rows) = read_results_page(&reader, &locations, 3, 9).await?;
+        assert_eq!(total_rows, 15);
+
+        let batches = StreamReader::try_new(Cursor::new(data), None)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let rows: Vec<(u32, i32)> = batches
+            .iter()
+            .flat_map(|batch| {
+                let partitions = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<UInt32Array>()
+                    .unwrap();
+                let rows = batch
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<Int32Array>()
+                    .unwrap();
+                (0..batch.num_rows())
+                    .map(|i| (partitions.value(i), rows.value(i)))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let expected: Vec<(u32, i32)> = (3..10)
+            .map(|row| (0, row))
+            .chain((0..2).map(|row| (1, row)))
+            .collect();
+        assert_eq!(rows, expected);
+
+        // a page past the last row is empty
+        let (data, total_rows) = read_results_page(&reader, &locations, 15, 10).await?;
+        assert!(data.is_empty());
+        assert_eq!(total_rows, 15);
+
+        Ok(())
+    }
+}
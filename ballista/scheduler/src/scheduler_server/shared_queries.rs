@@ -0,0 +1,205 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! De-duplication of identical queries submitted while one of them is running, so that their
+//! results are computed once and shared by all of their jobs
+
+use std::collections::HashMap;
+
+use parking_lot::Mutex;
+
+/// Outcome of joining the running jobs of identical queries
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SharedQuery<J> {
+    /// No identical query is running, the job runs and computes the results
+    Leader(J),
+    /// The job waits for the results of the running job of an identical query
+    Follower { leader_job_id: String },
+}
+
+#[derive(Debug)]
+struct SharedQueriesState<J> {
+    /// Running job computing the results of each fingerprint
+    leaders: HashMap<u64, String>,
+    /// Fingerprints of the running jobs
+    fingerprints: HashMap<String, u64>,
+    /// Jobs waiting for the results of each running job, in the order they joined
+    followers: HashMap<String, Vec<(String, J)>>,
+}
+
+impl<J> Default for SharedQueriesState<J> {
+    fn default() -> Self {
+        Self {
+            leaders: HashMap::new(),
+            fingerprints: HashMap::new(),
+            followers: HashMap::new(),
+        }
+    }
+}
+
+/// Jobs of identical queries, by the fingerprint of their results. The first job of a query
+/// runs, and the jobs of identical queries planned while it runs wait for it to complete and
+/// share its result partitions instead of running themselves.
+#[derive(Debug)]
+pub(crate) struct SharedQueries<J> {
+    state: Mutex<SharedQueriesState<J>>,
+}
+
+impl<J> Default for SharedQueries<J> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(SharedQueriesState::default()),
+        }
+    }
+}
+
+impl<J> SharedQueries<J> {
+    /// Join the job of a query with the fingerprint of its results. The job becomes the
+    /// leader of the query unless another job of it is running, in which case it waits for
+    /// that job's results.
+    pub(crate) fn join(&self, job_id: &str, fingerprint: u64, job: J) -> SharedQuery<J> {
+        let mut state = self.state.lock();
+        if let Some(leader_job_id) = state.leaders.get(&fingerprint).cloned() {
+            state
+                .followers
+                .entry(leader_job_id.clone())
+                .or_default()
+                .push((job_id.to_owned(), job));
+            SharedQuery::Follower { leader_job_id }
+        } else {
+            state.leaders.insert(fingerprint, job_id.to_owned());
+            state.fingerprints.insert(job_id.to_owned(), fingerprint);
+            SharedQuery::Leader(job)
+        }
+    }
+
+    /// Remove a completed job, returns the jobs waiting for its results
+    pub(crate) fn complete(&self, job_id: &str) -> Vec<(String, J)> {
+        let mut state = self.state.lock();
+        if let Some(fingerprint) = state.fingerprints.remove(job_id) {
+            state.leaders.remove(&fingerprint);
+        }
+        state.followers.remove(job_id).unwrap_or_default()
+    }
+
+    /// Stop a job from waiting for the results of another job, returns the job if it waited
+    pub(crate) fn remove_follower(&self, job_id: &str) -> Option<J> {
+        let mut state = self.state.lock();
+        state.followers.values_mut().find_map(|followers| {
+            let index = followers.iter().position(|(id, _)| id == job_id)?;
+            Some(followers.remove(index).1)
+        })
+    }
+
+    /// Remove the leaders which are no longer alive, e.g. because their job failed to be
+    /// submitted, returns the jobs which waited for their results
+    pub(crate) fn release_stale_leaders(
+        &self,
+        is_alive: impl Fn(&str) -> bool,
+    ) -> Vec<(String, J)> {
+        let mut state = self.state.lock();
+        let stale: Vec<(u64, String)> = state
+            .leaders
+            .iter()
+            .filter(|(_, job_id)| !is_alive(job_id))
+            .map(|(fingerprint, job_id)| (*fingerprint, job_id.clone()))
+            .collect();
+        let mut followers = vec![];
+        for (fingerprint, job_id) in stale {
+            state.leaders.remove(&fingerprint);
+            state.fingerprints.remove(&job_id);
+            followers.extend(state.followers.remove(&job_id).unwrap_or_default());
+        }
+        followers
+    }
+
+    /// Whether a job waits for the results of another job
+    pub(crate) fn is_follower(&self, job_id: &str) -> bool {
+        self.state
+            .lock()
+            .followers
+            .values()
+            .any(|followers| followers.iter().any(|(id, _)| id == job_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_queries() {
+        let queries = SharedQueries::default();
+        assert_eq!(queries.join("job-1", 1, "a"), SharedQuery::Leader("a"));
+        assert_eq!(
+            queries.join("job-2", 1, "b"),
+            SharedQuery::Follower {
+                leader_job_id: "job-1".to_owned()
+            }
+        );
+        assert_eq!(
+            queries.join("job-3", 1, "c"),
+            SharedQuery::Follower {
+                leader_job_id: "job-1".to_owned()
+            }
+        );
+        assert_eq!(queries.join("job-4", 2, "d"), SharedQuery::Leader("d"));
+        assert!(queries.is_follower("job-2"));
+        assert!(!queries.is_follower("job-1"));
+
+        // A cancelled follower no longer waits
+        assert_eq!(queries.remove_follower("job-2"), Some("b"));
+        assert_eq!(queries.remove_follower("job-2"), None);
+        assert!(!queries.is_follower("job-2"));
+
+        assert_eq!(queries.complete("job-1"), vec![("job-3".to_owned(), "c")]);
+        assert!(!queries.is_follower("job-3"));
+
+        // The next job of the query leads once the previous leader completed
+        assert_eq!(queries.join("job-5", 1, "e"), SharedQuery::Leader("e"));
+        assert!(queries.complete("job-4").is_empty());
+        assert!(queries.complete("job-5").is_empty());
+    }
+
+    #[test]
+    fn test_stale_leaders() {
+        let queries = SharedQueries::default();
+        assert_eq!(queries.join("job-1", 1, "a"), SharedQuery::Leader("a"));
+        assert_eq!(queries.join("job-2", 2, "b"), SharedQuery::Leader("b"));
+        assert!(matches!(
+            queries.join("job-3", 1, "c"),
+            SharedQuery::Follower { .. }
+        ));
+        assert!(matches!(
+            queries.join("job-4", 2, "d"),
+            SharedQuery::Follower { .. }
+        ));
+
+        // The followers of the leaders which are no longer alive are released
+        assert!(queries.release_stale_leaders(|_| true).is_empty());
+        assert_eq!(
+            queries.release_stale_leaders(|job_id| job_id != "job-1"),
+            vec![("job-3".to_owned(), "c")]
+        );
+        assert!(!queries.is_follower("job-3"));
+        assert!(queries.is_follower("job-4"));
+
+        // The next job of the query leads in place of the stale leader
+        assert_eq!(queries.join("job-5", 1, "e"), SharedQuery::Leader("e"));
+        assert_eq!(queries.complete("job-2"), vec![("job-4".to_owned(), "d")]);
+    }
+}
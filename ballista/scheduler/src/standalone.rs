@@ -46,7 +46,8 @@ pub async fn new_standalone_scheduler() -> Result<SocketAddr> {
             "localhost:50050".to_owned(),
             cluster,
             BallistaCodec::default(),
-            SchedulerConfig::default(),
+            // Only the gRPC service is served
+            SchedulerConfig::default().with_rest_api(false),
             metrics_collector,
         );
 
@@ -0,0 +1,629 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Tables whose files are bucketed by the hash of some of their columns, as Hive and Iceberg
+//! write them, and joins of such tables reading their matching buckets without a shuffle
+
+use std::any::Any;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::{DataType, SchemaRef};
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::config::ConfigOptions;
+use datafusion::datasource::file_format::FileFormat;
+use datafusion::datasource::listing::ListingTable;
+use datafusion::datasource::provider::TableProviderFactory;
+use datafusion::datasource::TableProvider;
+use datafusion::error::{DataFusionError, Result};
+use datafusion::execution::context::{SessionState, TaskContext};
+use datafusion::logical_expr::{
+    CreateExternalTable, Expr, TableProviderFilterPushDown, TableType,
+};
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+use datafusion::physical_plan::expressions::{Column, PhysicalSortExpr};
+use datafusion::physical_plan::file_format::ParquetExec;
+use datafusion::physical_plan::filter::FilterExec;
+use datafusion::physical_plan::joins::{HashJoinExec, PartitionMode};
+use datafusion::physical_plan::projection::ProjectionExec;
+use datafusion::physical_plan::repartition::RepartitionExec;
+use datafusion::physical_plan::sorts::sort_preserving_merge::SortPreservingMergeExec;
+use datafusion::physical_plan::{
+    with_new_children_if_necessary, DisplayFormatType, ExecutionPlan, Partitioning,
+    SendableRecordBatchStream, Statistics,
+};
+use log::{debug, warn};
+use object_store::path::Path;
+
+use crate::state::table_statistics::file_scan_config;
+
+/// Option of `CREATE EXTERNAL TABLE` listing the comma separated columns the files of the
+/// table are bucketed by
+pub const BUCKET_COLUMNS_OPTION: &str = "bucket_columns";
+/// Option of `CREATE EXTERNAL TABLE` with the number of buckets of the table
+pub const NUM_BUCKETS_OPTION: &str = "num_buckets";
+
+/// How the files of a table are bucketed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketSpec {
+    /// Columns whose hash chooses the bucket of a row, with their types
+    pub columns: Vec<(String, DataType)>,
+    pub num_buckets: usize,
+}
+
+/// Makes the tables created with the [BUCKET_COLUMNS_OPTION] and [NUM_BUCKETS_OPTION]
+/// options bucketed, leaving the others to the factory it wraps
+pub struct BucketedTableFactory {
+    inner: Arc<dyn TableProviderFactory>,
+}
+
+impl BucketedTableFactory {
+    pub fn new(inner: Arc<dyn TableProviderFactory>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl TableProviderFactory for BucketedTableFactory {
+    async fn create(
+        &self,
+        state: &SessionState,
+        cmd: &CreateExternalTable,
+    ) -> Result<Arc<dyn TableProvider>> {
+        let columns = cmd.options.get(BUCKET_COLUMNS_OPTION);
+        let num_buckets = cmd.options.get(NUM_BUCKETS_OPTION);
+        let (columns, num_buckets) = match (columns, num_buckets) {
+            (None, None) => return self.inner.create(state, cmd).await,
+            (Some(columns), Some(num_buckets)) => (columns, num_buckets),
+            _ => {
+                return Err(DataFusionError::Plan(format!(
+                    "Bucketed table {} needs both the {BUCKET_COLUMNS_OPTION} and \
+                     {NUM_BUCKETS_OPTION} options",
+                    cmd.name
+                )))
+            }
+        };
+        let num_buckets = match num_buckets.parse::<usize>() {
+            Ok(num_buckets) if num_buckets > 0 => num_buckets,
+            _ => {
+                return Err(DataFusionError::Plan(format!(
+                    "Invalid number of buckets {num_buckets:?} of table {}",
+                    cmd.name
+                )))
+            }
+        };
+
+        let mut cmd = cmd.clone();
+        cmd.options.remove(BUCKET_COLUMNS_OPTION);
+        cmd.options.remove(NUM_BUCKETS_OPTION);
+        let inner = self.inner.create(state, &cmd).await?;
+        let schema = inner.schema();
+        let columns = columns
+            .split(',')
+            .map(|column| {
+                let field = schema.field_with_name(column.trim())?;
+                Ok((field.name().to_owned(), field.data_type().clone()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if columns.is_empty() {
+            return Err(DataFusionError::Plan(format!(
+                "Bucketed table {} has no bucket columns",
+                cmd.name
+            )));
+        }
+
+        Ok(Arc::new(BucketedTable {
+            inner,
+            spec: BucketSpec {
+                columns,
+                num_buckets,
+            },
+        }))
+    }
+}
+
+/// Wrap the table factories of a session so that its tables can be bucketed
+pub fn with_bucketed_tables(mut state: SessionState) -> SessionState {
+    for factory in state.table_factories_mut().values_mut() {
+        *factory = Arc::new(BucketedTableFactory::new(factory.clone()));
+    }
+    state
+}
+
+/// A table whose files are bucketed. Its scans read one bucket per partition, the bucket of
+/// a file being given by a `bucket=N` or `*_bucket=N` directory, or else by the number its
+/// name starts with, as in Hive's `000003_0`. Tables with files whose bucket is unknown are
+/// scanned as if they were not bucketed.
+pub struct BucketedTable {
+    inner: Arc<dyn TableProvider>,
+    spec: BucketSpec,
+}
+
+#[async_trait]
+impl TableProvider for BucketedTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        self.inner.table_type()
+    }
+
+    async fn scan(
+        &self,
+        state: &SessionState,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let plan = self.inner.scan(state, projection, filters, limit).await?;
+        let format = match self.inner.as_any().downcast_ref::<ListingTable>() {
+            Some(table) => table.options().format.clone(),
+            None => return Ok(plan),
+        };
+        let config = match file_scan_config(plan.as_ref()) {
+            Some(config) => config,
+            None => return Ok(plan),
+        };
+
+        let mut buckets = vec![vec![]; self.spec.num_buckets];
+        for file in config.file_groups.iter().flatten() {
+            match bucket_id(&file.object_meta.location) {
+                Some(bucket) if bucket < self.spec.num_buckets => {
+                    buckets[bucket].push(file.clone())
+                }
+                _ => {
+                    warn!(
+                        "File {} is not in one of the {} buckets of its table, scanning \
+                         the table as if it was not bucketed",
+                        file.object_meta.location, self.spec.num_buckets
+                    );
+                    return Ok(plan);
+                }
+            }
+        }
+        let predicate = plan
+            .as_any()
+            .downcast_ref::<ParquetExec>()
+            .and_then(|exec| exec.predicate().cloned());
+        let mut config = config.clone();
+        config.file_groups = buckets;
+        let plan = format
+            .create_physical_plan(state, config, predicate.as_ref())
+            .await?;
+        Ok(Arc::new(BucketedScanExec::new(plan, self.spec.clone())))
+    }
+
+    fn supports_filter_pushdown(
+        &self,
+        filter: &Expr,
+    ) -> Result<TableProviderFilterPushDown> {
+        self.inner.supports_filter_pushdown(filter)
+    }
+
+    fn statistics(&self) -> Option<Statistics> {
+        self.inner.statistics()
+    }
+}
+
+/// Bucket of a file given by its path, if any
+fn bucket_id(path: &Path) -> Option<usize> {
+    for part in path.parts() {
+        if let Some((name, value)) = part.as_ref().split_once('=') {
+            if name == "bucket" || name.ends_with("_bucket") {
+                return value.parse().ok();
+            }
+        }
+    }
+    let name = path.filename()?;
+    let digits = name
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(name.len());
+    name[..digits].parse().ok()
+}
+
+/// Marks the scan of a bucketed table, which outputs one partition per bucket, while the
+/// plan is optimized. It is removed from the plan by [StoragePartitionedJoins].
+#[derive(Debug)]
+pub struct BucketedScanExec {
+    input: Arc<dyn ExecutionPlan>,
+    spec: BucketSpec,
+}
+
+impl BucketedScanExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, spec: BucketSpec) -> Self {
+        Self { input, spec }
+    }
+}
+
+impl ExecutionPlan for BucketedScanExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn benefits_from_input_partitioning(&self) -> bool {
+        // Keep other rules from splitting or merging the buckets
+        false
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(BucketedScanExec::new(
+            children[0].clone(),
+            self.spec.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                let columns: Vec<&str> = self
+                    .spec
+                    .columns
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect();
+                write!(
+                    f,
+                    "BucketedScanExec: columns=[{}], buckets={}",
+                    columns.join(", "),
+                    self.spec.num_buckets
+                )
+            }
+        }
+    }
+
+    fn statistics(&self) -> Statistics {
+        self.input.statistics()
+    }
+}
+
+/// Physical optimizer rule planning the partitioned hash joins of tables bucketed alike on
+/// their join keys without shuffling them: partition N of the join reads bucket N of both
+/// tables. It has to run after the other rules, as it removes the [BucketedScanExec]s from
+/// the plan.
+///
+/// Both tables must be bucketed by the same hash function, e.g. both written by Hive, into
+/// the same number of buckets, on columns of the same types which the join keys include.
+#[derive(Default)]
+pub struct StoragePartitionedJoins {}
+
+impl PhysicalOptimizerRule for StoragePartitionedJoins {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let plan = align_joins(plan, true)?;
+        plan.transform_up(
+            &|plan| match plan.as_any().downcast_ref::<BucketedScanExec>() {
+                Some(exec) => Ok(Transformed::Yes(exec.input.clone())),
+                None => Ok(Transformed::No(plan)),
+            },
+        )
+    }
+
+    fn name(&self) -> &str {
+        "storage_partitioned_joins"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+/// Remove the exchanges of the joins of bucketed tables, `exchange_parent` telling whether
+/// the plan is the input of an exchange or the root of the plan
+fn align_joins(
+    plan: Arc<dyn ExecutionPlan>,
+    exchange_parent: bool,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let any = plan.as_any();
+    let exchange = any.is::<RepartitionExec>()
+        || any.is::<CoalescePartitionsExec>()
+        || any.is::<SortPreservingMergeExec>();
+    let children = plan
+        .children()
+        .into_iter()
+        .map(|child| align_joins(child, exchange))
+        .collect::<Result<Vec<_>>>()?;
+    let plan: Arc<dyn ExecutionPlan> =
+        with_new_children_if_necessary(plan, children)?.into();
+
+    let join = match plan.as_any().downcast_ref::<HashJoinExec>() {
+        Some(join) if *join.partition_mode() == PartitionMode::Partitioned => join,
+        _ => return Ok(plan),
+    };
+    let (left_keys, right_keys): (Vec<Column>, Vec<Column>) =
+        join.on().iter().cloned().unzip();
+    let (left, right) = match (
+        bucketed_input(join.left(), &left_keys, false),
+        bucketed_input(join.right(), &right_keys, false),
+    ) {
+        (Some(left), Some(right)) if left.is_aligned_with(&right) => (left, right),
+        _ => return Ok(plan),
+    };
+    debug!(
+        "Joining the {} buckets of the inputs of {:?} without shuffling them",
+        left.spec.num_buckets, join
+    );
+
+    let partitioning = plan.output_partitioning();
+    let aligned = plan.with_new_children(vec![left.plan, right.plan])?;
+    if matches!(partitioning, Partitioning::Hash(_, _)) && !exchange_parent {
+        // The operators above the join may combine its output with inputs hash
+        // partitioned the way the join output was
+        Ok(Arc::new(RepartitionExec::try_new(aligned, partitioning)?))
+    } else {
+        Ok(aligned)
+    }
+}
+
+/// Input of a join reading a bucketed table, with its hash repartition removed
+struct BucketedInput {
+    plan: Arc<dyn ExecutionPlan>,
+    spec: BucketSpec,
+    /// Columns of the table the join keys are, with their types
+    keys: Vec<(String, DataType)>,
+}
+
+impl BucketedInput {
+    /// Whether the rows with the same join keys are in the same buckets of both inputs
+    fn is_aligned_with(&self, other: &BucketedInput) -> bool {
+        self.spec.num_buckets == other.spec.num_buckets
+            && self.spec.columns.len() == other.spec.columns.len()
+            && self
+                .spec
+                .columns
+                .iter()
+                .zip(&other.spec.columns)
+                .all(|(left, right)| {
+                    left.1 == right.1
+                        && self.keys.iter().zip(&other.keys).any(
+                            |(left_key, right_key)| {
+                                left_key == left && right_key == right
+                            },
+                        )
+                })
+    }
+}
+
+/// The input of a join reading the buckets of a table through operators which keep rows
+/// in their partition, without its hash repartition, `keys` being the join keys in the
+/// schema of `plan`. The round robin repartition the optimizer adds below the hash
+/// repartition to raise the parallelism of the scan is removed too.
+fn bucketed_input(
+    plan: &Arc<dyn ExecutionPlan>,
+    keys: &[Column],
+    repartitioned: bool,
+) -> Option<BucketedInput> {
+    let any = plan.as_any();
+    if let Some(scan) = any.downcast_ref::<BucketedScanExec>() {
+        let schema = scan.schema();
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let field = schema.field(key.index());
+                (field.name().to_owned(), field.data_type().clone())
+            })
+            .collect();
+        return Some(BucketedInput {
+            plan: plan.clone(),
+            spec: scan.spec.clone(),
+            keys,
+        });
+    }
+    if let Some(repartition) = any.downcast_ref::<RepartitionExec>() {
+        return match repartition.partitioning() {
+            Partitioning::Hash(_, _) if !repartitioned => {
+                bucketed_input(repartition.input(), keys, true)
+            }
+            Partitioning::RoundRobinBatch(_) if repartitioned => {
+                bucketed_input(repartition.input(), keys, true)
+            }
+            _ => None,
+        };
+    }
+
+    let keys = if let Some(projection) = any.downcast_ref::<ProjectionExec>() {
+        keys.iter()
+            .map(|key| {
+                let (expr, _) = projection.expr().get(key.index())?;
+                expr.as_any().downcast_ref::<Column>().cloned()
+            })
+            .collect::<Option<Vec<_>>>()?
+    } else if any.is::<CoalesceBatchesExec>() || any.is::<FilterExec>() {
+        keys.to_vec()
+    } else {
+        return None;
+    };
+    let input = bucketed_input(&plan.children()[0], &keys, repartitioned)?;
+    Some(BucketedInput {
+        plan: plan.clone().with_new_children(vec![input.plan]).ok()?,
+        ..input
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::Int64Array;
+    use datafusion::physical_plan::collect;
+    use datafusion::prelude::{SessionConfig, SessionContext};
+    use std::fs;
+
+    /// Write a table of two buckets of `(k, v)` rows bucketed by `k` modulo 2
+    fn write_bucketed_table(dir: &std::path::Path, rows: &[(i64, i64)]) {
+        fs::create_dir_all(dir).unwrap();
+        for bucket in 0..2i64 {
+            let data: String = rows
+                .iter()
+                .filter(|(k, _)| k % 2 == bucket)
+                .map(|(k, v)| format!("{k},{v}\n"))
+                .collect();
+            fs::write(dir.join(format!("00000{bucket}_0.csv")), data).unwrap();
+        }
+    }
+
+    async fn bucketed_context(
+        dir: &std::path::Path,
+        config: SessionConfig,
+    ) -> Result<SessionContext> {
+        write_bucketed_table(&dir.join("t1"), &[(1, 10), (2, 20), (3, 30), (4, 40)]);
+        write_bucketed_table(&dir.join("t2"), &[(1, 100), (2, 200), (3, 300), (5, 500)]);
+
+        let state = with_bucketed_tables(SessionContext::with_config(config).state());
+        let mut rules = state.physical_optimizers().to_vec();
+        rules.push(Arc::new(StoragePartitionedJoins::default()));
+        let ctx = SessionContext::with_state(state.with_physical_optimizer_rules(rules));
+        for table in ["t1", "t2"] {
+            let location = dir.join(table);
+            ctx.sql(&format!(
+                "CREATE EXTERNAL TABLE {table} (k BIGINT, v BIGINT) STORED AS CSV \
+                 LOCATION '{}/' OPTIONS ('bucket_columns' 'k', 'num_buckets' '2')",
+                location.display()
+            ))
+            .await?;
+        }
+        Ok(ctx)
+    }
+
+    /// Number of exchanges shuffling the inputs of the joins of a plan
+    fn count_join_exchanges(plan: &Arc<dyn ExecutionPlan>, below_join: bool) -> usize {
+        let own = (below_join && plan.as_any().is::<RepartitionExec>()) as usize;
+        let below_join = below_join || plan.as_any().is::<HashJoinExec>();
+        own + plan
+            .children()
+            .iter()
+            .map(|child| count_join_exchanges(child, below_join))
+            .sum::<usize>()
+    }
+
+    #[test]
+    fn test_bucket_id() {
+        assert_eq!(bucket_id(&Path::from("t/000003_0")), Some(3));
+        assert_eq!(
+            bucket_id(&Path::from("t/id_bucket=7/data.parquet")),
+            Some(7)
+        );
+        assert_eq!(bucket_id(&Path::from("t/part-0.parquet")), None);
+    }
+
+    #[tokio::test]
+    async fn test_storage_partitioned_join() -> Result<()> {
+        let config = SessionConfig::new()
+            .with_target_partitions(2)
+            .with_repartition_joins(true)
+            .set_bool("datafusion.optimizer.enable_round_robin_repartition", false);
+        check_storage_partitioned_join(config).await
+    }
+
+    #[tokio::test]
+    async fn test_storage_partitioned_join_round_robin() -> Result<()> {
+        // The default config repartitions the scans of fewer buckets than target
+        // partitions round robin before hash repartitioning them
+        let config = SessionConfig::new().with_target_partitions(4);
+        check_storage_partitioned_join(config).await
+    }
+
+    async fn check_storage_partitioned_join(config: SessionConfig) -> Result<()> {
+        let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let ctx = bucketed_context(&dir, config).await?;
+
+        let plan = ctx
+            .sql("SELECT t1.k, t1.v + t2.v FROM t1 JOIN t2 ON t1.k = t2.k")
+            .await?
+            .create_physical_plan()
+            .await?;
+        let displayed = format!(
+            "{}",
+            datafusion::physical_plan::displayable(plan.as_ref()).indent()
+        );
+        assert_eq!(count_join_exchanges(&plan, false), 0, "{displayed}");
+        assert!(!displayed.contains("BucketedScanExec"), "{displayed}");
+        let batches = collect(plan, ctx.task_ctx()).await?;
+        let mut rows: Vec<(i64, i64)> = batches
+            .iter()
+            .flat_map(|batch| {
+                let keys = batch
+                    .column(0)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap();
+                let sums = batch
+                    .column(1)
+                    .as_any()
+                    .downcast_ref::<Int64Array>()
+                    .unwrap();
+                (0..batch.num_rows()).map(|row| (keys.value(row), sums.value(row)))
+            })
+            .collect();
+        rows.sort();
+        assert_eq!(rows, vec![(1, 110), (2, 220), (3, 330)]);
+
+        // Joins on other columns than the bucket columns are shuffled as usual
+        let plan = ctx
+            .sql("SELECT t1.k FROM t1 JOIN t2 ON t1.v = t2.v")
+            .await?
+            .create_physical_plan()
+            .await?;
+        assert!(count_join_exchanges(&plan, false) >= 2);
+
+        fs::remove_dir_all(dir).unwrap();
+        Ok(())
+    }
+}
@@ -22,42 +22,55 @@ use std::iter::FromIterator;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use datafusion::arrow::datatypes::{Schema, SchemaRef};
+use datafusion::physical_plan::analyze::AnalyzeExec;
 use datafusion::physical_plan::display::DisplayableExecutionPlan;
 use datafusion::physical_plan::{
     accept, ExecutionPlan, ExecutionPlanVisitor, Partitioning,
 };
 use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
+use itertools::Itertools;
 use log::{error, info, warn};
 
 use ballista_core::error::{BallistaError, Result};
-use ballista_core::execution_plans::{ShuffleWriterExec, UnresolvedShuffleExec};
+use ballista_core::execution_plans::{
+    ResultExport, ShuffleReaderExec, ShuffleWriterExec, UnresolvedShuffleExec,
+    SHUFFLE_PARTITIONS_READ_METRIC, SHUFFLE_ROWS_READ_METRIC,
+};
 use ballista_core::serde::protobuf::failed_task::FailedReason;
 use ballista_core::serde::protobuf::job_status::Status;
 use ballista_core::serde::protobuf::{
     self, execution_graph_stage::StageType, FailedTask, JobStatus, ResultLost,
-    RunningJob, SuccessfulJob, TaskStatus,
+    RunningJob, SuccessfulJob, TaskAttempt, TaskAttemptState, TaskStatus,
+};
+use ballista_core::serde::protobuf::{
+    job_status, CancelledJob, FailedJob, ShuffleWritePartition,
 };
-use ballista_core::serde::protobuf::{job_status, FailedJob, ShuffleWritePartition};
-use ballista_core::serde::protobuf::{task_status, RunningTask};
+use ballista_core::serde::protobuf::{task_status, RunningTask, SuccessfulTask};
 use ballista_core::serde::scheduler::{
-    ExecutorMetadata, PartitionId, PartitionLocation, PartitionStats,
+    ExecutorMetadata, PartitionId, PartitionLocation, PartitionStats, TaskResources,
 };
 use ballista_core::serde::BallistaCodec;
 use datafusion_proto::physical_plan::AsExecutionPlan;
 
 use crate::display::print_stage_metrics;
-use crate::planner::DistributedPlanner;
+use crate::planner::{shared_scan_key, DistributedPlanner};
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 use crate::scheduler_server::timestamp_millis;
 pub(crate) use crate::state::execution_graph::execution_stage::{
-    ExecutionStage, FailedStage, ResolvedStage, StageOutput, SuccessfulStage, TaskInfo,
-    UnresolvedStage,
+    ExecutionStage, FailedStage, ResolvedStage, RunningStage, StageOutput,
+    SuccessfulStage, TaskInfo, UnresolvedStage,
 };
+use crate::state::executor_manager::ExecutorReservation;
 use crate::state::task_manager::UpdatedStages;
 
 mod execution_stage;
 
+/// Maximum number of finished task attempts kept by a job. The oldest successful attempts,
+/// then the oldest failed ones, are dropped beyond it.
+const MAX_TASK_ATTEMPTS: usize = 10_000;
+
 /// Represents the DAG for a distributed query plan.
 ///
 /// A distributed query plan consists of a set of stages which must be executed sequentially.
@@ -130,6 +143,44 @@ pub struct ExecutionGraph {
     /// Failed stage attempts, record the failed stage attempts to limit the retry times.
     /// Map from Stage ID -> Set<Stage_ATTPMPT_NUM>
     failed_stage_attempts: HashMap<usize, HashSet<usize>>,
+    /// Resources required by each task of a stage, Map from Stage ID -> TaskResources
+    task_resources: HashMap<usize, TaskResources>,
+    /// Whether the job runs the input of an EXPLAIN ANALYZE, whose stages are reported with
+    /// their metrics once the job succeeds
+    explain_analyze: bool,
+    /// Labels attached to the job by its session
+    labels: HashMap<String, String>,
+    /// Settings of the job overriding the ones of its session
+    settings: HashMap<String, String>,
+    /// ID of the group of the job, empty if it belongs to no group
+    job_group: String,
+    /// Attempts of the tasks of the job which finished, in the order they finished, at
+    /// most [MAX_TASK_ATTEMPTS] of them
+    task_attempts: Vec<TaskAttempt>,
+    /// Maximum number of task failures in an attempt of a stage before the stage, and the
+    /// job, fails. 0 means that only the failures of each task are limited.
+    max_stage_task_failures: usize,
+    /// Whether stages fail when their shuffle readers read another number of rows than the
+    /// stages they depend on wrote
+    verify_shuffle_rows: bool,
+    /// Number of result partitions waiting to be fetched by the client after which the tasks
+    /// of the final stage are held back. 0 means that results are not buffered.
+    result_buffer: usize,
+    /// Map partitions of the final stage whose output was fetched by the client
+    consumed_result_partitions: HashSet<usize>,
+    /// Maximum number of stages running at once, 0 means no limit
+    max_concurrent_stages: usize,
+    /// Maximum number of tasks running at once of the stages annotated with a limit, keyed
+    /// by stage ID
+    stage_max_concurrent_tasks: HashMap<usize, usize>,
+    /// Maximum number of tasks running at once of every stage, 0 means no limit
+    max_concurrent_tasks: usize,
+    /// Seconds the results of the job are kept once it succeeds, 0 for the default of the
+    /// scheduler
+    result_ttl_seconds: u64,
+    /// Scan stages held back until they adopt the output of an identical stage of another
+    /// job, see [ExecutionGraph::share_stage]
+    shared_stages: HashSet<usize>,
 }
 
 #[derive(Clone, Debug)]
@@ -149,13 +200,43 @@ impl ExecutionGraph {
         session_id: &str,
         plan: Arc<dyn ExecutionPlan>,
         queued_at: u64,
+        mut planner: DistributedPlanner,
     ) -> Result<Self> {
-        let mut planner = DistributedPlanner::new();
-
+        // An EXPLAIN ANALYZE runs its input as the job, instead of analyzing only the final
+        // stage on an executor
+        let explain_analyze = plan.as_any().is::<AnalyzeExec>();
+        let plan = if explain_analyze {
+            plan.children()[0].clone()
+        } else {
+            plan
+        };
         let output_partitions = plan.output_partitioning().partition_count();
 
         let shuffle_stages = planner.plan_query_stages(job_id, plan)?;
 
+        let task_resources = shuffle_stages
+            .iter()
+            .map(|stage| {
+                let stage_id = stage.stage_id();
+                let stage: Arc<dyn ExecutionPlan> = stage.clone();
+                let resources = planner
+                    .estimate_task_resources(&stage)
+                    .with_gpus(planner.stage_gpus(stage_id));
+                (stage_id, resources)
+            })
+            .collect();
+
+        let stage_max_concurrent_tasks = shuffle_stages
+            .iter()
+            .map(|stage| {
+                (
+                    stage.stage_id(),
+                    planner.max_concurrent_tasks(stage.stage_id()),
+                )
+            })
+            .filter(|(_, max_concurrent_tasks)| *max_concurrent_tasks > 0)
+            .collect();
+
         let builder = ExecutionStageBuilder::new();
         let stages = builder.build(shuffle_stages)?;
 
@@ -173,6 +254,7 @@ impl ExecutionGraph {
                     queued_at,
                     started_at,
                     scheduler: scheduler_id.to_string(),
+                    partition_location: vec![],
                 })),
             },
             queued_at,
@@ -183,6 +265,111 @@ impl ExecutionGraph {
             output_locations: vec![],
             task_id_gen: 0,
             failed_stage_attempts: HashMap::new(),
+            task_resources,
+            explain_analyze,
+            labels: HashMap::new(),
+            settings: HashMap::new(),
+            job_group: String::new(),
+            task_attempts: vec![],
+            max_stage_task_failures: 0,
+            verify_shuffle_rows: false,
+            result_buffer: 0,
+            consumed_result_partitions: HashSet::new(),
+            max_concurrent_stages: 0,
+            stage_max_concurrent_tasks,
+            max_concurrent_tasks: 0,
+            result_ttl_seconds: 0,
+            shared_stages: HashSet::new(),
+        })
+    }
+
+    /// Attach labels to the job
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Settings of the job overriding the ones of its session
+    pub fn with_settings(mut self, settings: HashMap<String, String>) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Add the job to a group of jobs followed and cancelled together
+    pub fn with_job_group(mut self, job_group: String) -> Self {
+        self.job_group = job_group;
+        self
+    }
+
+    /// Fail a stage, and the job, once this number of its tasks failed, even if each of them
+    /// may still be retried. 0 means that only the failures of each task are limited.
+    pub fn with_max_stage_task_failures(
+        mut self,
+        max_stage_task_failures: usize,
+    ) -> Self {
+        self.max_stage_task_failures = max_stage_task_failures;
+        self
+    }
+
+    /// Fail a stage, and the job, when its shuffle readers read until their end another number
+    /// of rows than the stages it depends on wrote, which reveals a bug in the exchange of data
+    pub fn with_verify_shuffle_rows(mut self, verify_shuffle_rows: bool) -> Self {
+        self.verify_shuffle_rows = verify_shuffle_rows;
+        self
+    }
+
+    /// Run at most this number of stages at once, resolved stages wait for running stages to
+    /// complete before their tasks are scheduled. 0 means no limit.
+    pub fn with_max_concurrent_stages(mut self, max_concurrent_stages: usize) -> Self {
+        self.max_concurrent_stages = max_concurrent_stages;
+        self
+    }
+
+    /// Run at most this number of tasks of every stage at once, whatever the number of
+    /// available task slots. Stages annotated with a lower limit keep theirs. 0 means no limit.
+    pub fn with_max_concurrent_tasks(mut self, max_concurrent_tasks: usize) -> Self {
+        self.max_concurrent_tasks = max_concurrent_tasks;
+        self
+    }
+
+    /// Hold back the tasks of the final stage once this number of result partitions wait to
+    /// be fetched by the client, which then reports them as consumed with
+    /// [Self::consume_result_partitions]. 0 means that results are not buffered.
+    pub fn with_result_buffer(mut self, result_buffer: usize) -> Self {
+        self.result_buffer = result_buffer;
+        self
+    }
+
+    /// Keep the results of the job for this number of seconds once it succeeds, after which
+    /// they are deleted and reported as expired. 0 for the default of the scheduler.
+    pub fn with_result_ttl_seconds(mut self, result_ttl_seconds: u64) -> Self {
+        self.result_ttl_seconds = result_ttl_seconds;
+        self
+    }
+
+    pub fn result_ttl_seconds(&self) -> u64 {
+        self.result_ttl_seconds
+    }
+
+    /// Object store prefix the results of the job are exported to, if any
+    pub fn result_export(&self) -> Option<ResultExport> {
+        self.stages.values().find_map(|stage| {
+            stage
+                .plan()
+                .as_any()
+                .downcast_ref::<ShuffleWriterExec>()
+                .and_then(|writer| writer.result_export().cloned())
+        })
+    }
+
+    /// Directory the outputs of the stages of the job are checkpointed to, if any
+    pub fn checkpoint_dir(&self) -> Option<String> {
+        self.stages.values().find_map(|stage| {
+            stage
+                .plan()
+                .as_any()
+                .downcast_ref::<ShuffleWriterExec>()
+                .and_then(|writer| writer.checkpoint_dir().map(str::to_owned))
         })
     }
 
@@ -198,10 +385,26 @@ impl ExecutionGraph {
         self.session_id.as_str()
     }
 
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.labels
+    }
+
+    pub fn settings(&self) -> &HashMap<String, String> {
+        &self.settings
+    }
+
+    pub fn job_group(&self) -> &str {
+        self.job_group.as_str()
+    }
+
     pub fn status(&self) -> JobStatus {
         self.status.clone()
     }
 
+    pub fn queued_at(&self) -> u64 {
+        self.queued_at
+    }
+
     pub fn start_time(&self) -> u64 {
         self.start_time
     }
@@ -231,25 +434,52 @@ impl ExecutionGraph {
             .all(|s| matches!(s, ExecutionStage::Successful(_)))
     }
 
+    /// Total execution time in milliseconds of the tasks of the successful stages, which is
+    /// the time the job held task slots for
+    pub fn task_time_ms(&self) -> u128 {
+        self.stages
+            .values()
+            .map(|stage| match stage {
+                ExecutionStage::Successful(stage) => stage.task_time_ms(),
+                _ => 0,
+            })
+            .sum()
+    }
+
     pub fn is_complete(&self) -> bool {
         self.stages
             .values()
             .all(|s| matches!(s, ExecutionStage::Successful(_)))
     }
 
-    /// Revive the execution graph by converting the resolved stages to running stages
+    /// Revive the execution graph by converting the resolved stages to running stages, up to
+    /// `max_concurrent_stages` running stages in the order of their IDs.
     /// If any stages are converted, return true; else false.
     pub fn revive(&mut self) -> bool {
+        let max_new_stages = if self.max_concurrent_stages > 0 {
+            let running = self
+                .stages
+                .values()
+                .filter(|stage| matches!(stage, ExecutionStage::Running(_)))
+                .count();
+            self.max_concurrent_stages.saturating_sub(running)
+        } else {
+            usize::MAX
+        };
         let running_stages = self
             .stages
             .values()
-            .filter_map(|stage| {
-                if let ExecutionStage::Resolved(resolved_stage) = stage {
-                    Some(resolved_stage.to_running())
-                } else {
-                    None
+            .filter_map(|stage| match stage {
+                ExecutionStage::Resolved(resolved_stage)
+                    if !self.shared_stages.contains(&resolved_stage.stage_id) =>
+                {
+                    Some(resolved_stage)
                 }
+                _ => None,
             })
+            .sorted_by_key(|stage| stage.stage_id)
+            .take(max_new_stages)
+            .map(|stage| stage.to_running())
             .collect::<Vec<_>>();
 
         if running_stages.is_empty() {
@@ -265,6 +495,160 @@ impl ExecutionGraph {
         }
     }
 
+    /// Scan stages which did not start running and whose output is read by other stages,
+    /// with the key identifying their output across jobs
+    pub fn shared_scan_keys(&self) -> Vec<(usize, String)> {
+        self.stages
+            .values()
+            .filter_map(|stage| match stage {
+                ExecutionStage::Resolved(stage)
+                    if stage.inputs.is_empty() && !stage.output_links.is_empty() =>
+                {
+                    shared_scan_key(stage.plan.as_ref()).map(|key| (stage.stage_id, key))
+                }
+                _ => None,
+            })
+            .sorted()
+            .collect()
+    }
+
+    /// Hold back a scan stage which did not start running until it adopts the output of an
+    /// identical stage of another job with [Self::adopt_shared_stage], or is released with
+    /// [Self::release_shared_stage]
+    pub fn share_stage(&mut self, stage_id: usize) {
+        self.shared_stages.insert(stage_id);
+    }
+
+    /// Let a stage held back by [Self::share_stage] run its own tasks. Returns true if stages
+    /// started running.
+    pub fn release_shared_stage(&mut self, stage_id: usize) -> bool {
+        self.shared_stages.remove(&stage_id) && self.revive()
+    }
+
+    /// Locations of the output partitions of a successful stage, None if the stage did not
+    /// succeed or its output is no longer complete
+    pub fn stage_output_locations(
+        &self,
+        stage_id: usize,
+    ) -> Option<Vec<PartitionLocation>> {
+        let stage = match self.stages.get(&stage_id) {
+            Some(ExecutionStage::Successful(stage)) => stage,
+            _ => return None,
+        };
+        // The output was pushed to the stages reading it
+        let inputs = match self.stages.get(stage.output_links.first()?)? {
+            ExecutionStage::UnResolved(stage) => &stage.inputs,
+            ExecutionStage::Resolved(stage) => &stage.inputs,
+            ExecutionStage::Running(stage) => &stage.inputs,
+            ExecutionStage::Successful(stage) => &stage.inputs,
+            ExecutionStage::Failed(_) => return None,
+        };
+        let output = inputs.get(&stage_id)?;
+        output.is_complete().then(|| {
+            output
+                .partition_locations
+                .values()
+                .flatten()
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Complete a scan stage which did not start running with the output of the identical
+    /// stage of another job, given by [Self::stage_output_locations]. The stages reading it
+    /// read the files of the other job, and run the stage again themselves if they are lost.
+    pub fn adopt_shared_stage(
+        &mut self,
+        stage_id: usize,
+        locations: Vec<PartitionLocation>,
+    ) -> Result<Vec<QueryStageSchedulerEvent>> {
+        self.shared_stages.remove(&stage_id);
+        let mut stage = match self.stages.get(&stage_id) {
+            Some(ExecutionStage::Resolved(stage)) => stage.to_running(),
+            _ => {
+                warn!(
+                    "Fail to find a resolved stage {}/{} to adopt the output of another job",
+                    self.job_id, stage_id
+                );
+                return Ok(vec![]);
+            }
+        };
+        info!(
+            "Stage {}/{} adopts the output of an identical stage of another job",
+            self.job_id, stage_id
+        );
+
+        let job_id = self.job_id.clone();
+        let locations: Vec<PartitionLocation> = locations
+            .into_iter()
+            .map(|mut location| {
+                location.partition_id.job_id = job_id.clone();
+                location.partition_id.stage_id = stage_id;
+                location
+            })
+            .collect();
+        let now = timestamp_millis() as u128;
+        for partition in 0..stage.partitions {
+            let task_locations: Vec<&PartitionLocation> = locations
+                .iter()
+                .filter(|location| location.map_partition_id == partition)
+                .collect();
+            let executor_id = task_locations
+                .first()
+                .map(|location| location.executor_meta.id.clone())
+                .unwrap_or_default();
+            let partitions = task_locations
+                .into_iter()
+                .map(|location| ShuffleWritePartition {
+                    partition_id: location.partition_id.partition_id as u64,
+                    path: location.path.clone(),
+                    num_batches: location.partition_stats.num_batches().unwrap_or(0),
+                    num_rows: location.partition_stats.num_rows().unwrap_or(0),
+                    num_bytes: location.partition_stats.num_bytes().unwrap_or(0),
+                    checksum: location
+                        .checksum
+                        .map(|crc32| protobuf::ShuffleChecksum { crc32 }),
+                    checkpoint_path: location.checkpoint_path.clone().unwrap_or_default(),
+                })
+                .collect();
+            stage.task_infos[partition] = Some(TaskInfo {
+                task_id: self.next_task_id(),
+                scheduled_time: now,
+                launch_time: now,
+                start_exec_time: now,
+                end_exec_time: now,
+                finish_time: now,
+                task_status: task_status::Status::Successful(SuccessfulTask {
+                    executor_id,
+                    partitions,
+                    output_schema: None,
+                }),
+                resource_usage: None,
+            });
+        }
+        stage.stage_metrics = Some(vec![]);
+        let output_links = stage.output_links.clone();
+        self.stages.insert(stage_id, ExecutionStage::Running(stage));
+
+        let mut pipelined_stages = HashSet::new();
+        let resolved_stages = self.update_stage_output_links(
+            stage_id,
+            true,
+            locations,
+            output_links,
+            &mut pipelined_stages,
+        )?;
+        self.processing_stages_update(UpdatedStages {
+            resolved_stages: resolved_stages.into_iter().collect(),
+            pipelined_stages,
+            successful_stages: HashSet::from([stage_id]),
+            failed_stages: HashMap::new(),
+            query_failed_stages: HashSet::new(),
+            rollback_running_stages: HashMap::new(),
+            resubmit_successful_stages: HashSet::new(),
+        })
+    }
+
     /// Update task statuses and task metrics in the graph.
     /// This will also push shuffle partitions to their respective shuffle read stages.
     pub fn update_task_status(
@@ -275,6 +659,8 @@ impl ExecutionGraph {
         max_stage_failures: usize,
     ) -> Result<Vec<QueryStageSchedulerEvent>> {
         let job_id = self.job_id().to_owned();
+        let max_stage_task_failures = self.max_stage_task_failures;
+        let verify_shuffle_rows = self.verify_shuffle_rows;
         // First of all, classify the statuses by stages
         let mut job_task_statuses: HashMap<usize, Vec<TaskStatus>> = HashMap::new();
         for task_status in task_statuses {
@@ -301,16 +687,20 @@ impl ExecutionGraph {
         let mut resolved_stages = HashSet::new();
         let mut successful_stages = HashSet::new();
         let mut failed_stages = HashMap::new();
+        let mut query_failed_stages = HashSet::new();
         let mut rollback_running_stages = HashMap::new();
         let mut resubmit_successful_stages: HashMap<usize, HashSet<usize>> =
             HashMap::new();
         let mut reset_running_stages: HashMap<usize, HashSet<usize>> = HashMap::new();
+        let mut pipelined_stages = HashSet::new();
+        let mut task_attempts = vec![];
 
         for (stage_id, stage_task_statuses) in job_task_statuses {
             if let Some(stage) = self.stages.get_mut(&stage_id) {
                 if let ExecutionStage::Running(running_stage) = stage {
                     let mut locations = vec![];
-                    for task_status in stage_task_statuses.into_iter() {
+                    let mut written_schemas: Vec<SchemaRef> = vec![];
+                    for mut task_status in stage_task_statuses.into_iter() {
                         let task_stage_attempt_num =
                             task_status.stage_attempt_num as usize;
                         if task_stage_attempt_num < running_stage.stage_attempt_num {
@@ -328,12 +718,36 @@ impl ExecutionGraph {
                             partition_id
                         );
                         let operator_metrics = task_status.metrics.clone();
+                        // The schema is only validated, not kept with the task
+                        let written_schema = match &mut task_status.status {
+                            Some(task_status::Status::Successful(successful_task)) => {
+                                successful_task.output_schema.take()
+                            }
+                            _ => None,
+                        };
 
                         if !running_stage
                             .update_task_info(partition_id, task_status.clone())
                         {
                             continue;
                         }
+                        if let Some(schema) = written_schema {
+                            match Schema::try_from(&schema) {
+                                Ok(schema) => {
+                                    if !written_schemas
+                                        .iter()
+                                        .any(|written| written.as_ref() == &schema)
+                                    {
+                                        written_schemas.push(Arc::new(schema));
+                                    }
+                                }
+                                Err(e) => warn!(
+                                    "Failed to decode the output schema of task {task_identity}: {e:?}"
+                                ),
+                            }
+                        }
+                        task_attempts
+                            .push(finished_task_attempt(&executor.id, &task_status));
 
                         if let Some(task_status::Status::Failed(failed_task)) =
                             task_status.status
@@ -402,16 +816,27 @@ impl ExecutionGraph {
                                 }
                                 Some(FailedReason::ExecutionError(_)) => {
                                     failed_stages.insert(stage_id, failed_task.error);
+                                    query_failed_stages.insert(stage_id);
                                 }
                                 Some(_) => {
-                                    if failed_task.retryable
+                                    if failed_task.count_to_failures
+                                        && max_stage_task_failures > 0
+                                        && running_stage.task_failures
+                                            >= max_stage_task_failures
+                                    {
+                                        let error_msg = format!(
+                                            "Stage {} had {} task failures, fail the stage, most recent failure reason: {:?}",
+                                            stage_id, running_stage.task_failures, failed_task.error
+                                        );
+                                        error!("{}", error_msg);
+                                        failed_stages.insert(stage_id, error_msg);
+                                    } else if failed_task.retryable
                                         && failed_task.count_to_failures
                                     {
                                         if running_stage.task_failure_number(partition_id)
                                             < max_task_failures
                                         {
-                                            // TODO add new struct to track all the failed task infos
-                                            // The failure TaskInfo is ignored and set to None here
+                                            // The failure is kept in the task attempts
                                             running_stage.reset_task_info(partition_id);
                                         } else {
                                             let error_msg = format!(
@@ -422,8 +847,7 @@ impl ExecutionGraph {
                                             failed_stages.insert(stage_id, error_msg);
                                         }
                                     } else if failed_task.retryable {
-                                        // TODO add new struct to track all the failed task infos
-                                        // The failure TaskInfo is ignored and set to None here
+                                        // The failure is kept in the task attempts
                                         running_stage.reset_task_info(partition_id);
                                     }
                                 }
@@ -459,8 +883,12 @@ impl ExecutionGraph {
 
                     let is_final_successful = running_stage.is_successful()
                         && !reset_running_stages.contains_key(&stage_id);
+                    let mut rows_mismatch = None;
                     if is_final_successful {
                         successful_stages.insert(stage_id);
+                        if verify_shuffle_rows {
+                            rows_mismatch = shuffle_rows_mismatch(&job_id, running_stage);
+                        }
                         // if this stage is final successful, we want to combine the stage metrics to plan's metric set and print out the plan
                         if let Some(stage_metrics) = running_stage.stage_metrics.as_ref()
                         {
@@ -473,7 +901,29 @@ impl ExecutionGraph {
                         }
                     }
 
+                    if let Some(mismatch) = rows_mismatch {
+                        error!("{}", mismatch);
+                        successful_stages.remove(&stage_id);
+                        failed_stages.insert(stage_id, mismatch);
+                        continue;
+                    }
+
                     let output_links = running_stage.output_links.clone();
+                    // Fail before the stages reading the output fail with Arrow errors. The
+                    // schemas of the batches the tasks wrote are checked, the one of the plan
+                    // of the stage only if none of the tasks reported it.
+                    if written_schemas.is_empty() {
+                        written_schemas.push(running_stage.plan.schema());
+                    }
+                    if let Some(mismatch) = written_schemas.iter().find_map(|schema| {
+                        self.output_schema_mismatch(stage_id, schema, &output_links)
+                    }) {
+                        error!("{}", mismatch);
+                        successful_stages.remove(&stage_id);
+                        failed_stages.insert(stage_id, mismatch);
+                        query_failed_stages.insert(stage_id);
+                        continue;
+                    }
                     resolved_stages.extend(
                         &mut self
                             .update_stage_output_links(
@@ -481,6 +931,7 @@ impl ExecutionGraph {
                                 is_final_successful,
                                 locations,
                                 output_links,
+                                &mut pipelined_stages,
                             )?
                             .into_iter(),
                     );
@@ -510,6 +961,7 @@ impl ExecutionGraph {
                                     Some(FailedReason::ExecutionError(_)) => {
                                         should_ignore = false;
                                         failed_stages.insert(stage_id, failed_task.error);
+                                        query_failed_stages.insert(stage_id);
                                     }
                                     Some(FailedReason::FetchPartitionError(
                                         fetch_partiton_error,
@@ -586,6 +1038,7 @@ impl ExecutionGraph {
             self.failed_stage_attempts
                 .insert(*stage_id, HashSet::from_iter(attempts.iter().copied()));
         }
+        self.record_task_attempts(task_attempts);
 
         for (stage_id, missing_parts) in &resubmit_successful_stages {
             if let Some(stage) = self.stages.get_mut(stage_id) {
@@ -606,6 +1059,27 @@ impl ExecutionGraph {
                             failed_reason: Some(FailedReason::ResultLost(ResultLost {})),
                         });
                     }
+                } else if let ExecutionStage::Running(running_stage) = stage {
+                    // A pipelined map stage still runs when the stages reading it fail to
+                    // fetch its partitions, its tasks which wrote them run again
+                    for partition in missing_parts {
+                        if *partition >= running_stage.partitions {
+                            return Err(BallistaError::Internal(format!(
+                                "Invalid partition ID {} in map stage {}",
+                                *partition, stage_id
+                            )));
+                        }
+                        if matches!(
+                            running_stage.task_infos[*partition],
+                            Some(TaskInfo {
+                                task_status: task_status::Status::Successful(_),
+                                ..
+                            })
+                        ) {
+                            running_stage.reset_task_info(*partition);
+                        }
+                    }
+                    pipelined_stages.insert(*stage_id);
                 } else {
                     warn!(
                         "Stage {}/{} is not in Successful state when try to resubmit this stage. ",
@@ -644,15 +1118,19 @@ impl ExecutionGraph {
             }
         }
 
+        // The running map stages already run their lost tasks again
+        let resubmit_successful_stages = resubmit_successful_stages
+            .into_keys()
+            .filter(|stage_id| !pipelined_stages.contains(stage_id))
+            .collect();
         self.processing_stages_update(UpdatedStages {
             resolved_stages,
+            pipelined_stages,
             successful_stages,
             failed_stages,
+            query_failed_stages,
             rollback_running_stages,
-            resubmit_successful_stages: resubmit_successful_stages
-                .keys()
-                .cloned()
-                .collect(),
+            resubmit_successful_stages,
         })
     }
 
@@ -670,9 +1148,16 @@ impl ExecutionGraph {
             has_resolved = true;
         }
 
-        for stage_id in updated_stages.successful_stages {
-            self.succeed_stage(stage_id);
+        for stage_id in updated_stages.successful_stages.iter() {
+            self.succeed_stage(*stage_id);
         }
+        // Resolved stages held back by `max_concurrent_stages` may run once stages complete
+        let has_waiting_stages = self.max_concurrent_stages > 0
+            && !updated_stages.successful_stages.is_empty()
+            && self
+                .stages
+                .values()
+                .any(|stage| matches!(stage, ExecutionStage::Resolved(_)));
 
         // Fail the stage and also abort the job
         for (stage_id, err_msg) in &updated_stages.failed_stages {
@@ -706,6 +1191,7 @@ impl ExecutionGraph {
             events.push(QueryStageSchedulerEvent::JobRunningFailed {
                 job_id,
                 fail_message: job_err_msg,
+                query_error: !updated_stages.query_failed_stages.is_empty(),
                 queued_at: self.queued_at,
                 failed_at: timestamp_millis(),
             });
@@ -718,48 +1204,91 @@ impl ExecutionGraph {
                 queued_at: self.queued_at,
                 completed_at: timestamp_millis(),
             });
-        } else if has_resolved {
+        } else if has_resolved
+            || has_waiting_stages
+            || !updated_stages.pipelined_stages.is_empty()
+        {
             events.push(QueryStageSchedulerEvent::JobUpdated(job_id))
         }
         Ok(events)
     }
 
-    /// Return a Vec of resolvable stage ids
+    /// Return a Vec of resolvable stage ids. The IDs of the stages resolved before the stage
+    /// completed which receive its partitions are added to `pipelined_stages`.
     fn update_stage_output_links(
         &mut self,
         stage_id: usize,
         is_completed: bool,
         locations: Vec<PartitionLocation>,
         output_links: Vec<usize>,
+        pipelined_stages: &mut HashSet<usize>,
     ) -> Result<Vec<usize>> {
         let mut resolved_stages = vec![];
         let job_id = &self.job_id;
         if output_links.is_empty() {
             // If `output_links` is empty, then this is a final stage
             self.output_locations.extend(locations);
+            if self.result_buffer > 0 {
+                self.update_buffered_results()?;
+            }
         } else {
+            // The stages resolved early wait for the tasks reading the added partitions,
+            // or for any of their tasks once an input completed
+            let added_partitions: HashSet<usize> = locations
+                .iter()
+                .map(|location| location.partition_id.partition_id)
+                .collect();
             for link in output_links.iter() {
+                let pipelined_inputs = match self.stages.get(link) {
+                    Some(ExecutionStage::UnResolved(stage)) => {
+                        self.pipelined_inputs(stage)
+                    }
+                    _ => HashSet::new(),
+                };
                 // If this is an intermediate stage, we need to push its `PartitionLocation`s to the parent stage
                 if let Some(linked_stage) = self.stages.get_mut(link) {
-                    if let ExecutionStage::UnResolved(linked_unresolved_stage) =
-                        linked_stage
-                    {
-                        linked_unresolved_stage
-                            .add_input_partitions(stage_id, locations.clone())?;
-
-                        // If all tasks for this stage are complete, mark the input complete in the parent stage
-                        if is_completed {
-                            linked_unresolved_stage.complete_input(stage_id);
-                        }
+                    match linked_stage {
+                        ExecutionStage::UnResolved(linked_unresolved_stage) => {
+                            linked_unresolved_stage
+                                .add_input_partitions(stage_id, locations.clone())?;
+
+                            // If all tasks for this stage are complete, mark the input complete in the parent stage
+                            if is_completed {
+                                linked_unresolved_stage.complete_input(stage_id);
+                            }
 
-                        // If all input partitions are ready, we can resolve any UnresolvedShuffleExec in the parent stage plan
-                        if linked_unresolved_stage.resolvable() {
-                            resolved_stages.push(linked_unresolved_stage.stage_id);
+                            // If all input partitions are ready, we can resolve any UnresolvedShuffleExec in the parent stage plan.
+                            // Stages reading pipelined inputs are resolved as soon as the inputs of one of their tasks are ready.
+                            if linked_unresolved_stage.resolvable()
+                                || linked_unresolved_stage.resolvable_early(
+                                    &pipelined_inputs,
+                                    (!is_completed).then_some(&added_partitions),
+                                )
+                            {
+                                resolved_stages.push(linked_unresolved_stage.stage_id);
+                            }
+                        }
+                        // The parent stage was resolved before this stage completed
+                        ExecutionStage::Resolved(linked_resolved_stage) => {
+                            linked_resolved_stage.add_input_partitions(
+                                stage_id,
+                                locations.clone(),
+                                is_completed,
+                            )?;
+                        }
+                        ExecutionStage::Running(linked_running_stage) => {
+                            linked_running_stage.add_input_partitions(
+                                stage_id,
+                                locations.clone(),
+                                is_completed,
+                            )?;
+                            pipelined_stages.insert(*link);
+                        }
+                        _ => {
+                            return Err(BallistaError::Internal(format!(
+                                "Error updating job {job_id}: The stage {link} as the output link of stage {stage_id} should be unresolved or resolved before it completed"
+                            )));
                         }
-                    } else {
-                        return Err(BallistaError::Internal(format!(
-                            "Error updating job {job_id}: The stage {link} as the output link of stage {stage_id}  should be unresolved"
-                        )));
                     }
                 } else {
                     return Err(BallistaError::Internal(format!(
@@ -771,6 +1300,45 @@ impl ExecutionGraph {
         Ok(resolved_stages)
     }
 
+    /// IDs of the input stages of an unresolved stage whose tasks only depend on the task of
+    /// the same partition of the input stage, such that they may run before the input stage
+    /// completes. These are the input stages which do not repartition their output, read
+    /// partition by partition in the stage.
+    fn pipelined_inputs(&self, stage: &UnresolvedStage) -> HashSet<usize> {
+        crate::planner::pipelined_shuffles(&stage.plan)
+            .into_iter()
+            .filter(|input_stage_id| {
+                matches!(
+                    self.stages.get(input_stage_id),
+                    Some(input_stage) if input_stage.output_partitioning().is_none()
+                )
+            })
+            .collect()
+    }
+
+    /// Describe how the schema of the output of a stage differs from the one expected by the
+    /// operators of the stages reading it, if it does
+    fn output_schema_mismatch(
+        &self,
+        stage_id: usize,
+        output_schema: &SchemaRef,
+        output_links: &[usize],
+    ) -> Option<String> {
+        output_links.iter().find_map(|link| {
+            let plan = match self.stages.get(link) {
+                Some(ExecutionStage::UnResolved(stage)) => &stage.plan,
+                _ => return None,
+            };
+            let (reader, expected_schema) = find_shuffle_reader(plan, stage_id)?;
+            let mismatch = schema_mismatch(output_schema, &expected_schema)?;
+            Some(format!(
+                "Output of stage {stage_id} of job {} does not match the schema expected by \
+                [{reader}] in stage {link}: {mismatch}",
+                self.job_id
+            ))
+        })
+    }
+
     /// Return all the currently running stage ids
     pub fn running_stages(&self) -> Vec<usize> {
         self.stages
@@ -811,13 +1379,94 @@ impl ExecutionGraph {
             .collect::<Vec<RunningTaskInfo>>()
     }
 
+    /// Record finished task attempts, dropping the oldest ones beyond [MAX_TASK_ATTEMPTS]
+    fn record_task_attempts(&mut self, attempts: impl IntoIterator<Item = TaskAttempt>) {
+        self.task_attempts.extend(attempts);
+        compact_task_attempts(&mut self.task_attempts, MAX_TASK_ATTEMPTS);
+    }
+
+    /// The attempts of the tasks of the job, or of a single stage, which finished or are still
+    /// running, ordered by stage, partition and task ID
+    pub fn task_attempts(&self, stage_id: Option<usize>) -> Vec<TaskAttempt> {
+        let running_attempts = self.stages.values().flat_map(|stage| match stage {
+            ExecutionStage::Running(stage) => running_task_attempts(stage, None),
+            _ => vec![],
+        });
+
+        self.task_attempts
+            .iter()
+            .cloned()
+            .chain(running_attempts)
+            .filter(|attempt| {
+                stage_id.map_or(true, |stage_id| attempt.stage_id as usize == stage_id)
+            })
+            .sorted_by_key(|attempt| {
+                (attempt.stage_id, attempt.partition_id, attempt.task_id)
+            })
+            .collect()
+    }
+
+    /// IDs of the executors running tasks of the job or holding the output of its tasks
+    pub fn executor_ids(&self) -> HashSet<String> {
+        self.stages
+            .values()
+            .flat_map(|stage| match stage {
+                ExecutionStage::Running(stage) => {
+                    stage.task_infos.iter().flatten().collect::<Vec<_>>()
+                }
+                ExecutionStage::Successful(stage) => stage.task_infos.iter().collect(),
+                _ => vec![],
+            })
+            .filter_map(|task| match &task.task_status {
+                task_status::Status::Running(RunningTask { executor_id })
+                | task_status::Status::Successful(SuccessfulTask {
+                    executor_id, ..
+                }) => Some(executor_id.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Re-queue a running task so that it is scheduled again, the caller is responsible for
+    /// killing the task on its executor. Returns whether the task was still running.
+    pub fn preempt_task(&mut self, task: &RunningTaskInfo) -> bool {
+        if let Some(ExecutionStage::Running(stage)) = self.stages.get_mut(&task.stage_id)
+        {
+            stage.preempt_task(task.partition_id, task.task_id)
+        } else {
+            false
+        }
+    }
+
+    /// Re-queue the tasks of the job running on an executor which the executor does not run
+    /// anymore, as their statuses were lost while this scheduler was restarting. Returns the
+    /// re-queued tasks.
+    pub fn requeue_lost_tasks(
+        &mut self,
+        executor_id: &str,
+        running_task_ids: &HashSet<usize>,
+    ) -> Vec<RunningTaskInfo> {
+        let lost_tasks: Vec<RunningTaskInfo> = self
+            .running_tasks()
+            .into_iter()
+            .filter(|task| {
+                task.executor_id == executor_id
+                    && !running_task_ids.contains(&task.task_id)
+            })
+            .collect();
+        lost_tasks
+            .into_iter()
+            .filter(|task| self.preempt_task(task))
+            .collect()
+    }
+
     /// Total number of tasks in this plan that are ready for scheduling
     pub fn available_tasks(&self) -> usize {
         self.stages
             .values()
             .map(|stage| {
                 if let ExecutionStage::Running(stage) = stage {
-                    stage.available_tasks()
+                    self.schedulable_tasks(stage)
                 } else {
                     0
                 }
@@ -825,6 +1474,112 @@ impl ExecutionGraph {
             .sum()
     }
 
+    /// Number of tasks ready for scheduling, grouped by the resources they require
+    pub fn available_tasks_by_resources(&self) -> HashMap<TaskResources, usize> {
+        let mut available_tasks: HashMap<TaskResources, usize> = HashMap::new();
+        for (stage_id, stage) in self.stages.iter() {
+            if let ExecutionStage::Running(stage) = stage {
+                let tasks = self.schedulable_tasks(stage);
+                if tasks > 0 {
+                    *available_tasks
+                        .entry(self.task_resources(*stage_id))
+                        .or_default() += tasks;
+                }
+            }
+        }
+        available_tasks
+    }
+
+    /// Maximum number of tasks of a stage running at once, the lowest of the limit annotated
+    /// on the stage and of the limit of every stage. 0 means no limit.
+    pub fn stage_max_concurrent_tasks(&self, stage_id: usize) -> usize {
+        let stage_limit = self
+            .stage_max_concurrent_tasks
+            .get(&stage_id)
+            .copied()
+            .unwrap_or_default();
+        match (stage_limit, self.max_concurrent_tasks) {
+            (0, limit) | (limit, 0) => limit,
+            (stage_limit, limit) => stage_limit.min(limit),
+        }
+    }
+
+    /// Number of the available tasks of a running stage which may be scheduled now, at most
+    /// the limit of concurrent tasks of the stage. The tasks of the final stage are also held
+    /// back while the client has not fetched enough of the results, so that these do not pile
+    /// up on the executors.
+    fn schedulable_tasks(&self, stage: &RunningStage) -> usize {
+        let mut available_tasks = stage.available_tasks();
+        let max_concurrent_tasks = self.stage_max_concurrent_tasks(stage.stage_id);
+        if max_concurrent_tasks > 0 {
+            available_tasks = available_tasks
+                .min(max_concurrent_tasks.saturating_sub(stage.running_tasks().len()));
+        }
+        if self.result_buffer == 0 || !stage.output_links.is_empty() {
+            return available_tasks;
+        }
+
+        let buffered = self
+            .output_locations
+            .iter()
+            .map(|loc| loc.map_partition_id)
+            .filter(|partition| !self.consumed_result_partitions.contains(partition))
+            .collect::<HashSet<_>>()
+            .len();
+        let running = stage.running_tasks().len();
+        available_tasks.min(self.result_buffer.saturating_sub(buffered + running))
+    }
+
+    /// Record the map partitions of the final stage whose output was fetched by the client,
+    /// which makes room in the result buffer of the job. Returns whether tasks held back by
+    /// the buffer may now be scheduled.
+    pub fn consume_result_partitions(
+        &mut self,
+        partitions: impl IntoIterator<Item = usize>,
+    ) -> Result<bool> {
+        let mut consumed = false;
+        for partition in partitions {
+            consumed |= self.consumed_result_partitions.insert(partition);
+        }
+        if !consumed || self.result_buffer == 0 {
+            return Ok(false);
+        }
+
+        self.update_buffered_results()?;
+        Ok(self.available_tasks() > 0)
+    }
+
+    /// Publish the results which were not fetched yet in the status of a running job, for
+    /// the client to fetch them before the job completes
+    fn update_buffered_results(&mut self) -> Result<()> {
+        if let Some(Status::Running(running)) = self.status.status.as_mut() {
+            running.partition_location = self
+                .output_locations
+                .iter()
+                .filter(|loc| {
+                    !self
+                        .consumed_result_partitions
+                        .contains(&loc.map_partition_id)
+                })
+                .map(|loc| loc.clone().try_into())
+                .collect::<Result<Vec<_>>>()?;
+        }
+        Ok(())
+    }
+
+    /// Resources required by the tasks of each stage
+    pub fn stage_task_resources(&self) -> HashMap<usize, TaskResources> {
+        self.task_resources.clone()
+    }
+
+    /// Resources required by each task of the given stage
+    pub fn task_resources(&self, stage_id: usize) -> TaskResources {
+        self.task_resources
+            .get(&stage_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
     /// Get next task that can be assigned to the given executor.
     /// This method should only be called when the resulting task is immediately
     /// being launched as the status will be set to Running and it will not be
@@ -835,10 +1590,26 @@ impl ExecutionGraph {
         &mut self,
         executor_id: &str,
     ) -> Result<Option<TaskDescription>> {
+        self.pop_next_task_for_reservation(
+            &ExecutorReservation::new_free(executor_id.to_owned())
+                .with_resources(TaskResources::unbounded()),
+        )
+    }
+
+    /// Get next task that can be launched on the given reservation, only tasks requiring
+    /// the resources held by the reservation are considered.
+    /// The same rules as for [Self::pop_next_task] apply to the returned task.
+    pub fn pop_next_task_for_reservation(
+        &mut self,
+        reservation: &ExecutorReservation,
+    ) -> Result<Option<TaskDescription>> {
+        let executor_id = reservation.executor_id.as_str();
         if matches!(
             self.status,
             JobStatus {
-                status: Some(job_status::Status::Failed(_)),
+                status: Some(
+                    job_status::Status::Failed(_) | job_status::Status::Cancelled(_)
+                ),
                 ..
             }
         ) {
@@ -849,39 +1620,43 @@ impl ExecutionGraph {
         let job_id = self.job_id.clone();
         let session_id = self.session_id.clone();
 
-        let find_candidate = self.stages.iter().any(|(_stage_id, stage)| {
-            if let ExecutionStage::Running(stage) = stage {
-                stage.available_tasks() > 0
-            } else {
-                false
-            }
-        });
-        let next_task_id = if find_candidate {
+        let candidate_stage = self
+            .stages
+            .iter()
+            .find(|(stage_id, stage)| {
+                if let ExecutionStage::Running(stage) = stage {
+                    self.schedulable_tasks(stage) > 0
+                        && reservation.accepts(&self.task_resources(**stage_id))
+                } else {
+                    false
+                }
+            })
+            .map(|(stage_id, _)| *stage_id);
+        let next_task_id = if candidate_stage.is_some() {
             Some(self.next_task_id())
         } else {
             None
         };
 
-        let mut next_task = self.stages.iter_mut().find(|(_stage_id, stage)| {
-            if let ExecutionStage::Running(stage) = stage {
-                stage.available_tasks() > 0
-            } else {
-                false
-            }
+        let mut next_task = candidate_stage.and_then(|stage_id| {
+            self.stages.get_mut(&stage_id).map(|stage| (stage_id, stage))
         }).map(|(stage_id, stage)| {
             if let ExecutionStage::Running(stage) = stage {
+                stage.refresh_plan()?;
                 let (partition_id, _) = stage
                     .task_infos
                     .iter()
                     .enumerate()
-                    .find(|(_partition, info)| info.is_none())
+                    .find(|(partition, info)| {
+                        info.is_none() && stage.partition_ready(*partition)
+                    })
                     .ok_or_else(|| {
                         BallistaError::Internal(format!("Error getting next task for job {job_id}: Stage {stage_id} is ready but has no pending tasks"))
                     })?;
 
                 let partition = PartitionId {
                     job_id,
-                    stage_id: *stage_id,
+                    stage_id,
                     partition_id,
                 };
 
@@ -901,6 +1676,7 @@ impl ExecutionGraph {
                     task_status: task_status::Status::Running(RunningTask {
                         executor_id: executor_id.to_owned()
                     }),
+                    resource_usage: None,
                 };
 
                 // Set the task info to Running for new task
@@ -924,7 +1700,7 @@ impl ExecutionGraph {
         // try to find a resolved stage and convert it to the running stage
         if next_task.is_none() {
             if self.revive() {
-                next_task = self.pop_next_task(executor_id)?;
+                next_task = self.pop_next_task_for_reservation(reservation)?;
             } else {
                 next_task = None;
             }
@@ -950,11 +1726,32 @@ impl ExecutionGraph {
     pub fn reset_stages_on_lost_executor(
         &mut self,
         executor_id: &str,
+    ) -> Result<(HashSet<usize>, Vec<RunningTaskInfo>)> {
+        self.reset_stages_on_executor(executor_id, None)
+    }
+
+    /// Reset the stages of an executor which restarted as `new_executor`, like
+    /// [Self::reset_stages_on_lost_executor], except that the outputs of its successful
+    /// stages whose `shuffle_files` are all intact are read from `new_executor` instead of
+    /// being recomputed.
+    pub fn reset_stages_on_restarted_executor(
+        &mut self,
+        executor_id: &str,
+        new_executor: &ExecutorMetadata,
+        shuffle_files: &HashSet<String>,
+    ) -> Result<(HashSet<usize>, Vec<RunningTaskInfo>)> {
+        self.reset_stages_on_executor(executor_id, Some((new_executor, shuffle_files)))
+    }
+
+    fn reset_stages_on_executor(
+        &mut self,
+        executor_id: &str,
+        restarted: Option<(&ExecutorMetadata, &HashSet<String>)>,
     ) -> Result<(HashSet<usize>, Vec<RunningTaskInfo>)> {
         let mut reset = HashSet::new();
         let mut tasks_to_cancel = vec![];
         loop {
-            let reset_stage = self.reset_stages_internal(executor_id)?;
+            let reset_stage = self.reset_stages_internal(executor_id, restarted)?;
             if !reset_stage.0.is_empty() {
                 reset.extend(reset_stage.0.iter());
                 tasks_to_cancel.extend(reset_stage.1)
@@ -967,6 +1764,7 @@ impl ExecutionGraph {
     fn reset_stages_internal(
         &mut self,
         executor_id: &str,
+        restarted: Option<(&ExecutorMetadata, &HashSet<String>)>,
     ) -> Result<(HashSet<usize>, Vec<RunningTaskInfo>)> {
         let job_id = self.job_id.clone();
         // collect the input stages that need to resubmit
@@ -976,8 +1774,26 @@ impl ExecutionGraph {
         let mut rollback_resolved_stages = HashSet::new();
         let mut rollback_running_stages = HashSet::new();
         let mut resubmit_successful_stages = HashSet::new();
+        // Stages whose plans read partitions of the executor which are now read from their
+        // checkpoints or from the executor it restarted as, re-resolved with the new locations
+        let mut checkpointed_resolved_stages = HashSet::new();
+        let mut checkpointed_running_stages = HashSet::new();
+        // Successful stages whose outputs are read from the executor it restarted as
+        let mut relocated_inputs = HashSet::new();
+        // Only the outputs of successful stages are moved, the tasks of running stages on
+        // the executor run again
+        let successful_stages: HashSet<usize> = match restarted {
+            Some(_) => self
+                .stages
+                .iter()
+                .filter(|(_, stage)| matches!(stage, ExecutionStage::Successful(_)))
+                .map(|(stage_id, _)| *stage_id)
+                .collect(),
+            None => HashSet::new(),
+        };
 
         let mut empty_inputs: HashMap<usize, StageOutput> = HashMap::new();
+        let mut lost_attempts = vec![];
         // check the unresolved, resolved and running stages
         self.stages
             .iter_mut()
@@ -990,6 +1806,7 @@ impl ExecutionGraph {
                         &mut stage.inputs
                     }
                     ExecutionStage::Running(stage) => {
+                        lost_attempts.extend(running_task_attempts(stage, Some(executor_id)));
                         let reset = stage.reset_tasks(executor_id);
                         if reset > 0 {
                             warn!(
@@ -1006,13 +1823,47 @@ impl ExecutionGraph {
                 // For each stage input, check whether there are input locations match that executor
                 // and calculate the resubmit input stages if the input stages are successful.
                 let mut rollback_stage = false;
+                let mut checkpoint_found = false;
                 stage_inputs.iter_mut().for_each(|(input_stage_id, stage_output)| {
                     let mut match_found = false;
+                    // The outputs of a stage on a restarted executor are moved only if they
+                    // are all intact
+                    let relocate_to = restarted.and_then(|(new_executor, shuffle_files)| {
+                        let intact = successful_stages.contains(input_stage_id)
+                            && stage_output
+                                .partition_locations
+                                .values()
+                                .flatten()
+                                .filter(|loc| loc.executor_meta.id == executor_id)
+                                .all(|loc| shuffle_files.contains(&loc.path));
+                        intact.then_some(new_executor)
+                    });
                     stage_output.partition_locations.iter_mut().for_each(
                         |(_partition, locs)| {
                             let before_len = locs.len();
-                            locs.retain(|loc| loc.executor_meta.id != executor_id);
-                            if locs.len() < before_len {
+                            locs.retain_mut(|loc| {
+                                if loc.executor_meta.id != executor_id {
+                                    return true;
+                                }
+                                if let Some(new_executor) = relocate_to {
+                                    loc.executor_meta = new_executor.clone();
+                                    relocated_inputs.insert(*input_stage_id);
+                                    checkpoint_found = true;
+                                    return true;
+                                }
+                                // Checkpointed partitions are read from their copy instead
+                                match &loc.checkpoint_path {
+                                    Some(checkpoint_path) => {
+                                        if loc.path != *checkpoint_path {
+                                            loc.path = checkpoint_path.clone();
+                                            checkpoint_found = true;
+                                        }
+                                        true
+                                    }
+                                    None => false,
+                                }
+                            });
+                            if locs.len() < before_len {
                                 match_found = true;
                             }
                         },
@@ -1040,9 +1891,33 @@ impl ExecutionGraph {
                         }
                         _ => {}
                     }
+                } else if checkpoint_found {
+                    match stage {
+                        ExecutionStage::Resolved(_) => {
+                            checkpointed_resolved_stages.insert(*stage_id);
+                        }
+                        ExecutionStage::Running(_) => {
+                            checkpointed_running_stages.insert(*stage_id);
+                        }
+                        _ => {}
+                    }
+                    info!(
+                        "Job/stage {}/{} reads the partitions of lost Executor {} from their checkpoints or the executor it restarted as",
+                        job_id, stage_id, executor_id
+                    );
                 }
             });
 
+        // The tasks running on the lost executor failed
+        let finish_time = timestamp_millis();
+        self.record_task_attempts(lost_attempts.into_iter().map(|attempt| TaskAttempt {
+            state: TaskAttemptState::AttemptFailed as i32,
+            finish_time,
+            error: format!("Executor {executor_id} lost"),
+            retryable: true,
+            ..attempt
+        }));
+
         // check and reset the successful stages
         if !resubmit_inputs.is_empty() {
             self.stages
@@ -1067,6 +1942,20 @@ impl ExecutionGraph {
                 });
         }
 
+        if let Some((new_executor, _)) = restarted {
+            for stage_id in relocated_inputs.iter() {
+                if let Some(ExecutionStage::Successful(stage)) =
+                    self.stages.get_mut(stage_id)
+                {
+                    let moved = stage.reassign_tasks(executor_id, &new_executor.id);
+                    info!(
+                        "Moved the output of {} tasks of job/stage {}/{} from restarted Executor {} to {}",
+                        moved, job_id, stage_id, executor_id, new_executor.id
+                    );
+                }
+            }
+        }
+
         for stage_id in rollback_resolved_stages.iter() {
             self.rollback_resolved_stage(*stage_id)?;
         }
@@ -1084,11 +1973,26 @@ impl ExecutionGraph {
             self.rerun_successful_stage(*stage_id);
         }
 
+        for stage_id in checkpointed_resolved_stages.iter() {
+            self.rollback_resolved_stage(*stage_id)?;
+            self.resolve_stage(*stage_id)?;
+        }
+        for stage_id in checkpointed_running_stages.iter() {
+            let tasks = self.rollback_running_stage(
+                *stage_id,
+                HashSet::from([executor_id.to_owned()]),
+            )?;
+            all_running_tasks.extend(tasks);
+            self.resolve_stage(*stage_id)?;
+        }
+
         let mut reset_stage = HashSet::new();
         reset_stage.extend(reset_running_stage);
         reset_stage.extend(rollback_resolved_stages);
         reset_stage.extend(rollback_running_stages);
         reset_stage.extend(resubmit_successful_stages);
+        reset_stage.extend(checkpointed_resolved_stages);
+        reset_stage.extend(checkpointed_running_stages);
         Ok((reset_stage, all_running_tasks))
     }
 
@@ -1110,7 +2014,14 @@ impl ExecutionGraph {
 
     /// Convert running stage to be successful
     pub fn succeed_stage(&mut self, stage_id: usize) -> bool {
-        if let Some(ExecutionStage::Running(stage)) = self.stages.remove(&stage_id) {
+        if let Some(ExecutionStage::Running(mut stage)) = self.stages.remove(&stage_id) {
+            // The map tasks of the stage may run again with the plan
+            if let Err(e) = stage.refresh_plan() {
+                warn!(
+                    "Fail to resolve the plan of stage {}/{} with its latest inputs: {:?}",
+                    self.job_id, stage_id, e
+                );
+            }
             self.stages
                 .insert(stage_id, ExecutionStage::Successful(stage.to_successful()));
             self.clear_stage_failure(stage_id);
@@ -1223,6 +2134,19 @@ impl ExecutionGraph {
         };
     }
 
+    /// Mark the job cancelled
+    pub fn cancel_job(&mut self) {
+        self.status = JobStatus {
+            job_id: self.job_id.clone(),
+            job_name: self.job_name.clone(),
+            status: Some(Status::Cancelled(CancelledJob {
+                queued_at: self.queued_at,
+                started_at: self.start_time,
+                ended_at: timestamp_millis(),
+            })),
+        };
+    }
+
     /// Mark the job success
     pub fn succeed_job(&mut self) -> Result<()> {
         if !self.is_successful() {
@@ -1247,6 +2171,13 @@ impl ExecutionGraph {
                 queued_at: self.queued_at,
                 started_at: self.start_time,
                 ended_at: self.end_time,
+                analyzed_plan: if self.explain_analyze {
+                    self.analyzed_plan()
+                } else {
+                    String::new()
+                },
+                results_expired: false,
+                shared_job_id: String::new(),
             })),
         };
         self.end_time = SystemTime::now()
@@ -1257,6 +2188,38 @@ impl ExecutionGraph {
         Ok(())
     }
 
+    /// Report the results of the successful job as expired once its result partitions were
+    /// deleted. Returns false if the job did not succeed or its results expired already.
+    pub fn expire_results(&mut self) -> bool {
+        match &mut self.status.status {
+            Some(job_status::Status::Successful(job)) if !job.results_expired => {
+                job.results_expired = true;
+                job.partition_location.clear();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The plans of the successful stages with their metrics, from the final stage down to the
+    /// stages reading the sources
+    pub fn analyzed_plan(&self) -> String {
+        let mut stages: Vec<&SuccessfulStage> = self
+            .stages
+            .values()
+            .filter_map(|stage| match stage {
+                ExecutionStage::Successful(stage) => Some(stage),
+                _ => None,
+            })
+            .collect();
+        stages.sort_by_key(|stage| std::cmp::Reverse(stage.stage_id));
+        stages
+            .iter()
+            .map(|stage| stage.analyzed_plan())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     /// Clear the stage failure count for this stage if the stage is finally success
     fn clear_stage_failure(&mut self, stage_id: usize) {
         self.failed_stage_attempts.remove(&stage_id);
@@ -1322,6 +2285,12 @@ impl ExecutionGraph {
             })
             .collect();
 
+        let task_resources = proto
+            .task_resources
+            .into_iter()
+            .map(|(stage_id, resources)| (stage_id as usize, resources.into()))
+            .collect();
+
         Ok(ExecutionGraph {
             scheduler_id: (!proto.scheduler_id.is_empty()).then_some(proto.scheduler_id),
             job_id: proto.job_id,
@@ -1340,6 +2309,27 @@ impl ExecutionGraph {
             output_locations,
             task_id_gen: proto.task_id_gen as usize,
             failed_stage_attempts,
+            task_resources,
+            explain_analyze: proto.explain_analyze,
+            labels: proto.labels,
+            settings: proto.settings,
+            job_group: proto.job_group,
+            task_attempts: proto.task_attempts,
+            max_stage_task_failures: 0,
+            verify_shuffle_rows: false,
+            result_buffer: 0,
+            consumed_result_partitions: HashSet::new(),
+            max_concurrent_stages: 0,
+            stage_max_concurrent_tasks: proto
+                .stage_max_concurrent_tasks
+                .into_iter()
+                .map(|(stage_id, max_concurrent_tasks)| {
+                    (stage_id as usize, max_concurrent_tasks as usize)
+                })
+                .collect(),
+            max_concurrent_tasks: 0,
+            result_ttl_seconds: proto.result_ttl_seconds,
+            shared_stages: HashSet::new(),
         })
     }
 
@@ -1362,9 +2352,23 @@ impl ExecutionGraph {
                     ExecutionStage::UnResolved(stage) => {
                         StageType::UnresolvedStage(UnresolvedStage::encode(stage, codec)?)
                     }
+                    // Stages resolved before their pipelined inputs completed are resolved
+                    // again once recovered, as their plans lack the pending partitions
+                    ExecutionStage::Resolved(stage) if stage.is_pipelined() => {
+                        StageType::UnresolvedStage(UnresolvedStage::encode(
+                            stage.to_unresolved()?,
+                            codec,
+                        )?)
+                    }
                     ExecutionStage::Resolved(stage) => {
                         StageType::ResolvedStage(ResolvedStage::encode(stage, codec)?)
                     }
+                    ExecutionStage::Running(stage) if stage.is_pipelined() => {
+                        StageType::UnresolvedStage(UnresolvedStage::encode(
+                            stage.to_unresolved(HashSet::new())?,
+                            codec,
+                        )?)
+                    }
                     ExecutionStage::Running(stage) => StageType::ResolvedStage(
                         ResolvedStage::encode(stage.to_resolved(), codec)?,
                     ),
@@ -1402,6 +2406,12 @@ impl ExecutionGraph {
             })
             .collect::<Vec<_>>();
 
+        let task_resources = graph
+            .task_resources
+            .into_iter()
+            .map(|(stage_id, resources)| (stage_id as u32, resources.into()))
+            .collect();
+
         Ok(protobuf::ExecutionGraph {
             job_id: graph.job_id,
             job_name: graph.job_name,
@@ -1416,6 +2426,20 @@ impl ExecutionGraph {
             scheduler_id: graph.scheduler_id.unwrap_or_default(),
             task_id_gen: graph.task_id_gen as u32,
             failed_attempts,
+            task_resources,
+            explain_analyze: graph.explain_analyze,
+            labels: graph.labels,
+            settings: graph.settings,
+            job_group: graph.job_group,
+            task_attempts: graph.task_attempts,
+            stage_max_concurrent_tasks: graph
+                .stage_max_concurrent_tasks
+                .into_iter()
+                .map(|(stage_id, max_concurrent_tasks)| {
+                    (stage_id as u32, max_concurrent_tasks as u32)
+                })
+                .collect(),
+            result_ttl_seconds: graph.result_ttl_seconds,
         })
     }
 }
@@ -1570,6 +2594,188 @@ impl Debug for TaskDescription {
     }
 }
 
+/// Find the operator reading the output of a stage in the plan of another stage, returned with
+/// the schema it expects
+fn find_shuffle_reader(
+    plan: &Arc<dyn ExecutionPlan>,
+    stage_id: usize,
+) -> Option<(String, SchemaRef)> {
+    plan.children().iter().find_map(|child| {
+        match child.as_any().downcast_ref::<UnresolvedShuffleExec>() {
+            Some(shuffle) if shuffle.stage_id == stage_id => {
+                let reader = DisplayableExecutionPlan::new(plan.as_ref())
+                    .indent()
+                    .to_string()
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .to_owned();
+                Some((reader, shuffle.schema()))
+            }
+            Some(_) => None,
+            None => find_shuffle_reader(child, stage_id),
+        }
+    })
+}
+
+/// Describe how the rows read by the shuffle readers of a successful stage differ from the rows
+/// written by the stages it depends on, if they do. Stages whose shuffle readers did not read
+/// all their partitions until the end, e.g. because of a limit, are not checked.
+fn shuffle_rows_mismatch(job_id: &str, stage: &RunningStage) -> Option<String> {
+    let stage_metrics = stage.stage_metrics.as_ref()?;
+    let rows_written = stage
+        .inputs
+        .values()
+        .flat_map(|input| input.partition_locations.values().flatten())
+        .map(|location| location.partition_stats.num_rows())
+        .sum::<Option<u64>>()?;
+    let metric_sum = |name: &str| -> usize {
+        stage_metrics
+            .iter()
+            .filter_map(|metrics| metrics.sum_by_name(name))
+            .map(|metric| metric.as_usize())
+            .sum()
+    };
+
+    let reader_partitions = shuffle_reader_partitions(stage.plan.as_ref());
+    if reader_partitions == 0
+        || metric_sum(SHUFFLE_PARTITIONS_READ_METRIC) != reader_partitions
+    {
+        return None;
+    }
+    let rows_read = metric_sum(SHUFFLE_ROWS_READ_METRIC) as u64;
+    (rows_read != rows_written).then(|| {
+        format!(
+            "Stage {} of job {job_id} read {rows_read} rows from the stages it depends on, \
+            which wrote {rows_written} rows",
+            stage.stage_id
+        )
+    })
+}
+
+/// Number of partitions of the shuffle readers of a stage plan
+fn shuffle_reader_partitions(plan: &dyn ExecutionPlan) -> usize {
+    match plan.as_any().downcast_ref::<ShuffleReaderExec>() {
+        Some(reader) => reader.partition.len(),
+        None => plan
+            .children()
+            .iter()
+            .map(|child| shuffle_reader_partitions(child.as_ref()))
+            .sum(),
+    }
+}
+
+/// Describe the first difference between the columns of a schema and the expected ones
+fn schema_mismatch(schema: &SchemaRef, expected: &SchemaRef) -> Option<String> {
+    if schema.fields().len() != expected.fields().len() {
+        return Some(format!(
+            "{} columns instead of {}",
+            schema.fields().len(),
+            expected.fields().len()
+        ));
+    }
+    schema
+        .fields()
+        .iter()
+        .zip(expected.fields().iter())
+        .enumerate()
+        .find(|(_, (field, expected))| field.data_type() != expected.data_type())
+        .map(|(idx, (field, expected))| {
+            format!(
+                "column {idx} ({}) is {} instead of {}",
+                expected.name(),
+                field.data_type(),
+                expected.data_type()
+            )
+        })
+}
+
+/// The attempts of the running tasks of a stage, optionally only those on an executor
+fn running_task_attempts(
+    stage: &RunningStage,
+    executor_id: Option<&str>,
+) -> Vec<TaskAttempt> {
+    stage
+        .task_infos
+        .iter()
+        .enumerate()
+        .filter_map(|(partition_id, task_info)| match task_info {
+            Some(
+                task_info @ TaskInfo {
+                    task_status:
+                        task_status::Status::Running(RunningTask { executor_id: id }),
+                    ..
+                },
+            ) if executor_id.map_or(true, |executor_id| executor_id == id) => {
+                Some(TaskAttempt {
+                    task_id: task_info.task_id as u32,
+                    stage_id: stage.stage_id as u32,
+                    stage_attempt_num: stage.stage_attempt_num as u32,
+                    partition_id: partition_id as u32,
+                    executor_id: id.clone(),
+                    state: TaskAttemptState::AttemptRunning as i32,
+                    launch_time: task_info.launch_time as u64,
+                    start_exec_time: task_info.start_exec_time as u64,
+                    ..Default::default()
+                })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Drop the oldest successful attempts, then the oldest failed ones, until at most
+/// `max_attempts` are left. The failed attempts are kept longer as they explain why stages
+/// were retried.
+fn compact_task_attempts(attempts: &mut Vec<TaskAttempt>, max_attempts: usize) {
+    if attempts.len() <= max_attempts {
+        return;
+    }
+    let mut excess = attempts.len() - max_attempts;
+    attempts.retain(|attempt| {
+        if excess > 0 && attempt.state() == TaskAttemptState::AttemptSuccessful {
+            excess -= 1;
+            false
+        } else {
+            true
+        }
+    });
+    attempts.drain(..excess);
+}
+
+/// The attempt of a task which reported its final status
+fn finished_task_attempt(executor_id: &str, status: &TaskStatus) -> TaskAttempt {
+    let (state, error, retryable) = match &status.status {
+        Some(task_status::Status::Failed(failed)) => (
+            TaskAttemptState::AttemptFailed,
+            failed.error.clone(),
+            failed.retryable,
+        ),
+        Some(task_status::Status::Successful(_)) => {
+            (TaskAttemptState::AttemptSuccessful, String::new(), false)
+        }
+        _ => (TaskAttemptState::AttemptRunning, String::new(), false),
+    };
+
+    TaskAttempt {
+        task_id: status.task_id,
+        stage_id: status.stage_id,
+        stage_attempt_num: status.stage_attempt_num,
+        partition_id: status.partition_id,
+        executor_id: executor_id.to_owned(),
+        state: state as i32,
+        launch_time: status.launch_time,
+        start_exec_time: status.start_exec_time,
+        end_exec_time: status.end_exec_time,
+        finish_time: timestamp_millis(),
+        error,
+        retryable,
+        metrics: status.metrics.clone(),
+        resource_usage: status.resource_usage.clone(),
+    }
+}
+
 fn partition_to_location(
     job_id: &str,
     map_partition_id: usize,
@@ -1593,6 +2799,12 @@ fn partition_to_location(
                 Some(shuffle.num_bytes),
             ),
             path: shuffle.path,
+            checksum: shuffle.checksum.map(|checksum| checksum.crc32),
+            checkpoint_path: if shuffle.checkpoint_path.is_empty() {
+                None
+            } else {
+                Some(shuffle.checkpoint_path)
+            },
         })
         .collect()
 }
@@ -1600,20 +2812,71 @@ fn partition_to_location(
 #[cfg(test)]
 mod test {
     use std::collections::HashSet;
+    use std::sync::Arc;
 
     use crate::scheduler_server::event::QueryStageSchedulerEvent;
     use ballista_core::error::Result;
+    use ballista_core::execution_plans::{
+        ShuffleWriterExec, UnresolvedShuffleExec, SHUFFLE_PARTITIONS_READ_METRIC,
+        SHUFFLE_ROWS_READ_METRIC,
+    };
     use ballista_core::serde::protobuf::{
         self, failed_task, job_status, ExecutionError, FailedTask, FetchPartitionError,
-        IoError, JobStatus, TaskKilled,
+        IoError, JobStatus, TaskAttempt, TaskAttemptState, TaskKilled,
     };
+    use ballista_core::serde::BallistaCodec;
 
-    use crate::state::execution_graph::ExecutionGraph;
+    use crate::planner::DistributedPlanner;
+    use crate::state::execution_graph::{
+        compact_task_attempts, ExecutionGraph, ExecutionStage,
+    };
     use crate::test_utils::{
         mock_completed_task, mock_executor, mock_failed_task, test_aggregation_plan,
-        test_coalesce_plan, test_join_plan, test_two_aggregations_plan,
-        test_union_all_plan, test_union_plan,
+        test_coalesce_plan, test_explain_analyze_plan, test_join_plan,
+        test_two_aggregations_plan, test_union_all_plan, test_union_plan,
     };
+    use datafusion::arrow::datatypes::{DataType, Field, Schema};
+    use datafusion::physical_plan::coalesce_batches::CoalesceBatchesExec;
+    use datafusion::physical_plan::coalesce_partitions::CoalescePartitionsExec;
+    use datafusion::physical_plan::memory::MemoryExec;
+    use datafusion::physical_plan::ExecutionPlan;
+    use datafusion_proto::protobuf::{LogicalPlanNode, PhysicalPlanNode};
+
+    #[tokio::test]
+    async fn test_explain_analyze() -> Result<()> {
+        let mut agg_graph = test_aggregation_plan(4).await;
+        let mut analyze_graph = test_explain_analyze_plan(4).await;
+
+        drain_tasks(&mut agg_graph)?;
+        drain_tasks(&mut analyze_graph)?;
+        agg_graph.succeed_job()?;
+        analyze_graph.succeed_job()?;
+
+        match agg_graph.status().status {
+            Some(job_status::Status::Successful(job)) => {
+                assert!(job.analyzed_plan.is_empty())
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+        match analyze_graph.status().status {
+            Some(job_status::Status::Successful(job)) => {
+                // The analyzed query ran as the job, each of its stages is reported
+                let plan = job.analyzed_plan;
+                let stage_count = analyze_graph.stage_count();
+                assert!(
+                    plan.starts_with(&format!("Stage {stage_count}: ")),
+                    "{plan}"
+                );
+                assert_eq!(plan.matches("output_rows=").count(), stage_count, "{plan}");
+                assert!(plan.contains("Stage 1: partitions=1"), "{plan}");
+                assert!(plan.contains("AggregateExec"), "{plan}");
+                assert!(!plan.contains("AnalyzeExec"), "{plan}");
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
+        Ok(())
+    }
 
     #[tokio::test]
     async fn test_drain_tasks() -> Result<()> {
@@ -1694,6 +2957,81 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_expire_results() -> Result<()> {
+        let mut agg_graph = test_aggregation_plan(4).await.with_result_ttl_seconds(60);
+        // Only the results of successful jobs expire
+        assert!(!agg_graph.expire_results());
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.expire_results());
+        assert!(!agg_graph.expire_results());
+
+        match agg_graph.status().status {
+            Some(job_status::Status::Successful(job)) => {
+                assert!(job.results_expired);
+                assert!(job.partition_location.is_empty());
+            }
+            other => panic!("Expected success status but found {other:?}"),
+        }
+
+        // The time to live of the results is persisted along with their expiry
+        let codec: BallistaCodec<LogicalPlanNode, PhysicalPlanNode> =
+            BallistaCodec::default();
+        let proto = ExecutionGraph::encode_execution_graph(agg_graph, &codec)?;
+        assert_eq!(proto.result_ttl_seconds, 60);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stage_resource_usage() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.revive();
+
+        // Complete all tasks of the first stage, each reporting its resource usage
+        let mut tasks = vec![];
+        while let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            tasks.push(task);
+        }
+        let num_tasks = tasks.len() as u64;
+        assert!(num_tasks > 0);
+        let stage_id = tasks[0].partition.stage_id;
+        let statuses = tasks
+            .into_iter()
+            .enumerate()
+            .map(|(i, task)| {
+                let mut task_status = mock_completed_task(task, &executor.id);
+                task_status.resource_usage = Some(protobuf::TaskResourceUsage {
+                    user_cpu_time_us: 10,
+                    sys_cpu_time_us: 2,
+                    peak_memory_bytes: 100 * (i as u64 + 1),
+                    spilled_bytes: 5,
+                    batch_size: 1024 * (i as u64 + 1),
+                    wall_clock_time_us: 20,
+                    output_rows: 3,
+                });
+                task_status
+            })
+            .collect();
+        agg_graph.update_task_status(&executor, statuses, 1, 1)?;
+
+        let usage = match agg_graph.stages().get(&stage_id) {
+            Some(ExecutionStage::Successful(stage)) => stage.resource_usage(),
+            _ => panic!("Stage {stage_id} should be successful"),
+        };
+        assert_eq!(usage.user_cpu_time_us, 10 * num_tasks);
+        assert_eq!(usage.sys_cpu_time_us, 2 * num_tasks);
+        assert_eq!(usage.peak_memory_bytes, 100 * num_tasks);
+        assert_eq!(usage.spilled_bytes, 5 * num_tasks);
+        assert_eq!(usage.batch_size, 1024 * num_tasks);
+        assert_eq!(usage.wall_clock_time_us, 20 * num_tasks);
+        assert_eq!(usage.output_rows, 3 * num_tasks);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_reset_completed_stage_executor_lost() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -1789,6 +3127,83 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_reset_stages_on_restarted_executor() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let executor3 = mock_executor("executor-id3".to_string());
+        let mut join_graph = test_join_plan(4).await;
+        join_graph.revive();
+
+        // Complete the first stage on the executor which restarts
+        let mut shuffle_files = HashSet::new();
+        if let Some(task) = join_graph.pop_next_task(&executor1.id)? {
+            shuffle_files.insert(format!(
+                "/{}/{}/{}",
+                task.partition.job_id,
+                task.partition.stage_id,
+                task.partition.partition_id
+            ));
+            let task_status = mock_completed_task(task, &executor1.id);
+            join_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        }
+
+        // Complete the second stage
+        if let Some(task) = join_graph.pop_next_task(&executor2.id)? {
+            let task_status = mock_completed_task(task, &executor2.id);
+            join_graph.update_task_status(&executor2, vec![task_status], 1, 1)?;
+        }
+        assert_eq!(join_graph.available_tasks(), 0);
+
+        // The intact output of the first stage is read from the restarted executor, only
+        // the resolved stage reading it is resolved again
+        let reset = join_graph.reset_stages_on_restarted_executor(
+            &executor1.id,
+            &executor3,
+            &shuffle_files,
+        )?;
+        assert_eq!(reset.0.len(), 1);
+        assert_eq!(join_graph.available_tasks(), 0);
+        let executor_ids = join_graph.executor_ids();
+        assert!(!executor_ids.contains(&executor1.id));
+        assert!(executor_ids.contains(&executor3.id));
+
+        join_graph.revive();
+        assert_eq!(join_graph.available_tasks(), 4);
+        drain_tasks(&mut join_graph)?;
+        assert!(join_graph.is_successful(), "Failed to complete join plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reset_stages_on_restarted_executor_lost_output() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let executor3 = mock_executor("executor-id3".to_string());
+        let mut join_graph = test_join_plan(4).await;
+        join_graph.revive();
+
+        for executor in [&executor1, &executor2] {
+            if let Some(task) = join_graph.pop_next_task(&executor.id)? {
+                let task_status = mock_completed_task(task, &executor.id);
+                join_graph.update_task_status(executor, vec![task_status], 1, 1)?;
+            }
+        }
+
+        // Without intact shuffle files the output is computed again, as if the executor
+        // was lost
+        let reset = join_graph.reset_stages_on_restarted_executor(
+            &executor1.id,
+            &executor3,
+            &HashSet::new(),
+        )?;
+        assert_eq!(reset.0.len(), 2);
+        assert_eq!(join_graph.available_tasks(), 1);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_task_update_after_reset_stage() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -1849,6 +3264,61 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_read_checkpoints_of_lost_executor() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.revive();
+
+        // Complete the first stage on executor 1, which checkpoints its output
+        if let Some(task) = agg_graph.pop_next_task(&executor1.id)? {
+            let mut task_status = mock_completed_task(task, &executor1.id);
+            if let Some(protobuf::task_status::Status::Successful(task)) =
+                &mut task_status.status
+            {
+                for partition in task.partitions.iter_mut() {
+                    partition.checkpoint_path = format!("/checkpoint{}", partition.path);
+                }
+            }
+            agg_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        }
+
+        // 1st task in the second stage
+        if let Some(task) = agg_graph.pop_next_task(&executor2.id)? {
+            let task_status = mock_completed_task(task, &executor2.id);
+            agg_graph.update_task_status(&executor2, vec![task_status], 1, 1)?;
+        }
+
+        let reset = agg_graph.reset_stages_on_lost_executor(&executor1.id)?;
+
+        // The second stage is resolved again to read the checkpoints and the first stage,
+        // whose output is checkpointed, does not run again
+        assert_eq!(reset.0, HashSet::from([2]));
+        assert!(matches!(
+            agg_graph.stages().get(&1),
+            Some(ExecutionStage::Successful(_))
+        ));
+        match agg_graph.stages().get(&2) {
+            Some(ExecutionStage::Resolved(stage)) => {
+                let locations = stage.inputs[&1].partition_locations.values().flatten();
+                for location in locations {
+                    assert!(location.path.starts_with("/checkpoint/"));
+                }
+            }
+            _ => panic!("Expected the second stage to be resolved again"),
+        }
+
+        // Call the reset again
+        let reset = agg_graph.reset_stages_on_lost_executor(&executor1.id)?;
+        assert_eq!(reset.0.len(), 0);
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_do_not_retry_killed_task() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -1898,24 +3368,90 @@ mod test {
     }
 
     #[tokio::test]
-    async fn test_max_task_failed_count() -> Result<()> {
+    async fn test_preempt_task() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
-        let executor2 = mock_executor("executor-id2".to_string());
-        let mut agg_graph = test_aggregation_plan(2).await;
+        let mut agg_graph = test_aggregation_plan(4).await;
         // Call revive to move the leaf Resolved stages to Running
         agg_graph.revive();
 
-        // Complete the first stage
-        if let Some(task) = agg_graph.pop_next_task(&executor1.id)? {
-            let task_status = mock_completed_task(task, &executor1.id);
-            agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
-        }
+        let task = agg_graph.pop_next_task(&executor1.id)?.unwrap();
+        assert_eq!(agg_graph.available_tasks(), 0);
 
-        // 1st task in the second stage
-        let task1 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
-        let task_status1 = mock_completed_task(task1, &executor2.id);
+        let running_tasks = agg_graph.running_tasks();
+        assert_eq!(running_tasks.len(), 1);
+        assert!(agg_graph.preempt_task(&running_tasks[0]));
+        // The task is no longer running so it can not be preempted again
+        assert!(!agg_graph.preempt_task(&running_tasks[0]));
+        assert_eq!(agg_graph.available_tasks(), 1);
 
-        // 2rd task in the second stage, failed due to IOError
+        // The status of the killed task arriving late is ignored
+        let task_status = mock_failed_task(
+            task,
+            FailedTask {
+                error: "Killed".to_string(),
+                retryable: false,
+                count_to_failures: false,
+                failed_reason: Some(failed_task::FailedReason::TaskKilled(TaskKilled {})),
+            },
+        );
+        agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        assert_eq!(agg_graph.available_tasks(), 1);
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_requeue_lost_tasks() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await;
+        agg_graph.revive();
+
+        let task1 = agg_graph.pop_next_task(&executor1.id)?.unwrap();
+        let task2 = agg_graph.pop_next_task(&executor1.id)?.unwrap();
+        let task3 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
+        let available_tasks = agg_graph.available_tasks();
+
+        // Only the task executor1 does not run anymore is re-queued
+        let requeued =
+            agg_graph.requeue_lost_tasks(&executor1.id, &HashSet::from([task1.task_id]));
+        assert_eq!(requeued.len(), 1);
+        assert_eq!(requeued[0].task_id, task2.task_id);
+        assert_eq!(agg_graph.available_tasks(), available_tasks + 1);
+        assert_eq!(agg_graph.running_tasks().len(), 2);
+
+        let task_status = mock_completed_task(task1, &executor1.id);
+        agg_graph.update_task_status(&executor1, vec![task_status], 1, 1)?;
+        let task_status = mock_completed_task(task3, &executor2.id);
+        agg_graph.update_task_status(&executor2, vec![task_status], 1, 1)?;
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_task_failed_count() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(2).await;
+        // Call revive to move the leaf Resolved stages to Running
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor1.id)? {
+            let task_status = mock_completed_task(task, &executor1.id);
+            agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        }
+
+        // 1st task in the second stage
+        let task1 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
+        let task_status1 = mock_completed_task(task1, &executor2.id);
+
+        // 2rd task in the second stage, failed due to IOError
         let task2 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
         let task_status2 = mock_failed_task(
             task2.clone(),
@@ -1982,6 +3518,572 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_output_schema_mismatch() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(2).await;
+
+        // Make the final stage expect another type for the first column of the first stage
+        let output_schema = match agg_graph.stages.get(&1) {
+            Some(ExecutionStage::Resolved(stage)) => stage.plan.schema(),
+            _ => panic!("Expected stage 1 to be resolved"),
+        };
+        let expected_schema = Arc::new(Schema::new(
+            output_schema
+                .fields()
+                .iter()
+                .enumerate()
+                .map(|(idx, field)| {
+                    if idx == 0 {
+                        Field::new(field.name(), DataType::Boolean, true)
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect(),
+        ));
+        if let Some(ExecutionStage::UnResolved(stage)) = agg_graph.stages.get_mut(&2) {
+            stage.plan = Arc::new(ShuffleWriterExec::try_new(
+                agg_graph.job_id.clone(),
+                2,
+                Arc::new(CoalesceBatchesExec::new(
+                    Arc::new(UnresolvedShuffleExec::new(1, expected_schema, 1, 2)),
+                    4096,
+                )),
+                "".to_owned(),
+                None,
+            )?);
+        } else {
+            panic!("Expected stage 2 to be unresolved");
+        }
+
+        agg_graph.revive();
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let task_status = mock_completed_task(task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+
+        assert!(
+            matches!(
+                agg_graph.status,
+                JobStatus {
+                    status: Some(job_status::Status::Failed(_)),
+                    ..
+                }
+            ),
+            "Expected job status to be Failed"
+        );
+        let failure_reason = format!("{:?}", agg_graph.status);
+        assert!(failure_reason.contains(
+            "does not match the schema expected by [CoalesceBatchesExec: target_batch_size=4096] in stage 2"
+        ));
+        assert!(failure_reason.contains("instead of Boolean"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_written_schema_mismatch() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(2).await;
+
+        // The task of the first stage wrote batches of another schema than the one of its plan
+        let output_schema = match agg_graph.stages.get(&1) {
+            Some(ExecutionStage::Resolved(stage)) => stage.plan.schema(),
+            _ => panic!("Expected stage 1 to be resolved"),
+        };
+        let written_schema = Schema::new(
+            output_schema
+                .fields()
+                .iter()
+                .enumerate()
+                .map(|(idx, field)| {
+                    if idx == 0 {
+                        Field::new(field.name(), DataType::Boolean, true)
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect(),
+        );
+
+        agg_graph.revive();
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let mut task_status = mock_completed_task(task, &executor.id);
+        if let Some(protobuf::task_status::Status::Successful(successful)) =
+            task_status.status.as_mut()
+        {
+            successful.output_schema = Some((&written_schema).try_into().unwrap());
+        }
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+
+        let failure_reason = format!("{:?}", agg_graph.status);
+        assert!(
+            failure_reason.contains("does not match the schema expected by"),
+            "Expected job to fail, status is {failure_reason}"
+        );
+        assert!(failure_reason.contains("is Boolean instead of"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_shuffle_rows() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+
+        // The first stage writes one row to each of the 2 partitions read by the final stage
+        for (rows_read, expect_failure) in [([1, 1], false), ([2, 1], true)] {
+            let mut agg_graph = test_aggregation_plan(2)
+                .await
+                .with_verify_shuffle_rows(true);
+            agg_graph.revive();
+
+            let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+
+            for rows in rows_read {
+                let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+                let mut task_status = mock_completed_task(task, &executor.id);
+                task_status.metrics = vec![shuffle_read_metrics(rows)];
+                agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+            }
+
+            let failed = matches!(
+                agg_graph.status,
+                JobStatus {
+                    status: Some(job_status::Status::Failed(_)),
+                    ..
+                }
+            );
+            assert_eq!(failed, expect_failure);
+            if expect_failure {
+                assert!(format!("{:?}", agg_graph.status).contains(
+                    "read 3 rows from the stages it depends on, which wrote 2 rows"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn shuffle_read_metrics(rows: u64) -> protobuf::OperatorMetricsSet {
+        let count = |name: &str, value: u64| protobuf::OperatorMetric {
+            metric: Some(protobuf::operator_metric::Metric::Count(
+                protobuf::NamedCount {
+                    name: name.to_owned(),
+                    value,
+                },
+            )),
+        };
+        protobuf::OperatorMetricsSet {
+            metrics: vec![
+                count(SHUFFLE_ROWS_READ_METRIC, rows),
+                count(SHUFFLE_PARTITIONS_READ_METRIC, 1),
+            ],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_result_buffer() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(2).await.with_result_buffer(1);
+        agg_graph.revive();
+
+        // Complete the first stage
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let task_status = mock_completed_task(task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+
+        // A single task of the final stage runs while its result is not fetched
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let partition_id = task.partition.partition_id;
+        assert!(agg_graph.pop_next_task(&executor.id)?.is_none());
+        let task_status = mock_completed_task(task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+        assert!(agg_graph.pop_next_task(&executor.id)?.is_none());
+        assert_eq!(agg_graph.available_tasks(), 0);
+
+        // The result is published while the job runs
+        let buffered = |graph: &ExecutionGraph| match graph.status().status {
+            Some(job_status::Status::Running(running)) => running
+                .partition_location
+                .iter()
+                .map(|loc| loc.map_partition_id as usize)
+                .collect::<Vec<_>>(),
+            status => panic!("Expected job to be running, got {status:?}"),
+        };
+        assert_eq!(buffered(&agg_graph), vec![partition_id]);
+
+        // Fetching the result lets the last task run
+        assert!(agg_graph.consume_result_partitions(vec![partition_id])?);
+        assert!(buffered(&agg_graph).is_empty());
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        let task_status = mock_completed_task(task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+        assert!(agg_graph.is_successful());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_stages() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut join_graph = test_join_plan(4).await.with_max_concurrent_stages(1);
+
+        // Only one of the two leaf stages runs
+        join_graph.revive();
+        assert_eq!(join_graph.running_stages().len(), 1);
+        assert_eq!(join_graph.available_tasks(), 1);
+
+        // The other one runs once the first completes
+        let task = join_graph.pop_next_task(&executor.id)?.unwrap();
+        let first_stage = task.partition.stage_id;
+        let task_status = mock_completed_task(task, &executor.id);
+        let events = join_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, QueryStageSchedulerEvent::JobUpdated(_))));
+        join_graph.revive();
+        let running_stages = join_graph.running_stages();
+        assert_eq!(running_stages.len(), 1);
+        assert_ne!(running_stages[0], first_stage);
+
+        drain_tasks(&mut join_graph)?;
+        assert!(join_graph.is_successful(), "Failed to complete join plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_concurrent_tasks() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut agg_graph = test_aggregation_plan(4).await.with_max_concurrent_tasks(1);
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor.id)? {
+            let task_status = mock_completed_task(task, &executor.id);
+            agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+
+        // Only one task of the second stage runs at once
+        assert_eq!(agg_graph.available_tasks(), 1);
+        let task = agg_graph.pop_next_task(&executor.id)?.unwrap();
+        assert_eq!(agg_graph.available_tasks(), 0);
+        assert!(agg_graph.pop_next_task(&executor.id)?.is_none());
+
+        let task_status = mock_completed_task(task, &executor.id);
+        agg_graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        assert_eq!(agg_graph.available_tasks(), 1);
+
+        // The lowest of the limits of the stage and of the job applies
+        agg_graph.stage_max_concurrent_tasks.insert(2, 3);
+        assert_eq!(agg_graph.stage_max_concurrent_tasks(2), 1);
+        agg_graph = agg_graph.with_max_concurrent_tasks(0);
+        assert_eq!(agg_graph.stage_max_concurrent_tasks(2), 3);
+
+        drain_tasks(&mut agg_graph)?;
+        assert!(agg_graph.is_successful(), "Failed to complete agg plan");
+
+        Ok(())
+    }
+
+    /// A plan whose final stage reads the 4 partitions of the first stage one by one
+    fn test_pipelined_plan() -> Result<ExecutionGraph> {
+        let schema =
+            Arc::new(Schema::new(vec![Field::new("id", DataType::UInt32, false)]));
+        let input = Arc::new(MemoryExec::try_new(
+            &[vec![], vec![], vec![], vec![]],
+            schema.clone(),
+            None,
+        )?);
+        let mut graph = ExecutionGraph::new(
+            "localhost:50050",
+            "job",
+            "",
+            "session",
+            Arc::new(CoalescePartitionsExec::new(input)),
+            0,
+            DistributedPlanner::new(),
+        )?;
+
+        // Make the final stage read the 4 partitions of the first stage one by one
+        if let Some(ExecutionStage::UnResolved(stage)) = graph.stages.get_mut(&2) {
+            stage.plan = Arc::new(ShuffleWriterExec::try_new(
+                graph.job_id.clone(),
+                2,
+                Arc::new(CoalesceBatchesExec::new(
+                    Arc::new(UnresolvedShuffleExec::new(1, schema, 4, 4)),
+                    4096,
+                )),
+                "".to_owned(),
+                None,
+            )?);
+        } else {
+            panic!("Expected stage 2 to be unresolved");
+        }
+        Ok(graph)
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_stages() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut graph = test_pipelined_plan()?;
+
+        // The final stage runs as soon as a task of the first stage completes
+        graph.revive();
+        let task = graph.pop_next_task(&executor.id)?.unwrap();
+        let partition_id = task.partition.partition_id;
+        let task_status = mock_completed_task(task, &executor.id);
+        let events = graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, QueryStageSchedulerEvent::JobUpdated(_))));
+        graph.revive();
+        match graph.stages.get(&2) {
+            Some(ExecutionStage::Running(stage)) => {
+                assert!(stage.partition_ready(partition_id));
+                assert_eq!(stage.available_tasks(), 1);
+            }
+            _ => panic!("Expected stage 2 to be running"),
+        }
+        assert_eq!(graph.available_tasks(), 4);
+
+        // Its other tasks wait for the tasks of the same partitions of the first stage
+        let task = loop {
+            let task = graph.pop_next_task(&executor.id)?.unwrap();
+            if task.partition.stage_id == 1 {
+                break task;
+            }
+            let task_status = mock_completed_task(task, &executor.id);
+            graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        };
+        let task_status = mock_completed_task(task, &executor.id);
+        let events = graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, QueryStageSchedulerEvent::JobUpdated(_))));
+        match graph.stages.get(&2) {
+            Some(ExecutionStage::Running(stage)) => {
+                assert_eq!(stage.available_tasks() + stage.scheduled_tasks(), 2);
+            }
+            _ => panic!("Expected stage 2 to be running"),
+        }
+
+        drain_tasks(&mut graph)?;
+        assert!(graph.is_successful(), "Failed to complete pipelined plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_stage_fetch_failure() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut graph = test_pipelined_plan()?;
+        graph.revive();
+
+        let task = graph.pop_next_task(&executor.id)?.unwrap();
+        let map_partition_id = task.partition.partition_id;
+        let task_status = mock_completed_task(task, &executor.id);
+        graph.update_task_status(&executor, vec![task_status], 1, 1)?;
+        graph.revive();
+
+        // The task of the final stage fails to fetch the partition of the running first stage
+        let mut map_tasks = vec![];
+        let task = loop {
+            let task = graph.pop_next_task(&executor.id)?.unwrap();
+            if task.partition.stage_id == 2 {
+                break task;
+            }
+            map_tasks.push(task);
+        };
+        let task_status = mock_failed_task(
+            task,
+            FailedTask {
+                error: "FetchPartitionError".to_string(),
+                retryable: false,
+                count_to_failures: false,
+                failed_reason: Some(failed_task::FailedReason::FetchPartitionError(
+                    FetchPartitionError {
+                        executor_id: executor.id.clone(),
+                        map_stage_id: 1,
+                        map_partition_id: map_partition_id as u32,
+                    },
+                )),
+            },
+        );
+        let events = graph.update_task_status(&executor, vec![task_status], 4, 4)?;
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, QueryStageSchedulerEvent::JobUpdated(_))));
+
+        // The final stage waits for the map task which runs again
+        assert!(matches!(
+            graph.stages.get(&2),
+            Some(ExecutionStage::UnResolved(_))
+        ));
+        match graph.stages.get(&1) {
+            Some(ExecutionStage::Running(stage)) => {
+                assert!(stage.task_infos[map_partition_id].is_none());
+            }
+            _ => panic!("Expected stage 1 to be running"),
+        }
+
+        let task_statuses = map_tasks
+            .into_iter()
+            .map(|task| mock_completed_task(task, &executor.id))
+            .collect();
+        graph.update_task_status(&executor, task_statuses, 1, 1)?;
+        drain_tasks(&mut graph)?;
+        assert!(graph.is_successful(), "Failed to complete pipelined plan");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_stage_task_failures() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(2)
+            .await
+            .with_max_stage_task_failures(2);
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor1.id)? {
+            let task_status = mock_completed_task(task, &executor1.id);
+            agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        }
+
+        let io_error = FailedTask {
+            error: "IOError".to_string(),
+            retryable: true,
+            count_to_failures: true,
+            failed_reason: Some(failed_task::FailedReason::IoError(IoError {})),
+        };
+
+        // The 1st failure of the stage is retried
+        let task1 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
+        let task2 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
+        let task_status1 = mock_failed_task(task1, io_error.clone());
+        agg_graph.update_task_status(&executor2, vec![task_status1], 4, 4)?;
+        assert_eq!(agg_graph.available_tasks(), 1);
+        assert!(!matches!(
+            agg_graph.status,
+            JobStatus {
+                status: Some(job_status::Status::Failed(_)),
+                ..
+            }
+        ));
+
+        // The 2nd failure, of another task, fails the stage even if each task may still
+        // be retried
+        let task_status2 = mock_failed_task(task2, io_error);
+        agg_graph.update_task_status(&executor2, vec![task_status2], 4, 4)?;
+        assert!(
+            matches!(
+                agg_graph.status,
+                JobStatus {
+                    status: Some(job_status::Status::Failed(_)),
+                    ..
+                }
+            ),
+            "Expected job status to be Failed"
+        );
+        let failure_reason = format!("{:?}", agg_graph.status);
+        assert!(failure_reason.contains("Stage 2 had 2 task failures, fail the stage"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_attempts() -> Result<()> {
+        let executor1 = mock_executor("executor-id1".to_string());
+        let executor2 = mock_executor("executor-id2".to_string());
+        let mut agg_graph = test_aggregation_plan(2).await;
+        agg_graph.revive();
+
+        // Complete the first stage
+        if let Some(task) = agg_graph.pop_next_task(&executor1.id)? {
+            let task_status = mock_completed_task(task, &executor1.id);
+            agg_graph.update_task_status(&executor1, vec![task_status], 4, 4)?;
+        }
+
+        // The 1st task of the second stage keeps running and the 2nd one fails once
+        let _task1 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
+        let task2 = agg_graph.pop_next_task(&executor2.id)?.unwrap();
+        let task_status2 = mock_failed_task(
+            task2,
+            FailedTask {
+                error: "IOError".to_string(),
+                retryable: true,
+                count_to_failures: true,
+                failed_reason: Some(failed_task::FailedReason::IoError(IoError {})),
+            },
+        );
+        agg_graph.update_task_status(&executor2, vec![task_status2], 4, 4)?;
+        let _task2_retry = agg_graph.pop_next_task(&executor2.id)?.unwrap();
+
+        let attempts = agg_graph.task_attempts(Some(2));
+        let states = attempts
+            .iter()
+            .map(|attempt| (attempt.partition_id, attempt.state()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            vec![
+                (0, TaskAttemptState::AttemptRunning),
+                (1, TaskAttemptState::AttemptFailed),
+                (1, TaskAttemptState::AttemptRunning),
+            ]
+        );
+        assert_eq!(attempts[1].error, "IOError");
+        assert_eq!(attempts[1].executor_id, executor2.id);
+        assert!(attempts[1].retryable);
+
+        // The running tasks fail with their executor
+        agg_graph.reset_stages_on_lost_executor(&executor2.id)?;
+        let attempts = agg_graph.task_attempts(Some(2));
+        assert_eq!(attempts.len(), 3);
+        assert!(attempts
+            .iter()
+            .all(|attempt| attempt.state() == TaskAttemptState::AttemptFailed));
+        assert_eq!(attempts[2].error, "Executor executor-id2 lost");
+
+        let attempts = agg_graph.task_attempts(None);
+        assert_eq!(attempts.len(), 4);
+        assert_eq!(attempts[0].stage_id, 1);
+        assert_eq!(attempts[0].state(), TaskAttemptState::AttemptSuccessful);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_task_attempts() {
+        let attempt = |task_id: u32, state: TaskAttemptState| TaskAttempt {
+            task_id,
+            state: state as i32,
+            ..Default::default()
+        };
+        let mut attempts = vec![
+            attempt(0, TaskAttemptState::AttemptFailed),
+            attempt(1, TaskAttemptState::AttemptSuccessful),
+            attempt(2, TaskAttemptState::AttemptFailed),
+            attempt(3, TaskAttemptState::AttemptSuccessful),
+            attempt(4, TaskAttemptState::AttemptSuccessful),
+        ];
+
+        // The oldest successful attempts are dropped first
+        compact_task_attempts(&mut attempts, 4);
+        let task_ids = attempts.iter().map(|a| a.task_id).collect::<Vec<_>>();
+        assert_eq!(task_ids, vec![0, 2, 3, 4]);
+
+        // Then the oldest failed ones
+        compact_task_attempts(&mut attempts, 1);
+        let task_ids = attempts.iter().map(|a| a.task_id).collect::<Vec<_>>();
+        assert_eq!(task_ids, vec![2]);
+    }
+
     #[tokio::test]
     async fn test_long_delayed_failed_task_after_executor_lost() -> Result<()> {
         let executor1 = mock_executor("executor-id1".to_string());
@@ -2238,9 +4340,13 @@ mod test {
                 } else {
                     // Job is failed after exceeds the max_stage_failures
                     assert_eq!(stage_events.len(), 1);
+                    // Lost data does not count as a failure of the query
                     assert!(matches!(
                         stage_events[0],
-                        QueryStageSchedulerEvent::JobRunningFailed { .. }
+                        QueryStageSchedulerEvent::JobRunningFailed {
+                            query_error: false,
+                            ..
+                        }
                     ));
                     // Stage 2 is still running
                     let running_stage = agg_graph.running_stages();
@@ -2730,7 +4836,10 @@ mod test {
         assert_eq!(stage_events.len(), 1);
         assert!(matches!(
             stage_events[0],
-            QueryStageSchedulerEvent::JobRunningFailed { .. }
+            QueryStageSchedulerEvent::JobRunningFailed {
+                query_error: true,
+                ..
+            }
         ));
 
         drain_tasks(&mut agg_graph)?;
@@ -2748,6 +4857,52 @@ mod test {
     //     todo!()
     // }
 
+    #[tokio::test]
+    async fn test_adopt_shared_stage() -> Result<()> {
+        let executor = mock_executor("executor-id1".to_string());
+        let mut producer = test_aggregation_plan(4).await;
+        let mut consumer = test_aggregation_plan(4).await;
+
+        let keys = producer.shared_scan_keys();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(consumer.shared_scan_keys(), keys);
+        let stage_id = keys[0].0;
+
+        // The scan stage of the consumer waits for the one of the producer
+        consumer.share_stage(stage_id);
+        consumer.revive();
+        assert_eq!(consumer.available_tasks(), 0);
+
+        producer.revive();
+        while producer.stage_output_locations(stage_id).is_none() {
+            let task = producer.pop_next_task(&executor.id)?.unwrap();
+            assert_eq!(task.partition.stage_id, stage_id);
+            let task_status = mock_completed_task(task, &executor.id);
+            producer.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+        let locations = producer.stage_output_locations(stage_id).unwrap();
+        assert!(!locations.is_empty());
+
+        let events = consumer.adopt_shared_stage(stage_id, locations)?;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            consumer.stages().get(&stage_id),
+            Some(ExecutionStage::Successful(_))
+        ));
+        assert!(consumer.shared_scan_keys().is_empty());
+
+        // Only the final stage of the consumer runs
+        consumer.revive();
+        while let Some(task) = consumer.pop_next_task(&executor.id)? {
+            assert_ne!(task.partition.stage_id, stage_id);
+            let task_status = mock_completed_task(task, &executor.id);
+            consumer.update_task_status(&executor, vec![task_status], 1, 1)?;
+        }
+        assert!(consumer.is_successful());
+
+        Ok(())
+    }
+
     fn drain_tasks(graph: &mut ExecutionGraph) -> Result<()> {
         let executor = mock_executor("executor-id1".to_string());
         while let Some(task) = graph.pop_next_task(&executor.id)? {
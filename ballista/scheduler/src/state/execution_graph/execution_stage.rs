@@ -35,7 +35,7 @@ use ballista_core::error::{BallistaError, Result};
 use ballista_core::serde::protobuf::failed_task::FailedReason;
 use ballista_core::serde::protobuf::{
     self, task_info, FailedTask, GraphStageInput, OperatorMetricsSet, ResultLost,
-    SuccessfulTask, TaskStatus,
+    SuccessfulTask, TaskResourceUsage, TaskStatus,
 };
 use ballista_core::serde::protobuf::{task_status, RunningTask};
 use ballista_core::serde::scheduler::to_proto::hash_partitioning_to_proto;
@@ -88,6 +88,18 @@ impl ExecutionStage {
         }
     }
 
+    /// Get the output partitioning of this query stage, None if its tasks do not repartition
+    /// their output
+    pub(crate) fn output_partitioning(&self) -> Option<&Partitioning> {
+        match self {
+            ExecutionStage::UnResolved(stage) => stage.output_partitioning.as_ref(),
+            ExecutionStage::Resolved(stage) => stage.output_partitioning.as_ref(),
+            ExecutionStage::Running(stage) => stage.output_partitioning.as_ref(),
+            ExecutionStage::Successful(stage) => stage.output_partitioning.as_ref(),
+            ExecutionStage::Failed(stage) => stage.output_partitioning.as_ref(),
+        }
+    }
+
     /// Get the query plan for this query stage
     pub(crate) fn plan(&self) -> &dyn ExecutionPlan {
         match self {
@@ -143,6 +155,9 @@ pub(crate) struct ResolvedStage {
     pub(crate) plan: Arc<dyn ExecutionPlan>,
     /// Record last attempt's failure reasons to avoid duplicate resubmits
     pub(crate) last_attempt_failure_reasons: HashSet<String>,
+    /// Whether input partitions of pipelined input stages were added since `plan` was
+    /// resolved, the plan is resolved again once needed rather than for every input task
+    pub(crate) plan_outdated: bool,
 }
 
 /// Different from the resolved stage, a running stage will
@@ -174,8 +189,15 @@ pub(crate) struct RunningStage {
     /// Track the number of failures for each partition's task attempts.
     /// The index of the Vec is the task's partition id.
     pub(crate) task_failure_numbers: Vec<usize>,
+    /// Number of the task attempts of this stage attempt which failed and counted to failures
+    pub(crate) task_failures: usize,
     /// Combined metrics of the already finished tasks in the stage, If it is None, no task is finished yet.
     pub(crate) stage_metrics: Option<Vec<MetricsSet>>,
+    /// Partitions whose tasks wait for the input partitions of pipelined input stages
+    pub(crate) waiting_partitions: HashSet<usize>,
+    /// Whether input partitions of pipelined input stages were added since `plan` was
+    /// resolved, the plan is resolved again once needed rather than for every input task
+    pub(crate) plan_outdated: bool,
 }
 
 /// If a stage finishes successfully, its task statuses and metrics will be finalized
@@ -246,6 +268,8 @@ pub(crate) struct TaskInfo {
     pub(super) finish_time: u128,
     /// Task Status
     pub(super) task_status: task_status::Status,
+    /// Resources used by the task, reported by the executor once the task finished
+    pub(super) resource_usage: Option<TaskResourceUsage>,
 }
 
 impl UnresolvedStage {
@@ -351,6 +375,30 @@ impl UnresolvedStage {
         self.inputs.iter().all(|(_, input)| input.is_complete())
     }
 
+    /// Returns true if the stage may be resolved before its `pipelined_inputs` complete,
+    /// i.e. its other inputs are complete and all the inputs of some of its tasks are
+    /// available. The other tasks wait for their inputs once the stage is running.
+    /// Only the `added_partitions` are checked if given, as the other partitions were
+    /// checked when their inputs were added.
+    pub(super) fn resolvable_early(
+        &self,
+        pipelined_inputs: &HashSet<usize>,
+        added_partitions: Option<&HashSet<usize>>,
+    ) -> bool {
+        if !self.inputs.iter().all(|(stage_id, input)| {
+            input.is_complete() || pipelined_inputs.contains(stage_id)
+        }) {
+            return false;
+        }
+        match added_partitions {
+            Some(partitions) => partitions
+                .iter()
+                .any(|partition| inputs_ready(&self.inputs, *partition)),
+            None => (0..self.plan.output_partitioning().partition_count())
+                .any(|partition| inputs_ready(&self.inputs, partition)),
+        }
+    }
+
     /// Change to the resolved state
     pub(super) fn to_resolved(&self) -> Result<ResolvedStage> {
         let input_locations = self
@@ -363,9 +411,14 @@ impl UnresolvedStage {
             &input_locations,
         )?;
 
-        // Optimize join order based on new resolved statistics
-        let optimize_join = JoinSelection::new();
-        let plan = optimize_join.optimize(plan, SessionConfig::default().options())?;
+        // Optimize join order based on new resolved statistics. The statistics of the inputs
+        // of stages resolved early are partial, and all their tasks must run the same joins.
+        let plan = if self.resolvable() {
+            let optimize_join = JoinSelection::new();
+            optimize_join.optimize(plan, SessionConfig::default().options())?
+        } else {
+            plan
+        };
 
         Ok(ResolvedStage::new(
             self.stage_id,
@@ -475,12 +528,46 @@ impl ResolvedStage {
             inputs,
             plan,
             last_attempt_failure_reasons,
+            plan_outdated: false,
         }
     }
 
+    /// Add input partitions published from a pipelined input stage, see
+    /// [RunningStage::add_input_partitions]
+    pub(super) fn add_input_partitions(
+        &mut self,
+        stage_id: usize,
+        locations: Vec<PartitionLocation>,
+        is_completed: bool,
+    ) -> Result<()> {
+        add_pipelined_input_partitions(
+            self.stage_id,
+            &mut self.inputs,
+            stage_id,
+            locations,
+            is_completed,
+        )?;
+        self.plan_outdated = true;
+        Ok(())
+    }
+
+    /// Resolve the plan again with the input partitions added since it was resolved
+    pub(super) fn refresh_plan(&mut self) -> Result<()> {
+        if self.plan_outdated {
+            self.plan = resolve_pipelined_plan(self.plan.clone(), &self.inputs)?;
+            self.plan_outdated = false;
+        }
+        Ok(())
+    }
+
+    /// Returns true if the stage was resolved before some of its inputs completed
+    pub(super) fn is_pipelined(&self) -> bool {
+        !self.inputs.values().all(|input| input.is_complete())
+    }
+
     /// Change to the running state
     pub(super) fn to_running(&self) -> RunningStage {
-        RunningStage::new(
+        let mut running = RunningStage::new(
             self.stage_id,
             self.stage_attempt_num,
             self.plan.clone(),
@@ -488,7 +575,9 @@ impl ResolvedStage {
             self.output_partitioning.clone(),
             self.output_links.clone(),
             self.inputs.clone(),
-        )
+        );
+        running.plan_outdated = self.plan_outdated;
+        running
     }
 
     /// Change to the unresolved state
@@ -538,13 +627,15 @@ impl ResolvedStage {
             last_attempt_failure_reasons: HashSet::from_iter(
                 stage.last_attempt_failure_reasons,
             ),
+            plan_outdated: false,
         })
     }
 
     pub(super) fn encode<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
-        stage: ResolvedStage,
+        mut stage: ResolvedStage,
         codec: &BallistaCodec<T, U>,
     ) -> Result<protobuf::ResolvedStage> {
+        stage.refresh_plan()?;
         let mut plan: Vec<u8> = vec![];
         U::try_from_physical_plan(stage.plan, codec.physical_extension_codec())
             .and_then(|proto| proto.try_encode(&mut plan))?;
@@ -591,6 +682,9 @@ impl RunningStage {
         output_links: Vec<usize>,
         inputs: HashMap<usize, StageOutput>,
     ) -> Self {
+        let waiting_partitions = (0..partitions)
+            .filter(|partition| !inputs_ready(&inputs, *partition))
+            .collect();
         Self {
             stage_id,
             stage_attempt_num,
@@ -601,7 +695,10 @@ impl RunningStage {
             plan,
             task_infos: vec![None; partitions],
             task_failure_numbers: vec![0; partitions],
+            task_failures: 0,
             stage_metrics: None,
+            waiting_partitions,
+            plan_outdated: false,
         }
     }
 
@@ -652,7 +749,7 @@ impl RunningStage {
 
     /// Change to the resolved state and bump the stage attempt number
     pub(super) fn to_resolved(&self) -> ResolvedStage {
-        ResolvedStage::new(
+        let mut resolved = ResolvedStage::new(
             self.stage_id,
             self.stage_attempt_num + 1,
             self.plan.clone(),
@@ -660,7 +757,9 @@ impl RunningStage {
             self.output_links.clone(),
             self.inputs.clone(),
             HashSet::new(),
-        )
+        );
+        resolved.plan_outdated = self.plan_outdated;
+        resolved
     }
 
     /// Change to the unresolved state and bump the stage attempt number
@@ -682,6 +781,62 @@ impl RunningStage {
         Ok(unresolved)
     }
 
+    /// Add input partitions published from a pipelined input stage, which was not complete
+    /// when this stage was resolved. The tasks waiting for these partitions become available.
+    pub(super) fn add_input_partitions(
+        &mut self,
+        stage_id: usize,
+        locations: Vec<PartitionLocation>,
+        is_completed: bool,
+    ) -> Result<()> {
+        let added_partitions: HashSet<usize> = locations
+            .iter()
+            .map(|location| location.partition_id.partition_id)
+            .collect();
+        add_pipelined_input_partitions(
+            self.stage_id,
+            &mut self.inputs,
+            stage_id,
+            locations,
+            is_completed,
+        )?;
+        self.plan_outdated = true;
+
+        // Only the partitions of the added locations may become ready, unless the input
+        // completed
+        let inputs = &self.inputs;
+        if is_completed {
+            self.waiting_partitions
+                .retain(|partition| !inputs_ready(inputs, *partition));
+        } else {
+            for partition in added_partitions {
+                if inputs_ready(inputs, partition) {
+                    self.waiting_partitions.remove(&partition);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the plan again with the input partitions added since it was resolved
+    pub(super) fn refresh_plan(&mut self) -> Result<()> {
+        if self.plan_outdated {
+            self.plan = resolve_pipelined_plan(self.plan.clone(), &self.inputs)?;
+            self.plan_outdated = false;
+        }
+        Ok(())
+    }
+
+    /// Returns true if the stage was resolved before some of its inputs completed
+    pub(super) fn is_pipelined(&self) -> bool {
+        !self.inputs.values().all(|input| input.is_complete())
+    }
+
+    /// Returns true if all the inputs of the task of the partition are available
+    pub(super) fn partition_ready(&self, partition_id: usize) -> bool {
+        !self.waiting_partitions.contains(&partition_id)
+    }
+
     /// Returns `true` if all tasks for this stage are successful
     pub(super) fn is_successful(&self) -> bool {
         self.task_infos.iter().all(|info| {
@@ -716,6 +871,11 @@ impl RunningStage {
         self.task_infos.iter().filter(|s| s.is_some()).count()
     }
 
+    /// Returns the combined resources used by the finished tasks in this stage
+    pub(crate) fn resource_usage(&self) -> TaskResourceUsage {
+        combine_resource_usage(self.task_infos.iter().flatten())
+    }
+
     /// Returns a vector of currently running tasks in this stage
     pub(super) fn running_tasks(&self) -> Vec<(usize, usize, usize, String)> {
         self.task_infos
@@ -733,9 +893,16 @@ impl RunningStage {
 
     /// Returns the number of tasks in this stage which are available for scheduling.
     /// If the stage is not yet resolved, then this will return `0`, otherwise it will
-    /// return the number of tasks where the task info is not yet set.
+    /// return the number of tasks where the task info is not yet set and whose inputs are
+    /// available.
     pub(super) fn available_tasks(&self) -> usize {
-        self.task_infos.iter().filter(|s| s.is_none()).count()
+        self.task_infos
+            .iter()
+            .enumerate()
+            .filter(|(partition, info)| {
+                info.is_none() && self.partition_ready(*partition)
+            })
+            .count()
     }
 
     /// Update the TaskInfo for task partition
@@ -745,7 +912,13 @@ impl RunningStage {
         status: TaskStatus,
     ) -> bool {
         debug!("Updating TaskInfo for partition {}", partition_id);
-        let task_info = self.task_infos[partition_id].as_ref().unwrap();
+        let task_info = if let Some(task_info) = self.task_infos[partition_id].as_ref() {
+            task_info
+        } else {
+            warn!("Ignore TaskStatus update with TID {} because partition {} has no running task, it may have been preempted",
+                status.task_id, partition_id);
+            return false;
+        };
         let task_id = task_info.task_id;
         if (status.task_id as usize) < task_id {
             warn!("Ignore TaskStatus update with TID {} because there is more recent task attempt with TID {} running for partition {}",
@@ -753,6 +926,7 @@ impl RunningStage {
             return false;
         }
         let scheduled_time = task_info.scheduled_time;
+        let resource_usage = status.resource_usage;
         let task_status = status.status.unwrap();
         let updated_task_info = TaskInfo {
             task_id,
@@ -765,6 +939,7 @@ impl RunningStage {
                 .unwrap()
                 .as_millis(),
             task_status: task_status.clone(),
+            resource_usage,
         };
         self.task_infos[partition_id] = Some(updated_task_info);
 
@@ -773,6 +948,9 @@ impl RunningStage {
             if failed_task.retryable {
                 self.task_failure_numbers[partition_id] += 1;
             }
+            if failed_task.count_to_failures {
+                self.task_failures += 1;
+            }
         } else {
             self.task_failure_numbers[partition_id] = 0;
         }
@@ -846,6 +1024,22 @@ impl RunningStage {
         self.task_infos[partition_id] = None;
     }
 
+    /// Reset the task info of the given task partition if it is still running with the given
+    /// task id, so that the partition is scheduled again. Returns whether the task was preempted.
+    pub(super) fn preempt_task(&mut self, partition_id: usize, task_id: usize) -> bool {
+        match self.task_infos.get(partition_id) {
+            Some(Some(TaskInfo {
+                task_id: running_task_id,
+                task_status: task_status::Status::Running(_),
+                ..
+            })) if *running_task_id == task_id => {
+                self.task_infos[partition_id] = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Reset the running and completed tasks on a given executor
     /// Returns the number of running tasks that were reset
     pub fn reset_tasks(&mut self, executor: &str) -> usize {
@@ -862,8 +1056,7 @@ impl RunningStage {
                 Some(TaskInfo {
                     task_status:
                         task_status::Status::Successful(SuccessfulTask {
-                            executor_id,
-                            partitions: _,
+                            executor_id, ..
                         }),
                     ..
                 }) if *executor == *executor_id => {
@@ -925,6 +1118,54 @@ impl Debug for RunningStage {
 }
 
 impl SuccessfulStage {
+    /// Returns the combined resources used by the tasks in this stage
+    pub(crate) fn resource_usage(&self) -> TaskResourceUsage {
+        combine_resource_usage(self.task_infos.iter())
+    }
+
+    /// Returns the total execution time in milliseconds of the tasks in this stage
+    pub(crate) fn task_time_ms(&self) -> u128 {
+        self.task_infos
+            .iter()
+            .map(|info| info.end_exec_time.saturating_sub(info.start_exec_time))
+            .sum()
+    }
+
+    /// The plan of the stage with the metrics of its operators, preceded by the rows and bytes
+    /// written by its tasks and the time elapsed from the start of the first task to the end
+    /// of the last one
+    pub(crate) fn analyzed_plan(&self) -> String {
+        let (mut rows, mut bytes) = (0, 0);
+        for info in self.task_infos.iter() {
+            if let task_status::Status::Successful(SuccessfulTask {
+                partitions, ..
+            }) = &info.task_status
+            {
+                rows += partitions.iter().map(|p| p.num_rows).sum::<u64>();
+                bytes += partitions.iter().map(|p| p.num_bytes).sum::<u64>();
+            }
+        }
+        let start = self
+            .task_infos
+            .iter()
+            .map(|info| info.start_exec_time)
+            .min();
+        let end = self.task_infos.iter().map(|info| info.end_exec_time).max();
+        let elapsed_ms = match (start, end) {
+            (Some(start), Some(end)) => end.saturating_sub(start),
+            _ => 0,
+        };
+        let plan = DisplayableBallistaExecutionPlan::new(
+            self.plan.as_ref(),
+            &self.stage_metrics,
+        )
+        .indent();
+        format!(
+            "Stage {}: partitions={}, output_rows={}, output_bytes={}, elapsed={}ms\n{}",
+            self.stage_id, self.partitions, rows, bytes, elapsed_ms, plan
+        )
+    }
+
     /// Change to the running state and bump the stage attempt number
     pub fn to_running(&self) -> RunningStage {
         let mut task_infos: Vec<Option<TaskInfo>> = Vec::new();
@@ -953,7 +1194,10 @@ impl SuccessfulStage {
             task_infos,
             // It is Ok to forget the previous task failure attempts
             task_failure_numbers: vec![0; self.partitions],
+            task_failures: 0,
             stage_metrics,
+            waiting_partitions: HashSet::new(),
+            plan_outdated: false,
         }
     }
 
@@ -986,6 +1230,7 @@ impl SuccessfulStage {
                             count_to_failures: false,
                             failed_reason: Some(FailedReason::ResultLost(ResultLost {})),
                         }),
+                        resource_usage: None,
                     };
                     reset += 1;
                 }
@@ -995,6 +1240,24 @@ impl SuccessfulStage {
         reset
     }
 
+    /// Move the successful tasks of a restarted executor, whose output is intact, to the
+    /// executor it restarted as. Returns the number of moved tasks
+    pub fn reassign_tasks(&mut self, executor: &str, new_executor: &str) -> usize {
+        let mut reassigned = 0;
+        for task in self.task_infos.iter_mut() {
+            if let task_status::Status::Successful(SuccessfulTask {
+                executor_id, ..
+            }) = &mut task.task_status
+            {
+                if *executor_id == executor {
+                    *executor_id = new_executor.to_owned();
+                    reassigned += 1;
+                }
+            }
+        }
+        reassigned
+    }
+
     pub(super) fn decode<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>(
         stage: protobuf::SuccessfulStage,
         codec: &BallistaCodec<T, U>,
@@ -1273,6 +1536,71 @@ impl StageOutput {
     pub(super) fn is_complete(&self) -> bool {
         self.complete
     }
+
+    /// Returns true if the locations of the partition are all known, which is the case
+    /// for the partitions of pipelined input stages once their task completed
+    pub(super) fn has_partition(&self, partition: usize) -> bool {
+        self.complete
+            || self
+                .partition_locations
+                .get(&partition)
+                .map(|locations| !locations.is_empty())
+                .unwrap_or(false)
+    }
+}
+
+/// Returns true if all the inputs of the task of the partition are available
+fn inputs_ready(inputs: &HashMap<usize, StageOutput>, partition: usize) -> bool {
+    inputs.values().all(|input| input.has_partition(partition))
+}
+
+/// Add the input partitions published by a pipelined input stage to the inputs of a stage
+/// resolved before the input stage completed
+fn add_pipelined_input_partitions(
+    stage_id: usize,
+    inputs: &mut HashMap<usize, StageOutput>,
+    input_stage_id: usize,
+    locations: Vec<PartitionLocation>,
+    is_completed: bool,
+) -> Result<()> {
+    let input = inputs
+        .get_mut(&input_stage_id)
+        .filter(|input| !input.is_complete())
+        .ok_or_else(|| {
+            BallistaError::Internal(format!(
+                "Error adding input partitions to stage {stage_id}, {input_stage_id} is not a pending input stage"
+            ))
+        })?;
+    for partition in locations {
+        // Drop the location of a previous attempt of the same input task
+        if let Some(partition_locations) = input
+            .partition_locations
+            .get_mut(&partition.partition_id.partition_id)
+        {
+            partition_locations.retain(|location| {
+                location.map_partition_id != partition.map_partition_id
+            });
+        }
+        input.add_partition(partition);
+    }
+    if is_completed {
+        input.complete = true;
+    }
+    Ok(())
+}
+
+/// Resolve the plan of a stage resolved before its pipelined input stages completed again
+/// with the current locations of its inputs
+fn resolve_pipelined_plan(
+    plan: Arc<dyn ExecutionPlan>,
+    inputs: &HashMap<usize, StageOutput>,
+) -> Result<Arc<dyn ExecutionPlan>> {
+    let input_locations = inputs
+        .iter()
+        .map(|(stage, input)| (*stage, input.partition_locations.clone()))
+        .collect();
+    let plan = crate::planner::rollback_resolved_shuffles(plan)?;
+    crate::planner::remove_unresolved_shuffles(plan, &input_locations)
 }
 
 fn decode_inputs(
@@ -1333,6 +1661,30 @@ fn encode_inputs(
     Ok(inputs)
 }
 
+/// Combine the resources used by the finished tasks of a stage. CPU and wall clock times,
+/// spilled bytes and output rows are summed up, the peak memory and the batch size are the
+/// highest of any task.
+fn combine_resource_usage<'a>(
+    task_infos: impl Iterator<Item = &'a TaskInfo>,
+) -> TaskResourceUsage {
+    task_infos
+        .filter_map(|task_info| task_info.resource_usage.as_ref())
+        .fold(TaskResourceUsage::default(), |combined, usage| {
+            TaskResourceUsage {
+                user_cpu_time_us: combined.user_cpu_time_us + usage.user_cpu_time_us,
+                sys_cpu_time_us: combined.sys_cpu_time_us + usage.sys_cpu_time_us,
+                peak_memory_bytes: combined
+                    .peak_memory_bytes
+                    .max(usage.peak_memory_bytes),
+                spilled_bytes: combined.spilled_bytes + usage.spilled_bytes,
+                batch_size: combined.batch_size.max(usage.batch_size),
+                wall_clock_time_us: combined.wall_clock_time_us
+                    + usage.wall_clock_time_us,
+                output_rows: combined.output_rows + usage.output_rows,
+            }
+        })
+}
+
 fn decode_taskinfo(task_info: protobuf::TaskInfo) -> TaskInfo {
     let task_info_status = match task_info.status {
         Some(task_info::Status::Running(running)) => {
@@ -1355,6 +1707,7 @@ fn decode_taskinfo(task_info: protobuf::TaskInfo) -> TaskInfo {
         end_exec_time: task_info.end_exec_time as u128,
         finish_time: task_info.finish_time as u128,
         task_status: task_info_status,
+        resource_usage: task_info.resource_usage,
     }
 }
 
@@ -1375,5 +1728,6 @@ fn encode_taskinfo(task_info: TaskInfo, partition_id: usize) -> protobuf::TaskIn
         end_exec_time: task_info.end_exec_time as u64,
         finish_time: task_info.finish_time as u64,
         status: Some(task_info_status),
+        resource_usage: task_info.resource_usage,
     }
 }
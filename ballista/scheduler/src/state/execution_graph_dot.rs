@@ -415,6 +415,7 @@ fn get_file_scan(scan: &FileScanConfig) -> String {
 
 #[cfg(test)]
 mod tests {
+    use crate::planner::DistributedPlanner;
     use crate::state::execution_graph::ExecutionGraph;
     use crate::state::execution_graph_dot::ExecutionGraphDot;
     use ballista_core::error::{BallistaError, Result};
@@ -644,7 +645,15 @@ filter_expr="]
             .await?;
         let plan = df.into_optimized_plan()?;
         let plan = ctx.state().create_physical_plan(&plan).await?;
-        ExecutionGraph::new("scheduler_id", "job_id", "job_name", "session_id", plan, 0)
+        ExecutionGraph::new(
+            "scheduler_id",
+            "job_id",
+            "job_name",
+            "session_id",
+            plan,
+            0,
+            DistributedPlanner::new(),
+        )
     }
 
     // With the improvement of https://github.com/apache/arrow-datafusion/pull/4122,
@@ -669,6 +678,14 @@ filter_expr="]
             .await?;
         let plan = df.into_optimized_plan()?;
         let plan = ctx.state().create_physical_plan(&plan).await?;
-        ExecutionGraph::new("scheduler_id", "job_id", "job_name", "session_id", plan, 0)
+        ExecutionGraph::new(
+            "scheduler_id",
+            "job_id",
+            "job_name",
+            "session_id",
+            plan,
+            0,
+            DistributedPlanner::new(),
+        )
     }
 }
@@ -15,40 +15,60 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
 
-#[cfg(not(test))]
+use ballista_core::client::BallistaClient;
+use ballista_core::config::GrpcCompression;
 use ballista_core::error::BallistaError;
 use ballista_core::error::Result;
 use ballista_core::serde::protobuf;
 
+use crate::clock::{system_clock, Clock};
 use crate::cluster::ClusterState;
 use crate::config::TaskDistribution;
 
-use crate::state::execution_graph::RunningTaskInfo;
+use crate::state::execution_graph::{RunningTaskInfo, TaskDescription};
+use crate::state::scheduling_recorder::{recorded_reservation, SchedulingRecorder};
 use ballista_core::serde::protobuf::executor_grpc_client::ExecutorGrpcClient;
+use ballista_core::serde::protobuf::scheduling_record::Record;
 use ballista_core::serde::protobuf::{
-    executor_status, CancelTasksParams, ExecutorHeartbeat, RemoveJobDataParams,
+    executor_metric, executor_status, CancelReservationsRecord, CancelTasksParams,
+    CgroupStats, ExecutorHeartbeat, GetRunningTasksParams, RegisterExecutorRecord,
+    RemoveExecutorRecord, RemoveJobDataParams, ReserveSlotsRecord, ResizeExecutorRecord,
+};
+use ballista_core::serde::scheduler::{
+    ExecutorData, ExecutorMetadata, ExecutorSpecification, TaskResources,
 };
-use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata};
 use ballista_core::utils::create_grpc_client_connection;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use log::{debug, error, info, warn};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tonic::transport::Channel;
 
 type ExecutorClients = Arc<DashMap<String, ExecutorGrpcClient<Channel>>>;
+type ExecutorFlightClients = Arc<DashMap<String, BallistaClient>>;
 
 /// Represents a task slot that is reserved (i.e. available for scheduling but not visible to the
 /// rest of the system).
 /// When tasks finish we want to preferentially assign new tasks from the same job, so the reservation
 /// can already be assigned to a particular job ID. In that case, the scheduler will try to schedule
 /// available tasks for that job to the reserved task slot.
+/// A reservation also holds the memory and cores reserved along with its task slot, and can only
+/// be filled with a task requiring exactly those resources so that nothing leaks when the
+/// reservation is returned.
+/// A reservation taken from the available task slots may hold a lease, after which its task
+/// slot returns to the available task slots unless it was filled or cancelled before.
+/// A reservation is only filled with the tasks of the jobs of the pool of its executor.
 #[derive(Clone, Debug)]
 pub struct ExecutorReservation {
     pub executor_id: String,
     pub job_id: Option<String>,
+    pub resources: TaskResources,
+    pub lease_id: Option<String>,
+    pub pool: String,
 }
 
 impl ExecutorReservation {
@@ -56,6 +76,9 @@ impl ExecutorReservation {
         Self {
             executor_id,
             job_id: None,
+            resources: TaskResources::default(),
+            lease_id: None,
+            pool: String::new(),
         }
     }
 
@@ -63,6 +86,9 @@ impl ExecutorReservation {
         Self {
             executor_id,
             job_id: Some(job_id),
+            resources: TaskResources::default(),
+            lease_id: None,
+            pool: String::new(),
         }
     }
 
@@ -74,6 +100,123 @@ impl ExecutorReservation {
     pub fn assigned(&self) -> bool {
         self.job_id.is_some()
     }
+
+    pub fn with_resources(mut self, resources: TaskResources) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    pub fn with_lease_id(mut self, lease_id: Option<String>) -> Self {
+        self.lease_id = lease_id;
+        self
+    }
+
+    pub fn with_pool(mut self, pool: String) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Whether a task requiring `resources` can be launched on this reservation. Reservations
+    /// with unbounded memory and cores are not accounted by the scheduler and accept any task
+    /// requiring no more GPUs than they hold.
+    pub fn accepts(&self, resources: &TaskResources) -> bool {
+        self.resources == *resources
+            || (self.resources.is_unbounded() && resources.gpus <= self.resources.gpus)
+    }
+}
+
+/// Point-in-time view of the task slots of the executors registered with an `ExecutorManager`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SlotMetrics {
+    /// Total task slots of the registered executors
+    pub total_slots: u64,
+    /// Task slots taken out of the pool of available slots and not returned yet, either held
+    /// by reservations or by the tasks launched on them
+    pub reserved_slots: u64,
+    /// Number of reservations returned to the pool of available slots
+    pub cancelled_reservations: u64,
+    /// Number of requests to reserve task slots
+    pub reservation_requests: u64,
+    /// Total time spent serving the requests to reserve task slots
+    pub reservation_latency: Duration,
+}
+
+/// Task slot accounting shared by the clones of an `ExecutorManager`
+#[derive(Default)]
+struct SlotCounters {
+    executor_slots: DashMap<String, u32>,
+    reserved_slots: DashMap<String, u32>,
+    cancelled_reservations: AtomicU64,
+    reservation_requests: AtomicU64,
+    reservation_latency_micros: AtomicU64,
+}
+
+impl SlotCounters {
+    fn register(&self, executor_id: &str, total_slots: u32, reserved_slots: u32) {
+        self.executor_slots
+            .insert(executor_id.to_owned(), total_slots);
+        self.reserved_slots
+            .insert(executor_id.to_owned(), reserved_slots);
+    }
+
+    fn resize(&self, executor_id: &str, total_slots: u32) {
+        if let Some(mut slots) = self.executor_slots.get_mut(executor_id) {
+            *slots = total_slots;
+        }
+    }
+
+    fn remove(&self, executor_id: &str) {
+        self.executor_slots.remove(executor_id);
+        self.reserved_slots.remove(executor_id);
+    }
+
+    fn reserved(&self, reservations: &[ExecutorReservation]) {
+        for reservation in reservations {
+            *self
+                .reserved_slots
+                .entry(reservation.executor_id.clone())
+                .or_default() += 1;
+        }
+    }
+
+    fn requested(&self, elapsed: Duration) {
+        self.reservation_requests.fetch_add(1, Ordering::Relaxed);
+        self.reservation_latency_micros
+            .fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn cancelled(&self, reservations: &[ExecutorReservation]) {
+        for reservation in reservations {
+            // Slots of executors which re-registered or were removed are not accounted anymore
+            if let Some(mut reserved) =
+                self.reserved_slots.get_mut(&reservation.executor_id)
+            {
+                *reserved = reserved.saturating_sub(1);
+            }
+        }
+        self.cancelled_reservations
+            .fetch_add(reservations.len() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> SlotMetrics {
+        SlotMetrics {
+            total_slots: self
+                .executor_slots
+                .iter()
+                .map(|slots| *slots.value() as u64)
+                .sum(),
+            reserved_slots: self
+                .reserved_slots
+                .iter()
+                .map(|slots| *slots.value() as u64)
+                .sum(),
+            cancelled_reservations: self.cancelled_reservations.load(Ordering::Relaxed),
+            reservation_requests: self.reservation_requests.load(Ordering::Relaxed),
+            reservation_latency: Duration::from_micros(
+                self.reservation_latency_micros.load(Ordering::Relaxed),
+            ),
+        }
+    }
 }
 
 // TODO move to configuration file
@@ -86,11 +229,33 @@ pub const DEFAULT_EXECUTOR_TIMEOUT_SECONDS: u64 = 180;
 /// Interval check for expired or dead executors
 pub const EXPIRE_DEAD_EXECUTOR_INTERVAL_SECS: u64 = 15;
 
+/// Interval check for expired leases of reserved task slots
+pub const EXPIRE_RESERVATION_LEASES_INTERVAL_SECS: u64 = 15;
+
 #[derive(Clone)]
 pub struct ExecutorManager {
     task_distribution: TaskDistribution,
     cluster_state: Arc<dyn ClusterState>,
     clients: ExecutorClients,
+    /// Clients of the flight services of executors, to fetch the logs of their tasks
+    flight_clients: ExecutorFlightClients,
+    grpc_compression: GrpcCompression,
+    slot_counters: Arc<SlotCounters>,
+    session_affinity: f64,
+    /// Executors which ran tasks of each session
+    session_executors: Arc<DashMap<String, HashSet<String>>>,
+    /// Pool of each executor, empty for the default pool
+    executor_pools: Arc<DashMap<String, String>>,
+    /// Executors being decommissioned, whose task slots are no longer reserved
+    decommissioning: Arc<DashSet<String>>,
+    /// Executors whose work dirs are short of disk space, which are offered no tasks
+    low_disk: Arc<DashSet<String>>,
+    /// Executors which rejected launched tasks, which are offered no tasks in the next
+    /// scheduling round so that the tasks run elsewhere
+    rejected_launches: Arc<DashSet<String>>,
+    recorder: Option<Arc<dyn SchedulingRecorder>>,
+    /// Clock of the heartbeats of executors and of the delayed clean ups of job data
+    clock: Arc<dyn Clock>,
 }
 
 impl ExecutorManager {
@@ -102,26 +267,364 @@ impl ExecutorManager {
             task_distribution,
             cluster_state,
             clients: Default::default(),
+            flight_clients: Default::default(),
+            grpc_compression: GrpcCompression::None,
+            slot_counters: Default::default(),
+            session_affinity: 0.0,
+            session_executors: Default::default(),
+            executor_pools: Default::default(),
+            decommissioning: Default::default(),
+            low_disk: Default::default(),
+            rejected_launches: Default::default(),
+            recorder: None,
+            clock: system_clock(),
         }
     }
 
+    /// Compress the messages sent to executors, such as the plans of launched tasks
+    pub fn with_grpc_compression(mut self, compression: GrpcCompression) -> Self {
+        self.grpc_compression = compression;
+        self
+    }
+
+    /// Share of the task slots reserved for a job on the executors which already ran tasks of
+    /// its session, between 0 (no affinity) and 1
+    pub fn with_session_affinity(mut self, session_affinity: f64) -> Self {
+        self.session_affinity = session_affinity.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Record the inputs and decisions about task slots to replay them later
+    pub fn with_scheduling_recorder(
+        mut self,
+        recorder: Option<Arc<dyn SchedulingRecorder>>,
+    ) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Expire executors and delay clean ups on `clock`, which should be the clock of the
+    /// cluster state
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
     pub async fn init(&self) -> Result<()> {
         self.cluster_state.init().await?;
+        self.refresh_decommissioning().await?;
 
         Ok(())
     }
 
-    /// Reserve up to n executor task slots. Once reserved these slots will not be available
-    /// for scheduling.
+    /// Reserve up to n task slots of the executors of `pool`, each of them reserving `resources`
+    /// on its executor. The empty pool is the default pool.
+    /// Once reserved these slots will not be available for scheduling.
+    /// This operation is atomic, so if this method return an Err, no slots have been reserved.
+    pub async fn reserve_slots(
+        &self,
+        pool: &str,
+        n: u32,
+        resources: TaskResources,
+    ) -> Result<Vec<ExecutorReservation>> {
+        self.reserve_slots_excluding(pool, n, resources, &HashSet::new())
+            .await
+    }
+
+    /// Reserve up to n task slots like [Self::reserve_slots], on none of the `excluded`
+    /// executors
+    pub async fn reserve_slots_excluding(
+        &self,
+        pool: &str,
+        n: u32,
+        resources: TaskResources,
+        excluded: &HashSet<String>,
+    ) -> Result<Vec<ExecutorReservation>> {
+        let mut alive_executors = self
+            .pool_executors(pool, self.get_alive_executors_within_one_minute())
+            .await;
+        alive_executors.retain(|executor_id| !excluded.contains(executor_id));
+
+        debug!("Alive executors of pool {pool:?}: {alive_executors:?}");
+
+        let start = Instant::now();
+        let reservations = self
+            .reserve_slots_on(pool, n, resources, alive_executors, false)
+            .await?;
+        self.slot_counters.requested(start.elapsed());
+
+        Ok(reservations)
+    }
+
+    /// Reserve up to n task slots on the given executors, each of them reserving `resources`
+    /// on its executor, e.g. on the executors holding the data read by the tasks or on an
+    /// executor whose task slots were just added. Only the alive executors of `pool` among
+    /// them are reserved.
+    /// This operation is atomic, so if this method return an Err, no slots have been reserved.
+    pub async fn reserve_slots_on_executors(
+        &self,
+        pool: &str,
+        executors: &HashSet<String>,
+        n: u32,
+        resources: TaskResources,
+    ) -> Result<Vec<ExecutorReservation>> {
+        let executors = self.targeted_executors(pool, executors).await;
+        let start = Instant::now();
+        let reservations = self
+            .reserve_slots_on(pool, n, resources, executors, false)
+            .await?;
+        self.slot_counters.requested(start.elapsed());
+
+        Ok(reservations)
+    }
+
+    /// Reserve exactly n task slots on the given executors, or none if fewer are available,
+    /// e.g. for tasks which have to run at the same time. Only the alive executors of `pool`
+    /// among them are reserved.
     /// This operation is atomic, so if this method return an Err, no slots have been reserved.
-    pub async fn reserve_slots(&self, n: u32) -> Result<Vec<ExecutorReservation>> {
+    pub async fn reserve_slots_on_executors_exact(
+        &self,
+        pool: &str,
+        executors: &HashSet<String>,
+        n: u32,
+        resources: TaskResources,
+    ) -> Result<Vec<ExecutorReservation>> {
+        let executors = self.targeted_executors(pool, executors).await;
+        let start = Instant::now();
+        let reservations = self
+            .reserve_slots_on(pool, n, resources, executors, true)
+            .await?;
+        self.slot_counters.requested(start.elapsed());
+
+        Ok(reservations)
+    }
+
+    /// The alive executors of `pool` among `executors`
+    async fn targeted_executors(
+        &self,
+        pool: &str,
+        executors: &HashSet<String>,
+    ) -> HashSet<String> {
         let alive_executors = self.get_alive_executors_within_one_minute();
+        self.pool_executors(
+            pool,
+            executors.intersection(&alive_executors).cloned().collect(),
+        )
+        .await
+    }
+
+    /// Reserve up to n task slots of the executors of `pool` for a job of the session
+    /// `session_id`. The share of the slots given by the session affinity is reserved on the
+    /// executors which already ran tasks of the session, as long as they have available slots,
+    /// so that their page cache and local data caches stay warm. The other slots are reserved
+    /// on any executor of the pool.
+    /// This operation is atomic, so if this method return an Err, no slots have been reserved.
+    pub async fn reserve_slots_for_session(
+        &self,
+        session_id: &str,
+        pool: &str,
+        n: u32,
+        resources: TaskResources,
+    ) -> Result<Vec<ExecutorReservation>> {
+        let sticky_slots = ((n as f64 * self.session_affinity).ceil() as u32).min(n);
+        let alive_executors = self
+            .pool_executors(pool, self.get_alive_executors_within_one_minute())
+            .await;
+        let session_executors: HashSet<String> = self
+            .session_executors
+            .get(session_id)
+            .map(|executors| executors.intersection(&alive_executors).cloned().collect())
+            .unwrap_or_default();
+
+        if sticky_slots == 0 || session_executors.is_empty() {
+            return self.reserve_slots(pool, n, resources).await;
+        }
 
-        debug!("Alive executors: {alive_executors:?}");
+        debug!("Executors of session {session_id}: {session_executors:?}");
 
-        self.cluster_state
-            .reserve_slots(n, self.task_distribution, Some(alive_executors))
-            .await
+        let start = Instant::now();
+        let mut reservations = self
+            .reserve_slots_on(pool, sticky_slots, resources, session_executors, false)
+            .await?;
+
+        let remaining = n - reservations.len() as u32;
+        if remaining > 0 {
+            match self
+                .reserve_slots_on(pool, remaining, resources, alive_executors, false)
+                .await
+            {
+                Ok(more_reservations) => reservations.extend(more_reservations),
+                Err(e) => {
+                    self.cancel_reservations(reservations).await?;
+                    return Err(e);
+                }
+            }
+        }
+        self.slot_counters.requested(start.elapsed());
+
+        Ok(reservations)
+    }
+
+    /// Reserve up to n task slots on the given executors of `pool`, or exactly n if `exact`
+    async fn reserve_slots_on(
+        &self,
+        pool: &str,
+        n: u32,
+        resources: TaskResources,
+        mut executors: HashSet<String>,
+        exact: bool,
+    ) -> Result<Vec<ExecutorReservation>> {
+        executors.retain(|executor_id| {
+            !self.is_decommissioning(executor_id) && !self.is_low_on_disk(executor_id)
+        });
+        let recorded_executors = self.recorder.as_ref().map(|_| {
+            let mut executors: Vec<String> = executors.iter().cloned().collect();
+            executors.sort();
+            executors
+        });
+
+        let reservations = if exact {
+            self.cluster_state
+                .reserve_slots_exact(
+                    n,
+                    resources,
+                    self.task_distribution,
+                    Some(executors),
+                )
+                .await?
+        } else {
+            self.cluster_state
+                .reserve_slots(n, resources, self.task_distribution, Some(executors))
+                .await?
+        };
+        let reservations: Vec<ExecutorReservation> = reservations
+            .into_iter()
+            .map(|reservation| reservation.with_pool(pool.to_owned()))
+            .collect();
+        self.slot_counters.reserved(&reservations);
+
+        if let (Some(recorder), Some(executors)) = (&self.recorder, recorded_executors) {
+            recorder.record(Record::ReserveSlots(ReserveSlotsRecord {
+                num_slots: n,
+                resources: Some(resources.into()),
+                executors,
+                reserved: reservations
+                    .iter()
+                    .map(|reservation| reservation.executor_id.clone())
+                    .collect(),
+                exact,
+            }));
+        }
+
+        Ok(reservations)
+    }
+
+    /// The executors among `executors` which belong to `pool`
+    async fn pool_executors(
+        &self,
+        pool: &str,
+        executors: HashSet<String>,
+    ) -> HashSet<String> {
+        let mut pool_executors = HashSet::with_capacity(executors.len());
+        for executor_id in executors {
+            if self.executor_pool(&executor_id).await.as_deref() == Some(pool) {
+                pool_executors.insert(executor_id);
+            }
+        }
+        pool_executors
+    }
+
+    /// Order the task slots offered to jobs following the task distribution policy, as the
+    /// first offered slots are filled first. With `BinPack` the slots of the executors
+    /// offering the fewest slots, which are the busiest ones, come first. With `Spread` the
+    /// executors take turns, starting with the executor offering the most slots. The other
+    /// policies keep the order of the offered slots.
+    pub(crate) fn order_reservations(
+        &self,
+        reservations: Vec<ExecutorReservation>,
+    ) -> Vec<ExecutorReservation> {
+        if !matches!(
+            self.task_distribution,
+            TaskDistribution::BinPack | TaskDistribution::Spread
+        ) {
+            return reservations;
+        }
+
+        let mut executors: Vec<Vec<ExecutorReservation>> = vec![];
+        for reservation in reservations {
+            match executors
+                .iter_mut()
+                .find(|slots| slots[0].executor_id == reservation.executor_id)
+            {
+                Some(slots) => slots.push(reservation),
+                None => executors.push(vec![reservation]),
+            }
+        }
+
+        if matches!(self.task_distribution, TaskDistribution::BinPack) {
+            executors.sort_by_key(|slots| slots.len());
+            return executors.into_iter().flatten().collect();
+        }
+
+        executors.sort_by_key(|slots| Reverse(slots.len()));
+        let total = executors.iter().map(Vec::len).sum();
+        let mut ordered = Vec::with_capacity(total);
+        let mut executors: Vec<_> = executors.into_iter().map(Vec::into_iter).collect();
+        while ordered.len() < total {
+            for slots in executors.iter_mut() {
+                ordered.extend(slots.next());
+            }
+        }
+        ordered
+    }
+
+    /// Pool of an executor, read from its metadata the first time it is needed, e.g. for the
+    /// executors registered with another scheduler. None if the executor is not known.
+    pub(crate) async fn executor_pool(&self, executor_id: &str) -> Option<String> {
+        if let Some(pool) = self.executor_pools.get(executor_id) {
+            return Some(pool.clone());
+        }
+        match self.get_executor_metadata(executor_id).await {
+            Ok(metadata) => {
+                self.executor_pools
+                    .insert(executor_id.to_owned(), metadata.pool.clone());
+                Some(metadata.pool)
+            }
+            Err(e) => {
+                warn!("Failed to get the pool of executor {executor_id}: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Remember the executors the tasks of each session were assigned to
+    pub(crate) fn record_session_tasks(&self, assignments: &[(String, TaskDescription)]) {
+        if self.session_affinity <= 0.0 {
+            return;
+        }
+        for (executor_id, task) in assignments {
+            self.session_executors
+                .entry(task.session_id.clone())
+                .or_default()
+                .insert(executor_id.clone());
+        }
+    }
+
+    /// Executors which ran tasks of the session `session_id`
+    pub fn session_executors(&self, session_id: &str) -> HashSet<String> {
+        self.session_executors
+            .get(session_id)
+            .map(|executors| executors.clone())
+            .unwrap_or_default()
+    }
+
+    /// Forget the executors of a session which was removed
+    pub(crate) fn remove_session_affinity(&self, session_id: &str) {
+        self.session_executors.remove(session_id);
     }
 
     /// Returned reserved task slots to the pool of available slots. This operation is atomic
@@ -130,7 +633,54 @@ impl ExecutorManager {
         &self,
         reservations: Vec<ExecutorReservation>,
     ) -> Result<()> {
-        self.cluster_state.cancel_reservations(reservations).await
+        self.cluster_state
+            .cancel_reservations(reservations.clone())
+            .await?;
+        self.slot_counters.cancelled(&reservations);
+        self.record(|| {
+            Record::CancelReservations(CancelReservationsRecord {
+                reservations: reservations.iter().map(recorded_reservation).collect(),
+            })
+        });
+
+        Ok(())
+    }
+
+    /// Release the leases of reservations which were filled with tasks, so that their task
+    /// slots stay reserved until the tasks complete
+    pub async fn release_reservation_leases(
+        &self,
+        lease_ids: HashSet<String>,
+    ) -> Result<()> {
+        if lease_ids.is_empty() {
+            return Ok(());
+        }
+        self.cluster_state
+            .release_reservation_leases(lease_ids)
+            .await
+    }
+
+    /// Return the task slots of the reservations whose lease expired to the pool of available
+    /// slots. Returns the number of expired reservations
+    pub async fn expire_reservation_leases(&self) -> Result<usize> {
+        self.cluster_state.expire_reservation_leases().await
+    }
+
+    pub(crate) fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Record an input or decision of the scheduler about task slots, if a recorder is set
+    pub(crate) fn record(&self, record: impl FnOnce() -> Record) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(record());
+        }
+    }
+
+    /// Current utilization of the task slots of the executors registered with this scheduler.
+    /// Once all the tasks completed, no task slot should remain reserved.
+    pub fn slot_metrics(&self) -> SlotMetrics {
+        self.slot_counters.snapshot()
     }
 
     /// Send rpc to Executors to cancel the running tasks
@@ -174,10 +724,21 @@ impl ExecutorManager {
         Ok(())
     }
 
+    /// Get the tasks running on an executor
+    pub async fn get_running_tasks(
+        &self,
+        executor_id: &str,
+    ) -> Result<Vec<protobuf::RunningTaskInfo>> {
+        let mut client = self.get_client(executor_id).await?;
+        let result = client.get_running_tasks(GetRunningTasksParams {}).await?;
+        Ok(result.into_inner().task_infos)
+    }
+
     /// Send rpc to Executors to clean up the job data by delayed clean_up_interval seconds
     pub(crate) fn clean_up_job_data_delayed(
         &self,
         job_id: String,
+        checkpoint_dir: Option<String>,
         clean_up_interval: u64,
     ) {
         if clean_up_interval == 0 {
@@ -190,29 +751,43 @@ impl ExecutorManager {
 
         let executor_manager = self.clone();
         tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_secs(clean_up_interval)).await;
-            executor_manager.clean_up_job_data_inner(job_id).await;
+            executor_manager
+                .clock
+                .sleep(Duration::from_secs(clean_up_interval))
+                .await;
+            executor_manager
+                .clean_up_job_data_inner(job_id, checkpoint_dir)
+                .await;
         });
     }
 
-    /// Send rpc to Executors to clean up the job data in a spawn thread
-    pub fn clean_up_job_data(&self, job_id: String) {
+    /// Send rpc to Executors to clean up the job data in a spawn thread, including the
+    /// checkpoints of its stage outputs in `checkpoint_dir`
+    pub fn clean_up_job_data(&self, job_id: String, checkpoint_dir: Option<String>) {
         let executor_manager = self.clone();
         tokio::spawn(async move {
-            executor_manager.clean_up_job_data_inner(job_id).await;
+            executor_manager
+                .clean_up_job_data_inner(job_id, checkpoint_dir)
+                .await;
         });
     }
 
     /// Send rpc to Executors to clean up the job data
-    async fn clean_up_job_data_inner(&self, job_id: String) {
+    async fn clean_up_job_data_inner(
+        &self,
+        job_id: String,
+        checkpoint_dir: Option<String>,
+    ) {
         let alive_executors = self.get_alive_executors_within_one_minute();
         for executor in alive_executors {
             let job_id_clone = job_id.to_owned();
+            let checkpoint_dir = checkpoint_dir.clone().unwrap_or_default();
             if let Ok(mut client) = self.get_client(&executor).await {
                 tokio::spawn(async move {
                     if let Err(err) = client
                         .remove_job_data(RemoveJobDataParams {
                             job_id: job_id_clone,
+                            checkpoint_dir,
                         })
                         .await
                     {
@@ -243,7 +818,10 @@ impl ExecutorManager {
                 executor_metadata.host, executor_metadata.grpc_port
             );
             let connection = create_grpc_client_connection(executor_url).await?;
-            let client = ExecutorGrpcClient::new(connection);
+            let mut client = ExecutorGrpcClient::new(connection);
+            if let Some(encoding) = self.grpc_compression.encoding() {
+                client = client.send_compressed(encoding).accept_compressed(encoding);
+            }
 
             {
                 self.clients.insert(executor_id.to_owned(), client.clone());
@@ -252,6 +830,27 @@ impl ExecutorManager {
         }
     }
 
+    /// Get a client of the flight service of an executor, reusing the client connected to it
+    /// before if any
+    pub async fn get_flight_client(&self, executor_id: &str) -> Result<BallistaClient> {
+        let client = self
+            .flight_clients
+            .get(executor_id)
+            .map(|value| value.clone());
+
+        if let Some(client) = client {
+            Ok(client)
+        } else {
+            let executor_metadata = self.get_executor_metadata(executor_id).await?;
+            let client =
+                BallistaClient::try_new(&executor_metadata.host, executor_metadata.port)
+                    .await?;
+            self.flight_clients
+                .insert(executor_id.to_owned(), client.clone());
+            Ok(client)
+        }
+    }
+
     /// Get a list of all executors along with the timestamp of their last recorded heartbeat
     pub async fn get_executor_state(&self) -> Result<Vec<(ExecutorMetadata, Duration)>> {
         let heartbeat_timestamps: Vec<(String, u64)> = self
@@ -273,6 +872,28 @@ impl ExecutorManager {
         Ok(state)
     }
 
+    /// Progress of the running tasks of a job reported in the last heartbeats of the
+    /// executors, with the ID of the executor running each task
+    pub fn get_task_progress(
+        &self,
+        job_id: &str,
+    ) -> Vec<(String, protobuf::TaskProgress)> {
+        let mut progress: Vec<(String, protobuf::TaskProgress)> = self
+            .cluster_state
+            .executor_heartbeats()
+            .into_iter()
+            .flat_map(|(executor_id, heartbeat)| {
+                heartbeat
+                    .task_progress
+                    .into_iter()
+                    .filter(|progress| progress.job_id == job_id)
+                    .map(move |progress| (executor_id.clone(), progress))
+            })
+            .collect();
+        progress.sort_by_key(|(_, progress)| (progress.stage_id, progress.partition_id));
+        progress
+    }
+
     pub async fn get_executor_metadata(
         &self,
         executor_id: &str,
@@ -281,6 +902,8 @@ impl ExecutorManager {
     }
 
     pub async fn save_executor_metadata(&self, metadata: ExecutorMetadata) -> Result<()> {
+        self.executor_pools
+            .insert(metadata.id.clone(), metadata.pool.clone());
         self.cluster_state.save_executor_metadata(metadata).await
     }
 
@@ -305,10 +928,21 @@ impl ExecutorManager {
 
         self.test_scheduler_connectivity(&metadata).await?;
 
+        let executor_id = metadata.id.clone();
+        let total_slots = specification.total_task_slots;
+        self.executor_pools
+            .insert(executor_id.clone(), metadata.pool.clone());
+
         if !reserve {
+            self.record(|| register_executor_record(&metadata, &specification, reserve));
             self.cluster_state
                 .register_executor(metadata, specification.clone(), reserve)
                 .await?;
+            self.slot_counters.register(
+                &executor_id,
+                total_slots,
+                total_slots.saturating_sub(specification.available_task_slots),
+            );
 
             Ok(vec![])
         } else {
@@ -316,70 +950,272 @@ impl ExecutorManager {
             let num_slots = specification.available_task_slots as usize;
             let mut reservations: Vec<ExecutorReservation> = vec![];
             for _ in 0..num_slots {
-                reservations.push(ExecutorReservation::new_free(metadata.id.clone()));
+                reservations.push(
+                    ExecutorReservation::new_free(metadata.id.clone())
+                        .with_pool(metadata.pool.clone()),
+                );
             }
 
             specification.available_task_slots = 0;
 
+            self.record(|| register_executor_record(&metadata, &specification, reserve));
             self.cluster_state
                 .register_executor(metadata, specification, reserve)
                 .await?;
+            // All the task slots of the executor are reserved
+            self.slot_counters
+                .register(&executor_id, total_slots, total_slots);
 
             Ok(reservations)
         }
     }
 
-    /// Remove the executor within the scheduler.
-    pub async fn remove_executor(
+    /// Hand out `task_slot_oversubscription_percent` logical task slots per hundred task
+    /// slots of the executor from now on, 0 for as many as its task slots. Returns the number
+    /// of task slots made available.
+    ///
+    /// Task slots removed while they are reserved are only taken away once their
+    /// reservations are cancelled, so the tasks running on them are not disturbed.
+    pub async fn set_task_slot_oversubscription(
         &self,
         executor_id: &str,
-        reason: Option<String>,
-    ) -> Result<()> {
-        info!("Removing executor {}: {:?}", executor_id, reason);
-        self.cluster_state.remove_executor(executor_id).await
+        task_slot_oversubscription_percent: u32,
+    ) -> Result<u32> {
+        if task_slot_oversubscription_percent != 0
+            && task_slot_oversubscription_percent < 100
+        {
+            return Err(BallistaError::General(format!(
+                "Task slot oversubscription must be at least 100 percent, got {task_slot_oversubscription_percent}"
+            )));
+        }
+
+        self.update_specification(executor_id, |specification| {
+            specification.task_slot_oversubscription_percent =
+                task_slot_oversubscription_percent
+        })
+        .await
     }
 
-    #[cfg(not(test))]
-    async fn test_scheduler_connectivity(
+    /// Resize the task slots of an executor to the `task_slots` it advertises while running,
+    /// e.g. shrinking it while it shares its host with other workloads. Returns the number of
+    /// task slots made available.
+    ///
+    /// Task slots removed while they are reserved are only taken away once their
+    /// reservations are cancelled, so the tasks running on them are not disturbed.
+    pub async fn set_task_slots(
         &self,
-        metadata: &ExecutorMetadata,
-    ) -> Result<()> {
-        let executor_url = format!("http://{}:{}", metadata.host, metadata.grpc_port);
-        debug!("Connecting to executor {:?}", executor_url);
-        let _ = protobuf::executor_grpc_client::ExecutorGrpcClient::connect(executor_url)
-            .await
-            .map_err(|e| {
-                BallistaError::Internal(format!(
-                    "Failed to register executor at {}:{}, could not connect: {:?}",
-                    metadata.host, metadata.grpc_port, e
-                ))
-            })?;
-        Ok(())
+        executor_id: &str,
+        task_slots: u32,
+    ) -> Result<u32> {
+        if task_slots == 0 {
+            return Err(BallistaError::General(format!(
+                "Executor {executor_id} needs at least one task slot"
+            )));
+        }
+
+        self.update_specification(executor_id, |specification| {
+            specification.task_slots = task_slots
+        })
+        .await
     }
 
-    #[cfg(test)]
-    async fn test_scheduler_connectivity(
+    /// Update the specification of an executor and resize its available task slots to the
+    /// logical task slots of the new specification. Returns the number of task slots made
+    /// available.
+    async fn update_specification(
         &self,
-        _metadata: &ExecutorMetadata,
-    ) -> Result<()> {
-        Ok(())
+        executor_id: &str,
+        update: impl FnOnce(&mut ExecutorSpecification),
+    ) -> Result<u32> {
+        let mut metadata = self.get_executor_metadata(executor_id).await?;
+        let old_slots = metadata.specification.logical_task_slots();
+        update(&mut metadata.specification);
+        let new_slots = metadata.specification.logical_task_slots();
+        self.save_executor_metadata(metadata).await?;
+
+        let delta = (new_slots as i64 - old_slots as i64) as i32;
+        if delta != 0 {
+            self.record(|| {
+                Record::ResizeExecutor(ResizeExecutorRecord {
+                    executor_id: executor_id.to_owned(),
+                    task_slots_delta: delta,
+                })
+            });
+            self.cluster_state
+                .resize_executor(executor_id, delta)
+                .await?;
+            self.slot_counters.resize(executor_id, new_slots);
+        }
+        info!(
+            "Executor {executor_id} hands out {new_slots} task slots instead of {old_slots}"
+        );
+
+        Ok(new_slots.saturating_sub(old_slots))
     }
 
-    pub(crate) async fn save_executor_heartbeat(
+    /// Stop reserving the task slots of an executor which is being decommissioned, so that
+    /// it is offered no more tasks. The decommissioning is persisted in the cluster state with
+    /// its `deadline` in seconds since the epoch (0 for none), so that it survives a scheduler
+    /// restart and applies to every scheduler. Returns false if it was being decommissioned
+    /// already.
+    pub(crate) async fn decommission_executor(
         &self,
-        heartbeat: ExecutorHeartbeat,
-    ) -> Result<()> {
-        self.cluster_state
-            .save_executor_heartbeat(heartbeat.clone())
+        executor_id: &str,
+        deadline: u64,
+    ) -> Result<bool> {
+        let decommissioned = self
+            .cluster_state
+            .decommission_executor(executor_id, deadline)
             .await?;
+        self.decommissioning.insert(executor_id.to_owned());
+        Ok(decommissioned)
+    }
 
-        Ok(())
+    /// Reload the executors being decommissioned from the cluster state, returning their
+    /// deadlines
+    pub(crate) async fn refresh_decommissioning(&self) -> Result<HashMap<String, u64>> {
+        let decommissioning = self.cluster_state.decommissioning_executors().await?;
+        self.decommissioning
+            .retain(|executor_id| decommissioning.contains_key(executor_id));
+        for executor_id in decommissioning.keys() {
+            self.decommissioning.insert(executor_id.clone());
+        }
+        Ok(decommissioning)
     }
 
-    pub(crate) fn is_dead_executor(&self, executor_id: &str) -> bool {
-        self.cluster_state
-            .get_executor_heartbeat(executor_id)
-            .map_or(true, |heartbeat| {
+    /// Whether an executor is being decommissioned
+    pub(crate) fn is_decommissioning(&self, executor_id: &str) -> bool {
+        self.decommissioning.contains(executor_id)
+    }
+
+    /// Offer no tasks to an executor which rejected launched tasks in the next scheduling
+    /// round
+    pub(crate) fn record_rejected_launch(&self, executor_id: &str) {
+        self.rejected_launches.insert(executor_id.to_owned());
+    }
+
+    /// The executors which rejected launched tasks since the last scheduling round, which
+    /// are offered tasks again in the following rounds
+    pub(crate) fn take_rejected_launches(&self) -> HashSet<String> {
+        let executors: HashSet<String> = self
+            .rejected_launches
+            .iter()
+            .map(|executor_id| executor_id.key().clone())
+            .collect();
+        for executor_id in executors.iter() {
+            self.rejected_launches.remove(executor_id);
+        }
+        executors
+    }
+
+    /// Whether the work dirs of an executor are short of disk space, as of its last heartbeat
+    pub(crate) fn is_low_on_disk(&self, executor_id: &str) -> bool {
+        self.low_disk.contains(executor_id)
+    }
+
+    /// CPU and memory usage of the cgroup of an executor, as of its last heartbeat. None if
+    /// the executor runs in no cgroup of its own.
+    pub(crate) fn cgroup_stats(&self, executor_id: &str) -> Option<CgroupStats> {
+        self.cluster_state
+            .get_executor_heartbeat(executor_id)?
+            .metrics
+            .into_iter()
+            .find_map(|metric| match metric.metric {
+                Some(executor_metric::Metric::CgroupStats(stats)) => Some(stats),
+                _ => None,
+            })
+    }
+
+    /// Remove the executor within the scheduler.
+    pub async fn remove_executor(
+        &self,
+        executor_id: &str,
+        reason: Option<String>,
+    ) -> Result<()> {
+        info!("Removing executor {}: {:?}", executor_id, reason);
+        self.cluster_state.remove_executor(executor_id).await?;
+        self.slot_counters.remove(executor_id);
+        self.executor_pools.remove(executor_id);
+        self.decommissioning.remove(executor_id);
+        self.low_disk.remove(executor_id);
+        self.flight_clients.remove(executor_id);
+        self.record(|| {
+            Record::RemoveExecutor(RemoveExecutorRecord {
+                executor_id: executor_id.to_owned(),
+            })
+        });
+        for mut executors in self.session_executors.iter_mut() {
+            executors.remove(executor_id);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(test))]
+    async fn test_scheduler_connectivity(
+        &self,
+        metadata: &ExecutorMetadata,
+    ) -> Result<()> {
+        let executor_url = format!("http://{}:{}", metadata.host, metadata.grpc_port);
+        debug!("Connecting to executor {:?}", executor_url);
+        let _ = protobuf::executor_grpc_client::ExecutorGrpcClient::connect(executor_url)
+            .await
+            .map_err(|e| {
+                BallistaError::Internal(format!(
+                    "Failed to register executor at {}:{}, could not connect: {:?}",
+                    metadata.host, metadata.grpc_port, e
+                ))
+            })?;
+        Ok(())
+    }
+
+    #[cfg(test)]
+    async fn test_scheduler_connectivity(
+        &self,
+        _metadata: &ExecutorMetadata,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    pub(crate) async fn save_executor_heartbeat(
+        &self,
+        heartbeat: ExecutorHeartbeat,
+    ) -> Result<()> {
+        let disk_usage =
+            heartbeat
+                .metrics
+                .iter()
+                .find_map(|metric| match &metric.metric {
+                    Some(executor_metric::Metric::DiskUsage(disk_usage)) => {
+                        Some(disk_usage)
+                    }
+                    _ => None,
+                });
+        if let Some(disk_usage) = disk_usage {
+            let executor_id = &heartbeat.executor_id;
+            if disk_usage.below_watermark {
+                if self.low_disk.insert(executor_id.clone()) {
+                    warn!(
+                        "Executor {executor_id} is short of disk space ({} of {} bytes available), offering it no tasks",
+                        disk_usage.available_bytes, disk_usage.total_bytes
+                    );
+                }
+            } else if self.low_disk.remove(executor_id).is_some() {
+                info!("Executor {executor_id} has enough disk space again");
+            }
+        }
+
+        self.cluster_state
+            .save_executor_heartbeat(heartbeat.clone())
+            .await?;
+
+        Ok(())
+    }
+
+    pub(crate) fn is_dead_executor(&self, executor_id: &str) -> bool {
+        self.cluster_state
+            .get_executor_heartbeat(executor_id)
+            .map_or(true, |heartbeat| {
                 matches!(
                     heartbeat.status,
                     Some(ballista_core::serde::generated::ballista::ExecutorStatus {
@@ -418,20 +1254,12 @@ impl ExecutorManager {
         &self,
         termination_grace_period: u64,
     ) -> Vec<ExecutorHeartbeat> {
-        let now_epoch_ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
+        let now = self.clock.now_secs();
         // Threshold for last heartbeat from Active executor before marking dead
-        let last_seen_threshold = now_epoch_ts
-            .checked_sub(Duration::from_secs(DEFAULT_EXECUTOR_TIMEOUT_SECONDS))
-            .unwrap_or_else(|| Duration::from_secs(0))
-            .as_secs();
+        let last_seen_threshold = now.saturating_sub(DEFAULT_EXECUTOR_TIMEOUT_SECONDS);
 
         // Threshold for last heartbeat for Fenced executor before marking dead
-        let termination_wait_threshold = now_epoch_ts
-            .checked_sub(Duration::from_secs(termination_grace_period))
-            .unwrap_or_else(|| Duration::from_secs(0))
-            .as_secs();
+        let termination_wait_threshold = now.saturating_sub(termination_grace_period);
 
         self.cluster_state
             .executor_heartbeats()
@@ -457,35 +1285,56 @@ impl ExecutorManager {
     }
 
     pub(crate) fn get_alive_executors_within_one_minute(&self) -> HashSet<String> {
-        let now_epoch_ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
-        let last_seen_threshold = now_epoch_ts
-            .checked_sub(Duration::from_secs(60))
-            .unwrap_or_else(|| Duration::from_secs(0));
-        self.get_alive_executors(last_seen_threshold.as_secs())
+        self.get_alive_executors(self.clock.now_secs().saturating_sub(60))
     }
 }
 
+fn register_executor_record(
+    metadata: &ExecutorMetadata,
+    specification: &ExecutorData,
+    reserve: bool,
+) -> Record {
+    Record::RegisterExecutor(RegisterExecutorRecord {
+        metadata: Some(metadata.clone().into()),
+        total_task_slots: specification.total_task_slots,
+        available_task_slots: specification.available_task_slots,
+        reserve,
+    })
+}
+
 #[cfg(test)]
 mod test {
 
+    use crate::clock::{Clock, ManualClock};
+    use crate::cluster::memory::InMemoryClusterState;
     use crate::config::TaskDistribution;
 
     use crate::scheduler_server::timestamp_secs;
-    use crate::state::executor_manager::{ExecutorManager, ExecutorReservation};
+    use crate::state::executor_manager::{
+        ExecutorManager, ExecutorReservation, SlotMetrics,
+        DEFAULT_EXECUTOR_TIMEOUT_SECONDS,
+    };
     use crate::test_utils::test_cluster_context;
     use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::executor_metric::Metric;
     use ballista_core::serde::protobuf::executor_status::Status;
-    use ballista_core::serde::protobuf::{ExecutorHeartbeat, ExecutorStatus};
+    use ballista_core::serde::protobuf::{
+        CgroupStats, DiskUsage, ExecutorHeartbeat, ExecutorMetric, ExecutorStatus,
+        TaskProgress,
+    };
     use ballista_core::serde::scheduler::{
-        ExecutorData, ExecutorMetadata, ExecutorSpecification,
+        ExecutorData, ExecutorMetadata, ExecutorSpecification, TaskResources,
     };
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::time::Duration;
 
     #[tokio::test]
     async fn test_reserve_and_cancel() -> Result<()> {
         test_reserve_and_cancel_inner(TaskDistribution::Bias).await?;
         test_reserve_and_cancel_inner(TaskDistribution::RoundRobin).await?;
+        test_reserve_and_cancel_inner(TaskDistribution::BinPack).await?;
+        test_reserve_and_cancel_inner(TaskDistribution::Spread).await?;
 
         Ok(())
     }
@@ -507,7 +1356,9 @@ mod test {
         }
 
         // Reserve all the slots
-        let reservations = executor_manager.reserve_slots(40).await?;
+        let reservations = executor_manager
+            .reserve_slots("", 40, TaskResources::default())
+            .await?;
 
         assert_eq!(
             reservations.len(),
@@ -519,7 +1370,9 @@ mod test {
         executor_manager.cancel_reservations(reservations).await?;
 
         // Now reserve again
-        let reservations = executor_manager.reserve_slots(40).await?;
+        let reservations = executor_manager
+            .reserve_slots("", 40, TaskResources::default())
+            .await?;
 
         assert_eq!(
             reservations.len(),
@@ -530,10 +1383,446 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_decommission_executor() -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+
+        for (executor_metadata, executor_data) in test_executors(2, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        assert!(
+            executor_manager
+                .decommission_executor("executor-0", 0)
+                .await?
+        );
+        assert!(
+            !executor_manager
+                .decommission_executor("executor-0", 0)
+                .await?
+        );
+        assert!(executor_manager.is_decommissioning("executor-0"));
+
+        // Another scheduler sharing the cluster state sees the decommissioning
+        let other_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+        other_manager.init().await?;
+        assert!(other_manager.is_decommissioning("executor-0"));
+
+        // Only the slots of the other executor are reserved
+        let reservations = executor_manager
+            .reserve_slots("", 8, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 4);
+        assert!(reservations
+            .iter()
+            .all(|reservation| reservation.executor_id == "executor-1"));
+
+        executor_manager
+            .remove_executor("executor-0", Some("decommissioned".to_owned()))
+            .await?;
+        assert!(!executor_manager.is_decommissioning("executor-0"));
+        other_manager.refresh_decommissioning().await?;
+        assert!(!other_manager.is_decommissioning("executor-0"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_low_on_disk() -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+
+        for (executor_metadata, executor_data) in test_executors(2, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let heartbeat = |below_watermark: bool| ExecutorHeartbeat {
+            executor_id: "executor-0".to_string(),
+            timestamp: timestamp_secs(),
+            metrics: vec![ExecutorMetric {
+                metric: Some(Metric::DiskUsage(DiskUsage {
+                    available_bytes: if below_watermark { 1 << 20 } else { 1 << 30 },
+                    total_bytes: 1 << 40,
+                    below_watermark,
+                })),
+            }],
+            status: Some(ExecutorStatus {
+                status: Some(Status::Active(String::default())),
+            }),
+            task_progress: vec![],
+        };
+
+        executor_manager
+            .save_executor_heartbeat(heartbeat(true))
+            .await?;
+        assert!(executor_manager.is_low_on_disk("executor-0"));
+
+        // Only the slots of the executor with enough disk space are reserved
+        let reservations = executor_manager
+            .reserve_slots("", 8, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 4);
+        assert!(reservations
+            .iter()
+            .all(|reservation| reservation.executor_id == "executor-1"));
+
+        executor_manager
+            .save_executor_heartbeat(heartbeat(false))
+            .await?;
+        assert!(!executor_manager.is_low_on_disk("executor-0"));
+
+        let reservations = executor_manager
+            .reserve_slots("", 8, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 4);
+        assert!(reservations
+            .iter()
+            .all(|reservation| reservation.executor_id == "executor-0"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_cgroup_stats() -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+
+        for (executor_metadata, executor_data) in test_executors(2, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let stats = CgroupStats {
+            cpu_usage_us: 5000,
+            cpu_periods: 20,
+            cpu_throttled_periods: 3,
+            cpu_throttled_us: 1500,
+            memory_bytes: 1 << 20,
+            memory_max_bytes: 1 << 30,
+            memory_high_events: 0,
+            memory_oom_kills: 0,
+        };
+        executor_manager
+            .save_executor_heartbeat(ExecutorHeartbeat {
+                executor_id: "executor-0".to_string(),
+                timestamp: timestamp_secs(),
+                metrics: vec![ExecutorMetric {
+                    metric: Some(Metric::CgroupStats(stats.clone())),
+                }],
+                status: Some(ExecutorStatus {
+                    status: Some(Status::Active(String::default())),
+                }),
+                task_progress: vec![],
+            })
+            .await?;
+
+        assert_eq!(executor_manager.cgroup_stats("executor-0"), Some(stats));
+        // The other executor runs in no cgroup of its own
+        assert_eq!(executor_manager.cgroup_stats("executor-1"), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_slot_metrics() -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+
+        let mut executors = test_executors(3, 4);
+        let (reserved_metadata, reserved_data) = executors.pop().unwrap();
+
+        for (executor_metadata, executor_data) in executors {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let reservations = executor_manager
+            .reserve_slots("", 6, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 6);
+
+        let metrics = executor_manager.slot_metrics();
+        assert_eq!(metrics.total_slots, 8);
+        assert_eq!(metrics.reserved_slots, 6);
+        assert_eq!(metrics.reservation_requests, 1);
+
+        // Slots of an executor registered with reservations are all reserved
+        let registered_reservations = executor_manager
+            .register_executor(reserved_metadata, reserved_data, true)
+            .await?;
+        assert_eq!(registered_reservations.len(), 4);
+
+        let metrics = executor_manager.slot_metrics();
+        assert_eq!(metrics.total_slots, 12);
+        assert_eq!(metrics.reserved_slots, 10);
+
+        executor_manager.cancel_reservations(reservations).await?;
+        executor_manager
+            .cancel_reservations(registered_reservations)
+            .await?;
+
+        // No reservation is leaked
+        let metrics = executor_manager.slot_metrics();
+        assert_eq!(
+            metrics,
+            SlotMetrics {
+                total_slots: 12,
+                reserved_slots: 0,
+                cancelled_reservations: 10,
+                reservation_requests: 1,
+                reservation_latency: metrics.reservation_latency,
+            }
+        );
+
+        // Removed executors do not count anymore
+        let reservations = executor_manager
+            .reserve_slots("", 12, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 12);
+        executor_manager.remove_executor("executor-0", None).await?;
+
+        let metrics = executor_manager.slot_metrics();
+        assert_eq!(metrics.total_slots, 8);
+        assert_eq!(metrics.reserved_slots, 8);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bin_pack_and_spread() -> Result<()> {
+        let used_executors = |reservations: &[ExecutorReservation]| {
+            reservations
+                .iter()
+                .map(|res| res.executor_id.clone())
+                .collect::<HashSet<_>>()
+                .len()
+        };
+
+        let cluster = test_cluster_context();
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::BinPack);
+        for (executor_metadata, executor_data) in test_executors(3, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let reservations = executor_manager
+            .reserve_slots("", 2, TaskResources::default())
+            .await?;
+        assert_eq!(used_executors(&reservations), 1);
+        // The busy executor is filled before another one is used
+        let more_reservations = executor_manager
+            .reserve_slots("", 3, TaskResources::default())
+            .await?;
+        assert_eq!(more_reservations.len(), 3);
+        let all_reservations: Vec<_> =
+            reservations.into_iter().chain(more_reservations).collect();
+        assert_eq!(used_executors(&all_reservations), 2);
+
+        let cluster = test_cluster_context();
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Spread);
+        for (executor_metadata, executor_data) in test_executors(3, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let reservations = executor_manager
+            .reserve_slots("", 3, TaskResources::default())
+            .await?;
+        assert_eq!(used_executors(&reservations), 3);
+        // The executor with the most slots left is used first
+        executor_manager
+            .cancel_reservations(reservations[..1].to_vec())
+            .await?;
+        let more_reservations = executor_manager
+            .reserve_slots("", 1, TaskResources::default())
+            .await?;
+        assert_eq!(
+            more_reservations[0].executor_id,
+            reservations[0].executor_id
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_order_reservations() {
+        let offered: Vec<ExecutorReservation> = ["e0", "e0", "e0", "e1", "e2", "e2"]
+            .iter()
+            .map(|executor_id| ExecutorReservation::new_free(executor_id.to_string()))
+            .collect();
+        let ordered = |task_distribution: TaskDistribution| {
+            let cluster = test_cluster_context();
+            ExecutorManager::new(cluster.cluster_state(), task_distribution)
+                .order_reservations(offered.clone())
+                .into_iter()
+                .map(|res| res.executor_id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            ordered(TaskDistribution::Bias),
+            vec!["e0", "e0", "e0", "e1", "e2", "e2"]
+        );
+        assert_eq!(
+            ordered(TaskDistribution::BinPack),
+            vec!["e1", "e2", "e2", "e0", "e0", "e0"]
+        );
+        assert_eq!(
+            ordered(TaskDistribution::Spread),
+            vec!["e0", "e2", "e1", "e0", "e2", "e0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_affinity() -> Result<()> {
+        test_session_affinity_inner(TaskDistribution::Bias).await?;
+        test_session_affinity_inner(TaskDistribution::RoundRobin).await?;
+
+        Ok(())
+    }
+
+    async fn test_session_affinity_inner(
+        task_distribution: TaskDistribution,
+    ) -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), task_distribution)
+                .with_session_affinity(0.5);
+
+        for (executor_metadata, executor_data) in test_executors(4, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        // executor-3 already ran tasks of the session
+        executor_manager
+            .session_executors
+            .entry("session".to_owned())
+            .or_default()
+            .insert("executor-3".to_owned());
+
+        let sticky_reservations = |reservations: &[ExecutorReservation]| {
+            reservations
+                .iter()
+                .filter(|res| res.executor_id == "executor-3")
+                .count()
+        };
+
+        // Half of the slots are reserved on the executor of the session
+        let reservations = executor_manager
+            .reserve_slots_for_session("session", "", 6, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 6);
+        assert!(
+            sticky_reservations(&reservations) >= 3,
+            "Expected half of the reservations on executor-3 for policy {task_distribution:?}"
+        );
+        executor_manager.cancel_reservations(reservations).await?;
+
+        // The other slots are reserved elsewhere once the executor of the session is full
+        let executor_manager = executor_manager.with_session_affinity(1.0);
+        let reservations = executor_manager
+            .reserve_slots_for_session("session", "", 6, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 6);
+        assert_eq!(sticky_reservations(&reservations), 4);
+        executor_manager.cancel_reservations(reservations).await?;
+        assert_eq!(executor_manager.slot_metrics().reserved_slots, 0);
+
+        // Removed executors are forgotten
+        executor_manager.remove_executor("executor-3", None).await?;
+        assert!(executor_manager.session_executors("session").is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reserve_slots_on_executors() -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+
+        for (executor_metadata, executor_data) in test_executors(4, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let executors: HashSet<String> =
+            HashSet::from(["executor-1".to_owned(), "executor-2".to_owned()]);
+
+        // Only the slots of the given executors are reserved
+        let reservations = executor_manager
+            .reserve_slots_on_executors("", &executors, 10, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 8);
+        assert!(reservations
+            .iter()
+            .all(|res| executors.contains(&res.executor_id)));
+        executor_manager.cancel_reservations(reservations).await?;
+
+        // Either all the slots or none are reserved
+        let reservations = executor_manager
+            .reserve_slots_on_executors_exact(
+                "",
+                &executors,
+                10,
+                TaskResources::default(),
+            )
+            .await?;
+        assert!(reservations.is_empty());
+        assert_eq!(executor_manager.slot_metrics().reserved_slots, 0);
+
+        let reservations = executor_manager
+            .reserve_slots_on_executors_exact("", &executors, 8, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 8);
+        executor_manager.cancel_reservations(reservations).await?;
+
+        // Unknown executors and the executors of other pools have no slots
+        let reservations = executor_manager
+            .reserve_slots_on_executors(
+                "batch",
+                &HashSet::from(["executor-1".to_owned(), "executor-9".to_owned()]),
+                4,
+                TaskResources::default(),
+            )
+            .await?;
+        assert!(reservations.is_empty());
+        assert_eq!(executor_manager.slot_metrics().reserved_slots, 0);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_reserve_partial() -> Result<()> {
         test_reserve_partial_inner(TaskDistribution::Bias).await?;
         test_reserve_partial_inner(TaskDistribution::RoundRobin).await?;
+        test_reserve_partial_inner(TaskDistribution::BinPack).await?;
+        test_reserve_partial_inner(TaskDistribution::Spread).await?;
 
         Ok(())
     }
@@ -555,12 +1844,16 @@ mod test {
         }
 
         // Reserve all the slots
-        let reservations = executor_manager.reserve_slots(30).await?;
+        let reservations = executor_manager
+            .reserve_slots("", 30, TaskResources::default())
+            .await?;
 
         assert_eq!(reservations.len(), 30);
 
         // Try to reserve 30 more. Only ten are available though so we should only get 10
-        let more_reservations = executor_manager.reserve_slots(30).await?;
+        let more_reservations = executor_manager
+            .reserve_slots("", 30, TaskResources::default())
+            .await?;
 
         assert_eq!(more_reservations.len(), 10);
 
@@ -571,11 +1864,15 @@ mod test {
             .await?;
 
         // Now reserve again
-        let reservations = executor_manager.reserve_slots(40).await?;
+        let reservations = executor_manager
+            .reserve_slots("", 40, TaskResources::default())
+            .await?;
 
         assert_eq!(reservations.len(), 40);
 
-        let more_reservations = executor_manager.reserve_slots(30).await?;
+        let more_reservations = executor_manager
+            .reserve_slots("", 30, TaskResources::default())
+            .await?;
 
         assert_eq!(more_reservations.len(), 0);
 
@@ -615,7 +1912,9 @@ mod test {
                 let executor_manager = executor_manager.clone();
                 let sender = sender.clone();
                 tokio::task::spawn(async move {
-                    let reservations = executor_manager.reserve_slots(40).await;
+                    let reservations = executor_manager
+                        .reserve_slots("", 40, TaskResources::default())
+                        .await;
                     sender.send(reservations).await.unwrap();
                 });
             }
@@ -660,7 +1959,9 @@ mod test {
         }
 
         // All slots should be reserved
-        let reservations = executor_manager.reserve_slots(1).await?;
+        let reservations = executor_manager
+            .reserve_slots("", 1, TaskResources::default())
+            .await?;
 
         assert_eq!(reservations.len(), 0);
 
@@ -701,10 +2002,13 @@ mod test {
                 status: Some(ExecutorStatus {
                     status: Some(Status::Terminating(String::default())),
                 }),
+                task_progress: vec![],
             })
             .await?;
 
-        let reservations = executor_manager.reserve_slots(8).await?;
+        let reservations = executor_manager
+            .reserve_slots("", 8, TaskResources::default())
+            .await?;
 
         assert_eq!(reservations.len(), 4, "Expected only four reservations");
 
@@ -718,6 +2022,231 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_task_progress() -> Result<()> {
+        let cluster = test_cluster_context();
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+        for (executor_metadata, executor_data) in test_executors(2, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let progress = |job_id: &str, partition_id: u32| TaskProgress {
+            task_id: partition_id,
+            job_id: job_id.to_owned(),
+            stage_id: 1,
+            partition_id,
+            operator: "ParquetExec".to_owned(),
+            output_rows: 100,
+            output_bytes: 0,
+            elapsed_ms: 2000,
+            idle_ms: 1000,
+        };
+        for (executor_id, task_progress) in [
+            (
+                "executor-0",
+                vec![progress("job-1", 1), progress("job-2", 0)],
+            ),
+            ("executor-1", vec![progress("job-1", 0)]),
+        ] {
+            executor_manager
+                .save_executor_heartbeat(ExecutorHeartbeat {
+                    executor_id: executor_id.to_owned(),
+                    timestamp: timestamp_secs(),
+                    metrics: vec![],
+                    status: Some(ExecutorStatus {
+                        status: Some(Status::Active(String::default())),
+                    }),
+                    task_progress,
+                })
+                .await?;
+        }
+
+        let job_progress = executor_manager.get_task_progress("job-1");
+        assert_eq!(
+            job_progress,
+            vec![
+                ("executor-1".to_owned(), progress("job-1", 0)),
+                ("executor-0".to_owned(), progress("job-1", 1)),
+            ]
+        );
+        assert!(executor_manager.get_task_progress("job-3").is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_task_slot_oversubscription() -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+
+        for (executor_metadata, executor_data) in test_executors(1, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        let reservations = executor_manager
+            .reserve_slots("", 10, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 4);
+
+        let added = executor_manager
+            .set_task_slot_oversubscription("executor-0", 150)
+            .await?;
+        assert_eq!(added, 2);
+        assert_eq!(executor_manager.slot_metrics().total_slots, 6);
+        let metadata = executor_manager.get_executor_metadata("executor-0").await?;
+        assert_eq!(metadata.specification.logical_task_slots(), 6);
+
+        let oversubscribed = executor_manager
+            .reserve_slots("", 10, TaskResources::default())
+            .await?;
+        assert_eq!(oversubscribed.len(), 2);
+
+        // Going back to one task per task slot waits for the reserved task slots to return
+        let added = executor_manager
+            .set_task_slot_oversubscription("executor-0", 0)
+            .await?;
+        assert_eq!(added, 0);
+        assert_eq!(executor_manager.slot_metrics().total_slots, 4);
+
+        executor_manager.cancel_reservations(reservations).await?;
+        executor_manager.cancel_reservations(oversubscribed).await?;
+
+        let reservations = executor_manager
+            .reserve_slots("", 10, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 4);
+
+        assert!(executor_manager
+            .set_task_slot_oversubscription("executor-0", 50)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_gpu_reservations() -> Result<()> {
+        let cluster = test_cluster_context();
+
+        let executor_manager =
+            ExecutorManager::new(cluster.cluster_state(), TaskDistribution::Bias);
+
+        for (mut executor_metadata, executor_data) in test_executors(3, 4) {
+            if executor_metadata.id == "executor-1" {
+                executor_metadata.specification.gpus = 2;
+            }
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        // Each task of the stage requires a GPU, so only two of them fit on the only executor
+        // with GPUs
+        let gpu_task = TaskResources::default().with_gpus(1);
+        let reservations = executor_manager.reserve_slots("", 10, gpu_task).await?;
+        assert_eq!(reservations.len(), 2);
+        assert!(reservations
+            .iter()
+            .all(|reservation| reservation.executor_id == "executor-1"));
+        assert!(executor_manager
+            .reserve_slots("", 10, gpu_task)
+            .await?
+            .is_empty());
+
+        // The tasks not requiring GPUs take the other task slots of all executors
+        let cpu_reservations = executor_manager
+            .reserve_slots("", 20, TaskResources::default())
+            .await?;
+        assert_eq!(cpu_reservations.len(), 10);
+
+        executor_manager.cancel_reservations(reservations).await?;
+        let reservations = executor_manager.reserve_slots("", 10, gpu_task).await?;
+        assert_eq!(reservations.len(), 2);
+
+        // Executors polling for tasks only accept the tasks requiring the GPUs they have
+        let polling = ExecutorReservation::new_free("executor-0".to_owned())
+            .with_resources(TaskResources::unbounded().with_gpus(0));
+        assert!(polling.accepts(&TaskResources::new(1 << 30, 2)));
+        assert!(!polling.accepts(&gpu_task));
+        assert!(polling
+            .with_resources(TaskResources::unbounded().with_gpus(2))
+            .accepts(&gpu_task));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_virtual_time() -> Result<()> {
+        let clock = Arc::new(ManualClock::new(1_700_000_000_000));
+        let cluster_state = Arc::new(
+            InMemoryClusterState::default()
+                .with_reservation_lease(Some(Duration::from_secs(30)))
+                .with_clock(clock.clone()),
+        );
+        let executor_manager =
+            ExecutorManager::new(cluster_state, TaskDistribution::Bias)
+                .with_clock(clock.clone());
+
+        for (executor_metadata, executor_data) in test_executors(2, 4) {
+            executor_manager
+                .register_executor(executor_metadata, executor_data, false)
+                .await?;
+        }
+
+        // The reservations neither filled nor cancelled return to the available task slots
+        // once their lease expired
+        let reservations = executor_manager
+            .reserve_slots("", 8, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 8);
+        clock.advance(Duration::from_secs(29));
+        assert_eq!(executor_manager.expire_reservation_leases().await?, 0);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(executor_manager.expire_reservation_leases().await?, 8);
+        let reservations = executor_manager
+            .reserve_slots("", 8, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 8);
+
+        // The executors which sent no heartbeat since they registered expire first
+        executor_manager
+            .save_executor_heartbeat(ExecutorHeartbeat {
+                executor_id: "executor-0".to_string(),
+                timestamp: clock.now_secs(),
+                metrics: vec![],
+                status: Some(ExecutorStatus {
+                    status: Some(Status::Active(String::default())),
+                }),
+                task_progress: vec![],
+            })
+            .await?;
+        assert_eq!(
+            executor_manager.get_alive_executors_within_one_minute(),
+            HashSet::from(["executor-0".to_owned(), "executor-1".to_owned()])
+        );
+        assert!(executor_manager.get_expired_executors(30).is_empty());
+
+        clock.advance(Duration::from_secs(DEFAULT_EXECUTOR_TIMEOUT_SECONDS - 30));
+        let expired: Vec<String> = executor_manager
+            .get_expired_executors(30)
+            .into_iter()
+            .map(|heartbeat| heartbeat.executor_id)
+            .collect();
+        assert_eq!(expired, vec!["executor-1"]);
+        assert!(executor_manager
+            .get_alive_executors_within_one_minute()
+            .is_empty());
+
+        Ok(())
+    }
+
     fn test_executors(
         total_executors: usize,
         slots_per_executor: u32,
@@ -733,7 +2262,9 @@ mod test {
                     grpc_port: 9090,
                     specification: ExecutorSpecification {
                         task_slots: slots_per_executor,
+                        ..Default::default()
                     },
+                    pool: String::new(),
                 },
                 ExecutorData {
                     executor_id: format!("executor-{i}"),
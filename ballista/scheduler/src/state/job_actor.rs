@@ -0,0 +1,253 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Actors owning the execution graphs of the active jobs. Each job is updated on its own
+//! tokio task, so that the updates of one job never wait for those of another.
+
+use std::any::Any;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use log::{debug, error};
+use parking_lot::Mutex;
+use tokio::sync::{mpsc, oneshot};
+
+use ballista_core::error::Result;
+
+use crate::cluster::JobState;
+use crate::scheduler_server::event::QueryStageSchedulerEvent;
+use crate::scheduler_server::timestamp_millis;
+use crate::state::execution_graph::ExecutionGraph;
+
+/// Number of commands queued for the actor of a job, beyond which senders wait for the actor
+/// to catch up
+const COMMAND_BUFFER_SIZE: usize = 64;
+
+enum JobCommand {
+    /// Run a function on the execution graph
+    Run(Box<dyn FnOnce(&mut ExecutionGraph) + Send>),
+    /// Save the execution graph to the backend
+    Save(oneshot::Sender<Result<()>>),
+    /// Stop the actor and hand back the execution graph
+    Stop(oneshot::Sender<ExecutionGraph>),
+}
+
+/// Handle of the actor owning the execution graph of an active job. Commands sent through the
+/// handle are applied to the graph one at a time, in the order they were sent. The actor stops
+/// once it is stopped explicitly or all of its handles are dropped. At most
+/// [COMMAND_BUFFER_SIZE] commands are queued, so that a job whose updates fall behind slows
+/// down its senders instead of growing the queue without bound.
+///
+/// A command which panics fails the job instead of stopping the actor, so that the failed
+/// graph can still be stopped and saved. The failure is reported once by [Self::take_failure].
+#[derive(Clone)]
+pub(crate) struct JobActor {
+    job_id: String,
+    commands: mpsc::Sender<JobCommand>,
+    failure: Arc<Mutex<Option<QueryStageSchedulerEvent>>>,
+}
+
+impl JobActor {
+    /// Spawn the actor of a job, which saves the graph to `state` when asked to
+    pub(crate) fn spawn(graph: ExecutionGraph, state: Arc<dyn JobState>) -> Self {
+        let job_id = graph.job_id().to_owned();
+        let (commands, mut receiver) = mpsc::channel(COMMAND_BUFFER_SIZE);
+        let failure = Arc::new(Mutex::new(None));
+        let job_failure = failure.clone();
+        tokio::spawn(async move {
+            let mut graph = graph;
+            while let Some(command) = receiver.recv().await {
+                match command {
+                    JobCommand::Run(f) => {
+                        let result =
+                            std::panic::catch_unwind(AssertUnwindSafe(|| f(&mut graph)));
+                        if let Err(panic) = result {
+                            let fail_message = format!(
+                                "Scheduler panicked while updating the job: {}",
+                                panic_message(&panic)
+                            );
+                            error!("Job {} failed: {}", graph.job_id(), fail_message);
+                            graph.fail_job(fail_message.clone());
+                            *job_failure.lock() =
+                                Some(QueryStageSchedulerEvent::JobRunningFailed {
+                                    job_id: graph.job_id().to_owned(),
+                                    fail_message,
+                                    // The same query would panic again
+                                    query_error: true,
+                                    queued_at: graph.queued_at(),
+                                    failed_at: timestamp_millis(),
+                                });
+                        }
+                    }
+                    JobCommand::Save(reply) => {
+                        let result = state.save_job(graph.job_id(), &graph).await;
+                        let _ = reply.send(result);
+                    }
+                    JobCommand::Stop(reply) => {
+                        let _ = reply.send(graph);
+                        return;
+                    }
+                }
+            }
+            debug!("Actor of job {} stopped", graph.job_id());
+        });
+        Self {
+            job_id,
+            commands,
+            failure,
+        }
+    }
+
+    pub(crate) fn job_id(&self) -> &str {
+        &self.job_id
+    }
+
+    /// The event failing the job after a command panicked, returned only once
+    pub(crate) fn take_failure(&self) -> Option<QueryStageSchedulerEvent> {
+        self.failure.lock().take()
+    }
+
+    /// Run `f` on the execution graph after the commands sent before, returns None if the
+    /// actor stopped
+    pub(crate) async fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut ExecutionGraph) -> R + Send + 'static,
+    ) -> Option<R> {
+        let (reply, result) = oneshot::channel();
+        let command = JobCommand::Run(Box::new(move |graph| {
+            let _ = reply.send(f(graph));
+        }));
+        self.commands.send(command).await.ok()?;
+        result.await.ok()
+    }
+
+    /// Save the execution graph to the backend. Nothing is saved once the actor stopped, as
+    /// the graph was handed over to whoever stopped it.
+    pub(crate) async fn save(&self) -> Result<()> {
+        let (reply, result) = oneshot::channel();
+        match self.commands.send(JobCommand::Save(reply)).await {
+            Ok(()) => result.await.unwrap_or(Ok(())),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Stop the actor once the commands sent before are applied and take back the execution
+    /// graph, returns None if the actor stopped already
+    pub(crate) async fn stop(&self) -> Option<ExecutionGraph> {
+        let (reply, graph) = oneshot::channel();
+        self.commands.send(JobCommand::Stop(reply)).await.ok()?;
+        graph.await.ok()
+    }
+}
+
+fn panic_message(panic: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::memory::InMemoryJobState;
+    use crate::test_utils::test_aggregation_plan;
+    use ballista_core::utils::default_session_builder;
+
+    #[tokio::test]
+    async fn test_job_actor() -> Result<()> {
+        let state: Arc<dyn JobState> =
+            Arc::new(InMemoryJobState::new("", default_session_builder));
+        let mut graph = test_aggregation_plan(4).await;
+        graph.revive();
+        let available_tasks = graph.available_tasks();
+        let actor = JobActor::spawn(graph, state);
+
+        let task = actor
+            .run(|graph| graph.pop_next_task("executor-1"))
+            .await
+            .unwrap()?;
+        assert!(task.is_some());
+        assert_eq!(
+            actor.run(|graph| graph.available_tasks()).await,
+            Some(available_tasks - 1)
+        );
+        actor.save().await?;
+
+        // Commands sent by clones of the handle are applied by the same actor
+        let graph = actor.clone().stop().await.expect("graph of running actor");
+        assert_eq!(graph.job_id(), actor.job_id());
+        assert_eq!(graph.running_tasks().len(), 1);
+
+        assert!(actor.run(|graph| graph.available_tasks()).await.is_none());
+        assert!(actor.stop().await.is_none());
+        actor.save().await?;
+
+        Ok(())
+    }
+
+    // Senders wait while the queue of the actor is full instead of failing
+    #[tokio::test]
+    async fn test_job_actor_full_queue() -> Result<()> {
+        let state: Arc<dyn JobState> =
+            Arc::new(InMemoryJobState::new("", default_session_builder));
+        let actor = JobActor::spawn(test_aggregation_plan(4).await, state);
+
+        let commands = (0..COMMAND_BUFFER_SIZE * 4).map(|i| actor.run(move |_| i));
+        let results = futures::future::join_all(commands).await;
+        assert_eq!(
+            results,
+            (0..COMMAND_BUFFER_SIZE * 4).map(Some).collect::<Vec<_>>()
+        );
+        assert!(actor.stop().await.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_actor_panic() -> Result<()> {
+        let state: Arc<dyn JobState> =
+            Arc::new(InMemoryJobState::new("", default_session_builder));
+        let mut graph = test_aggregation_plan(4).await;
+        graph.revive();
+        let actor = JobActor::spawn(graph, state);
+
+        let result: Option<()> = actor.run(|_| panic!("corrupt graph")).await;
+        assert!(result.is_none());
+        // The failure is reported once and the actor keeps the failed graph
+        let failure = actor.take_failure();
+        assert!(matches!(
+            failure,
+            Some(QueryStageSchedulerEvent::JobRunningFailed { ref fail_message, .. })
+                if fail_message.contains("corrupt graph")
+        ));
+        assert!(actor.take_failure().is_none());
+        assert_eq!(actor.run(|graph| graph.is_successful()).await, Some(false));
+        let graph = actor.stop().await.expect("graph of failed job");
+        assert!(matches!(
+            graph.status().status,
+            Some(ballista_core::serde::protobuf::job_status::Status::Failed(
+                _
+            ))
+        ));
+
+        Ok(())
+    }
+}
@@ -15,41 +15,73 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use datafusion::arrow::compute::concat_batches;
+use datafusion::arrow::record_batch::RecordBatch;
 use datafusion::common::tree_node::{TreeNode, VisitRecursion};
 use datafusion::datasource::listing::{ListingTable, ListingTableUrl};
 use datafusion::datasource::source_as_provider;
 use datafusion::error::DataFusionError;
 use std::any::type_name;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 
+use crate::state::bucketed_tables::StoragePartitionedJoins;
 use crate::state::executor_manager::{ExecutorManager, ExecutorReservation};
-use crate::state::session_manager::SessionManager;
+use crate::state::result_cache::{result_fingerprint, CachedResult};
+use crate::state::scheduler_peers::SchedulerPeers;
+use crate::state::scheduling_recorder::{recorded_reservation, recorded_task};
+use crate::state::session_manager::{session_ballista_config, SessionManager};
+use crate::state::table_statistics::{ApplyTableStatistics, RemoveTableStatistics};
 use crate::state::task_manager::{TaskLauncher, TaskManager};
 
 use crate::cluster::BallistaCluster;
 use crate::config::SchedulerConfig;
+use crate::display::display_query_stages;
+use crate::planner::DistributedPlanner;
 use crate::state::execution_graph::TaskDescription;
 use ballista_core::error::{BallistaError, Result};
-use ballista_core::serde::protobuf::TaskStatus;
+use ballista_core::execution_plans::ResultExport;
+use ballista_core::serde::protobuf::scheduling_record::Record;
+use ballista_core::serde::protobuf::{
+    job_status, FillReservationsRecord, JobStatus, LaunchTasksRecord, RecordedTask,
+    RunningTaskInfo, TaskLogLines, TaskStatus,
+};
+use ballista_core::serde::scheduler::TaskResources;
 use ballista_core::serde::BallistaCodec;
-use datafusion::logical_expr::LogicalPlan;
+use datafusion::execution::context::SessionState;
+use datafusion::logical_expr::{Explain, LogicalPlan, PlanType, StringifiedPlan};
+use datafusion::physical_optimizer::join_selection::JoinSelection;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::analyze::AnalyzeExec;
 use datafusion::physical_plan::display::DisplayableExecutionPlan;
-use datafusion::physical_plan::ExecutionPlan;
+use datafusion::physical_plan::explain::ExplainExec;
+use datafusion::physical_plan::{collect, ExecutionPlan};
 use datafusion::prelude::SessionContext;
 use datafusion_proto::logical_plan::AsLogicalPlan;
 use datafusion_proto::physical_plan::AsExecutionPlan;
-use log::{debug, error, info};
+use futures::stream::BoxStream;
+use log::{debug, error, info, warn};
 use prost::Message;
+use tonic::metadata::{Ascii, MetadataValue};
 
+pub mod bucketed_tables;
 pub mod execution_graph;
 pub mod execution_graph_dot;
 pub mod executor_manager;
+pub(crate) mod job_actor;
+pub mod pool_routing;
+pub mod query_cost;
+pub mod query_quarantine;
+pub mod result_cache;
+pub mod scheduler_peers;
+pub mod scheduling_recorder;
 pub mod session_manager;
 pub mod session_registry;
+pub mod shared_scans;
+pub mod table_statistics;
 pub mod task_manager;
 
 pub fn decode_protobuf<T: Message + Default>(bytes: &[u8]) -> Result<T> {
@@ -91,6 +123,7 @@ pub struct SchedulerState<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPl
     pub executor_manager: ExecutorManager,
     pub task_manager: TaskManager<T, U>,
     pub session_manager: SessionManager,
+    pub scheduler_peers: SchedulerPeers,
     pub codec: BallistaCodec<T, U>,
     pub config: SchedulerConfig,
 }
@@ -119,13 +152,38 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             executor_manager: ExecutorManager::new(
                 cluster.cluster_state(),
                 config.task_distribution,
-            ),
+            )
+            .with_grpc_compression(config.grpc_compression)
+            .with_session_affinity(config.session_affinity)
+            .with_scheduling_recorder(config.scheduling_recorder.clone())
+            .with_clock(config.clock.clone()),
             task_manager: TaskManager::new(
                 cluster.job_state(),
                 codec.clone(),
-                scheduler_name,
-            ),
-            session_manager: SessionManager::new(cluster.job_state()),
+                scheduler_name.clone(),
+            )
+            .with_row_codec_max_columns(config.row_codec_max_columns)
+            .with_export_config(config.export_config.clone())
+            .with_shuffle_write_buffer_size(config.shuffle_write_buffer_size)
+            .with_shuffle_checksums(config.shuffle_checksums)
+            .with_shuffle_dictionary_sharing(config.shuffle_dictionary_sharing)
+            .with_task_resources(config.task_resources())
+            .with_result_cache(
+                Duration::from_secs(config.result_cache_ttl_seconds),
+                config.result_cache_max_bytes,
+            )
+            .with_query_quarantine(
+                config.query_quarantine_failures,
+                Duration::from_secs(config.query_quarantine_window_seconds),
+            )
+            .with_clock(config.clock.clone()),
+            session_manager: SessionManager::new(cluster.job_state())
+                .with_catalogs(config.plugins.catalogs())
+                .with_default_settings(config.session_settings.clone())
+                .with_object_stores(config.object_stores.clone()),
+            scheduler_peers: SchedulerPeers::new(scheduler_name, cluster.cluster_state())
+                .with_job_sharding(config.job_sharding)
+                .with_grpc_compression(config.grpc_compression),
             codec,
             config,
         }
@@ -143,21 +201,115 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             executor_manager: ExecutorManager::new(
                 cluster.cluster_state(),
                 config.task_distribution,
-            ),
+            )
+            .with_grpc_compression(config.grpc_compression)
+            .with_session_affinity(config.session_affinity)
+            .with_scheduling_recorder(config.scheduling_recorder.clone())
+            .with_clock(config.clock.clone()),
             task_manager: TaskManager::with_launcher(
                 cluster.job_state(),
                 codec.clone(),
-                scheduler_name,
+                scheduler_name.clone(),
                 dispatcher,
-            ),
-            session_manager: SessionManager::new(cluster.job_state()),
+            )
+            .with_row_codec_max_columns(config.row_codec_max_columns)
+            .with_export_config(config.export_config.clone())
+            .with_shuffle_write_buffer_size(config.shuffle_write_buffer_size)
+            .with_shuffle_checksums(config.shuffle_checksums)
+            .with_shuffle_dictionary_sharing(config.shuffle_dictionary_sharing)
+            .with_task_resources(config.task_resources())
+            .with_result_cache(
+                Duration::from_secs(config.result_cache_ttl_seconds),
+                config.result_cache_max_bytes,
+            )
+            .with_query_quarantine(
+                config.query_quarantine_failures,
+                Duration::from_secs(config.query_quarantine_window_seconds),
+            )
+            .with_clock(config.clock.clone()),
+            session_manager: SessionManager::new(cluster.job_state())
+                .with_catalogs(config.plugins.catalogs())
+                .with_default_settings(config.session_settings.clone())
+                .with_object_stores(config.object_stores.clone()),
+            scheduler_peers: SchedulerPeers::new(scheduler_name, cluster.cluster_state())
+                .with_job_sharding(config.job_sharding)
+                .with_grpc_compression(config.grpc_compression),
             codec,
             config,
         }
     }
 
-    pub async fn init(&self) -> Result<()> {
-        self.executor_manager.init().await
+    /// Initialize the cluster state and reload the jobs this scheduler was running before it
+    /// restarted. Returns the IDs of the recovered jobs, whose scheduling is yet to be resumed.
+    /// The tasks of the jobs which alive executors do not run anymore are scheduled again,
+    /// executors polling for work report the tasks they run in their next poll instead.
+    pub async fn init(&self) -> Result<Vec<String>> {
+        self.executor_manager.init().await?;
+
+        let alive_executors = self
+            .executor_manager
+            .get_alive_executors_within_one_minute();
+        let recovered = self
+            .task_manager
+            .recover_active_jobs(&alive_executors)
+            .await?;
+
+        if self.config.is_push_staged_scheduling() {
+            for executor_id in self.task_manager.unreconciled_executors() {
+                match self.executor_manager.get_running_tasks(&executor_id).await {
+                    Ok(running) => {
+                        if let Err(e) =
+                            self.reconcile_executor_tasks(&executor_id, &running).await
+                        {
+                            warn!("Fail to reconcile the tasks of executor {executor_id}: {e:?}")
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Fail to get the running tasks of executor {executor_id}: {e:?}")
+                    }
+                }
+            }
+        }
+
+        Ok(recovered)
+    }
+
+    /// Re-queue the tasks of the recovered jobs which an executor does not run anymore, see
+    /// [TaskManager::reconcile_executor_tasks], and return the task slots reserved for them,
+    /// as no status will release them. Returns the number of re-queued tasks.
+    pub(crate) async fn reconcile_executor_tasks(
+        &self,
+        executor_id: &str,
+        running: &[RunningTaskInfo],
+    ) -> Result<usize> {
+        let lost_tasks = self
+            .task_manager
+            .reconcile_executor_tasks(executor_id, running)
+            .await;
+        if lost_tasks.is_empty() {
+            return Ok(0);
+        }
+
+        let executor = self
+            .executor_manager
+            .get_executor_metadata(executor_id)
+            .await?;
+        let mut reservations = Vec::with_capacity(lost_tasks.len());
+        for task in &lost_tasks {
+            let resources = self
+                .task_manager
+                .get_task_resources(&task.job_id, task.stage_id)
+                .await;
+            reservations.push(
+                ExecutorReservation::new_free(executor_id.to_owned())
+                    .with_resources(resources)
+                    .with_pool(executor.pool.clone()),
+            );
+        }
+        self.executor_manager
+            .cancel_reservations(reservations)
+            .await?;
+        Ok(lost_tasks.len())
     }
 
     pub(crate) async fn update_task_statuses(
@@ -170,10 +322,32 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             .get_executor_metadata(executor_id)
             .await?;
 
-        let total_num_tasks = tasks_status.len();
-        let reservations = (0..total_num_tasks)
-            .map(|_| ExecutorReservation::new_free(executor_id.to_owned()))
-            .collect();
+        // Executors report statuses in batches, which may hold several statuses of a task if
+        // it was reported again. Only the latest one of each task is applied.
+        let tasks_status = latest_task_statuses(tasks_status);
+
+        // Each finished task returns the resources it was reserved with
+        let mut stage_resources: HashMap<(&str, u32), TaskResources> = HashMap::new();
+        let mut reservations = Vec::with_capacity(tasks_status.len());
+        for status in tasks_status.iter() {
+            let key = (status.job_id.as_str(), status.stage_id);
+            let resources = match stage_resources.get(&key) {
+                Some(resources) => *resources,
+                None => {
+                    let resources = self
+                        .task_manager
+                        .get_task_resources(&status.job_id, status.stage_id as usize)
+                        .await;
+                    stage_resources.insert(key, resources);
+                    resources
+                }
+            };
+            reservations.push(
+                ExecutorReservation::new_free(executor_id.to_owned())
+                    .with_resources(resources)
+                    .with_pool(executor.pool.clone()),
+            );
+        }
 
         let events = self
             .task_manager
@@ -184,7 +358,8 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
     }
 
     /// Process reservations which are offered. The basic process is
-    /// 1. Attempt to fill the offered reservations with available tasks
+    /// 1. Attempt to fill the offered reservations with available tasks, in the order given by
+    ///    the task distribution policy
     /// 2. For any reservation that filled, launch the assigned task on the executor.
     /// 3. For any reservations that could not be filled, cancel the reservation (i.e. return the
     ///    task slot back to the pool of available task slots).
@@ -196,14 +371,47 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
         &self,
         reservations: Vec<ExecutorReservation>,
     ) -> Result<(Vec<ExecutorReservation>, usize)> {
+        // The slots of the executors being decommissioned are not filled with new tasks, nor
+        // the ones of the executors which just rejected launched tasks in this round
+        let rejected_launches = self.executor_manager.take_rejected_launches();
+        let (reservations, excluded): (Vec<_>, Vec<_>) =
+            reservations.into_iter().partition(|reservation| {
+                !self
+                    .executor_manager
+                    .is_decommissioning(&reservation.executor_id)
+                    && !rejected_launches.contains(&reservation.executor_id)
+            });
+        if !excluded.is_empty() {
+            if let Err(e) = self.executor_manager.cancel_reservations(excluded).await {
+                error!("Error cancelling reservations of excluded executors: {e:?}");
+            }
+        }
+        let reservations = self.executor_manager.order_reservations(reservations);
         let pending_tasks = match self.task_manager.fill_reservations(&reservations).await
         {
             Ok((assignments, unassigned_reservations, pending_tasks)) => {
+                self.executor_manager.record_session_tasks(&assignments);
+                self.executor_manager.record(|| {
+                    Record::FillReservations(FillReservationsRecord {
+                        offered: reservations.iter().map(recorded_reservation).collect(),
+                        assignments: assignments
+                            .iter()
+                            .map(|(executor_id, task)| {
+                                recorded_task(executor_id, &task.partition)
+                            })
+                            .collect(),
+                        unassigned: unassigned_reservations.len() as u32,
+                        pending_tasks: pending_tasks.values().sum::<usize>() as u64,
+                    })
+                });
                 let executor_stage_assignments = Self::combine_task(assignments);
+                let filled_leases =
+                    filled_lease_ids(&reservations, &unassigned_reservations);
 
                 self.spawn_tasks_and_persist_reservations_back(
                     executor_stage_assignments,
                     unassigned_reservations,
+                    filled_leases,
                 );
 
                 pending_tasks
@@ -214,22 +422,28 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
                 self.executor_manager
                     .cancel_reservations(reservations)
                     .await?;
-                0
+                HashMap::new()
             }
         };
 
         let mut new_reservations = vec![];
 
-        if pending_tasks > 0 {
-            // If there are pending tasks available, try and schedule them
+        // If there are pending tasks available, try and schedule them on the executors of the
+        // pool of their jobs
+        for ((pool, resources), tasks) in pending_tasks.iter() {
             let pending_reservations = self
                 .executor_manager
-                .reserve_slots(pending_tasks as u32)
+                .reserve_slots_excluding(
+                    pool,
+                    *tasks as u32,
+                    *resources,
+                    &rejected_launches,
+                )
                 .await?;
             new_reservations.extend(pending_reservations);
         }
 
-        Ok((new_reservations, pending_tasks))
+        Ok((new_reservations, pending_tasks.into_values().sum()))
     }
 
     fn spawn_tasks_and_persist_reservations_back(
@@ -239,38 +453,125 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             HashMap<(String, usize), Vec<TaskDescription>>,
         >,
         mut unassigned_reservations: Vec<ExecutorReservation>,
+        filled_leases: HashSet<String>,
     ) {
         let task_manager = self.task_manager.clone();
         let executor_manager = self.executor_manager.clone();
 
         tokio::spawn(async move {
             for (executor_id, tasks) in executor_stage_assignments.into_iter() {
+                // Resources held by the tasks of each stage to be launched for one executor
+                let mut task_resources: HashMap<(String, usize), (TaskResources, usize)> =
+                    HashMap::new();
+                for ((job_id, stage_id), stage_tasks) in tasks.iter() {
+                    let resources =
+                        task_manager.get_task_resources(job_id, *stage_id).await;
+                    task_resources.insert(
+                        (job_id.clone(), *stage_id),
+                        (resources, stage_tasks.len()),
+                    );
+                }
+                let recorded_tasks = executor_manager.is_recording().then(|| {
+                    tasks
+                        .values()
+                        .flatten()
+                        .map(|task| recorded_task(&executor_id, &task.partition))
+                        .collect::<Vec<_>>()
+                });
                 let tasks: Vec<Vec<TaskDescription>> = tasks.into_values().collect();
-                // Total number of tasks to be launched for one executor
-                let n_tasks: usize =
-                    tasks.iter().map(|stage_tasks| stage_tasks.len()).sum();
-
-                match executor_manager.get_executor_metadata(&executor_id).await {
-                    Ok(executor) => {
-                        if let Err(e) = task_manager
-                            .launch_multi_task(&executor, tasks, &executor_manager)
-                            .await
-                        {
+
+                let (launch_error, rejected_tasks) = match executor_manager
+                    .get_executor_metadata(&executor_id)
+                    .await
+                {
+                    Ok(executor) => match task_manager
+                        .launch_multi_task(&executor, tasks, &executor_manager)
+                        .await
+                    {
+                        Ok(rejected_tasks) => {
+                            if !rejected_tasks.is_empty() {
+                                warn!(
+                                    "Executor {} rejected {} of the launched tasks",
+                                    executor_id,
+                                    rejected_tasks.len()
+                                );
+                                // The task slots of the rejected tasks are returned and the
+                                // tasks are scheduled again, on other executors first
+                                executor_manager.record_rejected_launch(&executor_id);
+                                for task in rejected_tasks.iter() {
+                                    let stage_key =
+                                        (task.job_id.clone(), task.stage_id as usize);
+                                    if let Some((resources, _)) =
+                                        task_resources.get(&stage_key)
+                                    {
+                                        unassigned_reservations.push(
+                                            ExecutorReservation::new_free(
+                                                executor_id.clone(),
+                                            )
+                                            .with_resources(*resources),
+                                        );
+                                    }
+                                }
+                                task_manager
+                                    .requeue_rejected_tasks(&executor_id, &rejected_tasks)
+                                    .await;
+                            }
+                            (None, rejected_tasks)
+                        }
+                        Err(e) => {
                             error!("Failed to launch new task: {:?}", e);
                             // set resource back.
-                            unassigned_reservations.append(&mut vec![
+                            for (resources, n_tasks) in task_resources.into_values() {
+                                unassigned_reservations.append(&mut vec![
                                     ExecutorReservation::new_free(
                                         executor_id.clone(),
-                                    );
+                                    )
+                                    .with_resources(resources);
                                     n_tasks
                                 ]);
+                            }
+                            (Some(format!("{e:?}")), vec![])
                         }
-                    }
+                    },
                     Err(e) => {
                         error!("Failed to launch new task, could not get executor metadata: {:?}", e);
                         // here no need set resource back.
+                        (Some(format!("{e:?}")), vec![])
                     }
                 };
+                if let Some(tasks) = recorded_tasks {
+                    executor_manager.record(|| {
+                        Record::LaunchTasks(LaunchTasksRecord {
+                            rejected: rejected_tasks
+                                .iter()
+                                .map(|task| RecordedTask {
+                                    executor_id: executor_id.clone(),
+                                    job_id: task.job_id.clone(),
+                                    stage_id: task.stage_id,
+                                    partition_id: task
+                                        .task_id
+                                        .as_ref()
+                                        .map(|task_id| task_id.partition_id)
+                                        .unwrap_or_default(),
+                                })
+                                .collect(),
+                            executor_id,
+                            tasks,
+                            error: launch_error.unwrap_or_default(),
+                        })
+                    });
+                }
+            }
+            // The task slots of the filled reservations are now held by the launched tasks, or
+            // were returned above if the launch failed
+            if let Err(e) = executor_manager
+                .release_reservation_leases(filled_leases)
+                .await
+            {
+                error!(
+                    "Failed to release the leases of filled reservations: {:?}",
+                    e
+                );
             }
             if !unassigned_reservations.is_empty() {
                 // If any reserved slots remain, return them to the pool
@@ -320,10 +621,23 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
     ) -> Result<Arc<dyn ExecutionPlan>> {
         let start = Instant::now();
 
+        // The metrics of the stages of an EXPLAIN ANALYZE are aggregated over their tasks,
+        // so the metrics of each partition shown with VERBOSE are not available
+        if let LogicalPlan::Analyze(analyze) = plan {
+            if analyze.verbose {
+                return Err(BallistaError::NotImplemented(
+                    "EXPLAIN ANALYZE VERBOSE is not supported, use EXPLAIN ANALYZE to get \
+                    the plans of the stages with their metrics"
+                        .to_owned(),
+                ));
+            }
+        }
+
         if log::max_level() >= log::Level::Debug {
             // optimizing the plan here is redundant because the physical planner will do this again
             // but it is helpful to see what the optimized plan will be
-            let optimized_plan = session_ctx.state().optimize(plan)?;
+            let optimized_plan =
+                self.planning_state(session_ctx.as_ref()).optimize(plan)?;
             debug!("Optimized plan: {}", optimized_plan.display_indent());
         }
 
@@ -366,7 +680,15 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
             Ok(VisitRecursion::Continue)
         })?;
 
-        let plan = session_ctx.state().create_physical_plan(plan).await?;
+        let planning_state = self.planning_state(session_ctx.as_ref());
+        let physical_plan = planning_state.create_physical_plan(plan).await?;
+        let plan = match plan {
+            LogicalPlan::Explain(explain) => {
+                self.explain_query_stages(job_id, &planning_state, explain, physical_plan)
+                    .await?
+            }
+            _ => physical_plan,
+        };
         debug!(
             "Physical plan: {}",
             DisplayableExecutionPlan::new(plan.as_ref()).indent()
@@ -379,57 +701,431 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> SchedulerState<T,
         Ok(plan)
     }
 
-    /// Spawn a delayed future to clean up job data on both Scheduler and Executors
-    pub(crate) fn clean_up_successful_job(&self, job_id: String) {
-        self.executor_manager.clean_up_job_data_delayed(
-            job_id.clone(),
-            self.config.finished_job_data_clean_up_interval_seconds,
-        );
+    /// Plan an `EXPLAIN` statement and return its textual plans right away, without running
+    /// a job. The final physical plan is replaced with the query stages it is planned into.
+    pub(crate) async fn explain_job(
+        &self,
+        job_id: &str,
+        session_ctx: Arc<SessionContext>,
+        plan: &LogicalPlan,
+    ) -> Result<RecordBatch> {
+        let plan = self.plan_job(job_id, session_ctx.clone(), plan).await?;
+        let schema = plan.schema();
+        let batches = collect(plan, session_ctx.task_ctx()).await?;
+        Ok(concat_batches(&schema, &batches)?)
+    }
+
+    /// Replace the final physical plan of an `EXPLAIN` with the query stages it is planned
+    /// into, annotated with their partition counts and how their outputs are exchanged
+    async fn explain_query_stages(
+        &self,
+        job_id: &str,
+        planning_state: &SessionState,
+        explain: &Explain,
+        plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let explain_exec =
+            if let Some(explain_exec) = plan.as_any().downcast_ref::<ExplainExec>() {
+                explain_exec
+            } else {
+                return Ok(plan);
+            };
+        if !explain_exec
+            .stringified_plans()
+            .iter()
+            .any(|p| p.plan_type == PlanType::FinalPhysicalPlan)
+        {
+            // Only the logical plans are explained
+            return Ok(plan);
+        }
+
+        let input = planning_state.create_physical_plan(&explain.plan).await?;
+        let stages = DistributedPlanner::new().plan_query_stages(job_id, input)?;
+        let stages = display_query_stages(&stages)?;
+
+        let stringified_plans = explain_exec
+            .stringified_plans()
+            .iter()
+            .map(|p| match p.plan_type {
+                PlanType::FinalPhysicalPlan => {
+                    StringifiedPlan::new(PlanType::FinalPhysicalPlan, stages.clone())
+                }
+                _ => p.clone(),
+            })
+            .collect();
+
+        Ok(Arc::new(ExplainExec::new(
+            explain_exec.schema(),
+            stringified_plans,
+            explain_exec.verbose(),
+        )))
+    }
+
+    /// Session state to plan a job in, with the optimizer rules registered with the scheduler
+    /// appended to those of the session. When the statistics of tables scanned by previous
+    /// jobs are known, they are provided to the physical optimizer rules up to the join
+    /// selection, so that join orders and exchange strategies are chosen based on them. The
+    /// scans are unwrapped again before the remaining rules run, which repartition them and
+    /// enforce the distribution and ordering of the plan. Joins of bucketed tables are
+    /// planned last, as they rely on the exchanges the other rules added.
+    fn planning_state(&self, session_ctx: &SessionContext) -> SessionState {
+        let mut state = session_ctx.state();
+        let extra_rules = &self.config.optimizer_rules;
+        for rule in &extra_rules.logical {
+            state = state.add_optimizer_rule(rule.clone());
+        }
+        let table_statistics = self.task_manager.table_statistics();
+        let has_statistics = !table_statistics.is_empty();
+
+        let default_rules = state.physical_optimizers();
+        let join_selection_name = JoinSelection::new().name().to_owned();
+        let join_selection = default_rules
+            .iter()
+            .position(|rule| rule.name() == join_selection_name);
+
+        let mut rules: Vec<Arc<dyn PhysicalOptimizerRule + Send + Sync>> = vec![];
+        match join_selection {
+            Some(index) if has_statistics => {
+                rules.push(Arc::new(ApplyTableStatistics::new(table_statistics)));
+                rules.extend(default_rules[..=index].iter().cloned());
+                rules.push(Arc::new(RemoveTableStatistics::default()));
+                rules.extend(default_rules[index + 1..].iter().cloned());
+            }
+            _ => rules.extend(default_rules.iter().cloned()),
+        }
+        rules.extend(extra_rules.physical.iter().cloned());
+        rules.push(Arc::new(StoragePartitionedJoins::default()));
+        state.with_physical_optimizer_rules(rules)
+    }
+
+    /// Results of a recently completed job to serve for a planned job instead of executing it.
+    /// If there are none and the job's session enables the result cache, the job's results are
+    /// cached once it succeeds.
+    pub(crate) fn cached_result(
+        &self,
+        job_id: &str,
+        session_ctx: &SessionContext,
+        plan: &dyn ExecutionPlan,
+    ) -> Option<CachedResult> {
+        // Analyzed jobs have to run to collect the metrics of their stages
+        if plan.as_any().is::<AnalyzeExec>() {
+            return None;
+        }
+        let result_cache = self.task_manager.result_cache();
+        // The jobs exporting their results have to run to write them to their export location
+        if !result_cache.is_enabled()
+            || !session_ballista_config(session_ctx)
+                .map(|config| {
+                    config.cache_results() && config.job_export_location().is_empty()
+                })
+                .unwrap_or(false)
+        {
+            return None;
+        }
+        let fingerprint = result_fingerprint(plan)?;
+        let result = result_cache.get(fingerprint);
+        if result.is_none() {
+            result_cache.add_pending_job(job_id, fingerprint);
+        }
+        result
+    }
+
+    /// Fingerprint of the results of a planned job to share them with the jobs of identical
+    /// queries submitted while it runs, if the scheduler de-duplicates queries
+    pub(crate) fn shared_query_fingerprint(
+        &self,
+        session_ctx: &SessionContext,
+        plan: &dyn ExecutionPlan,
+    ) -> Option<u64> {
+        // Analyzed jobs have to run to collect the metrics of their stages, and the jobs
+        // exporting their results to write them to their export location
+        if !self.config.query_deduplication
+            || plan.as_any().is::<AnalyzeExec>()
+            || session_ballista_config(session_ctx)
+                .map(|config| !config.job_export_location().is_empty())
+                .unwrap_or(false)
+        {
+            return None;
+        }
+        result_fingerprint(plan)
+    }
+
+    /// Cache the results of a successful job if its session enabled the result cache, returns
+    /// whether they were cached
+    pub(crate) async fn cache_job_result(&self, job_id: &str) -> Result<bool> {
+        let result_cache = self.task_manager.result_cache();
+        let fingerprint = match result_cache.remove_pending_job(job_id) {
+            Some(fingerprint) => fingerprint,
+            None => return Ok(false),
+        };
+        if let Some(JobStatus {
+            status: Some(job_status::Status::Successful(job)),
+            ..
+        }) = self.task_manager.get_job_status(job_id).await?
+        {
+            Ok(result_cache.insert(fingerprint, job_id, job.partition_location))
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Spawn a delayed future to clean up job data on both Scheduler and Executors. The data
+    /// is kept for the result time to live of the job, if it has one. The data of jobs whose
+    /// results are cached is kept until the jobs served them last had the clean up interval to
+    /// read them, and the data of jobs whose shared scans other jobs still read until they
+    /// complete. The checkpoints of the stage
+    /// outputs of the job in `checkpoint_dir` are deleted with its data.
+    pub(crate) fn clean_up_successful_job(
+        &self,
+        job_id: String,
+        result_cached: bool,
+        result_ttl_seconds: u64,
+        checkpoint_dir: Option<String>,
+        result_export: Option<ResultExport>,
+    ) {
+        let mut data_clean_up_interval = if result_ttl_seconds > 0 {
+            result_ttl_seconds
+        } else {
+            self.config.finished_job_data_clean_up_interval_seconds
+        };
+        // An interval of 0 disables the clean up
+        if result_cached && data_clean_up_interval > 0 {
+            data_clean_up_interval = self
+                .task_manager
+                .result_cache()
+                .data_retention_seconds(data_clean_up_interval);
+        }
+        if !self
+            .task_manager
+            .shared_scans()
+            .defer_clean_up(&job_id, Some(data_clean_up_interval))
+        {
+            self.clean_up_job_results_delayed(
+                job_id.clone(),
+                data_clean_up_interval,
+                checkpoint_dir,
+                result_export,
+            );
+        }
         self.task_manager.clean_up_job_delayed(
             job_id,
             self.config.finished_job_state_clean_up_interval_seconds,
         );
     }
 
-    /// Spawn a delayed future to clean up job data on both Scheduler and Executors
-    pub(crate) fn clean_up_failed_job(&self, job_id: String) {
-        self.executor_manager.clean_up_job_data(job_id.clone());
+    /// Spawn a delayed future to clean up job data on both Scheduler and Executors. The data
+    /// of jobs whose shared scans other jobs still read is kept until they complete.
+    pub(crate) fn clean_up_failed_job(
+        &self,
+        job_id: String,
+        checkpoint_dir: Option<String>,
+    ) {
+        if !self
+            .task_manager
+            .shared_scans()
+            .defer_clean_up(&job_id, None)
+        {
+            self.executor_manager
+                .clean_up_job_data(job_id.clone(), checkpoint_dir);
+        }
         self.task_manager.clean_up_job_delayed(
             job_id,
             self.config.finished_job_state_clean_up_interval_seconds,
         );
     }
+
+    /// Forget the scans shared by a completed job and clean up the data of the jobs it was the
+    /// last to read. Returns the IDs of the jobs whose stages waited for its output and now
+    /// run their own tasks.
+    pub(crate) async fn complete_shared_scans(&self, job_id: &str) -> Vec<String> {
+        let (released_jobs, clean_ups) =
+            self.task_manager.complete_shared_scans(job_id).await;
+        for (producer_job_id, clean_up_interval) in clean_ups {
+            let checkpoint_dir =
+                self.task_manager.get_checkpoint_dir(&producer_job_id).await;
+            match clean_up_interval {
+                Some(interval) => {
+                    let result_export =
+                        self.task_manager.get_result_export(&producer_job_id).await;
+                    self.clean_up_job_results_delayed(
+                        producer_job_id,
+                        interval,
+                        checkpoint_dir,
+                        result_export,
+                    )
+                }
+                None => self
+                    .executor_manager
+                    .clean_up_job_data(producer_job_id, checkpoint_dir),
+            }
+        }
+        released_jobs
+    }
+
+    /// Delete the data of a successful job from the executors and its exported results after
+    /// `interval` seconds and report its results as expired
+    fn clean_up_job_results_delayed(
+        &self,
+        job_id: String,
+        interval: u64,
+        checkpoint_dir: Option<String>,
+        result_export: Option<ResultExport>,
+    ) {
+        self.task_manager.expire_job_results_delayed(
+            job_id.clone(),
+            interval,
+            result_export,
+        );
+        self.executor_manager
+            .clean_up_job_data_delayed(job_id, checkpoint_dir, interval);
+    }
+
+    /// Fetch the log lines of a task attempt from the executor it ran on, forwarding the
+    /// `authorization` header of the user. Returns `None` if the job or the task is unknown.
+    pub(crate) async fn fetch_task_logs(
+        &self,
+        job_id: &str,
+        task_id: usize,
+        from_line: u64,
+        follow: bool,
+        authorization: Option<MetadataValue<Ascii>>,
+    ) -> Result<Option<BoxStream<'static, Result<TaskLogLines>>>> {
+        let attempt = self
+            .task_manager
+            .get_task_attempts(job_id, None)
+            .await?
+            .and_then(|attempts| {
+                attempts
+                    .into_iter()
+                    .find(|attempt| attempt.task_id as usize == task_id)
+            });
+        let attempt = match attempt {
+            Some(attempt) => attempt,
+            None => return Ok(None),
+        };
+        let mut client = self
+            .executor_manager
+            .get_flight_client(&attempt.executor_id)
+            .await?;
+        client
+            .fetch_task_logs(job_id, task_id, from_line, follow, authorization)
+            .await
+            .map(Some)
+    }
+}
+
+/// Leases of the offered reservations which were filled with tasks
+fn filled_lease_ids(
+    offered: &[ExecutorReservation],
+    unassigned: &[ExecutorReservation],
+) -> HashSet<String> {
+    let unassigned: HashSet<&String> = unassigned
+        .iter()
+        .filter_map(|reservation| reservation.lease_id.as_ref())
+        .collect();
+    offered
+        .iter()
+        .filter_map(|reservation| reservation.lease_id.as_ref())
+        .filter(|lease_id| !unassigned.contains(lease_id))
+        .cloned()
+        .collect()
+}
+
+/// The latest status of each task of a batch of statuses, in the order of their arrival
+fn latest_task_statuses(tasks_status: Vec<TaskStatus>) -> Vec<TaskStatus> {
+    let mut seen: HashSet<(String, u32)> = HashSet::with_capacity(tasks_status.len());
+    let mut latest: Vec<TaskStatus> = tasks_status
+        .into_iter()
+        .rev()
+        .filter(|status| seen.insert((status.job_id.clone(), status.task_id)))
+        .collect();
+    latest.reverse();
+    latest
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::state::SchedulerState;
-    use ballista_core::config::{BallistaConfig, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS};
+    use crate::state::{latest_task_statuses, SchedulerState};
+    use ballista_core::config::{
+        BallistaConfig, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, BALLISTA_JOB_LABELS,
+        BALLISTA_JOB_POOL, BALLISTA_JOB_PRIORITY, BALLISTA_SESSION_MAX_TASK_SLOTS,
+    };
     use ballista_core::error::Result;
     use ballista_core::serde::protobuf::{
         task_status, ShuffleWritePartition, SuccessfulTask, TaskStatus,
     };
     use ballista_core::serde::scheduler::{
-        ExecutorData, ExecutorMetadata, ExecutorSpecification,
+        ExecutorData, ExecutorMetadata, ExecutorSpecification, TaskResources,
     };
     use ballista_core::serde::BallistaCodec;
 
+    use crate::clock::{Clock, ManualClock};
     use crate::config::SchedulerConfig;
+    use crate::state::executor_manager::ExecutorReservation;
+    use crate::state::task_manager::JobOverview;
 
     use crate::scheduler_server::timestamp_millis;
-    use crate::test_utils::{test_cluster_context, BlackholeTaskLauncher};
+    use crate::test_utils::{
+        await_condition, test_cluster_context, BlackholeTaskLauncher,
+        RejectingTaskLauncher,
+    };
+    use ballista_core::utils::collect_plan_metrics;
+    use datafusion::arrow::array::StringArray;
     use datafusion::arrow::datatypes::{DataType, Field, Schema};
-    use datafusion::logical_expr::{col, sum};
-    use datafusion::physical_plan::ExecutionPlan;
-    use datafusion::prelude::SessionContext;
+    use datafusion::config::ConfigOptions;
+    use datafusion::logical_expr::{col, sum, PlanType};
+    use datafusion::physical_optimizer::PhysicalOptimizerRule;
+    use datafusion::physical_plan::display::DisplayableExecutionPlan;
+    use datafusion::physical_plan::explain::ExplainExec;
+    use datafusion::physical_plan::{collect, ExecutionPlan};
+    use datafusion::prelude::{ParquetReadOptions, SessionContext};
     use datafusion::test_util::scan_empty;
     use datafusion_proto::protobuf::LogicalPlanNode;
     use datafusion_proto::protobuf::PhysicalPlanNode;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
+    use std::time::Duration;
 
     const TEST_SCHEDULER_NAME: &str = "localhost:50050";
 
+    #[tokio::test]
+    async fn test_clean_up_job_virtual_time() -> Result<()> {
+        let clock = Arc::new(ManualClock::new(1_700_000_000_000));
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default().with_clock(clock.clone()),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+        let task_manager = &state.task_manager;
+        task_manager
+            .queue_job("job-1", "", clock.now_millis())
+            .await?;
+        task_manager
+            .fail_unscheduled_job("job-1", "failed".to_owned())
+            .await?;
+
+        // The state of the completed job is kept until the clean up interval passed on the
+        // clock of the scheduler
+        task_manager.clean_up_job_delayed("job-1".to_owned(), 60);
+        tokio::task::yield_now().await;
+        clock.advance(Duration::from_secs(59));
+        tokio::task::yield_now().await;
+        assert!(task_manager.get_job_status("job-1").await?.is_some());
+
+        clock.advance(Duration::from_secs(1));
+        let removed = await_condition(Duration::from_millis(10), 100, || async {
+            Ok(task_manager.get_job_status("job-1").await?.is_none())
+        })
+        .await?;
+        assert!(removed);
+        Ok(())
+    }
+
     // We should free any reservations which are not assigned
     #[tokio::test]
     async fn test_offer_free_reservations() -> Result<()> {
@@ -455,8 +1151,12 @@ mod test {
 
         // Need sleep wait for the spawn task work done.
         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.executor_manager.slot_metrics().reserved_slots, 0);
         // All reservations should have been cancelled so we should be able to reserve them now
-        let reservations = state.executor_manager.reserve_slots(4).await?;
+        let reservations = state
+            .executor_manager
+            .reserve_slots("", 4, TaskResources::default())
+            .await?;
 
         assert_eq!(reservations.len(), 4);
 
@@ -556,23 +1256,28 @@ mod test {
         assert!(result.is_empty());
 
         // All task slots should be assigned so we should not be able to reserve more tasks
-        let reservations = state.executor_manager.reserve_slots(4).await?;
+        let reservations = state
+            .executor_manager
+            .reserve_slots("", 4, TaskResources::default())
+            .await?;
 
         assert_eq!(reservations.len(), 0);
 
         Ok(())
     }
 
-    // We should generate a new event for tasks that are still pending
+    // The tasks lost by an executor while the scheduler restarted are scheduled again and
+    // their slots returned
     #[tokio::test]
-    async fn test_offer_resubmit_pending() -> Result<()> {
+    async fn test_reconcile_executor_tasks() -> Result<()> {
         let config = BallistaConfig::builder()
             .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
             .build()?;
+        let cluster = test_cluster_context();
 
         let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
             Arc::new(SchedulerState::new_with_task_launcher(
-                test_cluster_context(),
+                cluster.clone(),
                 BallistaCodec::default(),
                 TEST_SCHEDULER_NAME.into(),
                 SchedulerConfig::default(),
@@ -580,105 +1285,577 @@ mod test {
             ));
 
         let session_ctx = state.session_manager.create_session(&config).await?;
-
         let plan = test_graph(session_ctx.clone()).await;
-
-        // Create a job
         state
             .task_manager
             .queue_job("job-1", "", timestamp_millis())
             .await?;
         state
             .task_manager
-            .submit_job(
-                "job-1",
-                "",
-                session_ctx.session_id().as_str(),
-                plan.clone(),
-                0,
-            )
+            .submit_job("job-1", "", session_ctx.session_id().as_str(), plan, 0)
             .await?;
 
         let executors = test_executors(1, 4);
-
         let (executor_metadata, executor_data) = executors[0].clone();
-
-        // Complete the first stage. So we should now have 4 pending tasks for this job stage 2
-        {
-            let plan_graph = state
-                .task_manager
-                .get_active_execution_graph("job-1")
-                .unwrap();
-            let task_def = plan_graph
-                .write()
-                .await
-                .pop_next_task(&executor_data.executor_id)?
-                .unwrap();
-            let mut partitions: Vec<ShuffleWritePartition> = vec![];
-            for partition_id in 0..4 {
-                partitions.push(ShuffleWritePartition {
-                    partition_id: partition_id as u64,
-                    path: "some/path".to_string(),
-                    num_batches: 1,
-                    num_rows: 1,
-                    num_bytes: 1,
-                })
-            }
-            state
-                .task_manager
-                .update_task_statuses(
-                    &executor_metadata,
-                    vec![TaskStatus {
-                        task_id: task_def.task_id as u32,
-                        job_id: "job-1".to_string(),
-                        stage_id: task_def.partition.stage_id as u32,
-                        stage_attempt_num: task_def.stage_attempt_num as u32,
-                        partition_id: task_def.partition.partition_id as u32,
-                        launch_time: 0,
-                        start_exec_time: 0,
-                        end_exec_time: 0,
-                        metrics: vec![],
-                        status: Some(task_status::Status::Successful(SuccessfulTask {
-                            executor_id: executor_data.executor_id.clone(),
-                            partitions,
-                        })),
-                    }],
-                )
-                .await?;
-        }
-
-        state
+        let executor_id = executor_metadata.id.clone();
+        let reservations = state
             .executor_manager
-            .register_executor(executor_metadata, executor_data, false)
+            .register_executor(executor_metadata, executor_data, true)
             .await?;
+        let (result, _) = state.offer_reservation(reservations).await?;
+        assert!(result.is_empty());
+        state.task_manager.save_active_jobs().await?;
 
-        let reservations = state.executor_manager.reserve_slots(1).await?;
-
-        assert_eq!(reservations.len(), 1);
-
-        // Offer the reservation. It should be filled with one of the 4 pending tasks. The other 3 should
-        // be reserved for the other 3 tasks, emitting another offer event
-        let (reservations, pending) = state.offer_reservation(reservations).await?;
-
-        assert_eq!(pending, 3);
-        assert_eq!(reservations.len(), 3);
-
-        // Remaining 3 task slots should be reserved for pending tasks
-        let reservations = state.executor_manager.reserve_slots(4).await?;
+        // The restarted scheduler recovers the job, whose tasks the executor lost
+        let restarted: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                cluster,
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+        let alive_executors = [executor_id.clone()].into_iter().collect();
+        let recovered = restarted
+            .task_manager
+            .recover_active_jobs(&alive_executors)
+            .await?;
+        assert_eq!(recovered, vec!["job-1".to_owned()]);
+        assert_eq!(
+            restarted
+                .executor_manager
+                .reserve_slots("", 4, TaskResources::default())
+                .await?
+                .len(),
+            0
+        );
 
-        assert_eq!(reservations.len(), 0);
+        let requeued = restarted
+            .reconcile_executor_tasks(&executor_id, &[])
+            .await?;
+        assert_eq!(requeued, 4);
+        assert_eq!(
+            restarted
+                .executor_manager
+                .reserve_slots("", 4, TaskResources::default())
+                .await?
+                .len(),
+            4
+        );
 
         Ok(())
     }
 
-    fn test_executors(
-        total_executors: usize,
-        slots_per_executor: u32,
-    ) -> Vec<(ExecutorMetadata, ExecutorData)> {
-        let mut result: Vec<(ExecutorMetadata, ExecutorData)> = vec![];
-
-        for i in 0..total_executors {
-            result.push((
+    // Tasks which the executor rejects are scheduled again and their slots returned
+    #[tokio::test]
+    async fn test_offer_rejected_tasks() -> Result<()> {
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .build()?;
+
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(RejectingTaskLauncher::default()),
+            ));
+
+        let session_ctx = state.session_manager.create_session(&config).await?;
+        let plan = test_graph(session_ctx.clone()).await;
+        state
+            .task_manager
+            .queue_job("job-1", "", timestamp_millis())
+            .await?;
+        state
+            .task_manager
+            .submit_job("job-1", "", session_ctx.session_id().as_str(), plan, 0)
+            .await?;
+
+        let available_tasks = || {
+            state
+                .task_manager
+                .with_active_graph("job-1", |graph| graph.available_tasks())
+        };
+        let launched_tasks = available_tasks().await.unwrap();
+        assert!(launched_tasks > 0);
+
+        let (executor_metadata, executor_data) = test_executors(1, 4)[0].clone();
+        let reservations = state
+            .executor_manager
+            .register_executor(executor_metadata, executor_data, true)
+            .await?;
+
+        let (result, pending) = state.offer_reservation(reservations).await?;
+        assert_eq!(pending, 0);
+        assert!(result.is_empty());
+        assert_eq!(available_tasks().await, Some(0));
+
+        // Need sleep wait for the spawn task work done.
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        assert_eq!(state.executor_manager.slot_metrics().reserved_slots, 0);
+        assert_eq!(available_tasks().await, Some(launched_tasks));
+
+        // The executor is offered no tasks in the next round
+        let reservations = state
+            .executor_manager
+            .reserve_slots("", 4, TaskResources::default())
+            .await?;
+        assert_eq!(reservations.len(), 4);
+        let (result, _) = state.offer_reservation(reservations).await?;
+        assert!(result.is_empty());
+        assert_eq!(state.executor_manager.slot_metrics().reserved_slots, 0);
+        assert_eq!(available_tasks().await, Some(launched_tasks));
+
+        // But in the following ones
+        let reservations = state
+            .executor_manager
+            .reserve_slots("", 4, TaskResources::default())
+            .await?;
+        state.offer_reservation(reservations).await?;
+        assert_eq!(available_tasks().await, Some(0));
+
+        Ok(())
+    }
+
+    // The statuses of the tasks of a cancelled job release the resources they were reserved
+    // with, not the default ones
+    #[tokio::test]
+    async fn test_task_resources_of_cancelled_job() -> Result<()> {
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .build()?;
+
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default()
+                    .with_task_memory_bytes(1 << 30)
+                    .with_task_vcores(1),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+
+        let session_ctx = state.session_manager.create_session(&config).await?;
+        let plan = test_graph(session_ctx.clone()).await;
+        state
+            .task_manager
+            .queue_job("job-1", "", timestamp_millis())
+            .await?;
+        state
+            .task_manager
+            .submit_job("job-1", "", session_ctx.session_id().as_str(), plan, 0)
+            .await?;
+
+        let resources = state.task_manager.get_task_resources("job-1", 1).await;
+        assert_ne!(resources, TaskResources::default());
+
+        state.task_manager.cancel_job("job-1").await?;
+        assert!(!state.task_manager.is_active_job("job-1"));
+        assert_eq!(
+            state.task_manager.get_task_resources("job-1", 1).await,
+            resources
+        );
+
+        Ok(())
+    }
+
+    // A session should not be assigned more tasks than its task slot quota
+    #[tokio::test]
+    async fn test_offer_session_max_task_slots() -> Result<()> {
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .set(BALLISTA_SESSION_MAX_TASK_SLOTS, "2")
+            .build()?;
+
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+
+        let session_ctx = state.session_manager.create_session(&config).await?;
+
+        let plan = test_graph(session_ctx.clone()).await;
+
+        // Create 4 jobs so we have four pending tasks
+        for job_id in ["job-1", "job-2", "job-3", "job-4"] {
+            state
+                .task_manager
+                .queue_job(job_id, "", timestamp_millis())
+                .await?;
+            state
+                .task_manager
+                .submit_job(
+                    job_id,
+                    "",
+                    session_ctx.session_id().as_str(),
+                    plan.clone(),
+                    0,
+                )
+                .await?;
+        }
+
+        let executors = test_executors(1, 4);
+
+        let (executor_metadata, executor_data) = executors[0].clone();
+
+        let reservations = state
+            .executor_manager
+            .register_executor(executor_metadata, executor_data, true)
+            .await?;
+
+        // Only 2 tasks fit in the session quota, the other 2 wait without holding a reservation
+        let (result, pending) = state.offer_reservation(reservations).await?;
+
+        assert_eq!(pending, 0);
+        assert!(result.is_empty());
+
+        // The reservations left unassigned are cancelled by a spawned task
+        let cancelled = await_condition(Duration::from_millis(10), 100, || async {
+            Ok(state.executor_manager.slot_metrics().reserved_slots == 2)
+        })
+        .await?;
+        assert!(cancelled);
+        let reservations = state
+            .executor_manager
+            .reserve_slots("", 4, TaskResources::default())
+            .await?;
+
+        assert_eq!(reservations.len(), 2);
+
+        Ok(())
+    }
+
+    // Jobs should only be assigned the task slots of the executors of their pool
+    #[tokio::test]
+    async fn test_offer_pool_reservations() -> Result<()> {
+        let default_config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .build()?;
+        let etl_config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .set(BALLISTA_JOB_POOL, "etl")
+            .build()?;
+
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+
+        let default_ctx = state
+            .session_manager
+            .create_session(&default_config)
+            .await?;
+        let etl_ctx = state.session_manager.create_session(&etl_config).await?;
+
+        let plan = test_graph(default_ctx.clone()).await;
+
+        // Create 2 jobs in each pool so each pool has two pending tasks
+        for (job_id, ctx) in [
+            ("job-1", &default_ctx),
+            ("job-2", &default_ctx),
+            ("job-3", &etl_ctx),
+            ("job-4", &etl_ctx),
+        ] {
+            state
+                .task_manager
+                .queue_job(job_id, "", timestamp_millis())
+                .await?;
+            state
+                .task_manager
+                .submit_job(job_id, "", ctx.session_id().as_str(), plan.clone(), 0)
+                .await?;
+        }
+
+        let mut executors = test_executors(2, 4);
+        executors[1].0.pool = "etl".to_owned();
+
+        // The executor of the default pool only runs the tasks of the default jobs, the tasks
+        // of the etl jobs find no task slots in their pool
+        let (executor_metadata, executor_data) = executors[0].clone();
+        let reservations = state
+            .executor_manager
+            .register_executor(executor_metadata, executor_data, true)
+            .await?;
+        let (result, pending) = state.offer_reservation(reservations).await?;
+        assert_eq!(pending, 2);
+        assert!(result.is_empty());
+
+        let (executor_metadata, executor_data) = executors[1].clone();
+        let etl_executor_id = executor_metadata.id.clone();
+        let reservations = state
+            .executor_manager
+            .register_executor(executor_metadata, executor_data, true)
+            .await?;
+        assert!(reservations
+            .iter()
+            .all(|reservation| reservation.pool == "etl"));
+        let (result, pending) = state.offer_reservation(reservations).await?;
+        assert_eq!(pending, 0);
+        assert!(result.is_empty());
+
+        for job_id in ["job-3", "job-4"] {
+            let running_tasks = state
+                .task_manager
+                .with_active_graph(job_id, |graph| graph.running_tasks())
+                .await
+                .unwrap();
+            assert_eq!(running_tasks.len(), 1);
+            assert_eq!(running_tasks[0].executor_id, etl_executor_id);
+        }
+
+        // Need sleep wait for the spawn task work done.
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        for pool in ["", "etl"] {
+            let reservations = state
+                .executor_manager
+                .reserve_slots(pool, 4, TaskResources::default())
+                .await?;
+            assert_eq!(reservations.len(), 2);
+        }
+
+        Ok(())
+    }
+
+    // Tasks of lower priority jobs should be preempted and slots offered to higher priority jobs first
+    #[tokio::test]
+    async fn test_preempt_low_priority_tasks() -> Result<()> {
+        let low_config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .build()?;
+        let high_config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .set(BALLISTA_JOB_PRIORITY, "1")
+            .build()?;
+
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+
+        let low_ctx = state.session_manager.create_session(&low_config).await?;
+        let high_ctx = state.session_manager.create_session(&high_config).await?;
+
+        let plan = test_graph(low_ctx.clone()).await;
+
+        // Fill all the task slots with tasks of low priority jobs
+        for job_id in ["job-1", "job-2", "job-3", "job-4"] {
+            state
+                .task_manager
+                .queue_job(job_id, "", timestamp_millis())
+                .await?;
+            state
+                .task_manager
+                .submit_job(job_id, "", low_ctx.session_id().as_str(), plan.clone(), 0)
+                .await?;
+        }
+
+        let executors = test_executors(1, 4);
+        let (executor_metadata, executor_data) = executors[0].clone();
+        let executor_id = executor_metadata.id.clone();
+
+        let reservations = state
+            .executor_manager
+            .register_executor(executor_metadata, executor_data, true)
+            .await?;
+        let (result, pending) = state.offer_reservation(reservations).await?;
+        assert_eq!(pending, 0);
+        assert!(result.is_empty());
+
+        state
+            .task_manager
+            .queue_job("job-high", "", timestamp_millis())
+            .await?;
+        state
+            .task_manager
+            .submit_job(
+                "job-high",
+                "",
+                high_ctx.session_id().as_str(),
+                plan.clone(),
+                0,
+            )
+            .await?;
+
+        // A low priority job can not preempt tasks of other jobs
+        let preempted = state.task_manager.preempt_tasks("job-1", 1).await?;
+        assert!(preempted.is_empty());
+
+        let preempted = state.task_manager.preempt_tasks("job-high", 1).await?;
+        assert_eq!(preempted.len(), 1);
+        assert_ne!(preempted[0].job_id, "job-high");
+        assert_eq!(
+            state
+                .task_manager
+                .get_available_task_count(&preempted[0].job_id)
+                .await?,
+            1
+        );
+
+        // The slot freed by the killed task goes to the high priority job
+        let (result, pending) = state
+            .offer_reservation(vec![ExecutorReservation::new_free(executor_id)])
+            .await?;
+        assert!(result.is_empty());
+        assert_eq!(pending, 1);
+        assert_eq!(
+            state
+                .task_manager
+                .get_available_task_count("job-high")
+                .await?,
+            0
+        );
+        assert_eq!(
+            state
+                .task_manager
+                .get_available_task_count(&preempted[0].job_id)
+                .await?,
+            1
+        );
+
+        Ok(())
+    }
+
+    // We should generate a new event for tasks that are still pending
+    #[tokio::test]
+    async fn test_offer_resubmit_pending() -> Result<()> {
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .build()?;
+
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+
+        let session_ctx = state.session_manager.create_session(&config).await?;
+
+        let plan = test_graph(session_ctx.clone()).await;
+
+        // Create a job
+        state
+            .task_manager
+            .queue_job("job-1", "", timestamp_millis())
+            .await?;
+        state
+            .task_manager
+            .submit_job(
+                "job-1",
+                "",
+                session_ctx.session_id().as_str(),
+                plan.clone(),
+                0,
+            )
+            .await?;
+
+        let executors = test_executors(1, 4);
+
+        let (executor_metadata, executor_data) = executors[0].clone();
+
+        // Complete the first stage. So we should now have 4 pending tasks for this job stage 2
+        {
+            let executor_id = executor_data.executor_id.clone();
+            let task_def = state
+                .task_manager
+                .with_active_graph("job-1", move |graph| {
+                    graph.pop_next_task(&executor_id)
+                })
+                .await
+                .unwrap()?
+                .unwrap();
+            let mut partitions: Vec<ShuffleWritePartition> = vec![];
+            for partition_id in 0..4 {
+                partitions.push(ShuffleWritePartition {
+                    partition_id: partition_id as u64,
+                    path: "some/path".to_string(),
+                    num_batches: 1,
+                    num_rows: 1,
+                    num_bytes: 1,
+                    checksum: None,
+                    checkpoint_path: String::new(),
+                })
+            }
+            state
+                .task_manager
+                .update_task_statuses(
+                    &executor_metadata,
+                    vec![TaskStatus {
+                        task_id: task_def.task_id as u32,
+                        job_id: "job-1".to_string(),
+                        stage_id: task_def.partition.stage_id as u32,
+                        stage_attempt_num: task_def.stage_attempt_num as u32,
+                        partition_id: task_def.partition.partition_id as u32,
+                        launch_time: 0,
+                        start_exec_time: 0,
+                        end_exec_time: 0,
+                        metrics: vec![],
+                        resource_usage: None,
+                        status: Some(task_status::Status::Successful(SuccessfulTask {
+                            executor_id: executor_data.executor_id.clone(),
+                            partitions,
+                            output_schema: None,
+                        })),
+                    }],
+                )
+                .await?;
+        }
+
+        state
+            .executor_manager
+            .register_executor(executor_metadata, executor_data, false)
+            .await?;
+
+        let reservations = state
+            .executor_manager
+            .reserve_slots("", 1, TaskResources::default())
+            .await?;
+
+        assert_eq!(reservations.len(), 1);
+
+        // Offer the reservation. It should be filled with one of the 4 pending tasks. The other 3 should
+        // be reserved for the other 3 tasks, emitting another offer event
+        let (reservations, pending) = state.offer_reservation(reservations).await?;
+
+        assert_eq!(pending, 3);
+        assert_eq!(reservations.len(), 3);
+
+        // Remaining 3 task slots should be reserved for pending tasks
+        let reservations = state
+            .executor_manager
+            .reserve_slots("", 4, TaskResources::default())
+            .await?;
+
+        assert_eq!(reservations.len(), 0);
+
+        Ok(())
+    }
+
+    fn test_executors(
+        total_executors: usize,
+        slots_per_executor: u32,
+    ) -> Vec<(ExecutorMetadata, ExecutorData)> {
+        let mut result: Vec<(ExecutorMetadata, ExecutorData)> = vec![];
+
+        for i in 0..total_executors {
+            result.push((
                 ExecutorMetadata {
                     id: format!("executor-{i}"),
                     host: format!("host-{i}"),
@@ -686,7 +1863,9 @@ mod test {
                     grpc_port: 9090,
                     specification: ExecutorSpecification {
                         task_slots: slots_per_executor,
+                        ..Default::default()
                     },
+                    pool: String::new(),
                 },
                 ExecutorData {
                     executor_id: format!("executor-{i}"),
@@ -699,6 +1878,361 @@ mod test {
         result
     }
 
+    #[derive(Default)]
+    struct CountingRule {
+        applied: AtomicUsize,
+    }
+
+    impl PhysicalOptimizerRule for CountingRule {
+        fn optimize(
+            &self,
+            plan: Arc<dyn ExecutionPlan>,
+            _config: &ConfigOptions,
+        ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+            self.applied.fetch_add(1, Ordering::SeqCst);
+            Ok(plan)
+        }
+
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn schema_check(&self) -> bool {
+            true
+        }
+    }
+
+    // Optimizer rules registered with the scheduler apply to the plans of all jobs
+    #[tokio::test]
+    async fn test_plan_job_with_optimizer_rules() -> Result<()> {
+        let rule = Arc::new(CountingRule::default());
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default().with_physical_optimizer_rule(rule.clone()),
+            ));
+
+        let schema = Schema::new(vec![Field::new("id", DataType::Utf8, false)]);
+        let plan = scan_empty(None, &schema, Some(vec![0]))?.build()?;
+        let ctx = state
+            .session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+        state.plan_job("job", ctx.clone(), &plan).await?;
+        assert_eq!(rule.applied.load(Ordering::SeqCst), 1);
+
+        // The session itself does not run the rule
+        ctx.state().create_physical_plan(&plan).await?;
+        assert_eq!(rule.applied.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    // The statistics of scanned tables do not keep the DataFusion rules following the join
+    // selection from pruning and repartitioning the scans
+    #[tokio::test]
+    async fn test_plan_job_with_table_statistics() -> Result<()> {
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .build()?;
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_default_scheduler_name(
+                test_cluster_context(),
+                BallistaCodec::default(),
+            ));
+        let ctx = state.session_manager.create_session(&config).await?;
+        ctx.register_parquet(
+            "alltypes",
+            "../client/testdata/alltypes_plain.parquet",
+            ParquetReadOptions::default(),
+        )
+        .await?;
+        ctx.sql("SET datafusion.optimizer.enable_round_robin_repartition = true")
+            .await?;
+        ctx.sql("SET datafusion.optimizer.repartition_file_min_size = 0")
+            .await?;
+
+        let query = "SELECT id FROM alltypes WHERE id > 5";
+        let plan = ctx.state().create_logical_plan(query).await?;
+        let scan_plan = state.plan_job("job-0", ctx.clone(), &plan).await?;
+        collect(scan_plan.clone(), ctx.task_ctx()).await?;
+        let table_statistics = state.task_manager.table_statistics();
+        table_statistics.record_stage(
+            scan_plan.as_ref(),
+            &collect_plan_metrics(scan_plan.as_ref()),
+        );
+        assert_eq!(table_statistics.len(), 1);
+
+        let plan = state.plan_job("job-1", ctx, &plan).await?;
+        let displayed =
+            format!("{}", DisplayableExecutionPlan::new(plan.as_ref()).indent());
+        assert!(!displayed.contains("TableStatisticsExec"), "{displayed}");
+        assert!(displayed.contains("pruning_predicate="), "{displayed}");
+        assert!(
+            displayed.contains("ParquetExec: file_groups={4 groups"),
+            "{displayed}"
+        );
+
+        Ok(())
+    }
+
+    // EXPLAIN shows the query stages of the plan instead of the DataFusion physical plan
+    #[tokio::test]
+    async fn test_plan_job_explain() -> Result<()> {
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_default_scheduler_name(
+                test_cluster_context(),
+                BallistaCodec::default(),
+            ));
+
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("gmv", DataType::UInt64, false),
+        ]);
+        let plan = scan_empty(None, &schema, Some(vec![0, 1]))?
+            .aggregate(vec![col("id")], vec![sum(col("gmv"))])?
+            .explain(false, false)?
+            .build()?;
+        let ctx = state
+            .session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+        let plan = state.plan_job("job", ctx, &plan).await?;
+
+        let explain = plan.as_any().downcast_ref::<ExplainExec>().unwrap();
+        let physical_plan = explain
+            .stringified_plans()
+            .iter()
+            .find(|p| p.plan_type == PlanType::FinalPhysicalPlan)
+            .unwrap();
+        assert!(physical_plan.plan.contains("exchange=hash([id@0]"));
+        assert!(physical_plan.plan.contains("inputs=[1], exchange=result"));
+
+        Ok(())
+    }
+
+    // EXPLAIN ANALYZE VERBOSE is rejected rather than answered without the per-partition
+    // metrics it asks for
+    #[tokio::test]
+    async fn test_plan_job_explain_analyze_verbose() -> Result<()> {
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_default_scheduler_name(
+                test_cluster_context(),
+                BallistaCodec::default(),
+            ));
+
+        let schema = Schema::new(vec![Field::new("id", DataType::Utf8, false)]);
+        let ctx = state
+            .session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+
+        let plan = scan_empty(None, &schema, None)?
+            .explain(true, true)?
+            .build()?;
+        let error = state
+            .plan_job("job-1", ctx.clone(), &plan)
+            .await
+            .expect_err("Expected EXPLAIN ANALYZE VERBOSE to be rejected");
+        assert!(
+            error.to_string().contains("EXPLAIN ANALYZE VERBOSE"),
+            "{error}"
+        );
+
+        let plan = scan_empty(None, &schema, None)?
+            .explain(false, true)?
+            .build()?;
+        state.plan_job("job-2", ctx, &plan).await?;
+
+        Ok(())
+    }
+
+    // EXPLAIN VERBOSE is answered with its plans as a result set, without running a job
+    #[tokio::test]
+    async fn test_explain_job() -> Result<()> {
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_default_scheduler_name(
+                test_cluster_context(),
+                BallistaCodec::default(),
+            ));
+
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("gmv", DataType::UInt64, false),
+        ]);
+        let plan = scan_empty(None, &schema, Some(vec![0, 1]))?
+            .aggregate(vec![col("id")], vec![sum(col("gmv"))])?
+            .explain(true, false)?
+            .build()?;
+        let ctx = state
+            .session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+        let batch = state.explain_job("job", ctx, &plan).await?;
+
+        let column = |name: &str| {
+            batch
+                .column(batch.schema().index_of(name).unwrap())
+                .as_any()
+                .downcast_ref::<StringArray>()
+                .unwrap()
+                .clone()
+        };
+        let (plan_types, plans) = (column("plan_type"), column("plan"));
+        assert!(batch.num_rows() > 2);
+        let physical_plan = (0..batch.num_rows())
+            .find(|row| plan_types.value(*row) == "physical_plan")
+            .map(|row| plans.value(row))
+            .unwrap();
+        assert!(physical_plan.contains("exchange=hash([id@0]"));
+        assert!(physical_plan.contains("inputs=[1], exchange=result"));
+        assert_eq!(state.task_manager.get_jobs().await?.len(), 0);
+
+        Ok(())
+    }
+
+    // Jobs carry the labels of their session and can be listed by label
+    #[tokio::test]
+    async fn test_job_labels() -> Result<()> {
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+
+        for (job_id, labels) in [
+            ("job-1", "team=analytics,pipeline=daily"),
+            ("job-2", "team=analytics,pipeline=hourly"),
+            ("job-3", ""),
+        ] {
+            let config = BallistaConfig::builder()
+                .set(BALLISTA_JOB_LABELS, labels)
+                .build()?;
+            let session_ctx = state.session_manager.create_session(&config).await?;
+            let plan = test_graph(session_ctx.clone()).await;
+            state
+                .task_manager
+                .queue_job(job_id, "", timestamp_millis())
+                .await?;
+            state
+                .task_manager
+                .submit_job(job_id, "", session_ctx.session_id().as_str(), plan, 0)
+                .await?;
+        }
+
+        let jobs = state.task_manager.get_jobs().await?;
+        let labelled = |labels: &[(&str, &str)]| {
+            let labels: HashMap<String, String> = labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            let mut job_ids: Vec<&str> = jobs
+                .iter()
+                .filter(|job| job.has_labels(&labels))
+                .map(|job| job.job_id.as_str())
+                .collect();
+            job_ids.sort();
+            job_ids
+        };
+        assert_eq!(labelled(&[]), vec!["job-1", "job-2", "job-3"]);
+        assert_eq!(labelled(&[("team", "analytics")]), vec!["job-1", "job-2"]);
+        assert_eq!(
+            labelled(&[("team", "analytics"), ("pipeline", "hourly")]),
+            vec!["job-2"]
+        );
+        assert!(labelled(&[("team", "finance")]).is_empty());
+
+        Ok(())
+    }
+
+    // Settings of a job override the ones of its session for this job only
+    #[tokio::test]
+    async fn test_job_settings() -> Result<()> {
+        let state: Arc<SchedulerState<LogicalPlanNode, PhysicalPlanNode>> =
+            Arc::new(SchedulerState::new_with_task_launcher(
+                test_cluster_context(),
+                BallistaCodec::default(),
+                TEST_SCHEDULER_NAME.into(),
+                SchedulerConfig::default(),
+                Arc::new(BlackholeTaskLauncher::default()),
+            ));
+
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_JOB_LABELS, "team=ops")
+            .build()?;
+        let session_ctx = state.session_manager.create_session(&config).await?;
+        let session_id = session_ctx.session_id();
+        let plan = test_graph(session_ctx.clone()).await;
+
+        let job_settings: HashMap<String, String> =
+            [(BALLISTA_JOB_LABELS.to_owned(), "team=analytics".to_owned())]
+                .into_iter()
+                .collect();
+        state
+            .task_manager
+            .queue_job("job-1", "", timestamp_millis())
+            .await?;
+        state
+            .task_manager
+            .submit_job_with_settings(
+                "job-1",
+                "",
+                &session_id,
+                plan.clone(),
+                0,
+                job_settings,
+            )
+            .await?;
+        state
+            .task_manager
+            .queue_job("job-2", "", timestamp_millis())
+            .await?;
+        state
+            .task_manager
+            .submit_job("job-2", "", &session_id, plan, 0)
+            .await?;
+
+        let labels = |job_id: &str, jobs: &[JobOverview]| {
+            jobs.iter()
+                .find(|job| job.job_id == job_id)
+                .map(|job| job.labels.get("team").cloned())
+                .unwrap()
+        };
+        let jobs = state.task_manager.get_jobs().await?;
+        assert_eq!(labels("job-1", &jobs).as_deref(), Some("analytics"));
+        assert_eq!(labels("job-2", &jobs).as_deref(), Some("ops"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_latest_task_statuses() {
+        let status = |job_id: &str, task_id: u32, launch_time: u64| TaskStatus {
+            task_id,
+            job_id: job_id.to_owned(),
+            launch_time,
+            ..Default::default()
+        };
+        let statuses = latest_task_statuses(vec![
+            status("job-1", 1, 0),
+            status("job-1", 2, 1),
+            status("job-2", 1, 0),
+            status("job-1", 1, 2),
+        ]);
+        let tasks: Vec<(&str, u32, u64)> = statuses
+            .iter()
+            .map(|status| (status.job_id.as_str(), status.task_id, status.launch_time))
+            .collect();
+        assert_eq!(
+            tasks,
+            vec![("job-1", 2, 1), ("job-2", 1, 0), ("job-1", 1, 2)]
+        );
+    }
+
     async fn test_graph(ctx: Arc<SessionContext>) -> Arc<dyn ExecutionPlan> {
         let schema = Schema::new(vec![
             Field::new("id", DataType::Utf8, false),
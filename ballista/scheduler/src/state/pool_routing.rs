@@ -0,0 +1,214 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Routing of the jobs which do not select a pool of executors to a pool, by their labels or
+//! the text of their SQL query
+
+use ballista_core::config::BallistaConfig;
+use ballista_core::error::{BallistaError, Result};
+
+/// Condition of a routing rule on a job
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteCondition {
+    /// The job has the label `key=value`
+    Label { key: String, value: String },
+    /// The SQL text of the job matches the pattern, ignoring case, where `*` matches any
+    /// characters
+    Sql(String),
+}
+
+impl RouteCondition {
+    fn matches(&self, config: &BallistaConfig, sql: Option<&str>) -> bool {
+        match self {
+            RouteCondition::Label { key, value } => {
+                config.job_labels().get(key) == Some(value)
+            }
+            RouteCondition::Sql(pattern) => sql
+                .map(|sql| wildcard_match(pattern, &sql.trim().to_lowercase()))
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Route the jobs matching a condition to a pool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolRoute {
+    pub condition: RouteCondition,
+    pub pool: String,
+}
+
+/// Rules routing the jobs which do not set `ballista.job.pool` to a pool of executors, so
+/// that e.g. the ETL jobs run on a batch pool and the queries of dashboards on an
+/// interactive pool. The first matching rule wins, jobs matching none run on the default
+/// pool.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolRoutingRules {
+    routes: Vec<PoolRoute>,
+}
+
+impl PoolRoutingRules {
+    pub fn new(routes: Vec<PoolRoute>) -> Self {
+        Self { routes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// The pool a job with the configuration of its session and job settings is routed to,
+    /// none if it selects a pool itself or matches no rule
+    pub fn route(&self, config: &BallistaConfig, sql: Option<&str>) -> Option<&str> {
+        if !config.job_pool().is_empty() {
+            return None;
+        }
+        self.routes
+            .iter()
+            .find(|route| route.condition.matches(config, sql))
+            .map(|route| route.pool.as_str())
+    }
+}
+
+/// Parse semicolon separated routing rules of the form `condition->pool`, where the condition
+/// is `label:key=value` or `sql:pattern`, e.g.
+/// `label:team=etl->batch; sql:select * from dashboard_*->interactive`
+pub fn parse_pool_routing_rules(rules: &str) -> Result<PoolRoutingRules> {
+    let routes = rules
+        .split(';')
+        .filter(|rule| !rule.trim().is_empty())
+        .map(|rule| {
+            let invalid = || {
+                BallistaError::General(format!(
+                    "Invalid pool routing rule {rule:?}, expected label:key=value->pool \
+                     or sql:pattern->pool"
+                ))
+            };
+            let (condition, pool) = rule.rsplit_once("->").ok_or_else(invalid)?;
+            let pool = pool.trim();
+            if pool.is_empty() {
+                return Err(invalid());
+            }
+            let condition = match condition.trim().split_once(':') {
+                Some(("label", label)) => match label.split_once('=') {
+                    Some((key, value)) if !key.trim().is_empty() => {
+                        RouteCondition::Label {
+                            key: key.trim().to_owned(),
+                            value: value.trim().to_owned(),
+                        }
+                    }
+                    _ => return Err(invalid()),
+                },
+                Some(("sql", pattern)) if !pattern.trim().is_empty() => {
+                    RouteCondition::Sql(pattern.trim().to_lowercase())
+                }
+                _ => return Err(invalid()),
+            };
+            Ok(PoolRoute {
+                condition,
+                pool: pool.to_owned(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(PoolRoutingRules::new(routes))
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any characters
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if text.len() < first.len() + last.len()
+        || !text.starts_with(first)
+        || !text.ends_with(last)
+    {
+        return false;
+    }
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ballista_core::config::{BALLISTA_JOB_LABELS, BALLISTA_JOB_POOL};
+
+    #[test]
+    fn test_pool_routing() -> Result<()> {
+        let rules = parse_pool_routing_rules(
+            "label:team=etl->batch; sql:SELECT * FROM dashboard_*->interactive;",
+        )?;
+        assert_eq!(
+            rules,
+            PoolRoutingRules::new(vec![
+                PoolRoute {
+                    condition: RouteCondition::Label {
+                        key: "team".to_owned(),
+                        value: "etl".to_owned(),
+                    },
+                    pool: "batch".to_owned(),
+                },
+                PoolRoute {
+                    condition: RouteCondition::Sql(
+                        "select * from dashboard_*".to_owned()
+                    ),
+                    pool: "interactive".to_owned(),
+                },
+            ])
+        );
+
+        let etl = BallistaConfig::builder()
+            .set(BALLISTA_JOB_LABELS, "team=etl")
+            .build()?;
+        let default = BallistaConfig::new()?;
+        let dashboard = "select * from DASHBOARD_sales where day = 1";
+        assert_eq!(rules.route(&etl, Some(dashboard)), Some("batch"));
+        assert_eq!(rules.route(&default, Some(dashboard)), Some("interactive"));
+        assert_eq!(rules.route(&default, Some("SELECT 1")), None);
+        assert_eq!(rules.route(&default, None), None);
+
+        // Jobs selecting a pool are not routed
+        let pinned = BallistaConfig::builder()
+            .set(BALLISTA_JOB_LABELS, "team=etl")
+            .set(BALLISTA_JOB_POOL, "adhoc")
+            .build()?;
+        assert_eq!(rules.route(&pinned, None), None);
+
+        assert!(parse_pool_routing_rules("")?.is_empty());
+        assert!(parse_pool_routing_rules("label:team->batch").is_err());
+        assert!(parse_pool_routing_rules("sql:select *->").is_err());
+        assert!(parse_pool_routing_rules("user:alice->batch").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("select *", "select 1"));
+        assert!(wildcard_match("*from t*", "select a from t where a > 1"));
+        assert!(wildcard_match("select * from t", "select a from t"));
+        assert!(!wildcard_match("select * from t", "select a from u"));
+        assert!(!wildcard_match("a*a", "a"));
+        assert!(wildcard_match("exact", "exact"));
+        assert!(!wildcard_match("exact", "exactly"));
+    }
+}
@@ -0,0 +1,229 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Estimation of the cost of a query before it is executed
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use datafusion::physical_plan::ExecutionPlan;
+use parking_lot::Mutex;
+
+use ballista_core::serde::protobuf::{
+    estimate_query_result, EstimateQueryResult as ProtoEstimateQueryResult,
+};
+
+/// Number of completed jobs remembered to estimate the cost of similar queries
+pub const DEFAULT_QUERY_COST_HISTORY_SIZE: usize = 1000;
+
+/// Estimated cost of executing a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryCostEstimate {
+    /// Bytes scanned from the sources of the query, if their statistics are known
+    pub scanned_bytes: Option<u64>,
+    /// Number of stages the query is split into
+    pub stage_count: usize,
+    /// Number of tasks across all stages
+    pub task_count: usize,
+    /// Expected time in seconds the tasks of the query hold task slots for
+    pub slot_seconds: f64,
+    /// Number of recently completed jobs with a similar plan the slot seconds are based on,
+    /// 0 if no similar job completed recently
+    pub similar_jobs: usize,
+}
+
+impl QueryCostEstimate {
+    /// Convert to the result of an estimate made in the given session
+    pub fn into_proto(self, session_id: String) -> ProtoEstimateQueryResult {
+        ProtoEstimateQueryResult {
+            optional_scanned_bytes: self
+                .scanned_bytes
+                .map(estimate_query_result::OptionalScannedBytes::ScannedBytes),
+            stage_count: self.stage_count as u32,
+            task_count: self.task_count as u64,
+            slot_seconds: self.slot_seconds,
+            similar_jobs: self.similar_jobs as u32,
+            session_id,
+        }
+    }
+}
+
+/// Cost of a completed job
+#[derive(Debug, Clone)]
+struct JobCost {
+    fingerprint: u64,
+    scanned_bytes: Option<u64>,
+    slot_seconds: f64,
+}
+
+/// Costs of recently completed jobs, used to estimate the cost of queries with a similar plan
+#[derive(Debug)]
+pub struct QueryCostHistory {
+    capacity: usize,
+    jobs: Mutex<VecDeque<JobCost>>,
+}
+
+impl Default for QueryCostHistory {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUERY_COST_HISTORY_SIZE)
+    }
+}
+
+impl QueryCostHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            jobs: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Record the cost of a completed job, evicting the oldest job once full
+    pub fn record(
+        &self,
+        fingerprint: u64,
+        scanned_bytes: Option<u64>,
+        slot_seconds: f64,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut jobs = self.jobs.lock();
+        if jobs.len() >= self.capacity {
+            jobs.pop_front();
+        }
+        jobs.push_back(JobCost {
+            fingerprint,
+            scanned_bytes,
+            slot_seconds,
+        });
+    }
+
+    /// Estimate the slot seconds of a query from the recently completed jobs with the same
+    /// plan fingerprint. When the scanned bytes are known the slot seconds per scanned byte
+    /// of the similar jobs are scaled to the query, otherwise their slot seconds are averaged.
+    /// Returns the estimate and the number of similar jobs.
+    pub fn estimate_slot_seconds(
+        &self,
+        fingerprint: u64,
+        scanned_bytes: Option<u64>,
+    ) -> (f64, usize) {
+        let jobs = self.jobs.lock();
+        let similar: Vec<&JobCost> = jobs
+            .iter()
+            .filter(|job| job.fingerprint == fingerprint)
+            .collect();
+        if similar.is_empty() {
+            return (0.0, 0);
+        }
+
+        let rates: Vec<f64> = similar
+            .iter()
+            .filter_map(|job| match job.scanned_bytes {
+                Some(bytes) if bytes > 0 => Some(job.slot_seconds / bytes as f64),
+                _ => None,
+            })
+            .collect();
+        let slot_seconds = match scanned_bytes {
+            Some(bytes) if !rates.is_empty() => {
+                rates.iter().sum::<f64>() / rates.len() as f64 * bytes as f64
+            }
+            _ => {
+                similar.iter().map(|job| job.slot_seconds).sum::<f64>()
+                    / similar.len() as f64
+            }
+        };
+        (slot_seconds, similar.len())
+    }
+}
+
+/// Fingerprint of the shape of a physical plan: its operators and their output columns.
+/// Queries of the same shape over the same tables share a fingerprint even if their
+/// literals or the files of their tables differ.
+pub fn plan_fingerprint(plan: &dyn ExecutionPlan) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash_plan(plan, &mut hasher);
+    hasher.finish()
+}
+
+fn hash_plan(plan: &dyn ExecutionPlan, hasher: &mut DefaultHasher) {
+    plan.as_any().type_id().hash(hasher);
+    for field in plan.schema().fields() {
+        field.name().hash(hasher);
+    }
+    let children = plan.children();
+    children.len().hash(hasher);
+    for child in children {
+        hash_plan(child.as_ref(), hasher);
+    }
+}
+
+/// Bytes scanned by the leaves of a physical plan according to their statistics, None if
+/// the size of any of them is unknown
+pub fn scanned_bytes(plan: &Arc<dyn ExecutionPlan>) -> Option<u64> {
+    let children = plan.children();
+    if children.is_empty() {
+        plan.statistics().total_byte_size.map(|bytes| bytes as u64)
+    } else {
+        children
+            .iter()
+            .map(scanned_bytes)
+            .try_fold(0u64, |total, bytes| bytes.map(|bytes| total + bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_without_history() {
+        let history = QueryCostHistory::default();
+        assert_eq!(history.estimate_slot_seconds(1, Some(100)), (0.0, 0));
+    }
+
+    #[test]
+    fn test_estimate_scales_with_scanned_bytes() {
+        let history = QueryCostHistory::default();
+        history.record(1, Some(100), 2.0);
+        history.record(1, Some(200), 2.0);
+        history.record(2, Some(100), 50.0);
+
+        // 0.015 slot seconds per byte on average
+        let (slot_seconds, similar_jobs) = history.estimate_slot_seconds(1, Some(400));
+        assert!((slot_seconds - 6.0).abs() < 1e-9);
+        assert_eq!(similar_jobs, 2);
+
+        // Without scanned bytes the slot seconds of similar jobs are averaged
+        let (slot_seconds, similar_jobs) = history.estimate_slot_seconds(1, None);
+        assert!((slot_seconds - 2.0).abs() < 1e-9);
+        assert_eq!(similar_jobs, 2);
+    }
+
+    #[test]
+    fn test_history_capacity() {
+        let history = QueryCostHistory::new(2);
+        history.record(1, None, 1.0);
+        history.record(1, None, 2.0);
+        history.record(1, None, 3.0);
+
+        let (slot_seconds, similar_jobs) = history.estimate_slot_seconds(1, None);
+        assert!((slot_seconds - 2.5).abs() < 1e-9);
+        assert_eq!(similar_jobs, 2);
+    }
+}
@@ -0,0 +1,253 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Quarantine of the SQL queries which repeatedly fail, to protect the cluster from clients
+//! retrying them
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use parking_lot::Mutex;
+use serde::Serialize;
+
+use crate::scheduler_server::timestamp_millis;
+
+/// A failed job of a query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryFailure {
+    pub job_id: String,
+    pub error: String,
+    /// Time of the failure in milliseconds since the epoch
+    pub failed_at: u64,
+}
+
+/// A query whose recent jobs failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FailedQuery {
+    /// Fingerprint of the SQL text of the query, in hexadecimal
+    pub fingerprint: String,
+    pub sql: String,
+    /// Failures within the quarantine window, the oldest first
+    pub failures: Vec<QueryFailure>,
+}
+
+#[derive(Debug, Default)]
+struct QuarantineState {
+    /// Failed queries by the fingerprint of their SQL text
+    queries: HashMap<u64, FailedQuery>,
+    /// Fingerprints and SQL text of the running jobs
+    jobs: HashMap<String, (u64, String)>,
+}
+
+impl QuarantineState {
+    fn remove_expired(&mut self, window: Duration, now: u64) {
+        let since = now.saturating_sub(window.as_millis() as u64);
+        self.queries.retain(|_, query| {
+            query.failures.retain(|failure| failure.failed_at > since);
+            !query.failures.is_empty()
+        });
+    }
+}
+
+/// Tracks the failures of the jobs running SQL queries. A query whose jobs failed
+/// `max_failures` times within the window is quarantined: it is rejected right away until its
+/// failures are older than the window, or an administrator clears them.
+#[derive(Debug)]
+pub struct QueryQuarantine {
+    max_failures: usize,
+    window: Duration,
+    state: Mutex<QuarantineState>,
+}
+
+impl Default for QueryQuarantine {
+    fn default() -> Self {
+        Self::new(0, Duration::ZERO)
+    }
+}
+
+impl QueryQuarantine {
+    pub fn new(max_failures: usize, window: Duration) -> Self {
+        Self {
+            max_failures,
+            window,
+            state: Mutex::new(QuarantineState::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.max_failures > 0 && !self.window.is_zero()
+    }
+
+    /// The reason to reject a query if it is quarantined, which does not tell the failed jobs
+    pub fn check(&self, sql: &str) -> Option<String> {
+        if !self.is_enabled() {
+            return None;
+        }
+        let mut state = self.state.lock();
+        state.remove_expired(self.window, timestamp_millis());
+        let query = state.queries.get(&sql_fingerprint(sql))?;
+        if query.failures.len() < self.max_failures {
+            return None;
+        }
+        // The failed jobs may belong to other users, administrators look them up with
+        // `quarantined`
+        Some(format!(
+            "Query {} is quarantined after failing {} times in the last {} seconds",
+            query.fingerprint,
+            query.failures.len(),
+            self.window.as_secs(),
+        ))
+    }
+
+    /// Track the outcome of a job running a SQL query
+    pub fn add_job(&self, job_id: &str, sql: &str) {
+        if self.is_enabled() {
+            self.state
+                .lock()
+                .jobs
+                .insert(job_id.to_owned(), (sql_fingerprint(sql), sql.to_owned()));
+        }
+    }
+
+    /// Record the failure of a job, which counts towards the quarantine of its query
+    pub fn record_failure(&self, job_id: &str, error: &str) {
+        let mut state = self.state.lock();
+        let (fingerprint, sql) = match state.jobs.remove(job_id) {
+            Some(job) => job,
+            None => return,
+        };
+        let now = timestamp_millis();
+        state.remove_expired(self.window, now);
+        let query = state
+            .queries
+            .entry(fingerprint)
+            .or_insert_with(|| FailedQuery {
+                fingerprint: format!("{fingerprint:016x}"),
+                sql,
+                failures: vec![],
+            });
+        query.failures.push(QueryFailure {
+            job_id: job_id.to_owned(),
+            error: error.to_owned(),
+            failed_at: now,
+        });
+        if query.failures.len() > self.max_failures {
+            query.failures.remove(0);
+        }
+    }
+
+    /// Record the success of a job, which clears the failures of its query
+    pub fn record_success(&self, job_id: &str) {
+        let mut state = self.state.lock();
+        if let Some((fingerprint, _)) = state.jobs.remove(job_id) {
+            state.queries.remove(&fingerprint);
+        }
+    }
+
+    /// Stop tracking a job which neither succeeded nor failed, such as a cancelled job
+    pub fn remove_job(&self, job_id: &str) {
+        self.state.lock().jobs.remove(job_id);
+    }
+
+    /// The queries which are quarantined
+    pub fn quarantined(&self) -> Vec<FailedQuery> {
+        let mut state = self.state.lock();
+        state.remove_expired(self.window, timestamp_millis());
+        let mut queries: Vec<FailedQuery> = state
+            .queries
+            .values()
+            .filter(|query| query.failures.len() >= self.max_failures)
+            .cloned()
+            .collect();
+        queries.sort_by(|a, b| a.fingerprint.cmp(&b.fingerprint));
+        queries
+    }
+
+    /// Clear the failures of a query given its fingerprint, returns whether it had any
+    pub fn clear(&self, fingerprint: &str) -> bool {
+        match u64::from_str_radix(fingerprint, 16) {
+            Ok(fingerprint) => self.state.lock().queries.remove(&fingerprint).is_some(),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Fingerprint of the SQL text of a query, ignoring leading and trailing whitespace
+fn sql_fingerprint(sql: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    sql.trim().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_quarantine() {
+        let quarantine = QueryQuarantine::new(2, Duration::from_secs(600));
+        let sql = "SELECT * FROM t";
+
+        quarantine.add_job("job-1", sql);
+        quarantine.record_failure("job-1", "Task failed");
+        assert_eq!(quarantine.check(sql), None);
+
+        // Cancelled jobs do not count
+        quarantine.add_job("job-2", sql);
+        quarantine.remove_job("job-2");
+        quarantine.record_failure("job-2", "Cancelled");
+        assert_eq!(quarantine.check(sql), None);
+
+        quarantine.add_job("job-3", &format!(" {sql}\n"));
+        quarantine.record_failure("job-3", "Task failed");
+        let reason = quarantine.check(sql).unwrap();
+        assert!(reason.contains("after failing 2 times"));
+        assert!(!reason.contains("job-1"));
+        assert!(!reason.contains("Task failed"));
+        assert_eq!(quarantine.check("SELECT 1"), None);
+
+        let quarantined = quarantine.quarantined();
+        assert_eq!(quarantined.len(), 1);
+        assert_eq!(quarantined[0].sql, sql);
+        assert!(!quarantine.clear("not a fingerprint"));
+        assert!(quarantine.clear(&quarantined[0].fingerprint));
+        assert_eq!(quarantine.check(sql), None);
+        assert!(quarantine.quarantined().is_empty());
+    }
+
+    #[test]
+    fn test_query_success_clears_failures() {
+        let quarantine = QueryQuarantine::new(2, Duration::from_secs(600));
+        let sql = "SELECT * FROM t";
+
+        quarantine.add_job("job-1", sql);
+        quarantine.record_failure("job-1", "Task failed");
+        quarantine.add_job("job-2", sql);
+        quarantine.record_success("job-2");
+        quarantine.add_job("job-3", sql);
+        quarantine.record_failure("job-3", "Task failed");
+        assert_eq!(quarantine.check(sql), None);
+
+        // Nothing is tracked when the quarantine is disabled
+        let quarantine = QueryQuarantine::default();
+        quarantine.add_job("job-1", sql);
+        quarantine.record_failure("job-1", "Task failed");
+        assert_eq!(quarantine.check(sql), None);
+    }
+}
@@ -0,0 +1,347 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Caching of the results of jobs to serve repeated identical queries without executing them
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use datafusion::physical_plan::display::DisplayableExecutionPlan;
+use datafusion::physical_plan::ExecutionPlan;
+use parking_lot::Mutex;
+
+use ballista_core::serde::protobuf::PartitionLocation;
+
+use crate::state::table_statistics::{file_scan_config, scan_key};
+
+/// Results of a successful job kept on the executors which wrote them
+#[derive(Debug, Clone)]
+pub struct CachedResult {
+    /// ID of the job which computed the results
+    pub job_id: String,
+    pub partition_location: Vec<PartitionLocation>,
+    /// Size of the result partitions in bytes
+    pub bytes: u64,
+    cached_at: Instant,
+}
+
+#[derive(Debug, Default)]
+struct CachedResults {
+    results: HashMap<u64, CachedResult>,
+    /// Fingerprints of the results in the order they were cached
+    order: VecDeque<u64>,
+    total_bytes: u64,
+    /// Fingerprints of the running jobs whose results are cached once they succeed
+    pending_jobs: HashMap<String, u64>,
+}
+
+impl CachedResults {
+    fn remove(&mut self, fingerprint: u64) -> Option<CachedResult> {
+        let result = self.results.remove(&fingerprint)?;
+        self.order.retain(|f| *f != fingerprint);
+        self.total_bytes -= result.bytes;
+        Some(result)
+    }
+
+    fn remove_expired(&mut self, ttl: Duration, now: Instant) {
+        while let Some(fingerprint) = self.order.front().copied() {
+            match self.results.get(&fingerprint) {
+                Some(result) if now.duration_since(result.cached_at) < ttl => break,
+                _ => {
+                    self.remove(fingerprint);
+                }
+            }
+        }
+    }
+}
+
+/// Results of recently completed jobs keyed by the fingerprint of their physical plan and the
+/// files they read. Results are served for the time to live of the cache, and the result
+/// partitions stay on the executors which wrote them until the jobs served last had the usual
+/// clean up interval to read them.
+#[derive(Debug)]
+pub struct ResultCache {
+    ttl: Duration,
+    max_bytes: u64,
+    cached: Mutex<CachedResults>,
+}
+
+impl Default for ResultCache {
+    fn default() -> Self {
+        Self::new(Duration::ZERO, 0)
+    }
+}
+
+impl ResultCache {
+    pub fn new(ttl: Duration, max_bytes: u64) -> Self {
+        Self {
+            ttl,
+            max_bytes,
+            cached: Mutex::new(CachedResults::default()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.max_bytes > 0
+    }
+
+    /// Time for which results are served from the cache
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Seconds for which the data of a job whose results are cached is kept, so that the jobs
+    /// served its results right before they expire from the cache still have the
+    /// `clean_up_interval` to read them
+    pub fn data_retention_seconds(&self, clean_up_interval: u64) -> u64 {
+        clean_up_interval.saturating_add(self.ttl.as_secs_f64().ceil() as u64)
+    }
+
+    /// Results of a recently completed job with the same fingerprint, if they are cached
+    pub fn get(&self, fingerprint: u64) -> Option<CachedResult> {
+        self.get_at(fingerprint, Instant::now())
+    }
+
+    fn get_at(&self, fingerprint: u64, now: Instant) -> Option<CachedResult> {
+        let mut cached = self.cached.lock();
+        cached.remove_expired(self.ttl, now);
+        cached.results.get(&fingerprint).cloned()
+    }
+
+    /// Cache the results of the job once it succeeds
+    pub fn add_pending_job(&self, job_id: &str, fingerprint: u64) {
+        self.cached
+            .lock()
+            .pending_jobs
+            .insert(job_id.to_owned(), fingerprint);
+    }
+
+    /// Stop waiting for the results of a job, returns the fingerprint of its results if they
+    /// were to be cached
+    pub fn remove_pending_job(&self, job_id: &str) -> Option<u64> {
+        self.cached.lock().pending_jobs.remove(job_id)
+    }
+
+    /// Cache the results of a successful job, evicting the oldest results once the cache is
+    /// full. Returns whether the results were cached, which they are not if results with the
+    /// same fingerprint are cached already or they are larger than the cache.
+    pub fn insert(
+        &self,
+        fingerprint: u64,
+        job_id: &str,
+        partition_location: Vec<PartitionLocation>,
+    ) -> bool {
+        let bytes: u64 = partition_location
+            .iter()
+            .filter_map(|location| location.partition_stats.as_ref())
+            .map(|stats| stats.num_bytes.max(0) as u64)
+            .sum();
+        if !self.is_enabled() || bytes > self.max_bytes {
+            return false;
+        }
+
+        let mut cached = self.cached.lock();
+        let now = Instant::now();
+        cached.remove_expired(self.ttl, now);
+        if cached.results.contains_key(&fingerprint) {
+            return false;
+        }
+        while cached.total_bytes + bytes > self.max_bytes {
+            match cached.order.front().copied() {
+                Some(oldest) => {
+                    cached.remove(oldest);
+                }
+                None => break,
+            }
+        }
+        cached.results.insert(
+            fingerprint,
+            CachedResult {
+                job_id: job_id.to_owned(),
+                partition_location,
+                bytes,
+                cached_at: now,
+            },
+        );
+        cached.order.push_back(fingerprint);
+        cached.total_bytes += bytes;
+        true
+    }
+
+    /// Remove the results with partitions on a lost executor
+    pub fn remove_executor(&self, executor_id: &str) {
+        let mut cached = self.cached.lock();
+        let lost: Vec<u64> = cached
+            .results
+            .iter()
+            .filter(|(_, result)| {
+                result.partition_location.iter().any(|location| {
+                    location
+                        .executor_meta
+                        .as_ref()
+                        .map(|meta| meta.id == executor_id)
+                        .unwrap_or(false)
+                })
+            })
+            .map(|(fingerprint, _)| *fingerprint)
+            .collect();
+        for fingerprint in lost {
+            cached.remove(fingerprint);
+        }
+    }
+}
+
+/// Fingerprint of the results of a physical plan: its operators with their expressions and
+/// the names, sizes and modification times of the files it reads. None if the plan reads from
+/// sources other than files, whose changes cannot be detected.
+pub fn result_fingerprint(plan: &dyn ExecutionPlan) -> Option<u64> {
+    let mut hasher = DefaultHasher::new();
+    hash_sources(plan, &mut hasher)?;
+    DisplayableExecutionPlan::new(plan)
+        .indent()
+        .to_string()
+        .hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+fn hash_sources(plan: &dyn ExecutionPlan, hasher: &mut DefaultHasher) -> Option<()> {
+    let children = plan.children();
+    if children.is_empty() {
+        scan_key(file_scan_config(plan)?).hash(hasher);
+    }
+    for child in children {
+        hash_sources(child.as_ref(), hasher)?;
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::datafusion_test_context;
+    use ballista_core::error::Result;
+    use ballista_core::serde::protobuf::{ExecutorMetadata, PartitionStats};
+    use datafusion::arrow::datatypes::Schema;
+    use datafusion::physical_plan::empty::EmptyExec;
+    use datafusion::prelude::SessionContext;
+    use std::sync::Arc;
+
+    fn test_locations(executor_id: &str, num_bytes: i64) -> Vec<PartitionLocation> {
+        vec![PartitionLocation {
+            executor_meta: Some(ExecutorMetadata {
+                id: executor_id.to_owned(),
+                ..Default::default()
+            }),
+            partition_stats: Some(PartitionStats {
+                num_bytes,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }]
+    }
+
+    async fn test_plan(
+        ctx: &SessionContext,
+        sql: &str,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(ctx.sql(sql).await?.create_physical_plan().await?)
+    }
+
+    #[tokio::test]
+    async fn test_result_fingerprint() -> Result<()> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let sql = "SELECT n_name FROM nation WHERE n_regionkey = 1";
+        let fingerprint = result_fingerprint(test_plan(&ctx, sql).await?.as_ref());
+        assert!(fingerprint.is_some());
+        assert_eq!(
+            fingerprint,
+            result_fingerprint(test_plan(&ctx, sql).await?.as_ref())
+        );
+        assert_ne!(
+            fingerprint,
+            result_fingerprint(
+                test_plan(&ctx, "SELECT n_name FROM nation WHERE n_regionkey = 2")
+                    .await?
+                    .as_ref()
+            )
+        );
+
+        // Sources other than files are not cached
+        let plan = EmptyExec::new(true, Arc::new(Schema::empty()));
+        assert!(result_fingerprint(&plan).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_result_cache() {
+        let cache = ResultCache::new(Duration::from_secs(60), 100);
+        assert!(cache.is_enabled());
+        assert!(cache.get(1).is_none());
+
+        cache.add_pending_job("job1", 1);
+        assert_eq!(cache.remove_pending_job("job1"), Some(1));
+        assert_eq!(cache.remove_pending_job("job1"), None);
+
+        assert!(cache.insert(1, "job1", test_locations("executor1", 40)));
+        assert_eq!(cache.get(1).unwrap().job_id, "job1");
+        // The results of the first job with a fingerprint are kept
+        assert!(!cache.insert(1, "job2", test_locations("executor1", 40)));
+        // Results larger than the cache are not cached
+        assert!(!cache.insert(2, "job2", test_locations("executor1", 101)));
+
+        // The oldest results are evicted once full
+        assert!(cache.insert(2, "job2", test_locations("executor2", 40)));
+        assert!(cache.insert(3, "job3", test_locations("executor1", 40)));
+        assert!(cache.get(1).is_none());
+        assert!(cache.get(2).is_some());
+
+        cache.remove_executor("executor1");
+        assert!(cache.get(3).is_none());
+        assert!(cache.get(2).is_some());
+    }
+
+    #[test]
+    fn test_result_cache_expiry() {
+        let cache = ResultCache::new(Duration::from_millis(10), 100);
+        assert!(cache.insert(1, "job1", test_locations("executor1", 10)));
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(cache.get(1).is_none());
+
+        assert!(!ResultCache::default().is_enabled());
+    }
+
+    #[test]
+    fn test_result_cache_hit_near_ttl() {
+        let ttl = Duration::from_secs(60);
+        let clean_up_interval = 30;
+        let cache = ResultCache::new(ttl, 100);
+        assert!(cache.insert(1, "job1", test_locations("executor1", 10)));
+        let cached_at = cache.cached.lock().results[&1].cached_at;
+
+        // A hit right before the results expire from the cache still has the clean up
+        // interval to read them before the data of the cached job is deleted
+        let hit_at = cached_at + ttl - Duration::from_secs(1);
+        assert_eq!(cache.get_at(1, hit_at).unwrap().job_id, "job1");
+        let deleted_at = cached_at
+            + Duration::from_secs(cache.data_retention_seconds(clean_up_interval));
+        assert!(deleted_at >= hit_at + Duration::from_secs(clean_up_interval));
+
+        assert!(cache.get_at(1, cached_at + ttl).is_none());
+    }
+}
@@ -0,0 +1,384 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sharding of job ownership between the schedulers sharing a cluster. Each scheduler saves a
+//! heartbeat to the cluster state, and every new job is owned by one of the schedulers with a
+//! recent heartbeat, chosen by rendezvous hashing of the job ID so that the jobs of a scheduler
+//! which goes away are spread over the remaining ones.
+
+use std::hash::Hasher;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use tonic::metadata::MetadataMap;
+use tonic::transport::Channel;
+use twox_hash::XxHash64;
+
+use ballista_core::config::GrpcCompression;
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::scheduler_grpc_client::SchedulerGrpcClient;
+use ballista_core::serde::protobuf::{
+    job_status, CancelJobParams, ExecuteQueryParams, ExecuteQueryResult,
+    GetJobStatusParams, JobStatus, RunningJob, SchedulerHeartbeat, TaskStatus,
+    UpdateTaskStatusParams,
+};
+use ballista_core::utils::create_grpc_client_connection;
+
+use crate::cluster::ClusterState;
+use crate::scheduler_server::timestamp_secs;
+
+/// Interval at which a scheduler sharing the cluster saves its heartbeat
+pub const SCHEDULER_HEARTBEAT_INTERVAL_SECS: u64 = 15;
+
+/// Time after its last heartbeat after which a scheduler is no longer assigned new jobs
+pub const SCHEDULER_TIMEOUT_SECS: u64 = 60;
+
+/// Metadata key carrying the ID of a job whose query or cancellation was forwarded to its
+/// owner
+pub const JOB_ID_METADATA_KEY: &str = "ballista-job-id";
+
+/// The other schedulers sharing the cluster, if job sharding is enabled
+pub struct SchedulerPeers {
+    scheduler_id: String,
+    cluster_state: Arc<dyn ClusterState>,
+    job_sharding: bool,
+    grpc_compression: GrpcCompression,
+    clients: DashMap<String, SchedulerGrpcClient<Channel>>,
+}
+
+impl SchedulerPeers {
+    pub fn new(scheduler_id: String, cluster_state: Arc<dyn ClusterState>) -> Self {
+        Self {
+            scheduler_id,
+            cluster_state,
+            job_sharding: false,
+            grpc_compression: GrpcCompression::None,
+            clients: DashMap::new(),
+        }
+    }
+
+    pub fn with_job_sharding(mut self, job_sharding: bool) -> Self {
+        self.job_sharding = job_sharding;
+        self
+    }
+
+    pub fn with_grpc_compression(mut self, compression: GrpcCompression) -> Self {
+        self.grpc_compression = compression;
+        self
+    }
+
+    pub fn is_job_sharding(&self) -> bool {
+        self.job_sharding
+    }
+
+    /// Save the heartbeat of this scheduler to the cluster state
+    pub async fn heartbeat(&self) -> Result<()> {
+        self.cluster_state
+            .save_scheduler_heartbeat(SchedulerHeartbeat {
+                scheduler_id: self.scheduler_id.clone(),
+                timestamp: timestamp_secs(),
+            })
+            .await
+    }
+
+    /// IDs of the schedulers whose last heartbeat is recent enough, this scheduler included
+    pub async fn live_schedulers(&self) -> Result<Vec<String>> {
+        let deadline = timestamp_secs().saturating_sub(SCHEDULER_TIMEOUT_SECS);
+        let mut schedulers: Vec<String> = self
+            .cluster_state
+            .scheduler_heartbeats()
+            .await?
+            .into_iter()
+            .filter(|heartbeat| heartbeat.timestamp >= deadline)
+            .map(|heartbeat| heartbeat.scheduler_id)
+            .collect();
+        if !schedulers.contains(&self.scheduler_id) {
+            schedulers.push(self.scheduler_id.clone());
+        }
+        Ok(schedulers)
+    }
+
+    /// The scheduler which should own a new job, if job sharding is enabled and it is not
+    /// this scheduler
+    pub async fn remote_job_owner(&self, job_id: &str) -> Result<Option<String>> {
+        if !self.job_sharding {
+            return Ok(None);
+        }
+        let schedulers = self.live_schedulers().await?;
+        Ok(job_owner(job_id, &schedulers)
+            .filter(|owner| *owner != self.scheduler_id)
+            .map(str::to_owned))
+    }
+
+    /// The scheduler owning a job of the given status, if job sharding is enabled and it is
+    /// not this scheduler. Running jobs are owned by the scheduler running them, queued ones
+    /// by the scheduler they were assigned to.
+    pub async fn remote_owner_of(
+        &self,
+        job_id: &str,
+        status: Option<&JobStatus>,
+    ) -> Result<Option<String>> {
+        if !self.job_sharding {
+            return Ok(None);
+        }
+        match status.and_then(|status| status.status.as_ref()) {
+            Some(job_status::Status::Running(RunningJob { scheduler, .. })) => {
+                Ok((*scheduler != self.scheduler_id).then(|| scheduler.clone()))
+            }
+            Some(job_status::Status::Queued(_)) => self.remote_job_owner(job_id).await,
+            _ => Ok(None),
+        }
+    }
+
+    /// Submit a query to the scheduler owning its job, which runs it with the given job ID.
+    /// The metadata of the client's request is passed on for the owner to authenticate it.
+    pub async fn forward_query(
+        &self,
+        scheduler_id: &str,
+        job_id: &str,
+        params: ExecuteQueryParams,
+        metadata: MetadataMap,
+    ) -> Result<ExecuteQueryResult> {
+        let mut request = tonic::Request::new(params);
+        *request.metadata_mut() = metadata;
+        let job_id = job_id.parse().map_err(|e| {
+            BallistaError::Internal(format!("Invalid job ID {job_id}: {e:?}"))
+        })?;
+        request.metadata_mut().insert(JOB_ID_METADATA_KEY, job_id);
+        let mut client = self.get_client(scheduler_id).await?;
+        Ok(client.execute_query(request).await?.into_inner())
+    }
+
+    /// Cancel a job on the scheduler owning it. The metadata of the client's request is
+    /// passed on for the owner to authenticate it. Returns whether the job was cancelled.
+    pub async fn forward_cancel_job(
+        &self,
+        scheduler_id: &str,
+        job_id: &str,
+        metadata: MetadataMap,
+    ) -> Result<bool> {
+        let mut request = tonic::Request::new(CancelJobParams {
+            job_id: job_id.to_owned(),
+        });
+        *request.metadata_mut() = metadata;
+        let job_id_value = job_id.parse().map_err(|e| {
+            BallistaError::Internal(format!("Invalid job ID {job_id}: {e:?}"))
+        })?;
+        request
+            .metadata_mut()
+            .insert(JOB_ID_METADATA_KEY, job_id_value);
+        let mut client = self.get_client(scheduler_id).await?;
+        Ok(client.cancel_job(request).await?.into_inner().cancelled)
+    }
+
+    /// The status of a job on the first of the other live schedulers which knows it, none if
+    /// job sharding is disabled or none of them knows the job
+    pub async fn peer_job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        if !self.job_sharding {
+            return Ok(None);
+        }
+        for scheduler_id in self.live_schedulers().await? {
+            if scheduler_id == self.scheduler_id {
+                continue;
+            }
+            let mut client = self.get_client(&scheduler_id).await?;
+            let status = client
+                .get_job_status(GetJobStatusParams {
+                    job_id: job_id.to_owned(),
+                    consumed_partitions: vec![],
+                })
+                .await?
+                .into_inner()
+                .status;
+            if status.is_some() {
+                return Ok(status);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Send task status updates of an executor to the scheduler owning their jobs
+    pub async fn forward_task_status(
+        &self,
+        scheduler_id: &str,
+        executor_id: &str,
+        task_status: Vec<TaskStatus>,
+    ) -> Result<()> {
+        let mut client = self.get_client(scheduler_id).await?;
+        client
+            .update_task_status(UpdateTaskStatusParams {
+                executor_id: executor_id.to_owned(),
+                task_status,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn get_client(
+        &self,
+        scheduler_id: &str,
+    ) -> Result<SchedulerGrpcClient<Channel>> {
+        if let Some(client) = self.clients.get(scheduler_id) {
+            return Ok(client.clone());
+        }
+        let connection =
+            create_grpc_client_connection(format!("http://{scheduler_id}")).await?;
+        let mut client = SchedulerGrpcClient::new(connection);
+        if let Some(encoding) = self.grpc_compression.encoding() {
+            client = client.send_compressed(encoding).accept_compressed(encoding);
+        }
+        self.clients.insert(scheduler_id.to_owned(), client.clone());
+        Ok(client)
+    }
+}
+
+/// The scheduler owning a job among the given ones, the one with the highest hash of its ID
+/// combined with the job ID. Adding or removing a scheduler only moves the jobs owned by it.
+/// The hash is XXH64 with a fixed seed, so that schedulers of different builds and platforms
+/// agree on the owner.
+pub fn job_owner<'a>(job_id: &str, schedulers: &'a [String]) -> Option<&'a str> {
+    schedulers
+        .iter()
+        .max_by_key(|scheduler_id| {
+            let mut hasher = XxHash64::with_seed(0);
+            hasher.write(scheduler_id.as_bytes());
+            // separates the IDs, so that moving bytes from one ID to the other changes the hash
+            hasher.write_u8(0xff);
+            hasher.write(job_id.as_bytes());
+            hasher.finish()
+        })
+        .map(String::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::memory::InMemoryClusterState;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_job_owner() {
+        let schedulers: Vec<String> =
+            (0..3).map(|i| format!("scheduler-{i}:50050")).collect();
+        assert_eq!(job_owner("job", &[]), None);
+
+        let mut owned: HashMap<&str, usize> = HashMap::new();
+        for i in 0..300 {
+            let job_id = format!("job-{i}");
+            let owner = job_owner(&job_id, &schedulers).unwrap();
+            assert_eq!(job_owner(&job_id, &schedulers), Some(owner));
+            *owned.entry(owner).or_default() += 1;
+
+            // Removing another scheduler keeps the owner of the job
+            let remaining: Vec<String> = schedulers
+                .iter()
+                .filter(|s| s.as_str() == owner || *s != &schedulers[0])
+                .cloned()
+                .collect();
+            assert_eq!(job_owner(&job_id, &remaining), Some(owner));
+        }
+        assert_eq!(owned.len(), 3);
+        assert!(owned.values().all(|jobs| *jobs > 50));
+
+        // The owners do not depend on the build or the platform of the scheduler
+        for (job_id, owner) in [
+            ("job-0", "scheduler-1:50050"),
+            ("job-1", "scheduler-0:50050"),
+            ("job-2", "scheduler-2:50050"),
+        ] {
+            assert_eq!(job_owner(job_id, &schedulers), Some(owner));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_live_schedulers() -> Result<()> {
+        let cluster_state: Arc<dyn ClusterState> =
+            Arc::new(InMemoryClusterState::default());
+        let peers =
+            SchedulerPeers::new("localhost:50050".to_owned(), cluster_state.clone());
+        assert_eq!(peers.live_schedulers().await?, vec!["localhost:50050"]);
+        assert_eq!(peers.remote_job_owner("job").await?, None);
+
+        peers.heartbeat().await?;
+        cluster_state
+            .save_scheduler_heartbeat(SchedulerHeartbeat {
+                scheduler_id: "localhost:50051".to_owned(),
+                timestamp: timestamp_secs(),
+            })
+            .await?;
+        cluster_state
+            .save_scheduler_heartbeat(SchedulerHeartbeat {
+                scheduler_id: "localhost:50052".to_owned(),
+                timestamp: timestamp_secs() - SCHEDULER_TIMEOUT_SECS - 1,
+            })
+            .await?;
+        let mut schedulers = peers.live_schedulers().await?;
+        schedulers.sort();
+        assert_eq!(schedulers, vec!["localhost:50050", "localhost:50051"]);
+
+        // Jobs are only owned by other schedulers with sharding enabled
+        let peers = peers.with_job_sharding(true);
+        for i in 0..20 {
+            let job_id = format!("job-{i}");
+            let owner = peers.remote_job_owner(&job_id).await?;
+            let expected = job_owner(&job_id, &schedulers)
+                .filter(|owner| *owner != "localhost:50050")
+                .map(str::to_owned);
+            assert_eq!(owner, expected);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remote_owner_of() -> Result<()> {
+        let cluster_state: Arc<dyn ClusterState> =
+            Arc::new(InMemoryClusterState::default());
+        let peers =
+            SchedulerPeers::new("localhost:50050".to_owned(), cluster_state.clone());
+        let running = |scheduler: &str| JobStatus {
+            job_id: "job".to_owned(),
+            job_name: String::new(),
+            status: Some(job_status::Status::Running(RunningJob {
+                scheduler: scheduler.to_owned(),
+                ..Default::default()
+            })),
+        };
+        assert_eq!(
+            peers
+                .remote_owner_of("job", Some(&running("localhost:50051")))
+                .await?,
+            None
+        );
+
+        // Running jobs are owned by the scheduler running them
+        let peers = peers.with_job_sharding(true);
+        assert_eq!(
+            peers
+                .remote_owner_of("job", Some(&running("localhost:50051")))
+                .await?,
+            Some("localhost:50051".to_owned())
+        );
+        assert_eq!(
+            peers
+                .remote_owner_of("job", Some(&running("localhost:50050")))
+                .await?,
+            None
+        );
+        assert_eq!(peers.remote_owner_of("job", None).await?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,384 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Recording of the inputs and decisions of the scheduler about task slots, which can be
+//! replayed to reproduce a scheduling sequence deterministically
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::sync::Arc;
+
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::scheduling_record::Record;
+use ballista_core::serde::protobuf::{
+    RecordedReservation, RecordedTask, RegisterExecutorRecord, ReserveSlotsRecord,
+    SchedulingRecord,
+};
+use ballista_core::serde::scheduler::{ExecutorData, ExecutorMetadata, PartitionId};
+use log::warn;
+use parking_lot::Mutex;
+use prost::Message;
+
+use crate::cluster::ClusterState;
+use crate::config::TaskDistribution;
+use crate::state::executor_manager::ExecutorReservation;
+
+/// Sink of the inputs and decisions of a scheduler about task slots: the executors registered
+/// and removed, the task slots reserved and cancelled, the reservations filled with tasks and
+/// the tasks launched on executors.
+pub trait SchedulingRecorder: Debug + Send + Sync {
+    fn record(&self, record: Record);
+}
+
+/// Appends the records to a file as length-delimited `SchedulingRecord` messages, which can be
+/// read back with [`read_scheduling_records`]
+#[derive(Debug)]
+pub struct FileSchedulingRecorder {
+    path: String,
+    file: Mutex<File>,
+}
+
+impl FileSchedulingRecorder {
+    pub fn try_new(path: &str) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl SchedulingRecorder for FileSchedulingRecorder {
+    fn record(&self, record: Record) {
+        let buf = SchedulingRecord {
+            record: Some(record),
+        }
+        .encode_length_delimited_to_vec();
+        if let Err(e) = self.file.lock().write_all(&buf) {
+            warn!(
+                "Failed to write scheduling record to {}: {:?}",
+                self.path, e
+            );
+        }
+    }
+}
+
+/// Keeps the records in memory
+#[derive(Debug, Default)]
+pub struct InMemorySchedulingRecorder {
+    records: Mutex<Vec<Record>>,
+}
+
+impl InMemorySchedulingRecorder {
+    pub fn records(&self) -> Vec<Record> {
+        self.records.lock().clone()
+    }
+}
+
+impl SchedulingRecorder for InMemorySchedulingRecorder {
+    fn record(&self, record: Record) {
+        self.records.lock().push(record);
+    }
+}
+
+/// Read the records written by a [`FileSchedulingRecorder`]
+pub fn read_scheduling_records(path: &str) -> Result<Vec<Record>> {
+    let mut buf = vec![];
+    File::open(path)?.read_to_end(&mut buf)?;
+
+    let mut buf = buf.as_slice();
+    let mut records = vec![];
+    while !buf.is_empty() {
+        let record =
+            SchedulingRecord::decode_length_delimited(&mut buf).map_err(|e| {
+                BallistaError::Internal(format!(
+                    "Invalid scheduling record in {path}: {e:?}"
+                ))
+            })?;
+        records.extend(record.record);
+    }
+
+    Ok(records)
+}
+
+/// Replay the registrations, resizes and removals of executors and the reservations and
+/// cancellations of task slots of a recorded sequence against `cluster_state`, which should
+/// be empty. Fails at the first reservation which does not reserve the same task slots as
+/// recorded.
+/// Returns the number of task slots still reserved at the end of the sequence, either held by
+/// reservations or by the tasks launched on them.
+pub async fn replay_scheduling(
+    records: &[Record],
+    cluster_state: Arc<dyn ClusterState>,
+    task_distribution: TaskDistribution,
+) -> Result<usize> {
+    let mut reserved_slots: HashMap<String, usize> = HashMap::new();
+
+    for (idx, record) in records.iter().enumerate() {
+        match record {
+            Record::RegisterExecutor(RegisterExecutorRecord {
+                metadata,
+                total_task_slots,
+                available_task_slots,
+                reserve,
+            }) => {
+                let metadata: ExecutorMetadata = metadata
+                    .clone()
+                    .ok_or_else(|| {
+                        BallistaError::Internal(format!(
+                            "Scheduling record {idx} has no executor metadata"
+                        ))
+                    })?
+                    .into();
+                let executor_id = metadata.id.clone();
+                let reservations = cluster_state
+                    .register_executor(
+                        metadata,
+                        ExecutorData {
+                            executor_id: executor_id.clone(),
+                            total_task_slots: *total_task_slots,
+                            available_task_slots: *available_task_slots,
+                        },
+                        *reserve,
+                    )
+                    .await?;
+                reserved_slots.insert(
+                    executor_id,
+                    total_task_slots.saturating_sub(*available_task_slots) as usize
+                        + reservations.len(),
+                );
+            }
+            Record::RemoveExecutor(record) => {
+                cluster_state.remove_executor(&record.executor_id).await?;
+                reserved_slots.remove(&record.executor_id);
+            }
+            Record::ResizeExecutor(record) => {
+                cluster_state
+                    .resize_executor(&record.executor_id, record.task_slots_delta)
+                    .await?;
+            }
+            Record::ReserveSlots(ReserveSlotsRecord {
+                num_slots,
+                resources,
+                executors,
+                reserved,
+                exact,
+            }) => {
+                let resources = resources.clone().unwrap_or_default().into();
+                let executors = Some(executors.iter().cloned().collect());
+                let reservations = if *exact {
+                    cluster_state
+                        .reserve_slots_exact(
+                            *num_slots,
+                            resources,
+                            task_distribution,
+                            executors,
+                        )
+                        .await?
+                } else {
+                    cluster_state
+                        .reserve_slots(
+                            *num_slots,
+                            resources,
+                            task_distribution,
+                            executors,
+                        )
+                        .await?
+                };
+                let replayed: Vec<String> = reservations
+                    .into_iter()
+                    .map(|reservation| reservation.executor_id)
+                    .collect();
+                if replayed != *reserved {
+                    return Err(BallistaError::Internal(format!(
+                        "Scheduling record {idx} reserved task slots on {reserved:?} \
+                        but the replay reserved them on {replayed:?}"
+                    )));
+                }
+                for executor_id in replayed {
+                    *reserved_slots.entry(executor_id).or_default() += 1;
+                }
+            }
+            Record::CancelReservations(record) => {
+                let reservations: Vec<ExecutorReservation> = record
+                    .reservations
+                    .iter()
+                    .map(|reservation| {
+                        ExecutorReservation::new_free(reservation.executor_id.clone())
+                            .with_resources(
+                                reservation.resources.clone().unwrap_or_default().into(),
+                            )
+                    })
+                    .collect();
+                for reservation in reservations.iter() {
+                    if let Some(slots) = reserved_slots.get_mut(&reservation.executor_id)
+                    {
+                        *slots = slots.saturating_sub(1);
+                    }
+                }
+                cluster_state.cancel_reservations(reservations).await?;
+            }
+            // The decisions of the task manager are kept for diagnosing a sequence, they do
+            // not change the task slots
+            Record::FillReservations(_) | Record::LaunchTasks(_) => {}
+        }
+    }
+
+    Ok(reserved_slots.values().sum())
+}
+
+pub(crate) fn recorded_reservation(
+    reservation: &ExecutorReservation,
+) -> RecordedReservation {
+    RecordedReservation {
+        executor_id: reservation.executor_id.clone(),
+        job_id: reservation.job_id.clone().unwrap_or_default(),
+        resources: Some(reservation.resources.into()),
+    }
+}
+
+pub(crate) fn recorded_task(executor_id: &str, partition: &PartitionId) -> RecordedTask {
+    RecordedTask {
+        executor_id: executor_id.to_owned(),
+        job_id: partition.job_id.clone(),
+        stage_id: partition.stage_id as u32,
+        partition_id: partition.partition_id as u32,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use ballista_core::error::Result;
+    use ballista_core::serde::scheduler::{
+        ExecutorData, ExecutorMetadata, ExecutorSpecification, TaskResources,
+    };
+
+    use crate::config::TaskDistribution;
+    use crate::state::executor_manager::ExecutorManager;
+    use crate::state::scheduling_recorder::{
+        read_scheduling_records, replay_scheduling, FileSchedulingRecorder,
+        InMemorySchedulingRecorder,
+    };
+    use crate::test_utils::test_cluster_context;
+
+    async fn record_sequence(executor_manager: &ExecutorManager) -> Result<()> {
+        for i in 0..2 {
+            executor_manager
+                .register_executor(
+                    ExecutorMetadata {
+                        id: format!("executor-{i}"),
+                        host: format!("host-{i}"),
+                        port: 8080,
+                        grpc_port: 9090,
+                        specification: ExecutorSpecification {
+                            task_slots: 4,
+                            ..Default::default()
+                        },
+                        pool: String::new(),
+                    },
+                    ExecutorData {
+                        executor_id: format!("executor-{i}"),
+                        total_task_slots: 4,
+                        available_task_slots: 4,
+                    },
+                    false,
+                )
+                .await?;
+        }
+
+        let mut reservations = executor_manager
+            .reserve_slots("", 6, TaskResources::default())
+            .await?;
+        let cancelled = reservations.split_off(4);
+        executor_manager.cancel_reservations(cancelled).await?;
+        executor_manager
+            .reserve_slots("", 3, TaskResources::default())
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replay_scheduling() -> Result<()> {
+        let recorder = Arc::new(InMemorySchedulingRecorder::default());
+        let executor_manager = ExecutorManager::new(
+            test_cluster_context().cluster_state(),
+            TaskDistribution::Bias,
+        )
+        .with_scheduling_recorder(Some(recorder.clone()));
+
+        record_sequence(&executor_manager).await?;
+        let records = recorder.records();
+        assert_eq!(records.len(), 5);
+
+        let reserved_slots = replay_scheduling(
+            &records,
+            test_cluster_context().cluster_state(),
+            TaskDistribution::Bias,
+        )
+        .await?;
+        assert_eq!(reserved_slots, 7);
+        assert_eq!(
+            executor_manager.slot_metrics().reserved_slots,
+            reserved_slots as u64
+        );
+
+        // Distributing the tasks differently does not reserve the same task slots
+        let replayed = replay_scheduling(
+            &records,
+            test_cluster_context().cluster_state(),
+            TaskDistribution::RoundRobin,
+        )
+        .await;
+        assert!(replayed.is_err(), "Expected the replay to diverge");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_file_scheduling_recorder() -> Result<()> {
+        let path = std::env::temp_dir()
+            .join(format!("scheduling-records-{}", uuid::Uuid::new_v4()));
+        let path = path.to_str().unwrap();
+
+        let recorder = Arc::new(InMemorySchedulingRecorder::default());
+        let executor_manager = ExecutorManager::new(
+            test_cluster_context().cluster_state(),
+            TaskDistribution::Bias,
+        )
+        .with_scheduling_recorder(Some(Arc::new(FileSchedulingRecorder::try_new(path)?)));
+        record_sequence(&executor_manager).await?;
+
+        let in_memory_executor_manager = ExecutorManager::new(
+            test_cluster_context().cluster_state(),
+            TaskDistribution::Bias,
+        )
+        .with_scheduling_recorder(Some(recorder.clone()));
+        record_sequence(&in_memory_executor_manager).await?;
+
+        let records = read_scheduling_records(path)?;
+        std::fs::remove_file(path)?;
+        assert_eq!(records, recorder.records());
+
+        Ok(())
+    }
+}
@@ -15,22 +15,93 @@
 // specific language governing permissions and limitations
 // under the License.
 
-use crate::scheduler_server::SessionBuilder;
-use ballista_core::config::BallistaConfig;
-use ballista_core::error::Result;
+use crate::scheduler_server::{timestamp_secs, SessionBuilder};
+use ballista_core::config::{
+    parse_key_values, BallistaConfig, BALLISTA_JOB_OWNER, BALLISTA_JOB_RESULT_BUFFER,
+};
+use ballista_core::error::{BallistaError, Result};
+use ballista_core::serde::protobuf::{
+    ClusterConfig, PreparedStatementDefinition, TableDefinition,
+};
+use ballista_core::utils::DefaultObjectStores;
+use datafusion::catalog::catalog::CatalogProvider;
+use datafusion::common::DFSchema;
+use datafusion::datasource::provider::TableProviderFactory;
+use datafusion::logical_expr::{CreateExternalTable, DdlStatement, LogicalPlan};
 use datafusion::prelude::{SessionConfig, SessionContext};
+use datafusion_proto::logical_plan::{AsLogicalPlan, DefaultLogicalExtensionCodec};
+use datafusion_proto::protobuf::LogicalPlanNode;
+use itertools::Itertools;
+use log::warn;
+use uuid::Uuid;
 
+use crate::cluster::event::{ClusterEventSender, EventSubscriber};
 use crate::cluster::JobState;
+use crate::state::bucketed_tables::with_bucketed_tables;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
+/// Interval at which the access of a session is recorded in a shared state backend, the
+/// precision of the idle time of sessions
+pub const SESSION_ACCESS_INTERVAL_SECS: u64 = 60;
+
+/// Interval at which idle sessions are removed
+pub const SESSION_CLEAN_UP_INTERVAL_SECS: u64 = 60;
+
+/// Interval at which the dropped tables older than the retention window are purged
+pub const DROPPED_TABLES_PURGE_INTERVAL_SECS: u64 = 60;
+
+/// Formats of the files which tables can be created for, the ones of the listing tables which
+/// can be sent to the executors in the plans of tasks
+pub const SUPPORTED_FILE_FORMATS: [&str; 3] = ["avro", "csv", "parquet"];
+
 #[derive(Clone)]
 pub struct SessionManager {
     state: Arc<dyn JobState>,
+    /// Catalogs registered with every session
+    catalogs: HashMap<String, Arc<dyn CatalogProvider>>,
+    /// Settings of every session which the settings of the session do not override
+    default_settings: HashMap<String, String>,
+    /// Object stores configured with options registered with every session
+    object_stores: DefaultObjectStores,
+    /// IDs of the sessions removed by this scheduler
+    removed_sessions: Arc<ClusterEventSender<String>>,
 }
 
 impl SessionManager {
     pub fn new(state: Arc<dyn JobState>) -> Self {
-        Self { state }
+        Self {
+            state,
+            catalogs: HashMap::new(),
+            default_settings: HashMap::new(),
+            object_stores: DefaultObjectStores::default(),
+            removed_sessions: Arc::new(ClusterEventSender::default()),
+        }
+    }
+
+    /// Register `object_stores` with every session, e.g. S3 compatible stores with their
+    /// endpoints
+    pub fn with_object_stores(mut self, object_stores: DefaultObjectStores) -> Self {
+        self.object_stores = object_stores;
+        self
+    }
+
+    /// Apply `default_settings`, checked with [parse_session_settings], to every session
+    /// which does not set them, e.g. `datafusion.catalog.information_schema=true`
+    pub fn with_default_settings(
+        mut self,
+        default_settings: HashMap<String, String>,
+    ) -> Self {
+        self.default_settings = default_settings;
+        self
+    }
+
+    pub fn with_catalogs(
+        mut self,
+        catalogs: HashMap<String, Arc<dyn CatalogProvider>>,
+    ) -> Self {
+        self.catalogs.extend(catalogs);
+        self
     }
 
     pub async fn update_session(
@@ -38,36 +109,976 @@ impl SessionManager {
         session_id: &str,
         config: &BallistaConfig,
     ) -> Result<Arc<SessionContext>> {
-        self.state.update_session(session_id, config).await
+        let config = self.with_default_settings(config)?;
+        let session = self.state.update_session(session_id, &config).await?;
+        self.register_shared(session).await
     }
 
     pub async fn create_session(
         &self,
         config: &BallistaConfig,
     ) -> Result<Arc<SessionContext>> {
-        self.state.create_session(config).await
+        let config = self.with_default_settings(config)?;
+        let session = self.state.create_session(&config).await?;
+        self.register_shared(session).await
+    }
+
+    /// The configuration of a session with the default settings it does not override
+    fn with_default_settings(&self, config: &BallistaConfig) -> Result<BallistaConfig> {
+        if self.default_settings.is_empty() {
+            return Ok(config.clone());
+        }
+        let mut settings = self.default_settings.clone();
+        settings.extend(
+            config
+                .settings()
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+        BallistaConfig::with_settings(settings)
     }
 
     pub async fn get_session(&self, session_id: &str) -> Result<Arc<SessionContext>> {
-        self.state.get_session(session_id).await
+        let session = self.state.get_session(session_id).await?;
+        self.register_shared(session).await
+    }
+
+    /// Register the catalogs and the shared tables with a session
+    async fn register_shared(
+        &self,
+        session: Arc<SessionContext>,
+    ) -> Result<Arc<SessionContext>> {
+        for (name, catalog) in &self.catalogs {
+            session.register_catalog(name, catalog.clone());
+        }
+        self.object_stores.register(&session.runtime_env());
+        for table in self.state.get_tables().await? {
+            if table.dropped_at > 0 {
+                session.deregister_table(table.name.as_str())?;
+                continue;
+            }
+            if session.table_exist(table.name.as_str())? {
+                continue;
+            }
+            // A table which cannot be created, e.g. because its schema is missing from the
+            // catalog of the session, should not make the session unusable
+            if let Err(e) = create_table(&session, &table).await {
+                warn!("Failed to create shared table {}: {e}", table.name);
+            }
+        }
+        Ok(session)
+    }
+
+    /// Share a table created with a CREATE EXTERNAL TABLE plan with all sessions, saving its
+    /// definition with the schema resolved in `session`. Returns whether the table was
+    /// registered, false if a table of the same name exists and the plan is IF NOT EXISTS.
+    /// A dropped table of the same name is replaced and can no longer be restored.
+    pub async fn register_table(
+        &self,
+        session: &SessionContext,
+        plan: LogicalPlan,
+    ) -> Result<bool> {
+        let cmd = match plan {
+            LogicalPlan::Ddl(DdlStatement::CreateExternalTable(cmd)) => cmd,
+            other => {
+                return Err(BallistaError::General(format!(
+                    "Expected a CREATE EXTERNAL TABLE plan but got {other:?}"
+                )))
+            }
+        };
+        let name = cmd.name.to_string();
+        let exists = self
+            .state
+            .get_tables()
+            .await?
+            .iter()
+            .any(|table| table.name == name && table.dropped_at == 0);
+        if exists || session.table_exist(cmd.name.clone())? {
+            return table_exists(&name, cmd.if_not_exists);
+        }
+
+        session
+            .execute_logical_plan(LogicalPlan::Ddl(DdlStatement::CreateExternalTable(
+                cmd.clone(),
+            )))
+            .await?;
+        let if_not_exists = cmd.if_not_exists;
+        let definition = table_definition(session, cmd).await?;
+        if !self.state.save_new_table(definition).await? {
+            // Another scheduler shared a table of the same name since the check
+            session.deregister_table(name.as_str())?;
+            return table_exists(&name, if_not_exists);
+        }
+        Ok(true)
+    }
+
+    /// Get a session with the tables it created, restored from the state backend so that
+    /// any scheduler can serve the session
+    pub async fn get_session_with_objects(
+        &self,
+        session_id: &str,
+    ) -> Result<Arc<SessionContext>> {
+        let session = self.get_session(session_id).await?;
+        for table in self.state.get_session_objects(session_id).await?.tables {
+            if session.table_exist(table.name.as_str())? {
+                continue;
+            }
+            if let Err(e) = create_table(&session, &table).await {
+                warn!(
+                    "Failed to create table {} of session {session_id}: {e}",
+                    table.name
+                );
+            }
+        }
+        Ok(session)
+    }
+
+    /// Save a table created in `session` with a CREATE EXTERNAL TABLE plan, which was
+    /// executed in it already, so that the session has it on any scheduler
+    pub async fn save_session_table(
+        &self,
+        session: &SessionContext,
+        cmd: CreateExternalTable,
+    ) -> Result<()> {
+        let session_id = session.session_id();
+        let table = table_definition(session, cmd).await?;
+        self.state
+            .update_session_objects(
+                &session_id,
+                Box::new(move |objects| {
+                    objects.tables.retain(|saved| saved.name != table.name);
+                    objects.tables.push(table);
+                    true
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Remove a table of a session, returns whether the session created it
+    pub async fn remove_session_table(
+        &self,
+        session_id: &str,
+        name: &str,
+    ) -> Result<bool> {
+        let name = name.to_owned();
+        self.state
+            .update_session_objects(
+                session_id,
+                Box::new(move |objects| {
+                    let num_tables = objects.tables.len();
+                    objects.tables.retain(|table| table.name != name);
+                    objects.tables.len() < num_tables
+                }),
+            )
+            .await
+    }
+
+    /// Save the SQL of a statement prepared in a session, which the schedulers which did
+    /// not prepare it plan again
+    pub async fn save_prepared_statement(
+        &self,
+        session_id: &str,
+        handle: &[u8],
+        query: &str,
+    ) -> Result<()> {
+        let statement = PreparedStatementDefinition {
+            handle: handle.to_vec(),
+            query: query.to_owned(),
+        };
+        self.state
+            .update_session_objects(
+                session_id,
+                Box::new(move |objects| {
+                    objects
+                        .prepared_statements
+                        .retain(|saved| saved.handle != statement.handle);
+                    objects.prepared_statements.push(statement);
+                    true
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// The SQL of a statement prepared in a session, none if it was closed
+    pub async fn prepared_statement(
+        &self,
+        session_id: &str,
+        handle: &[u8],
+    ) -> Result<Option<String>> {
+        Ok(self
+            .state
+            .get_session_objects(session_id)
+            .await?
+            .prepared_statements
+            .into_iter()
+            .find(|statement| statement.handle == handle)
+            .map(|statement| statement.query))
+    }
+
+    /// Remove a closed prepared statement of a session
+    pub async fn remove_prepared_statement(
+        &self,
+        session_id: &str,
+        handle: &[u8],
+    ) -> Result<()> {
+        let handle = handle.to_vec();
+        self.state
+            .update_session_objects(
+                session_id,
+                Box::new(move |objects| {
+                    let num_statements = objects.prepared_statements.len();
+                    objects
+                        .prepared_statements
+                        .retain(|statement| statement.handle != handle);
+                    objects.prepared_statements.len() < num_statements
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Issue a bearer token for a session, a random secret saved with the objects of the
+    /// session prefixed with its ID, so that any scheduler maps the token to the session
+    /// while the ID of the session alone grants no access to it
+    pub async fn issue_token(&self, session_id: &str) -> Result<String> {
+        let secret = Uuid::new_v4().simple().to_string();
+        let token_secret = secret.clone();
+        self.state
+            .update_session_objects(
+                session_id,
+                Box::new(move |objects| {
+                    objects.token_secret = token_secret;
+                    true
+                }),
+            )
+            .await?;
+        Ok(format!("{session_id}.{secret}"))
+    }
+
+    /// Make `owner` the owner of a session unless it has one already, so that only the user
+    /// who created the session may close it
+    pub async fn claim_session(&self, session_id: &str, owner: &str) -> Result<()> {
+        let owner = owner.to_owned();
+        self.state
+            .update_session_objects(
+                session_id,
+                Box::new(move |objects| {
+                    if objects.owner.is_empty() {
+                        objects.owner = owner;
+                        true
+                    } else {
+                        false
+                    }
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// The user who created a session, none if the client was not authenticated
+    pub async fn session_owner(&self, session_id: &str) -> Result<Option<String>> {
+        let objects = self.state.get_session_objects(session_id).await?;
+        Ok(Some(objects.owner).filter(|owner| !owner.is_empty()))
+    }
+
+    /// The ID of the session of a bearer token issued by [Self::issue_token], none if the
+    /// token is invalid or its session was removed
+    pub async fn token_session(&self, token: &str) -> Result<Option<String>> {
+        let (session_id, secret) = match token.rsplit_once('.') {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        let objects = self.state.get_session_objects(session_id).await?;
+        let valid = !objects.token_secret.is_empty()
+            && constant_time_eq(objects.token_secret.as_bytes(), secret.as_bytes());
+        Ok(valid.then(|| session_id.to_owned()))
+    }
+
+    /// Tables shared by all sessions, without the dropped ones
+    pub async fn tables(&self) -> Result<Vec<TableDefinition>> {
+        Ok(self
+            .state
+            .get_tables()
+            .await?
+            .into_iter()
+            .filter(|table| table.dropped_at == 0)
+            .collect())
+    }
+
+    /// Drop a shared table, which sessions no longer have. Its definition is kept until it
+    /// is purged so that it can be restored with [Self::undrop_table]. Returns whether the
+    /// table was dropped, false if there is no shared table of that name and `if_exists`.
+    pub async fn drop_table(&self, name: &str, if_exists: bool) -> Result<bool> {
+        let table = self
+            .state
+            .get_tables()
+            .await?
+            .into_iter()
+            .find(|table| table.name == name && table.dropped_at == 0);
+        match table {
+            Some(table) => {
+                self.state
+                    .save_table(TableDefinition {
+                        dropped_at: timestamp_secs(),
+                        ..table
+                    })
+                    .await?;
+                Ok(true)
+            }
+            None if if_exists => Ok(false),
+            None => Err(BallistaError::General(format!(
+                "Shared table {name} does not exist"
+            ))),
+        }
+    }
+
+    /// Restore a dropped shared table which was not purged yet
+    pub async fn undrop_table(&self, name: &str) -> Result<()> {
+        let table = self
+            .state
+            .get_tables()
+            .await?
+            .into_iter()
+            .find(|table| table.name == name && table.dropped_at > 0)
+            .ok_or_else(|| {
+                BallistaError::General(format!("No dropped table {name} to restore"))
+            })?;
+        self.state
+            .save_table(TableDefinition {
+                dropped_at: 0,
+                ..table
+            })
+            .await
+    }
+
+    /// Remove the definitions of the tables dropped more than `retention_seconds` ago,
+    /// which can no longer be restored. Returns the names of the purged tables.
+    pub async fn purge_dropped_tables(
+        &self,
+        retention_seconds: u64,
+    ) -> Result<Vec<String>> {
+        let dropped_before = timestamp_secs().saturating_sub(retention_seconds);
+        let mut purged = vec![];
+        for table in self.state.get_tables().await? {
+            if table.dropped_at > 0 && table.dropped_at <= dropped_before {
+                self.state.remove_table(&table.name).await?;
+                purged.push(table.name);
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Defaults and capabilities of the sessions of the cluster
+    pub fn cluster_config(&self) -> Result<ClusterConfig> {
+        let session = self.state.default_session()?;
+        let config =
+            create_session_config(&self.with_default_settings(&BallistaConfig::new()?)?);
+        let table_factories = session
+            .state()
+            .table_factories()
+            .keys()
+            .cloned()
+            .sorted()
+            .collect();
+        Ok(ClusterConfig {
+            default_shuffle_partitions: config.target_partitions() as u64,
+            default_batch_size: config.batch_size() as u64,
+            file_formats: SUPPORTED_FILE_FORMATS
+                .iter()
+                .map(|format| format.to_string())
+                .collect(),
+            table_factories,
+            // Set by the scheduler, which knows the services it serves
+            rest_api: false,
+        })
+    }
+
+    /// Remove a session, returns whether it existed. Queries submitted later with the ID of
+    /// the session create it again.
+    pub async fn remove_session(&self, session_id: &str) -> Result<bool> {
+        let removed = self.state.remove_session(session_id).await?;
+        if removed {
+            self.removed_sessions.send(&session_id.to_owned());
+        }
+        Ok(removed)
+    }
+
+    /// Remove the sessions which were not accessed for `idle_timeout_seconds`, except for
+    /// the sessions in `keep`. Returns the IDs of the removed sessions.
+    pub async fn remove_idle_sessions(
+        &self,
+        idle_timeout_seconds: u64,
+        keep: &HashSet<String>,
+    ) -> Result<Vec<String>> {
+        let accessed_before = timestamp_secs().saturating_sub(idle_timeout_seconds);
+        let removed = self
+            .state
+            .remove_idle_sessions(accessed_before, keep)
+            .await?;
+        for session_id in removed.iter() {
+            self.removed_sessions.send(session_id);
+        }
+        Ok(removed)
+    }
+
+    /// The IDs of the sessions this scheduler removes from now on, to drop what is kept in
+    /// memory for them
+    pub(crate) fn removed_sessions(&self) -> EventSubscriber<String> {
+        self.removed_sessions.subscribe()
     }
 }
 
-/// Create a DataFusion session context that is compatible with Ballista Configuration
+/// Definition of a table created in `session` with a CREATE EXTERNAL TABLE plan, with the
+/// schema resolved in the session so that sessions do not have to infer it again
+/// Whether `a` and `b` are equal, in a time independent of the position of their first
+/// difference so that secrets cannot be guessed byte after byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn table_definition(
+    session: &SessionContext,
+    mut cmd: CreateExternalTable,
+) -> Result<TableDefinition> {
+    let name = cmd.name.to_string();
+    let schema = session.table_provider(cmd.name.clone()).await?.schema();
+    cmd.schema = Arc::new(DFSchema::try_from(schema.as_ref().clone())?);
+    let node = LogicalPlanNode::try_from_logical_plan(
+        &LogicalPlan::Ddl(DdlStatement::CreateExternalTable(cmd)),
+        &DefaultLogicalExtensionCodec {},
+    )?;
+    let mut plan = vec![];
+    node.try_encode(&mut plan)?;
+    Ok(TableDefinition {
+        name,
+        plan,
+        dropped_at: 0,
+    })
+}
+
+/// The result of creating a table which already exists
+fn table_exists(name: &str, if_not_exists: bool) -> Result<bool> {
+    if if_not_exists {
+        Ok(false)
+    } else {
+        Err(BallistaError::General(format!(
+            "Table {name} already exists"
+        )))
+    }
+}
+
+/// Create a shared table in a session from its definition
+async fn create_table(session: &SessionContext, table: &TableDefinition) -> Result<()> {
+    let plan = LogicalPlanNode::try_decode(&table.plan)?
+        .try_into_logical_plan(session, &DefaultLogicalExtensionCodec {})?;
+    session.execute_logical_plan(plan).await?;
+    Ok(())
+}
+
+/// Parse the comma separated `key=value` settings applied to every session, checking that
+/// both Ballista and DataFusion accept them
+pub fn parse_session_settings(settings: &str) -> Result<HashMap<String, String>> {
+    let settings = parse_key_values(settings).map_err(|e| {
+        BallistaError::General(format!("Invalid session settings {settings:?}: {e}"))
+    })?;
+    BallistaConfig::with_settings(settings.clone())?;
+    SessionConfig::from_string_hash_map(settings.clone())?;
+    Ok(settings)
+}
+
+/// Create a DataFusion session context that is compatible with Ballista Configuration,
+/// creating the tables of the file types of `table_factories` with them
 pub fn create_datafusion_context(
     ballista_config: &BallistaConfig,
     session_builder: SessionBuilder,
+    table_factories: &HashMap<String, Arc<dyn TableProviderFactory>>,
 ) -> Arc<SessionContext> {
+    let mut session_state = session_builder(create_session_config(ballista_config));
+    // DataFusion looks the factories up by the upper case file type
+    session_state.table_factories_mut().extend(
+        table_factories
+            .iter()
+            .map(|(file_type, factory)| (file_type.to_uppercase(), factory.clone())),
+    );
+    Arc::new(SessionContext::with_state(with_bucketed_tables(
+        session_state,
+    )))
+}
+
+/// Create the DataFusion configuration of a session from its Ballista configuration
+fn create_session_config(ballista_config: &BallistaConfig) -> SessionConfig {
     let config =
         SessionConfig::from_string_hash_map(ballista_config.settings().clone()).unwrap();
-    let config = config
+    config
         .with_target_partitions(ballista_config.default_shuffle_partitions())
         .with_batch_size(ballista_config.default_batch_size())
         .with_repartition_joins(ballista_config.repartition_joins())
         .with_repartition_aggregations(ballista_config.repartition_aggregations())
         .with_repartition_windows(ballista_config.repartition_windows())
         .with_parquet_pruning(ballista_config.parquet_pruning())
-        .set_bool("datafusion.optimizer.enable_round_robin_repartition", false);
-    let session_state = session_builder(config);
-    Arc::new(SessionContext::with_state(session_state))
+        .set_bool("datafusion.optimizer.enable_round_robin_repartition", false)
+        .with_extension(Arc::new(ballista_config.clone()))
+}
+
+/// Get the Ballista configuration a session context was created with
+pub fn session_ballista_config(ctx: &SessionContext) -> Option<Arc<BallistaConfig>> {
+    ctx.copied_config().get_extension::<BallistaConfig>()
+}
+
+/// Settings of a job overriding the ones of its session, kept in the configuration of the
+/// context planning the job
+struct JobSettings(HashMap<String, String>);
+
+/// Create the context planning a single job of a session, with settings overriding the ones
+/// of the session. The context shares the catalogs of the session, whose configuration is
+/// left unchanged.
+pub fn create_job_context(
+    session: &SessionContext,
+    job_settings: HashMap<String, String>,
+) -> Result<Arc<SessionContext>> {
+    let config =
+        job_ballista_config(session_ballista_config(session).as_deref(), &job_settings)?;
+    let mut state = session.state();
+    *state.config_mut() = create_session_config(&config)
+        .with_extension(Arc::new(JobSettings(job_settings)));
+    Ok(Arc::new(SessionContext::with_state(state)))
+}
+
+/// Create the context planning a single job like [create_job_context], with the job
+/// settings of `ctx` and the user owning the job, replacing any owner set by the client.
+/// The result buffer of the job is disabled unless its client reports the result partitions
+/// it fetched, without which the tasks held back by the buffer would never run.
+pub fn create_owned_job_context(
+    ctx: &SessionContext,
+    owner: Option<&str>,
+    reports_consumed_partitions: bool,
+) -> Result<Arc<SessionContext>> {
+    let mut settings = job_settings(ctx);
+    settings.insert(
+        BALLISTA_JOB_OWNER.to_owned(),
+        owner.unwrap_or_default().to_owned(),
+    );
+    if !reports_consumed_partitions {
+        settings.insert(BALLISTA_JOB_RESULT_BUFFER.to_owned(), "0".to_owned());
+    }
+    create_job_context(ctx, settings)
+}
+
+/// Get the settings overriding the ones of the session of the job planned by a context
+pub fn job_settings(ctx: &SessionContext) -> HashMap<String, String> {
+    ctx.copied_config()
+        .get_extension::<JobSettings>()
+        .map(|settings| settings.0.clone())
+        .unwrap_or_default()
+}
+
+/// Ballista configuration of a job, the one of its session with the settings of the job
+/// overriding the ones of the session
+pub fn job_ballista_config(
+    session_config: Option<&BallistaConfig>,
+    job_settings: &HashMap<String, String>,
+) -> Result<BallistaConfig> {
+    let mut settings = session_config
+        .map(|config| config.settings().clone())
+        .unwrap_or_default();
+    settings.extend(
+        job_settings
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone())),
+    );
+    BallistaConfig::with_settings(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::memory::InMemoryJobState;
+    use ballista_core::config::ObjectStoreRetryConfig;
+    use ballista_core::config::{
+        BALLISTA_DEFAULT_BATCH_SIZE, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS,
+        BALLISTA_JOB_PRIORITY,
+    };
+    use ballista_core::utils::default_session_builder;
+    use datafusion::datasource::listing_table_factory::ListingTableFactory;
+    use datafusion::datasource::object_store::ObjectStoreUrl;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_shared_tables() -> Result<()> {
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )));
+        let config = BallistaConfig::new()?;
+        let session = session_manager.create_session(&config).await?;
+        let existing = session_manager.create_session(&config).await?;
+
+        let sql = "CREATE EXTERNAL TABLE region (r_regionkey INT, r_name VARCHAR, \
+                   r_comment VARCHAR) STORED AS CSV DELIMITER '|' \
+                   LOCATION 'testdata/region/region.tbl'";
+        let plan = session.state().create_logical_plan(sql).await?;
+        assert!(
+            session_manager
+                .register_table(&session, plan.clone())
+                .await?
+        );
+        assert!(session.table_exist("region")?);
+        assert!(session_manager
+            .register_table(&session, plan)
+            .await
+            .is_err());
+        let plan = session
+            .state()
+            .create_logical_plan(&sql.replace("TABLE", "TABLE IF NOT EXISTS"))
+            .await?;
+        assert!(!session_manager.register_table(&session, plan).await?);
+        assert_eq!(session_manager.tables().await?.len(), 1);
+
+        // Sessions created before and after the table was registered both have it
+        let existing = session_manager.get_session(&existing.session_id()).await?;
+        let created = session_manager.create_session(&config).await?;
+        for ctx in [existing, created] {
+            let schema = ctx.table_provider("region").await?.schema();
+            assert_eq!(schema.fields().len(), 3);
+            assert_eq!(schema.field(1).name(), "r_name");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_table_factories() -> Result<()> {
+        let factory: Arc<dyn TableProviderFactory> = Arc::new(ListingTableFactory::new());
+        let session_manager = SessionManager::new(Arc::new(
+            InMemoryJobState::new("", default_session_builder).with_table_factories(
+                HashMap::from([("deltatable".to_owned(), factory)]),
+            ),
+        ));
+        let session = session_manager
+            .create_session(&BallistaConfig::new()?)
+            .await?;
+        let table_factories = session.state().table_factories().clone();
+        // The factories are looked up by the upper case file type, next to the ones of
+        // DataFusion
+        assert!(table_factories.contains_key("DELTATABLE"));
+        assert!(table_factories.contains_key("PARQUET"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_objects() -> Result<()> {
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )));
+        let config = BallistaConfig::new()?;
+        let session = session_manager.create_session(&config).await?;
+        let other = session_manager.create_session(&config).await?;
+        let session_id = session.session_id();
+
+        let sql = "CREATE EXTERNAL TABLE region (r_regionkey INT, r_name VARCHAR, \
+                   r_comment VARCHAR) STORED AS CSV DELIMITER '|' \
+                   LOCATION 'testdata/region/region.tbl'";
+        let cmd = match session.state().create_logical_plan(sql).await? {
+            LogicalPlan::Ddl(DdlStatement::CreateExternalTable(cmd)) => cmd,
+            other => panic!("Expected a CREATE EXTERNAL TABLE plan but got {other:?}"),
+        };
+        session.sql(sql).await?;
+        session_manager.save_session_table(&session, cmd).await?;
+        session_manager
+            .save_prepared_statement(&session_id, &[1], "SELECT * FROM region")
+            .await?;
+
+        // A session replaced by new settings, such as on another scheduler, still has its
+        // table, which other sessions do not have
+        session_manager.update_session(&session_id, &config).await?;
+        let session = session_manager
+            .get_session_with_objects(&session_id)
+            .await?;
+        let schema = session.table_provider("region").await?.schema();
+        assert_eq!(schema.field(1).name(), "r_name");
+        let other = session_manager
+            .get_session_with_objects(&other.session_id())
+            .await?;
+        assert!(!other.table_exist("region")?);
+
+        assert_eq!(
+            session_manager
+                .prepared_statement(&session_id, &[1])
+                .await?
+                .as_deref(),
+            Some("SELECT * FROM region")
+        );
+        session_manager
+            .remove_prepared_statement(&session_id, &[1])
+            .await?;
+        assert_eq!(
+            session_manager
+                .prepared_statement(&session_id, &[1])
+                .await?,
+            None
+        );
+
+        assert!(
+            session_manager
+                .remove_session_table(&session_id, "region")
+                .await?
+        );
+        assert!(
+            !session_manager
+                .remove_session_table(&session_id, "region")
+                .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_tokens() -> Result<()> {
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )));
+        let config = BallistaConfig::new()?;
+        let session_id = session_manager.create_session(&config).await?.session_id();
+        let other_id = session_manager.create_session(&config).await?.session_id();
+
+        let token = session_manager.issue_token(&session_id).await?;
+        assert_eq!(
+            session_manager.token_session(&token).await?.as_deref(),
+            Some(session_id.as_str())
+        );
+        // Neither the ID of the session nor the secret of another session is a token
+        assert_eq!(session_manager.token_session(&session_id).await?, None);
+        let other_token = session_manager.issue_token(&other_id).await?;
+        let (_, other_secret) = other_token.rsplit_once('.').unwrap();
+        assert_eq!(
+            session_manager
+                .token_session(&format!("{session_id}.{other_secret}"))
+                .await?,
+            None
+        );
+        assert_eq!(
+            session_manager
+                .token_session(&format!("{other_id}.guess"))
+                .await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_drop_shared_table() -> Result<()> {
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )));
+        let config = BallistaConfig::new()?;
+        let session = session_manager.create_session(&config).await?;
+        let sql = "CREATE EXTERNAL TABLE region (r_regionkey INT, r_name VARCHAR, \
+                   r_comment VARCHAR) STORED AS CSV DELIMITER '|' \
+                   LOCATION 'testdata/region/region.tbl'";
+        let plan = session.state().create_logical_plan(sql).await?;
+        assert!(session_manager.register_table(&session, plan).await?);
+
+        assert!(session_manager.drop_table("region", false).await?);
+        assert!(session_manager.tables().await?.is_empty());
+        assert!(!session_manager.drop_table("region", true).await?);
+        assert!(session_manager.drop_table("region", false).await.is_err());
+        let session = session_manager.get_session(&session.session_id()).await?;
+        assert!(!session.table_exist("region")?);
+
+        // Dropped tables are kept for the retention window
+        assert!(session_manager.purge_dropped_tables(60).await?.is_empty());
+        session_manager.undrop_table("region").await?;
+        assert!(session_manager.undrop_table("region").await.is_err());
+        assert_eq!(session_manager.tables().await?.len(), 1);
+        let session = session_manager.get_session(&session.session_id()).await?;
+        assert!(session.table_exist("region")?);
+
+        assert!(session_manager.drop_table("region", false).await?);
+        assert_eq!(
+            session_manager.purge_dropped_tables(0).await?,
+            vec!["region".to_owned()]
+        );
+        assert!(session_manager.undrop_table("region").await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cluster_config() -> Result<()> {
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )));
+        let defaults = BallistaConfig::new()?;
+        let config = session_manager.cluster_config()?;
+        assert_eq!(
+            config.default_shuffle_partitions,
+            defaults.default_shuffle_partitions() as u64
+        );
+        assert_eq!(
+            config.default_batch_size,
+            defaults.default_batch_size() as u64
+        );
+        assert_eq!(config.file_formats, vec!["avro", "csv", "parquet"]);
+        assert!(config.table_factories.contains(&"PARQUET".to_owned()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_settings() -> Result<()> {
+        let default_settings = parse_session_settings(&format!(
+            "datafusion.catalog.information_schema=true, \
+             {BALLISTA_DEFAULT_SHUFFLE_PARTITIONS}=4, {BALLISTA_DEFAULT_BATCH_SIZE}=1024"
+        ))?;
+        assert_eq!(default_settings.len(), 3);
+        assert!(parse_session_settings("datafusion.catalog.information_schema").is_err());
+        assert!(parse_session_settings(&format!(
+            "{BALLISTA_DEFAULT_SHUFFLE_PARTITIONS}=many"
+        ))
+        .is_err());
+        assert!(parse_session_settings("datafusion.unknown=1").is_err());
+
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )))
+        .with_default_settings(default_settings);
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "8")
+            .build()?;
+
+        // the settings of the session override the default ones
+        let session = session_manager.create_session(&config).await?;
+        let session_config = session.copied_config();
+        assert!(session_config.information_schema());
+        assert_eq!(session_config.target_partitions(), 8);
+        assert_eq!(session_config.batch_size(), 1024);
+        session.sql("SHOW TABLES").await?;
+
+        let session = session_manager
+            .update_session(&session.session_id(), &BallistaConfig::new()?)
+            .await?;
+        assert_eq!(session.copied_config().target_partitions(), 4);
+
+        let cluster_config = session_manager.cluster_config()?;
+        assert_eq!(cluster_config.default_shuffle_partitions, 4);
+        assert_eq!(cluster_config.default_batch_size, 1024);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_stores() -> Result<()> {
+        let retry = ObjectStoreRetryConfig::default();
+        assert!(DefaultObjectStores::try_new("warehouse", retry.clone()).is_err());
+        assert!(
+            DefaultObjectStores::try_new("s3://warehouse?unknown=1", retry.clone())
+                .is_err()
+        );
+
+        let object_stores = DefaultObjectStores::try_new(
+            "s3://warehouse?aws_endpoint=http://localhost:9000&aws_region=eu-west-1",
+            retry,
+        )?;
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )))
+        .with_object_stores(object_stores);
+
+        // every session uses the configured object store rather than detecting one
+        let url = ObjectStoreUrl::parse("s3://warehouse")?;
+        let mut stores = vec![];
+        for _ in 0..2 {
+            let session = session_manager
+                .create_session(&BallistaConfig::new()?)
+                .await?;
+            stores.push(session.runtime_env().object_store(&url)?);
+        }
+        assert_eq!(
+            Arc::as_ptr(&stores[0]) as *const u8,
+            Arc::as_ptr(&stores[1]) as *const u8
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_context() -> Result<()> {
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )));
+        let config = BallistaConfig::builder()
+            .set(BALLISTA_DEFAULT_SHUFFLE_PARTITIONS, "4")
+            .set(BALLISTA_JOB_PRIORITY, "2")
+            .build()?;
+        let session = session_manager.create_session(&config).await?;
+
+        let overrides: HashMap<String, String> = [
+            (
+                BALLISTA_DEFAULT_SHUFFLE_PARTITIONS.to_owned(),
+                "16".to_owned(),
+            ),
+            (BALLISTA_DEFAULT_BATCH_SIZE.to_owned(), "1024".to_owned()),
+        ]
+        .into_iter()
+        .collect();
+        let job_ctx = create_job_context(&session, overrides.clone())?;
+        assert_eq!(job_ctx.session_id(), session.session_id());
+        assert_eq!(job_settings(&job_ctx), overrides);
+        let job_config = session_ballista_config(&job_ctx).unwrap();
+        assert_eq!(job_config.default_shuffle_partitions(), 16);
+        assert_eq!(job_config.default_batch_size(), 1024);
+        assert_eq!(job_config.job_priority(), 2);
+        assert_eq!(job_ctx.copied_config().target_partitions(), 16);
+        assert_eq!(job_ctx.copied_config().batch_size(), 1024);
+
+        // The session keeps its own settings
+        assert!(job_settings(&session).is_empty());
+        assert_eq!(session.copied_config().target_partitions(), 4);
+        let session = session_manager.get_session(&session.session_id()).await?;
+        assert_eq!(session.copied_config().target_partitions(), 4);
+
+        // Overrides are validated like the settings of sessions
+        let invalid = [(BALLISTA_DEFAULT_BATCH_SIZE.to_owned(), "many".to_owned())]
+            .into_iter()
+            .collect();
+        assert!(create_job_context(&session, invalid).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_removed_sessions() -> Result<()> {
+        let session_manager = SessionManager::new(Arc::new(InMemoryJobState::new(
+            "",
+            default_session_builder,
+        )));
+        let mut removed_sessions = session_manager.removed_sessions();
+        let config = BallistaConfig::new()?;
+        let session = session_manager.create_session(&config).await?;
+        let other_session = session_manager.create_session(&config).await?;
+
+        assert!(
+            session_manager
+                .remove_session(&session.session_id())
+                .await?
+        );
+        assert!(
+            !session_manager
+                .remove_session(&session.session_id())
+                .await?
+        );
+        assert!(
+            session_manager
+                .remove_session(&other_session.session_id())
+                .await?
+        );
+        assert_eq!(removed_sessions.next().await, Some(session.session_id()));
+        assert_eq!(
+            removed_sessions.next().await,
+            Some(other_session.session_id())
+        );
+        Ok(())
+    }
 }
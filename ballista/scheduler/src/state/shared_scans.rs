@@ -0,0 +1,250 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Sharing of the identical scan stages of the jobs running at the same time
+
+use std::collections::{HashMap, HashSet};
+
+use parking_lot::Mutex;
+
+/// A stage of a job, as (job ID, stage ID)
+pub type JobStage = (String, usize);
+
+#[derive(Debug, Default)]
+struct SharedScansState {
+    /// Stage running each scan for the jobs sharing it, by the key of the scan
+    producers: HashMap<String, JobStage>,
+    /// Stages of other jobs waiting for the output of each producer stage
+    waiting: HashMap<JobStage, Vec<JobStage>>,
+    /// Jobs reading the output of the stages of each producer job
+    readers: HashMap<String, HashSet<String>>,
+    /// Data clean up interval of the completed producer jobs whose output is still read, none
+    /// to clean up their data right away
+    deferred_clean_ups: HashMap<String, Option<u64>>,
+}
+
+/// Outcome of the completion of a job for the jobs it shared scans with
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CompletedSharedScans {
+    /// Stages of other jobs which waited for the output of the job and run their own tasks
+    /// instead
+    pub released: Vec<JobStage>,
+    /// Producer jobs whose data is no longer read, with their data clean up interval
+    pub clean_ups: Vec<(String, Option<u64>)>,
+}
+
+/// Scan stages of the in-flight jobs enabling `ballista.job.shared_scans`, keyed by the
+/// description of the scan and its output partitioning.
+///
+/// The first job submitted with a scan runs it as usual. The identical stages of the jobs
+/// submitted while it runs wait for it to complete and then read its output, instead of
+/// scanning the same files again. Once a producer job completes, the data of its stages is
+/// kept until the jobs reading it complete too.
+#[derive(Debug, Default)]
+pub struct SharedScans {
+    state: Mutex<SharedScansState>,
+}
+
+impl SharedScans {
+    /// Register a scan stage of a job. Returns the stage of another job running the same
+    /// scan, if any, otherwise the stage becomes the producer of the scan.
+    pub fn register(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+        key: String,
+    ) -> Option<JobStage> {
+        let mut state = self.state.lock();
+        match state.producers.get(&key) {
+            Some(producer) if producer.0 != job_id => Some(producer.clone()),
+            _ => {
+                state.producers.insert(key, (job_id.to_owned(), stage_id));
+                None
+            }
+        }
+    }
+
+    /// Make a stage wait for the output of the producer stage. Returns false if the stage is
+    /// no longer the producer of its scan, as its job completed.
+    pub fn wait(&self, producer: JobStage, consumer: JobStage) -> bool {
+        let mut state = self.state.lock();
+        if !state.producers.values().any(|stage| *stage == producer) {
+            return false;
+        }
+        state
+            .readers
+            .entry(producer.0.clone())
+            .or_default()
+            .insert(consumer.0.clone());
+        state.waiting.entry(producer).or_default().push(consumer);
+        true
+    }
+
+    /// Record that a job reads the output of a producer job
+    pub fn add_reader(&self, producer_job_id: &str, job_id: &str) {
+        self.state
+            .lock()
+            .readers
+            .entry(producer_job_id.to_owned())
+            .or_default()
+            .insert(job_id.to_owned());
+    }
+
+    /// Stages of a job whose output other stages wait for
+    pub fn waited_stages(&self, job_id: &str) -> Vec<usize> {
+        self.state
+            .lock()
+            .waiting
+            .keys()
+            .filter(|(producer_job_id, _)| producer_job_id == job_id)
+            .map(|(_, stage_id)| *stage_id)
+            .collect()
+    }
+
+    /// Take the stages waiting for the output of a producer stage which completed
+    pub fn take_waiting(&self, job_id: &str, stage_id: usize) -> Vec<JobStage> {
+        self.state
+            .lock()
+            .waiting
+            .remove(&(job_id.to_owned(), stage_id))
+            .unwrap_or_default()
+    }
+
+    /// Keep the data of a completed producer job while other jobs read it. Returns true if
+    /// the job data is cleaned up with `clean_up_interval` once they complete.
+    pub fn defer_clean_up(&self, job_id: &str, clean_up_interval: Option<u64>) -> bool {
+        let mut state = self.state.lock();
+        if state
+            .readers
+            .get(job_id)
+            .map(|readers| readers.is_empty())
+            .unwrap_or(true)
+        {
+            state.readers.remove(job_id);
+            false
+        } else {
+            state
+                .deferred_clean_ups
+                .insert(job_id.to_owned(), clean_up_interval);
+            true
+        }
+    }
+
+    /// Forget the scans of a completed job. The stages waiting for its output are released
+    /// and the producer jobs whose data it was the last to read can be cleaned up.
+    pub fn complete_job(&self, job_id: &str) -> CompletedSharedScans {
+        let mut state = self.state.lock();
+        let state = &mut *state;
+        state
+            .producers
+            .retain(|_, (producer, _)| producer != job_id);
+        // Its waiting stages won't read the output of their producers
+        for consumers in state.waiting.values_mut() {
+            consumers.retain(|(consumer, _)| consumer != job_id);
+        }
+
+        let mut released = vec![];
+        state.waiting.retain(|(producer, _), consumers| {
+            if producer == job_id {
+                released.append(consumers);
+                false
+            } else {
+                true
+            }
+        });
+        if let Some(readers) = state.readers.get_mut(job_id) {
+            for (consumer, _) in &released {
+                readers.remove(consumer);
+            }
+        }
+
+        let mut clean_ups = vec![];
+        for (producer, readers) in state.readers.iter_mut() {
+            if readers.remove(job_id) && readers.is_empty() {
+                if let Some(interval) = state.deferred_clean_ups.remove(producer) {
+                    clean_ups.push((producer.clone(), interval));
+                }
+            }
+        }
+        state.readers.retain(|producer, readers| {
+            !readers.is_empty() || state.deferred_clean_ups.contains_key(producer)
+        });
+
+        CompletedSharedScans {
+            released,
+            clean_ups,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_scans() {
+        let scans = SharedScans::default();
+        assert_eq!(scans.register("job1", 1, "scan".to_owned()), None);
+        assert_eq!(scans.register("job1", 2, "other".to_owned()), None);
+        let producer = scans.register("job2", 3, "scan".to_owned());
+        assert_eq!(producer, Some(("job1".to_owned(), 1)));
+        assert!(scans.wait(producer.unwrap(), ("job2".to_owned(), 3)));
+        let producer = scans.register("job3", 1, "scan".to_owned());
+        assert!(scans.wait(producer.unwrap(), ("job3".to_owned(), 1)));
+
+        assert_eq!(scans.waited_stages("job1"), vec![1]);
+        assert_eq!(
+            scans.take_waiting("job1", 1),
+            vec![("job2".to_owned(), 3), ("job3".to_owned(), 1)]
+        );
+        assert!(scans.waited_stages("job1").is_empty());
+
+        // The data of job1 is kept while job2 and job3 read it
+        assert!(scans.defer_clean_up("job1", Some(60)));
+        assert_eq!(scans.complete_job("job1"), CompletedSharedScans::default());
+        // job1 no longer runs the scan
+        assert_eq!(scans.register("job4", 2, "scan".to_owned()), None);
+        assert_eq!(scans.complete_job("job2"), CompletedSharedScans::default());
+        assert_eq!(
+            scans.complete_job("job3"),
+            CompletedSharedScans {
+                released: vec![],
+                clean_ups: vec![("job1".to_owned(), Some(60))],
+            }
+        );
+    }
+
+    #[test]
+    fn test_release_waiting_stages() {
+        let scans = SharedScans::default();
+        assert_eq!(scans.register("job1", 1, "scan".to_owned()), None);
+        let producer = scans.register("job2", 1, "scan".to_owned()).unwrap();
+        assert!(scans.wait(producer.clone(), ("job2".to_owned(), 1)));
+
+        // job1 failed before its scan completed, job2 scans the files itself
+        assert_eq!(
+            scans.complete_job("job1"),
+            CompletedSharedScans {
+                released: vec![("job2".to_owned(), 1)],
+                clean_ups: vec![],
+            }
+        );
+        assert!(!scans.defer_clean_up("job1", None));
+        assert!(!scans.wait(producer, ("job3".to_owned(), 1)));
+        assert_eq!(scans.complete_job("job2"), CompletedSharedScans::default());
+    }
+}
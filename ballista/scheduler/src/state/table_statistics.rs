@@ -0,0 +1,461 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+//! Statistics of the tables scanned by completed jobs, used to plan later queries over the
+//! same tables when DataFusion has no statistics of its own
+
+use std::any::Any;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::common::tree_node::{Transformed, TreeNode};
+use datafusion::config::ConfigOptions;
+use datafusion::error::Result;
+use datafusion::execution::context::TaskContext;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::expressions::PhysicalSortExpr;
+use datafusion::physical_plan::file_format::{
+    CsvExec, FileScanConfig, NdJsonExec, ParquetExec,
+};
+use datafusion::physical_plan::metrics::MetricsSet;
+use datafusion::physical_plan::{
+    DisplayFormatType, ExecutionPlan, Partitioning, SendableRecordBatchStream, Statistics,
+};
+use parking_lot::RwLock;
+
+/// Number of scanned tables whose statistics are remembered
+pub const DEFAULT_TABLE_STATISTICS_CACHE_SIZE: usize = 10000;
+
+/// Metrics of Parquet scans counting rows skipped by predicates. Scans which skipped rows do
+/// not output all the rows of their files.
+const PARQUET_PRUNING_METRICS: [&str; 3] = [
+    "row_groups_pruned",
+    "pushdown_rows_filtered",
+    "page_index_rows_filtered",
+];
+
+/// Statistics of the files of a table observed when they were scanned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableStatistics {
+    /// Number of rows in the files
+    pub num_rows: usize,
+    /// Size of the files in bytes
+    pub total_byte_size: usize,
+}
+
+#[derive(Debug, Default)]
+struct CachedTables {
+    tables: HashMap<u64, TableStatistics>,
+    /// Keys of the tables in the order they were first recorded, to evict the oldest
+    order: VecDeque<u64>,
+}
+
+/// Statistics of the tables scanned by completed jobs, including `EXPLAIN ANALYZE` queries.
+///
+/// A table is identified by the files a scan reads along with their sizes and modification
+/// times, so scans of different partitions of a table are tracked separately and the
+/// statistics of rewritten files are not used.
+#[derive(Debug)]
+pub struct TableStatisticsCache {
+    capacity: usize,
+    cached: RwLock<CachedTables>,
+}
+
+impl Default for TableStatisticsCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_STATISTICS_CACHE_SIZE)
+    }
+}
+
+impl TableStatisticsCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            cached: RwLock::new(CachedTables::default()),
+        }
+    }
+
+    /// Number of tables with cached statistics
+    pub fn len(&self) -> usize {
+        self.cached.read().tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Statistics of the table read by a file scan, if it was scanned before
+    pub fn get(&self, scan: &dyn ExecutionPlan) -> Option<TableStatistics> {
+        let key = scan_key(file_scan_config(scan)?);
+        self.cached.read().tables.get(&key).copied()
+    }
+
+    /// Record the statistics of the file scans in the plan of a successful stage from the
+    /// stage metrics, collected in the order of
+    /// [collect_plan_metrics](ballista_core::utils::collect_plan_metrics). Only scans which
+    /// read all the rows of their files are recorded.
+    pub fn record_stage(&self, plan: &dyn ExecutionPlan, stage_metrics: &[MetricsSet]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut metrics = stage_metrics.iter();
+        self.record_plan(plan, &mut metrics);
+    }
+
+    fn record_plan<'a>(
+        &self,
+        plan: &dyn ExecutionPlan,
+        metrics: &mut impl Iterator<Item = &'a MetricsSet>,
+    ) {
+        if plan.metrics().is_some() {
+            match metrics.next() {
+                Some(plan_metrics) => self.record_scan(plan, plan_metrics),
+                // The stage metrics do not match the plan
+                None => return,
+            }
+        }
+        for child in plan.children() {
+            self.record_plan(child.as_ref(), metrics);
+        }
+    }
+
+    fn record_scan(&self, plan: &dyn ExecutionPlan, metrics: &MetricsSet) {
+        let config = match file_scan_config(plan) {
+            Some(config) if config.limit.is_none() => config,
+            _ => return,
+        };
+        let num_rows = match metrics.output_rows() {
+            Some(num_rows) => num_rows,
+            None => return,
+        };
+        let pruned = PARQUET_PRUNING_METRICS
+            .iter()
+            .any(|name| metrics.sum_by_name(name).map(|v| v.as_usize()).unwrap_or(0) > 0);
+        if pruned {
+            return;
+        }
+        let total_byte_size = config
+            .file_groups
+            .iter()
+            .flatten()
+            .map(|file| file.object_meta.size)
+            .sum();
+        self.insert(
+            scan_key(config),
+            TableStatistics {
+                num_rows,
+                total_byte_size,
+            },
+        );
+    }
+
+    fn insert(&self, key: u64, statistics: TableStatistics) {
+        let mut cached = self.cached.write();
+        if cached.tables.insert(key, statistics).is_none() {
+            cached.order.push_back(key);
+            while cached.order.len() > self.capacity {
+                if let Some(oldest) = cached.order.pop_front() {
+                    cached.tables.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn file_scan_config(plan: &dyn ExecutionPlan) -> Option<&FileScanConfig> {
+    let any = plan.as_any();
+    if let Some(exec) = any.downcast_ref::<ParquetExec>() {
+        Some(exec.base_config())
+    } else if let Some(exec) = any.downcast_ref::<CsvExec>() {
+        Some(exec.base_config())
+    } else {
+        any.downcast_ref::<NdJsonExec>()
+            .map(|exec| exec.base_config())
+    }
+}
+
+/// Key of the table read by a file scan, independent of how its files are grouped into
+/// partitions
+pub(crate) fn scan_key(config: &FileScanConfig) -> u64 {
+    let mut files: Vec<_> = config
+        .file_groups
+        .iter()
+        .flatten()
+        .map(|file| {
+            (
+                file.object_meta.location.as_ref(),
+                file.object_meta.size,
+                file.object_meta.last_modified,
+            )
+        })
+        .collect();
+    files.sort();
+
+    let mut hasher = DefaultHasher::new();
+    config.object_store_url.as_str().hash(&mut hasher);
+    files.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Physical optimizer rule which provides the cached statistics of the tables of file scans
+/// without statistics of their own to the rules following it, such as the join selection
+/// choosing the build side of joins and whether to broadcast it. Has to be paired with
+/// [RemoveTableStatistics] as the last rule.
+pub struct ApplyTableStatistics {
+    cache: Arc<TableStatisticsCache>,
+}
+
+impl ApplyTableStatistics {
+    pub fn new(cache: Arc<TableStatisticsCache>) -> Self {
+        Self { cache }
+    }
+}
+
+impl PhysicalOptimizerRule for ApplyTableStatistics {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        plan.transform_up(&|plan| {
+            if plan.statistics().num_rows.is_some() {
+                return Ok(Transformed::No(plan));
+            }
+            match self.cache.get(plan.as_ref()) {
+                Some(statistics) => Ok(Transformed::Yes(Arc::new(
+                    TableStatisticsExec::new(plan, statistics),
+                ))),
+                None => Ok(Transformed::No(plan)),
+            }
+        })
+    }
+
+    fn name(&self) -> &str {
+        "apply_table_statistics"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+/// Physical optimizer rule removing the [TableStatisticsExec]s added by
+/// [ApplyTableStatistics] once the plan is optimized
+#[derive(Default)]
+pub struct RemoveTableStatistics {}
+
+impl PhysicalOptimizerRule for RemoveTableStatistics {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        plan.transform_up(&|plan| match plan
+            .as_any()
+            .downcast_ref::<TableStatisticsExec>()
+        {
+            Some(exec) => Ok(Transformed::Yes(exec.input.clone())),
+            None => Ok(Transformed::No(plan)),
+        })
+    }
+
+    fn name(&self) -> &str {
+        "remove_table_statistics"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+/// Reports the cached statistics of the table of the file scan it wraps while the plan is
+/// optimized. It is removed from the plan before the plan is executed.
+#[derive(Debug)]
+pub struct TableStatisticsExec {
+    input: Arc<dyn ExecutionPlan>,
+    statistics: TableStatistics,
+}
+
+impl TableStatisticsExec {
+    pub fn new(input: Arc<dyn ExecutionPlan>, statistics: TableStatistics) -> Self {
+        Self { input, statistics }
+    }
+}
+
+impl ExecutionPlan for TableStatisticsExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.input.schema()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        self.input.output_partitioning()
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        self.input.output_ordering()
+    }
+
+    fn maintains_input_order(&self) -> Vec<bool> {
+        vec![true]
+    }
+
+    fn benefits_from_input_partitioning(&self) -> bool {
+        // Keep other rules from changing the plan between the scan and this node
+        false
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(TableStatisticsExec::new(
+            children[0].clone(),
+            self.statistics,
+        )))
+    }
+
+    fn execute(
+        &self,
+        partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        self.input.execute(partition, context)
+    }
+
+    fn fmt_as(
+        &self,
+        t: DisplayFormatType,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match t {
+            DisplayFormatType::Default => {
+                write!(
+                    f,
+                    "TableStatisticsExec: rows={}, bytes={}",
+                    self.statistics.num_rows, self.statistics.total_byte_size
+                )
+            }
+        }
+    }
+
+    fn statistics(&self) -> Statistics {
+        // The files may have changed without changing their size and modification time, so
+        // the statistics are not exact and are never used to answer queries
+        Statistics {
+            num_rows: Some(self.statistics.num_rows),
+            total_byte_size: Some(self.statistics.total_byte_size),
+            column_statistics: None,
+            is_exact: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::datafusion_test_context;
+    use ballista_core::utils::collect_plan_metrics;
+    use datafusion::physical_plan::collect;
+
+    async fn scan_plan(
+        ctx: &datafusion::prelude::SessionContext,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        ctx.sql("SELECT * FROM nation")
+            .await?
+            .create_physical_plan()
+            .await
+    }
+
+    fn find_scan(plan: &Arc<dyn ExecutionPlan>) -> Arc<dyn ExecutionPlan> {
+        let mut plan = plan.clone();
+        while !plan.children().is_empty() {
+            plan = plan.children()[0].clone();
+        }
+        plan
+    }
+
+    #[tokio::test]
+    async fn test_record_and_apply_table_statistics() -> Result<()> {
+        let ctx = datafusion_test_context("testdata").await?;
+        let cache = Arc::new(TableStatisticsCache::default());
+
+        let plan = scan_plan(&ctx).await?;
+        assert!(cache.get(find_scan(&plan).as_ref()).is_none());
+        let num_rows: usize = collect(plan.clone(), ctx.task_ctx())
+            .await?
+            .iter()
+            .map(|batch| batch.num_rows())
+            .sum();
+
+        cache.record_stage(plan.as_ref(), &collect_plan_metrics(plan.as_ref()));
+        assert_eq!(cache.len(), 1);
+
+        // A new plan over the same files gets the statistics of the previous scan
+        let plan = scan_plan(&ctx).await?;
+        let statistics = cache.get(find_scan(&plan).as_ref()).unwrap();
+        assert_eq!(statistics.num_rows, num_rows);
+        assert!(statistics.total_byte_size > 0);
+
+        let config = ConfigOptions::default();
+        let optimized = ApplyTableStatistics::new(cache).optimize(plan, &config)?;
+        assert_eq!(optimized.statistics().num_rows, Some(num_rows));
+        assert!(!optimized.statistics().is_exact);
+        let wrapped = find_scan(&optimized);
+        assert!(wrapped.as_any().downcast_ref::<CsvExec>().is_some());
+
+        let removed = RemoveTableStatistics::default().optimize(optimized, &config)?;
+        let mut plan = removed.clone();
+        loop {
+            assert!(plan
+                .as_any()
+                .downcast_ref::<TableStatisticsExec>()
+                .is_none());
+            match plan.children().first() {
+                Some(child) => plan = child.clone(),
+                None => break,
+            }
+        }
+        assert_eq!(removed.statistics().num_rows, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_capacity() {
+        let cache = TableStatisticsCache::new(2);
+        let statistics = TableStatistics {
+            num_rows: 1,
+            total_byte_size: 1,
+        };
+        cache.insert(1, statistics);
+        cache.insert(2, statistics);
+        cache.insert(3, statistics);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.cached.read().tables.contains_key(&1));
+    }
+}
@@ -15,23 +15,37 @@
 // specific language governing permissions and limitations
 // under the License.
 
+use crate::clock::{system_clock, Clock};
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 
 use crate::state::execution_graph::{
     ExecutionGraph, ExecutionStage, RunningTaskInfo, TaskDescription,
 };
 use crate::state::executor_manager::{ExecutorManager, ExecutorReservation};
+use crate::state::job_actor::JobActor;
+use crate::state::query_cost::{
+    plan_fingerprint, scanned_bytes, QueryCostEstimate, QueryCostHistory,
+};
+use crate::state::query_quarantine::QueryQuarantine;
+use crate::state::result_cache::ResultCache;
+use crate::state::session_manager::{job_ballista_config, session_ballista_config};
+use crate::state::shared_scans::SharedScans;
+use crate::state::table_statistics::TableStatisticsCache;
 
+use ballista_core::config::{BallistaConfig, BALLISTA_JOB_OWNER};
 use ballista_core::error::BallistaError;
 use ballista_core::error::Result;
+use ballista_core::execution_plans::{ExportConfig, ResultExport};
 
 use crate::cluster::JobState;
+use crate::planner::DistributedPlanner;
 use ballista_core::serde::protobuf::{
-    self, JobStatus, MultiTaskDefinition, TaskDefinition, TaskId, TaskStatus,
+    self, HeldJob, JobStatus, KeyValuePair, MultiTaskDefinition, PartitionLocation,
+    RejectedTask, TaskAttempt, TaskDefinition, TaskId, TaskStatus,
 };
-use ballista_core::serde::scheduler::ExecutorMetadata;
+use ballista_core::serde::scheduler::{ExecutorMetadata, TaskResources};
 use ballista_core::serde::BallistaCodec;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use datafusion::physical_plan::ExecutionPlan;
 
 use datafusion_proto::logical_plan::AsLogicalPlan;
@@ -39,12 +53,10 @@ use datafusion_proto::physical_plan::AsExecutionPlan;
 use log::{debug, error, info, warn};
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use std::cmp::Reverse;
 use std::collections::{HashMap, HashSet};
-use std::ops::Deref;
 use std::sync::Arc;
 use std::time::Duration;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::RwLock;
 
 use tracing::trace;
 
@@ -55,15 +67,22 @@ type ActiveJobCache = Arc<DashMap<String, JobInfoCache>>;
 pub const TASK_MAX_FAILURES: usize = 4;
 /// Default max failure attempts for stage level retry
 pub const STAGE_MAX_FAILURES: usize = 4;
+/// Max length of the job IDs supplied by clients
+pub const MAX_JOB_ID_LEN: usize = 64;
+/// Time the task resources of a job whose state is never cleaned up are kept once it is no
+/// longer active, for the statuses of its last tasks to release them
+const FINISHED_JOB_RESOURCES_RETENTION: Duration = Duration::from_secs(600);
 
 #[async_trait::async_trait]
 pub trait TaskLauncher: Send + Sync + 'static {
+    /// Launch tasks on an executor in a single request. Returns the tasks which the executor
+    /// did not accept, the other tasks were launched
     async fn launch_tasks(
         &self,
         executor: &ExecutorMetadata,
         tasks: Vec<MultiTaskDefinition>,
         executor_manager: &ExecutorManager,
-    ) -> Result<()>;
+    ) -> Result<Vec<RejectedTask>>;
 }
 
 struct DefaultTaskLauncher {
@@ -83,7 +102,7 @@ impl TaskLauncher for DefaultTaskLauncher {
         executor: &ExecutorMetadata,
         tasks: Vec<MultiTaskDefinition>,
         executor_manager: &ExecutorManager,
-    ) -> Result<()> {
+    ) -> Result<Vec<RejectedTask>> {
         if log::max_level() >= log::Level::Info {
             let tasks_ids: Vec<String> = tasks
                 .iter()
@@ -102,7 +121,7 @@ impl TaskLauncher for DefaultTaskLauncher {
             );
         }
         let mut client = executor_manager.get_client(&executor.id).await?;
-        client
+        let result = client
             .launch_multi_task(protobuf::LaunchMultiTaskParams {
                 multi_tasks: tasks,
                 scheduler_id: self.scheduler_id.clone(),
@@ -114,7 +133,7 @@ impl TaskLauncher for DefaultTaskLauncher {
                     executor.id, e
                 ))
             })?;
-        Ok(())
+        Ok(result.into_inner().rejected_tasks)
     }
 }
 
@@ -126,21 +145,87 @@ pub struct TaskManager<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan>
     // Cache for active jobs curated by this scheduler
     active_job_cache: ActiveJobCache,
     launcher: Arc<dyn TaskLauncher>,
+    // Maximum number of columns for which hash shuffles use the row codec
+    row_codec_max_columns: usize,
+    // Bytes of rows buffered per output partition by hash shuffle writes
+    shuffle_write_buffer_size: usize,
+    // Record the CRC32 checksum of shuffle files
+    shuffle_checksums: bool,
+    // Write the shuffle files of schemas with dictionary encoded columns as IPC streams
+    shuffle_dictionary_sharing: bool,
+    // Base resources required by every task
+    task_resources: TaskResources,
+    // Profiles and local root of the exports of results, to delete the expired ones
+    export_config: Arc<ExportConfig>,
+    // Costs of recently completed jobs
+    cost_history: Arc<QueryCostHistory>,
+    // Statistics of the tables scanned by completed jobs
+    table_statistics: Arc<TableStatisticsCache>,
+    // Results of recently completed jobs served to identical queries
+    result_cache: Arc<ResultCache>,
+    // Failures of the recent jobs of SQL queries, to quarantine the repeatedly failing ones
+    query_quarantine: Arc<QueryQuarantine>,
+    // Scan stages shared by the in-flight jobs enabling it
+    shared_scans: Arc<SharedScans>,
+    // Executors which restarted under a new ID, by their previous ID, with the shuffle files
+    // they recovered
+    restarted_executors: Arc<DashMap<String, (ExecutorMetadata, HashSet<String>)>>,
+    // Resources of the tasks of each stage of the jobs no longer active, kept until the job
+    // is cleaned up so that the statuses of their tasks reported afterwards release the
+    // resources the tasks were reserved with
+    finished_job_resources: Arc<DashMap<String, HashMap<usize, TaskResources>>>,
+    // Alive executors which ran tasks of the jobs recovered after a restart of this
+    // scheduler, until the tasks they still run are reconciled with the jobs
+    unreconciled_executors: Arc<DashSet<String>>,
+    // Clock of the launch times of tasks and of the delayed expiry and clean up of jobs
+    clock: Arc<dyn Clock>,
 }
 
 #[derive(Clone)]
 struct JobInfoCache {
-    // Cache for active execution graphs curated by this scheduler
-    execution_graph: Arc<RwLock<ExecutionGraph>>,
-    // Cache for encoded execution stage plan to avoid duplicated encoding for multiple tasks
-    encoded_stage_plans: HashMap<usize, Vec<u8>>,
+    // Actor owning the execution graph of the job curated by this scheduler
+    job: JobActor,
+    // Cache for encoded execution stage plan to avoid duplicated encoding for multiple tasks,
+    // along with the encoded plan as the plans of pipelined stages change with their inputs
+    encoded_stage_plans: HashMap<usize, (Arc<dyn ExecutionPlan>, Vec<u8>)>,
+    // Maximum number of task slots the jobs of the job's session may use, 0 means unlimited
+    session_max_task_slots: usize,
+    // Priority of the job, jobs with a higher priority are scheduled first
+    job_priority: usize,
+    // Pool of executors the tasks of the job run on, empty for the default pool
+    job_pool: String,
+    // DataFusion settings of the job applied to the contexts of its tasks by executors
+    task_props: Vec<KeyValuePair>,
+    // User owning the job, sent along its tasks so that executors serve their logs to the
+    // owner only. Empty for jobs without an owner.
+    job_owner: String,
+    // Fingerprint of the job's physical plan and the bytes it scans, to record its cost.
+    // Unknown for jobs recovered after a restart of the scheduler.
+    plan_fingerprint: Option<u64>,
+    scanned_bytes: Option<u64>,
 }
 
 impl JobInfoCache {
-    fn new(graph: ExecutionGraph) -> Self {
+    fn new(
+        job: JobActor,
+        session_max_task_slots: usize,
+        job_priority: usize,
+        job_pool: String,
+        task_props: Vec<KeyValuePair>,
+        job_owner: String,
+        plan_fingerprint: Option<u64>,
+        scanned_bytes: Option<u64>,
+    ) -> Self {
         Self {
-            execution_graph: Arc::new(RwLock::new(graph)),
+            job,
             encoded_stage_plans: HashMap::new(),
+            session_max_task_slots,
+            job_priority,
+            job_pool,
+            task_props,
+            job_owner,
+            plan_fingerprint,
+            scanned_bytes,
         }
     }
 }
@@ -148,8 +233,14 @@ impl JobInfoCache {
 #[derive(Clone)]
 pub struct UpdatedStages {
     pub resolved_stages: HashSet<usize>,
+    /// Running stages some of whose tasks became available, as their inputs were added or
+    /// as the partitions they wrote were lost
+    pub pipelined_stages: HashSet<usize>,
     pub successful_stages: HashSet<usize>,
     pub failed_stages: HashMap<usize, String>,
+    /// Failed stages whose failure was caused by their plan rather than lost executors or
+    /// data, such as an execution error
+    pub query_failed_stages: HashSet<usize>,
     pub rollback_running_stages: HashMap<usize, HashSet<String>>,
     pub resubmit_successful_stages: HashSet<usize>,
 }
@@ -166,6 +257,21 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             scheduler_id: scheduler_id.clone(),
             active_job_cache: Arc::new(DashMap::new()),
             launcher: Arc::new(DefaultTaskLauncher::new(scheduler_id)),
+            row_codec_max_columns: 0,
+            shuffle_write_buffer_size: 0,
+            shuffle_checksums: false,
+            shuffle_dictionary_sharing: true,
+            task_resources: TaskResources::default(),
+            export_config: Arc::new(ExportConfig::default()),
+            cost_history: Arc::new(QueryCostHistory::default()),
+            table_statistics: Arc::new(TableStatisticsCache::default()),
+            result_cache: Arc::new(ResultCache::default()),
+            query_quarantine: Arc::new(QueryQuarantine::default()),
+            shared_scans: Arc::new(SharedScans::default()),
+            restarted_executors: Arc::new(DashMap::new()),
+            finished_job_resources: Arc::new(DashMap::new()),
+            unreconciled_executors: Arc::new(DashSet::new()),
+            clock: system_clock(),
         }
     }
 
@@ -182,9 +288,112 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             scheduler_id,
             active_job_cache: Arc::new(DashMap::new()),
             launcher,
+            row_codec_max_columns: 0,
+            shuffle_write_buffer_size: 0,
+            shuffle_checksums: false,
+            shuffle_dictionary_sharing: true,
+            task_resources: TaskResources::default(),
+            export_config: Arc::new(ExportConfig::default()),
+            cost_history: Arc::new(QueryCostHistory::default()),
+            table_statistics: Arc::new(TableStatisticsCache::default()),
+            result_cache: Arc::new(ResultCache::default()),
+            query_quarantine: Arc::new(QueryQuarantine::default()),
+            shared_scans: Arc::new(SharedScans::default()),
+            restarted_executors: Arc::new(DashMap::new()),
+            finished_job_resources: Arc::new(DashMap::new()),
+            unreconciled_executors: Arc::new(DashSet::new()),
+            clock: system_clock(),
         }
     }
 
+    /// Delete the expired exports of results with the credentials of the profiles of
+    /// `export_config`
+    pub fn with_export_config(mut self, export_config: ExportConfig) -> Self {
+        self.export_config = Arc::new(export_config);
+        self
+    }
+
+    /// Use the row codec for hash shuffles with at most `row_codec_max_columns` columns
+    pub fn with_row_codec_max_columns(mut self, row_codec_max_columns: usize) -> Self {
+        self.row_codec_max_columns = row_codec_max_columns;
+        self
+    }
+
+    /// Buffer `shuffle_write_buffer_size` bytes of rows per output partition in hash shuffle
+    /// writes
+    pub fn with_shuffle_write_buffer_size(
+        mut self,
+        shuffle_write_buffer_size: usize,
+    ) -> Self {
+        self.shuffle_write_buffer_size = shuffle_write_buffer_size;
+        self
+    }
+
+    /// Record the CRC32 checksum of shuffle files, verified by their readers
+    pub fn with_shuffle_checksums(mut self, shuffle_checksums: bool) -> Self {
+        self.shuffle_checksums = shuffle_checksums;
+        self
+    }
+
+    /// Write the shuffle files of schemas with dictionary encoded columns in the Arrow IPC
+    /// streaming format
+    pub fn with_shuffle_dictionary_sharing(
+        mut self,
+        shuffle_dictionary_sharing: bool,
+    ) -> Self {
+        self.shuffle_dictionary_sharing = shuffle_dictionary_sharing;
+        self
+    }
+
+    /// Base resources required by every task, scaled per stage by the planner
+    pub fn with_task_resources(mut self, task_resources: TaskResources) -> Self {
+        self.task_resources = task_resources;
+        self
+    }
+
+    /// Serve the results of jobs to identical queries for `ttl`, holding at most `max_bytes`
+    /// of results
+    pub fn with_result_cache(mut self, ttl: Duration, max_bytes: u64) -> Self {
+        self.result_cache = Arc::new(ResultCache::new(ttl, max_bytes));
+        self
+    }
+
+    /// Results of recently completed jobs
+    pub fn result_cache(&self) -> &ResultCache {
+        &self.result_cache
+    }
+
+    /// Reject the SQL queries whose jobs failed `max_failures` times within `window`
+    pub fn with_query_quarantine(
+        mut self,
+        max_failures: usize,
+        window: Duration,
+    ) -> Self {
+        self.query_quarantine = Arc::new(QueryQuarantine::new(max_failures, window));
+        self
+    }
+
+    /// Failures of the recent jobs of SQL queries
+    pub fn query_quarantine(&self) -> &QueryQuarantine {
+        &self.query_quarantine
+    }
+
+    /// Time the launches of tasks and delay the expiry and clean up of jobs on `clock`
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Scan stages shared by the in-flight jobs
+    pub fn shared_scans(&self) -> &SharedScans {
+        &self.shared_scans
+    }
+
+    /// Statistics of the tables scanned by completed jobs
+    pub fn table_statistics(&self) -> Arc<TableStatisticsCache> {
+        self.table_statistics.clone()
+    }
+
     /// Enqueue a job for scheduling
     pub async fn queue_job(
         &self,
@@ -206,6 +415,37 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         plan: Arc<dyn ExecutionPlan>,
         queued_at: u64,
     ) -> Result<()> {
+        self.submit_job_with_settings(
+            job_id,
+            job_name,
+            session_id,
+            plan,
+            queued_at,
+            HashMap::new(),
+        )
+        .await
+    }
+
+    /// Submit a job like [`submit_job`](Self::submit_job), with settings overriding the ones
+    /// of its session for this job only
+    pub async fn submit_job_with_settings(
+        &self,
+        job_id: &str,
+        job_name: &str,
+        session_id: &str,
+        plan: Arc<dyn ExecutionPlan>,
+        queued_at: u64,
+        job_settings: HashMap<String, String>,
+    ) -> Result<()> {
+        let fingerprint = plan_fingerprint(plan.as_ref());
+        let scanned_bytes = scanned_bytes(&plan);
+        let job_config = self.job_config(job_id, session_id, &job_settings).await;
+        let single_task = single_task(job_config.as_deref(), scanned_bytes);
+        if single_task {
+            info!(
+                "Planning job {job_id} scanning {scanned_bytes:?} bytes as a single task"
+            );
+        }
         let mut graph = ExecutionGraph::new(
             &self.scheduler_id,
             job_id,
@@ -213,60 +453,438 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             session_id,
             plan,
             queued_at,
-        )?;
+            DistributedPlanner::new()
+                .with_row_codec_max_columns(self.row_codec_max_columns)
+                .with_shuffle_write_buffer_size(self.shuffle_write_buffer_size)
+                .with_shuffle_checksums(self.shuffle_checksums)
+                .with_shuffle_dictionary_sharing(self.shuffle_dictionary_sharing)
+                .with_checkpoint_dir(checkpoint_dir(job_config.as_deref()))
+                .with_result_export(
+                    job_config.as_deref().and_then(ResultExport::from_config),
+                )
+                .with_single_task(single_task)
+                .with_task_resources(self.task_resources),
+        )?
+        .with_labels(
+            job_config
+                .as_ref()
+                .map(|config| config.job_labels())
+                .unwrap_or_default(),
+        )
+        .with_settings(job_settings)
+        .with_job_group(
+            job_config
+                .as_ref()
+                .map(|config| config.job_group())
+                .unwrap_or_default(),
+        )
+        .with_max_stage_task_failures(stage_max_failures(job_config.as_deref()))
+        .with_verify_shuffle_rows(verify_shuffle_rows(job_config.as_deref()))
+        .with_result_buffer(result_buffer(job_config.as_deref()))
+        .with_max_concurrent_stages(max_concurrent_stages(job_config.as_deref()))
+        .with_max_concurrent_tasks(max_concurrent_tasks(job_config.as_deref()))
+        .with_result_ttl_seconds(result_ttl_seconds(job_config.as_deref()));
+        if shared_scans(job_config.as_deref()) {
+            self.share_scans(job_id, &mut graph).await?;
+        }
         info!("Submitting execution graph: {:?}", graph);
 
         self.state.submit_job(job_id.to_string(), &graph).await?;
 
+        let (session_max_task_slots, job_priority) =
+            session_job_config(job_config.as_deref());
+
         graph.revive();
-        self.active_job_cache
-            .insert(job_id.to_owned(), JobInfoCache::new(graph));
+        let owner = job_owner(graph.settings());
+        self.active_job_cache.insert(
+            job_id.to_owned(),
+            JobInfoCache::new(
+                JobActor::spawn(graph, self.state.clone()),
+                session_max_task_slots,
+                job_priority,
+                job_pool(job_config.as_deref()),
+                job_task_props(job_config.as_deref()),
+                owner,
+                Some(fingerprint),
+                scanned_bytes,
+            ),
+        );
+
+        Ok(())
+    }
 
+    /// Share the scan stages of a job being submitted with the identical stages of the other
+    /// in-flight jobs. The stages whose identical stage completed adopt its output, the others
+    /// wait for it to complete.
+    async fn share_scans(&self, job_id: &str, graph: &mut ExecutionGraph) -> Result<()> {
+        for (stage_id, key) in graph.shared_scan_keys() {
+            let (producer_job_id, producer_stage_id) =
+                match self.shared_scans.register(job_id, stage_id, key) {
+                    Some(producer) => producer,
+                    None => continue,
+                };
+            let locations = self
+                .with_active_graph(&producer_job_id, move |graph| {
+                    graph.stage_output_locations(producer_stage_id)
+                })
+                .await;
+            match locations {
+                Some(Some(locations)) => {
+                    graph.adopt_shared_stage(stage_id, locations)?;
+                    self.shared_scans.add_reader(&producer_job_id, job_id);
+                }
+                Some(None) => {
+                    if self.shared_scans.wait(
+                        (producer_job_id.clone(), producer_stage_id),
+                        (job_id.to_owned(), stage_id),
+                    ) {
+                        info!(
+                            "Stage {job_id}/{stage_id} waits for the identical stage {producer_job_id}/{producer_stage_id}"
+                        );
+                        graph.share_stage(stage_id);
+                    }
+                }
+                // The producer job completed meanwhile, the stage runs its own tasks
+                None => {}
+            }
+        }
         Ok(())
     }
 
+    /// Let the stages waiting for the stages of a job which completed adopt their output
+    async fn adopt_shared_scans(
+        &self,
+        job_id: &str,
+    ) -> Result<Vec<QueryStageSchedulerEvent>> {
+        let mut events = vec![];
+        for stage_id in self.shared_scans.waited_stages(job_id) {
+            let locations = match self
+                .with_active_graph(job_id, move |graph| {
+                    graph.stage_output_locations(stage_id)
+                })
+                .await
+            {
+                Some(Some(locations)) => locations,
+                _ => continue,
+            };
+            for (consumer_job_id, consumer_stage_id) in
+                self.shared_scans.take_waiting(job_id, stage_id)
+            {
+                let consumer_locations = locations.clone();
+                match self
+                    .with_active_graph(&consumer_job_id, move |graph| {
+                        graph.adopt_shared_stage(consumer_stage_id, consumer_locations)
+                    })
+                    .await
+                {
+                    Some(consumer_events) => events.extend(consumer_events?),
+                    // The consumer job is still being submitted, it is released once this
+                    // job completes
+                    None => {
+                        self.shared_scans.wait(
+                            (job_id.to_owned(), stage_id),
+                            (consumer_job_id, consumer_stage_id),
+                        );
+                    }
+                }
+            }
+        }
+        Ok(events)
+    }
+
+    /// Forget the scans shared by a completed job. The stages of other jobs waiting for its
+    /// output run their own tasks. Returns the IDs of their jobs, together with the jobs whose
+    /// output was read by the completed job and whose data can now be cleaned up.
+    pub(crate) async fn complete_shared_scans(
+        &self,
+        job_id: &str,
+    ) -> (Vec<String>, Vec<(String, Option<u64>)>) {
+        let completed = self.shared_scans.complete_job(job_id);
+        let mut released_jobs = vec![];
+        for (consumer_job_id, stage_id) in completed.released {
+            info!(
+                "Stage {consumer_job_id}/{stage_id} runs its own tasks as job {job_id} completed"
+            );
+            if let Some(true) = self
+                .with_active_graph(&consumer_job_id, move |graph| {
+                    graph.release_shared_stage(stage_id)
+                })
+                .await
+            {
+                released_jobs.push(consumer_job_id);
+            }
+        }
+        (released_jobs, completed.clean_ups)
+    }
+
+    /// Ballista configuration of a job, the one of its session with the settings of the job
+    /// overriding the ones of the session. None if the job has no settings of its own and
+    /// its session is no longer known.
+    async fn job_config(
+        &self,
+        job_id: &str,
+        session_id: &str,
+        job_settings: &HashMap<String, String>,
+    ) -> Option<Arc<BallistaConfig>> {
+        let session_config = match self.state.get_session(session_id).await {
+            Ok(session) => session_ballista_config(&session),
+            Err(e) => {
+                warn!("Fail to get session {session_id} of job {job_id}: {e:?}");
+                None
+            }
+        };
+        if job_settings.is_empty() {
+            return session_config;
+        }
+        match job_ballista_config(session_config.as_deref(), job_settings) {
+            Ok(config) => Some(Arc::new(config)),
+            Err(e) => {
+                warn!("Ignoring the invalid settings of job {job_id}: {e:?}");
+                session_config
+            }
+        }
+    }
+
+    /// Resume the jobs this scheduler was running before it restarted. The tasks of the
+    /// jobs on executors which are not alive anymore are reset, together with the stages
+    /// whose output those executors held, so that they are scheduled again. The alive
+    /// executors running tasks of the jobs are left to [Self::reconcile_executor_tasks].
+    /// Returns the IDs of the recovered jobs.
+    pub(crate) async fn recover_active_jobs(
+        &self,
+        alive_executors: &HashSet<String>,
+    ) -> Result<Vec<String>> {
+        let mut recovered = vec![];
+        for job_id in self.state.get_jobs().await? {
+            if self.active_job_cache.contains_key(&job_id) {
+                continue;
+            }
+            let mut graph = match self.state.try_acquire_job(&job_id).await {
+                Ok(Some(graph)) => graph,
+                Ok(None) => continue,
+                Err(e) => {
+                    warn!("Fail to recover job {job_id}: {e:?}");
+                    continue;
+                }
+            };
+
+            for executor_id in graph.executor_ids() {
+                if !alive_executors.contains(&executor_id) {
+                    let (reset_stages, _) =
+                        graph.reset_stages_on_lost_executor(&executor_id)?;
+                    if !reset_stages.is_empty() {
+                        info!(
+                            "Reset stages {reset_stages:?} of recovered job {job_id} on lost executor {executor_id}"
+                        );
+                    }
+                }
+            }
+            graph.revive();
+            self.state.save_job(&job_id, &graph).await?;
+            for task in graph.running_tasks() {
+                self.unreconciled_executors.insert(task.executor_id);
+            }
+
+            let job_config = self
+                .job_config(&job_id, graph.session_id(), graph.settings())
+                .await;
+            let graph = graph
+                .with_max_stage_task_failures(stage_max_failures(job_config.as_deref()))
+                .with_verify_shuffle_rows(verify_shuffle_rows(job_config.as_deref()))
+                .with_result_buffer(result_buffer(job_config.as_deref()))
+                .with_max_concurrent_stages(max_concurrent_stages(job_config.as_deref()))
+                .with_max_concurrent_tasks(max_concurrent_tasks(job_config.as_deref()));
+            let (session_max_task_slots, job_priority) =
+                session_job_config(job_config.as_deref());
+            let owner = job_owner(graph.settings());
+            self.active_job_cache.insert(
+                job_id.clone(),
+                JobInfoCache::new(
+                    JobActor::spawn(graph, self.state.clone()),
+                    session_max_task_slots,
+                    job_priority,
+                    job_pool(job_config.as_deref()),
+                    job_task_props(job_config.as_deref()),
+                    owner,
+                    None,
+                    None,
+                ),
+            );
+            info!("Recovered job {job_id}");
+            recovered.push(job_id);
+        }
+
+        Ok(recovered)
+    }
+
+    /// The user owning a job, if the client submitting it was authenticated
+    pub async fn job_owner(&self, job_id: &str) -> Result<Option<String>> {
+        let settings = match self
+            .with_active_graph(job_id, |graph| graph.settings().clone())
+            .await
+        {
+            Some(settings) => settings,
+            None => match self.state.get_execution_graph(job_id).await? {
+                Some(graph) => graph.settings().clone(),
+                None => return Ok(None),
+            },
+        };
+        Ok(settings
+            .get(BALLISTA_JOB_OWNER)
+            .filter(|owner| !owner.is_empty())
+            .cloned())
+    }
+
+    /// Alive executors running tasks of the recovered jobs which are yet to be reconciled
+    pub(crate) fn unreconciled_executors(&self) -> Vec<String> {
+        self.unreconciled_executors
+            .iter()
+            .map(|executor_id| executor_id.clone())
+            .collect()
+    }
+
+    /// Re-queue the tasks of the recovered jobs which an executor ran before this scheduler
+    /// restarted but does not run anymore, given the tasks it is `running`, as their
+    /// statuses were lost. Each executor is reconciled once, other calls do nothing. Returns
+    /// the re-queued tasks.
+    pub(crate) async fn reconcile_executor_tasks(
+        &self,
+        executor_id: &str,
+        running: &[protobuf::RunningTaskInfo],
+    ) -> Vec<RunningTaskInfo> {
+        if self.unreconciled_executors.remove(executor_id).is_none() {
+            return vec![];
+        }
+
+        let mut requeued = vec![];
+        let job_ids: Vec<String> = self
+            .active_job_cache
+            .iter()
+            .map(|entry| entry.key().clone())
+            .collect();
+        for job_id in job_ids {
+            let running_task_ids: HashSet<usize> = running
+                .iter()
+                .filter(|task| task.job_id == job_id)
+                .map(|task| task.task_id as usize)
+                .collect();
+            let executor = executor_id.to_owned();
+            let lost_tasks = self
+                .with_active_graph(&job_id, move |graph| {
+                    graph.requeue_lost_tasks(&executor, &running_task_ids)
+                })
+                .await
+                .unwrap_or_default();
+            if !lost_tasks.is_empty() {
+                info!(
+                    "Re-queued {} tasks of recovered job {job_id} which executor {executor_id} does not run anymore",
+                    lost_tasks.len()
+                );
+            }
+            requeued.extend(lost_tasks);
+        }
+        requeued
+    }
+
+    /// GPUs used by each task of a stage of an active job, 0 if the job is not active
+    pub(crate) async fn stage_gpus(&self, job_id: &str, stage_id: usize) -> u32 {
+        self.with_active_graph(job_id, move |graph| graph.task_resources(stage_id).gpus)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// GPUs in use on an executor by the tasks it reports running, from the GPUs required by
+    /// the tasks of their stages
+    pub(crate) async fn gpus_in_use(&self, running: &[protobuf::RunningTaskInfo]) -> u32 {
+        let mut tasks: HashMap<(String, usize), u32> = HashMap::new();
+        for task in running {
+            *tasks
+                .entry((task.job_id.clone(), task.stage_id as usize))
+                .or_default() += 1;
+        }
+        let mut gpus = 0u32;
+        for ((job_id, stage_id), num_tasks) in tasks {
+            let stage_gpus = self.stage_gpus(&job_id, stage_id).await;
+            gpus = gpus.saturating_add(stage_gpus.saturating_mul(num_tasks));
+        }
+        gpus
+    }
+
     /// Get a list of active job ids
     pub async fn get_jobs(&self) -> Result<Vec<JobOverview>> {
-        let job_ids = self.state.get_jobs().await?;
+        // The state may only know the completed jobs, so the active ones are added
+        let mut job_ids = self.state.get_jobs().await?;
+        job_ids.extend(
+            self.active_job_cache
+                .iter()
+                .map(|entry| entry.key().clone()),
+        );
 
         let mut jobs = vec![];
         for job_id in &job_ids {
-            if let Some(cached) = self.get_active_execution_graph(job_id) {
-                let graph = cached.read().await;
-                jobs.push(graph.deref().into());
-            } else {
-                let graph = self.state
-                    .get_execution_graph(job_id)
-                    .await?
-                    .ok_or_else(|| BallistaError::Internal(format!("Error getting job overview, no execution graph found for job {job_id}")))?;
+            if let Some(overview) = self
+                .with_active_graph(job_id, |graph| JobOverview::from(&*graph))
+                .await
+            {
+                jobs.push(overview);
+            } else if let Some(graph) = self.state.get_execution_graph(job_id).await? {
                 jobs.push((&graph).into());
+            } else {
+                // Jobs which failed or were cancelled before being planned have no graph
+                let status = self.state.get_job_status(job_id).await?.ok_or_else(|| {
+                    BallistaError::Internal(format!(
+                        "Error getting job overview, no execution graph found for job {job_id}"
+                    ))
+                })?;
+                jobs.push(JobOverview::from(status));
             }
         }
         Ok(jobs)
     }
 
+    /// Get the overviews of the planned jobs of a group
+    pub async fn get_job_group(&self, group_id: &str) -> Result<Vec<JobOverview>> {
+        Ok(self
+            .get_jobs()
+            .await?
+            .into_iter()
+            .filter(|job| job.job_group == group_id)
+            .collect())
+    }
+
     /// Get the status of of a job. First look in the active cache.
     /// If no one found, then in the Active/Completed jobs, and then in Failed jobs
     pub async fn get_job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
-        if let Some(graph) = self.get_active_execution_graph(job_id) {
-            let guard = graph.read().await;
-
-            Ok(Some(guard.status()))
+        if let Some(status) = self.with_active_graph(job_id, |graph| graph.status()).await
+        {
+            Ok(Some(status))
         } else {
             self.state.get_job_status(job_id).await
         }
     }
 
+    /// Record the result partitions of an active job fetched by its client. Returns whether
+    /// tasks of the job held back until its results are consumed may now be scheduled.
+    pub async fn consume_job_results(
+        &self,
+        job_id: &str,
+        partitions: Vec<usize>,
+    ) -> Result<bool> {
+        self.with_active_graph(job_id, |graph| {
+            graph.consume_result_partitions(partitions)
+        })
+        .await
+        .unwrap_or(Ok(false))
+    }
+
     /// Get the execution graph of of a job. First look in the active cache.
     /// If no one found, then in the Active/Completed jobs.
     pub(crate) async fn get_job_execution_graph(
         &self,
         job_id: &str,
     ) -> Result<Option<Arc<ExecutionGraph>>> {
-        if let Some(cached) = self.get_active_execution_graph(job_id) {
-            let guard = cached.read().await;
-
-            Ok(Some(Arc::new(guard.deref().clone())))
+        if let Some(graph) = self.with_active_graph(job_id, |graph| graph.clone()).await {
+            Ok(Some(Arc::new(graph)))
         } else {
             let graph = self.state.get_execution_graph(job_id).await?;
 
@@ -274,6 +892,25 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
+    /// Get the attempts of the tasks of a job, or of a single stage of the job, which finished
+    /// or are still running. Returns `None` if the job is unknown.
+    pub(crate) async fn get_task_attempts(
+        &self,
+        job_id: &str,
+        stage_id: Option<usize>,
+    ) -> Result<Option<Vec<TaskAttempt>>> {
+        if let Some(attempts) = self
+            .with_active_graph(job_id, move |graph| graph.task_attempts(stage_id))
+            .await
+        {
+            Ok(Some(attempts))
+        } else {
+            let graph = self.state.get_execution_graph(job_id).await?;
+
+            Ok(graph.map(|graph| graph.task_attempts(stage_id)))
+        }
+    }
+
     /// Update given task statuses in the respective job and return a tuple containing:
     /// 1. A list of QueryStageSchedulerEvent to publish.
     /// 2. A list of reservations that can now be offered.
@@ -289,32 +926,46 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             let job_task_statuses = job_updates.entry(job_id).or_insert_with(Vec::new);
             job_task_statuses.push(status);
         }
+        let job_ids: Vec<String> = job_updates.keys().cloned().collect();
 
-        let mut events: Vec<QueryStageSchedulerEvent> = vec![];
-        for (job_id, statuses) in job_updates {
+        // The jobs update their graphs concurrently
+        let updates = job_updates.into_iter().map(|(job_id, statuses)| async move {
             let num_tasks = statuses.len();
             debug!("Updating {} tasks in job {}", num_tasks, job_id);
 
-            // let graph = self.get_active_execution_graph(&job_id).await;
-            let job_events = if let Some(cached) =
-                self.get_active_execution_graph(&job_id)
-            {
-                let mut graph = cached.write().await;
-                graph.update_task_status(
-                    executor,
-                    statuses,
-                    TASK_MAX_FAILURES,
-                    STAGE_MAX_FAILURES,
-                )?
-            } else {
+            let executor = executor.clone();
+            let job_events = self
+                .with_active_graph(&job_id, move |graph| {
+                    graph.update_task_status(
+                        &executor,
+                        statuses,
+                        TASK_MAX_FAILURES,
+                        STAGE_MAX_FAILURES,
+                    )
+                })
+                .await;
+            let mut job_events = job_events.unwrap_or_else(|| {
                 // TODO Deal with curator changed case
                 error!("Fail to find job {} in the active cache and it may not be curated by this scheduler", job_id);
-                vec![]
-            };
-
-            for event in job_events {
-                events.push(event);
+                Ok(vec![])
+            });
+            // The update panicked, which fails the job
+            if let Some(failure) = self
+                .get_active_job(&job_id)
+                .and_then(|job| job.take_failure())
+            {
+                job_events = Ok(vec![failure]);
             }
+            job_events
+        });
+
+        let mut events: Vec<QueryStageSchedulerEvent> = vec![];
+        for job_events in futures::future::join_all(updates).await {
+            events.extend(job_events?);
+        }
+        // The stages of other jobs waiting for the completed stages read their output
+        for job_id in &job_ids {
+            events.extend(self.adopt_shared_scans(job_id).await?);
         }
 
         Ok(events)
@@ -325,53 +976,132 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
     ///
     /// Here we use the following  algorithm:
     ///
-    /// 1. For each free reservation, try to assign a task from one of the active jobs
+    /// 1. For each free reservation, try to assign a task from one of the active jobs which
+    ///    requires the resources held by the reservation. Jobs are visited in descending order
+    ///    of their priority (`ballista.job.priority`) and are only offered the reservations
+    ///    of the executors of their pool (`ballista.job.pool`)
     /// 2. If we cannot find a task in all active jobs, then add the reservation to the list of unassigned reservations
     ///
+    /// Jobs of a session with a task slot quota (`ballista.session.max_task_slots`) are not
+    /// assigned any more tasks once the running tasks of the session fill its quota. Their
+    /// extra tasks wait and are not counted as pending so they don't take new reservations.
+    ///
     /// Finally, we return:
     /// 1. A list of assignments which is a (Executor ID, Task) tuple
     /// 2. A list of unassigned reservations which we could not find tasks for
-    /// 3. The number of pending tasks across active jobs
+    /// 3. The number of pending tasks across active jobs, grouped by the pool of executors
+    ///    and the resources they require
     pub async fn fill_reservations(
         &self,
         reservations: &[ExecutorReservation],
     ) -> Result<(
         Vec<(String, TaskDescription)>,
         Vec<ExecutorReservation>,
-        usize,
+        HashMap<(String, TaskResources), usize>,
     )> {
         // Reinitialize the free reservations.
-        let free_reservations: Vec<ExecutorReservation> = reservations
+        let mut unassigned: Vec<ExecutorReservation> = reservations
             .iter()
             .map(|reservation| {
                 ExecutorReservation::new_free(reservation.executor_id.clone())
+                    .with_resources(reservation.resources)
+                    .with_lease_id(reservation.lease_id.clone())
+                    .with_pool(reservation.pool.clone())
             })
             .collect();
 
-        let mut assignments: Vec<(String, TaskDescription)> = vec![];
-        let mut pending_tasks = 0usize;
-        let mut assign_tasks = 0usize;
-        for pairs in self.active_job_cache.iter() {
-            let (_job_id, job_info) = pairs.pair();
-            let mut graph = job_info.execution_graph.write().await;
-            for reservation in free_reservations.iter().skip(assign_tasks) {
-                if let Some(task) = graph.pop_next_task(&reservation.executor_id)? {
-                    assignments.push((reservation.executor_id.clone(), task));
-                    assign_tasks += 1;
-                } else {
-                    break;
+        // Offer the reservations to jobs with a higher priority first
+        let mut jobs: Vec<(usize, usize, String, JobActor)> = self
+            .active_job_cache
+            .iter()
+            .map(|pairs| {
+                let job_info = pairs.value();
+                (
+                    job_info.job_priority,
+                    job_info.session_max_task_slots,
+                    job_info.job_pool.clone(),
+                    job_info.job.clone(),
+                )
+            })
+            .collect();
+        jobs.sort_by_key(|(job_priority, _, _, _)| Reverse(*job_priority));
+
+        // Sessions of the jobs and the task slots used by the running tasks of sessions
+        // with a quota
+        let mut job_sessions: Vec<Option<String>> = Vec::with_capacity(jobs.len());
+        let mut session_used_slots: HashMap<String, usize> = HashMap::new();
+        for (_, max_task_slots, _, job) in jobs.iter() {
+            let has_quota = *max_task_slots > 0;
+            let session = job
+                .run(move |graph| {
+                    let running_tasks = if has_quota {
+                        graph.running_tasks().len()
+                    } else {
+                        0
+                    };
+                    (graph.session_id().to_owned(), running_tasks)
+                })
+                .await;
+            if let Some((session_id, running_tasks)) = &session {
+                if has_quota {
+                    *session_used_slots.entry(session_id.clone()).or_default() +=
+                        running_tasks;
                 }
             }
-            if assign_tasks >= free_reservations.len() {
-                pending_tasks += graph.available_tasks();
+            job_sessions.push(session.map(|(session_id, _)| session_id));
+        }
+        // Task slots of sessions with a quota already counted as pending
+        let mut session_pending_slots: HashMap<String, usize> = HashMap::new();
+
+        let mut assignments: Vec<(String, TaskDescription)> = vec![];
+        let mut pending_tasks: HashMap<(String, TaskResources), usize> = HashMap::new();
+        for ((_, max_task_slots, job_pool, job), session_id) in
+            jobs.iter().zip(job_sessions)
+        {
+            // Jobs which completed in the meantime have no tasks left
+            let session_id = match session_id {
+                Some(session_id) => session_id,
+                None => continue,
+            };
+            let slots = SessionSlots {
+                max_task_slots: *max_task_slots,
+                used_slots: session_used_slots.get(&session_id).copied(),
+                pending_slots: session_pending_slots
+                    .get(&session_id)
+                    .copied()
+                    .unwrap_or_default(),
+            };
+
+            // Reservations of the executors of other pools are kept for other jobs
+            let (reservations, mut other_pools): (Vec<_>, Vec<_>) = unassigned
+                .iter()
+                .cloned()
+                .partition(|reservation| reservation.pool == *job_pool);
+            let filled = job
+                .run(move |graph| fill_job_reservations(graph, reservations, slots))
+                .await;
+            let filled = match filled {
+                Some(filled) => filled?,
+                None => continue,
+            };
+
+            if let Some(used_slots) = filled.slots.used_slots {
+                session_used_slots.insert(session_id.clone(), used_slots);
+            }
+            session_pending_slots.insert(session_id, filled.slots.pending_slots);
+            assignments.extend(filled.assignments);
+            for (resources, tasks) in filled.pending_tasks {
+                *pending_tasks
+                    .entry((job_pool.clone(), resources))
+                    .or_default() += tasks;
+            }
+            other_pools.extend(filled.unassigned);
+            unassigned = other_pools;
+            if unassigned.is_empty() {
                 break;
             }
         }
 
-        let mut unassigned = vec![];
-        for reservation in free_reservations.iter().skip(assign_tasks) {
-            unassigned.push(reservation.clone());
-        }
         Ok((assignments, unassigned, pending_tasks))
     }
 
@@ -380,9 +1110,33 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
     pub(crate) async fn succeed_job(&self, job_id: &str) -> Result<()> {
         debug!("Moving job {} from Active to Success", job_id);
 
-        if let Some(graph) = self.remove_active_execution_graph(job_id) {
-            let graph = graph.read().await.clone();
+        if let Some((_, job_info)) = self.active_job_cache.remove(job_id) {
+            let job_cost = job_info
+                .plan_fingerprint
+                .map(|fingerprint| (fingerprint, job_info.scanned_bytes));
+            let graph = match job_info.job.stop().await {
+                Some(graph) => graph,
+                None => {
+                    warn!("Actor of job {} stopped before the job completed", job_id);
+                    return Ok(());
+                }
+            };
+            self.finished_job_resources
+                .insert(job_id.to_owned(), graph.stage_task_resources());
             if graph.is_successful() {
+                if let Some((fingerprint, scanned_bytes)) = job_cost {
+                    self.cost_history.record(
+                        fingerprint,
+                        scanned_bytes,
+                        graph.task_time_ms() as f64 / 1000.0,
+                    );
+                }
+                for stage in graph.stages().values() {
+                    if let ExecutionStage::Successful(stage) = stage {
+                        self.table_statistics
+                            .record_stage(stage.plan.as_ref(), &stage.stage_metrics);
+                    }
+                }
                 self.state.save_job(job_id, &graph).await?;
             } else {
                 error!("Job {} has not finished and cannot be completed", job_id);
@@ -395,12 +1149,42 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         Ok(())
     }
 
+    /// Estimate the cost of executing a physical plan from the statistics of its sources and
+    /// the costs of recently completed jobs with a similar plan
+    pub fn estimate_query(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+    ) -> Result<QueryCostEstimate> {
+        let fingerprint = plan_fingerprint(plan.as_ref());
+        let scanned_bytes = scanned_bytes(&plan);
+
+        let mut planner = DistributedPlanner::new();
+        let stages = planner.plan_query_stages("estimate", plan)?;
+        // Each stage runs one task per input partition
+        let task_count = stages
+            .iter()
+            .map(|stage| stage.output_partitioning().partition_count())
+            .sum();
+
+        let (slot_seconds, similar_jobs) = self
+            .cost_history
+            .estimate_slot_seconds(fingerprint, scanned_bytes);
+
+        Ok(QueryCostEstimate {
+            scanned_bytes,
+            stage_count: stages.len(),
+            task_count,
+            slot_seconds,
+            similar_jobs,
+        })
+    }
+
     /// Cancel the job and return a Vec of running tasks need to cancel
     pub(crate) async fn cancel_job(
         &self,
         job_id: &str,
     ) -> Result<(Vec<RunningTaskInfo>, usize)> {
-        self.abort_job(job_id, "Cancelled".to_owned()).await
+        self.stop_job(job_id, |graph| graph.cancel_job()).await
     }
 
     /// Abort the job and return a Vec of running tasks need to cancel
@@ -409,13 +1193,28 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         job_id: &str,
         failure_reason: String,
     ) -> Result<(Vec<RunningTaskInfo>, usize)> {
-        let (tasks_to_cancel, pending_tasks) = if let Some(graph) =
-            self.get_active_execution_graph(job_id)
-        {
-            let mut guard = graph.write().await;
+        self.stop_job(job_id, |graph| graph.fail_job(failure_reason))
+            .await
+    }
 
-            let pending_tasks = guard.available_tasks();
-            let running_tasks = guard.running_tasks();
+    /// Stop the job, setting its final status with `complete`, and return a Vec of running
+    /// tasks need to cancel
+    async fn stop_job(
+        &self,
+        job_id: &str,
+        complete: impl FnOnce(&mut ExecutionGraph),
+    ) -> Result<(Vec<RunningTaskInfo>, usize)> {
+        // The graph of the aborted job is taken from its actor, which stops
+        let graph = match self.active_job_cache.remove(job_id) {
+            Some((_, job_info)) => job_info.job.stop().await,
+            None => None,
+        };
+        let (tasks_to_cancel, pending_tasks) = if let Some(mut graph) = graph {
+            // The cancelled tasks release their resources once they report their statuses
+            self.finished_job_resources
+                .insert(job_id.to_owned(), graph.stage_task_resources());
+            let pending_tasks = graph.available_tasks();
+            let running_tasks = graph.running_tasks();
 
             info!(
                 "Cancelling {} running tasks for job {}",
@@ -423,9 +1222,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                 job_id
             );
 
-            guard.fail_job(failure_reason);
+            complete(&mut graph);
 
-            self.state.save_job(job_id, &guard).await?;
+            self.state.save_job(job_id, &graph).await?;
 
             (running_tasks, pending_tasks)
         } else {
@@ -449,20 +1248,51 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             .await
     }
 
+    /// Mark a unscheduled job as cancelled, e.g. a job held in the queue
+    pub async fn cancel_unscheduled_job(&self, job_id: &str) -> Result<()> {
+        self.state.cancel_unscheduled_job(job_id).await
+    }
+
+    /// Save a queued job held back by the maximum number of concurrent jobs, see
+    /// [JobState::save_held_job]
+    pub(crate) async fn save_held_job(&self, job: HeldJob) -> Result<()> {
+        self.state.save_held_job(job).await
+    }
+
+    /// Get the held jobs saved before the scheduler restarted which are still queued, see
+    /// [JobState::recover_held_jobs]
+    pub(crate) async fn recover_held_jobs(&self) -> Result<Vec<HeldJob>> {
+        self.state.recover_held_jobs().await
+    }
+
+    /// Mark a unscheduled job as successful with the results of another job, e.g. served
+    /// from the result cache
+    pub async fn succeed_unscheduled_job(
+        &self,
+        job_id: &str,
+        partition_location: Vec<PartitionLocation>,
+        shared_job_id: &str,
+    ) -> Result<()> {
+        self.state
+            .succeed_unscheduled_job(job_id, partition_location, shared_job_id)
+            .await
+    }
+
     pub async fn update_job(&self, job_id: &str) -> Result<usize> {
         debug!("Update active job {job_id}");
-        if let Some(graph) = self.get_active_execution_graph(job_id) {
-            let mut graph = graph.write().await;
-
-            let curr_available_tasks = graph.available_tasks();
+        if let Some(job) = self.get_active_job(job_id) {
+            let new_tasks = job.run(|graph| {
+                let curr_available_tasks = graph.available_tasks();
 
-            graph.revive();
+                graph.revive();
 
-            println!("Saving job with status {:?}", graph.status());
+                debug!("Saving job with status {:?}", graph.status());
 
-            self.state.save_job(job_id, &graph).await?;
+                graph.available_tasks() - curr_available_tasks
+            });
+            let new_tasks = new_tasks.await.unwrap_or_default();
 
-            let new_tasks = graph.available_tasks() - curr_available_tasks;
+            job.save().await?;
 
             Ok(new_tasks)
         } else {
@@ -472,32 +1302,263 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
+    /// Save the execution graphs of all active jobs to the backend, returns the number of
+    /// saved jobs
+    pub async fn save_active_jobs(&self) -> Result<usize> {
+        let jobs: Vec<JobActor> = self
+            .active_job_cache
+            .iter()
+            .map(|entry| entry.value().job.clone())
+            .collect();
+        for job in &jobs {
+            job.save().await?;
+        }
+        Ok(jobs.len())
+    }
+
+    /// Preempt up to `num_tasks` running tasks of jobs with a lower priority than the given job,
+    /// starting with the jobs of the lowest priority. The preempted partitions are re-queued in
+    /// their execution graphs and the returned tasks need to be killed on their executors.
+    pub async fn preempt_tasks(
+        &self,
+        job_id: &str,
+        num_tasks: usize,
+    ) -> Result<Vec<RunningTaskInfo>> {
+        let job_priority = if let Some(job_info) = self.active_job_cache.get(job_id) {
+            job_info.job_priority
+        } else {
+            warn!("Fail to find job {} in the cache", job_id);
+            return Ok(vec![]);
+        };
+
+        let mut victims: Vec<(usize, JobActor)> = self
+            .active_job_cache
+            .iter()
+            .filter(|pairs| pairs.value().job_priority < job_priority)
+            .map(|pairs| {
+                let job_info = pairs.value();
+                (job_info.job_priority, job_info.job.clone())
+            })
+            .collect();
+        victims.sort_by_key(|(victim_priority, _)| *victim_priority);
+
+        let mut preempted: Vec<RunningTaskInfo> = vec![];
+        for (_, victim) in victims {
+            let remaining = num_tasks.saturating_sub(preempted.len());
+            if remaining == 0 {
+                break;
+            }
+            let victim_preempted = victim.run(move |graph| {
+                let mut preempted = vec![];
+                for task in graph.running_tasks() {
+                    if preempted.len() >= remaining {
+                        break;
+                    }
+                    if graph.preempt_task(&task) {
+                        preempted.push(task);
+                    }
+                }
+                preempted
+            });
+            for task in victim_preempted.await.unwrap_or_default() {
+                info!(
+                    "Preempting task {} of job {} for job {}",
+                    task.task_id, task.job_id, job_id
+                );
+                preempted.push(task);
+            }
+        }
+
+        Ok(preempted)
+    }
+
+    /// Re-queue the tasks which an executor did not accept when they were launched, so that
+    /// they are scheduled again. Returns the number of re-queued tasks
+    pub async fn requeue_rejected_tasks(
+        &self,
+        executor_id: &str,
+        rejected_tasks: &[RejectedTask],
+    ) -> usize {
+        let mut job_tasks: HashMap<&str, Vec<RunningTaskInfo>> = HashMap::new();
+        for task in rejected_tasks {
+            if let Some(task_id) = &task.task_id {
+                job_tasks
+                    .entry(&task.job_id)
+                    .or_default()
+                    .push(RunningTaskInfo {
+                        task_id: task_id.task_id as usize,
+                        job_id: task.job_id.clone(),
+                        stage_id: task.stage_id as usize,
+                        partition_id: task_id.partition_id as usize,
+                        executor_id: executor_id.to_owned(),
+                    });
+            }
+        }
+
+        let mut requeued = 0;
+        for (job_id, tasks) in job_tasks {
+            let job = match self.get_active_job(job_id) {
+                Some(job) => job,
+                None => continue,
+            };
+            requeued += job
+                .run(move |graph| {
+                    tasks.iter().filter(|task| graph.preempt_task(task)).count()
+                })
+                .await
+                .unwrap_or_default();
+        }
+        requeued
+    }
+
     /// return a Vec of running tasks need to cancel
     pub async fn executor_lost(&self, executor_id: &str) -> Result<Vec<RunningTaskInfo>> {
+        let jobs: Vec<JobActor> = self
+            .active_job_cache
+            .iter()
+            .map(|pairs| pairs.value().job.clone())
+            .collect();
+        let restarted = self
+            .restarted_executors
+            .remove(executor_id)
+            .map(|(_, restarted)| Arc::new(restarted));
+        // The jobs reset their stages concurrently
+        let resets = jobs.iter().map(|job| {
+            let executor_id = executor_id.to_owned();
+            let restarted = restarted.clone();
+            job.run(move |graph| match restarted.as_deref() {
+                Some((new_executor, shuffle_files)) => graph
+                    .reset_stages_on_restarted_executor(
+                        &executor_id,
+                        new_executor,
+                        shuffle_files,
+                    ),
+                None => graph.reset_stages_on_lost_executor(&executor_id),
+            })
+        });
+
         // Collect all the running task need to cancel when there are running stages rolled back.
         let mut running_tasks_to_cancel: Vec<RunningTaskInfo> = vec![];
-        // Collect graphs we update so we can update them in storage
-        let updated_graphs: DashMap<String, ExecutionGraph> = DashMap::new();
+        for reset in futures::future::join_all(resets)
+            .await
+            .into_iter()
+            .flatten()
         {
-            for pairs in self.active_job_cache.iter() {
-                let (job_id, job_info) = pairs.pair();
-                let mut graph = job_info.execution_graph.write().await;
-                let reset = graph.reset_stages_on_lost_executor(executor_id)?;
-                if !reset.0.is_empty() {
-                    updated_graphs.insert(job_id.to_owned(), graph.clone());
-                    running_tasks_to_cancel.extend(reset.1);
-                }
+            let (reset_stages, running_tasks) = reset?;
+            if !reset_stages.is_empty() {
+                running_tasks_to_cancel.extend(running_tasks);
             }
         }
 
         Ok(running_tasks_to_cancel)
     }
 
+    /// Record that the executor `previous_executor_id` restarted as `executor` with the
+    /// intact `shuffle_files` of its work dirs, so that the stages reset once the previous
+    /// executor is removed read these files from the new executor instead of recomputing them
+    pub fn executor_restarted(
+        &self,
+        previous_executor_id: &str,
+        executor: ExecutorMetadata,
+        shuffle_files: HashSet<String>,
+    ) {
+        self.restarted_executors
+            .insert(previous_executor_id.to_owned(), (executor, shuffle_files));
+    }
+
+    /// Get the number of available tasks of a job grouped by the resources they require
+    pub async fn get_available_tasks_by_resources(
+        &self,
+        job_id: &str,
+    ) -> Result<HashMap<TaskResources, usize>> {
+        if let Some(available_tasks) = self
+            .with_active_graph(job_id, |graph| graph.available_tasks_by_resources())
+            .await
+        {
+            Ok(available_tasks)
+        } else {
+            warn!("Fail to find job {} in the cache", job_id);
+            Ok(HashMap::new())
+        }
+    }
+
+    /// Get the pool of executors the tasks of the given job run on, empty for the default pool
+    pub(crate) fn get_job_pool(&self, job_id: &str) -> String {
+        self.active_job_cache
+            .get(job_id)
+            .map(|job_info| job_info.job_pool.clone())
+            .unwrap_or_default()
+    }
+
+    /// Get the number of task slots the session of the given job may still use under its
+    /// quota (`ballista.session.max_task_slots`), taken by the running tasks of the active
+    /// jobs of the session. None if the session has no quota.
+    pub(crate) async fn get_free_session_task_slots(
+        &self,
+        job_id: &str,
+    ) -> Option<usize> {
+        let (max_task_slots, job) = self
+            .active_job_cache
+            .get(job_id)
+            .map(|job_info| (job_info.session_max_task_slots, job_info.job.clone()))?;
+        if max_task_slots == 0 {
+            return None;
+        }
+        let session_id = job.run(|graph| graph.session_id().to_owned()).await?;
+
+        let jobs: Vec<JobActor> = self
+            .active_job_cache
+            .iter()
+            .map(|pairs| pairs.value().job.clone())
+            .collect();
+        let mut used_slots = 0;
+        for job in jobs {
+            let session_id = session_id.clone();
+            used_slots += job
+                .run(move |graph| {
+                    if graph.session_id() == session_id {
+                        graph.running_tasks().len()
+                    } else {
+                        0
+                    }
+                })
+                .await
+                .unwrap_or_default();
+        }
+        Some(max_task_slots.saturating_sub(used_slots))
+    }
+
+    /// Get the resources required by each task of the given stage, also once the job is no
+    /// longer active until it is cleaned up
+    pub async fn get_task_resources(
+        &self,
+        job_id: &str,
+        stage_id: usize,
+    ) -> TaskResources {
+        if let Some(resources) = self
+            .with_active_graph(job_id, move |graph| graph.task_resources(stage_id))
+            .await
+        {
+            return resources;
+        }
+        match self.finished_job_resources.get(job_id) {
+            Some(stages) => stages.get(&stage_id).copied().unwrap_or_default(),
+            None => {
+                warn!(
+                    "Unknown resources of the tasks of stage {stage_id} of job {job_id}"
+                );
+                TaskResources::default()
+            }
+        }
+    }
+
     /// Retrieve the number of available tasks for the given job. The value returned
     /// is strictly a point-in-time snapshot
     pub async fn get_available_task_count(&self, job_id: &str) -> Result<usize> {
-        if let Some(graph) = self.get_active_execution_graph(job_id) {
-            let available_tasks = graph.read().await.available_tasks();
+        if let Some(available_tasks) = self
+            .with_active_graph(job_id, |graph| graph.available_tasks())
+            .await
+        {
             Ok(available_tasks)
         } else {
             warn!("Fail to find job {} in the cache", job_id);
@@ -516,22 +1577,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         let stage_id = task.partition.stage_id;
 
         if let Some(mut job_info) = self.active_job_cache.get_mut(&job_id) {
-            let plan = if let Some(plan) = job_info.encoded_stage_plans.get(&stage_id) {
-                plan.clone()
-            } else {
-                let mut plan_buf: Vec<u8> = vec![];
-                let plan_proto = U::try_from_physical_plan(
-                    task.plan,
-                    self.codec.physical_extension_codec(),
-                )?;
-                plan_proto.try_encode(&mut plan_buf)?;
-
-                job_info
-                    .encoded_stage_plans
-                    .insert(stage_id, plan_buf.clone());
-
-                plan_buf
-            };
+            let plan = self.encode_stage_plan(&mut job_info, stage_id, &task.plan)?;
 
             let task_definition = TaskDefinition {
                 task_id: task.task_id as u32,
@@ -542,11 +1588,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                 partition_id: task.partition.partition_id as u32,
                 plan,
                 session_id: task.session_id,
-                launch_time: SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as u64,
-                props: vec![],
+                launch_time: self.clock.now_millis(),
+                props: job_info.task_props.clone(),
+                owner: job_info.job_owner.clone(),
             };
             Ok(task_definition)
         } else {
@@ -556,13 +1600,14 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
-    /// Launch the given tasks on the specified executor
+    /// Launch the given tasks on the specified executor, all of them in a single request.
+    /// Returns the tasks which the executor did not accept
     pub(crate) async fn launch_multi_task(
         &self,
         executor: &ExecutorMetadata,
         tasks: Vec<Vec<TaskDescription>>,
         executor_manager: &ExecutorManager,
-    ) -> Result<()> {
+    ) -> Result<Vec<RejectedTask>> {
         let multi_tasks: Result<Vec<MultiTaskDefinition>> = tasks
             .into_iter()
             .map(|stage_tasks| self.prepare_multi_task_definition(stage_tasks))
@@ -595,23 +1640,7 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             }
 
             if let Some(mut job_info) = self.active_job_cache.get_mut(&job_id) {
-                let plan = if let Some(plan) = job_info.encoded_stage_plans.get(&stage_id)
-                {
-                    plan.clone()
-                } else {
-                    let mut plan_buf: Vec<u8> = vec![];
-                    let plan_proto = U::try_from_physical_plan(
-                        task.plan.clone(),
-                        self.codec.physical_extension_codec(),
-                    )?;
-                    plan_proto.try_encode(&mut plan_buf)?;
-
-                    job_info
-                        .encoded_stage_plans
-                        .insert(stage_id, plan_buf.clone());
-
-                    plan_buf
-                };
+                let plan = self.encode_stage_plan(&mut job_info, stage_id, &task.plan)?;
 
                 let task_ids = tasks
                     .iter()
@@ -629,11 +1658,9 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
                     stage_attempt_num: stage_attempt_num as u32,
                     plan,
                     session_id,
-                    launch_time: SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as u64,
-                    props: vec![],
+                    launch_time: self.clock.now_millis(),
+                    props: job_info.task_props.clone(),
+                    owner: job_info.job_owner.clone(),
                 };
                 Ok(multi_task_definition)
             } else {
@@ -646,25 +1673,125 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
         }
     }
 
-    /// Get the `ExecutionGraph` for the given job ID from cache
-    pub(crate) fn get_active_execution_graph(
+    /// Encode the plan of a stage of a job for its tasks, reusing the encoded plan of the
+    /// previous tasks of the stage unless the plan changed since
+    fn encode_stage_plan(
         &self,
-        job_id: &str,
-    ) -> Option<Arc<RwLock<ExecutionGraph>>> {
+        job_info: &mut JobInfoCache,
+        stage_id: usize,
+        plan: &Arc<dyn ExecutionPlan>,
+    ) -> Result<Vec<u8>> {
+        if let Some((encoded_plan, plan_buf)) =
+            job_info.encoded_stage_plans.get(&stage_id)
+        {
+            if std::ptr::eq(
+                Arc::as_ptr(encoded_plan) as *const u8,
+                Arc::as_ptr(plan) as *const u8,
+            ) {
+                return Ok(plan_buf.clone());
+            }
+        }
+
+        let mut plan_buf: Vec<u8> = vec![];
+        let plan_proto = U::try_from_physical_plan(
+            plan.clone(),
+            self.codec.physical_extension_codec(),
+        )?;
+        plan_proto.try_encode(&mut plan_buf)?;
+
+        job_info
+            .encoded_stage_plans
+            .insert(stage_id, (plan.clone(), plan_buf.clone()));
+
+        Ok(plan_buf)
+    }
+
+    /// Get the actor owning the `ExecutionGraph` of the given job ID from cache
+    fn get_active_job(&self, job_id: &str) -> Option<JobActor> {
         self.active_job_cache
             .get(job_id)
             .as_deref()
-            .map(|cached| cached.execution_graph.clone())
+            .map(|cached| cached.job.clone())
+    }
+
+    /// Whether the job is planned and scheduled by this scheduler
+    pub(crate) fn is_active_job(&self, job_id: &str) -> bool {
+        self.active_job_cache.contains_key(job_id)
     }
 
-    /// Remove the `ExecutionGraph` for the given job ID from cache
-    pub(crate) fn remove_active_execution_graph(
+    /// Sessions of the jobs planned and scheduled by this scheduler
+    pub(crate) async fn active_sessions(&self) -> HashSet<String> {
+        let jobs: Vec<JobActor> = self
+            .active_job_cache
+            .iter()
+            .map(|cached| cached.job.clone())
+            .collect();
+        let mut sessions = HashSet::new();
+        for job in jobs {
+            if let Some(session_id) = job.run(|graph| graph.session_id().to_owned()).await
+            {
+                sessions.insert(session_id);
+            }
+        }
+        sessions
+    }
+
+    /// Sessions of the unfinished jobs of all the schedulers sharing the state backend, which
+    /// are in use even if this scheduler did not access them lately
+    pub(crate) async fn unfinished_job_sessions(&self) -> Result<HashSet<String>> {
+        let mut sessions = self.active_sessions().await;
+        for job_id in self.state.get_jobs().await? {
+            if self.active_job_cache.contains_key(&job_id) {
+                continue;
+            }
+            let unfinished = matches!(
+                self.state
+                    .get_job_status(&job_id)
+                    .await?
+                    .and_then(|status| status.status),
+                Some(
+                    protobuf::job_status::Status::Queued(_)
+                        | protobuf::job_status::Status::Running(_)
+                )
+            );
+            if unfinished {
+                if let Some(graph) = self.state.get_execution_graph(&job_id).await? {
+                    sessions.insert(graph.session_id().to_owned());
+                }
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// IDs of the active jobs which run tasks on an executor or read the output of its tasks
+    pub(crate) async fn executor_jobs(&self, executor_id: &str) -> Vec<String> {
+        let jobs: Vec<(String, JobActor)> = self
+            .active_job_cache
+            .iter()
+            .map(|cached| (cached.key().clone(), cached.job.clone()))
+            .collect();
+        let mut executor_jobs = vec![];
+        for (job_id, job) in jobs {
+            let executor_id = executor_id.to_owned();
+            if job
+                .run(move |graph| graph.executor_ids().contains(&executor_id))
+                .await
+                .unwrap_or(false)
+            {
+                executor_jobs.push(job_id);
+            }
+        }
+        executor_jobs
+    }
+
+    /// Run `f` on the `ExecutionGraph` of the given active job, returns None if the job is
+    /// not active
+    pub(crate) async fn with_active_graph<R: Send + 'static>(
         &self,
         job_id: &str,
-    ) -> Option<Arc<RwLock<ExecutionGraph>>> {
-        self.active_job_cache
-            .remove(job_id)
-            .map(|value| value.1.execution_graph)
+        f: impl FnOnce(&mut ExecutionGraph) -> R + Send + 'static,
+    ) -> Option<R> {
+        self.get_active_job(job_id)?.run(f).await
     }
 
     /// Generate a new random Job ID
@@ -677,17 +1804,116 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
             .collect()
     }
 
-    /// Clean up a failed job in FailedJobs Keyspace by delayed clean_up_interval seconds
+    /// Whether a queued, running or completed job of the cluster has the given ID
+    pub async fn job_exists(&self, job_id: &str) -> Result<bool> {
+        Ok(self.active_job_cache.contains_key(job_id)
+            || self.state.get_job_status(job_id).await?.is_some())
+    }
+
+    /// Seconds the results of an active job are kept once it succeeds, 0 for the default of
+    /// the scheduler
+    pub(crate) async fn get_result_ttl_seconds(&self, job_id: &str) -> u64 {
+        self.with_active_graph(job_id, |graph| graph.result_ttl_seconds())
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Directory the outputs of the stages of a job are checkpointed to, if any
+    pub(crate) async fn get_checkpoint_dir(&self, job_id: &str) -> Option<String> {
+        if let Some(checkpoint_dir) = self
+            .with_active_graph(job_id, |graph| graph.checkpoint_dir())
+            .await
+        {
+            return checkpoint_dir;
+        }
+        match self.state.get_execution_graph(job_id).await {
+            Ok(graph) => graph.and_then(|graph| graph.checkpoint_dir()),
+            Err(e) => {
+                warn!("Failed to get the checkpoint directory of job {job_id}: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Object store prefix the results of a job are exported to, if any
+    pub(crate) async fn get_result_export(&self, job_id: &str) -> Option<ResultExport> {
+        if let Some(result_export) = self
+            .with_active_graph(job_id, |graph| graph.result_export())
+            .await
+        {
+            return result_export;
+        }
+        match self.state.get_execution_graph(job_id).await {
+            Ok(graph) => graph.and_then(|graph| graph.result_export()),
+            Err(e) => {
+                warn!("Failed to get the result export of job {job_id}: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Report the results of a successful job as expired after `ttl` seconds, once its result
+    /// partitions were deleted from the executors and the files they were exported to, if
+    /// any, were deleted from the object store
+    pub(crate) fn expire_job_results_delayed(
+        &self,
+        job_id: String,
+        ttl: u64,
+        result_export: Option<ResultExport>,
+    ) {
+        if ttl == 0 {
+            return;
+        }
+
+        let state = self.state.clone();
+        let clock = self.clock.clone();
+        let export_config = self.export_config.clone();
+        tokio::spawn(async move {
+            clock.sleep(Duration::from_secs(ttl)).await;
+            if let Some(result_export) = result_export {
+                if let Err(err) =
+                    result_export.delete_results(&job_id, &export_config).await
+                {
+                    error!(
+                        "Failed to delete the exported results of job {job_id}: {err:?}"
+                    );
+                }
+            }
+            // The state of the job may have been cleaned up already
+            let result = match state.get_execution_graph(&job_id).await {
+                Ok(Some(mut graph)) if graph.expire_results() => {
+                    state.save_job(&job_id, &graph).await.map(|_| true)
+                }
+                Ok(_) => Ok(false),
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(true) => info!("Results of job {job_id} expired"),
+                Ok(false) => {}
+                Err(err) => {
+                    error!("Failed to expire the results of job {job_id}: {err:?}")
+                }
+            }
+        });
+    }
+
+    /// Clean up the state of a completed job after `clean_up_interval` seconds
     pub(crate) fn clean_up_job_delayed(&self, job_id: String, clean_up_interval: u64) {
+        let finished_job_resources = self.finished_job_resources.clone();
+        let clock = self.clock.clone();
         if clean_up_interval == 0 {
             info!("The interval is 0 and the clean up for the failed job state {} will not triggered", job_id);
+            tokio::spawn(async move {
+                clock.sleep(FINISHED_JOB_RESOURCES_RETENTION).await;
+                finished_job_resources.remove(&job_id);
+            });
             return;
         }
 
         let state = self.state.clone();
         tokio::spawn(async move {
-            let job_id = job_id;
-            tokio::time::sleep(Duration::from_secs(clean_up_interval)).await;
+            clock.sleep(Duration::from_secs(clean_up_interval)).await;
+            finished_job_resources.remove(&job_id);
             if let Err(err) = state.remove_job(&job_id).await {
                 error!("Failed to delete job {job_id}: {err:?}");
             }
@@ -695,6 +1921,200 @@ impl<T: 'static + AsLogicalPlan, U: 'static + AsExecutionPlan> TaskManager<T, U>
     }
 }
 
+/// Task slots of the session of a job while filling reservations
+#[derive(Clone, Copy)]
+struct SessionSlots {
+    // Maximum number of task slots the jobs of the session may use, 0 means unlimited
+    max_task_slots: usize,
+    // Task slots used by the running tasks of the session, if it has a quota
+    used_slots: Option<usize>,
+    // Task slots of the session already counted as pending
+    pending_slots: usize,
+}
+
+/// Reservations filled with the tasks of a job
+struct JobReservations {
+    assignments: Vec<(String, TaskDescription)>,
+    unassigned: Vec<ExecutorReservation>,
+    pending_tasks: HashMap<TaskResources, usize>,
+    slots: SessionSlots,
+}
+
+/// Fill reservations with the tasks of a job, run by the actor of the job. See
+/// [TaskManager::fill_reservations].
+fn fill_job_reservations(
+    graph: &mut ExecutionGraph,
+    reservations: Vec<ExecutorReservation>,
+    mut slots: SessionSlots,
+) -> Result<JobReservations> {
+    let mut assignments: Vec<(String, TaskDescription)> = vec![];
+    let mut unassigned = Vec::with_capacity(reservations.len());
+    for reservation in reservations {
+        if slots.max_task_slots > 0 {
+            let used_slots = slots.used_slots.get_or_insert(0);
+            if *used_slots >= slots.max_task_slots {
+                unassigned.push(reservation);
+                continue;
+            }
+            if let Some(task) = graph.pop_next_task_for_reservation(&reservation)? {
+                *used_slots += 1;
+                assignments.push((reservation.executor_id.clone(), task));
+                continue;
+            }
+        } else if let Some(task) = graph.pop_next_task_for_reservation(&reservation)? {
+            assignments.push((reservation.executor_id.clone(), task));
+            continue;
+        }
+        unassigned.push(reservation);
+    }
+
+    // Tasks left over either found no reservation at all or none holding the
+    // resources they require
+    let mut free_slots = if slots.max_task_slots > 0 {
+        slots
+            .max_task_slots
+            .saturating_sub(slots.used_slots.unwrap_or_default() + slots.pending_slots)
+    } else {
+        usize::MAX
+    };
+    let mut pending_tasks: HashMap<TaskResources, usize> = HashMap::new();
+    for (resources, tasks) in graph.available_tasks_by_resources() {
+        let tasks = tasks.min(free_slots);
+        free_slots -= tasks;
+        slots.pending_slots += tasks;
+        if tasks > 0 {
+            *pending_tasks.entry(resources).or_default() += tasks;
+        }
+    }
+
+    Ok(JobReservations {
+        assignments,
+        unassigned,
+        pending_tasks,
+        slots,
+    })
+}
+
+/// Check that a job ID supplied by a client is made of at most [`MAX_JOB_ID_LEN`] ASCII
+/// letters, digits, `-` and `_`, as job IDs are used in the paths of shuffle files
+pub fn validate_job_id(job_id: &str) -> Result<()> {
+    if job_id.is_empty() || job_id.len() > MAX_JOB_ID_LEN {
+        return Err(BallistaError::General(format!(
+            "Job ID {job_id:?} must have between 1 and {MAX_JOB_ID_LEN} characters"
+        )));
+    }
+    if !job_id
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(BallistaError::General(format!(
+            "Job ID {job_id:?} may only contain ASCII letters, digits, '-' and '_'"
+        )));
+    }
+    Ok(())
+}
+
+/// DataFusion settings of a job applied to the contexts of its tasks by executors, and the
+/// Ballista settings of its session the executors apply to its tasks, e.g. the retry policy
+/// of the object stores. Executors use their own defaults for the settings which are not set.
+fn job_task_props(config: Option<&BallistaConfig>) -> Vec<KeyValuePair> {
+    config
+        .map(|config| {
+            let batch_size = KeyValuePair {
+                key: "datafusion.execution.batch_size".to_owned(),
+                value: config.default_batch_size().to_string(),
+            };
+            std::iter::once(batch_size)
+                .chain(config.task_settings().map(|(key, value)| KeyValuePair {
+                    key: key.clone(),
+                    value: value.clone(),
+                }))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The user owning a job from the settings of its graph, empty for none
+fn job_owner(settings: &HashMap<String, String>) -> String {
+    settings
+        .get(BALLISTA_JOB_OWNER)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Maximum task slots of the session of a job and the priority of the job. Jobs of sessions
+/// which are no longer known are not limited and get the lowest priority.
+fn session_job_config(config: Option<&BallistaConfig>) -> (usize, usize) {
+    config
+        .map(|config| (config.session_max_task_slots(), config.job_priority()))
+        .unwrap_or_default()
+}
+
+/// Pool of executors the tasks of a job run on. Jobs of sessions which are no longer known
+/// run on the default pool.
+fn job_pool(config: Option<&BallistaConfig>) -> String {
+    config.map(|config| config.job_pool()).unwrap_or_default()
+}
+
+fn stage_max_failures(config: Option<&BallistaConfig>) -> usize {
+    config
+        .map(|config| config.stage_max_failures())
+        .unwrap_or_default()
+}
+
+fn verify_shuffle_rows(config: Option<&BallistaConfig>) -> bool {
+    config
+        .map(|config| config.shuffle_verify_rows())
+        .unwrap_or_default()
+}
+
+fn result_buffer(config: Option<&BallistaConfig>) -> usize {
+    config
+        // The clients of the jobs exporting their results do not fetch them
+        .filter(|config| config.job_export_location().is_empty())
+        .map(|config| config.job_result_buffer())
+        .unwrap_or_default()
+}
+
+fn max_concurrent_stages(config: Option<&BallistaConfig>) -> usize {
+    config
+        .map(|config| config.max_concurrent_stages_per_job())
+        .unwrap_or_default()
+}
+
+fn max_concurrent_tasks(config: Option<&BallistaConfig>) -> usize {
+    config
+        .map(|config| config.stage_max_concurrent_tasks())
+        .unwrap_or_default()
+}
+
+fn result_ttl_seconds(config: Option<&BallistaConfig>) -> u64 {
+    config
+        .map(|config| config.job_result_ttl_seconds() as u64)
+        .unwrap_or_default()
+}
+
+/// Whether a job runs as a single task, when its estimated scanned bytes are known and do
+/// not exceed the limit of its session
+fn single_task(config: Option<&BallistaConfig>, scanned_bytes: Option<u64>) -> bool {
+    let max_bytes = config
+        .map(|config| config.job_single_task_max_bytes())
+        .unwrap_or_default() as u64;
+    max_bytes > 0 && scanned_bytes.map_or(false, |bytes| bytes <= max_bytes)
+}
+
+fn shared_scans(config: Option<&BallistaConfig>) -> bool {
+    config
+        .map(|config| config.job_shared_scans())
+        .unwrap_or_default()
+}
+
+fn checkpoint_dir(config: Option<&BallistaConfig>) -> Option<String> {
+    config
+        .map(|config| config.job_checkpoint_dir())
+        .filter(|checkpoint_dir| !checkpoint_dir.is_empty())
+}
+
 pub struct JobOverview {
     pub job_id: String,
     pub job_name: String,
@@ -703,6 +2123,36 @@ pub struct JobOverview {
     pub end_time: u64,
     pub num_stages: usize,
     pub completed_stages: usize,
+    pub labels: HashMap<String, String>,
+    pub job_group: String,
+    /// The user owning the job, if the client submitting it was authenticated
+    pub owner: Option<String>,
+}
+
+impl JobOverview {
+    /// Whether the job has all the given labels
+    pub fn has_labels(&self, labels: &HashMap<String, String>) -> bool {
+        labels
+            .iter()
+            .all(|(key, value)| self.labels.get(key) == Some(value))
+    }
+}
+
+impl From<JobStatus> for JobOverview {
+    fn from(status: JobStatus) -> Self {
+        Self {
+            job_id: status.job_id.clone(),
+            job_name: status.job_name.clone(),
+            status,
+            start_time: 0,
+            end_time: 0,
+            num_stages: 0,
+            completed_stages: 0,
+            labels: HashMap::new(),
+            job_group: String::new(),
+            owner: None,
+        }
+    }
 }
 
 impl From<&ExecutionGraph> for JobOverview {
@@ -722,6 +2172,9 @@ impl From<&ExecutionGraph> for JobOverview {
             end_time: value.end_time(),
             num_stages: value.stage_count(),
             completed_stages,
+            labels: value.labels().clone(),
+            job_group: value.job_group().to_owned(),
+            owner: Some(job_owner(value.settings())).filter(|owner| !owner.is_empty()),
         }
     }
 }
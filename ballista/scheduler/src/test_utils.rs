@@ -28,14 +28,14 @@ use crate::config::SchedulerConfig;
 use crate::metrics::SchedulerMetricsCollector;
 use crate::scheduler_server::{timestamp_millis, SchedulerServer};
 
-use crate::state::executor_manager::ExecutorManager;
+use crate::state::executor_manager::{ExecutorManager, SlotMetrics};
 use crate::state::task_manager::TaskLauncher;
 
 use ballista_core::config::{BallistaConfig, BALLISTA_DEFAULT_SHUFFLE_PARTITIONS};
 use ballista_core::serde::protobuf::job_status::Status;
 use ballista_core::serde::protobuf::{
-    task_status, FailedTask, JobStatus, MultiTaskDefinition, ShuffleWritePartition,
-    SuccessfulTask, TaskId, TaskStatus,
+    task_status, FailedTask, JobStatus, MultiTaskDefinition, RejectedTask,
+    ShuffleWritePartition, SuccessfulTask, TaskId, TaskStatus,
 };
 use ballista_core::serde::scheduler::{
     ExecutorData, ExecutorMetadata, ExecutorSpecification,
@@ -53,6 +53,7 @@ use datafusion::prelude::{col, count, sum, CsvReadOptions, JoinType};
 use datafusion::test_util::scan_empty;
 
 use crate::cluster::BallistaCluster;
+use crate::planner::DistributedPlanner;
 use crate::scheduler_server::event::QueryStageSchedulerEvent;
 
 use crate::scheduler_server::query_stage_scheduler::QueryStageScheduler;
@@ -278,6 +279,8 @@ pub fn default_task_runner() -> impl TaskRunner {
                 num_batches: 1,
                 num_rows: 1,
                 num_bytes: 1,
+                checksum: None,
+                checkpoint_path: String::new(),
             })
             .collect();
 
@@ -298,9 +301,11 @@ pub fn default_task_runner() -> impl TaskRunner {
                 start_exec_time: timestamp,
                 end_exec_time: timestamp,
                 metrics: vec![],
+                resource_usage: None,
                 status: Some(task_status::Status::Successful(SuccessfulTask {
                     executor_id: executor_id.clone(),
                     partitions: partitions.clone(),
+                    output_schema: None,
                 })),
             });
         }
@@ -333,8 +338,40 @@ impl TaskLauncher for BlackholeTaskLauncher {
         _executor: &ExecutorMetadata,
         _tasks: Vec<MultiTaskDefinition>,
         _executor_manager: &ExecutorManager,
-    ) -> Result<()> {
-        Ok(())
+    ) -> Result<Vec<RejectedTask>> {
+        Ok(vec![])
+    }
+}
+
+/// Launcher whose executors reject all the launched tasks
+#[derive(Default)]
+pub struct RejectingTaskLauncher {}
+
+#[async_trait]
+impl TaskLauncher for RejectingTaskLauncher {
+    async fn launch_tasks(
+        &self,
+        _executor: &ExecutorMetadata,
+        tasks: Vec<MultiTaskDefinition>,
+        _executor_manager: &ExecutorManager,
+    ) -> Result<Vec<RejectedTask>> {
+        Ok(tasks
+            .into_iter()
+            .flat_map(|multi_task| {
+                let MultiTaskDefinition {
+                    job_id,
+                    stage_id,
+                    task_ids,
+                    ..
+                } = multi_task;
+                task_ids.into_iter().map(move |task_id| RejectedTask {
+                    job_id: job_id.clone(),
+                    stage_id,
+                    task_id: Some(task_id),
+                    reason: "Rejected".to_owned(),
+                })
+            })
+            .collect())
     }
 }
 
@@ -350,7 +387,7 @@ impl TaskLauncher for VirtualTaskLauncher {
         executor: &ExecutorMetadata,
         tasks: Vec<MultiTaskDefinition>,
         _executor_manager: &ExecutorManager,
-    ) -> Result<()> {
+    ) -> Result<Vec<RejectedTask>> {
         let virtual_executor = self.executors.get(&executor.id).ok_or_else(|| {
             BallistaError::Internal(format!(
                 "No virtual executor with ID {} found",
@@ -368,7 +405,9 @@ impl TaskLauncher for VirtualTaskLauncher {
             .await
             .map_err(|e| {
                 BallistaError::Internal(format!("Error sending task status: {e:?}"))
-            })
+            })?;
+
+        Ok(vec![])
     }
 }
 
@@ -439,7 +478,9 @@ impl SchedulerTest {
                 grpc_port: 0,
                 specification: ExecutorSpecification {
                     task_slots: task_slots as u32,
+                    ..Default::default()
                 },
+                pool: String::new(),
             };
 
             let executor_data = ExecutorData {
@@ -466,6 +507,10 @@ impl SchedulerTest {
         self.scheduler.pending_tasks()
     }
 
+    pub fn slot_metrics(&self) -> SlotMetrics {
+        self.scheduler.state.executor_manager.slot_metrics()
+    }
+
     pub async fn ctx(&self) -> Result<Arc<SessionContext>> {
         self.scheduler
             .state
@@ -532,6 +577,14 @@ impl SchedulerTest {
             .await
     }
 
+    pub async fn job_status(&self, job_id: &str) -> Result<Option<JobStatus>> {
+        self.scheduler
+            .state
+            .task_manager
+            .get_job_status(job_id)
+            .await
+    }
+
     pub async fn await_completion_timeout(
         &self,
         job_id: &str,
@@ -552,7 +605,7 @@ impl SchedulerTest {
             }) = status.as_ref()
             {
                 match inner {
-                    Status::Failed(_) | Status::Successful(_) => {
+                    Status::Failed(_) | Status::Successful(_) | Status::Cancelled(_) => {
                         break Ok(status.unwrap())
                     }
                     _ => {
@@ -587,7 +640,7 @@ impl SchedulerTest {
             }) = status.as_ref()
             {
                 match inner {
-                    Status::Failed(_) | Status::Successful(_) => {
+                    Status::Failed(_) | Status::Successful(_) | Status::Cancelled(_) => {
                         break Ok(status.unwrap())
                     }
                     _ => continue,
@@ -643,7 +696,7 @@ impl SchedulerTest {
             }) = status.as_ref()
             {
                 match inner {
-                    Status::Failed(_) | Status::Successful(_) => {
+                    Status::Failed(_) | Status::Successful(_) | Status::Cancelled(_) => {
                         break Ok(status.unwrap())
                     }
                     _ => continue,
@@ -735,6 +788,10 @@ impl SchedulerMetricsCollector for TestMetricsCollector {
 
     fn set_pending_tasks_queue_size(&self, _value: u64) {}
 
+    fn set_slot_metrics(&self, _metrics: &SlotMetrics) {}
+
+    fn set_event_loop_metrics(&self, _queued_events: u64, _lag: Duration) {}
+
     fn gather_metrics(&self) -> Result<Option<(Vec<u8>, String)>> {
         Ok(None)
     }
@@ -811,7 +868,54 @@ pub async fn test_aggregation_plan(partition: usize) -> ExecutionGraph {
 
     println!("{}", DisplayableExecutionPlan::new(plan.as_ref()).indent());
 
-    ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0).unwrap()
+    ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        DistributedPlanner::new(),
+    )
+    .unwrap()
+}
+
+pub async fn test_explain_analyze_plan(partition: usize) -> ExecutionGraph {
+    let config = SessionConfig::new().with_target_partitions(partition);
+    let ctx = Arc::new(SessionContext::with_config(config));
+    let session_state = ctx.state();
+
+    let schema = Schema::new(vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("gmv", DataType::UInt64, false),
+    ]);
+
+    let logical_plan = scan_empty(None, &schema, Some(vec![0, 1]))
+        .unwrap()
+        .aggregate(vec![col("id")], vec![sum(col("gmv"))])
+        .unwrap()
+        .explain(false, true)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let optimized_plan = session_state.optimize(&logical_plan).unwrap();
+
+    let plan = session_state
+        .create_physical_plan(&optimized_plan)
+        .await
+        .unwrap();
+
+    ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        DistributedPlanner::new(),
+    )
+    .unwrap()
 }
 
 pub async fn test_two_aggregations_plan(partition: usize) -> ExecutionGraph {
@@ -843,7 +947,16 @@ pub async fn test_two_aggregations_plan(partition: usize) -> ExecutionGraph {
 
     println!("{}", DisplayableExecutionPlan::new(plan.as_ref()).indent());
 
-    ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0).unwrap()
+    ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        DistributedPlanner::new(),
+    )
+    .unwrap()
 }
 
 pub async fn test_coalesce_plan(partition: usize) -> ExecutionGraph {
@@ -870,7 +983,16 @@ pub async fn test_coalesce_plan(partition: usize) -> ExecutionGraph {
         .await
         .unwrap();
 
-    ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0).unwrap()
+    ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        DistributedPlanner::new(),
+    )
+    .unwrap()
 }
 
 pub async fn test_join_plan(partition: usize) -> ExecutionGraph {
@@ -915,8 +1037,16 @@ pub async fn test_join_plan(partition: usize) -> ExecutionGraph {
 
     println!("{}", DisplayableExecutionPlan::new(plan.as_ref()).indent());
 
-    let graph =
-        ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0).unwrap();
+    let graph = ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        DistributedPlanner::new(),
+    )
+    .unwrap();
 
     println!("{graph:?}");
 
@@ -944,8 +1074,16 @@ pub async fn test_union_all_plan(partition: usize) -> ExecutionGraph {
 
     println!("{}", DisplayableExecutionPlan::new(plan.as_ref()).indent());
 
-    let graph =
-        ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0).unwrap();
+    let graph = ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        DistributedPlanner::new(),
+    )
+    .unwrap();
 
     println!("{graph:?}");
 
@@ -973,8 +1111,16 @@ pub async fn test_union_plan(partition: usize) -> ExecutionGraph {
 
     println!("{}", DisplayableExecutionPlan::new(plan.as_ref()).indent());
 
-    let graph =
-        ExecutionGraph::new("localhost:50050", "job", "", "session", plan, 0).unwrap();
+    let graph = ExecutionGraph::new(
+        "localhost:50050",
+        "job",
+        "",
+        "session",
+        plan,
+        0,
+        DistributedPlanner::new(),
+    )
+    .unwrap();
 
     println!("{graph:?}");
 
@@ -987,7 +1133,11 @@ pub fn mock_executor(executor_id: String) -> ExecutorMetadata {
         host: "localhost2".to_string(),
         port: 8080,
         grpc_port: 9090,
-        specification: ExecutorSpecification { task_slots: 1 },
+        specification: ExecutorSpecification {
+            task_slots: 1,
+            ..Default::default()
+        },
+        pool: String::new(),
     }
 }
 
@@ -1011,6 +1161,8 @@ pub fn mock_completed_task(task: TaskDescription, executor_id: &str) -> TaskStat
             num_batches: 1,
             num_rows: 1,
             num_bytes: 1,
+            checksum: None,
+            checkpoint_path: String::new(),
         })
     }
 
@@ -1025,9 +1177,11 @@ pub fn mock_completed_task(task: TaskDescription, executor_id: &str) -> TaskStat
         start_exec_time: 0,
         end_exec_time: 0,
         metrics: vec![],
+        resource_usage: None,
         status: Some(task_status::Status::Successful(protobuf::SuccessfulTask {
             executor_id: executor_id.to_owned(),
             partitions,
+            output_schema: None,
         })),
     }
 }
@@ -1052,6 +1206,8 @@ pub fn mock_failed_task(task: TaskDescription, failed_task: FailedTask) -> TaskS
             num_batches: 1,
             num_rows: 1,
             num_bytes: 1,
+            checksum: None,
+            checkpoint_path: String::new(),
         })
     }
 
@@ -1066,6 +1222,7 @@ pub fn mock_failed_task(task: TaskDescription, failed_task: FailedTask) -> TaskS
         start_exec_time: 0,
         end_exec_time: 0,
         metrics: vec![],
+        resource_usage: None,
         status: Some(task_status::Status::Failed(failed_task)),
     }
 }
@@ -31,6 +31,11 @@ export interface Stage {
   input_rows: number;
   output_rows: number;
   elapsed_compute: string;
+  user_cpu_time: string;
+  sys_cpu_time: string;
+  wall_clock_time: string;
+  peak_memory_bytes: number;
+  spilled_bytes: number;
 }
 
 export interface StagesListProps {
@@ -58,6 +63,26 @@ const columns: Column<any>[] = [
     Header: "Computation time",
     accessor: "elapsed_compute",
   },
+  {
+    Header: "User CPU time",
+    accessor: "user_cpu_time",
+  },
+  {
+    Header: "System CPU time",
+    accessor: "sys_cpu_time",
+  },
+  {
+    Header: "Task Time",
+    accessor: "wall_clock_time",
+  },
+  {
+    Header: "Peak Task Memory (bytes)",
+    accessor: "peak_memory_bytes",
+  },
+  {
+    Header: "Spilled Bytes",
+    accessor: "spilled_bytes",
+  },
 ];
 
 const getSkeleton = () => (
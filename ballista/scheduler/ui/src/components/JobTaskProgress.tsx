@@ -0,0 +1,92 @@
+// Licensed to the Apache Software Foundation (ASF) under one
+// or more contributor license agreements.  See the NOTICE file
+// distributed with this work for additional information
+// regarding copyright ownership.  The ASF licenses this file
+// to you under the Apache License, Version 2.0 (the
+// "License"); you may not use this file except in compliance
+// with the License.  You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing,
+// software distributed under the License is distributed on an
+// "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.  See the License for the
+// specific language governing permissions and limitations
+// under the License.
+
+import { Skeleton, Box } from "@chakra-ui/react";
+import { Column, DataTable } from "./DataTable";
+
+export interface TaskProgress {
+  task_id: number;
+  stage_id: number;
+  partition_id: number;
+  executor_id: string;
+  operator: string;
+  output_rows: number;
+  output_bytes: number;
+  elapsed_ms: number;
+  idle_ms: number;
+  stalled: boolean;
+}
+
+export interface TaskProgressListProps {
+  tasks?: TaskProgress[];
+}
+
+const columns: Column<TaskProgress>[] = [
+  {
+    Header: "Stage ID",
+    accessor: "stage_id",
+  },
+  {
+    Header: "Partition",
+    accessor: "partition_id",
+  },
+  {
+    Header: "Executor",
+    accessor: "executor_id",
+  },
+  {
+    Header: "Operator",
+    accessor: "operator",
+  },
+  {
+    Header: "Output Rows",
+    accessor: "output_rows",
+  },
+  {
+    Header: "Written Bytes",
+    accessor: "output_bytes",
+  },
+  {
+    Header: "Elapsed (ms)",
+    accessor: "elapsed_ms",
+  },
+  {
+    Header: "Idle (ms)",
+    accessor: "idle_ms",
+  },
+  {
+    Header: "Stalled",
+    accessor: (row) => ((row as TaskProgress).stalled ? "Yes" : "No"),
+    id: "stalled",
+  },
+];
+
+export const JobTaskProgress: React.FunctionComponent<TaskProgressListProps> = ({
+  tasks,
+}) => {
+  const isLoaded = typeof tasks !== "undefined";
+
+  return (
+    <Box w={"100%"} flex={1}>
+      {isLoaded ? (
+        <DataTable columns={columns} data={tasks || []} pageSize={10} pb={10} />
+      ) : (
+        <Skeleton height={5} />
+      )}
+    </Box>
+  );
+};
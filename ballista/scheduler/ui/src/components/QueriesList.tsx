@@ -40,6 +40,7 @@ import { GrDocumentDownload, GrOverview } from "react-icons/gr";
 import fileDownload from "js-file-download";
 import SVG from "react-inlinesvg";
 import { JobStagesQueries } from "./JobStagesMetrics";
+import { JobTaskProgress } from "./JobTaskProgress";
 
 export enum QueryStatus {
   QUEUED = "QUEUED",
@@ -144,6 +145,7 @@ export const ActionsCell: (props: any) => React.ReactNode = (props: any) => {
 
 export const JobLinkCell: (props: any) => React.ReactNode = (props: any) => {
   const [stages, setData] = useState();
+  const [tasks, setTasks] = useState();
   const [loaded, setLoaded] = useState(false);
   const { isOpen, onOpen, onClose } = useDisclosure();
 
@@ -159,9 +161,22 @@ export const JobLinkCell: (props: any) => React.ReactNode = (props: any) => {
     });
   };
 
+  const getTaskProgress = (url: string) => {
+    fetch(url, {
+      method: "GET",
+      headers: {
+        Accept: "application/json",
+      },
+    }).then(async (res) => {
+      const jsonObj = await res.json();
+      setTasks(jsonObj["tasks"]);
+    });
+  };
+
   useEffect(() => {
     if (isOpen && !loaded) {
       getStages("/api/job/" + props.value + "/stages");
+      getTaskProgress("/api/job/" + props.value + "/progress");
       setLoaded(true);
     }
   }, [stages, isOpen, loaded, props.value]);
@@ -180,6 +195,7 @@ export const JobLinkCell: (props: any) => React.ReactNode = (props: any) => {
           <ModalCloseButton />
           <ModalBody margin="auto">
             <JobStagesQueries stages={stages} />
+            <JobTaskProgress tasks={tasks} />
           </ModalBody>
           <ModalFooter>
             <Button colorScheme="blue" mr={3} onClick={onClose}>
@@ -41,6 +41,7 @@ def update_cargo_toml(cargo_toml: str, new_version: str):
     # ballista crates also depend on each other
     ballista_deps = (
         'ballista',
+        'ballista-api',
         'ballista-core',
         'ballista-executor',
         'ballista-scheduler',
@@ -79,6 +80,7 @@ def main():
         os.path.join(repo_root, rel_path, "Cargo.toml")
         for rel_path in [
             'ballista-cli',
+            'ballista/api',
             'ballista/core',
             'ballista/scheduler',
             'ballista/executor',
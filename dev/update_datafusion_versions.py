@@ -42,6 +42,7 @@ crates = {
 
 ballista_crates = {
     'ballista-cli': 'ballista-cli/Cargo.toml',
+    'api': 'ballista/api/Cargo.toml',
     'core': 'ballista/core/Cargo.toml',
     'client': 'ballista/client/Cargo.toml',
     'executor': 'ballista/executor/Cargo.toml',